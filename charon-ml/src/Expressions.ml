@@ -3,6 +3,7 @@ open Types
 open PrimitiveValues
 module VarId = IdGen ()
 module GlobalDeclId = IdGen ()
+module FunDeclId = IdGen ()
 
 (** We define this type to control the name of the visitor functions
     (see e.g., {!Charon.UllbcAst.iter_statement_base}).
@@ -14,6 +15,11 @@ type var_id = VarId.id [@@deriving show, ord]
   *)
 type global_decl_id = GlobalDeclId.id [@@deriving show]
 
+(** We define this type to control the name of the visitor functions
+    (see e.g., {!aggregate_kind}).
+  *)
+type fun_decl_id = FunDeclId.id [@@deriving show]
+
 (** Ancestor the field_proj_kind iter visitor *)
 class ['self] iter_field_proj_kind_base =
   object (_self : 'self)
@@ -41,7 +47,11 @@ type field_proj_kind =
   | ProjAdt of type_decl_id * variant_id option
   | ProjOption of variant_id
       (** Option is an assumed type, coming from the standard library *)
+  | ProjControlFlow of variant_id
+      (** [ControlFlow] is an assumed type as well (it is introduced by the
+          reconstruction of the [?] operator) *)
   | ProjTuple of int  (** The integer gives the arity of the tuple *)
+  | ProjUnion of type_decl_id  (** A field of a union *)
 [@@deriving
   show,
     visitors
@@ -61,7 +71,20 @@ type field_proj_kind =
         concrete = true;
       }]
 
-type projection_elem = Deref | DerefBox | Field of field_proj_kind * field_id
+type projection_elem =
+  | Deref
+  | DerefBox
+  | DerefRawPtr
+  | DerefRc  (** Dereference an [Rc] or an [Arc] *)
+  | Field of field_proj_kind * field_id
+  | Index of var_id
+      (** Index an array or a slice with a variable (which has type [usize]) *)
+  | ConstantIndex of int * int * bool
+      (** [ConstantIndex (offset, min_length, from_end)]: index an array or a
+          slice with a constant, introduced by the slice patterns (for
+          instance, [[x, ..]]). If [from_end] is [true], the index is
+          [length - offset]. The array or slice has at least [min_length]
+          elements. *)
 [@@deriving
   show,
     visitors
@@ -135,11 +158,25 @@ type place = { var_id : var_id; projection : projection }
 
 type borrow_kind = Shared | Mut | TwoPhaseMut | Shallow [@@deriving show]
 
-type unop =
-  | Not
-  | Neg
-  | Cast of integer_type * integer_type
-      (** Cast an integer from a source type to a target type *)
+type unop = Not | Neg [@@deriving show, ord]
+
+(** The kinds of the casts ([x as u64]), see {!Cast} *)
+type cast_kind =
+  | IntToInt
+      (** Between two integer types. The value is truncated or extended (with
+          its sign, if the source type is signed): the cast never fails. *)
+  | BoolToInt  (** From a boolean to an integer *)
+  | CharToInt  (** From a character to an integer (its code point) *)
+  | U8ToChar  (** From a [u8] to a character *)
+  | EnumToInt
+      (** From a fieldless enumeration to an integer, which gives the
+          discriminant of the variant. The source type is the type of the
+          discriminant. *)
+  | FloatToInt
+      (** From a float to an integer. The value is rounded toward zero, and
+          saturates if it doesn't fit in the target type. *)
+  | IntToFloat  (** From an integer to a float *)
+  | FloatToFloat  (** Between two float types *)
 [@@deriving show, ord]
 
 (** A binary operation
@@ -166,6 +203,7 @@ type binop =
   | Mul
   | Shl
   | Shr
+  | Offset  (** Offset a raw pointer by a number of elements *)
 [@@deriving show, ord]
 
 let all_binops =
@@ -188,6 +226,23 @@ let all_binops =
     Shr;
   ]
 
+(** A constant value: a primitive value, or a constant array (whose elements
+    are primitive values or arrays, for instance:
+    [const ARR: [u32; 3] = [1, 2, 3];]).
+ *)
+type constant_value =
+  | PrimitiveValue of primitive_value
+  | Array of constant_value list
+  | Bytes of int list  (** A constant array of bytes ([[u8; N]]) *)
+  | TraitConst of trait_decl_id * ety list * string
+      (** An associated constant of a trait, whose implementation can't be
+          determined statically ([T::CONST]): [TraitConst (trait_id, type_args,
+          name)]. The first type argument is the [Self] type. *)
+  | ZST of ety
+      (** The value of a zero-sized type other than unit (for instance
+          [PhantomData<T>]) *)
+[@@deriving show]
+
 (** Ancestor the operand iter visitor *)
 class ['self] iter_operand_base =
   object (_self : 'self)
@@ -196,6 +251,9 @@ class ['self] iter_operand_base =
 
     method visit_primitive_value : 'env -> primitive_value -> unit =
       fun _ _ -> ()
+
+    method visit_constant_value : 'env -> constant_value -> unit =
+      fun _ _ -> ()
   end
 
 (** Ancestor the operand map visitor *)
@@ -206,12 +264,15 @@ class ['self] map_operand_base =
 
     method visit_primitive_value : 'env -> primitive_value -> primitive_value =
       fun _ x -> x
+
+    method visit_constant_value : 'env -> constant_value -> constant_value =
+      fun _ x -> x
   end
 
 type operand =
   | Copy of place
   | Move of place
-  | Constant of ety * primitive_value
+  | Constant of ety * constant_value
 [@@deriving
   show,
     visitors
@@ -231,11 +292,20 @@ type operand =
         concrete = true;
       }]
 
+(** How a closure captures a variable of its environment *)
+type capture_mode =
+  | ByRef  (** Capture by shared reference ([&x]) *)
+  | ByMutRef  (** Capture by mutable reference ([&mut x]) *)
+  | ByValue  (** Move the variable into the environment *)
+[@@deriving show]
+
 (** Ancestor the operand iter visitor *)
 class ['self] iter_aggregate_kind_base =
   object (_self : 'self)
     inherit [_] iter_operand
     method visit_erased_region : 'env -> erased_region -> unit = fun _ _ -> ()
+    method visit_fun_decl_id : 'env -> fun_decl_id -> unit = fun _ _ -> ()
+    method visit_capture_mode : 'env -> capture_mode -> unit = fun _ _ -> ()
   end
 
 (** Ancestor the operand map visitor *)
@@ -245,6 +315,12 @@ class ['self] map_aggregate_kind_base =
 
     method visit_erased_region : 'env -> erased_region -> erased_region =
       fun _ x -> x
+
+    method visit_fun_decl_id : 'env -> fun_decl_id -> fun_decl_id =
+      fun _ x -> x
+
+    method visit_capture_mode : 'env -> capture_mode -> capture_mode =
+      fun _ x -> x
   end
 
 (** An aggregated ADT.
@@ -274,6 +350,8 @@ type aggregate_kind =
   (* TODO: AggregatedOption should be merged with AggregatedAdt *)
   | AggregatedAdt of
       type_decl_id * variant_id option * erased_region list * ety list
+  | AggregatedClosure of fun_decl_id * capture_mode list
+      (** A closure: the operands are the captured variables *)
 [@@deriving
   show,
     visitors
@@ -298,9 +376,11 @@ class ['self] iter_rvalue_base =
   object (_self : 'self)
     inherit [_] iter_aggregate_kind
     method visit_unop : 'env -> unop -> unit = fun _ _ -> ()
+    method visit_cast_kind : 'env -> cast_kind -> unit = fun _ _ -> ()
     method visit_binop : 'env -> binop -> unit = fun _ _ -> ()
     method visit_borrow_kind : 'env -> borrow_kind -> unit = fun _ _ -> ()
     method visit_global_decl_id : 'env -> global_decl_id -> unit = fun _ _ -> ()
+    method visit_ref_kind : 'env -> ref_kind -> unit = fun _ _ -> ()
   end
 
 (** Ancestor the rvalue map visitor *)
@@ -308,22 +388,42 @@ class ['self] map_rvalue_base =
   object (_self : 'self)
     inherit [_] map_aggregate_kind
     method visit_unop : 'env -> unop -> unop = fun _ x -> x
+    method visit_cast_kind : 'env -> cast_kind -> cast_kind = fun _ x -> x
     method visit_binop : 'env -> binop -> binop = fun _ x -> x
     method visit_borrow_kind : 'env -> borrow_kind -> borrow_kind = fun _ x -> x
 
     method visit_global_decl_id : 'env -> global_decl_id -> global_decl_id =
       fun _ x -> x
+
+    method visit_ref_kind : 'env -> ref_kind -> ref_kind = fun _ x -> x
   end
 
 (* TODO: move the aggregate kind to operands *)
 type rvalue =
   | Use of operand
   | Ref of place * borrow_kind
+  | RawPtr of place * ref_kind  (** [&raw const p] or [&raw mut p] *)
   | UnaryOp of unop * operand
   | BinaryOp of binop * operand * operand
+  | Cast of cast_kind * operand * ety * ety
+      (** [Cast (kind, op, src_ty, tgt_ty)]: a cast [op as tgt_ty] between
+          scalar types *)
   | Discriminant of place
+  | Len of place
+      (** The length of an array, a slice or a vector (it has type [usize]) *)
   | Aggregate of aggregate_kind * operand list
   | Global of global_decl_id
+  | GlobalRef of global_decl_id * ref_kind
+      (** A reference to a global, through which the global is read or written *)
+  | IsVariant of place * variant_id list
+      (** Test whether an enumeration value is one of the given variants *)
+  | Slice of place * operand * operand * borrow_kind
+      (** Borrow the sub-slice [base[from..to]] of an array, a slice or a vector *)
+  | Unsize of operand * ety * ety
+      (** [Unsize (op, src_ty, tgt_ty)]: an unsizing coercion from a pointer to
+          a sized value to a pointer to an unsized value ([&[T; N]] to [&[T]],
+          or [&T] to [&dyn Trait]). The types are the types of the pointers. *)
+  | FnPtr of fun_decl_id  (** The address of a function *)
 [@@deriving
   show,
     visitors
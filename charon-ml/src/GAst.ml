@@ -6,8 +6,9 @@ open Types
 open PrimitiveValues
 open Expressions
 open Meta
-module FunDeclId = IdGen ()
+module FunDeclId = Expressions.FunDeclId
 module GlobalDeclId = Expressions.GlobalDeclId
+module TraitImplId = IdGen ()
 
 (** A variable, as used in a function definition *)
 type var = {
@@ -27,6 +28,12 @@ type assumed_fun_id =
   | BoxDerefMut
       (** [core::ops::deref::DerefMut::<alloc::boxed::Box<T>>::deref_mut] *)
   | BoxFree
+  | RcNew  (** [alloc::rc::Rc::new] *)
+  | RcClone
+      (** [core::clone::Clone::<alloc::rc::Rc<T>>::clone]: the type argument is
+          [T] *)
+  | ArcNew  (** [alloc::sync::Arc::new] *)
+  | ArcClone  (** [core::clone::Clone::<alloc::sync::Arc<T>>::clone] *)
   | VecNew
   | VecPush
   | VecInsert
@@ -34,16 +41,66 @@ type assumed_fun_id =
   | VecIndex  (** [core::ops::index::Index::index<alloc::vec::Vec<T>, usize>] *)
   | VecIndexMut
       (** [core::ops::index::IndexMut::index_mut<alloc::vec::Vec<T>, usize>] *)
+  | OptionUnwrap  (** [core::option::Option::unwrap] *)
+  | OptionExpect  (** [core::option::Option::expect] *)
+  | OptionUnwrapOr  (** [core::option::Option::unwrap_or] *)
+  | OptionIsSome  (** [core::option::Option::is_some] *)
+  | OptionIsNone  (** [core::option::Option::is_none] *)
+  | OptionMap
+      (** [core::option::Option::map<T, U, F>]: the last type argument is the
+          type of the closure *)
+  | ResultUnwrap  (** [core::result::Result::unwrap] *)
+  | ResultExpect  (** [core::result::Result::expect] *)
+  | ResultIsOk  (** [core::result::Result::is_ok] *)
+  | ResultIsErr  (** [core::result::Result::is_err] *)
+  | ResultMap  (** [core::result::Result::map<T, E, U, F>] *)
+  | ResultMapErr  (** [core::result::Result::map_err<T, E, F, O>] *)
+  | TryBranch
+      (** [core::ops::try_trait::Try::branch], introduced by the [?] operator.
+          The type argument is the type on which we use [?]. *)
+  | FromResidual
+      (** [core::ops::try_trait::FromResidual::from_residual], introduced by
+          the [?] operator to convert the residual *)
+  | IntoIter
+      (** [core::iter::traits::collect::IntoIterator::into_iter]. The type
+          argument is the [Self] type. *)
+  | IteratorNext  (** [core::iter::traits::iterator::Iterator::next] *)
 [@@deriving show, ord]
 
-type fun_id = Regular of FunDeclId.id | Assumed of assumed_fun_id
+type fun_id =
+  | Regular of FunDeclId.id
+  | TraitMethod of TraitImplId.id * FunDeclId.id
+      (** A trait method, resolved to the method of an implementation of the
+          trait: [TraitMethod (impl_id, fun_id)], where [fun_id] is the
+          function generated for the method defined in the implementation *)
+  | Virtual of trait_decl_id * FunDeclId.id
+      (** A call to a trait method through a trait object, dispatched at
+          runtime: [Virtual (trait_id, fun_id)], where [fun_id] is the
+          function generated for the method declared in the trait *)
+  | Indirect of (operand[@compare fun op0 op1 -> Stdlib.compare op0 op1])
+      (** A call through a function pointer: the operand evaluates to the
+          address of the function to call. The operands have no generated
+          comparison function: we compare them structurally. *)
+  | Assumed of assumed_fun_id
 [@@deriving show, ord]
 
+(** A function signature, as used when declaring functions *)
+type fun_sig = {
+  region_params : region_var list;
+  num_early_bound_regions : int;
+  regions_hierarchy : region_var_groups;
+  type_params : type_var list;
+  inputs : sty list;
+  output : sty;
+}
+[@@deriving show]
+
 (** Ancestor the AST iter visitors *)
 class ['self] iter_ast_base =
   object (_self : 'self)
     inherit [_] iter_rvalue
     method visit_fun_id : 'env -> fun_id -> unit = fun _ _ -> ()
+    method visit_fun_sig : 'env -> fun_sig -> unit = fun _ _ -> ()
     method visit_meta : 'env -> meta -> unit = fun _ _ -> ()
     method visit_integer_type : 'env -> integer_type -> unit = fun _ _ -> ()
     method visit_scalar_value : 'env -> scalar_value -> unit = fun _ _ -> ()
@@ -54,6 +111,7 @@ class ['self] map_ast_base =
   object (_self : 'self)
     inherit [_] map_rvalue
     method visit_fun_id : 'env -> fun_id -> fun_id = fun _ x -> x
+    method visit_fun_sig : 'env -> fun_sig -> fun_sig = fun _ x -> x
     method visit_meta : 'env -> meta -> meta = fun _ x -> x
 
     method visit_integer_type : 'env -> integer_type -> integer_type =
@@ -89,6 +147,10 @@ type call = {
   type_args : ety list;
   args : operand list;
   dest : place;
+  callee_sig : fun_sig option;
+      (** The signature of the callee, with its region parameters (the type
+          parameters are instantiated by [type_args]). Only computed in the
+          non-erased mode. *)
 }
 [@@deriving
   show,
@@ -121,17 +183,6 @@ class ['self] map_statement_base =
     inherit [_] map_call
   end
 
-(** A function signature, as used when declaring functions *)
-type fun_sig = {
-  region_params : region_var list;
-  num_early_bound_regions : int;
-  regions_hierarchy : region_var_groups;
-  type_params : type_var list;
-  inputs : sty list;
-  output : sty;
-}
-[@@deriving show]
-
 type 'body gexpr_body = {
   meta : meta;
   arg_count : int;
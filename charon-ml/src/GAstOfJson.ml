@@ -184,6 +184,116 @@ let integer_type_of_json (js : json) : (T.integer_type, string) result =
   | `String "U128" -> Ok T.U128
   | _ -> Error ("integer_type_of_json failed on: " ^ show js)
 
+let big_int_of_json (js : json) : (PV.big_int, string) result =
+  combine_error_msgs js __FUNCTION__
+    (match js with
+    | `Int i -> Ok (Z.of_int i)
+    | `String is -> Ok (Z.of_string is)
+    | _ -> Error "")
+
+(** Deserialize a {!PV.scalar_value} from JSON and **check the ranges**.
+    
+    Note that in practice we also check that the values are in range
+    in the interpreter functions. Still, it doesn't cost much to be
+    a bit conservative.
+ *)
+let scalar_value_of_json (js : json) : (PV.scalar_value, string) result =
+  let res =
+    combine_error_msgs js __FUNCTION__
+      (match js with
+      | `Assoc [ ("Isize", `List [ bi ]) ] ->
+          let* bi = big_int_of_json bi in
+          Ok { PV.value = bi; int_ty = Isize }
+      | `Assoc [ ("I8", `List [ bi ]) ] ->
+          let* bi = big_int_of_json bi in
+          Ok { PV.value = bi; int_ty = I8 }
+      | `Assoc [ ("I16", `List [ bi ]) ] ->
+          let* bi = big_int_of_json bi in
+          Ok { PV.value = bi; int_ty = I16 }
+      | `Assoc [ ("I32", `List [ bi ]) ] ->
+          let* bi = big_int_of_json bi in
+          Ok { PV.value = bi; int_ty = I32 }
+      | `Assoc [ ("I64", `List [ bi ]) ] ->
+          let* bi = big_int_of_json bi in
+          Ok { PV.value = bi; int_ty = I64 }
+      | `Assoc [ ("I128", `List [ bi ]) ] ->
+          let* bi = big_int_of_json bi in
+          Ok { PV.value = bi; int_ty = I128 }
+      | `Assoc [ ("Usize", `List [ bi ]) ] ->
+          let* bi = big_int_of_json bi in
+          Ok { PV.value = bi; int_ty = Usize }
+      | `Assoc [ ("U8", `List [ bi ]) ] ->
+          let* bi = big_int_of_json bi in
+          Ok { PV.value = bi; int_ty = U8 }
+      | `Assoc [ ("U16", `List [ bi ]) ] ->
+          let* bi = big_int_of_json bi in
+          Ok { PV.value = bi; int_ty = U16 }
+      | `Assoc [ ("U32", `List [ bi ]) ] ->
+          let* bi = big_int_of_json bi in
+          Ok { PV.value = bi; int_ty = U32 }
+      | `Assoc [ ("U64", `List [ bi ]) ] ->
+          let* bi = big_int_of_json bi in
+          Ok { PV.value = bi; int_ty = U64 }
+      | `Assoc [ ("U128", `List [ bi ]) ] ->
+          let* bi = big_int_of_json bi in
+          Ok { PV.value = bi; int_ty = U128 }
+      | _ -> Error "")
+  in
+  match res with
+  | Error _ -> res
+  | Ok sv ->
+      if not (S.check_scalar_value_in_range sv) then (
+        log#serror ("Scalar value not in range: " ^ PV.show_scalar_value sv);
+        raise
+          (Failure ("Scalar value not in range: " ^ PV.show_scalar_value sv)));
+      res
+
+let float_type_of_json (js : json) : (T.float_type, string) result =
+  match js with
+  | `String "F32" -> Ok T.F32
+  | `String "F64" -> Ok T.F64
+  | _ -> Error ("float_type_of_json failed on: " ^ show js)
+
+let float_value_of_json (js : json) : (PV.float_value, string) result =
+  combine_error_msgs js __FUNCTION__
+    (match js with
+    | `Assoc [ ("F32", `String float_value) ] ->
+        Ok { PV.float_value; float_ty = T.F32 }
+    | `Assoc [ ("F64", `String float_value) ] ->
+        Ok { PV.float_value; float_ty = T.F64 }
+    | _ -> Error "")
+
+let primitive_value_of_json (js : json) : (PV.primitive_value, string) result =
+  combine_error_msgs js __FUNCTION__
+    (match js with
+    | `Assoc [ ("Scalar", scalar_value) ] ->
+        let* scalar_value = scalar_value_of_json scalar_value in
+        Ok (PV.Scalar scalar_value)
+    | `Assoc [ ("Bool", v) ] ->
+        let* v = bool_of_json v in
+        Ok (PV.Bool v)
+    | `Assoc [ ("Char", v) ] ->
+        let* v = char_of_json v in
+        Ok (PV.Char v)
+    | `Assoc [ ("String", v) ] ->
+        let* v = string_of_json v in
+        Ok (PV.String v)
+    | `Assoc [ ("Float", v) ] ->
+        let* v = float_value_of_json v in
+        Ok (PV.Float v)
+    | _ -> Error "")
+
+let const_generic_of_json (js : json) : (T.const_generic, string) result =
+  combine_error_msgs js __FUNCTION__
+    (match js with
+    | `Assoc [ ("Var", id) ] ->
+        let* id = T.ConstGenericVarId.id_of_json id in
+        Ok (T.ConstGenericVar id)
+    | `Assoc [ ("Value", v) ] ->
+        let* v = primitive_value_of_json v in
+        Ok (T.ConstGenericValue v)
+    | _ -> Error "")
+
 let ref_kind_of_json (js : json) : (T.ref_kind, string) result =
   match js with
   | `String "Mut" -> Ok T.Mut
@@ -196,6 +306,9 @@ let assumed_ty_of_json (js : json) : (T.assumed_ty, string) result =
     | `String "Box" -> Ok T.Box
     | `String "Vec" -> Ok T.Vec
     | `String "Option" -> Ok T.Option
+    | `String "ControlFlow" -> Ok T.ControlFlow
+    | `String "Rc" -> Ok T.Rc
+    | `String "Arc" -> Ok T.Arc
     | _ -> Error "")
 
 let type_id_of_json (js : json) : (T.type_id, string) result =
@@ -230,8 +343,11 @@ let rec ty_of_json (r_of_json : json -> ('r, string) result) (js : json) :
     | `Assoc [ ("Integer", `List [ int_ty ]) ] ->
         let* int_ty = integer_type_of_json int_ty in
         Ok (T.Integer int_ty)
+    | `Assoc [ ("Float", `List [ float_ty ]) ] ->
+        let* float_ty = float_type_of_json float_ty in
+        Ok (T.Float float_ty)
     | `String "Str" -> Ok Str
-    | `Assoc [ ("Array", `List [ ty ]) ] ->
+    | `Assoc [ ("Array", `List [ ty; _len ]) ] ->
         let* ty = ty_of_json r_of_json ty in
         Ok (T.Array ty)
     | `Assoc [ ("Slice", `List [ ty ]) ] ->
@@ -242,6 +358,31 @@ let rec ty_of_json (r_of_json : json -> ('r, string) result) (js : json) :
         let* ty = ty_of_json r_of_json ty in
         let* ref_kind = ref_kind_of_json ref_kind in
         Ok (T.Ref (region, ty, ref_kind))
+    | `Assoc [ ("RawPtr", `List [ ty; ref_kind ]) ] ->
+        let* ty = ty_of_json r_of_json ty in
+        let* ref_kind = ref_kind_of_json ref_kind in
+        Ok (T.RawPtr (ty, ref_kind))
+    | `Assoc [ ("ConstGeneric", `List [ cg ]) ] ->
+        let* cg = const_generic_of_json cg in
+        Ok (T.ConstGeneric cg)
+    | `Assoc [ ("DynTrait", `List [ trait_id ]) ] ->
+        let* trait_id = T.TraitDeclId.id_of_json trait_id in
+        Ok (T.DynTrait trait_id)
+    | `Assoc [ ("FnPtr", `List [ inputs; output ]) ] ->
+        let* inputs = list_of_json (ty_of_json r_of_json) inputs in
+        let* output = ty_of_json r_of_json output in
+        Ok (T.FnPtr (inputs, output))
+    | `Assoc [ ("Opaque", `List [ hidden_ty; bounds ]) ] ->
+        let* hidden_ty = option_of_json (ty_of_json r_of_json) hidden_ty in
+        let* bounds = list_of_json T.TraitDeclId.id_of_json bounds in
+        Ok (T.Opaque (hidden_ty, bounds))
+    | `Assoc [ ("TraitProjection", `List [ trait_id; regions; types; name ]) ]
+      ->
+        let* trait_id = T.TraitDeclId.id_of_json trait_id in
+        let* regions = list_of_json r_of_json regions in
+        let* types = list_of_json (ty_of_json r_of_json) types in
+        let* name = string_of_json name in
+        Ok (T.TraitProjection (trait_id, regions, types, name))
     | _ -> Error "")
 
 let sty_of_json (js : json) : (T.sty, string) result =
@@ -282,6 +423,9 @@ let type_decl_kind_of_json (id_to_file : id_to_file_map) (js : json) :
     | `Assoc [ ("Enum", variants) ] ->
         let* variants = list_of_json (variant_of_json id_to_file) variants in
         Ok (T.Enum variants)
+    | `Assoc [ ("Union", fields) ] ->
+        let* fields = list_of_json (field_of_json id_to_file) fields in
+        Ok (T.Union fields)
     | `String "Opaque" -> Ok T.Opaque
     | _ -> Error "")
 
@@ -310,8 +454,13 @@ let type_decl_of_json (id_to_file : id_to_file_map) (js : json) :
           ("name", name);
           ("region_params", region_params);
           ("type_params", type_params);
+          ("const_generic_params", _const_generic_params);
+          ("trait_clauses", _trait_clauses);
           ("regions_hierarchy", regions_hierarchy);
           ("kind", kind);
+          ("repr_packed", _repr_packed);
+          ("recursive_fields", _recursive_fields);
+          ("attr_info", _attr_info);
         ] ->
         let* def_id = T.TypeDeclId.id_of_json def_id in
         let* meta = meta_of_json id_to_file meta in
@@ -342,70 +491,6 @@ let var_of_json (js : json) : (A.var, string) result =
         Ok { A.index; name; var_ty }
     | _ -> Error "")
 
-let big_int_of_json (js : json) : (PV.big_int, string) result =
-  combine_error_msgs js __FUNCTION__
-    (match js with
-    | `Int i -> Ok (Z.of_int i)
-    | `String is -> Ok (Z.of_string is)
-    | _ -> Error "")
-
-(** Deserialize a {!PV.scalar_value} from JSON and **check the ranges**.
-    
-    Note that in practice we also check that the values are in range
-    in the interpreter functions. Still, it doesn't cost much to be
-    a bit conservative.
- *)
-let scalar_value_of_json (js : json) : (PV.scalar_value, string) result =
-  let res =
-    combine_error_msgs js __FUNCTION__
-      (match js with
-      | `Assoc [ ("Isize", `List [ bi ]) ] ->
-          let* bi = big_int_of_json bi in
-          Ok { PV.value = bi; int_ty = Isize }
-      | `Assoc [ ("I8", `List [ bi ]) ] ->
-          let* bi = big_int_of_json bi in
-          Ok { PV.value = bi; int_ty = I8 }
-      | `Assoc [ ("I16", `List [ bi ]) ] ->
-          let* bi = big_int_of_json bi in
-          Ok { PV.value = bi; int_ty = I16 }
-      | `Assoc [ ("I32", `List [ bi ]) ] ->
-          let* bi = big_int_of_json bi in
-          Ok { PV.value = bi; int_ty = I32 }
-      | `Assoc [ ("I64", `List [ bi ]) ] ->
-          let* bi = big_int_of_json bi in
-          Ok { PV.value = bi; int_ty = I64 }
-      | `Assoc [ ("I128", `List [ bi ]) ] ->
-          let* bi = big_int_of_json bi in
-          Ok { PV.value = bi; int_ty = I128 }
-      | `Assoc [ ("Usize", `List [ bi ]) ] ->
-          let* bi = big_int_of_json bi in
-          Ok { PV.value = bi; int_ty = Usize }
-      | `Assoc [ ("U8", `List [ bi ]) ] ->
-          let* bi = big_int_of_json bi in
-          Ok { PV.value = bi; int_ty = U8 }
-      | `Assoc [ ("U16", `List [ bi ]) ] ->
-          let* bi = big_int_of_json bi in
-          Ok { PV.value = bi; int_ty = U16 }
-      | `Assoc [ ("U32", `List [ bi ]) ] ->
-          let* bi = big_int_of_json bi in
-          Ok { PV.value = bi; int_ty = U32 }
-      | `Assoc [ ("U64", `List [ bi ]) ] ->
-          let* bi = big_int_of_json bi in
-          Ok { PV.value = bi; int_ty = U64 }
-      | `Assoc [ ("U128", `List [ bi ]) ] ->
-          let* bi = big_int_of_json bi in
-          Ok { PV.value = bi; int_ty = U128 }
-      | _ -> Error "")
-  in
-  match res with
-  | Error _ -> res
-  | Ok sv ->
-      if not (S.check_scalar_value_in_range sv) then (
-        log#serror ("Scalar value not in range: " ^ PV.show_scalar_value sv);
-        raise
-          (Failure ("Scalar value not in range: " ^ PV.show_scalar_value sv)));
-      res
-
 let field_proj_kind_of_json (js : json) : (E.field_proj_kind, string) result =
   combine_error_msgs js __FUNCTION__
     (match js with
@@ -421,6 +506,12 @@ let field_proj_kind_of_json (js : json) : (E.field_proj_kind, string) result =
     | `Assoc [ ("ProjOption", variant_id) ] ->
         let* variant_id = T.VariantId.id_of_json variant_id in
         Ok (E.ProjOption variant_id)
+    | `Assoc [ ("ProjControlFlow", variant_id) ] ->
+        let* variant_id = T.VariantId.id_of_json variant_id in
+        Ok (E.ProjControlFlow variant_id)
+    | `Assoc [ ("ProjUnion", def_id) ] ->
+        let* def_id = T.TypeDeclId.id_of_json def_id in
+        Ok (E.ProjUnion def_id)
     | _ -> Error "")
 
 let projection_elem_of_json (js : json) : (E.projection_elem, string) result =
@@ -428,10 +519,29 @@ let projection_elem_of_json (js : json) : (E.projection_elem, string) result =
     (match js with
     | `String "Deref" -> Ok E.Deref
     | `String "DerefBox" -> Ok E.DerefBox
+    | `String "DerefRawPtr" -> Ok E.DerefRawPtr
+    | `String "DerefRc" -> Ok E.DerefRc
     | `Assoc [ ("Field", `List [ proj_kind; field_id ]) ] ->
         let* proj_kind = field_proj_kind_of_json proj_kind in
         let* field_id = T.FieldId.id_of_json field_id in
         Ok (E.Field (proj_kind, field_id))
+    | `Assoc [ ("Index", var_id) ] ->
+        let* var_id = E.VarId.id_of_json var_id in
+        Ok (E.Index var_id)
+    | `Assoc
+        [
+          ( "ConstantIndex",
+            `Assoc
+              [
+                ("offset", offset);
+                ("min_length", min_length);
+                ("from_end", from_end);
+              ] );
+        ] ->
+        let* offset = int_of_json offset in
+        let* min_length = int_of_json min_length in
+        let* from_end = bool_of_json from_end in
+        Ok (E.ConstantIndex (offset, min_length, from_end))
     | _ -> Error ("projection_elem_of_json failed on:" ^ show js))
 
 let projection_of_json (js : json) : (E.projection, string) result =
@@ -458,12 +568,20 @@ let unop_of_json (js : json) : (E.unop, string) result =
   match js with
   | `String "Not" -> Ok E.Not
   | `String "Neg" -> Ok E.Neg
-  | `Assoc [ ("Cast", `List [ src_ty; tgt_ty ]) ] ->
-      let* src_ty = integer_type_of_json src_ty in
-      let* tgt_ty = integer_type_of_json tgt_ty in
-      Ok (E.Cast (src_ty, tgt_ty))
   | _ -> Error ("unop_of_json failed on:" ^ show js)
 
+let cast_kind_of_json (js : json) : (E.cast_kind, string) result =
+  match js with
+  | `String "IntToInt" -> Ok E.IntToInt
+  | `String "BoolToInt" -> Ok E.BoolToInt
+  | `String "CharToInt" -> Ok E.CharToInt
+  | `String "U8ToChar" -> Ok E.U8ToChar
+  | `String "EnumToInt" -> Ok E.EnumToInt
+  | `String "FloatToInt" -> Ok E.FloatToInt
+  | `String "IntToFloat" -> Ok E.IntToFloat
+  | `String "FloatToFloat" -> Ok E.FloatToFloat
+  | _ -> Error ("cast_kind_of_json failed on:" ^ show js)
+
 let binop_of_json (js : json) : (E.binop, string) result =
   match js with
   | `String "BitXor" -> Ok E.BitXor
@@ -482,24 +600,31 @@ let binop_of_json (js : json) : (E.binop, string) result =
   | `String "Mul" -> Ok E.Mul
   | `String "Shl" -> Ok E.Shl
   | `String "Shr" -> Ok E.Shr
+  | `String "Offset" -> Ok E.Offset
   | _ -> Error ("binop_of_json failed on:" ^ show js)
 
-let primitive_value_of_json (js : json) : (PV.primitive_value, string) result =
+(* The primitive values are serialized directly, while the arrays are wrapped
+   in an [Array] or a [Bytes] variant *)
+let rec constant_value_of_json (js : json) : (E.constant_value, string) result =
   combine_error_msgs js __FUNCTION__
     (match js with
-    | `Assoc [ ("Scalar", scalar_value) ] ->
-        let* scalar_value = scalar_value_of_json scalar_value in
-        Ok (PV.Scalar scalar_value)
-    | `Assoc [ ("Bool", v) ] ->
-        let* v = bool_of_json v in
-        Ok (PV.Bool v)
-    | `Assoc [ ("Char", v) ] ->
-        let* v = char_of_json v in
-        Ok (PV.Char v)
-    | `Assoc [ ("String", v) ] ->
-        let* v = string_of_json v in
-        Ok (PV.String v)
-    | _ -> Error "")
+    | `Assoc [ ("Array", cvs) ] ->
+        let* cvs = list_of_json constant_value_of_json cvs in
+        Ok (E.Array cvs)
+    | `Assoc [ ("Bytes", bytes) ] ->
+        let* bytes = list_of_json int_of_json bytes in
+        Ok (E.Bytes bytes)
+    | `Assoc [ ("TraitConst", `List [ trait_id; tys; name ]) ] ->
+        let* trait_id = T.TraitDeclId.id_of_json trait_id in
+        let* tys = list_of_json ety_of_json tys in
+        let* name = string_of_json name in
+        Ok (E.TraitConst (trait_id, tys, name))
+    | `Assoc [ ("ZST", ty) ] ->
+        let* ty = ety_of_json ty in
+        Ok (E.ZST ty)
+    | _ ->
+        let* cv = primitive_value_of_json js in
+        Ok (E.PrimitiveValue cv))
 
 let operand_of_json (js : json) : (E.operand, string) result =
   combine_error_msgs js __FUNCTION__
@@ -512,10 +637,17 @@ let operand_of_json (js : json) : (E.operand, string) result =
         Ok (E.Move place)
     | `Assoc [ ("Const", `List [ ty; cv ]) ] ->
         let* ty = ety_of_json ty in
-        let* cv = primitive_value_of_json cv in
+        let* cv = constant_value_of_json cv in
         Ok (E.Constant (ty, cv))
     | _ -> Error "")
 
+let capture_mode_of_json (js : json) : (E.capture_mode, string) result =
+  match js with
+  | `String "ByRef" -> Ok E.ByRef
+  | `String "ByMutRef" -> Ok E.ByMutRef
+  | `String "ByValue" -> Ok E.ByValue
+  | _ -> Error ("capture_mode_of_json failed on:" ^ show js)
+
 let aggregate_kind_of_json (js : json) : (E.aggregate_kind, string) result =
   combine_error_msgs js __FUNCTION__
     (match js with
@@ -533,6 +665,10 @@ let aggregate_kind_of_json (js : json) : (E.aggregate_kind, string) result =
         let* regions = list_of_json erased_region_of_json regions in
         let* tys = list_of_json ety_of_json tys in
         Ok (E.AggregatedAdt (id, opt_variant_id, regions, tys))
+    | `Assoc [ ("AggregatedClosure", `List [ id; capture_modes ]) ] ->
+        let* id = E.FunDeclId.id_of_json id in
+        let* capture_modes = list_of_json capture_mode_of_json capture_modes in
+        Ok (E.AggregatedClosure (id, capture_modes))
     | _ -> Error "")
 
 let rvalue_of_json (js : json) : (E.rvalue, string) result =
@@ -545,6 +681,10 @@ let rvalue_of_json (js : json) : (E.rvalue, string) result =
         let* place = place_of_json place in
         let* borrow_kind = borrow_kind_of_json borrow_kind in
         Ok (E.Ref (place, borrow_kind))
+    | `Assoc [ ("RawPtr", `List [ place; ref_kind ]) ] ->
+        let* place = place_of_json place in
+        let* ref_kind = ref_kind_of_json ref_kind in
+        Ok (E.RawPtr (place, ref_kind))
     | `Assoc [ ("UnaryOp", `List [ unop; op ]) ] ->
         let* unop = unop_of_json unop in
         let* op = operand_of_json op in
@@ -554,16 +694,52 @@ let rvalue_of_json (js : json) : (E.rvalue, string) result =
         let* op1 = operand_of_json op1 in
         let* op2 = operand_of_json op2 in
         Ok (E.BinaryOp (binop, op1, op2))
+    | `Assoc [ ("Cast", `List [ kind; op; src_ty; tgt_ty ]) ] ->
+        let* kind = cast_kind_of_json kind in
+        let* op = operand_of_json op in
+        let* src_ty = ety_of_json src_ty in
+        let* tgt_ty = ety_of_json tgt_ty in
+        Ok (E.Cast (kind, op, src_ty, tgt_ty))
     | `Assoc [ ("Discriminant", place) ] ->
         let* place = place_of_json place in
         Ok (E.Discriminant place)
+    | `Assoc [ ("Len", place) ] ->
+        let* place = place_of_json place in
+        Ok (E.Len place)
     | `Assoc [ ("Global", gid) ] ->
         let* gid = E.GlobalDeclId.id_of_json gid in
         Ok (E.Global gid)
+    | `Assoc [ ("GlobalRef", `List [ gid; ref_kind ]) ] ->
+        let* gid = E.GlobalDeclId.id_of_json gid in
+        let* ref_kind = ref_kind_of_json ref_kind in
+        Ok (E.GlobalRef (gid, ref_kind))
+    | `Assoc [ ("IsVariant", `List [ place; variant_ids ]) ] ->
+        let* place = place_of_json place in
+        let* variant_ids = list_of_json T.VariantId.id_of_json variant_ids in
+        Ok (E.IsVariant (place, variant_ids))
+    | `Assoc
+        [
+          ( "Slice",
+            `Assoc
+              [ ("base", base); ("from", from); ("to", to_); ("kind", kind) ] );
+        ] ->
+        let* base = place_of_json base in
+        let* from = operand_of_json from in
+        let* to_ = operand_of_json to_ in
+        let* kind = borrow_kind_of_json kind in
+        Ok (E.Slice (base, from, to_, kind))
     | `Assoc [ ("Aggregate", `List [ aggregate_kind; ops ]) ] ->
         let* aggregate_kind = aggregate_kind_of_json aggregate_kind in
         let* ops = list_of_json operand_of_json ops in
         Ok (E.Aggregate (aggregate_kind, ops))
+    | `Assoc [ ("Unsize", `List [ op; src_ty; tgt_ty ]) ] ->
+        let* op = operand_of_json op in
+        let* src_ty = ety_of_json src_ty in
+        let* tgt_ty = ety_of_json tgt_ty in
+        Ok (E.Unsize (op, src_ty, tgt_ty))
+    | `Assoc [ ("FnPtr", fid) ] ->
+        let* fid = E.FunDeclId.id_of_json fid in
+        Ok (E.FnPtr fid)
     | _ -> Error "")
 
 let assumed_fun_id_of_json (js : json) : (A.assumed_fun_id, string) result =
@@ -573,12 +749,32 @@ let assumed_fun_id_of_json (js : json) : (A.assumed_fun_id, string) result =
   | `String "BoxDeref" -> Ok A.BoxDeref
   | `String "BoxDerefMut" -> Ok A.BoxDerefMut
   | `String "BoxFree" -> Ok A.BoxFree
+  | `String "RcNew" -> Ok A.RcNew
+  | `String "RcClone" -> Ok A.RcClone
+  | `String "ArcNew" -> Ok A.ArcNew
+  | `String "ArcClone" -> Ok A.ArcClone
   | `String "VecNew" -> Ok A.VecNew
   | `String "VecPush" -> Ok A.VecPush
   | `String "VecInsert" -> Ok A.VecInsert
   | `String "VecLen" -> Ok A.VecLen
   | `String "VecIndex" -> Ok A.VecIndex
   | `String "VecIndexMut" -> Ok A.VecIndexMut
+  | `String "OptionUnwrap" -> Ok A.OptionUnwrap
+  | `String "OptionExpect" -> Ok A.OptionExpect
+  | `String "OptionUnwrapOr" -> Ok A.OptionUnwrapOr
+  | `String "OptionIsSome" -> Ok A.OptionIsSome
+  | `String "OptionIsNone" -> Ok A.OptionIsNone
+  | `String "OptionMap" -> Ok A.OptionMap
+  | `String "ResultUnwrap" -> Ok A.ResultUnwrap
+  | `String "ResultExpect" -> Ok A.ResultExpect
+  | `String "ResultIsOk" -> Ok A.ResultIsOk
+  | `String "ResultIsErr" -> Ok A.ResultIsErr
+  | `String "ResultMap" -> Ok A.ResultMap
+  | `String "ResultMapErr" -> Ok A.ResultMapErr
+  | `String "TryBranch" -> Ok A.TryBranch
+  | `String "FromResidual" -> Ok A.FromResidual
+  | `String "IntoIter" -> Ok A.IntoIter
+  | `String "IteratorNext" -> Ok A.IteratorNext
   | _ -> Error ("assumed_fun_id_of_json failed on:" ^ show js)
 
 let fun_id_of_json (js : json) : (A.fun_id, string) result =
@@ -587,6 +783,17 @@ let fun_id_of_json (js : json) : (A.fun_id, string) result =
     | `Assoc [ ("Regular", id) ] ->
         let* id = A.FunDeclId.id_of_json id in
         Ok (A.Regular id)
+    | `Assoc [ ("TraitMethod", `List [ impl_id; id ]) ] ->
+        let* impl_id = A.TraitImplId.id_of_json impl_id in
+        let* id = A.FunDeclId.id_of_json id in
+        Ok (A.TraitMethod (impl_id, id))
+    | `Assoc [ ("Virtual", `List [ trait_id; id ]) ] ->
+        let* trait_id = T.TraitDeclId.id_of_json trait_id in
+        let* id = A.FunDeclId.id_of_json id in
+        Ok (A.Virtual (trait_id, id))
+    | `Assoc [ ("Indirect", op) ] ->
+        let* op = operand_of_json op in
+        Ok (A.Indirect op)
     | `Assoc [ ("Assumed", fid) ] ->
         let* fid = assumed_fun_id_of_json fid in
         Ok (A.Assumed fid)
@@ -601,6 +808,8 @@ let fun_sig_of_json (js : json) : (A.fun_sig, string) result =
           ("num_early_bound_regions", num_early_bound_regions);
           ("regions_hierarchy", regions_hierarchy);
           ("type_params", type_params);
+          ("const_generic_params", _const_generic_params);
+          ("trait_clauses", _trait_clauses);
           ("inputs", inputs);
           ("output", output);
         ] ->
@@ -630,6 +839,7 @@ let gexpr_body_of_json (body_of_json : json -> ('body, string) result)
         [
           ("meta", meta);
           ("arg_count", arg_count);
+          ("return_local", _return_local);
           ("locals", locals);
           ("body", body);
         ] ->
@@ -652,6 +862,10 @@ let gfun_decl_of_json (body_of_json : json -> ('body, string) result)
           ("name", name);
           ("signature", signature);
           ("body", body);
+          ("precision_losses", _precision_losses);
+          ("unsafe_operations", _unsafe_operations);
+          ("attr_info", _attr_info);
+          ("contract", _contract);
         ] ->
         let* def_id = A.FunDeclId.id_of_json def_id in
         let* meta = meta_of_json id_to_file meta in
@@ -685,7 +899,10 @@ let gglobal_decl_of_json (body_of_json : json -> ('body, string) result)
           ("meta", meta);
           ("name", name);
           ("ty", ty);
+          ("is_mutable", _is_mutable);
+          ("interior_mutability", _interior_mutability);
           ("body", body);
+          ("attr_info", _attr_info);
         ] ->
         let* global_id = A.GlobalDeclId.id_of_json def_id in
         let* meta = meta_of_json id_to_file meta in
@@ -749,3 +966,25 @@ let length_of_json_list (js : json) : (int, string) result =
     (match js with
     | `List jsl -> Ok (List.length jsl)
     | _ -> Error ("not a list: " ^ show js))
+
+(** The major version of the format of the files we can read: it must be the
+    same as [FORMAT_VERSION] in [charon/src/export.rs] *)
+let format_major_version = 2
+
+(** Check the header of a (U)LLBC file, and return the crate it contains *)
+let crate_of_exported_json (js : json) : (json, string) result =
+  combine_error_msgs js __FUNCTION__
+    (match js with
+    | `Assoc [ ("header", header); ("crate", crate) ] -> (
+        match header with
+        | `Assoc
+            (("format_version", `Assoc [ ("major", major); ("minor", _) ]) :: _)
+          ->
+            let* major = int_of_json major in
+            if major = format_major_version then Ok crate
+            else
+              Error
+                ("unsupported format version: " ^ string_of_int major
+               ^ " (expected " ^ string_of_int format_major_version ^ ")")
+        | _ -> Error "")
+    | _ -> Error "")
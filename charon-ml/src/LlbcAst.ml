@@ -12,6 +12,8 @@ type statement = {
 
 and raw_statement =
   | Assign of place * rvalue
+  | CompoundAssign of place * binop * operand
+      (** [p op= rhs]: only generated with [--compound-assignments] *)
   | FakeRead of place
   | SetDiscriminant of place * variant_id
   | Drop of place
@@ -32,6 +34,10 @@ and raw_statement =
   | Sequence of statement * statement
   | Switch of switch
   | Loop of statement
+  | While of statement * operand * statement
+      (** [while { st; op } { body }]: the statement computes the condition,
+          and doesn't contain [break], [continue] nor [return]. Not generated
+          with [--no-while-loops] *)
 
 and switch =
   | If of operand * statement * statement
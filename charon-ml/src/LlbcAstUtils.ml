@@ -9,6 +9,7 @@ let statement_has_loops (st : statement) : bool =
     object
       inherit [_] iter_statement
       method! visit_Loop _ _ = raise Found
+      method! visit_While _ _ _ _ = raise Found
     end
   in
   try
@@ -39,8 +40,8 @@ let mk_sequence (st1 : statement) (st2 : statement) : statement =
  *)
 let rec chain_statements (st1 : statement) (st2 : statement) : statement =
   match st1.content with
-  | SetDiscriminant _ | Assert _ | Call _ | Assign _ | FakeRead _ | Drop _
-  | Loop _ ->
+  | SetDiscriminant _ | Assert _ | Call _ | Assign _ | CompoundAssign _
+  | FakeRead _ | Drop _ | Loop _ | While _ ->
       (* Simply create a sequence *)
       mk_sequence st1 st2
   | Nop -> (* Ignore the nop *) st2
@@ -10,7 +10,7 @@ module A = LlbcAst
 let assertion_of_json (js : json) : (A.assertion, string) result =
   combine_error_msgs js __FUNCTION__
     (match js with
-    | `Assoc [ ("cond", cond); ("expected", expected) ] ->
+    | `Assoc [ ("cond", cond); ("expected", expected); ("kind", _kind) ] ->
         let* cond = operand_of_json cond in
         let* expected = bool_of_json expected in
         Ok { A.cond; expected }
@@ -26,20 +26,22 @@ let call_of_json (js : json) : (A.call, string) result =
           ("type_args", type_args);
           ("args", args);
           ("dest", dest);
+          ("sig", callee_sig);
         ] ->
         let* func = fun_id_of_json func in
         let* region_args = list_of_json erased_region_of_json region_args in
         let* type_args = list_of_json ety_of_json type_args in
         let* args = list_of_json operand_of_json args in
         let* dest = place_of_json dest in
-        Ok { A.func; region_args; type_args; args; dest }
+        let* callee_sig = option_of_json fun_sig_of_json callee_sig in
+        Ok { A.func; region_args; type_args; args; dest; callee_sig }
     | _ -> Error "")
 
 let rec statement_of_json (id_to_file : id_to_file_map) (js : json) :
     (A.statement, string) result =
   combine_error_msgs js __FUNCTION__
     (match js with
-    | `Assoc [ ("meta", meta); ("content", content) ] ->
+    | `Assoc (("meta", meta) :: ("content", content) :: _optional_fields) ->
         let* meta = meta_of_json id_to_file meta in
         let* content = raw_statement_of_json id_to_file content in
         Ok { A.meta; content }
@@ -53,6 +55,11 @@ and raw_statement_of_json (id_to_file : id_to_file_map) (js : json) :
         let* place = place_of_json place in
         let* rvalue = rvalue_of_json rvalue in
         Ok (A.Assign (place, rvalue))
+    | `Assoc [ ("CompoundAssign", `List [ place; binop; rhs ]) ] ->
+        let* place = place_of_json place in
+        let* binop = binop_of_json binop in
+        let* rhs = operand_of_json rhs in
+        Ok (A.CompoundAssign (place, binop, rhs))
     | `Assoc [ ("FakeRead", place) ] ->
         let* place = place_of_json place in
         Ok (A.FakeRead place)
@@ -85,9 +92,14 @@ and raw_statement_of_json (id_to_file : id_to_file_map) (js : json) :
     | `Assoc [ ("Switch", tgt) ] ->
         let* switch = switch_of_json id_to_file tgt in
         Ok (A.Switch switch)
-    | `Assoc [ ("Loop", st) ] ->
+    | `Assoc [ ("Loop", `List [ st; _invariants ]) ] ->
         let* st = statement_of_json id_to_file st in
         Ok (A.Loop st)
+    | `Assoc [ ("While", `List [ cond; op; body; _invariants ]) ] ->
+        let* cond = statement_of_json id_to_file cond in
+        let* op = operand_of_json op in
+        let* body = statement_of_json id_to_file body in
+        Ok (A.While (cond, op, body))
     | _ -> Error "")
 
 and switch_of_json (id_to_file : id_to_file_map) (js : json) :
@@ -111,7 +123,7 @@ and switch_of_json (id_to_file : id_to_file_map) (js : json) :
         in
         let* otherwise = statement_of_json id_to_file otherwise in
         Ok (A.SwitchInt (op, int_ty, tgts, otherwise))
-    | `Assoc [ ("Match", `List [ p; tgts; otherwise ]) ] ->
+    | `Assoc [ ("Match", `List [ p; tgts; otherwise; _provenance ]) ] ->
         let* p = place_of_json p in
         let* tgts =
           list_of_json
@@ -176,6 +188,7 @@ let global_decl_of_json (id_to_file : id_to_file_map) (js : json)
          } ))
 
 let crate_of_json (js : json) : (A.crate, string) result =
+  let* js = crate_of_exported_json js in
   combine_error_msgs js __FUNCTION__
     (match js with
     | `Assoc
@@ -183,9 +196,13 @@ let crate_of_json (js : json) : (A.crate, string) result =
           ("name", name);
           ("id_to_file", id_to_file);
           ("declarations", declarations);
+          ("declarations_info", _declarations_info);
           ("types", types);
           ("functions", functions);
           ("globals", globals);
+          ("trait_decls", _trait_decls);
+          ("trait_impls", _trait_impls);
+          ("unstructured_functions", _unstructured_functions);
         ] ->
         (* We first deserialize the declaration groups (which simply contain ids)
          * and all the declarations *butù* the globals *)
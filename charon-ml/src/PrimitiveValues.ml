@@ -1,6 +1,23 @@
 (** The primitive values. *)
 
-open Types
+(** The integer types. They are reexported by {!Types}. *)
+type integer_type =
+  | Isize
+  | I8
+  | I16
+  | I32
+  | I64
+  | I128
+  | Usize
+  | U8
+  | U16
+  | U32
+  | U64
+  | U128
+[@@deriving show, ord]
+
+(** The floating-point types. They are reexported by {!Types}. *)
+type float_type = F32 | F64 [@@deriving show, ord]
 
 (** We use big integers to store the integer values (this way we don't have
     to think about the bounds, nor architecture issues - Rust allows to
@@ -35,9 +52,15 @@ type scalar_value = { value : big_int; int_ty : integer_type }
     Can be used by operands (in which case it represents a constant) or by
     the interpreter to represent a concrete, primitive value.
  *)
+(** A floating-point value. We keep the literal as a string (for instance,
+    ["1.5"]) so as not to lose precision. *)
+type float_value = { float_value : string; float_ty : float_type }
+[@@deriving show, ord]
+
 type primitive_value =
   | Scalar of scalar_value
   | Bool of bool
   | Char of char
   | String of string
+  | Float of float_value
 [@@deriving show, ord]
@@ -56,11 +56,28 @@ let rec projection_to_string (fmt : expr_formatter) (inside : string)
       match pe with
       | E.Deref -> "*(" ^ s ^ ")"
       | E.DerefBox -> "deref_box(" ^ s ^ ")"
+      | E.DerefRawPtr -> "deref_raw_ptr(" ^ s ^ ")"
+      | E.DerefRc -> "deref_rc(" ^ s ^ ")"
+      | E.Index var_id -> s ^ "[" ^ fmt.var_id_to_string var_id ^ "]"
+      | E.ConstantIndex (offset, min_length, from_end) ->
+          let from_end = if from_end then "-" else "" in
+          s ^ "[" ^ from_end ^ string_of_int offset ^ " of "
+          ^ string_of_int min_length ^ "]"
       | E.Field (E.ProjOption variant_id, fid) ->
           assert (variant_id = T.option_some_id);
           assert (fid = T.FieldId.zero);
           "(" ^ s ^ " as Option::Some)." ^ T.FieldId.to_string fid
       | E.Field (E.ProjTuple _, fid) -> "(" ^ s ^ ")." ^ T.FieldId.to_string fid
+      | E.Field (E.ProjControlFlow variant_id, fid) ->
+          "(" ^ s ^ " as variant @" ^ T.VariantId.to_string variant_id ^ ")."
+          ^ T.FieldId.to_string fid
+      | E.Field (E.ProjUnion adt_id, fid) ->
+          let field_name =
+            match fmt.adt_field_to_string adt_id None fid with
+            | Some field_name -> field_name
+            | None -> T.FieldId.to_string fid
+          in
+          "(" ^ s ^ ")." ^ field_name
       | E.Field (E.ProjAdt (adt_id, opt_variant_id), fid) -> (
           let field_name =
             match fmt.adt_field_to_string adt_id opt_variant_id fid with
@@ -81,12 +98,6 @@ let unop_to_string (unop : E.unop) : string =
   match unop with
   | E.Not -> "¬"
   | E.Neg -> "-"
-  | E.Cast (src, tgt) ->
-      "cast<"
-      ^ PT.integer_type_to_string src
-      ^ ","
-      ^ PT.integer_type_to_string tgt
-      ^ ">"
 
 let binop_to_string (binop : E.binop) : string =
   match binop with
@@ -106,6 +117,28 @@ let binop_to_string (binop : E.binop) : string =
   | E.Mul -> "*"
   | E.Shl -> "<<"
   | E.Shr -> ">>"
+  | E.Offset -> "offset"
+
+let rec constant_value_to_string (fmt : expr_formatter)
+    (cv : E.constant_value) : string =
+  match cv with
+  | E.PrimitiveValue cv -> PPV.primitive_value_to_string cv
+  | E.Array cvs ->
+      "["
+      ^ String.concat ", " (List.map (constant_value_to_string fmt) cvs)
+      ^ "]"
+  | E.Bytes bytes -> "[" ^ String.concat ", " (List.map string_of_int bytes) ^ "]"
+  | E.TraitConst (trait_id, tys, name) ->
+      (* The first type argument is the [Self] type *)
+      let tys = List.map (PT.ety_to_string (expr_to_etype_formatter fmt)) tys in
+      let trait_name = "@Trait" ^ T.TraitDeclId.to_string trait_id in
+      let trait_name =
+        match List.tl tys with
+        | [] -> trait_name
+        | args -> trait_name ^ "<" ^ String.concat ", " args ^ ">"
+      in
+      "<" ^ List.hd tys ^ " as " ^ trait_name ^ ">::" ^ name
+  | E.ZST _ -> "zst"
 
 let operand_to_string (fmt : expr_formatter) (op : E.operand) : string =
   match op with
@@ -113,7 +146,7 @@ let operand_to_string (fmt : expr_formatter) (op : E.operand) : string =
   | E.Move p -> "move " ^ place_to_string fmt p
   | E.Constant (ty, cv) ->
       "("
-      ^ PPV.primitive_value_to_string cv
+      ^ constant_value_to_string fmt cv
       ^ " : "
       ^ PT.ety_to_string (expr_to_etype_formatter fmt) ty
       ^ ")"
@@ -128,12 +161,50 @@ let rvalue_to_string (fmt : expr_formatter) (rv : E.rvalue) : string =
       | E.Mut -> "&mut " ^ p
       | E.TwoPhaseMut -> "&two-phase " ^ p
       | E.Shallow -> "&shallow " ^ p)
+  | E.RawPtr (p, rk) -> (
+      let p = place_to_string fmt p in
+      match rk with T.Shared -> "&raw const " ^ p | T.Mut -> "&raw mut " ^ p)
   | E.UnaryOp (unop, op) -> unop_to_string unop ^ " " ^ operand_to_string fmt op
   | E.BinaryOp (binop, op1, op2) ->
       operand_to_string fmt op1 ^ " " ^ binop_to_string binop ^ " "
       ^ operand_to_string fmt op2
+  | E.Cast (kind, op, src, tgt) ->
+      let ty_fmt = expr_to_etype_formatter fmt in
+      let op = operand_to_string fmt op in
+      let op =
+        match kind with E.EnumToInt -> "@discriminant(" ^ op ^ ")" | _ -> op
+      in
+      "cast<"
+      ^ PT.ety_to_string ty_fmt src
+      ^ ","
+      ^ PT.ety_to_string ty_fmt tgt
+      ^ ">("
+      ^ op
+      ^ ")"
   | E.Discriminant p -> "discriminant(" ^ place_to_string fmt p ^ ")"
+  | E.Len p -> "len(" ^ place_to_string fmt p ^ ")"
+  | E.Unsize (op, _, _) -> "@unsize(" ^ operand_to_string fmt op ^ ")"
+  | E.FnPtr fid -> "@FnPtr(" ^ fmt.fun_decl_id_to_string fid ^ ")"
   | E.Global gid -> "global " ^ fmt.global_decl_id_to_string gid
+  | E.GlobalRef (gid, rk) -> (
+      let gid = fmt.global_decl_id_to_string gid in
+      match rk with T.Shared -> "&global " ^ gid | T.Mut -> "&mut global " ^ gid)
+  | E.IsVariant (p, variant_ids) ->
+      let variant_ids = List.map T.VariantId.to_string variant_ids in
+      "@is_variant(" ^ place_to_string fmt p ^ ", "
+      ^ String.concat " | " variant_ids
+      ^ ")"
+  | E.Slice (base, from, to_, bk) ->
+      let borrow =
+        match bk with
+        | E.Mut | E.TwoPhaseMut -> "&mut "
+        | E.Shared | E.Shallow -> "&"
+      in
+      borrow ^ place_to_string fmt base ^ "["
+      ^ operand_to_string fmt from
+      ^ ".."
+      ^ operand_to_string fmt to_
+      ^ "]"
   | E.Aggregate (akind, ops) -> (
       let ops = List.map (operand_to_string fmt) ops in
       match akind with
@@ -168,4 +239,7 @@ let rvalue_to_string (fmt : expr_formatter) (rv : E.rvalue) : string =
                 let fields = String.concat " " fields in
                 "{ " ^ fields ^ " }"
           in
-          variant_name ^ " " ^ fields)
+          variant_name ^ " " ^ fields
+      | E.AggregatedClosure (fid, _) ->
+          "@Closure(" ^ fmt.fun_decl_id_to_string fid ^ ", "
+          ^ String.concat ", " ops ^ ")")
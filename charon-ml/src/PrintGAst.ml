@@ -83,7 +83,14 @@ let call_to_string (fmt : ast_formatter) (indent : string) (call : GA.call) :
   let args = "(" ^ String.concat ", " args ^ ")" in
   let name_args =
     match call.GA.func with
-    | GA.Regular fid -> fmt.fun_decl_id_to_string fid ^ t_params
+    | GA.Regular fid | GA.TraitMethod (_, fid) ->
+        (* The name of the method of the implementation is enough to
+           identify it *)
+        fmt.fun_decl_id_to_string fid ^ t_params
+    | GA.Virtual (_, fid) ->
+        (* The method is dispatched at runtime *)
+        "@virtual(" ^ fmt.fun_decl_id_to_string fid ^ ")" ^ t_params
+    | GA.Indirect op -> "(*" ^ PE.operand_to_string fmt op ^ ")"
     | GA.Assumed fid -> (
         match fid with
         | GA.Replace -> "core::mem::replace" ^ t_params
@@ -92,6 +99,12 @@ let call_to_string (fmt : ast_formatter) (indent : string) (call : GA.call) :
         | GA.BoxDerefMut ->
             "core::ops::deref::DerefMut" ^ t_params ^ "::deref_mut"
         | GA.BoxFree -> "alloc::alloc::box_free" ^ t_params
+        | GA.RcNew -> "alloc::rc::Rc" ^ t_params ^ "::new"
+        | GA.RcClone ->
+            "core::clone::Clone<alloc::rc::Rc" ^ t_params ^ ">::clone"
+        | GA.ArcNew -> "alloc::sync::Arc" ^ t_params ^ "::new"
+        | GA.ArcClone ->
+            "core::clone::Clone<alloc::sync::Arc" ^ t_params ^ ">::clone"
         | GA.VecNew -> "alloc::vec::Vec" ^ t_params ^ "::new"
         | GA.VecPush -> "alloc::vec::Vec" ^ t_params ^ "::push"
         | GA.VecInsert -> "alloc::vec::Vec" ^ t_params ^ "::insert"
@@ -100,7 +113,27 @@ let call_to_string (fmt : ast_formatter) (indent : string) (call : GA.call) :
             "core::ops::index::Index<alloc::vec::Vec" ^ t_params ^ ">::index"
         | GA.VecIndexMut ->
             "core::ops::index::IndexMut<alloc::vec::Vec" ^ t_params
-            ^ ">::index_mut")
+            ^ ">::index_mut"
+        | GA.OptionUnwrap -> "core::option::Option::unwrap" ^ t_params
+        | GA.OptionExpect -> "core::option::Option::expect" ^ t_params
+        | GA.OptionUnwrapOr -> "core::option::Option::unwrap_or" ^ t_params
+        | GA.OptionIsSome -> "core::option::Option::is_some" ^ t_params
+        | GA.OptionIsNone -> "core::option::Option::is_none" ^ t_params
+        | GA.OptionMap -> "core::option::Option::map" ^ t_params
+        | GA.ResultUnwrap -> "core::result::Result::unwrap" ^ t_params
+        | GA.ResultExpect -> "core::result::Result::expect" ^ t_params
+        | GA.ResultIsOk -> "core::result::Result::is_ok" ^ t_params
+        | GA.ResultIsErr -> "core::result::Result::is_err" ^ t_params
+        | GA.ResultMap -> "core::result::Result::map" ^ t_params
+        | GA.ResultMapErr -> "core::result::Result::map_err" ^ t_params
+        | GA.TryBranch -> "core::ops::try_trait::Try" ^ t_params ^ "::branch"
+        | GA.FromResidual ->
+            "core::ops::try_trait::FromResidual" ^ t_params ^ "::from_residual"
+        | GA.IntoIter ->
+            "core::iter::traits::collect::IntoIterator" ^ t_params
+            ^ "::into_iter"
+        | GA.IteratorNext ->
+            "core::iter::traits::iterator::Iterator" ^ t_params ^ "::next")
   in
   let dest = PE.place_to_string fmt call.GA.dest in
   indent ^ dest ^ " := move " ^ name_args ^ args
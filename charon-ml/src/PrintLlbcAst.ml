@@ -29,6 +29,9 @@ module Ast = struct
     match st with
     | A.Assign (p, rv) ->
         indent ^ PE.place_to_string fmt p ^ " := " ^ PE.rvalue_to_string fmt rv
+    | A.CompoundAssign (p, binop, rhs) ->
+        indent ^ PE.place_to_string fmt p ^ " " ^ PE.binop_to_string binop
+        ^ "= " ^ PE.operand_to_string fmt rhs
     | A.FakeRead p -> indent ^ "fake_read " ^ PE.place_to_string fmt p
     | A.SetDiscriminant (p, variant_id) ->
         (* TODO: improve this to lookup the variant name by using the def id *)
@@ -113,6 +116,13 @@ module Ast = struct
         indent ^ "loop {\n"
         ^ statement_to_string fmt (indent ^ indent_incr) indent_incr loop_st
         ^ "\n" ^ indent ^ "}"
+    | A.While (cond, op, body) ->
+        let indent1 = indent ^ indent_incr in
+        indent ^ "while {\n"
+        ^ statement_to_string fmt indent1 indent_incr cond
+        ^ "\n" ^ indent1 ^ PE.operand_to_string fmt op ^ "\n" ^ indent ^ "} {\n"
+        ^ statement_to_string fmt indent1 indent_incr body
+        ^ "\n" ^ indent ^ "}"
 
   let fun_decl_to_string (fmt : ast_formatter) (indent : string)
       (indent_incr : string) (def : A.fun_decl) : string =
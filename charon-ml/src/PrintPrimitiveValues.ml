@@ -4,13 +4,28 @@ module T = Types
 module TU = TypesUtils
 module E = Expressions
 module A = LlbcAst
-module PT = PrintTypes
 open PrimitiveValues
 
+let integer_type_to_string = function
+  | Isize -> "isize"
+  | I8 -> "i8"
+  | I16 -> "i16"
+  | I32 -> "i32"
+  | I64 -> "i64"
+  | I128 -> "i128"
+  | Usize -> "usize"
+  | U8 -> "u8"
+  | U16 -> "u16"
+  | U32 -> "u32"
+  | U64 -> "u64"
+  | U128 -> "u128"
+
+let float_type_to_string = function F32 -> "f32" | F64 -> "f64"
+
 let big_int_to_string (bi : big_int) : string = Z.to_string bi
 
 let scalar_value_to_string (sv : scalar_value) : string =
-  big_int_to_string sv.value ^ ": " ^ PT.integer_type_to_string sv.int_ty
+  big_int_to_string sv.value ^ ": " ^ integer_type_to_string sv.int_ty
 
 let primitive_value_to_string (cv : primitive_value) : string =
   match cv with
@@ -18,3 +33,4 @@ let primitive_value_to_string (cv : primitive_value) : string =
   | Bool b -> Bool.to_string b
   | Char c -> String.make 1 c
   | String s -> s
+  | Float fv -> fv.float_value ^ ": " ^ float_type_to_string fv.float_ty
@@ -4,6 +4,7 @@ module T = Types
 module TU = TypesUtils
 module E = Expressions
 module A = LlbcAst
+module PPV = PrintPrimitiveValues
 open PrintUtils
 
 let type_var_id_to_string (id : T.TypeVarId.id) : string =
@@ -43,19 +44,16 @@ type stype_formatter = T.RegionVarId.id T.region type_formatter
 type rtype_formatter = T.RegionId.id T.region type_formatter
 type etype_formatter = T.erased_region type_formatter
 
-let integer_type_to_string = function
-  | T.Isize -> "isize"
-  | T.I8 -> "i8"
-  | T.I16 -> "i16"
-  | T.I32 -> "i32"
-  | T.I64 -> "i64"
-  | T.I128 -> "i128"
-  | T.Usize -> "usize"
-  | T.U8 -> "u8"
-  | T.U16 -> "u16"
-  | T.U32 -> "u32"
-  | T.U64 -> "u64"
-  | T.U128 -> "u128"
+let integer_type_to_string = PPV.integer_type_to_string
+let float_type_to_string = PPV.float_type_to_string
+
+let const_generic_var_id_to_string (id : T.ConstGenericVarId.id) : string =
+  "@Cg" ^ T.ConstGenericVarId.to_string id
+
+let const_generic_to_string (cg : T.const_generic) : string =
+  match cg with
+  | T.ConstGenericVar id -> const_generic_var_id_to_string id
+  | T.ConstGenericValue v -> PPV.primitive_value_to_string v
 
 let type_id_to_string (fmt : 'r type_formatter) (id : T.type_id) : string =
   match id with
@@ -65,7 +63,10 @@ let type_id_to_string (fmt : 'r type_formatter) (id : T.type_id) : string =
       match aty with
       | Box -> "alloc::boxed::Box"
       | Vec -> "alloc::vec::Vec"
-      | Option -> "core::option::Option")
+      | Option -> "core::option::Option"
+      | ControlFlow -> "core::ops::control_flow::ControlFlow"
+      | Rc -> "alloc::rc::Rc"
+      | Arc -> "alloc::sync::Arc")
 
 let rec ty_to_string (fmt : 'r type_formatter) (ty : 'r T.ty) : string =
   match ty with
@@ -78,6 +79,7 @@ let rec ty_to_string (fmt : 'r type_formatter) (ty : 'r T.ty) : string =
   | T.Char -> "char"
   | T.Never -> "!"
   | T.Integer int_ty -> integer_type_to_string int_ty
+  | T.Float float_ty -> float_type_to_string float_ty
   | T.Str -> "str"
   | T.Array aty -> "[" ^ ty_to_string fmt aty ^ "; ?]"
   | T.Slice sty -> "[" ^ ty_to_string fmt sty ^ "]"
@@ -85,6 +87,37 @@ let rec ty_to_string (fmt : 'r type_formatter) (ty : 'r T.ty) : string =
       match ref_kind with
       | T.Mut -> "&" ^ fmt.r_to_string r ^ " mut (" ^ ty_to_string fmt rty ^ ")"
       | T.Shared -> "&" ^ fmt.r_to_string r ^ " (" ^ ty_to_string fmt rty ^ ")")
+  | T.RawPtr (rty, ref_kind) -> (
+      match ref_kind with
+      | T.Mut -> "*mut " ^ ty_to_string fmt rty
+      | T.Shared -> "*const " ^ ty_to_string fmt rty)
+  | T.ConstGeneric cg -> const_generic_to_string cg
+  | T.DynTrait trait_id -> "dyn @Trait" ^ T.TraitDeclId.to_string trait_id
+  | T.FnPtr (inputs, output) ->
+      let inputs = List.map (ty_to_string fmt) inputs in
+      "fn(" ^ String.concat ", " inputs ^ ") -> " ^ ty_to_string fmt output
+  | T.Opaque (hidden_ty, bounds) -> (
+      let bounds =
+        List.map (fun id -> "@Trait" ^ T.TraitDeclId.to_string id) bounds
+      in
+      let bounds = if bounds = [] then "_" else String.concat " + " bounds in
+      match hidden_ty with
+      | Some ty -> "impl " ^ bounds ^ " (= " ^ ty_to_string fmt ty ^ ")"
+      | None -> "impl " ^ bounds)
+  | T.TraitProjection (trait_id, regions, tys, name) ->
+      (* The first type argument is the [Self] type *)
+      let self_ty = ty_to_string fmt (List.hd tys) in
+      let args =
+        List.append
+          (List.map fmt.r_to_string regions)
+          (List.map (ty_to_string fmt) (List.tl tys))
+      in
+      let trait_name = "@Trait" ^ T.TraitDeclId.to_string trait_id in
+      let trait_name =
+        if args = [] then trait_name
+        else trait_name ^ "<" ^ String.concat ", " args ^ ">"
+      in
+      "<" ^ self_ty ^ " as " ^ trait_name ^ ">::" ^ name
 
 and params_to_string (fmt : 'r type_formatter) (is_tuple : bool)
     (regions : 'r list) (types : 'r T.ty list) : string =
@@ -154,6 +187,12 @@ let type_decl_to_string (type_decl_id_to_string : T.TypeDeclId.id -> string)
       in
       let variants = String.concat "\n" variants in
       "enum " ^ name ^ params ^ " =\n" ^ variants
+  | T.Union fields ->
+      let fields =
+        String.concat ","
+          (List.map (fun f -> "\n  " ^ field_to_string fmt f) fields)
+      in
+      "union " ^ name ^ params ^ "{" ^ fields ^ "}"
   | T.Opaque -> "opaque type " ^ name ^ params
 
 let type_ctx_to_adt_variant_to_string_fun (ctx : T.type_decl T.TypeDeclId.Map.t)
@@ -161,7 +200,7 @@ let type_ctx_to_adt_variant_to_string_fun (ctx : T.type_decl T.TypeDeclId.Map.t)
  fun def_id variant_id ->
   let def = T.TypeDeclId.Map.find def_id ctx in
   match def.kind with
-  | Struct _ | Opaque -> raise (Failure "Unreachable")
+  | Struct _ | Union _ | Opaque -> raise (Failure "Unreachable")
   | Enum variants ->
       let variant = T.VariantId.nth variants variant_id in
       name_to_string def.name ^ "::" ^ variant.variant_name
@@ -5,6 +5,8 @@ module TypeVarId = IdGen ()
 module TypeDeclId = IdGen ()
 module VariantId = IdGen ()
 module FieldId = IdGen ()
+module TraitDeclId = IdGen ()
+module ConstGenericVarId = IdGen ()
 
 (** We define this type to control the name of the visitor functions
     (see e.g., {!Types.iter_ty_base} and {!Types.TypeVar}).
@@ -18,6 +20,8 @@ type variant_id = VariantId.id [@@deriving show, ord]
 
 type field_id = FieldId.id [@@deriving show, ord]
 type type_decl_id = TypeDeclId.id [@@deriving show]
+type trait_decl_id = TraitDeclId.id [@@deriving show, ord]
+type const_generic_var_id = ConstGenericVarId.id [@@deriving show, ord]
 
 (** Region variable ids. Used in function signatures. *)
 module RegionVarId = IdGen ()
@@ -75,7 +79,9 @@ type region_var_group = (RegionGroupId.id, RegionVarId.id) g_region_group
 type region_var_groups = (RegionGroupId.id, RegionVarId.id) g_region_groups
 [@@deriving show]
 
-type integer_type =
+(** The integer types are defined in {!PrimitiveValues}, because the const
+    generic arguments (see {!const_generic}) may be primitive values *)
+type integer_type = PrimitiveValues.integer_type =
   | Isize
   | I8
   | I16
@@ -94,8 +100,32 @@ let all_signed_int_types = [ Isize; I8; I16; I32; I64; I128 ]
 let all_unsigned_int_types = [ Usize; U8; U16; U32; U64; U128 ]
 let all_int_types = List.append all_signed_int_types all_unsigned_int_types
 
+type float_type = PrimitiveValues.float_type = F32 | F64
+[@@deriving show, ord]
+
 type ref_kind = Mut | Shared [@@deriving show, ord]
-type assumed_ty = Box | Vec | Option [@@deriving show, ord]
+
+(** A const generic argument *)
+type const_generic =
+  | ConstGenericVar of const_generic_var_id
+      (** A const generic parameter of the enclosing definition *)
+  | ConstGenericValue of PrimitiveValues.primitive_value
+      (** A constant value (the constant expressions, like [N + 1] when [N]
+          is known, are evaluated) *)
+[@@deriving show, ord]
+type assumed_ty =
+  | Box
+  | Vec
+  | Option
+  | ControlFlow
+      (** [core::ops::control_flow::ControlFlow<B, C>], introduced by the [?]
+          operator. Its variants are [Continue(C)] and [Break(B)], in this
+          order. *)
+  | Rc
+      (** [alloc::rc::Rc<T>]: an opaque smart pointer, dereferenced with the
+          [DerefRc] projection *)
+  | Arc  (** [alloc::sync::Arc<T>] *)
+[@@deriving show, ord]
 
 (** The variant id for [Option::None] *)
 let option_none_id = VariantId.of_int 0
@@ -119,7 +149,10 @@ class ['self] iter_ty_base =
     method visit_type_var_id : 'env -> type_var_id -> unit = fun _ _ -> ()
     method visit_type_id : 'env -> type_id -> unit = fun _ _ -> ()
     method visit_integer_type : 'env -> integer_type -> unit = fun _ _ -> ()
+    method visit_float_type : 'env -> float_type -> unit = fun _ _ -> ()
     method visit_ref_kind : 'env -> ref_kind -> unit = fun _ _ -> ()
+    method visit_trait_decl_id : 'env -> trait_decl_id -> unit = fun _ _ -> ()
+    method visit_const_generic : 'env -> const_generic -> unit = fun _ _ -> ()
   end
 
 (** Ancestor for map visitor for {!Types.ty} *)
@@ -136,7 +169,16 @@ class virtual ['self] map_ty_base =
     method visit_integer_type : 'env -> integer_type -> integer_type =
       fun _ ity -> ity
 
+    method visit_float_type : 'env -> float_type -> float_type =
+      fun _ fty -> fty
+
     method visit_ref_kind : 'env -> ref_kind -> ref_kind = fun _ rk -> rk
+
+    method visit_trait_decl_id : 'env -> trait_decl_id -> trait_decl_id =
+      fun _ id -> id
+
+    method visit_const_generic : 'env -> const_generic -> const_generic =
+      fun _ cg -> cg
   end
 
 type 'r ty =
@@ -147,10 +189,27 @@ type 'r ty =
   | Char
   | Never
   | Integer of integer_type
+  | Float of float_type
   | Str
   | Array of 'r ty (* TODO: there should be a constant with the array *)
   | Slice of 'r ty
   | Ref of 'r * 'r ty * ref_kind
+  | RawPtr of 'r ty * ref_kind
+      (** A raw pointer: [*const T] if the kind is [Shared], [*mut T] if it
+          is [Mut] *)
+  | ConstGeneric of const_generic
+      (** A const generic argument. This only appears in the arguments of the
+          ADTs and of the function calls, after the type arguments. *)
+  | DynTrait of trait_decl_id
+      (** A trait object ([dyn Trait]): we only keep the principal trait *)
+  | FnPtr of 'r ty list * 'r ty
+      (** A function pointer type ([fn(u32) -> bool]): the inputs and the
+          output *)
+  | Opaque of 'r ty option * trait_decl_id list
+      (** An [impl Trait] type, with its hidden type if we could reveal it *)
+  | TraitProjection of trait_decl_id * 'r list * 'r ty list * string
+      (** An associated type of a trait ([<T as Trait>::Name]). The first type
+          argument is the [Self] type. *)
 [@@deriving
   show,
     ord,
@@ -205,6 +264,9 @@ type variant = { meta : meta; variant_name : string; fields : field list }
 type type_decl_kind =
   | Struct of field list
   | Enum of variant list
+  | Union of field list
+      (** A union: the fields share the same memory, and the reads of the
+          fields are unsafe *)
   | Opaque
       (** An opaque type: either a local type marked as opaque, or an external type *)
 [@@deriving show]
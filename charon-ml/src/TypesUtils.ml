@@ -2,7 +2,7 @@ open Types
 open Utils
 
 let type_decl_is_opaque (d : type_decl) : bool =
-  match d.kind with Struct _ | Enum _ -> false | Opaque -> true
+  match d.kind with Struct _ | Enum _ | Union _ -> false | Opaque -> true
 
 (** Retrieve the list of fields for the given variant of a {!Types.type_decl}.
 
@@ -12,7 +12,7 @@ let type_decl_get_fields (def : type_decl)
     (opt_variant_id : VariantId.id option) : field list =
   match (def.kind, opt_variant_id) with
   | Enum variants, Some variant_id -> (VariantId.nth variants variant_id).fields
-  | Struct fields, None -> fields
+  | (Struct fields | Union fields), None -> fields
   | _ ->
       let opt_variant_id =
         match opt_variant_id with None -> "None" | Some _ -> "Some"
@@ -118,9 +118,22 @@ let rec ety_no_regions_to_gr_ty (ty : ety) : 'a gr_ty =
   | Char -> Char
   | Never -> Never
   | Integer int_ty -> Integer int_ty
+  | Float float_ty -> Float float_ty
   | Str -> Str
   | Array ty -> Array (ety_no_regions_to_gr_ty ty)
   | Slice ty -> Slice (ety_no_regions_to_gr_ty ty)
+  | RawPtr (ty, ref_kind) -> RawPtr (ety_no_regions_to_gr_ty ty, ref_kind)
+  | ConstGeneric cg -> ConstGeneric cg
+  | DynTrait trait_id -> DynTrait trait_id
+  | FnPtr (inputs, output) ->
+      FnPtr
+        (List.map ety_no_regions_to_gr_ty inputs, ety_no_regions_to_gr_ty output)
+  | Opaque (hidden_ty, bounds) ->
+      Opaque (Option.map ety_no_regions_to_gr_ty hidden_ty, bounds)
+  | TraitProjection (trait_id, regions, tys, name) ->
+      assert (regions = []);
+      TraitProjection
+        (trait_id, [], List.map ety_no_regions_to_gr_ty tys, name)
   | Ref (_, _, _) ->
       raise
         (Failure
@@ -143,6 +156,10 @@ let ty_has_regions_in_set (rset : RegionId.Set.t) (ty : rty) : bool =
       method! visit_Ref env r ty rkind =
         if region_in_set r rset then raise Found
         else super#visit_Ref env r ty rkind
+
+      method! visit_TraitProjection env trait_id regions tys name =
+        List.iter (fun r -> if region_in_set r rset then raise Found) regions;
+        super#visit_TraitProjection env trait_id regions tys name
     end
   in
   try
@@ -161,10 +178,14 @@ let ty_has_regions_in_set (rset : RegionId.Set.t) (ty : rty) : bool =
   *)
 let rec ty_is_primitively_copyable (ty : 'r ty) : bool =
   match ty with
-  | Adt (Assumed Option, _, tys) -> List.for_all ty_is_primitively_copyable tys
-  | Adt ((AdtId _ | Assumed (Box | Vec)), _, _) -> false
+  | Adt (Assumed (Option | ControlFlow), _, tys) ->
+      List.for_all ty_is_primitively_copyable tys
+  | Adt ((AdtId _ | Assumed (Box | Vec | Rc | Arc)), _, _) -> false
   | Adt (Tuple, _, tys) -> List.for_all ty_is_primitively_copyable tys
-  | TypeVar _ | Never | Str | Array _ | Slice _ -> false
-  | Bool | Char | Integer _ -> true
+  | TypeVar _ | Never | Str | Array _ | Slice _ | DynTrait _ | Opaque _
+  | TraitProjection _ ->
+      false
+  | Bool | Char | Integer _ | Float _ | RawPtr _ | ConstGeneric _ | FnPtr _ ->
+      true
   | Ref (_, _, Mut) -> false
   | Ref (_, _, Shared) -> true
@@ -11,7 +11,7 @@ let rec statement_of_json (id_to_file : id_to_file_map) (js : json) :
     (A.statement, string) result =
   combine_error_msgs js __FUNCTION__
     (match js with
-    | `Assoc [ ("meta", meta); ("content", content) ] ->
+    | `Assoc (("meta", meta) :: ("content", content) :: _optional_fields) ->
         let* meta = meta_of_json id_to_file meta in
         let* content = raw_statement_of_json content in
         Ok ({ meta; content } : A.statement)
@@ -67,6 +67,7 @@ let call_of_json (js : json) : (A.raw_terminator, string) result =
           ("type_args", type_args);
           ("args", args);
           ("dest", dest);
+          ("sig", callee_sig);
           ("target", target);
         ] ->
         let* func = fun_id_of_json func in
@@ -74,8 +75,11 @@ let call_of_json (js : json) : (A.raw_terminator, string) result =
         let* type_args = list_of_json ety_of_json type_args in
         let* args = list_of_json operand_of_json args in
         let* dest = place_of_json dest in
+        let* callee_sig = option_of_json fun_sig_of_json callee_sig in
         let* target = A.BlockId.id_of_json target in
-        let call = { A.func; region_args; type_args; args; dest } in
+        let call =
+          { A.func; region_args; type_args; args; dest; callee_sig }
+        in
         Ok (A.Call (call, target))
     | _ -> Error "")
 
@@ -83,7 +87,7 @@ let rec terminator_of_json (id_to_file : id_to_file_map) (js : json) :
     (A.terminator, string) result =
   combine_error_msgs js __FUNCTION__
     (match js with
-    | `Assoc [ ("meta", meta); ("content", content) ] ->
+    | `Assoc (("meta", meta) :: ("content", content) :: _optional_fields) ->
         let* meta = meta_of_json id_to_file meta in
         let* content = raw_terminator_of_json content in
         Ok ({ meta; content } : A.terminator)
@@ -112,7 +116,12 @@ and raw_terminator_of_json (js : json) : (A.raw_terminator, string) result =
         [
           ( "Assert",
             `Assoc
-              [ ("cond", cond); ("expected", expected); ("target", target) ] );
+              [
+                ("cond", cond);
+                ("expected", expected);
+                ("kind", _kind);
+                ("target", target);
+              ] );
         ] ->
         let* cond = operand_of_json cond in
         let* expected = bool_of_json expected in
@@ -152,6 +161,7 @@ let global_decl_of_json (id_to_file : id_to_file_map) (js : json) :
      Ok { A.def_id = global_id; meta; body; name; ty })
 
 let crate_of_json (js : json) : (A.crate, string) result =
+  let* js = crate_of_exported_json js in
   combine_error_msgs js __FUNCTION__
     (match js with
     | `Assoc
@@ -159,9 +169,13 @@ let crate_of_json (js : json) : (A.crate, string) result =
           ("name", name);
           ("id_to_file", id_to_file);
           ("declarations", declarations);
+          ("declarations_info", _declarations_info);
           ("types", types);
           ("functions", functions);
           ("globals", globals);
+          ("trait_decls", _trait_decls);
+          ("trait_impls", _trait_impls);
+          ("unstructured_functions", _unstructured_functions);
         ] ->
         let* name = string_of_json name in
         let* id_to_file = id_to_file_of_json id_to_file in
@@ -0,0 +1,114 @@
+open Charon
+open Logging
+module EL = Easy_logging.Logging
+module U = Yojson.Basic.Util
+
+let log = main_log
+
+(* The last identifier of a name (e.g., ["Pair"] for [traits::Pair]) *)
+let last_ident (name : Names.name) : string option =
+  match List.rev name with Names.Ident s :: _ -> Some s | _ -> None
+
+let check (b : bool) (msg : string) : unit =
+  if not b then (
+    log#error "traits.llbc: %s\n" msg;
+    exit 1)
+
+(* Check the translation of [tests/src/traits.rs]: the method [clone] of the
+   derived implementation of [Clone] for [Pair] must be translated to a regular
+   function, listed in the implementation and tagged with its trait and type
+   in the declarations information. *)
+let run_tests (folder : string) : unit =
+  let file = folder ^ "/traits.llbc" in
+  log#ldebug (lazy ("Checking the traits of: " ^ file));
+  let json = Yojson.Basic.from_file file in
+  let crate =
+    match LlbcOfJson.crate_of_json json with
+    | Ok crate -> crate
+    | Error s ->
+        log#error "Error when deserializing file %s: %s\n" file s;
+        exit 1
+  in
+  (* The declarations of the traits are not deserialized by [crate_of_json]:
+     we read them directly *)
+  let raw_crate =
+    match GAstOfJson.crate_of_exported_json json with
+    | Ok js -> js
+    | Error s ->
+        log#error "Error when deserializing file %s: %s\n" file s;
+        exit 1
+  in
+
+  (* The type [Pair] *)
+  let pair =
+    List.find_opt
+      (fun (d : Types.type_decl) -> last_ident d.name = Some "Pair")
+      crate.types
+  in
+  check (Option.is_some pair) "no type declaration for [Pair]";
+  let pair_id = Types.TypeDeclId.to_int (Option.get pair).def_id in
+
+  (* The trait [core::clone::Clone] *)
+  let clone_trait =
+    List.find_opt
+      (fun d ->
+        match GAstOfJson.name_of_json (U.member "name" d) with
+        | Ok name -> Names.name_to_string name = "core::clone::Clone"
+        | Error _ -> false)
+      (U.to_list (U.member "trait_decls" raw_crate))
+  in
+  check (Option.is_some clone_trait) "no trait declaration for [Clone]";
+  let clone_trait_id = U.to_int (U.member "def_id" (Option.get clone_trait)) in
+
+  (* The implementation of [Clone] for [Pair] *)
+  let clone_impl =
+    List.find_opt
+      (fun d ->
+        U.to_int (U.member "trait_id" d) = clone_trait_id
+        && U.member "self_type" d = `Int pair_id)
+      (U.to_list (U.member "trait_impls" raw_crate))
+  in
+  check (Option.is_some clone_impl) "no implementation of [Clone] for [Pair]";
+  let clone_fun_id =
+    List.find_map
+      (fun m ->
+        match U.to_list m with
+        | [ `String "clone"; `Int id ] -> Some id
+        | _ -> None)
+      (U.to_list (U.member "methods" (Option.get clone_impl)))
+  in
+  check (Option.is_some clone_fun_id) "no method [clone] in the implementation";
+  let clone_fun_id = Option.get clone_fun_id in
+
+  (* The method is a regular function, with a body *)
+  let clone_fun =
+    List.find_opt
+      (fun (d : LlbcAst.fun_decl) ->
+        LlbcAst.FunDeclId.to_int d.def_id = clone_fun_id)
+      crate.functions
+  in
+  check (Option.is_some clone_fun) "no function declaration for [clone]";
+  check (Option.is_some (Option.get clone_fun).body) "[clone] has no body";
+
+  (* The declaration information of the method *)
+  let clone_info =
+    List.find_map
+      (fun info ->
+        match U.to_list info with
+        | [ `Assoc [ ("Fun", `Int id) ]; info ] when id = clone_fun_id ->
+            Some info
+        | _ -> None)
+      (U.to_list (U.member "declarations_info" raw_crate))
+  in
+  check (Option.is_some clone_info) "no declaration information for [clone]";
+  let trait_impl = U.member "trait_impl" (Option.get clone_info) in
+  check
+    (U.member "method_name" trait_impl = `String "clone")
+    "[clone] is not tagged with its method name";
+  check
+    (U.member "self_type" trait_impl = `Int pair_id)
+    "[clone] is not tagged with the type [Pair]";
+  check
+    (U.member "is_derived" trait_impl = `Bool true)
+    "[clone] is not tagged as derived";
+  log#linfo (lazy ("Checked the traits of: " ^ file))
@@ -13,3 +13,6 @@ let () =
 
 (* Call the tests *)
 let () = Test_Deserialize.run_tests "../../../tests/serialized"
+
+(* Check the translation of the traits *)
+let () = Test_Traits.run_tests "../../../tests/serialized"
@@ -95,6 +95,16 @@ pub mod {} {{
             serializer.serialize_u32(self.index as u32)
         }}
     }}
+
+    impl<'de> serde::Deserialize<'de> for Id {{
+        fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {{
+            let index = <u32 as serde::Deserialize>::deserialize(deserializer)?;
+            Ok(Id::new(index as usize))
+        }}
+    }}
  
     impl Generator {{
         pub fn new() -> Generator {{
@@ -14,10 +14,23 @@ use crate::ullbc_ast;
 pub static BOX_NAME: [&str; 3] = ["alloc", "boxed", "Box"];
 pub static VEC_NAME: [&str; 3] = ["alloc", "vec", "Vec"];
 pub static OPTION_NAME: [&str; 3] = ["core", "option", "Option"];
+// We don't model the pinning: `Pin<P>` is translated to `P`
+pub static PIN_NAME: [&str; 3] = ["core", "pin", "Pin"];
+// The shared pointers, which we see as opaque smart pointers
+pub static RC_NAME: [&str; 3] = ["alloc", "rc", "Rc"];
+pub static ARC_NAME: [&str; 3] = ["alloc", "sync", "Arc"];
+// Not assumed, but we filter the fields of this type (see [crate::erase_phantoms])
+pub static PHANTOM_DATA_NAME: [&str; 3] = ["core", "marker", "PhantomData"];
 
 pub static OPTION_NONE_VARIANT_ID: types::VariantId::Id = types::VariantId::ZERO;
 pub static OPTION_SOME_VARIANT_ID: types::VariantId::Id = types::VariantId::ONE;
 
+// Introduced by the `?` operator (see [crate::reconstruct_try])
+pub static CONTROL_FLOW_NAME: [&str; 4] = ["core", "ops", "control_flow", "ControlFlow"];
+
+pub static CONTROL_FLOW_CONTINUE_VARIANT_ID: types::VariantId::Id = types::VariantId::ZERO;
+pub static CONTROL_FLOW_BREAK_VARIANT_ID: types::VariantId::Id = types::VariantId::ONE;
+
 //
 // Assumed functions/traits
 //
@@ -27,12 +40,27 @@ pub static REPLACE_NAME: [&str; 3] = ["core", "mem", "replace"];
 
 // Boxes
 pub static BOX_NEW_NAME: [&str; 4] = ["alloc", "boxed", "Box", "new"];
-// This is a trait: for now we assume it is only used on boxes
+// Translated like `Box::new` (we don't model the pinning, see [PIN_NAME])
+pub static BOX_PIN_NAME: [&str; 4] = ["alloc", "boxed", "Box", "pin"];
+// `<Box<T> as From<T>>::from` is translated like `Box::new`: we identify it
+// with [crate::translate_traits::is_box_from_call]
+pub static FROM_NAME: [&str; 4] = ["core", "convert", "From", "from"];
+// The allocation of the boxes which are initialized in place (`#[rustc_box]`):
+// we translate it, together with the `ShallowInitBox` which follows, to `Box::new`
+pub static EXCHANGE_MALLOC_NAME: [&str; 3] = ["alloc", "alloc", "exchange_malloc"];
+// This is a trait: for now we assume it is only used on boxes and on the shared
+// pointers (see [crate::translate_traits::is_rc_deref_call])
 pub static DEREF_DEREF_NAME: [&str; 5] = ["core", "ops", "deref", "Deref", "deref"];
 // This is a trait: for now we assume it is only used on boxes
 pub static DEREF_DEREF_MUT_NAME: [&str; 5] = ["core", "ops", "deref", "DerefMut", "deref_mut"];
 pub static BOX_FREE_NAME: [&str; 3] = ["alloc", "alloc", "box_free"];
 
+// Shared pointers. `Clone::clone` is a trait method: we identify its uses on
+// the shared pointers with [crate::translate_traits::is_rc_clone_call]
+pub static RC_NEW_NAME: [&str; 4] = ["alloc", "rc", "Rc", "new"];
+pub static ARC_NEW_NAME: [&str; 4] = ["alloc", "sync", "Arc", "new"];
+pub static CLONE_NAME: [&str; 4] = ["core", "clone", "Clone", "clone"];
+
 // Vectors
 pub static VEC_NEW_NAME: [&str; 4] = ["alloc", "vec", "Vec", "new"];
 pub static VEC_PUSH_NAME: [&str; 4] = ["alloc", "vec", "Vec", "push"];
@@ -43,6 +71,51 @@ pub static INDEX_NAME: [&str; 5] = ["core", "ops", "index", "Index", "index"];
 // This is a trait: for now we assume it is only used on vectors
 pub static INDEX_MUT_NAME: [&str; 5] = ["core", "ops", "index", "IndexMut", "index_mut"];
 
+// Options
+pub static OPTION_UNWRAP_NAME: [&str; 4] = ["core", "option", "Option", "unwrap"];
+pub static OPTION_EXPECT_NAME: [&str; 4] = ["core", "option", "Option", "expect"];
+pub static OPTION_UNWRAP_OR_NAME: [&str; 4] = ["core", "option", "Option", "unwrap_or"];
+pub static OPTION_IS_SOME_NAME: [&str; 4] = ["core", "option", "Option", "is_some"];
+pub static OPTION_IS_NONE_NAME: [&str; 4] = ["core", "option", "Option", "is_none"];
+pub static OPTION_MAP_NAME: [&str; 4] = ["core", "option", "Option", "map"];
+
+// Results: we don't consider `Result` as assumed, but its methods are
+// defined in `core`, for which we don't have the MIR
+pub static RESULT_UNWRAP_NAME: [&str; 4] = ["core", "result", "Result", "unwrap"];
+pub static RESULT_EXPECT_NAME: [&str; 4] = ["core", "result", "Result", "expect"];
+pub static RESULT_IS_OK_NAME: [&str; 4] = ["core", "result", "Result", "is_ok"];
+pub static RESULT_IS_ERR_NAME: [&str; 4] = ["core", "result", "Result", "is_err"];
+pub static RESULT_MAP_NAME: [&str; 4] = ["core", "result", "Result", "map"];
+pub static RESULT_MAP_ERR_NAME: [&str; 4] = ["core", "result", "Result", "map_err"];
+
+// The `?` operator. Those are traits: for now we assume they are only
+// used on options and results
+pub static TRY_BRANCH_NAME: [&str; 5] = ["core", "ops", "try_trait", "Try", "branch"];
+pub static FROM_RESIDUAL_NAME: [&str; 5] =
+    ["core", "ops", "try_trait", "FromResidual", "from_residual"];
+
+// Iterators. Those are traits: they are primitive, at the exception of
+// the implementations for local types (see
+// [crate::translate_traits::is_local_iterator_call]). The `for` loops over
+// ranges are reconstructed in [crate::range_loops].
+pub static INTO_ITER_NAME: [&str; 6] = [
+    "core",
+    "iter",
+    "traits",
+    "collect",
+    "IntoIterator",
+    "into_iter",
+];
+pub static ITERATOR_NEXT_NAME: [&str; 5] =
+    ["core", "iter", "traits", "iterator", "Iterator", "next"];
+
+// Ranges: we don't model them, but use them to reconstruct the slicing
+// operations (see [crate::slice_accesses])
+pub static RANGE_NAME: [&str; 4] = ["core", "ops", "range", "Range"];
+pub static RANGE_FROM_NAME: [&str; 4] = ["core", "ops", "range", "RangeFrom"];
+pub static RANGE_TO_NAME: [&str; 4] = ["core", "ops", "range", "RangeTo"];
+pub static RANGE_FULL_NAME: [&str; 4] = ["core", "ops", "range", "RangeFull"];
+
 // Pointers
 pub static PTR_UNIQUE_NAME: [&str; 3] = ["core", "ptr", "Unique"];
 pub static PTR_NON_NULL_NAME: [&str; 3] = ["core", "ptr", "NonNull"];
@@ -60,15 +133,36 @@ enum FunId {
     BeginPanic,
     Replace,
     BoxNew,
+    BoxPin,
+    /// `alloc::alloc::exchange_malloc`
+    ExchangeMalloc,
     BoxDeref,
     BoxDerefMut,
     BoxFree,
+    RcNew,
+    ArcNew,
     VecNew,
     VecPush,
     VecInsert,
     VecLen,
     VecIndex,
     VecIndexMut,
+    OptionUnwrap,
+    OptionExpect,
+    OptionUnwrapOr,
+    OptionIsSome,
+    OptionIsNone,
+    OptionMap,
+    ResultUnwrap,
+    ResultExpect,
+    ResultIsOk,
+    ResultIsErr,
+    ResultMap,
+    ResultMapErr,
+    TryBranch,
+    FromResidual,
+    IntoIter,
+    IteratorNext,
 }
 
 pub fn get_type_id_from_name(name: &TypeName) -> Option<types::AssumedTy> {
@@ -78,10 +172,16 @@ pub fn get_type_id_from_name(name: &TypeName) -> Option<types::AssumedTy> {
         Option::Some(types::AssumedTy::Vec)
     } else if name.equals_ref_name(&OPTION_NAME) {
         Option::Some(types::AssumedTy::Option)
+    } else if name.equals_ref_name(&CONTROL_FLOW_NAME) {
+        Option::Some(types::AssumedTy::ControlFlow)
     } else if name.equals_ref_name(&PTR_UNIQUE_NAME) {
         Option::Some(types::AssumedTy::PtrUnique)
     } else if name.equals_ref_name(&PTR_NON_NULL_NAME) {
         Option::Some(types::AssumedTy::PtrNonNull)
+    } else if name.equals_ref_name(&RC_NAME) {
+        Option::Some(types::AssumedTy::Rc)
+    } else if name.equals_ref_name(&ARC_NAME) {
+        Option::Some(types::AssumedTy::Arc)
     } else {
         Option::None
     }
@@ -93,8 +193,11 @@ pub fn get_name_from_type_id(id: types::AssumedTy) -> Vec<String> {
         AssumedTy::Box => BOX_NAME.iter().map(|s| s.to_string()).collect(),
         AssumedTy::Vec => VEC_NAME.iter().map(|s| s.to_string()).collect(),
         AssumedTy::Option => OPTION_NAME.iter().map(|s| s.to_string()).collect(),
+        AssumedTy::ControlFlow => CONTROL_FLOW_NAME.iter().map(|s| s.to_string()).collect(),
         AssumedTy::PtrUnique => PTR_UNIQUE_NAME.iter().map(|s| s.to_string()).collect(),
         AssumedTy::PtrNonNull => PTR_NON_NULL_NAME.iter().map(|s| s.to_string()).collect(),
+        AssumedTy::Rc => RC_NAME.iter().map(|s| s.to_string()).collect(),
+        AssumedTy::Arc => ARC_NAME.iter().map(|s| s.to_string()).collect(),
     }
 }
 
@@ -107,12 +210,20 @@ fn get_fun_id_from_name_full(name: &FunName) -> Option<FunId> {
         Option::Some(FunId::Replace)
     } else if name.equals_ref_name(&BOX_NEW_NAME) {
         Option::Some(FunId::BoxNew)
+    } else if name.equals_ref_name(&BOX_PIN_NAME) {
+        Option::Some(FunId::BoxPin)
+    } else if name.equals_ref_name(&EXCHANGE_MALLOC_NAME) {
+        Option::Some(FunId::ExchangeMalloc)
     } else if name.equals_ref_name(&DEREF_DEREF_NAME) {
         Option::Some(FunId::BoxDeref)
     } else if name.equals_ref_name(&DEREF_DEREF_MUT_NAME) {
         Option::Some(FunId::BoxDerefMut)
     } else if name.equals_ref_name(&BOX_FREE_NAME) {
         Option::Some(FunId::BoxFree)
+    } else if name.equals_ref_name(&RC_NEW_NAME) {
+        Option::Some(FunId::RcNew)
+    } else if name.equals_ref_name(&ARC_NEW_NAME) {
+        Option::Some(FunId::ArcNew)
     } else if name.equals_ref_name(&VEC_NEW_NAME) {
         Option::Some(FunId::VecNew)
     } else if name.equals_ref_name(&VEC_PUSH_NAME) {
@@ -125,6 +236,38 @@ fn get_fun_id_from_name_full(name: &FunName) -> Option<FunId> {
         Option::Some(FunId::VecIndex)
     } else if name.equals_ref_name(&INDEX_MUT_NAME) {
         Option::Some(FunId::VecIndexMut)
+    } else if name.equals_ref_name(&OPTION_UNWRAP_NAME) {
+        Option::Some(FunId::OptionUnwrap)
+    } else if name.equals_ref_name(&OPTION_EXPECT_NAME) {
+        Option::Some(FunId::OptionExpect)
+    } else if name.equals_ref_name(&OPTION_UNWRAP_OR_NAME) {
+        Option::Some(FunId::OptionUnwrapOr)
+    } else if name.equals_ref_name(&OPTION_IS_SOME_NAME) {
+        Option::Some(FunId::OptionIsSome)
+    } else if name.equals_ref_name(&OPTION_IS_NONE_NAME) {
+        Option::Some(FunId::OptionIsNone)
+    } else if name.equals_ref_name(&OPTION_MAP_NAME) {
+        Option::Some(FunId::OptionMap)
+    } else if name.equals_ref_name(&RESULT_UNWRAP_NAME) {
+        Option::Some(FunId::ResultUnwrap)
+    } else if name.equals_ref_name(&RESULT_EXPECT_NAME) {
+        Option::Some(FunId::ResultExpect)
+    } else if name.equals_ref_name(&RESULT_IS_OK_NAME) {
+        Option::Some(FunId::ResultIsOk)
+    } else if name.equals_ref_name(&RESULT_IS_ERR_NAME) {
+        Option::Some(FunId::ResultIsErr)
+    } else if name.equals_ref_name(&RESULT_MAP_NAME) {
+        Option::Some(FunId::ResultMap)
+    } else if name.equals_ref_name(&RESULT_MAP_ERR_NAME) {
+        Option::Some(FunId::ResultMapErr)
+    } else if name.equals_ref_name(&TRY_BRANCH_NAME) {
+        Option::Some(FunId::TryBranch)
+    } else if name.equals_ref_name(&FROM_RESIDUAL_NAME) {
+        Option::Some(FunId::FromResidual)
+    } else if name.equals_ref_name(&INTO_ITER_NAME) {
+        Option::Some(FunId::IntoIter)
+    } else if name.equals_ref_name(&ITERATOR_NEXT_NAME) {
+        Option::Some(FunId::IteratorNext)
     } else {
         Option::None
     }
@@ -134,18 +277,36 @@ pub fn get_fun_id_from_name(name: &FunName) -> Option<ullbc_ast::AssumedFunId> {
     match get_fun_id_from_name_full(name) {
         Option::Some(id) => {
             let id = match id {
-                FunId::Panic | FunId::BeginPanic => unreachable!(),
+                FunId::Panic | FunId::BeginPanic | FunId::ExchangeMalloc => unreachable!(),
                 FunId::Replace => ullbc_ast::AssumedFunId::Replace,
-                FunId::BoxNew => ullbc_ast::AssumedFunId::BoxNew,
+                FunId::BoxNew | FunId::BoxPin => ullbc_ast::AssumedFunId::BoxNew,
                 FunId::BoxDeref => ullbc_ast::AssumedFunId::BoxDeref,
                 FunId::BoxDerefMut => ullbc_ast::AssumedFunId::BoxDerefMut,
                 FunId::BoxFree => ullbc_ast::AssumedFunId::BoxFree,
+                FunId::RcNew => ullbc_ast::AssumedFunId::RcNew,
+                FunId::ArcNew => ullbc_ast::AssumedFunId::ArcNew,
                 FunId::VecNew => ullbc_ast::AssumedFunId::VecNew,
                 FunId::VecPush => ullbc_ast::AssumedFunId::VecPush,
                 FunId::VecInsert => ullbc_ast::AssumedFunId::VecInsert,
                 FunId::VecLen => ullbc_ast::AssumedFunId::VecLen,
                 FunId::VecIndex => ullbc_ast::AssumedFunId::VecIndex,
                 FunId::VecIndexMut => ullbc_ast::AssumedFunId::VecIndexMut,
+                FunId::OptionUnwrap => ullbc_ast::AssumedFunId::OptionUnwrap,
+                FunId::OptionExpect => ullbc_ast::AssumedFunId::OptionExpect,
+                FunId::OptionUnwrapOr => ullbc_ast::AssumedFunId::OptionUnwrapOr,
+                FunId::OptionIsSome => ullbc_ast::AssumedFunId::OptionIsSome,
+                FunId::OptionIsNone => ullbc_ast::AssumedFunId::OptionIsNone,
+                FunId::OptionMap => ullbc_ast::AssumedFunId::OptionMap,
+                FunId::ResultUnwrap => ullbc_ast::AssumedFunId::ResultUnwrap,
+                FunId::ResultExpect => ullbc_ast::AssumedFunId::ResultExpect,
+                FunId::ResultIsOk => ullbc_ast::AssumedFunId::ResultIsOk,
+                FunId::ResultIsErr => ullbc_ast::AssumedFunId::ResultIsErr,
+                FunId::ResultMap => ullbc_ast::AssumedFunId::ResultMap,
+                FunId::ResultMapErr => ullbc_ast::AssumedFunId::ResultMapErr,
+                FunId::TryBranch => ullbc_ast::AssumedFunId::TryBranch,
+                FunId::FromResidual => ullbc_ast::AssumedFunId::FromResidual,
+                FunId::IntoIter => ullbc_ast::AssumedFunId::IntoIter,
+                FunId::IteratorNext => ullbc_ast::AssumedFunId::IteratorNext,
             };
             Option::Some(id)
         }
@@ -153,12 +314,30 @@ pub fn get_fun_id_from_name(name: &FunName) -> Option<ullbc_ast::AssumedFunId> {
     }
 }
 
+pub fn is_iterator_method_name(name: &FunName) -> bool {
+    name.equals_ref_name(&INTO_ITER_NAME) || name.equals_ref_name(&ITERATOR_NEXT_NAME)
+}
+
+pub fn is_pin_name(name: &TypeName) -> bool {
+    name.equals_ref_name(&PIN_NAME)
+}
+
+pub fn is_range_name(name: &TypeName) -> bool {
+    name.equals_ref_name(&RANGE_NAME)
+        || name.equals_ref_name(&RANGE_FROM_NAME)
+        || name.equals_ref_name(&RANGE_TO_NAME)
+        || name.equals_ref_name(&RANGE_FULL_NAME)
+}
+
 /// When translating from MIR to ULLBC, we ignore some type parameters for some
 /// assumed types.
 /// For instance, many types like box or vec are parameterized (in MIR) by an allocator
 /// (`std::alloc::Allocator`): we ignore it.
 pub fn type_to_used_params(name: &TypeName) -> Option<Vec<bool>> {
     trace!("{}", name);
+    if is_pin_name(name) {
+        return Option::Some(vec![true]);
+    }
     match get_type_id_from_name(name) {
         Option::None => Option::None,
         Option::Some(id) => {
@@ -173,9 +352,15 @@ pub fn type_to_used_params(name: &TypeName) -> Option<Vec<bool>> {
                 AssumedTy::Option => {
                     vec![true]
                 }
+                AssumedTy::ControlFlow => {
+                    vec![true, true]
+                }
                 AssumedTy::PtrUnique | AssumedTy::PtrNonNull => {
                     vec![true]
                 }
+                AssumedTy::Rc | AssumedTy::Arc => {
+                    vec![true]
+                }
             };
             Option::Some(id)
         }
@@ -207,10 +392,14 @@ pub fn function_to_info(name: &FunName) -> Option<FunInfo> {
                     used_type_params: vec![true],
                     used_args: vec![true, true],
                 },
-                FunId::BoxNew => FunInfo {
+                FunId::BoxNew | FunId::BoxPin => FunInfo {
                     used_type_params: vec![true],
                     used_args: vec![true],
                 },
+                FunId::ExchangeMalloc => FunInfo {
+                    used_type_params: vec![],
+                    used_args: vec![true, true],
+                },
                 FunId::BoxDeref => FunInfo {
                     used_type_params: vec![true],
                     used_args: vec![true],
@@ -223,6 +412,10 @@ pub fn function_to_info(name: &FunName) -> Option<FunInfo> {
                     used_type_params: vec![true, false],
                     used_args: vec![true, false],
                 },
+                FunId::RcNew | FunId::ArcNew => FunInfo {
+                    used_type_params: vec![true],
+                    used_args: vec![true],
+                },
                 FunId::VecNew => FunInfo {
                     used_type_params: vec![true],
                     used_args: vec![],
@@ -249,8 +442,100 @@ pub fn function_to_info(name: &FunName) -> Option<FunInfo> {
                     used_type_params: vec![true, false],
                     used_args: vec![true, true],
                 },
+                FunId::OptionUnwrap | FunId::OptionIsSome | FunId::OptionIsNone => FunInfo {
+                    used_type_params: vec![true],
+                    used_args: vec![true],
+                },
+                FunId::OptionExpect | FunId::OptionUnwrapOr => FunInfo {
+                    used_type_params: vec![true],
+                    used_args: vec![true, true],
+                },
+                FunId::OptionMap => FunInfo {
+                    // `Option<T>::map<U, F>`: we keep the type of the closure
+                    used_type_params: vec![true, true, true],
+                    used_args: vec![true, true],
+                },
+                FunId::ResultUnwrap | FunId::ResultIsOk | FunId::ResultIsErr => FunInfo {
+                    used_type_params: vec![true, true],
+                    used_args: vec![true],
+                },
+                FunId::ResultExpect => FunInfo {
+                    used_type_params: vec![true, true],
+                    used_args: vec![true, true],
+                },
+                FunId::ResultMap | FunId::ResultMapErr => FunInfo {
+                    used_type_params: vec![true, true, true, true],
+                    used_args: vec![true, true],
+                },
+                FunId::TryBranch => FunInfo {
+                    // The type parameter is the `Self` type (`Option<T>`, `Result<T, E>`)
+                    used_type_params: vec![true],
+                    used_args: vec![true],
+                },
+                FunId::FromResidual => FunInfo {
+                    // `Self` and the type of the residual
+                    used_type_params: vec![true, true],
+                    used_args: vec![true],
+                },
+                FunId::IntoIter | FunId::IteratorNext => FunInfo {
+                    // The type parameter is the `Self` type (`Range<usize>`, etc.)
+                    used_type_params: vec![true],
+                    used_args: vec![true],
+                },
             };
             Option::Some(info)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::assumed::*;
+    use crate::names::Name;
+
+    fn mk_name(name: &[&str]) -> Name {
+        Name::from(name.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn test_box_allocations() {
+        // `Box::pin` is translated like `Box::new`
+        let id = get_fun_id_from_name(&mk_name(&BOX_PIN_NAME)).unwrap();
+        assert!(id.is_box_new());
+        let info = function_to_info(&mk_name(&BOX_PIN_NAME)).unwrap();
+        assert!(info.used_type_params == vec![true] && info.used_args == vec![true]);
+
+        // `Pin<P>` is translated to `P`
+        assert!(is_pin_name(&mk_name(&PIN_NAME)));
+        assert!(type_to_used_params(&mk_name(&PIN_NAME)) == Option::Some(vec![true]));
+    }
+
+    #[test]
+    fn test_option_result_combinators() {
+        let id = get_fun_id_from_name(&mk_name(&OPTION_MAP_NAME)).unwrap();
+        assert!(id.is_option_map());
+        let id = get_fun_id_from_name(&mk_name(&RESULT_MAP_ERR_NAME)).unwrap();
+        assert!(id.is_result_map_err());
+
+        // The `?` operator
+        let info = function_to_info(&mk_name(&FROM_RESIDUAL_NAME)).unwrap();
+        assert!(info.used_type_params.len() == 2 && info.used_args.len() == 1);
+
+        // The methods which are not in the table are external functions
+        assert!(
+            get_fun_id_from_name(&mk_name(&["core", "option", "Option", "and_then"])).is_none()
+        );
+    }
+
+    #[test]
+    fn test_shared_pointers() {
+        let id = get_fun_id_from_name(&mk_name(&RC_NEW_NAME)).unwrap();
+        assert!(id.is_rc_new());
+        let id = get_fun_id_from_name(&mk_name(&ARC_NEW_NAME)).unwrap();
+        assert!(id.is_arc_new());
+
+        assert!(get_type_id_from_name(&mk_name(&ARC_NAME)) == Option::Some(types::AssumedTy::Arc));
+        assert!(type_to_used_params(&mk_name(&RC_NAME)) == Option::Some(vec![true]));
+        assert!(get_name_from_type_id(types::AssumedTy::Rc) == RC_NAME.to_vec());
+    }
+}
@@ -0,0 +1,93 @@
+//! Compute the reverse call edges between the functions: for every function,
+//! the places where it is called.
+#![allow(dead_code)]
+
+use crate::llbc_ast::{visit_statements, FunDecls, RawStatement, Statement};
+use crate::meta::Meta;
+use crate::ullbc_ast::{FunDeclId, FunId};
+use std::collections::HashMap;
+
+/// The location of a function call
+#[derive(Debug, Clone)]
+pub struct CallSite {
+    /// The function in which the call happens
+    pub caller: FunDeclId::Id,
+    /// The meta information of the call statement
+    pub meta: Meta,
+}
+
+/// Register the call sites of the statement
+fn add_call_sites(
+    index: &mut HashMap<FunDeclId::Id, Vec<CallSite>>,
+    caller: FunDeclId::Id,
+    body: &Statement,
+) {
+    visit_statements(
+        &mut |st: &Statement| {
+            if let RawStatement::Call(call) = &st.content {
+                // We ignore the calls to the assumed functions and the calls
+                // through function pointers. The virtual calls are call sites
+                // of the method declared in the trait.
+                if let FunId::Regular(callee)
+                | FunId::TraitMethod(_, callee)
+                | FunId::Virtual(_, callee) = call.func
+                {
+                    index.entry(callee).or_default().push(CallSite {
+                        caller,
+                        meta: st.meta,
+                    });
+                }
+            }
+        },
+        body,
+    );
+}
+
+/// Compute, for every function, the sites where it is called.
+///
+/// The functions which are never called are not present in the map.
+pub fn build_call_index(decls: &FunDecls) -> HashMap<FunDeclId::Id, Vec<CallSite>> {
+    let mut index = HashMap::new();
+    for decl in decls.iter() {
+        if let Some(body) = &decl.body {
+            add_call_sites(&mut index, decl.def_id, &body.body);
+        }
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::call_index::*;
+    use crate::expressions::Place;
+    use crate::test_utils::*;
+    use crate::values::VarId;
+
+    fn call(callee: FunDeclId::Id) -> RawStatement {
+        RawStatement::Call(mk_call(
+            FunId::Regular(callee),
+            vec![],
+            Place::new(VarId::ZERO),
+        ))
+    }
+
+    #[test]
+    fn test_call_sites() {
+        let f0 = FunDeclId::Id::new(0);
+        let f1 = FunDeclId::Id::new(1);
+        let g = FunDeclId::Id::new(2);
+
+        // `f0` calls `g` once, and calls `f1`, which calls `g` once
+        let mut index = HashMap::new();
+        let body0 = mk_statements(vec![call(g), call(f1), RawStatement::Return]);
+        let body1 = mk_statements(vec![call(g), RawStatement::Return]);
+        add_call_sites(&mut index, f0, &body0);
+        add_call_sites(&mut index, f1, &body1);
+
+        let callers: Vec<FunDeclId::Id> =
+            index.get(&g).unwrap().iter().map(|cs| cs.caller).collect();
+        assert!(callers == vec![f0, f1]);
+        assert!(index.get(&f1).unwrap().len() == 1);
+        assert!(index.get(&f0).is_none());
+    }
+}
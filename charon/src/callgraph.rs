@@ -0,0 +1,254 @@
+//! The call graph of the crate: there is an edge from a function to every
+//! function it calls. We build it from the ULLBC or the LLBC bodies, and can
+//! export it (see the `--call-graph` option) either to JSON or to the
+//! Graphviz DOT format.
+//!
+//! As in [crate::call_index], we ignore the calls to the assumed functions and
+//! the calls through function pointers, and the virtual calls are edges to
+//! the method declared in the trait. The opaque functions have no outgoing
+//! edges.
+
+use crate::llbc_ast;
+use crate::llbc_ast::visit_statements;
+use crate::ullbc_ast;
+use crate::ullbc_ast::{FunDeclId, FunId};
+use petgraph::algo::tarjan_scc;
+use petgraph::graphmap::DiGraphMap;
+use petgraph::Direction;
+use serde::ser::SerializeSeq;
+use serde::{Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
+
+pub struct CallGraph {
+    graph: DiGraphMap<FunDeclId::Id, ()>,
+    /// The names of the functions, for the exported graphs
+    names: HashMap<FunDeclId::Id, String>,
+}
+
+/// The function statically called by a call, if there is one
+fn callee(func: &FunId) -> Option<FunDeclId::Id> {
+    match func {
+        FunId::Regular(id) | FunId::TraitMethod(_, id) | FunId::Virtual(_, id) => Some(*id),
+        FunId::Indirect(_) | FunId::Assumed(_) => None,
+    }
+}
+
+impl CallGraph {
+    pub fn new() -> Self {
+        CallGraph {
+            graph: DiGraphMap::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    pub fn add_function(&mut self, id: FunDeclId::Id, name: String) {
+        self.graph.add_node(id);
+        self.names.insert(id, name);
+    }
+
+    pub fn add_call(&mut self, caller: FunDeclId::Id, callee: FunDeclId::Id) {
+        self.graph.add_edge(caller, callee, ());
+    }
+
+    /// Add the calls performed by an LLBC statement
+    fn add_statement_calls(&mut self, caller: FunDeclId::Id, body: &llbc_ast::Statement) {
+        visit_statements(
+            &mut |st: &llbc_ast::Statement| {
+                if let llbc_ast::RawStatement::Call(call) = &st.content {
+                    if let Some(id) = callee(&call.func) {
+                        self.add_call(caller, id);
+                    }
+                }
+            },
+            body,
+        )
+    }
+
+    pub fn from_ullbc(funs: &ullbc_ast::FunDecls) -> Self {
+        let mut graph = CallGraph::new();
+        for f in funs.iter() {
+            graph.add_function(f.def_id, f.name.to_string());
+        }
+        for f in funs.iter() {
+            if let Some(body) = &f.body {
+                for block in body.body.iter() {
+                    if let ullbc_ast::RawTerminator::Call { func, .. } = &block.terminator.content {
+                        if let Some(id) = callee(func) {
+                            graph.add_call(f.def_id, id);
+                        }
+                    }
+                }
+            }
+        }
+        graph
+    }
+
+    pub fn from_llbc(funs: &llbc_ast::FunDecls) -> Self {
+        let mut graph = CallGraph::new();
+        for f in funs.iter() {
+            graph.add_function(f.def_id, f.name.to_string());
+        }
+        for f in funs.iter() {
+            if let Some(body) = &f.body {
+                graph.add_statement_calls(f.def_id, &body.body);
+            }
+        }
+        graph
+    }
+
+    /// The functions called by a function (in no particular order)
+    pub fn callees(&self, id: FunDeclId::Id) -> impl Iterator<Item = FunDeclId::Id> + '_ {
+        self.graph.neighbors_directed(id, Direction::Outgoing)
+    }
+
+    /// The functions which call a function (in no particular order)
+    pub fn callers(&self, id: FunDeclId::Id) -> impl Iterator<Item = FunDeclId::Id> + '_ {
+        self.graph.neighbors_directed(id, Direction::Incoming)
+    }
+
+    /// The strongly connected components of the graph, that is the groups of
+    /// mutually recursive functions. The callees come before their callers,
+    /// and the functions of a group are sorted by identifier.
+    pub fn sccs(&self) -> impl Iterator<Item = Vec<FunDeclId::Id>> {
+        tarjan_scc(&self.graph).into_iter().map(|mut scc| {
+            scc.sort();
+            scc
+        })
+    }
+
+    /// The functions which are (directly or mutually) recursive
+    pub fn recursive_functions(&self) -> HashSet<FunDeclId::Id> {
+        self.sccs()
+            .filter(|scc| scc.len() > 1 || self.graph.contains_edge(scc[0], scc[0]))
+            .flatten()
+            .collect()
+    }
+
+    /// The functions, sorted by identifier
+    fn sorted_functions(&self) -> Vec<FunDeclId::Id> {
+        let mut ids: Vec<FunDeclId::Id> = self.graph.nodes().collect();
+        ids.sort();
+        ids
+    }
+
+    fn sorted_callees(&self, id: FunDeclId::Id) -> Vec<FunDeclId::Id> {
+        let mut callees: Vec<FunDeclId::Id> = self.callees(id).collect();
+        callees.sort();
+        callees
+    }
+
+    /// Generate the graph in the Graphviz DOT format. The recursive functions
+    /// are highlighted.
+    pub fn to_dot(&self, name: &str) -> String {
+        let recursive = self.recursive_functions();
+        let mut out = format!("digraph \"{}\" {{\n", name.replace('"', "\\\""));
+        out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+        for id in self.sorted_functions() {
+            let label = self.names.get(&id).map_or("", |n| n.as_str());
+            let style = if recursive.contains(&id) {
+                ", color=red"
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "  f{id} [label=\"{}\"{style}];\n",
+                label.replace('"', "\\\"")
+            ));
+        }
+        for id in self.sorted_functions() {
+            for callee in self.sorted_callees(id) {
+                out.push_str(&format!("  f{id} -> f{callee};\n"));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+impl Default for CallGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An exported node of the graph
+#[derive(Serialize)]
+struct Node<'a> {
+    id: FunDeclId::Id,
+    name: &'a str,
+    callees: Vec<FunDeclId::Id>,
+}
+
+/// We serialize the graph as the list of its functions (sorted by
+/// identifier), with their callees.
+impl Serialize for CallGraph {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let ids = self.sorted_functions();
+        let mut seq = serializer.serialize_seq(Some(ids.len()))?;
+        for id in ids {
+            seq.serialize_element(&Node {
+                id,
+                name: self.names.get(&id).map_or("", |n| n.as_str()),
+                callees: self.sorted_callees(id),
+            })?;
+        }
+        seq.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::callgraph::*;
+    use crate::expressions::Place;
+    use crate::llbc_ast::RawStatement;
+    use crate::test_utils::*;
+    use crate::values::VarId;
+
+    fn call(callee: FunDeclId::Id) -> RawStatement {
+        RawStatement::Call(mk_call(
+            FunId::Regular(callee),
+            vec![],
+            Place::new(VarId::ZERO),
+        ))
+    }
+
+    #[test]
+    fn test_call_graph() {
+        let f = FunDeclId::Id::new(0);
+        let g = FunDeclId::Id::new(1);
+        let h = FunDeclId::Id::new(2);
+        let k = FunDeclId::Id::new(3);
+
+        // `f` calls `g` and `h`, `g` calls `f`, and `k` calls itself
+        let mut graph = CallGraph::new();
+        for (id, name) in [(f, "f"), (g, "g"), (h, "h"), (k, "k")] {
+            graph.add_function(id, name.to_string());
+        }
+        let ret = RawStatement::Return;
+        graph.add_statement_calls(f, &mk_statements(vec![call(g), call(h), ret.clone()]));
+        graph.add_statement_calls(g, &mk_statements(vec![call(f), ret.clone()]));
+        graph.add_statement_calls(k, &mk_statements(vec![call(k), ret]));
+
+        assert!(graph.sorted_callees(f) == vec![g, h]);
+        assert!(graph.callers(h).collect::<Vec<_>>() == vec![f]);
+        assert!(graph.callees(h).next().is_none());
+
+        // The callees come first
+        let sccs: Vec<Vec<FunDeclId::Id>> = graph.sccs().collect();
+        let pos = |id| sccs.iter().position(|scc| scc.contains(&id)).unwrap();
+        assert!(sccs.contains(&vec![f, g]));
+        assert!(pos(h) < pos(f));
+        assert!(graph.recursive_functions() == HashSet::from([f, g, k]));
+
+        let dot = graph.to_dot("test");
+        assert!(dot.contains("f0 -> f1;\n  f0 -> f2;\n  f1 -> f0;\n  f3 -> f3;\n"));
+        assert!(dot.contains("f2 [label=\"h\"];"));
+        assert!(dot.contains("f3 [label=\"k\", color=red];"));
+
+        let json = serde_json::to_string(&graph).unwrap();
+        assert!(json.starts_with("[{\"id\":0,\"name\":\"f\",\"callees\":[1,2]}"));
+    }
+}
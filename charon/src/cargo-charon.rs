@@ -0,0 +1,29 @@
+//! The `cargo charon` subcommand.
+//!
+//! Cargo calls `cargo-charon` with the name of the subcommand as first
+//! argument (`cargo charon --workspace` calls `cargo-charon charon
+//! --workspace`): we remove it and forward the remaining arguments to the
+//! `charon` executable, which lives in the same directory.
+
+use std::env;
+use std::process::Command;
+
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(|arg| arg.as_str()) == Some("charon") {
+        args.next();
+    }
+
+    let mut path = env::current_exe()
+        .expect("current executable path invalid")
+        .with_file_name("charon");
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+
+    let exit_status = Command::new(path)
+        .args(args)
+        .status()
+        .expect("could not run charon");
+    std::process::exit(exit_status.code().unwrap_or(-1));
+}
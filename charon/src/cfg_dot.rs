@@ -0,0 +1,186 @@
+//! Dump the control-flow graphs of the ULLBC bodies in the Graphviz DOT
+//! format (see the `--dump-cfg` option). This is mostly useful to debug the
+//! control-flow reconstruction (see [crate::ullbc_to_llbc]): the graphs can be
+//! rendered with, e.g., `dot -Tsvg f.dot -o f.svg`.
+//!
+//! We generate one file per function with a body, in the directory
+//! `{crate}.cfg`. Every block is a node, labeled with its statements and its
+//! terminator, and the edges are annotated with the kind of the transition
+//! (`goto`, the values of a switch, etc.).
+
+use crate::common::*;
+use crate::gast_utils::{FunNamesFormatter, GlobalNamesFormatter};
+use crate::ullbc_ast::{
+    BlockId, CtxNames, ExprBody, FunDecls, GAstFormatter, RawTerminator, SwitchTargets,
+};
+use std::path::PathBuf;
+
+/// Escape a line of a label. We left-justify the lines with `\l`.
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\l"),
+            _ => out.push(c),
+        }
+    }
+    out.push_str("\\l");
+    out
+}
+
+/// The outgoing edges of a block, with their labels
+fn edges(terminator: &RawTerminator) -> Vec<(BlockId::Id, String)> {
+    match terminator {
+        RawTerminator::Goto { target } => vec![(*target, "goto".to_string())],
+        RawTerminator::Switch { targets, .. } => match targets {
+            SwitchTargets::If(then_block, else_block) => vec![
+                (*then_block, "true".to_string()),
+                (*else_block, "false".to_string()),
+            ],
+            SwitchTargets::SwitchInt(_, maps, otherwise) => maps
+                .iter()
+                .map(|(v, bid)| (*bid, v.to_string()))
+                .chain(std::iter::once((*otherwise, "otherwise".to_string())))
+                .collect(),
+        },
+        RawTerminator::Panic | RawTerminator::Return | RawTerminator::Unreachable => Vec::new(),
+        RawTerminator::Drop { target, .. } => vec![(*target, "drop".to_string())],
+        RawTerminator::Call { target, .. } => vec![(*target, "call".to_string())],
+        RawTerminator::Assert { target, .. } => vec![(*target, "assert".to_string())],
+    }
+}
+
+/// Generate the DOT graph of a body. `name` is the name of the graph.
+pub fn body_to_dot(ctx: &CtxNames<'_>, name: &str, body: &ExprBody) -> String {
+    let fun_ctx = FunNamesFormatter::new(ctx.fun_context);
+    let global_ctx = GlobalNamesFormatter::new(ctx.global_context);
+    let fmt_ctx = GAstFormatter::new(
+        ctx.type_context,
+        &fun_ctx,
+        &global_ctx,
+        None,
+        Some(&body.locals),
+    );
+
+    let mut out = format!("digraph \"{}\" {{\n", name.replace('"', "\\\""));
+    out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+    for (bid, block) in body.body.iter_indexed_values() {
+        let mut label = escape(&format!("bb{bid}:"));
+        for st in &block.statements {
+            label.push_str(&escape(&st.fmt_with_ctx(&fmt_ctx)));
+        }
+        label.push_str(&escape(&block.terminator.fmt_with_ctx(&fmt_ctx)));
+        // Highlight the exits of the function
+        let style = match block.terminator.content {
+            RawTerminator::Return => ", peripheries=2",
+            RawTerminator::Panic | RawTerminator::Unreachable => ", color=red",
+            _ => "",
+        };
+        out.push_str(&format!("  bb{bid} [label=\"{label}\"{style}];\n"));
+        for (target, edge_label) in edges(&block.terminator.content) {
+            out.push_str(&format!(
+                "  bb{bid} -> bb{target} [label=\"{}\"];\n",
+                edge_label.replace('"', "\\\"")
+            ));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Turn a name into something we can use in a file name
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Write the DOT files of the function bodies to `{dest_dir}/{crate}.cfg`.
+/// The files are prefixed with the identifiers of the functions, because
+/// several functions may have the same (sanitized) name.
+pub fn dump(
+    ctx: &CtxNames<'_>,
+    crate_name: &str,
+    dest_dir: &Option<PathBuf>,
+    funs: &FunDecls,
+) -> Result<()> {
+    let mut dir = dest_dir
+        .as_deref()
+        .map_or_else(PathBuf::new, |d| d.to_path_buf());
+    dir.push(format!("{crate_name}.cfg"));
+    if std::fs::create_dir_all(&dir).is_err() {
+        error!("Could not create the directory: {:?}", dir);
+        return Err(());
+    }
+
+    // The opaque functions don't have bodies: we skip them
+    for f in funs.iter().filter(|f| f.body.is_some()) {
+        let body = f.body.as_ref().unwrap();
+        let name = f.name.to_string();
+        let dot = body_to_dot(ctx, &name, body);
+        let filename = dir.join(format!("{}_{}.dot", f.def_id, sanitize(&name)));
+        if std::fs::write(&filename, dot).is_err() {
+            error!("Could not write to {:?}", filename);
+            return Err(());
+        }
+    }
+    info!("Generated the control-flow graphs in: {:?}", dir);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cfg_dot::*;
+    use crate::expressions::*;
+    use crate::test_utils::*;
+    use crate::types::*;
+    use crate::ullbc_ast::{FunDeclId, GlobalDeclId};
+    use crate::values::*;
+
+    #[test]
+    fn test_body_to_dot() {
+        // `bb0: if move x -> bb1 else -> bb2; bb1: return; bb2: panic`
+        let x = VarId::Id::new(1);
+        let locals = vec![
+            mk_var(0, None, Ty::Integer(IntegerTy::U32)),
+            mk_var(1, Some("x"), Ty::Bool),
+        ];
+        let bb1 = BlockId::Id::new(1);
+        let bb2 = BlockId::Id::new(2);
+        let body = mk_cfg(
+            1,
+            locals,
+            vec![
+                mk_block(
+                    vec![],
+                    RawTerminator::Switch {
+                        discr: Operand::Move(Place::new(x)),
+                        targets: SwitchTargets::If(bb1, bb2),
+                    },
+                ),
+                mk_block(vec![], RawTerminator::Return),
+                mk_block(vec![], RawTerminator::Panic),
+            ],
+        );
+
+        let type_context = TypeDecls::new();
+        let fun_context = FunDeclId::Vector::new();
+        let global_context = GlobalDeclId::Vector::new();
+        let ctx = CtxNames::new(&type_context, &fun_context, &global_context);
+        let dot = body_to_dot(&ctx, "f", &body);
+        assert!(dot.starts_with("digraph \"f\" {"));
+        assert!(dot.contains("bb0 -> bb1 [label=\"true\"];"));
+        assert!(dot.contains("bb0 -> bb2 [label=\"false\"];"));
+        assert!(dot.contains("peripheries=2"));
+        // The exits don't have successors
+        assert!(!dot.contains("bb1 ->") && !dot.contains("bb2 ->"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert!(escape("a := \"b\"") == "a := \\\"b\\\"\\l");
+        assert!(sanitize("core::option::Option<T>") == "core__option__Option_T_");
+    }
+}
@@ -34,9 +34,30 @@ extern crate take_mut;
 #[macro_use]
 mod common;
 mod assumed;
+mod call_index;
+mod call_signatures;
+mod callgraph;
+mod cfg_dot;
+mod check;
+mod check_calls;
+mod check_cfg;
+mod check_places;
+mod dominators;
+mod collapse_deref_ref;
+mod common_subexpressions;
+mod complete_switches;
+mod compound_assign;
+mod config;
+mod contracts;
+mod decls_index;
+mod diagnostics;
+mod closures;
 mod cli_options;
 mod divergent;
 mod driver;
+mod erase_phantoms;
+mod errors;
+mod expand_box_drops;
 mod export;
 mod expressions;
 mod expressions_utils;
@@ -47,26 +68,59 @@ mod gast_utils;
 mod generics;
 mod get_mir;
 mod graphs;
+mod html_report;
 mod id_vector;
+mod if_let_chains;
+mod incremental;
+mod index_accesses;
+mod inline_functions;
 mod insert_assign_return_unit;
+mod insert_overflow_checks;
+mod interp;
+mod json_schema;
+mod let_else;
 mod llbc_ast;
 mod llbc_ast_utils;
+mod llbc_builder;
+#[cfg(test)]
+mod llbc_gen;
+mod llbc_visitor;
 mod logger;
+mod match_bindings;
+mod match_provenance;
 mod meta;
 mod meta_utils;
+mod monomorphize;
 mod names;
 mod names_utils;
+mod packed_fields;
+mod passes;
+mod propagate_copies;
+mod range_loops;
+mod reaching_defs;
+mod recognize_matches;
+mod recursive_types;
 mod reconstruct_asserts;
+mod reconstruct_try;
 mod regions_hierarchy;
 mod register;
 mod regularize_constant_adts;
 mod remove_drop_never;
+mod remove_fake_reads;
 mod remove_read_discriminant;
+mod remove_redundant_set_discriminant;
 mod remove_unused_locals;
+mod relocate_decls;
 mod reorder_decls;
 mod rust_to_local_ids;
 mod simplify_ops;
+mod single_exit;
+mod slice_accesses;
+#[cfg(test)]
+mod test_utils;
+mod tool_attributes;
 mod translate_functions_to_ullbc;
+mod translate_traits;
 mod translate_types;
 mod types;
 mod types_utils;
@@ -75,6 +129,7 @@ mod ullbc_ast_utils;
 mod ullbc_to_llbc;
 mod values;
 mod values_utils;
+mod while_loops;
 
 use crate::driver::{arg_value, get_args_crate_index, get_args_source_index, CharonCallbacks};
 use rustc_driver::RunCompiler;
@@ -135,6 +190,8 @@ fn main() {
     if options.use_polonius {
         compiler_args.push("-Zpolonius".to_string());
     }
+    // Register the `charon` tool, to allow the `#[charon::...]` attributes
+    compiler_args.extend(tool_attributes::register_tool_args());
 
     // In order to have some flexibility in our tests, we give the possibility
     // of specifying the source (the input file which gives the entry to the
@@ -190,17 +247,35 @@ fn main() {
 
     trace!("Compiler arguments: {:?}", compiler_args);
 
-    // Call the Rust compiler with our custom callback.
     // When we use RUSTC_WRAPPER_WORKSPACE to call charon-driver while piggy-backing
-    // on Cargo, the charon-driver is only called on the target (and not its
-    // dependencies).
+    // on Cargo, the charon-driver is only called on the members of the
+    // workspace. However, when the target depends on other members of the
+    // workspace (see the `--workspace` option), Cargo also calls us to build
+    // them: in this case we simply act as Rustc (our callback stops the
+    // compilation, and Cargo needs the compiled dependencies). Cargo sets
+    // `CARGO_PRIMARY_PACKAGE` for the packages selected on the command line.
+    if std::env::var("CARGO_PKG_NAME").is_ok() && std::env::var("CARGO_PRIMARY_PACKAGE").is_err() {
+        trace!("Not a primary package: calling Rustc");
+        RunCompiler::new(&compiler_args, &mut rustc_driver::TimePassesCallbacks::default())
+            .run()
+            .unwrap();
+        return;
+    }
+
+    // Call the Rust compiler with our custom callback.
     //
     // Note that the first call to the driver is with "--crate-name ___" and no
     // source file, for Cargo to retrieve some information about the crate.
     // We don't need to check this case in order to use the default Rustc callbacks
     // instead of the Charon callback: because there is nothing to build, Rustc will
     // take care of everything and actually not call us back.
-    RunCompiler::new(&compiler_args, &mut CharonCallbacks { options })
-        .run()
-        .unwrap();
+    RunCompiler::new(
+        &compiler_args,
+        &mut CharonCallbacks {
+            options,
+            passes: Vec::new(),
+        },
+    )
+    .run()
+    .unwrap();
 }
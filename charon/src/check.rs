@@ -0,0 +1,758 @@
+//! A type checker for the LLBC bodies (see the `--check` option).
+//!
+//! The micro-passes rewrite the bodies in place, and a bug in a pass often
+//! produces code which is still well-formed as a tree, but ill-typed: an
+//! assignment of a `bool` to a `u32`, a projection through a field which
+//! doesn't exist, a call with a missing argument, etc. With `--check`, we
+//! type-check the bodies after every pass of [crate::passes::PassPipeline],
+//! so that we can find out which pass introduced the error.
+//!
+//! We check that:
+//! - the places project through existing fields, with the proper kinds of
+//!   projections
+//! - the types of the assigned values match the types of the destinations
+//! - the numbers (and types) of the arguments of the calls match the
+//!   signatures of the callees
+//! - the types of the scrutinees of the switches match their branches
+//! - the sequences are not left-nested (see [crate::llbc_ast::chain_statements])
+//!
+//! We ignore the regions, which are erased in the bodies. The check is
+//! conservative: we don't compute the types of some rvalues (the closures,
+//! the slices, etc.), and we then don't check the assignments.
+#![allow(dead_code)]
+
+use crate::assumed;
+use crate::errors::{DeclErrors, TransResult, TranslationError};
+use crate::expressions::*;
+use crate::id_vector::ToUsize;
+use crate::llbc_ast::*;
+use crate::llbc_visitor::{walk_statement, AstVisitor};
+use crate::meta::Meta;
+use crate::types::*;
+use crate::ullbc_ast::FunId;
+use crate::values::*;
+
+/// Compare two types, up to the regions. `!` is compatible with any type
+/// (the code which manipulates a value of type `!` is dead), and so are the
+/// opaque types we know nothing about.
+pub fn same_type(ty1: &ETy, ty2: &ETy) -> bool {
+    let same_types = |tys1: &im::Vector<ETy>, tys2: &im::Vector<ETy>| {
+        tys1.len() == tys2.len()
+            && tys1
+                .iter()
+                .zip(tys2.iter())
+                .all(|(t1, t2)| same_type(t1, t2))
+    };
+    match (ty1, ty2) {
+        (Ty::Never, _) | (_, Ty::Never) => true,
+        (Ty::Opaque(None, _), _) | (_, Ty::Opaque(None, _)) => true,
+        (Ty::Opaque(Some(ty1), _), ty2) | (ty2, Ty::Opaque(Some(ty1), _)) => same_type(ty1, ty2),
+        (Ty::Adt(id1, _, tys1), Ty::Adt(id2, _, tys2)) => id1 == id2 && same_types(tys1, tys2),
+        (Ty::Array(ty1, _), Ty::Array(ty2, _)) | (Ty::Slice(ty1), Ty::Slice(ty2)) => {
+            same_type(ty1, ty2)
+        }
+        (Ty::Ref(_, ty1, kind1), Ty::Ref(_, ty2, kind2))
+        | (Ty::RawPtr(ty1, kind1), Ty::RawPtr(ty2, kind2)) => kind1 == kind2 && same_type(ty1, ty2),
+        (Ty::FnPtr(inputs1, output1), Ty::FnPtr(inputs2, output2)) => {
+            same_types(inputs1, inputs2) && same_type(output1, output2)
+        }
+        _ => ty1 == ty2,
+    }
+}
+
+fn check_same_type(what: &str, expected: &ETy, actual: &ETy) -> Result<(), String> {
+    if same_type(expected, actual) {
+        Ok(())
+    } else {
+        Err(format!(
+            "ill-typed {what}: expected a value of type {}, got a value of type {}",
+            expected.to_string(),
+            actual.to_string()
+        ))
+    }
+}
+
+fn can_overflow(binop: BinOp) -> bool {
+    matches!(
+        binop,
+        BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Shl | BinOp::Shr
+    )
+}
+
+fn mk_adt(id: TypeId, tys: im::Vector<ETy>) -> ETy {
+    Ty::Adt(id, im::Vector::new(), tys)
+}
+
+/// The type checker of a body. We only remember the first error.
+struct Checker<'a> {
+    type_defs: &'a TypeDecls,
+    funs: &'a FunDecls,
+    globals: &'a GlobalDecls,
+    locals: &'a VarId::Vector<Var>,
+    /// The meta data of the statement we are checking
+    meta: Meta,
+    error: Option<TranslationError>,
+}
+
+impl<'a> Checker<'a> {
+    fn fail(&mut self, msg: String) {
+        if self.error.is_none() {
+            self.error = Some(TranslationError::new(self.meta, msg));
+        }
+    }
+
+    /// Retrieve the definition of an ADT, which must not be opaque
+    fn type_decl(&self, id: TypeDeclId::Id) -> Result<&'a TypeDecl, String> {
+        match self.type_defs.get_type_def(id) {
+            Some(decl) if !decl.kind.is_opaque() => Ok(decl),
+            Some(_) => Err(format!("projection over the opaque type @Adt{id}")),
+            None => Err(format!("unknown type @Adt{id}")),
+        }
+    }
+
+    /// The fields of a variant of an ADT (or of the ADT itself, if it is a
+    /// structure or a union)
+    fn fields(
+        &self,
+        decl: &'a TypeDecl,
+        variant_id: Option<VariantId::Id>,
+    ) -> Result<&'a FieldId::Vector<Field>, String> {
+        match (&decl.kind, variant_id) {
+            (TypeDeclKind::Struct(fields) | TypeDeclKind::Union(fields), None) => Ok(fields),
+            (TypeDeclKind::Enum(variants), Some(variant_id)) => match variants.get(variant_id) {
+                Some(variant) => Ok(&variant.fields),
+                None => Err(format!(
+                    "unknown variant {variant_id} of @Adt{}",
+                    decl.def_id
+                )),
+            },
+            _ => Err(format!(
+                "invalid variant {:?} for the type @Adt{}",
+                variant_id, decl.def_id
+            )),
+        }
+    }
+
+    /// The type of a field of an ADT, instantiated with the arguments `tys`
+    fn field_type(
+        &self,
+        decl: &'a TypeDecl,
+        variant_id: Option<VariantId::Id>,
+        tys: &im::Vector<ETy>,
+        field_id: FieldId::Id,
+    ) -> Result<ETy, String> {
+        let num_params = decl.type_params.len() + decl.const_generic_params.len();
+        if tys.len() != num_params {
+            return Err(format!(
+                "the type @Adt{} has {num_params} parameter(s), but is given {} argument(s)",
+                decl.def_id,
+                tys.len()
+            ));
+        }
+        if self.fields(decl, variant_id)?.get(field_id).is_none() {
+            return Err(format!("unknown field {field_id} of @Adt{}", decl.def_id));
+        }
+        Ok(decl.get_erased_regions_instantiated_field_type(variant_id, tys, field_id))
+    }
+
+    /// Compute the type of a place. This is [crate::gast::GExprBody::place_type]
+    /// (see the comments there), but returning errors instead of panicking.
+    fn place_type(&self, p: &Place) -> Result<ETy, String> {
+        let mut ty = match self.locals.get(p.var_id) {
+            Some(var) => var.ty.clone(),
+            None => return Err(format!("unknown local {}", p.var_id)),
+        };
+        let mut projection = p.projection.iter().peekable();
+        while let Some(pe) = projection.next() {
+            let ill_typed = |ty: &ETy| {
+                format!(
+                    "ill-typed projection: {:?} over a value of type {}",
+                    pe,
+                    ty.to_string()
+                )
+            };
+            ty = match (pe, ty) {
+                (_, Ty::Never) => Ty::Never,
+                (ProjectionElem::Deref, Ty::Ref(_, ty, _))
+                | (ProjectionElem::DerefRawPtr, Ty::RawPtr(ty, _)) => *ty,
+                (
+                    ProjectionElem::DerefBox,
+                    Ty::Adt(TypeId::Assumed(AssumedTy::Box), _, mut tys),
+                ) if tys.len() == 1 => {
+                    let ty = tys.pop_front().unwrap();
+                    if projection.peek() == Some(&&ProjectionElem::DerefPtrUnique) {
+                        mk_adt(TypeId::Assumed(AssumedTy::PtrUnique), im::vector![ty])
+                    } else {
+                        ty
+                    }
+                }
+                (
+                    ProjectionElem::DerefPtrUnique,
+                    Ty::Adt(TypeId::Assumed(AssumedTy::PtrUnique), _, tys),
+                ) => mk_adt(TypeId::Assumed(AssumedTy::PtrNonNull), tys),
+                (
+                    ProjectionElem::DerefPtrNonNull,
+                    Ty::Adt(TypeId::Assumed(AssumedTy::PtrNonNull), _, mut tys),
+                ) if tys.len() == 1 => {
+                    Ty::RawPtr(Box::new(tys.pop_front().unwrap()), RefKind::Shared)
+                }
+                (
+                    ProjectionElem::DerefRc,
+                    Ty::Adt(TypeId::Assumed(AssumedTy::Rc | AssumedTy::Arc), _, mut tys),
+                ) if tys.len() == 1 => tys.pop_front().unwrap(),
+                (
+                    ProjectionElem::Field(FieldProjKind::Adt(type_id, variant_id), field_id),
+                    Ty::Adt(TypeId::Adt(id), _, tys),
+                ) if id == *type_id => {
+                    let decl = self.type_decl(id)?;
+                    if decl.kind.is_union() {
+                        return Err(format!("projection over the union @Adt{id} as a structure"));
+                    }
+                    self.field_type(decl, *variant_id, &tys, *field_id)?
+                }
+                (
+                    ProjectionElem::Field(FieldProjKind::Union(type_id), field_id),
+                    Ty::Adt(TypeId::Adt(id), _, tys),
+                ) if id == *type_id => {
+                    let decl = self.type_decl(id)?;
+                    if !decl.kind.is_union() {
+                        return Err(format!("projection over @Adt{id} as a union"));
+                    }
+                    self.field_type(decl, None, &tys, *field_id)?
+                }
+                (
+                    ProjectionElem::Field(FieldProjKind::Tuple(arity), field_id),
+                    Ty::Adt(TypeId::Tuple, _, tys),
+                ) if *arity == tys.len() && field_id.to_usize() < tys.len() => {
+                    tys.get(field_id.to_usize()).unwrap().clone()
+                }
+                (
+                    ProjectionElem::Field(FieldProjKind::Option(variant_id), field_id),
+                    Ty::Adt(TypeId::Assumed(AssumedTy::Option), _, mut tys),
+                ) if *variant_id == assumed::OPTION_SOME_VARIANT_ID
+                    && field_id.to_usize() == 0
+                    && tys.len() == 1 =>
+                {
+                    tys.pop_front().unwrap()
+                }
+                (
+                    ProjectionElem::Field(FieldProjKind::ControlFlow(variant_id), field_id),
+                    Ty::Adt(TypeId::Assumed(AssumedTy::ControlFlow), _, mut tys),
+                ) if variant_id.to_usize() < 2 && field_id.to_usize() == 0 && tys.len() == 2 => {
+                    if *variant_id == assumed::CONTROL_FLOW_CONTINUE_VARIANT_ID {
+                        tys.pop_back().unwrap()
+                    } else {
+                        tys.pop_front().unwrap()
+                    }
+                }
+                (ProjectionElem::Index(_), Ty::Array(ty, _) | Ty::Slice(ty))
+                | (ProjectionElem::ConstantIndex { .. }, Ty::Array(ty, _) | Ty::Slice(ty)) => *ty,
+                (
+                    ProjectionElem::Index(_),
+                    Ty::Adt(TypeId::Assumed(AssumedTy::Vec), _, mut tys),
+                ) if tys.len() == 1 => tys.pop_front().unwrap(),
+                (_, ty) => return Err(ill_typed(&ty)),
+            };
+            if let ProjectionElem::Index(i) = pe {
+                let i = Place::new(*i);
+                check_same_type(
+                    "index",
+                    &Ty::Integer(IntegerTy::Usize),
+                    &self.place_type(&i)?,
+                )?;
+            }
+        }
+        Ok(ty)
+    }
+
+    fn operand_type(&self, op: &Operand) -> Result<ETy, String> {
+        match op {
+            Operand::Copy(p) | Operand::Move(p) => self.place_type(p),
+            Operand::Const(ty, _) => Ok(ty.clone()),
+        }
+    }
+
+    fn binop_type(&self, binop: BinOp, op1: &Operand, op2: &Operand) -> Result<ETy, String> {
+        let ty1 = self.operand_type(op1)?;
+        let ty2 = self.operand_type(op2)?;
+        match binop {
+            // The shifts and the offsets have operands of different types
+            BinOp::Shl | BinOp::Shr | BinOp::Offset => Ok(ty1),
+            BinOp::Eq | BinOp::Lt | BinOp::Le | BinOp::Ne | BinOp::Ge | BinOp::Gt => {
+                check_same_type("comparison", &ty1, &ty2)?;
+                Ok(Ty::Bool)
+            }
+            _ => {
+                check_same_type("binary operation", &ty1, &ty2)?;
+                Ok(ty1)
+            }
+        }
+    }
+
+    /// Compute the type of an rvalue, if we know how to
+    fn rvalue_type(&self, rv: &Rvalue) -> Result<Option<ETy>, String> {
+        let ty = match rv {
+            Rvalue::Use(op) | Rvalue::UnaryOp(_, op) => self.operand_type(op)?,
+            Rvalue::BinaryOp(binop, op1, op2) => self.binop_type(*binop, op1, op2)?,
+            Rvalue::Ref(p, kind) => {
+                let kind = match kind {
+                    BorrowKind::Shared | BorrowKind::Shallow => RefKind::Shared,
+                    BorrowKind::Mut | BorrowKind::TwoPhaseMut => RefKind::Mut,
+                };
+                Ty::Ref(ErasedRegion::Erased, Box::new(self.place_type(p)?), kind)
+            }
+            Rvalue::RawPtr(p, kind) => Ty::RawPtr(Box::new(self.place_type(p)?), *kind),
+            Rvalue::Len(p) => match self.place_type(p)? {
+                Ty::Array(..) | Ty::Slice(_) | Ty::Never => Ty::Integer(IntegerTy::Usize),
+                Ty::Adt(TypeId::Assumed(AssumedTy::Vec), _, _) => Ty::Integer(IntegerTy::Usize),
+                ty => return Err(format!("length of a value of type {}", ty.to_string())),
+            },
+            Rvalue::IsVariant(p, variants) => {
+                self.check_variants(&self.place_type(p)?, variants)?;
+                Ty::Bool
+            }
+            Rvalue::Aggregate(kind, ops) => return self.aggregate_type(kind, ops),
+            Rvalue::Global(id) => match self.globals.get(*id) {
+                Some(global) => global.ty.clone(),
+                None => return Err(format!("unknown global @Global{id}")),
+            },
+            Rvalue::Unsize(op, src, tgt) => {
+                check_same_type("unsizing coercion", src, &self.operand_type(op)?)?;
+                tgt.clone()
+            }
+            Rvalue::Cast(_, op, src, tgt) => {
+                check_same_type("cast", src, &self.operand_type(op)?)?;
+                tgt.clone()
+            }
+            Rvalue::Discriminant(_)
+            | Rvalue::GlobalRef(_, _)
+            | Rvalue::Slice { .. }
+            | Rvalue::FnPtr(_) => return Ok(None),
+        };
+        Ok(Some(ty))
+    }
+
+    fn aggregate_type(&self, kind: &AggregateKind, ops: &[Operand]) -> Result<Option<ETy>, String> {
+        let op_tys = ops
+            .iter()
+            .map(|op| self.operand_type(op))
+            .collect::<Result<Vec<ETy>, String>>()?;
+        match kind {
+            AggregateKind::Tuple => Ok(Some(mk_adt(TypeId::Tuple, op_tys.into_iter().collect()))),
+            AggregateKind::Option(variant_id, ty) => {
+                let num_fields = if *variant_id == assumed::OPTION_SOME_VARIANT_ID {
+                    1
+                } else {
+                    0
+                };
+                if op_tys.len() != num_fields {
+                    return Err(format!(
+                        "option variant {variant_id} with {} field(s)",
+                        ops.len()
+                    ));
+                }
+                for op_ty in &op_tys {
+                    check_same_type("option field", ty, op_ty)?;
+                }
+                let tys = im::vector![ty.clone()];
+                Ok(Some(mk_adt(TypeId::Assumed(AssumedTy::Option), tys)))
+            }
+            AggregateKind::Adt(id, variant_id, _, tys) => {
+                let decl = self.type_decl(*id)?;
+                let tys: im::Vector<ETy> = tys.iter().cloned().collect();
+                // The aggregates of unions only initialize one field
+                let num_fields = if decl.kind.is_union() {
+                    1
+                } else {
+                    self.fields(decl, *variant_id)?.len()
+                };
+                if op_tys.len() != num_fields {
+                    return Err(format!(
+                        "aggregate of @Adt{id} with {} field(s), expected {num_fields}",
+                        ops.len()
+                    ));
+                }
+                if !decl.kind.is_union() {
+                    for (i, op_ty) in op_tys.iter().enumerate() {
+                        let field_ty =
+                            self.field_type(decl, *variant_id, &tys, FieldId::Id::new(i))?;
+                        check_same_type("field", &field_ty, op_ty)?;
+                    }
+                }
+                Ok(Some(mk_adt(TypeId::Adt(*id), tys)))
+            }
+            AggregateKind::Closure(..) => Ok(None),
+        }
+    }
+
+    /// Check that the variants are valid for a value of type `ty`, which must
+    /// be an enumeration
+    fn check_variants(&self, ty: &ETy, variants: &[VariantId::Id]) -> Result<(), String> {
+        let num_variants = match ty {
+            Ty::Never => return Ok(()),
+            Ty::Adt(TypeId::Adt(id), _, _) => match &self.type_decl(*id)?.kind {
+                TypeDeclKind::Enum(variants) => variants.len(),
+                _ => return Err(format!("the type @Adt{id} is not an enumeration")),
+            },
+            Ty::Adt(TypeId::Assumed(AssumedTy::Option | AssumedTy::ControlFlow), _, _) => 2,
+            ty => return Err(format!("the type {} is not an enumeration", ty.to_string())),
+        };
+        match variants.iter().find(|id| id.to_usize() >= num_variants) {
+            Some(id) => Err(format!(
+                "unknown variant {id} of the type {}",
+                ty.to_string()
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn check_call(&self, call: &Call) -> Result<(), String> {
+        let arg_tys = call
+            .args
+            .iter()
+            .map(|op| self.operand_type(op))
+            .collect::<Result<Vec<ETy>, String>>()?;
+        let dest_ty = self.place_type(&call.dest)?;
+        let (callee, sig) = match &call.func {
+            FunId::Regular(id) | FunId::TraitMethod(_, id) | FunId::Virtual(_, id) => {
+                match self.funs.get(*id) {
+                    Some(decl) => (format!("@Fun{id}"), &decl.signature),
+                    None => return Err(format!("call to the unknown function @Fun{id}")),
+                }
+            }
+            FunId::Indirect(op) => {
+                return match self.operand_type(op)? {
+                    Ty::FnPtr(inputs, output) => {
+                        if inputs.len() != arg_tys.len() {
+                            return Err(format!(
+                                "indirect call with {} argument(s), expected {}",
+                                arg_tys.len(),
+                                inputs.len()
+                            ));
+                        }
+                        for (input, arg_ty) in inputs.iter().zip(arg_tys.iter()) {
+                            check_same_type("argument", input, arg_ty)?;
+                        }
+                        check_same_type("call destination", &dest_ty, &output)
+                    }
+                    Ty::Never => Ok(()),
+                    ty => Err(format!("call through a value of type {}", ty.to_string())),
+                };
+            }
+            FunId::Assumed(_) => return Ok(()),
+        };
+        if sig.inputs.len() != call.args.len() {
+            return Err(format!(
+                "call to {callee} with {} argument(s), expected {}",
+                call.args.len(),
+                sig.inputs.len()
+            ));
+        }
+        // The calls to the trait methods may have more arguments than the
+        // generics of the function: we only check the types when the numbers
+        // match
+        if call.type_args.len() != sig.type_params.len() + sig.const_generic_params.len() {
+            return Ok(());
+        }
+        let type_args: im::Vector<ETy> = call.type_args.iter().cloned().collect();
+        let (tys, cgs) = split_const_generic_args(&type_args);
+        let ty_subst = make_type_subst(sig.type_params.iter().map(|x| x.index), tys.iter());
+        let cg_subst =
+            make_const_generic_subst(sig.const_generic_params.iter().map(|x| x.index), cgs.iter());
+        for (input, arg_ty) in sig.inputs.iter().zip(arg_tys.iter()) {
+            let input = input.erase_regions_substitute_types(&ty_subst, &cg_subst);
+            check_same_type("argument", &input, arg_ty)?;
+        }
+        let output = sig
+            .output
+            .erase_regions_substitute_types(&ty_subst, &cg_subst);
+        check_same_type("call destination", &dest_ty, &output)
+    }
+
+    fn check_switch(&self, switch: &Switch) -> Result<(), String> {
+        match switch {
+            Switch::If(op, _, _) => {
+                check_same_type("condition", &Ty::Bool, &self.operand_type(op)?)
+            }
+            Switch::SwitchInt(op, int_ty, targets, _) => {
+                check_same_type("scrutinee", &Ty::Integer(*int_ty), &self.operand_type(op)?)?;
+                for v in targets.iter().flat_map(|(values, _)| values.iter()) {
+                    if v.get_integer_ty() != *int_ty {
+                        return Err(format!(
+                            "the value {} of a switch over a value of type {}",
+                            v.to_string(),
+                            Ty::<ErasedRegion>::Integer(*int_ty).to_string()
+                        ));
+                    }
+                }
+                Ok(())
+            }
+            Switch::Match(p, targets, _, _) => {
+                let variants: Vec<VariantId::Id> = targets
+                    .iter()
+                    .flat_map(|(ids, _)| ids.iter().copied())
+                    .collect();
+                self.check_variants(&self.place_type(p)?, &variants)
+            }
+        }
+    }
+
+    fn check_statement(&self, st: &Statement) -> Result<(), String> {
+        match &st.content {
+            RawStatement::Assign(p, rv) => match self.rvalue_type(rv)? {
+                Some(rv_ty) => {
+                    let p_ty = self.place_type(p)?;
+                    // Before [crate::simplify_ops], the checked arithmetic
+                    // operations compute a pair (result, overflowed)
+                    let is_checked_binop = match (rv, &p_ty) {
+                        (Rvalue::BinaryOp(binop, _, _), Ty::Adt(TypeId::Tuple, _, tys)) => {
+                            can_overflow(*binop)
+                                && tys.len() == 2
+                                && tys[1] == Ty::Bool
+                                && same_type(&tys[0], &rv_ty)
+                        }
+                        _ => false,
+                    };
+                    if is_checked_binop {
+                        Ok(())
+                    } else {
+                        check_same_type("assignment", &p_ty, &rv_ty)
+                    }
+                }
+                None => self.place_type(p).map(|_| ()),
+            },
+            RawStatement::CompoundAssign(p, binop, op) => {
+                let p_ty = self.place_type(p)?;
+                let op_ty = self.operand_type(op)?;
+                match binop {
+                    BinOp::Shl | BinOp::Shr => Ok(()),
+                    _ => check_same_type("compound assignment", &p_ty, &op_ty),
+                }
+            }
+            RawStatement::SetDiscriminant(p, variant_id) => {
+                self.check_variants(&self.place_type(p)?, &[*variant_id])
+            }
+            RawStatement::Assert(assert) => {
+                check_same_type("assertion", &Ty::Bool, &self.operand_type(&assert.cond)?)
+            }
+            RawStatement::Call(call) => self.check_call(call),
+            RawStatement::Switch(switch) => self.check_switch(switch),
+            RawStatement::While(_, op, _, _) => {
+                check_same_type("loop condition", &Ty::Bool, &self.operand_type(op)?)
+            }
+            RawStatement::Sequence(st1, _) if st1.content.is_sequence() => {
+                Err("left-nested sequence".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<'a> AstVisitor for Checker<'a> {
+    fn visit_statement(&mut self, st: &Statement) {
+        let meta = std::mem::replace(&mut self.meta, st.meta);
+        if let Err(msg) = self.check_statement(st) {
+            self.fail(msg);
+        }
+        walk_statement(self, st);
+        self.meta = meta;
+    }
+
+    fn visit_place(&mut self, p: &Place) {
+        if let Err(msg) = self.place_type(p) {
+            self.fail(msg);
+        }
+    }
+}
+
+/// Type-check a body. We report the first error we find.
+pub fn check_body(
+    type_defs: &TypeDecls,
+    funs: &FunDecls,
+    globals: &GlobalDecls,
+    body: &ExprBody,
+) -> TransResult<()> {
+    let mut checker = Checker {
+        type_defs,
+        funs,
+        globals,
+        locals: &body.locals,
+        meta: body.meta,
+        error: None,
+    };
+    checker.visit_statement(&body.body);
+    match checker.error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Type-check all the function and global bodies
+pub fn check_decls(type_defs: &TypeDecls, funs: &FunDecls, globals: &GlobalDecls) -> DeclErrors {
+    let mut errors = DeclErrors::default();
+    for decl in funs.iter() {
+        if let Some(body) = &decl.body {
+            if let Err(err) = check_body(type_defs, funs, globals, body) {
+                errors.funs.push((decl.def_id, err));
+            }
+        }
+    }
+    for decl in globals.iter() {
+        if let Some(body) = &decl.body {
+            if let Err(err) = check_body(type_defs, funs, globals, body) {
+                errors.globals.push((decl.def_id, err));
+            }
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::check::*;
+    use crate::test_utils::*;
+
+    fn mk(content: RawStatement) -> Statement {
+        Statement::new(Meta::dummy(), content)
+    }
+
+    /// A function `f(x: u32) -> u32`, with the body `sts; return` and the
+    /// locals: `_0: u32, _1: bool, _2: (u32, u32)`
+    fn mk_fun(mut sts: Vec<RawStatement>) -> FunDecl {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let pair_ty = Ty::Adt(
+            TypeId::Tuple,
+            im::Vector::new(),
+            im::vector![u32_ty.clone(), u32_ty.clone()],
+        );
+        let locals = vec![
+            mk_var(0, None, u32_ty.clone()),
+            mk_var(1, None, Ty::Bool),
+            mk_var(2, None, pair_ty),
+        ];
+        sts.push(RawStatement::Return);
+        let sig = mk_sig(
+            vec![Ty::Integer(IntegerTy::U32)],
+            Ty::Integer(IntegerTy::U32),
+        );
+        mk_fun_decl(0, &["f"], sig, Some(mk_body(0, locals, sts)))
+    }
+
+    fn check_fun(sts: Vec<RawStatement>) -> Result<(), String> {
+        let funs = FunDecls::from(vec![mk_fun(sts)]);
+        let body = funs.get(FunDeclId::ZERO).unwrap().body.as_ref().unwrap();
+        check_body(&TypeDecls::new(), &funs, &GlobalDecls::new(), body).map_err(|err| err.msg)
+    }
+
+    #[test]
+    fn test_check() {
+        let local = |i: usize| Place::new(VarId::Id::new(i));
+        let field = |i: usize, field_id: usize| {
+            let mut p = local(i);
+            let kind = FieldProjKind::Tuple(2);
+            let pe = ProjectionElem::Field(kind, FieldId::Id::new(field_id));
+            p.projection.push_back(pe);
+            p
+        };
+        let int = |v: ScalarValue| {
+            Operand::Const(
+                Ty::Integer(v.get_integer_ty()),
+                OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(v)),
+            )
+        };
+        let call = |args: Vec<Operand>| {
+            RawStatement::Call(Call {
+                func: FunId::Regular(FunDeclId::ZERO),
+                region_args: Vec::new(),
+                type_args: Vec::new(),
+                args,
+                dest: local(0),
+                sig: None,
+            })
+        };
+
+        // ```
+        // _0 := copy (_2.1) + const 1;
+        // if copy _1 { _0 := f(copy _0) } else { nop }
+        // ```
+        let add = Rvalue::BinaryOp(
+            BinOp::Add,
+            Operand::Copy(field(2, 1)),
+            int(ScalarValue::U32(1)),
+        );
+        let switch = Switch::If(
+            Operand::Copy(local(1)),
+            Box::new(mk(call(vec![Operand::Copy(local(0))]))),
+            Box::new(mk(RawStatement::Nop)),
+        );
+        let well_typed = vec![
+            RawStatement::Assign(local(0), add),
+            RawStatement::Switch(switch),
+        ];
+        assert!(check_fun(well_typed).is_ok());
+
+        // `_0 := copy _1`
+        let res = check_fun(vec![RawStatement::Assign(
+            local(0),
+            Rvalue::Use(Operand::Copy(local(1))),
+        )]);
+        assert!(
+            res == Err(
+                "ill-typed assignment: expected a value of type u32, got a value of type bool"
+                    .to_string()
+            )
+        );
+
+        // A projection through a field which doesn't exist
+        let res = check_fun(vec![RawStatement::Assign(
+            local(0),
+            Rvalue::Use(Operand::Copy(field(2, 2))),
+        )]);
+        assert!(res.unwrap_err().starts_with("ill-typed projection"));
+
+        // A call with a missing argument
+        let res = check_fun(vec![call(Vec::new())]);
+        assert!(res == Err("call to @Fun0 with 0 argument(s), expected 1".to_string()));
+
+        // A switch over a `u32` with an `i32` value
+        let targets = vec![(vec![ScalarValue::I32(1)], mk(RawStatement::Nop))];
+        let switch = Switch::SwitchInt(
+            Operand::Copy(local(0)),
+            IntegerTy::U32,
+            targets,
+            Box::new(mk(RawStatement::Nop)),
+        );
+        let res = check_fun(vec![RawStatement::Switch(switch)]);
+        assert!(res
+            .unwrap_err()
+            .starts_with("the value 1 : i32 of a switch"));
+
+        // A left-nested sequence
+        let seq = RawStatement::Sequence(
+            Box::new(mk(RawStatement::Sequence(
+                Box::new(mk(RawStatement::Nop)),
+                Box::new(mk(RawStatement::Nop)),
+            ))),
+            Box::new(mk(RawStatement::Return)),
+        );
+        assert!(check_fun(vec![seq]) == Err("left-nested sequence".to_string()));
+    }
+
+    #[test]
+    fn test_same_type() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let shared_ref = |ty: ETy| Ty::Ref(ErasedRegion::Erased, Box::new(ty), RefKind::Shared);
+        assert!(same_type(
+            &shared_ref(u32_ty.clone()),
+            &shared_ref(u32_ty.clone())
+        ));
+        assert!(!same_type(
+            &shared_ref(u32_ty.clone()),
+            &shared_ref(Ty::Bool)
+        ));
+        // `!` is compatible with any type
+        assert!(same_type(&Ty::Never, &shared_ref(Ty::Bool)));
+        let mut_ref = Ty::Ref(ErasedRegion::Erased, Box::new(u32_ty), RefKind::Mut);
+        assert!(!same_type(&shared_ref(Ty::Never), &mut_ref));
+    }
+}
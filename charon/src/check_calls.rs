@@ -0,0 +1,131 @@
+//! Sanity checks on the function calls of the ULLBC bodies.
+//!
+//! The region arguments of the calls are erased, and thus not really useful,
+//! but their number must still match the number of early-bound region
+//! parameters of the callee: a mismatch is the sign of a bug in the
+//! translation of the generics (we check this in debug builds only).
+#![allow(dead_code)]
+
+use crate::types::ErasedRegion;
+use crate::ullbc_ast::*;
+
+/// An error in a function call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallError {
+    /// The call to `callee` has `actual` region arguments, while the
+    /// signature of `callee` has `expected` early-bound region parameters
+    RegionArity {
+        callee: FunDeclId::Id,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl std::fmt::Display for CallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            CallError::RegionArity {
+                callee,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "call to function @{callee} with {actual} region argument(s), expected {expected}"
+            ),
+        }
+    }
+}
+
+/// Check that the number of region arguments of a call matches the signature
+/// of the callee. We ignore the calls to the assumed functions.
+pub fn check_call_region_arity(
+    funs: &FunDecls,
+    func: &FunId,
+    region_args: &[ErasedRegion],
+) -> Result<(), CallError> {
+    let callee = match func {
+        FunId::Regular(callee) | FunId::TraitMethod(_, callee) | FunId::Virtual(_, callee) => {
+            *callee
+        }
+        FunId::Indirect(_) | FunId::Assumed(_) => return Ok(()),
+    };
+    let expected = funs.get(callee).unwrap().signature.num_early_bound_regions;
+    if region_args.len() == expected {
+        Ok(())
+    } else {
+        Err(CallError::RegionArity {
+            callee,
+            expected,
+            actual: region_args.len(),
+        })
+    }
+}
+
+/// Check the calls of a body
+pub fn check_body_calls(funs: &FunDecls, body: &ExprBody) -> Result<(), CallError> {
+    for block in body.body.iter() {
+        if let RawTerminator::Call {
+            func, region_args, ..
+        } = &block.terminator.content
+        {
+            check_call_region_arity(funs, func, region_args)?;
+        }
+    }
+    Ok(())
+}
+
+/// Check the calls in all the function and global bodies, and panic if we
+/// find an error (the errors are bugs in Charon, not in the user code).
+pub fn check(funs: &FunDecls, globals: &GlobalDecls) {
+    let fun_bodies = funs
+        .iter()
+        .filter_map(|d| d.body.as_ref().map(|b| (d.name.to_string(), b)));
+    let global_bodies = globals
+        .iter()
+        .filter_map(|d| d.body.as_ref().map(|b| (d.name.to_string(), b)));
+    for (name, body) in fun_bodies.chain(global_bodies) {
+        if let Err(err) = check_body_calls(funs, body) {
+            panic!("Ill-formed function call in {}: {}", name, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::check_calls::*;
+    use crate::test_utils::*;
+    use crate::types::*;
+
+    /// A method defined in an `impl<'a> Foo<'a>` block: it has one
+    /// early-bound region parameter
+    fn mk_method() -> FunDecl {
+        let mut sig = mk_sig(Vec::new(), Ty::Bool);
+        sig.region_params = RegionVarId::Vector::from(vec![RegionVar {
+            index: RegionVarId::ZERO,
+            name: Some("'a".to_string()),
+        }]);
+        sig.num_early_bound_regions = 1;
+        mk_fun_decl(0, &["Foo", "bar"], sig, None)
+    }
+
+    #[test]
+    fn test_region_arity() {
+        let funs = FunDeclId::Vector::from(vec![mk_method()]);
+        let func = FunId::Regular(FunDeclId::ZERO);
+        assert!(check_call_region_arity(&funs, &func, &[ErasedRegion::Erased]) == Ok(()));
+        assert!(
+            check_call_region_arity(&funs, &func, &[])
+                == Err(CallError::RegionArity {
+                    callee: FunDeclId::ZERO,
+                    expected: 1,
+                    actual: 0,
+                })
+        );
+        assert!(check_call_region_arity(
+            &funs,
+            &func,
+            &[ErasedRegion::Erased, ErasedRegion::Erased]
+        )
+        .is_err());
+    }
+}
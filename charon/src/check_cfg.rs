@@ -0,0 +1,96 @@
+//! Sanity checks on the control-flow graph of the ULLBC bodies.
+//!
+//! The passes which work on the ULLBC may add, remove or renumber blocks: if
+//! they do so incorrectly, a terminator may end up referencing a block which
+//! doesn't exist anymore. Such dangling edges make the control-flow
+//! reconstruction crash in a way which is hard to debug, so we check for them
+//! right after the passes which modify the CFG (in debug builds only).
+#![allow(dead_code)]
+
+use crate::ullbc_ast::*;
+
+/// An error in the control-flow graph of a body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CfgError {
+    /// The terminator of block `source` targets block `target`, which doesn't
+    /// exist
+    DanglingTarget {
+        source: BlockId::Id,
+        target: BlockId::Id,
+    },
+}
+
+impl std::fmt::Display for CfgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            CfgError::DanglingTarget { source, target } => {
+                write!(f, "bb{source} jumps to bb{target}, which doesn't exist")
+            }
+        }
+    }
+}
+
+/// Check that all the blocks targeted by the terminators exist.
+pub fn check_block_targets(body: &ExprBody) -> Result<(), CfgError> {
+    for (source, block) in body.body.iter_indexed_values() {
+        for target in block.terminator.content.successors() {
+            if body.body.get(target).is_none() {
+                return Err(CfgError::DanglingTarget { source, target });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check the CFG of all the function and global bodies, and panic if we
+/// find an error (the errors are bugs in Charon, not in the user code).
+pub fn check(funs: &FunDecls, globals: &GlobalDecls) {
+    let fun_bodies = funs
+        .iter()
+        .filter_map(|d| d.body.as_ref().map(|b| (d.name.to_string(), b)));
+    let global_bodies = globals
+        .iter()
+        .filter_map(|d| d.body.as_ref().map(|b| (d.name.to_string(), b)));
+    for (name, body) in fun_bodies.chain(global_bodies) {
+        if let Err(err) = check_block_targets(body) {
+            panic!("Ill-formed control-flow graph in {}: {}", name, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::check_cfg::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_check_block_targets() {
+        let bb1 = BlockId::Id::new(1);
+        let body = mk_cfg(
+            0,
+            vec![],
+            vec![
+                mk_block(vec![], RawTerminator::Goto { target: bb1 }),
+                mk_block(vec![], RawTerminator::Return),
+            ],
+        );
+        assert!(check_block_targets(&body) == Ok(()));
+
+        let bb2 = BlockId::Id::new(2);
+        let body = mk_cfg(
+            0,
+            vec![],
+            vec![
+                mk_block(vec![], RawTerminator::Goto { target: bb1 }),
+                mk_block(vec![], RawTerminator::Goto { target: bb2 }),
+            ],
+        );
+        assert!(
+            check_block_targets(&body)
+                == Err(CfgError::DanglingTarget {
+                    source: bb1,
+                    target: bb2,
+                })
+        );
+    }
+}
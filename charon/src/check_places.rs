@@ -0,0 +1,196 @@
+//! Sanity checks on the projections of the places of the LLBC bodies.
+//!
+//! In MIR, a field projection into an enumeration must be preceded by a
+//! downcast to the proper variant (`(x as Cons).1`). We merge the downcasts
+//! with the field projections which follow them (see [crate::expressions]):
+//! the invariant becomes that the field projections into an enumeration carry
+//! the variant, while the field projections into a structure don't. The
+//! passes which synthesize places may break this invariant, so we check it
+//! after the micro-passes (in debug builds only).
+#![allow(dead_code)]
+
+use crate::expressions::*;
+use crate::llbc_ast::{FunDecls, GlobalDecls};
+use crate::types::*;
+use crate::ullbc_ast::{iter_function_bodies, iter_global_bodies};
+
+/// An error in the projection of a place
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProjectionError {
+    /// We project the field `field_id` of the enumeration `type_id` before
+    /// downcasting it to a variant
+    MissingDowncast {
+        type_id: TypeDeclId::Id,
+        field_id: FieldId::Id,
+    },
+    /// We downcast the ADT `type_id` to a variant it doesn't have
+    InvalidDowncast {
+        type_id: TypeDeclId::Id,
+        variant_id: VariantId::Id,
+    },
+}
+
+impl std::fmt::Display for ProjectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            ProjectionError::MissingDowncast { type_id, field_id } => write!(
+                f,
+                "projection of the field {field_id} of the enumeration @Adt{type_id} without a downcast"
+            ),
+            ProjectionError::InvalidDowncast {
+                type_id,
+                variant_id,
+            } => write!(
+                f,
+                "downcast of @Adt{type_id} to the variant {variant_id}, which doesn't exist"
+            ),
+        }
+    }
+}
+
+/// Check that the field projections into the enumerations are preceded by a
+/// downcast (i.e., carry a variant id), and normalize the field projections
+/// into the structures: a downcast of a structure to its (unique) variant is
+/// redundant, and we remove it.
+pub fn canonicalize_projection(
+    type_defs: &TypeDecls,
+    place: &mut Place,
+) -> Result<(), ProjectionError> {
+    for pe in place.projection.iter_mut() {
+        if let ProjectionElem::Field(FieldProjKind::Adt(type_id, variant_id), field_id) = pe {
+            let type_def = type_defs.get_type_def(*type_id).unwrap();
+            match (&type_def.kind, *variant_id) {
+                (TypeDeclKind::Enum(_), None) => {
+                    return Err(ProjectionError::MissingDowncast {
+                        type_id: *type_id,
+                        field_id: *field_id,
+                    })
+                }
+                (TypeDeclKind::Enum(variants), Some(vid)) if variants.get(vid).is_none() => {
+                    return Err(ProjectionError::InvalidDowncast {
+                        type_id: *type_id,
+                        variant_id: vid,
+                    })
+                }
+                (TypeDeclKind::Struct(_), Some(vid)) if vid.is_zero() => *variant_id = None,
+                (TypeDeclKind::Struct(_), Some(vid)) => {
+                    return Err(ProjectionError::InvalidDowncast {
+                        type_id: *type_id,
+                        variant_id: vid,
+                    })
+                }
+                // We can't project the fields of the opaque types, and we
+                // project the fields of the unions with [FieldProjKind::Union]
+                (TypeDeclKind::Opaque | TypeDeclKind::Union(_), _) => unreachable!(),
+                (TypeDeclKind::Enum(_), Some(_)) | (TypeDeclKind::Struct(_), None) => (),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Canonicalize the places in all the function and global bodies, and panic if
+/// we find an error (the errors are bugs in Charon, not in the user code).
+pub fn check(type_defs: &TypeDecls, funs: &mut FunDecls, globals: &mut GlobalDecls) {
+    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+        b.visit_places_mut(&mut |p| {
+            if let Err(err) = canonicalize_projection(type_defs, p) {
+                panic!("Ill-formed place in {}: {}", name, err);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::check_places::*;
+    use crate::meta::{AttrInfo, Meta};
+    use crate::names::TypeName;
+    use crate::regions_hierarchy::RegionGroups;
+    use crate::values::VarId;
+
+    fn mk_type_decl(def_id: TypeDeclId::Id, name: &str, kind: TypeDeclKind) -> TypeDecl {
+        TypeDecl {
+            def_id,
+            meta: Meta::dummy(),
+            name: TypeName::from(vec![name.to_string()]),
+            region_params: RegionVarId::Vector::new(),
+            type_params: TypeVarId::Vector::new(),
+            const_generic_params: ConstGenericVarId::Vector::new(),
+            trait_clauses: Vec::new(),
+            regions_hierarchy: RegionGroups::new(),
+            kind,
+            repr_packed: None,
+            under_aligned_fields: Vec::new(),
+            recursive_fields: Vec::new(),
+            attr_info: AttrInfo::default(),
+        }
+    }
+
+    fn mk_field(ty: RTy) -> Field {
+        Field {
+            meta: Meta::dummy(),
+            name: None,
+            ty,
+        }
+    }
+
+    fn mk_place(proj_kind: FieldProjKind, field_id: FieldId::Id) -> Place {
+        let mut p = Place::new(VarId::Id::new(1));
+        p.projection
+            .push_back(ProjectionElem::Field(proj_kind, field_id));
+        p
+    }
+
+    #[test]
+    fn test_downcasts() {
+        // ```
+        // enum E { A(u32), B }
+        // struct S(u32);
+        // ```
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let a = Variant {
+            meta: Meta::dummy(),
+            name: "A".to_string(),
+            fields: FieldId::Vector::from(vec![mk_field(u32_ty.clone())]),
+        };
+        let b = Variant {
+            meta: Meta::dummy(),
+            name: "B".to_string(),
+            fields: FieldId::Vector::new(),
+        };
+        let (e_id, s_id) = (TypeDeclId::ZERO, TypeDeclId::ONE);
+        let mut type_defs = TypeDecls::new();
+        let e = TypeDeclKind::Enum(VariantId::Vector::from(vec![a, b]));
+        type_defs.types.push_back(mk_type_decl(e_id, "E", e));
+        let s = TypeDeclKind::Struct(FieldId::Vector::from(vec![mk_field(u32_ty)]));
+        type_defs.types.push_back(mk_type_decl(s_id, "S", s));
+
+        // `(x as A).0` is well-formed
+        let mut p = mk_place(
+            FieldProjKind::Adt(e_id, Some(VariantId::ZERO)),
+            FieldId::ZERO,
+        );
+        let p1 = p.clone();
+        assert!(canonicalize_projection(&type_defs, &mut p) == Ok(()));
+        assert!(p == p1);
+
+        // `x.0`: the field is projected before the downcast
+        let mut p = mk_place(FieldProjKind::Adt(e_id, None), FieldId::ZERO);
+        assert!(
+            canonicalize_projection(&type_defs, &mut p)
+                == Err(ProjectionError::MissingDowncast {
+                    type_id: e_id,
+                    field_id: FieldId::ZERO,
+                })
+        );
+
+        // `(x as S).0` is normalized to `x.0`
+        let mut p = mk_place(
+            FieldProjKind::Adt(s_id, Some(VariantId::ZERO)),
+            FieldId::ZERO,
+        );
+        assert!(canonicalize_projection(&type_defs, &mut p) == Ok(()));
+        assert!(p == mk_place(FieldProjKind::Adt(s_id, None), FieldId::ZERO));
+    }
+}
@@ -3,8 +3,112 @@
 /// The options received as input by cargo-charon
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::str::FromStr;
 use structopt::StructOpt;
 
+/// The languages we can extract the crate to (see the `--emit` option)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Emit {
+    /// The unstructured LLBC: the bodies are control-flow graphs
+    Ullbc,
+    /// The structured LLBC
+    Llbc,
+    /// The structured LLBC, where the declarations also carry their
+    /// unstructured bodies
+    Both,
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ullbc" => Ok(Emit::Ullbc),
+            "llbc" => Ok(Emit::Llbc),
+            "both" => Ok(Emit::Both),
+            _ => Err(format!(
+                "Unknown language: `{s}` (expected `ullbc`, `llbc` or `both`)"
+            )),
+        }
+    }
+}
+
+/// The formats we can export the crate to (see the `--output-format` option).
+///
+/// In the binary formats, the enumerations are encoded with the indices of
+/// their variants (the CBOR files also use the indices of the fields of the
+/// structures, rather than their names). For those tags to be stable across
+/// releases, the new variants and fields must be added *at the end* of the
+/// definitions, and the existing ones must never be reordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputFormat {
+    Json,
+    Cbor,
+    Bincode,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "cbor" => Ok(OutputFormat::Cbor),
+            "bincode" => Ok(OutputFormat::Bincode),
+            _ => Err(format!(
+                "Unknown output format: `{s}` (expected `json`, `cbor` or `bincode`)"
+            )),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// The extension of the generated file, for a crate exported to
+    /// `language` (`llbc`, `ullbc`): we keep the same extension as before for
+    /// the JSON files.
+    pub fn extension(&self, language: &str) -> String {
+        match self {
+            OutputFormat::Json => language.to_string(),
+            OutputFormat::Cbor => format!("{language}.cbor"),
+            OutputFormat::Bincode => format!("{language}.bin"),
+        }
+    }
+
+    /// Guess the format of a generated file from its extension (see
+    /// [OutputFormat::extension])
+    pub fn of_path(path: &std::path::Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("cbor") => OutputFormat::Cbor,
+            Some("bin") => OutputFormat::Bincode,
+            _ => OutputFormat::Json,
+        }
+    }
+}
+
+/// How we report the diagnostics (see the `--diagnostics-format` option and
+/// [crate::diagnostics])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiagnosticsFormat {
+    /// Print them like the compiler errors
+    Human,
+    /// Also write them to a JSON file
+    Json,
+}
+
+impl FromStr for DiagnosticsFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(DiagnosticsFormat::Human),
+            "json" => Ok(DiagnosticsFormat::Json),
+            _ => Err(format!(
+                "Unknown diagnostics format: `{s}` (expected `human` or `json`)"
+            )),
+        }
+    }
+}
+
 // This structure is used to store the command-line instructions.
 // We automatically derive a command-line parser based on this structure.
 // Note that the doc comments are used to generate the help message when using
@@ -13,12 +117,23 @@ use structopt::StructOpt;
 // Note that because we need to transmit the options to the charon driver,
 // we store them in a file before calling this driver (hence the `Serialize`,
 // `Deserialize` options).
-#[derive(StructOpt, Serialize, Deserialize)]
+#[derive(StructOpt, Clone, Serialize, Deserialize)]
 #[structopt(name = "Charon")]
 pub struct CliOpts {
     /// Extract the unstructured LLBC (i.e., don't reconstruct the control-flow)
     #[structopt(long = "ullbc")]
     pub ullbc: bool,
+    /// What to extract: `ullbc`, `llbc` (the default) or `both`. With `both`,
+    /// we generate an .llbc file where the function and global declarations
+    /// also carry their unstructured bodies, in the field `ullbc_body`, which
+    /// allows to cross-check the control-flow reconstruction.
+    #[structopt(long = "emit")]
+    pub emit: Option<Emit>,
+    /// The format of the generated file: `json` (the default), `cbor` or
+    /// `bincode`. The binary formats are much more compact and faster to
+    /// parse than JSON, which matters for big crates.
+    #[structopt(long = "output-format", default_value = "json")]
+    pub output_format: OutputFormat,
     /// Compile for release target instead of debug
     #[structopt(long = "release")]
     pub release: bool,
@@ -28,6 +143,13 @@ pub struct CliOpts {
     /// Compile the specified binary
     #[structopt(long = "bin")]
     pub bin: Option<String>,
+    /// Extract all the members of the workspace: we generate one file per
+    /// crate, and a manifest (`charon-manifest.json`) which lists those files
+    /// with the dependencies between the crates (see [crate::workspace]). The
+    /// files are generated in the root of the workspace, unless `--dest` is
+    /// given.
+    #[structopt(long = "workspace")]
+    pub workspace: bool,
     /// Extract the promoted MIR instead of the built MIR
     #[structopt(long = "mir_promoted")]
     pub mir_promoted: bool,
@@ -96,9 +218,154 @@ performs: `y := (x as E2).1`). Producing a better reconstruction is non-trivial.
     pub no_code_duplication: bool,
     /// A list of modules of the extracted crate that we consider as opaque: we
     /// extract only the signature information, without the definition content
-    /// (of the functions, types, etc.).
+    /// (of the functions, types, etc.). The modules are given by their paths
+    /// relative to the crate root (`module` or `module::submodule`). They can
+    /// also be listed in the `charon.toml` file of the crate (see
+    /// [crate::config]).
     #[structopt(long = "opaque")]
     pub opaque_modules: Vec<String>,
+    /// Link the external declarations to a crate extracted beforehand, given
+    /// as `name=path` (for instance, `--extern-llbc utils=out/utils.llbc`).
+    /// The external declarations are still declared as opaque, but their
+    /// information gives their identifiers in the file of their crate. The
+    /// option can be repeated.
+    #[structopt(long = "extern-llbc")]
+    pub extern_llbc: Vec<String>,
+    /// Extract the crate incrementally: we save a hash of every function next
+    /// to the generated file (in `{crate}.cache.json`), and only translate
+    /// the functions which changed since the previous extraction. The other
+    /// functions are loaded from the previous output. This is only supported
+    /// when emitting LLBC, without `--inline-threshold`.
+    #[structopt(long = "incremental")]
+    pub incremental: bool,
+    /// The number of threads we use to reconstruct the control-flow of the
+    /// functions and to apply the micro-passes. By default, we use one thread
+    /// per CPU.
+    #[structopt(long = "jobs")]
+    pub jobs: Option<usize>,
+    /// If a micro-pass fails on a declaration (because its body doesn't have
+    /// the shape we expect), report the error as a warning, and export the
+    /// declaration without its body, instead of stopping the extraction.
+    #[structopt(long = "errors-as-warnings")]
+    pub errors_as_warnings: bool,
+    /// Type-check the generated code after every micro-pass, to catch the
+    /// bugs of the passes early. This is slow: we use it for debugging.
+    #[structopt(long = "check")]
+    pub check: bool,
+    /// How to report the errors about the code we can't translate: `human`
+    /// (the default) prints them like the compiler errors, with an error code
+    /// (`CHARON-0005`, for instance); `json` also writes them, with their
+    /// locations, to `{crate}.diagnostics.json`, next to the generated file.
+    #[structopt(long = "diagnostics-format", default_value = "human")]
+    pub diagnostics_format: DiagnosticsFormat,
+    /// Only translate the declarations whose full names match one of the given
+    /// glob patterns (for instance, `mycrate::parser::*`). In the patterns,
+    /// `*` matches any sequence of characters, including `::`. The
+    /// declarations the translated declarations depend on are still
+    /// extracted, but as opaque declarations.
+    #[structopt(long = "include")]
+    pub include: Vec<String>,
+    /// Do not translate the declarations whose full names match one of the
+    /// given glob patterns (see `--include`). The exclusions take precedence
+    /// over the inclusions.
+    #[structopt(long = "exclude")]
+    pub exclude: Vec<String>,
+    /// Do not remove the fake reads which are immediately followed by a
+    /// storage dead of the same local. Those pairs are redundant, unless you
+    /// track the causes of the fake reads or the storage liveness of the locals.
+    #[structopt(long = "keep-fake-reads")]
+    pub keep_fake_reads: bool,
+    /// Do not erase the region information at the call sites: the calls then
+    /// carry the signature of their callee, with its region parameters and
+    /// the outlives constraints between them. Note that the regions are still
+    /// erased in the types of the bodies.
+    #[structopt(long = "keep-regions")]
+    pub keep_regions: bool,
+    /// Remove the `PhantomData` fields from the structures and the
+    /// enumerations. Those fields don't carry any data: the reads of such
+    /// fields become zero-sized constants, and the writes are removed.
+    #[structopt(long = "erase-phantoms")]
+    pub erase_phantoms: bool,
+    /// Recognize the compound assignments: rewrite the assignments of the
+    /// shape `x := copy x + y` (which come from `x += y` in the Rust source)
+    /// to `x += y`.
+    #[structopt(long = "compound-assignments")]
+    pub compound_assignments: bool,
+    /// Propagate the locals which are copies of other locals (`tmp := copy x`)
+    /// or constants to the places where they are read, inside the blocks of
+    /// statements, then remove the temporaries which become unused.
+    #[structopt(long = "propagate-copies")]
+    pub propagate_copies: bool,
+    /// Inline the calls to the small functions (the functions which don't
+    /// contain control-flow) whose bodies contain at most the given number
+    /// of statements. We don't inline the recursive functions.
+    #[structopt(long = "inline-threshold")]
+    pub inline_threshold: Option<usize>,
+    /// Inline the calls to the functions marked `#[inline(always)]` which
+    /// don't contain control-flow, whatever their size.
+    #[structopt(long = "inline-always")]
+    pub inline_always: bool,
+    /// Move the `return` statements to the end of the function bodies, when
+    /// it is possible without introducing new control-flow, so that the
+    /// functions have a single exit point.
+    #[structopt(long = "single-exit")]
+    pub single_exit: bool,
+    /// Annotate the branches of the matches with their provenance: the span
+    /// of their pattern, and whether they have a guard. Without this, the
+    /// guards can't be distinguished from the `if`s which start the branches.
+    #[structopt(long = "match-provenance")]
+    pub match_provenance: bool,
+    /// Don't reconstruct the `while` loops: all the loops are then infinite
+    /// loops, which are exited with `break` (this is a more uniform encoding,
+    /// which some backends prefer).
+    #[structopt(long = "no-while-loops")]
+    pub no_while_loops: bool,
+    /// Monomorphize the program: starting from the public non-generic
+    /// functions, instantiate the generic types and functions at the type
+    /// arguments they are used with, for the backends which don't support
+    /// polymorphism. The instances get mangled names (`Wrapper_u32`), and
+    /// the declarations which are not used by the entry points are dropped.
+    /// This is only supported when emitting LLBC.
+    #[structopt(long = "monomorphize")]
+    pub monomorphize: bool,
+    /// Emit a warning whenever we remove the overflow check of an arithmetic
+    /// operation: the type of the operation then changes from `(T, bool)`
+    /// (the result and the overflow flag) to `T`.
+    #[structopt(long = "warn-simplified-binops")]
+    pub warn_simplified_binops: bool,
+    /// A list of functions (given by their full names, for instance
+    /// `crate::module::f`) for which we don't reconstruct the control-flow
+    /// nor apply the micro-passes: we export their unstructured bodies
+    /// instead. This is meant for debugging, and can also be done by
+    /// annotating the functions with `#[charon::no_simplify]`.
+    #[structopt(long = "no-simplify")]
+    pub no_simplify: Vec<String>,
+    /// Also generate an index file (`{crate}.index.json`), which maps the full
+    /// paths of the declarations (for instance `crate::module::f`) to their
+    /// identifiers and to their spans in the source.
+    #[structopt(long = "index")]
+    pub index: bool,
+    /// Dump the control-flow graphs of the functions, before we reconstruct
+    /// the control-flow, to Graphviz DOT files (one file per function, in
+    /// the directory `{crate}.cfg`).
+    #[structopt(long = "dump-cfg")]
+    pub dump_cfg: bool,
+    /// Also export the call graph of the functions, to `{crate}.callgraph.json`
+    /// and to `{crate}.callgraph.dot` (in the Graphviz DOT format). The graph
+    /// is computed on the bodies we export.
+    #[structopt(long = "call-graph")]
+    pub call_graph: bool,
+    /// Also write a human-readable dump of the LLBC declarations, with the
+    /// names of the declarations they refer to, to `{crate}.llbc.txt`. This
+    /// is ignored if we only emit the ULLBC.
+    #[structopt(long = "print-llbc")]
+    pub print_llbc: bool,
+    /// Also generate an HTML report (`{crate}.report.html`) which shows the
+    /// LLBC functions side by side with their Rust source: hovering over a
+    /// statement highlights the source lines it comes from. This is ignored if
+    /// we only emit the ULLBC.
+    #[structopt(long = "html-report")]
+    pub html_report: bool,
     /// Do not provide a Rust version argument to Cargo (e.g., `+nightly-2022-01-29`).
     /// This is for Nix: outside of Nix, we use Rustup to call the proper version
     /// of Cargo (and thus need this argument), but within Nix we build and call a very
@@ -107,6 +374,17 @@ performs: `y := (x as E2).1`). Producing a better reconstruction is non-trivial.
     pub cargo_no_rust_version: bool,
 }
 
+impl CliOpts {
+    /// `--ullbc` is a shortcut for `--emit=ullbc`
+    pub fn emit(&self) -> Emit {
+        if self.ullbc {
+            Emit::Ullbc
+        } else {
+            self.emit.unwrap_or(Emit::Llbc)
+        }
+    }
+}
+
 /// The name of the environment variable we use to save the serialized Cli options
 /// when calling charon-driver from cargo-charon.
 pub const CHARON_ARGS: &str = "CHARON_ARGS";
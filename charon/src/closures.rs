@@ -0,0 +1,173 @@
+//! The closures are represented by a function, and by a structure holding
+//! their environment, i.e., the variables they capture. The environment is
+//! built with [crate::expressions::AggregateKind::Closure].
+//!
+//! The way a variable is captured ([CaptureMode]) determines the type of the
+//! corresponding field in the environment:
+//! - a variable captured by reference becomes a reference field, with its own
+//!   region parameter. Dropping the environment doesn't drop the variable,
+//!   which is still owned by the function which created the closure.
+//! - a variable captured by value is moved into the environment, and is
+//!   dropped with it.
+//!
+//! For instance:
+//! ```text
+//! let x = 0;
+//! let y = Box::new(1);
+//! let f = || { let z = y; x + *z }; // captures `x` by reference and `y` by value
+//! ```
+//! gives the environment:
+//! ```text
+//! struct Env<'a> {
+//!   x: &'a u32,
+//!   y: Box<u32>,
+//! }
+//! ```
+//!
+//! The body of the closure becomes a standalone function, whose first
+//! parameter is the environment (borrowed or moved, depending on the trait
+//! the closure implements). The calls to closures, which rustc desugars to
+//! calls to the methods of the `Fn`, `FnMut` and `FnOnce` traits, become
+//! calls to this function: the environment is the first argument, and the
+//! tupled arguments of the trait method are given separately.
+#![allow(dead_code)]
+
+use crate::expressions::CaptureMode;
+use crate::meta::{AttrInfo, Meta};
+use crate::names::TypeName;
+use crate::regions_hierarchy::{RegionGroup, RegionGroupId, RegionGroups};
+use crate::types::*;
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::subst::SubstsRef;
+use rustc_middle::ty::{ClosureKind, TyCtxt};
+
+/// A variable captured by a closure
+#[derive(Debug, Clone)]
+pub struct CapturedVar {
+    pub name: Option<String>,
+    pub mode: CaptureMode,
+    /// The type of the variable itself, before we take a reference to it
+    pub ty: RTy,
+}
+
+/// Generate the declaration of the structure which holds the environment of
+/// a closure. We introduce a region parameter for every variable captured by
+/// reference: those regions are independent from each other.
+pub fn mk_env_type_decl(
+    def_id: TypeDeclId::Id,
+    meta: Meta,
+    name: TypeName,
+    captures: &[CapturedVar],
+) -> TypeDecl {
+    let mut region_params = RegionVarId::Vector::new();
+    let mut regions_hierarchy = RegionGroups::new();
+    let mut fields = FieldId::Vector::new();
+    for var in captures {
+        let ty = match var.mode.ref_kind() {
+            None => var.ty.clone(),
+            Some(ref_kind) => {
+                let rid = RegionVarId::Id::new(region_params.len());
+                region_params.push_back(RegionVar {
+                    index: rid,
+                    name: None,
+                });
+                regions_hierarchy.push_back(RegionGroup {
+                    id: RegionGroupId::Id::new(regions_hierarchy.len()),
+                    regions: vec![rid],
+                    parents: Vec::new(),
+                });
+                Ty::Ref(Region::Var(rid), Box::new(var.ty.clone()), ref_kind)
+            }
+        };
+        fields.push_back(Field {
+            meta,
+            name: var.name.clone(),
+            ty,
+        });
+    }
+
+    TypeDecl {
+        def_id,
+        meta,
+        name,
+        region_params,
+        type_params: TypeVarId::Vector::new(),
+        const_generic_params: ConstGenericVarId::Vector::new(),
+        trait_clauses: Vec::new(),
+        regions_hierarchy,
+        kind: TypeDeclKind::Struct(fields),
+        repr_packed: None,
+        under_aligned_fields: Vec::new(),
+        recursive_fields: Vec::new(),
+        attr_info: AttrInfo::default(),
+    }
+}
+
+/// Check if a function call is a call to a closure, i.e., a call to one of
+/// the methods of the `Fn`, `FnMut` and `FnOnce` traits where the `Self` type
+/// is a closure. If so, return the closure identifier, together with the
+/// trait the method belongs to.
+pub fn get_closure_call(
+    tcx: TyCtxt,
+    fid: DefId,
+    substs: SubstsRef<'_>,
+) -> Option<(DefId, ClosureKind)> {
+    let trait_id = tcx.trait_of_item(fid)?;
+    let kind = tcx.fn_trait_kind_from_def_id(trait_id)?;
+    match substs.type_at(0).kind() {
+        rustc_middle::ty::TyKind::Closure(closure_id, _) => Some((*closure_id, kind)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::closures::*;
+
+    #[test]
+    fn test_env_type_decl() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let box_ty = Ty::Adt(
+            TypeId::Assumed(AssumedTy::Box),
+            im::Vector::new(),
+            im::Vector::from(vec![u32_ty.clone()]),
+        );
+        // `|| { let z = y; x + *z }`, which captures `x` by reference and
+        // `y` by value
+        let captures = vec![
+            CapturedVar {
+                name: Some("x".to_string()),
+                mode: CaptureMode::ByRef,
+                ty: u32_ty.clone(),
+            },
+            CapturedVar {
+                name: Some("y".to_string()),
+                mode: CaptureMode::ByValue,
+                ty: box_ty.clone(),
+            },
+        ];
+        let decl = mk_env_type_decl(
+            TypeDeclId::ZERO,
+            Meta::dummy(),
+            TypeName::from(vec!["f".to_string(), "{closure#0}".to_string()]),
+            &captures,
+        );
+
+        // ```
+        // struct Env<'a> {
+        //   x: &'a u32,
+        //   y: Box<u32>,
+        // }
+        // ```
+        let rid = RegionVarId::ZERO;
+        assert!(decl.region_params.len() == 1);
+        assert!(decl.regions_hierarchy.len() == 1);
+        let fields = decl.kind.as_struct();
+        assert!(fields.len() == 2);
+        let x = fields.get(FieldId::ZERO).unwrap();
+        assert!(x.name == Some("x".to_string()));
+        assert!(x.ty == Ty::Ref(Region::Var(rid), Box::new(u32_ty), RefKind::Shared));
+        let y = fields.get(FieldId::Id::new(1)).unwrap();
+        assert!(y.ty == box_ty);
+    }
+}
@@ -0,0 +1,258 @@
+//! Collapse the dereferences of references which are only introduced to be
+//! immediately dereferenced. The lowering of aggregates often generates code
+//! of the following shape:
+//! ```text
+//! tmp := &x;
+//! ...
+//! y := copy (*tmp).f;
+//! drop tmp;
+//! ```
+//! which we rewrite to:
+//! ```text
+//! y := copy x.f;
+//! ```
+//!
+//! This is valid only if `tmp` is a temporary introduced by rustc which is
+//! assigned exactly once (the borrow), and is only ever used behind a
+//! dereference. We also require the borrowed place not to go through any
+//! dereference itself: the place is then a path inside a local, which can't
+//! be modified while the borrow is alive. The genuine indirections (for
+//! instance, dereferencing an input reference) are left untouched.
+
+use crate::expressions::*;
+use crate::id_vector::ToUsize;
+use crate::llbc_ast::{CtxNames, ExprBody, FunDecls, GlobalDecls, RawStatement, Statement};
+use crate::ullbc_ast::par_iter_bodies;
+use crate::values::*;
+use std::collections::{HashMap, HashSet};
+
+/// Return true if the projection element goes through a pointer
+fn is_deref(pe: &ProjectionElem) -> bool {
+    match pe {
+        ProjectionElem::Deref
+        | ProjectionElem::DerefBox
+        | ProjectionElem::DerefRawPtr
+        | ProjectionElem::DerefPtrUnique
+        | ProjectionElem::DerefPtrNonNull
+        | ProjectionElem::DerefRc => true,
+        ProjectionElem::Field(_, _)
+        | ProjectionElem::Index(_)
+        | ProjectionElem::ConstantIndex { .. } => false,
+    }
+}
+
+/// If the statement is of the shape `tmp := &p` (where `p` doesn't go through
+/// any dereference nor index), return `tmp` and `p`. We forbid the indices
+/// because the variables they use may be updated while the borrow is alive.
+fn as_local_borrow(st: &RawStatement) -> Option<(VarId::Id, &Place)> {
+    match st {
+        RawStatement::Assign(dest, Rvalue::Ref(p, _))
+            if dest.projection.is_empty()
+                && dest.var_id != p.var_id
+                && !p
+                    .projection
+                    .iter()
+                    .any(|pe| is_deref(pe) || matches!(pe, ProjectionElem::Index(_))) =>
+        {
+            Some((dest.var_id, p))
+        }
+        _ => None,
+    }
+}
+
+/// Return true if the statement is `drop tmp`
+fn is_drop_of(st: &RawStatement, var_id: VarId::Id) -> bool {
+    match st {
+        RawStatement::Drop(p) => p.var_id == var_id && p.projection.is_empty(),
+        _ => false,
+    }
+}
+
+/// Compute the temporaries we can eliminate, together with the places they
+/// borrow.
+fn compute_collapsible(body: &mut ExprBody) -> HashMap<VarId::Id, Place> {
+    // The places borrowed by the locals, if they are assigned by borrows
+    let mut borrows: HashMap<VarId::Id, Vec<Place>> = HashMap::new();
+    // The locals which are used in a way which prevents the collapse
+    let mut invalid: HashSet<VarId::Id> = HashSet::new();
+
+    body.body.transform(&mut |st| {
+        if let Some((var_id, p)) = as_local_borrow(&st.content) {
+            borrows.entry(var_id).or_default().push(p.clone());
+            // The borrowed place is itself a use of its local
+            invalid.insert(p.var_id);
+        } else if let RawStatement::Drop(p) = &st.content {
+            // Dropping a reference is a no-op: it doesn't count as
+            // a use (we only need to check the projections)
+            if !p.projection.is_empty() {
+                invalid.insert(p.var_id);
+            }
+        } else {
+            st.transform_shallow_places(&mut |p| {
+                if p.projection.front() != Some(&ProjectionElem::Deref) {
+                    invalid.insert(p.var_id);
+                }
+            });
+        }
+    });
+
+    let arg_count = body.arg_count;
+    let locals = &body.locals;
+    borrows
+        .into_iter()
+        .filter_map(|(var_id, mut places)| {
+            // Only consider the temporaries (and never the return value or
+            // the input arguments)
+            let var = locals.get(var_id).unwrap();
+            let is_temporary = var_id.to_usize() > arg_count && var.name.is_none();
+            if is_temporary && places.len() == 1 && !invalid.contains(&var_id) {
+                places.pop().map(|p| (var_id, p))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Replace `*tmp` with the place `tmp` borrows
+fn collapse_place(collapsible: &HashMap<VarId::Id, Place>, p: &mut Place) {
+    if let Some(borrowed) = collapsible.get(&p.var_id) {
+        let mut projection = p.projection.clone();
+        let deref = projection.pop_front();
+        assert!(deref == Some(ProjectionElem::Deref));
+        let mut np = borrowed.clone();
+        np.projection.append(projection);
+        *p = np;
+    }
+}
+
+fn transform_st(collapsible: &HashMap<VarId::Id, Place>, st: &mut Statement) {
+    // Remove the borrows and the drops of the temporaries
+    let is_removed = match as_local_borrow(&st.content) {
+        Some((var_id, _)) => collapsible.contains_key(&var_id),
+        None => collapsible
+            .keys()
+            .any(|var_id| is_drop_of(&st.content, *var_id)),
+    };
+    if is_removed {
+        st.content = RawStatement::Nop;
+    } else {
+        st.transform_shallow_places(&mut |p| collapse_place(collapsible, p));
+    }
+}
+
+fn transform_body(body: &mut ExprBody) {
+    let collapsible = compute_collapsible(body);
+    if !collapsible.is_empty() {
+        body.body
+            .transform(&mut |st| transform_st(&collapsible, st));
+    }
+}
+
+pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
+    par_iter_bodies(funs, globals, |name, b| {
+        trace!(
+            "# About to collapse the dereferences of references in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        transform_body(b);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collapse_deref_ref::*;
+    use crate::test_utils::*;
+    use crate::types::*;
+
+    fn deref(var_id: VarId::Id) -> Place {
+        Place {
+            var_id,
+            projection: im::Vector::from(vec![ProjectionElem::Deref]),
+        }
+    }
+
+    /// Return the rvalue of the first assignment to the return variable
+    fn get_returned_rvalue(st: &Statement) -> Option<&Rvalue> {
+        match &st.content {
+            RawStatement::Assign(p, rv) if p.var_id == VarId::ZERO => Some(rv),
+            RawStatement::Sequence(st1, st2) => {
+                get_returned_rvalue(st1).or_else(|| get_returned_rvalue(st2))
+            }
+            _ => None,
+        }
+    }
+
+    fn is_copy_of(rv: &Rvalue, p: &Place) -> bool {
+        match rv {
+            Rvalue::Use(Operand::Copy(p1)) => p1 == p,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_collapse_deref_of_ref() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let ref_ty = Ty::Ref(
+            ErasedRegion::Erased,
+            Box::new(u32_ty.clone()),
+            RefKind::Shared,
+        );
+        let ret = VarId::Id::new(0);
+        let x = VarId::Id::new(1);
+        let tmp = VarId::Id::new(2);
+        // ```
+        // tmp := &x;
+        // @return := copy *(tmp);
+        // drop tmp;
+        // return
+        // ```
+        let mut body = mk_body(
+            0,
+            vec![
+                mk_var(0, None, u32_ty.clone()),
+                mk_var(1, Some("x"), u32_ty.clone()),
+                mk_var(2, None, ref_ty),
+            ],
+            vec![
+                RawStatement::Assign(
+                    Place::new(tmp),
+                    Rvalue::Ref(Place::new(x), BorrowKind::Shared),
+                ),
+                RawStatement::Assign(Place::new(ret), Rvalue::Use(Operand::Copy(deref(tmp)))),
+                RawStatement::Drop(Place::new(tmp)),
+                RawStatement::Return,
+            ],
+        );
+        transform_body(&mut body);
+        let rv = get_returned_rvalue(&body.body).unwrap();
+        assert!(is_copy_of(rv, &Place::new(x)));
+    }
+
+    #[test]
+    fn test_keep_genuine_deref() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let ref_ty = Ty::Ref(
+            ErasedRegion::Erased,
+            Box::new(u32_ty.clone()),
+            RefKind::Shared,
+        );
+        let ret = VarId::Id::new(0);
+        let p = VarId::Id::new(1);
+        // ```
+        // @return := copy *(p); // `p` is an input argument
+        // return
+        // ```
+        let mut body = mk_body(
+            1,
+            vec![mk_var(0, None, u32_ty), mk_var(1, Some("p"), ref_ty)],
+            vec![
+                RawStatement::Assign(Place::new(ret), Rvalue::Use(Operand::Copy(deref(p)))),
+                RawStatement::Return,
+            ],
+        );
+        transform_body(&mut body);
+        let rv = get_returned_rvalue(&body.body).unwrap();
+        assert!(is_copy_of(rv, &deref(p)));
+    }
+}
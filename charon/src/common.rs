@@ -6,7 +6,9 @@ use im::Vector;
 use rustc_error_messages::MultiSpan;
 use rustc_errors::DiagnosticId;
 use rustc_session::Session;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::collections::HashMap;
 use std::iter::FromIterator;
 
 /// Our redefinition of Result - we don't care much about the I/O part.
@@ -214,6 +216,11 @@ pub fn serialize_collection<T: Serialize, I: IntoIterator<Item = T>, S: Serializ
     serialize_vec(&v, serializer)
 }
 
+/// Serialize an im::Vector as a sequence, from its front to its back.
+///
+/// For the projections, this means the projection elements are listed in the
+/// order in which they are applied to the variable (this is also the order in
+/// which we print them): see [deserialize_vector] for the converse.
 pub fn serialize_vector<T: Clone + Serialize, S: Serializer>(
     v: &Vector<T>,
     serializer: S,
@@ -221,6 +228,39 @@ pub fn serialize_vector<T: Clone + Serialize, S: Serializer>(
     serialize_collection(v.iter(), serializer)
 }
 
+/// Deserialize an im::Vector serialized with [serialize_vector]: the elements
+/// of the sequence are pushed at the back, so that we get the same order.
+pub fn deserialize_vector<'de, T: Clone + Deserialize<'de>, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vector<T>, D::Error> {
+    let v: Vec<T> = Deserialize::deserialize(deserializer)?;
+    Ok(Vector::from_iter(v))
+}
+
+/// Serialize a map as a vector of (key, value) pairs, sorted by key.
+///
+/// The iteration order of the `HashMap`s is not deterministic: we use this
+/// function for all the maps we serialize, to make sure the generated files
+/// are stable. It can be used with the
+/// `#[serde(serialize_with = "serialize_sorted_map")]` attribute, on fields
+/// which are maps or references to maps (serde then gives us a reference to
+/// the reference).
+pub fn serialize_sorted_map<M, K, V, S>(
+    map: &M,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    M: Borrow<HashMap<K, V>>,
+    K: Ord + Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    let map: &HashMap<K, V> = <M as Borrow<HashMap<K, V>>>::borrow(map);
+    let mut entries: Vec<(&K, &V)> = map.iter().collect();
+    entries.sort_by(|(k0, _), (k1, _)| k0.cmp(k1));
+    serialize_vec(&entries, serializer)
+}
+
 /// Wrapper to serialize std::vec::Vec
 ///
 /// We need this because serialization is implemented via the trait system.
@@ -243,7 +283,8 @@ impl<'a, T: Serialize> Serialize for VecSerializer<'a, T> {
     }
 }
 
-/// Wrapper to serialize vectors from im::Vector.
+/// Wrapper to serialize vectors from im::Vector. The elements are serialized
+/// from the front to the back (see [serialize_vector]).
 ///
 /// We need this because serialization is implemented via the trait system.
 pub struct VectorSerializer<'a, T: Clone> {
@@ -304,3 +345,32 @@ fn test_propagate_error() {
     assert!(res.is_err());
     assert!(sum == 6);
 }
+
+#[test]
+fn test_serialize_sorted_map() {
+    #[derive(Serialize)]
+    struct Wrapper {
+        #[serde(serialize_with = "serialize_sorted_map")]
+        map: HashMap<u32, String>,
+    }
+
+    #[derive(Serialize)]
+    struct RefWrapper<'a> {
+        #[serde(serialize_with = "serialize_sorted_map")]
+        map: &'a HashMap<u32, String>,
+    }
+
+    // The maps use different random states: their iteration orders differ
+    let mk_map = |keys: &mut dyn Iterator<Item = u32>| Wrapper {
+        map: HashMap::from_iter(keys.map(|k| (k, k.to_string()))),
+    };
+    let map0 = mk_map(&mut (0..32));
+    let map1 = mk_map(&mut (0..32).rev());
+    let s0 = serde_json::to_string(&map0).unwrap();
+    let s1 = serde_json::to_string(&map1).unwrap();
+    assert!(s0 == s1);
+    assert!(s0 == serde_json::to_string(&map0).unwrap());
+    assert!(s0.starts_with("{\"map\":[[0,\"0\"],[1,\"1\"],[2,\"2\"],"));
+    let s2 = serde_json::to_string(&RefWrapper { map: &map1.map }).unwrap();
+    assert!(s2 == s0);
+}
@@ -0,0 +1,400 @@
+//! Eliminate the common subexpressions inside the blocks of statements.
+//!
+//! If a pure rvalue is computed twice in the same block, and its operands
+//! were not modified in between, we reuse the result of the first computation:
+//! ```text
+//! x := copy a + copy b;
+//! ...
+//! y := copy a + copy b;
+//! ```
+//! becomes:
+//! ```text
+//! x := copy a + copy b;
+//! ...
+//! y := copy x;
+//! ```
+//!
+//! We only consider the unary and binary operations and the lengths, whose
+//! operands are copied or constant. We are conservative: the available
+//! expressions are forgotten when we exit a block (at the switches and the
+//! loops), at the function calls, and whenever we write through a pointer.
+
+use crate::expressions::*;
+use crate::llbc_ast::{
+    new_sequence, CtxNames, FunDecls, GlobalDecls, RawStatement, Statement, Switch,
+};
+use crate::llbc_visitor::AstVisitor;
+use crate::types::RefKind;
+use crate::ullbc_ast::par_iter_bodies;
+use crate::values::*;
+use std::collections::HashMap;
+use take_mut::take;
+
+/// The expressions available in the current block: we map the rvalues to the
+/// locals which contain their values.
+type Available = HashMap<Rvalue, VarId::Id>;
+
+/// The facts about the values of the locals we accumulate while exploring a
+/// block (the available expressions here, the copies in
+/// [crate::propagate_copies]), and which the updates invalidate
+pub(crate) trait BlockFacts {
+    /// Forget the facts which depend on a local, because it is updated
+    fn kill_var(&mut self, var_id: VarId::Id);
+
+    /// Forget all the facts, because we don't know what is updated
+    fn kill_all(&mut self);
+}
+
+/// Return true if the projection element goes through a pointer
+fn is_deref(pe: &ProjectionElem) -> bool {
+    match pe {
+        ProjectionElem::Deref
+        | ProjectionElem::DerefBox
+        | ProjectionElem::DerefRawPtr
+        | ProjectionElem::DerefPtrUnique
+        | ProjectionElem::DerefPtrNonNull
+        | ProjectionElem::DerefRc => true,
+        ProjectionElem::Field(_, _)
+        | ProjectionElem::Index(_)
+        | ProjectionElem::ConstantIndex { .. } => false,
+    }
+}
+
+/// Return true if we can read the place several times and get the same value,
+/// as long as the locals it uses are not updated. We accept the dereferences
+/// of references and boxes (the values they point to can only be updated
+/// through those pointers) but not the raw pointers.
+pub(crate) fn is_stable_place(p: &Place) -> bool {
+    p.projection.iter().all(|pe| {
+        !matches!(
+            pe,
+            ProjectionElem::DerefRawPtr
+                | ProjectionElem::DerefPtrUnique
+                | ProjectionElem::DerefPtrNonNull
+        )
+    })
+}
+
+fn is_pure_operand(op: &Operand) -> bool {
+    match op {
+        Operand::Copy(p) => is_stable_place(p),
+        Operand::Const(_, _) => true,
+        Operand::Move(_) => false,
+    }
+}
+
+/// Return true if the rvalue can be shared between several computations
+fn is_pure_rvalue(rv: &Rvalue) -> bool {
+    match rv {
+        Rvalue::UnaryOp(_, op) | Rvalue::Cast(_, op, _, _) => is_pure_operand(op),
+        Rvalue::BinaryOp(_, op1, op2) => is_pure_operand(op1) && is_pure_operand(op2),
+        Rvalue::Len(p) | Rvalue::IsVariant(p, _) => is_stable_place(p),
+        Rvalue::Use(_)
+        | Rvalue::Unsize(_, _, _)
+        | Rvalue::Ref(_, _)
+        | Rvalue::RawPtr(_, _)
+        | Rvalue::Discriminant(_)
+        | Rvalue::Aggregate(_, _)
+        | Rvalue::Slice { .. }
+        | Rvalue::Global(_)
+        | Rvalue::GlobalRef(_, _)
+        | Rvalue::FnPtr(_) => false,
+    }
+}
+
+/// Check if a local is used (see [rvalue_uses_var])
+struct UsesVar {
+    var_id: VarId::Id,
+    found: bool,
+}
+
+impl AstVisitor for UsesVar {
+    fn visit_var_id(&mut self, id: &VarId::Id) {
+        self.found |= *id == self.var_id;
+    }
+}
+
+/// Return true if the value of the rvalue depends on the local
+fn rvalue_uses_var(rv: &Rvalue, var_id: VarId::Id) -> bool {
+    let mut v = UsesVar {
+        var_id,
+        found: false,
+    };
+    v.visit_rvalue(rv);
+    v.found
+}
+
+pub(crate) fn operand_uses_var(op: &Operand, var_id: VarId::Id) -> bool {
+    let mut v = UsesVar {
+        var_id,
+        found: false,
+    };
+    v.visit_operand(op);
+    v.found
+}
+
+impl BlockFacts for Available {
+    fn kill_var(&mut self, var_id: VarId::Id) {
+        self.retain(|rv, holder| *holder != var_id && !rvalue_uses_var(rv, var_id));
+    }
+
+    fn kill_all(&mut self) {
+        self.clear()
+    }
+}
+
+/// The place is written to
+fn kill_place<F: BlockFacts>(available: &mut F, p: &Place) {
+    if p.projection.iter().any(is_deref) {
+        // We don't know which values are updated
+        available.kill_all();
+    } else {
+        available.kill_var(p.var_id);
+    }
+}
+
+/// The operand is read: if it is moved, we can't use the moved local anymore
+fn kill_operand<F: BlockFacts>(available: &mut F, op: &Operand) {
+    if let Operand::Move(p) = op {
+        available.kill_var(p.var_id);
+    }
+}
+
+/// Update the facts after a statement (which doesn't contain sub-statements)
+/// has been evaluated.
+pub(crate) fn kill_statement<F: BlockFacts>(available: &mut F, st: &RawStatement) {
+    match st {
+        RawStatement::Assign(p, rv) => {
+            match rv {
+                Rvalue::Use(op)
+                | Rvalue::UnaryOp(_, op)
+                | Rvalue::Unsize(op, _, _)
+                | Rvalue::Cast(_, op, _, _) => kill_operand(available, op),
+                Rvalue::BinaryOp(_, op1, op2) => {
+                    kill_operand(available, op1);
+                    kill_operand(available, op2);
+                }
+                Rvalue::Aggregate(_, ops) => {
+                    for op in ops {
+                        kill_operand(available, op);
+                    }
+                }
+                // The mutable borrows may be used to update the borrowed place
+                Rvalue::Ref(bp, kind) if *kind != BorrowKind::Shared => kill_place(available, bp),
+                Rvalue::RawPtr(bp, RefKind::Mut) => kill_place(available, bp),
+                Rvalue::Slice {
+                    base,
+                    from,
+                    to,
+                    kind,
+                } => {
+                    kill_operand(available, from);
+                    kill_operand(available, to);
+                    if *kind != BorrowKind::Shared {
+                        kill_place(available, base);
+                    }
+                }
+                Rvalue::Ref(_, _)
+                | Rvalue::RawPtr(_, RefKind::Shared)
+                | Rvalue::Discriminant(_)
+                | Rvalue::Len(_)
+                | Rvalue::IsVariant(_, _)
+                | Rvalue::Global(_)
+                | Rvalue::GlobalRef(_, _)
+                | Rvalue::FnPtr(_) => (),
+            }
+            kill_place(available, p);
+        }
+        RawStatement::CompoundAssign(p, _, rhs) => {
+            kill_operand(available, rhs);
+            kill_place(available, p);
+        }
+        RawStatement::SetDiscriminant(p, _) | RawStatement::Drop(p) => kill_place(available, p),
+        RawStatement::Assert(assert) => kill_operand(available, &assert.cond),
+        // We don't know what the functions do
+        RawStatement::Call(_) => available.kill_all(),
+        RawStatement::FakeRead(_)
+        | RawStatement::Panic
+        | RawStatement::Return
+        | RawStatement::Break(_)
+        | RawStatement::Continue(_)
+        | RawStatement::Nop => (),
+        RawStatement::Sequence(_, _)
+        | RawStatement::Switch(_)
+        | RawStatement::Loop(_, _)
+        | RawStatement::While(_, _, _, _) => unreachable!(),
+    }
+}
+
+/// Transform a statement which doesn't contain sub-statements
+fn transform_leaf(available: &mut Available, st: &mut RawStatement) {
+    // Reuse the result of a previous computation, if there is one
+    if let RawStatement::Assign(p, rv) = st {
+        if let Some(holder) = available.get(rv) {
+            if *holder != p.var_id {
+                *rv = Rvalue::Use(Operand::Copy(Place::new(*holder)));
+            }
+        }
+    }
+
+    kill_statement(available, st);
+
+    // Register the computation (if the destination is not used by the
+    // rvalue itself)
+    if let RawStatement::Assign(p, rv) = st {
+        if p.projection.is_empty() && is_pure_rvalue(rv) && !rvalue_uses_var(rv, p.var_id) {
+            available.insert(rv.clone(), p.var_id);
+        }
+    }
+}
+
+fn transform_st(available: &mut Available, mut st: Statement) -> Statement {
+    st.content = match st.content {
+        RawStatement::Sequence(st1, st2) => {
+            let st1 = transform_st(available, *st1);
+            let st2 = transform_st(available, *st2);
+            new_sequence(st1, st2).content
+        }
+        RawStatement::Switch(switch) => {
+            // The branches are separate blocks
+            let switch = match switch {
+                Switch::If(op, st1, st2) => Switch::If(
+                    op,
+                    Box::new(transform_st(&mut Available::new(), *st1)),
+                    Box::new(transform_st(&mut Available::new(), *st2)),
+                ),
+                Switch::SwitchInt(op, int_ty, branches, otherwise) => Switch::SwitchInt(
+                    op,
+                    int_ty,
+                    branches
+                        .into_iter()
+                        .map(|(v, st)| (v, transform_st(&mut Available::new(), st)))
+                        .collect(),
+                    Box::new(transform_st(&mut Available::new(), *otherwise)),
+                ),
+                Switch::Match(p, branches, otherwise, provenance) => Switch::Match(
+                    p,
+                    branches
+                        .into_iter()
+                        .map(|(v, st)| (v, transform_st(&mut Available::new(), st)))
+                        .collect(),
+                    Box::new(transform_st(&mut Available::new(), *otherwise)),
+                    provenance,
+                ),
+            };
+            available.clear();
+            RawStatement::Switch(switch)
+        }
+        RawStatement::Loop(st, invariants) => {
+            let st = transform_st(&mut Available::new(), *st);
+            available.clear();
+            RawStatement::Loop(Box::new(st), invariants)
+        }
+        RawStatement::While(cond, op, body, invariants) => {
+            // The condition is evaluated at every iteration: it is a separate
+            // block, like the body
+            let cond = transform_st(&mut Available::new(), *cond);
+            let body = transform_st(&mut Available::new(), *body);
+            available.clear();
+            RawStatement::While(Box::new(cond), op, Box::new(body), invariants)
+        }
+        mut content => {
+            transform_leaf(available, &mut content);
+            content
+        }
+    };
+    st
+}
+
+/// `fmt_ctx` is used for pretty-printing purposes.
+pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
+    par_iter_bodies(funs, globals, |name, b| {
+        trace!(
+            "# About to eliminate the common subexpressions in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        take(&mut b.body, |st| transform_st(&mut Available::new(), st));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_subexpressions::*;
+    use crate::test_utils::*;
+    use crate::types::*;
+
+    fn add(a: VarId::Id, b: VarId::Id) -> Rvalue {
+        Rvalue::BinaryOp(
+            BinOp::Add,
+            Operand::Copy(Place::new(a)),
+            Operand::Copy(Place::new(b)),
+        )
+    }
+
+    #[test]
+    fn test_duplicated_add() {
+        let a = VarId::Id::new(1);
+        let b = VarId::Id::new(2);
+        let x = VarId::Id::new(3);
+        let y = VarId::Id::new(4);
+        let z = VarId::Id::new(5);
+        // ```
+        // x := copy a + copy b;
+        // y := copy a + copy b;
+        // a := const 0;
+        // z := copy a + copy b;
+        // return
+        // ```
+        let zero = Operand::Const(
+            Ty::Integer(IntegerTy::U32),
+            OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(ScalarValue::U32(0))),
+        );
+        let body = mk_statements(vec![
+            RawStatement::Assign(Place::new(x), add(a, b)),
+            RawStatement::Assign(Place::new(y), add(a, b)),
+            RawStatement::Assign(Place::new(a), Rvalue::Use(zero)),
+            RawStatement::Assign(Place::new(z), add(a, b)),
+            RawStatement::Return,
+        ]);
+        let body = transform_st(&mut Available::new(), body);
+
+        // ```
+        // x := copy a + copy b;
+        // y := copy x;
+        // a := const 0;
+        // z := copy a + copy b; // `a` was updated
+        // return
+        // ```
+        let sts = flatten(&body);
+        assert!(sts.len() == 5);
+        assert!(matches!(sts[0], RawStatement::Assign(_, rv) if *rv == add(a, b)));
+        assert!(
+            matches!(sts[1], RawStatement::Assign(p, Rvalue::Use(Operand::Copy(p1))) if *p == Place::new(y) && *p1 == Place::new(x))
+        );
+        assert!(matches!(sts[3], RawStatement::Assign(_, rv) if *rv == add(a, b)));
+    }
+
+    #[test]
+    fn test_mut_borrow_invalidates() {
+        let a = VarId::Id::new(1);
+        let b = VarId::Id::new(2);
+        let x = VarId::Id::new(3);
+        let y = VarId::Id::new(4);
+        let r = VarId::Id::new(5);
+        // ```
+        // x := copy a + copy b;
+        // r := &mut a;
+        // y := copy a + copy b;
+        // return
+        // ```
+        let body = mk_statements(vec![
+            RawStatement::Assign(Place::new(x), add(a, b)),
+            RawStatement::Assign(Place::new(r), Rvalue::Ref(Place::new(a), BorrowKind::Mut)),
+            RawStatement::Assign(Place::new(y), add(a, b)),
+            RawStatement::Return,
+        ]);
+        let body = transform_st(&mut Available::new(), body);
+        let sts = flatten(&body);
+        assert!(matches!(sts[2], RawStatement::Assign(_, rv) if *rv == add(a, b)));
+    }
+}
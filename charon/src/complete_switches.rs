@@ -0,0 +1,235 @@
+//! Simplify the switches whose scrutinee is of a type with few values.
+//!
+//! The switches over a boolean in the MIR are translated to [Switch::If] (see
+//! [crate::translate_functions_to_ullbc]), but the switches built by the
+//! user passes may still switch over a boolean with [Switch::SwitchInt]:
+//! ```text
+//! switch move b {
+//!   1 => { ... },
+//!   _ => { ... }
+//! }
+//! ```
+//! We rewrite those switches to `if move b { ... } else { ... }`.
+//!
+//! Also, when the branches of a switch cover all the values of its scrutinee
+//! (all the variants of an enumeration, for instance), the otherwise branch
+//! is unreachable. If there is a single branch, the switch doesn't test
+//! anything, and we replace it with its branch:
+//! ```text
+//! match x {
+//!   0 | 1 => { ... },
+//!   _ => { panic }
+//! }
+//! ```
+//! becomes `...`, if `x` is of an enumeration with two variants. Note that we
+//! can't tell from the LLBC whether an otherwise branch is unreachable or
+//! genuinely panics (both are translated to `panic`): we only use the type of
+//! the scrutinee.
+//!
+//! We need to perform this transformation after [crate::remove_read_discriminant],
+//! which introduces the matches.
+
+use crate::expressions::*;
+use crate::llbc_ast::{
+    transform_statements, CtxNames, ExprBody, FunDecls, GlobalDecls, RawStatement, Statement,
+    Switch,
+};
+use crate::types::*;
+use crate::ullbc_ast::{iter_function_bodies, iter_global_bodies};
+use crate::values::*;
+use std::collections::HashSet;
+
+fn operand_type(type_defs: &TypeDecls, body: &ExprBody, op: &Operand) -> ETy {
+    match op {
+        Operand::Copy(p) | Operand::Move(p) => body.place_type(type_defs, p),
+        Operand::Const(ty, _) => ty.clone(),
+    }
+}
+
+/// The number of values of a type we switch over with [Switch::SwitchInt], if
+/// it is small enough for a switch to cover all of them
+fn num_int_values(ty: &ETy) -> Option<usize> {
+    match ty {
+        Ty::Integer(IntegerTy::U8 | IntegerTy::I8) => Some(256),
+        _ => None,
+    }
+}
+
+/// The number of variants of the enumeration we match over with
+/// [Switch::Match]
+fn num_variants(type_defs: &TypeDecls, ty: &ETy) -> Option<usize> {
+    match ty {
+        Ty::Adt(TypeId::Adt(id), _, _) => match &type_defs.get_type_def(*id)?.kind {
+            TypeDeclKind::Enum(variants) => Some(variants.len()),
+            _ => None,
+        },
+        Ty::Adt(TypeId::Assumed(AssumedTy::Option | AssumedTy::ControlFlow), _, _) => Some(2),
+        _ => None,
+    }
+}
+
+fn is_zero(v: &ScalarValue) -> bool {
+    if v.is_uint() {
+        matches!(v.as_uint(), Ok(0))
+    } else {
+        matches!(v.as_int(), Ok(0))
+    }
+}
+
+/// Rewrite a switch over a boolean to an `if`
+fn switch_int_to_if(
+    op: Operand,
+    branches: Vec<(Vec<ScalarValue>, Statement)>,
+    otherwise: Statement,
+) -> Switch {
+    let mut then_st = None;
+    let mut else_st = None;
+    for (values, st) in branches {
+        // A branch may cover both values, in which case we duplicate it
+        if then_st.is_none() && values.iter().any(|v| !is_zero(v)) {
+            then_st = Some(st.clone());
+        }
+        if else_st.is_none() && values.iter().any(is_zero) {
+            else_st = Some(st);
+        }
+    }
+    let (then_st, else_st) = match (then_st, else_st) {
+        (Some(then_st), Some(else_st)) => (then_st, else_st),
+        (Some(then_st), None) => (then_st, otherwise),
+        (None, Some(else_st)) => (otherwise, else_st),
+        (None, None) => (otherwise.clone(), otherwise),
+    };
+    Switch::If(op, Box::new(then_st), Box::new(else_st))
+}
+
+fn transform_st(type_defs: &TypeDecls, body: &ExprBody, mut st: Statement) -> Statement {
+    let switch = match st.content {
+        RawStatement::Switch(switch) => switch,
+        content => {
+            st.content = content;
+            return st;
+        }
+    };
+    st.content = match switch {
+        Switch::SwitchInt(op, _, branches, otherwise)
+            if operand_type(type_defs, body, &op).is_bool() =>
+        {
+            RawStatement::Switch(switch_int_to_if(op, branches, *otherwise))
+        }
+        Switch::SwitchInt(op, int_ty, mut branches, otherwise) => {
+            let ty = operand_type(type_defs, body, &op);
+            let values: HashSet<&ScalarValue> = branches.iter().flat_map(|(v, _)| v).collect();
+            if branches.len() == 1 && num_int_values(&ty) == Some(values.len()) {
+                return branches.pop().unwrap().1;
+            }
+            RawStatement::Switch(Switch::SwitchInt(op, int_ty, branches, otherwise))
+        }
+        Switch::Match(p, mut branches, otherwise, provenance) => {
+            let ty = body.place_type(type_defs, &p);
+            let variants: HashSet<&VariantId::Id> = branches.iter().flat_map(|(v, _)| v).collect();
+            if branches.len() == 1 && num_variants(type_defs, &ty) == Some(variants.len()) {
+                return branches.pop().unwrap().1;
+            }
+            RawStatement::Switch(Switch::Match(p, branches, otherwise, provenance))
+        }
+        switch @ Switch::If(_, _, _) => RawStatement::Switch(switch),
+    };
+    st
+}
+
+fn transform_body(type_defs: &TypeDecls, body: &mut ExprBody) {
+    // We need to compute the types of the scrutinees while transforming the
+    // body: we temporarily move the statements out of it
+    let nop = Statement::new(body.body.meta, RawStatement::Nop);
+    let st = std::mem::replace(&mut body.body, nop);
+    let st = transform_statements(&mut |st| transform_st(type_defs, body, st), st);
+    body.body = st;
+}
+
+/// `fmt_ctx` is used for pretty-printing purposes.
+pub fn transform(
+    fmt_ctx: &CtxNames<'_>,
+    type_defs: &TypeDecls,
+    funs: &mut FunDecls,
+    globals: &mut GlobalDecls,
+) {
+    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+        trace!(
+            "# About to simplify the switches in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        transform_body(type_defs, b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::complete_switches::*;
+    use crate::llbc_builder::*;
+
+    #[test]
+    fn test_switch_over_bool() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let mut b = BodyBuilder::new(u32_ty.clone(), vec![Ty::Bool]);
+        let x = b.arg(0);
+        let ret = Place::new(b.ret());
+        let mk_u32 = |v| {
+            Rvalue::Use(Operand::Const(
+                u32_ty.clone(),
+                OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(ScalarValue::U32(v))),
+            ))
+        };
+        // ```
+        // switch copy x { 0 => { @0 := const 0 }, _ => { @0 := const 1 } }
+        // ```
+        let body = b.build(Switch::switch_int(
+            Operand::Copy(Place::new(x)),
+            IntegerTy::U8,
+            vec![(
+                vec![ScalarValue::U8(0)],
+                Block::new().assign(ret.clone(), mk_u32(0)).end(),
+            )],
+            Block::new().assign(ret, mk_u32(1)).end(),
+        ));
+        let st = transform_st(&TypeDecls::new(), &body, body.body.clone());
+
+        // ```
+        // if copy x { @0 := const 1 } else { @0 := const 0 }
+        // ```
+        let (op, st1, st2) = st.content.as_switch().as_if();
+        assert!(*op == Operand::Copy(Place::new(x)));
+        let (_, rv1) = st1.content.as_sequence().0.content.as_assign();
+        let (_, rv2) = st2.content.as_sequence().0.content.as_assign();
+        assert!(*rv1 == mk_u32(1) && *rv2 == mk_u32(0));
+    }
+
+    #[test]
+    fn test_exhaustive_match() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let option_ty = Ty::Adt(
+            TypeId::Assumed(AssumedTy::Option),
+            im::Vector::new(),
+            im::vector![u32_ty.clone()],
+        );
+        let mut b = BodyBuilder::new(u32_ty, vec![option_ty]);
+        let x = Place::new(b.arg(0));
+        let none = VariantId::Id::new(0);
+        let some = VariantId::Id::new(1);
+        let mk_match = |variants| {
+            Switch::match_variants(
+                x.clone(),
+                vec![(variants, Block::new().drop(x.clone()).end())],
+                Block::new().panic(),
+            )
+        };
+
+        // The branch covers all the variants: we remove the match
+        let body = b.build(mk_match(vec![none, some]));
+        let st = transform_st(&TypeDecls::new(), &body, body.body.clone());
+        assert!(st.content.is_sequence());
+
+        // The otherwise branch is reachable
+        let st = transform_st(&TypeDecls::new(), &body, mk_match(vec![some]));
+        assert!(st.content.is_switch());
+    }
+}
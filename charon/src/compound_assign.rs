@@ -0,0 +1,121 @@
+//! Recognize the compound assignments (`x += y`), which rustc desugars to
+//! regular assignments:
+//! ```text
+//! x := copy x + copy y;
+//! ```
+//! We rewrite those assignments to:
+//! ```text
+//! x += copy y;
+//! ```
+//!
+//! Note that we perform this transformation after [crate::simplify_ops]: the
+//! checked binops introduced by rustc have then been simplified to the above
+//! shape. The functions in which the overflow checks were reinserted (see
+//! [crate::insert_overflow_checks]) compute the operation in a temporary
+//! before checking it, and are thus left untouched: this is on purpose, as we
+//! want to keep the overflow checks explicit.
+
+use crate::expressions::*;
+use crate::llbc_ast::{
+    transform_statements, CtxNames, FunDecls, GlobalDecls, RawStatement, Statement,
+};
+use crate::ullbc_ast::{iter_function_bodies, iter_global_bodies};
+use take_mut::take;
+
+/// Return true if there is a compound assignment operator for the binop
+/// (`==` or `<`, for instance, don't have one).
+fn has_compound_assign(binop: BinOp) -> bool {
+    match binop {
+        BinOp::BitXor
+        | BinOp::BitAnd
+        | BinOp::BitOr
+        | BinOp::Div
+        | BinOp::Rem
+        | BinOp::Add
+        | BinOp::Sub
+        | BinOp::Mul
+        | BinOp::Shl
+        | BinOp::Shr => true,
+        BinOp::Eq | BinOp::Lt | BinOp::Le | BinOp::Ne | BinOp::Ge | BinOp::Gt | BinOp::Offset => {
+            false
+        }
+    }
+}
+
+fn transform_st(mut st: Statement) -> Statement {
+    st.content = match st.content {
+        RawStatement::Assign(p, Rvalue::BinaryOp(binop, Operand::Copy(p1), rhs))
+            if p == p1 && has_compound_assign(binop) =>
+        {
+            RawStatement::CompoundAssign(p, binop, rhs)
+        }
+        content => content,
+    };
+    st
+}
+
+/// `fmt_ctx` is used for pretty-printing purposes.
+pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
+    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+        trace!(
+            "# About to recognize the compound assignments in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        take(&mut b.body, |st| {
+            transform_statements(&mut transform_st, st)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compound_assign::*;
+    use crate::llbc_ast::new_sequence;
+    use crate::meta::Meta;
+    use crate::types::*;
+    use crate::values::*;
+
+    fn mk_u32(v: u32) -> Operand {
+        Operand::Const(
+            Ty::Integer(IntegerTy::U32),
+            OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(ScalarValue::U32(v))),
+        )
+    }
+
+    #[test]
+    fn test_add_assign() {
+        let x = VarId::Id::new(1);
+        let y = VarId::Id::new(2);
+        // ```
+        // x := copy x + const 1;
+        // y := copy x < const 1;
+        // ```
+        let st = new_sequence(
+            Statement::new(
+                Meta::dummy(),
+                RawStatement::Assign(
+                    Place::new(x),
+                    Rvalue::BinaryOp(BinOp::Add, Operand::Copy(Place::new(x)), mk_u32(1)),
+                ),
+            ),
+            Statement::new(
+                Meta::dummy(),
+                RawStatement::Assign(
+                    Place::new(y),
+                    Rvalue::BinaryOp(BinOp::Lt, Operand::Copy(Place::new(x)), mk_u32(1)),
+                ),
+            ),
+        );
+        let st = transform_statements(&mut transform_st, st);
+
+        // ```
+        // x += const 1;
+        // y := copy x < const 1;
+        // ```
+        let (st1, st2) = st.content.as_sequence();
+        assert!(
+            matches!(&st1.content, RawStatement::CompoundAssign(p, BinOp::Add, rhs) if *p == Place::new(x) && *rhs == mk_u32(1))
+        );
+        assert!(st2.content.is_assign());
+    }
+}
@@ -0,0 +1,79 @@
+//! The configuration file of a crate: `charon.toml`, in the crate root (next
+//! to the `Cargo.toml` file). For now, it only allows to mark modules as
+//! opaque, in addition to the modules given with the `--opaque` option:
+//! ```toml
+//! [opaque]
+//! # The modules are given by their paths relative to the crate root
+//! modules = ["trusted", "ffi::bindings"]
+//! ```
+//! We extract the signatures of the declarations in the opaque modules, but
+//! not their bodies.
+
+use crate::common::*;
+use serde::Deserialize;
+use std::path::Path;
+
+pub const CONFIG_FILE_NAME: &str = "charon.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub opaque: OpaqueConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpaqueConfig {
+    /// The opaque modules
+    #[serde(default)]
+    pub modules: Vec<String>,
+}
+
+impl Config {
+    pub fn parse(content: &str) -> std::result::Result<Config, String> {
+        toml::from_str(content).map_err(|err| err.to_string())
+    }
+
+    /// Load the configuration file from the crate root. We use the default
+    /// configuration if there is no such file.
+    pub fn load(crate_root: &Path) -> Result<Config> {
+        let path = crate_root.join(CONFIG_FILE_NAME);
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let content = match std::fs::read_to_string(&path) {
+            std::result::Result::Ok(content) => content,
+            std::result::Result::Err(err) => {
+                error!("Could not read {:?}: {}", path, err);
+                return Err(());
+            }
+        };
+        match Config::parse(&content) {
+            std::result::Result::Ok(config) => {
+                trace!("Loaded the configuration file {:?}: {:?}", path, config);
+                Ok(config)
+            }
+            std::result::Result::Err(err) => {
+                error!("Invalid configuration file {:?}: {}", path, err);
+                Err(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse("[opaque]\nmodules = [\"trusted\", \"ffi::bindings\"]\n");
+        let config = config.unwrap();
+        assert!(config.opaque.modules == vec!["trusted", "ffi::bindings"]);
+
+        // All the sections are optional, but we reject the unknown fields
+        assert!(Config::parse("").unwrap().opaque.modules.is_empty());
+        assert!(Config::parse("[opaque]\nmodule = [\"trusted\"]\n").is_err());
+    }
+}
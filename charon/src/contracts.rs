@@ -0,0 +1,657 @@
+//! The contracts of the functions: the pre and postconditions the users
+//! write with the `#[charon::requires(...)]` and `#[charon::ensures(...)]`
+//! attributes.
+//!
+//! We parse the conditions to a small language of specification expressions
+//! ([SpecExpr]) and export them with the functions (see [FunContract]), so
+//! that the backends don't have to parse the source files. The conditions
+//! are Rust expressions which may refer to the arguments of the function
+//! and, in the postconditions, to its result with `result`:
+//! ```text
+//! #[charon::requires(x < u32::MAX)]
+//! #[charon::ensures(result == x + 1)]
+//! fn incr(x: u32) -> u32 { x + 1 }
+//! ```
+//! The users can also give the invariants of the loops, with the
+//! `#[charon::invariant(...)]` attribute (several times if needed). The
+//! invariants may refer to the local variables in scope:
+//! ```text
+//! let mut i = 0;
+//! #[charon::invariant(i <= n)]
+//! while i < n { i += 1; }
+//! ```
+//! We attach them to the loops of the LLBC (see
+//! [crate::llbc_ast::RawStatement::Loop]). The MIR marks the entry of every
+//! loop with a `FalseUnwind` terminator, whose span is the span of the loop
+//! expression, which is where the compiler puts the attributes: we use it to
+//! find the loop heads (see [crate::ullbc_ast::Terminator::loop_invariants]).
+//! Note that the optimized MIR doesn't have those terminators anymore: we
+//! lose the invariants with `--mir_optimized`.
+//!
+//! We check that the conditions are well-formed when registering the
+//! declarations (see [crate::register]).
+#![allow(dead_code)]
+
+use crate::expressions::{BinOp, UnOp};
+use crate::tool_attributes;
+use crate::types::IntegerTy;
+use crate::values::VarId;
+use rustc_ast::ast::{AttrKind, Attribute};
+use rustc_hir::def_id::{DefId, LocalDefId};
+use rustc_hir::intravisit::{self, Visitor};
+use rustc_hir::{Expr, ExprKind};
+use rustc_middle::ty::TyCtxt;
+use rustc_span::Span;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A specification expression
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpecExpr {
+    /// An argument of the function, or its result. We use the identifiers of
+    /// the locals of the body: the result is the local 0, and the arguments
+    /// are the locals 1, 2, etc.
+    Var(VarId::Id),
+    Bool(bool),
+    /// An integer literal, with the type given by its suffix if it has one
+    /// (`1u32`). As for the scalar values, we serialize the integer as a
+    /// string (without the underscores), because it may not fit in the
+    /// integers of the consumers.
+    Int(String, Option<IntegerTy>),
+    UnaryOp(UnOp, Box<SpecExpr>),
+    BinaryOp(BinOp, Box<SpecExpr>, Box<SpecExpr>),
+    /// The lazy boolean operators `&&` and `||`, which don't have a
+    /// counterpart in [BinOp]
+    And(Box<SpecExpr>, Box<SpecExpr>),
+    Or(Box<SpecExpr>, Box<SpecExpr>),
+    /// `*e`
+    Deref(Box<SpecExpr>),
+    /// A field of a structure or of a tuple: `e.f`, `e.0`
+    Field(Box<SpecExpr>, String),
+    /// `e1[e2]`
+    Index(Box<SpecExpr>, Box<SpecExpr>),
+    /// A constant or a function called with a path: `u32::MAX`, `f(x)`,
+    /// `Vec::len(v)`. We don't resolve the paths: the backends interpret
+    /// them.
+    Path(Vec<String>),
+    Call(Vec<String>, Vec<SpecExpr>),
+    /// `e.f(args)`
+    MethodCall(Box<SpecExpr>, String, Vec<SpecExpr>),
+}
+
+impl SpecExpr {
+    /// Apply a function to the variables of the expression
+    pub fn for_each_var(&self, f: &mut impl FnMut(&VarId::Id)) {
+        match self {
+            SpecExpr::Var(id) => f(id),
+            SpecExpr::Bool(_) | SpecExpr::Int(_, _) | SpecExpr::Path(_) => (),
+            SpecExpr::UnaryOp(_, e) | SpecExpr::Deref(e) | SpecExpr::Field(e, _) => {
+                e.for_each_var(f)
+            }
+            SpecExpr::BinaryOp(_, e1, e2)
+            | SpecExpr::And(e1, e2)
+            | SpecExpr::Or(e1, e2)
+            | SpecExpr::Index(e1, e2) => {
+                e1.for_each_var(f);
+                e2.for_each_var(f);
+            }
+            SpecExpr::Call(_, args) => args.iter().for_each(|e| e.for_each_var(f)),
+            SpecExpr::MethodCall(e, _, args) => {
+                e.for_each_var(f);
+                args.iter().for_each(|e| e.for_each_var(f));
+            }
+        }
+    }
+
+    pub fn for_each_var_mut(&mut self, f: &mut impl FnMut(&mut VarId::Id)) {
+        match self {
+            SpecExpr::Var(id) => f(id),
+            SpecExpr::Bool(_) | SpecExpr::Int(_, _) | SpecExpr::Path(_) => (),
+            SpecExpr::UnaryOp(_, e) | SpecExpr::Deref(e) | SpecExpr::Field(e, _) => {
+                e.for_each_var_mut(f)
+            }
+            SpecExpr::BinaryOp(_, e1, e2)
+            | SpecExpr::And(e1, e2)
+            | SpecExpr::Or(e1, e2)
+            | SpecExpr::Index(e1, e2) => {
+                e1.for_each_var_mut(f);
+                e2.for_each_var_mut(f);
+            }
+            SpecExpr::Call(_, args) => args.iter_mut().for_each(|e| e.for_each_var_mut(f)),
+            SpecExpr::MethodCall(e, _, args) => {
+                e.for_each_var_mut(f);
+                args.iter_mut().for_each(|e| e.for_each_var_mut(f));
+            }
+        }
+    }
+}
+
+/// A pre or postcondition, or a loop invariant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Spec {
+    /// The condition, as written in the source code
+    pub source: String,
+    pub expr: SpecExpr,
+}
+
+/// The contract of a function: the preconditions the callers must
+/// establish, and the postconditions the function establishes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FunContract {
+    pub requires: Vec<Spec>,
+    pub ensures: Vec<Spec>,
+}
+
+impl FunContract {
+    pub fn is_empty(&self) -> bool {
+        self.requires.is_empty() && self.ensures.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Int(String, Option<IntegerTy>),
+    /// The punctuation, including the operators made of several characters
+    /// (`==`, `&&`, `::`, ...)
+    Punct(&'static str),
+}
+
+const PUNCTS: [&str; 28] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<<", ">>", "::", "+", "-", "*", "/", "%", "&", "|", "^",
+    "!", "<", ">", "(", ")", "[", "]", ",", ".", "{", "}",
+];
+
+fn integer_suffix(suffix: &str) -> Option<IntegerTy> {
+    let ty = match suffix {
+        "isize" => IntegerTy::Isize,
+        "i8" => IntegerTy::I8,
+        "i16" => IntegerTy::I16,
+        "i32" => IntegerTy::I32,
+        "i64" => IntegerTy::I64,
+        "i128" => IntegerTy::I128,
+        "usize" => IntegerTy::Usize,
+        "u8" => IntegerTy::U8,
+        "u16" => IntegerTy::U16,
+        "u32" => IntegerTy::U32,
+        "u64" => IntegerTy::U64,
+        "u128" => IntegerTy::U128,
+        _ => return None,
+    };
+    Some(ty)
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = src.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() {
+            // An integer literal, which may contain underscores and end with
+            // a suffix
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let lit = rest[..len].replace('_', "");
+            let digits = lit.find(|c: char| !c.is_ascii_digit()).unwrap_or(lit.len());
+            let suffix = &lit[digits..];
+            let ty = if suffix.is_empty() {
+                None
+            } else {
+                Some(
+                    integer_suffix(suffix)
+                        .ok_or(format!("unsupported literal `{}`", &rest[..len]))?,
+                )
+            };
+            if lit[..digits].parse::<u128>().is_err() {
+                return Err(format!("the literal `{}` is too large", &rest[..len]));
+            }
+            tokens.push(Token::Int(lit[..digits].to_string(), ty));
+            len
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            len
+        } else {
+            match PUNCTS.iter().find(|p| rest.starts_with(*p)) {
+                Some(p) => {
+                    tokens.push(Token::Punct(*p));
+                    p.len()
+                }
+                None => return Err(format!("unexpected character `{c}`")),
+            }
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// The binary operators, by increasing precedence (as in Rust). The
+/// comparison operators are not associative.
+const BINOP_LEVELS: [&[&str]; 8] = [
+    &["||"],
+    &["&&"],
+    &["==", "!=", "<", "<=", ">", ">="],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+];
+const MUL_LEVEL: &[&str] = &["*", "/", "%"];
+
+fn mk_binop(op: &str, e1: SpecExpr, e2: SpecExpr) -> SpecExpr {
+    let (e1, e2) = (Box::new(e1), Box::new(e2));
+    let binop = match op {
+        "||" => return SpecExpr::Or(e1, e2),
+        "&&" => return SpecExpr::And(e1, e2),
+        "==" => BinOp::Eq,
+        "!=" => BinOp::Ne,
+        "<" => BinOp::Lt,
+        "<=" => BinOp::Le,
+        ">" => BinOp::Gt,
+        ">=" => BinOp::Ge,
+        "|" => BinOp::BitOr,
+        "^" => BinOp::BitXor,
+        "&" => BinOp::BitAnd,
+        "<<" => BinOp::Shl,
+        ">>" => BinOp::Shr,
+        "+" => BinOp::Add,
+        "-" => BinOp::Sub,
+        "*" => BinOp::Mul,
+        "/" => BinOp::Div,
+        "%" => BinOp::Rem,
+        _ => unreachable!(),
+    };
+    SpecExpr::BinaryOp(binop, e1, e2)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// The variables the condition may refer to. If several variables have
+    /// the same name, the last one shadows the others.
+    vars: &'a [(String, VarId::Id)],
+    /// `true` if we parse a postcondition, which may refer to `result`
+    postcondition: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_punct(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Punct(p)) => Some(*p),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let tok = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| "unexpected end of the condition".to_string())?;
+        self.pos += 1;
+        Ok(tok)
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        match self.next()? {
+            Token::Punct(p) if p == punct => Ok(()),
+            tok => Err(format!("expected `{punct}`, found {tok:?}")),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Token::Ident(id) => Ok(id),
+            tok => Err(format!("expected an identifier, found {tok:?}")),
+        }
+    }
+
+    fn binop(&mut self, level: usize) -> Result<SpecExpr, String> {
+        let (ops, next_level): (&[&str], Option<usize>) = if level < BINOP_LEVELS.len() {
+            (BINOP_LEVELS[level], Some(level + 1))
+        } else {
+            (MUL_LEVEL, None)
+        };
+        let operand = |parser: &mut Self| match next_level {
+            Some(level) => parser.binop(level),
+            None => parser.unary(),
+        };
+        let mut e = operand(self)?;
+        while let Some(op) = self.peek_punct().filter(|p| ops.contains(p)) {
+            self.pos += 1;
+            let e2 = operand(self)?;
+            e = mk_binop(op, e, e2);
+            // The comparisons are not associative: `a < b < c` is forbidden
+            if ops.contains(&"==") && self.peek_punct().filter(|p| ops.contains(p)).is_some() {
+                return Err("the comparison operators can't be chained".to_string());
+            }
+        }
+        Ok(e)
+    }
+
+    fn unary(&mut self) -> Result<SpecExpr, String> {
+        match self.peek_punct() {
+            Some("!") => {
+                self.pos += 1;
+                Ok(SpecExpr::UnaryOp(UnOp::Not, Box::new(self.unary()?)))
+            }
+            Some("-") => {
+                self.pos += 1;
+                Ok(SpecExpr::UnaryOp(UnOp::Neg, Box::new(self.unary()?)))
+            }
+            Some("*") => {
+                self.pos += 1;
+                Ok(SpecExpr::Deref(Box::new(self.unary()?)))
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn call_args(&mut self) -> Result<Vec<SpecExpr>, String> {
+        self.expect("(")?;
+        let mut args = Vec::new();
+        while self.peek_punct() != Some(")") {
+            args.push(self.binop(0)?);
+            if self.peek_punct() != Some(")") {
+                self.expect(",")?;
+            }
+        }
+        self.expect(")")?;
+        Ok(args)
+    }
+
+    fn postfix(&mut self) -> Result<SpecExpr, String> {
+        let mut e = self.atom()?;
+        loop {
+            match self.peek_punct() {
+                Some(".") => {
+                    self.pos += 1;
+                    let field = match self.next()? {
+                        Token::Ident(id) => id,
+                        Token::Int(i, None) => i,
+                        tok => return Err(format!("expected a field, found {tok:?}")),
+                    };
+                    e = if self.peek_punct() == Some("(") {
+                        let args = self.call_args()?;
+                        SpecExpr::MethodCall(Box::new(e), field, args)
+                    } else {
+                        SpecExpr::Field(Box::new(e), field)
+                    };
+                }
+                Some("[") => {
+                    self.pos += 1;
+                    let index = self.binop(0)?;
+                    self.expect("]")?;
+                    e = SpecExpr::Index(Box::new(e), Box::new(index));
+                }
+                _ => return Ok(e),
+            }
+        }
+    }
+
+    fn atom(&mut self) -> Result<SpecExpr, String> {
+        match self.next()? {
+            Token::Int(v, ty) => Ok(SpecExpr::Int(v, ty)),
+            Token::Punct("(") => {
+                let e = self.binop(0)?;
+                self.expect(")")?;
+                Ok(e)
+            }
+            Token::Ident(id) => {
+                let mut path = vec![id];
+                while self.peek_punct() == Some("::") {
+                    self.pos += 1;
+                    path.push(self.ident()?);
+                }
+                if self.peek_punct() == Some("(") {
+                    let args = self.call_args()?;
+                    return Ok(SpecExpr::Call(path, args));
+                }
+                if path.len() > 1 {
+                    return Ok(SpecExpr::Path(path));
+                }
+                let id = path.pop().unwrap();
+                match id.as_str() {
+                    "true" => Ok(SpecExpr::Bool(true)),
+                    "false" => Ok(SpecExpr::Bool(false)),
+                    "result" if self.postcondition => Ok(SpecExpr::Var(VarId::ZERO)),
+                    "result" => Err("`result` can only appear in the postconditions".to_string()),
+                    _ => match self.vars.iter().rev().find(|(name, _)| *name == id) {
+                        Some((_, var_id)) => Ok(SpecExpr::Var(*var_id)),
+                        None => Err(format!("unknown variable `{id}`")),
+                    },
+                }
+            }
+            tok => Err(format!("unexpected {tok:?}")),
+        }
+    }
+}
+
+fn parse(src: &str, vars: &[(String, VarId::Id)], postcondition: bool) -> Result<SpecExpr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(src)?,
+        pos: 0,
+        vars,
+        postcondition,
+    };
+    let e = parser.binop(0)?;
+    match parser.peek() {
+        None => Ok(e),
+        Some(tok) => Err(format!("unexpected {tok:?}")),
+    }
+}
+
+/// Parse a pre or postcondition. `args` gives the names of the arguments of
+/// the function.
+pub fn parse_spec(src: &str, args: &[String], postcondition: bool) -> Result<SpecExpr, String> {
+    let vars: Vec<(String, VarId::Id)> = args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| (arg.clone(), VarId::Id::new(i + 1)))
+        .collect();
+    parse(src, &vars, postcondition)
+}
+
+/// Parse a loop invariant. `vars` gives the names of the local variables.
+pub fn parse_invariant(src: &str, vars: &[(String, VarId::Id)]) -> Result<SpecExpr, String> {
+    parse(src, vars, false)
+}
+
+/// If this is a `#[charon::...]` attribute, return its name
+fn tool_attribute_name(attr: &Attribute) -> Option<&str> {
+    match &attr.kind {
+        AttrKind::Normal(normal) => match normal.item.path.segments.as_slice() {
+            [tool, name] if tool.ident.as_str() == tool_attributes::TOOL_NAME => {
+                Some(name.ident.as_str())
+            }
+            _ => None,
+        },
+        AttrKind::DocComment(_, _) => None,
+    }
+}
+
+/// Retrieve the condition given by an attribute. We don't have the source
+/// code of the external definitions: we return `None` for those.
+fn attribute_condition(tcx: TyCtxt, attr: &Attribute) -> Result<Option<String>, (Span, String)> {
+    match tcx.sess.source_map().span_to_snippet(attr.span) {
+        Ok(snippet) => match tool_attributes::attribute_args(&snippet) {
+            Some(source) => Ok(Some(source)),
+            None => Err((attr.span, "expected a condition".to_string())),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+/// Retrieve the contract of a function from its attributes. In case of
+/// error, we return the span of the ill-formed attribute.
+pub fn get_fun_contract(
+    tcx: TyCtxt,
+    def_id: DefId,
+) -> Result<FunContract, (rustc_span::Span, String)> {
+    let mut contract = FunContract::default();
+    // The closures don't have contracts
+    if tcx.is_closure(def_id) {
+        return Ok(contract);
+    }
+    let args: Vec<String> = tcx
+        .fn_arg_names(def_id)
+        .iter()
+        .map(|id| id.to_string())
+        .collect();
+    for attr in tcx.get_attrs_unchecked(def_id) {
+        let postcondition = match tool_attribute_name(attr) {
+            Some(tool_attributes::REQUIRES) => false,
+            Some(tool_attributes::ENSURES) => true,
+            _ => continue,
+        };
+        let source = match attribute_condition(tcx, attr)? {
+            Some(source) => source,
+            None => continue,
+        };
+        let expr = parse_spec(&source, &args, postcondition)
+            .map_err(|msg| (attr.span, format!("ill-formed condition: {msg}")))?;
+        let spec = Spec { source, expr };
+        if postcondition {
+            contract.ensures.push(spec);
+        } else {
+            contract.requires.push(spec);
+        }
+    }
+    Ok(contract)
+}
+
+/// Collect the `#[charon::invariant(...)]` attributes of the expressions of
+/// a body
+struct InvariantAttrs<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    /// The span of the annotated loop, and the annotation
+    attrs: Vec<(Span, &'tcx Attribute)>,
+    /// The invariants attached to expressions which are not loops
+    misplaced: Vec<Span>,
+}
+
+impl<'tcx> Visitor<'tcx> for InvariantAttrs<'tcx> {
+    fn visit_expr(&mut self, expr: &'tcx Expr<'tcx>) {
+        for attr in self.tcx.hir().attrs(expr.hir_id) {
+            if tool_attribute_name(attr) == Some(tool_attributes::INVARIANT) {
+                match expr.kind {
+                    ExprKind::Loop(..) => self.attrs.push((expr.span, attr)),
+                    _ => self.misplaced.push(attr.span),
+                }
+            }
+        }
+        intravisit::walk_expr(self, expr)
+    }
+}
+
+/// Retrieve the loop invariants of a function body, indexed by the spans of
+/// the loops. `vars` gives the names of the local variables. In case of
+/// error, we return the span of the ill-formed attribute.
+pub fn get_loop_invariants(
+    tcx: TyCtxt,
+    def_id: LocalDefId,
+    vars: &[(String, VarId::Id)],
+) -> Result<HashMap<Span, Vec<Spec>>, (Span, String)> {
+    let mut visitor = InvariantAttrs {
+        tcx,
+        attrs: Vec::new(),
+        misplaced: Vec::new(),
+    };
+    let body = tcx.hir().body(tcx.hir().body_owned_by(def_id));
+    visitor.visit_body(body);
+    if let Some(span) = visitor.misplaced.first() {
+        return Err((
+            *span,
+            "the invariants must be attached to loops".to_string(),
+        ));
+    }
+    let mut invariants: HashMap<Span, Vec<Spec>> = HashMap::new();
+    for (loop_span, attr) in visitor.attrs {
+        let source = match attribute_condition(tcx, attr)? {
+            Some(source) => source,
+            None => continue,
+        };
+        let expr = parse_invariant(&source, vars)
+            .map_err(|msg| (attr.span, format!("ill-formed invariant: {msg}")))?;
+        invariants
+            .entry(loop_span)
+            .or_default()
+            .push(Spec { source, expr });
+    }
+    Ok(invariants)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::contracts::*;
+
+    fn var(i: usize) -> Box<SpecExpr> {
+        Box::new(SpecExpr::Var(VarId::Id::new(i)))
+    }
+
+    #[test]
+    fn test_parse_spec() {
+        let args = vec!["x".to_string(), "v".to_string()];
+        let parse = |src: &str| parse_spec(src, &args, true);
+
+        // `result == x + 1u32 * 2`
+        let e = parse("result == x + 1u32 * 2").unwrap();
+        let mul = SpecExpr::BinaryOp(
+            BinOp::Mul,
+            Box::new(SpecExpr::Int("1".to_string(), Some(IntegerTy::U32))),
+            Box::new(SpecExpr::Int("2".to_string(), None)),
+        );
+        let add = SpecExpr::BinaryOp(BinOp::Add, var(1), Box::new(mul));
+        assert!(e == SpecExpr::BinaryOp(BinOp::Eq, var(0), Box::new(add)));
+
+        // The lazy operators, the method calls and the paths
+        let e = parse("x < u32::MAX && !(v.len() == 0 || v[0] > x)").unwrap();
+        match e {
+            SpecExpr::And(e1, e2) => {
+                assert!(
+                    *e1 == SpecExpr::BinaryOp(
+                        BinOp::Lt,
+                        var(1),
+                        Box::new(SpecExpr::Path(vec!["u32".to_string(), "MAX".to_string()]))
+                    )
+                );
+                match *e2 {
+                    SpecExpr::UnaryOp(UnOp::Not, e) => assert!(matches!(*e, SpecExpr::Or(_, _))),
+                    _ => panic!(),
+                }
+            }
+            _ => panic!(),
+        }
+
+        // The ill-formed conditions
+        assert!(parse_spec("result > 0", &args, false).is_err());
+        assert!(parse("y > 0").is_err());
+        assert!(parse("x < 1 < 2").is_err());
+        assert!(parse("x +").is_err());
+        assert!(parse("x > 'a'").is_err());
+    }
+
+    #[test]
+    fn test_parse_invariant() {
+        // The second `i` shadows the first one
+        let vars = vec![
+            ("i".to_string(), VarId::Id::new(1)),
+            ("n".to_string(), VarId::Id::new(2)),
+            ("i".to_string(), VarId::Id::new(4)),
+        ];
+        let e = parse_invariant("i <= n", &vars).unwrap();
+        assert!(e == SpecExpr::BinaryOp(BinOp::Le, var(4), var(2)));
+        let mut ids = Vec::new();
+        e.for_each_var(&mut |id| ids.push(*id));
+        assert!(ids == vec![VarId::Id::new(4), VarId::Id::new(2)]);
+
+        // The invariants can't refer to the result of the function
+        assert!(parse_invariant("result <= n", &vars).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+//! Build an index of the translated declarations, which maps their full Rust
+//! paths (`crate::module::f`) to their identifiers and to their location in
+//! the source. We export it alongside the translated crate (see the `--index`
+//! option), so that the external tools (IDE integrations, etc.) can jump from
+//! a symbol of the source to its translated declaration.
+//!
+//! The paths are the names of the declarations, which we compute from their
+//! HIR paths (see [crate::names]).
+
+use crate::meta::Meta;
+use crate::rust_to_local_ids::AnyDeclId;
+use crate::types::TypeDecls;
+use crate::ullbc_ast::{FunDecls, GlobalDecls};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct IndexEntry {
+    pub path: String,
+    pub id: AnyDeclId,
+    /// The location of the declaration in the source
+    pub meta: Meta,
+}
+
+/// Build the index of the type, function and global declarations. The
+/// entries are sorted by path, to make the generated file stable.
+pub fn build_index(
+    type_defs: &TypeDecls,
+    fun_defs: &FunDecls,
+    global_defs: &GlobalDecls,
+) -> Vec<IndexEntry> {
+    let types = type_defs.types.iter().map(|d| IndexEntry {
+        path: d.name.to_string(),
+        id: AnyDeclId::Type(d.def_id),
+        meta: d.meta,
+    });
+    let funs = fun_defs.iter().map(|d| IndexEntry {
+        path: d.name.to_string(),
+        id: AnyDeclId::Fun(d.def_id),
+        meta: d.meta,
+    });
+    let globals = global_defs.iter().map(|d| IndexEntry {
+        path: d.name.to_string(),
+        id: AnyDeclId::Global(d.def_id),
+        meta: d.meta,
+    });
+    let mut index: Vec<IndexEntry> = types.chain(funs).chain(globals).collect();
+    index.sort_by(|e0, e1| e0.path.cmp(&e1.path));
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decls_index::*;
+    use crate::test_utils::*;
+    use crate::types::*;
+    use crate::ullbc_ast::{FunDecl, FunDeclId};
+
+    /// A function `fn name() -> bool` without body
+    fn mk_opaque_fun(def_id: usize, name: &[&str]) -> FunDecl {
+        mk_fun_decl(def_id, name, mk_sig(Vec::new(), Ty::Bool), None)
+    }
+
+    #[test]
+    fn test_index() {
+        let funs = FunDeclId::Vector::from(vec![
+            mk_opaque_fun(0, &["mycrate", "module", "g"]),
+            mk_opaque_fun(1, &["mycrate", "module", "f"]),
+        ]);
+        let index = build_index(&TypeDecls::new(), &funs, &GlobalDecls::new());
+        assert!(index.len() == 2);
+        // The entries are sorted by path
+        assert!(index[0].path == "mycrate::module::f");
+        assert!(index[0].id == AnyDeclId::Fun(FunDeclId::Id::new(1)));
+        assert!(index[1].path == "mycrate::module::g");
+        assert!(index[1].id == AnyDeclId::Fun(FunDeclId::ZERO));
+    }
+}
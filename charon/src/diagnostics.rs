@@ -0,0 +1,299 @@
+//! Structured diagnostics.
+//!
+//! We collect the errors (and warnings) about the code we can't translate:
+//! the unsupported features, which we detect when registering the
+//! declarations (see [crate::register]), and the bodies which don't have the
+//! shape the micro-passes expect (see [crate::errors]) or which the
+//! micro-passes make ill-typed (see [crate::check]). Every diagnostic has
+//! an error code, for instance `CHARON-0005` for the generators, and the
+//! location of the offending code.
+//!
+//! With `--diagnostics-format human` (the default), we print the diagnostics
+//! like the compiler errors. With `--diagnostics-format json`, we also write
+//! them to `{crate}.diagnostics.json`, next to the generated file, for the
+//! continuous integration scripts:
+//! ```text
+//! { "crate_name": "mycrate",
+//!   "diagnostics": [ {
+//!     "code": { "id": "CHARON-0005", "description": "generator" },
+//!     "level": "error",
+//!     "message": "Generators are not supported",
+//!     "decl": null,
+//!     "location": { "file": "src/lib.rs", "beg": { "line": 3, "col": 4 },
+//!                   "end": { "line": 3, "col": 12 } } } ] }
+//! ```
+#![allow(dead_code)]
+
+use crate::cli_options::DiagnosticsFormat;
+use crate::meta::{convert_loc, get_filename_from_rspan, FileId, FileName, Loc, Meta};
+use rustc_errors::DiagnosticId;
+use rustc_session::Session;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// The error codes. Their numbers appear in the CI scripts of the users: we
+/// must never change them (we only add new codes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    FfiType,
+    InferType,
+    HigherRankedFnPtr,
+    TraitObjectWithoutPrincipal,
+    Generator,
+    IllTyped,
+    FnPointer,
+    InlineAsm,
+    /// A `#[charon::requires]`, `#[charon::ensures]` or `#[charon::invariant]`
+    /// attribute we can't parse (see [crate::contracts])
+    IllFormedContract,
+    /// Mutually recursive declarations of different kinds, for instance a
+    /// type and a function (see [crate::reorder_decls])
+    MixedRecursion,
+    /// An associated type or constant of a trait which has no methods: we
+    /// only translate the traits through their methods (see
+    /// [crate::translate_traits])
+    TraitWithoutMethods,
+    /// A closure defined in another crate: we translate the closures with the
+    /// functions which define them (see [crate::closures])
+    ForeignClosure,
+    /// A body doesn't have the shape a micro-pass expects
+    UnexpectedShape,
+    /// A micro-pass generated ill-typed code (see [crate::check])
+    IllFormedLlbc,
+}
+
+impl Code {
+    pub fn number(self) -> u32 {
+        match self {
+            Code::FfiType => 1,
+            Code::InferType => 2,
+            Code::HigherRankedFnPtr => 3,
+            Code::TraitObjectWithoutPrincipal => 4,
+            Code::Generator => 5,
+            Code::IllTyped => 6,
+            Code::FnPointer => 7,
+            Code::InlineAsm => 8,
+            Code::IllFormedContract => 9,
+            Code::MixedRecursion => 10,
+            Code::TraitWithoutMethods => 11,
+            Code::ForeignClosure => 12,
+            Code::UnexpectedShape => 100,
+            Code::IllFormedLlbc => 101,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Code::FfiType => "FFI type",
+            Code::InferType => "unresolved type inference variable",
+            Code::HigherRankedFnPtr => "higher-ranked function pointer",
+            Code::TraitObjectWithoutPrincipal => "trait object without a principal trait",
+            Code::Generator => "generator",
+            Code::IllTyped => "ill-typed code",
+            Code::FnPointer => "pointer to a primitive or polymorphic function",
+            Code::InlineAsm => "inline assembly",
+            Code::IllFormedContract => "ill-formed contract",
+            Code::MixedRecursion => "mutually recursive declarations of different kinds",
+            Code::TraitWithoutMethods => "associated item of a trait without methods",
+            Code::ForeignClosure => "closure defined in another crate",
+            Code::UnexpectedShape => "unexpected shape of a body",
+            Code::IllFormedLlbc => "ill-typed code generated by Charon",
+        }
+    }
+
+    /// The identifier of the code, for instance `CHARON-0005`
+    pub fn id(self) -> String {
+        format!("CHARON-{:04}", self.number())
+    }
+}
+
+impl Serialize for Code {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Code", 2)?;
+        s.serialize_field("id", &self.id())?;
+        s.serialize_field("description", self.description())?;
+        s.end()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Location {
+    pub file: String,
+    pub beg: Loc,
+    pub end: Loc,
+}
+
+impl Location {
+    fn of_file_name(file: &FileName, beg: Loc, end: Loc) -> Location {
+        let file = match file {
+            FileName::Local(path) | FileName::Virtual(path) => path.display().to_string(),
+            FileName::NotReal(name) => name.clone(),
+        };
+        Location { file, beg, end }
+    }
+
+    pub fn from_rspan(sess: &Session, span: rustc_span::Span) -> Option<Location> {
+        let (beg, end) = sess.source_map().is_valid_span(span).ok()?;
+        let file = get_filename_from_rspan(sess, span);
+        Some(Location::of_file_name(
+            &file,
+            convert_loc(beg),
+            convert_loc(end),
+        ))
+    }
+
+    /// We don't have a location for the dummy spans
+    pub fn from_meta(id_to_file: &HashMap<FileId::Id, FileName>, meta: &Meta) -> Option<Location> {
+        if meta.is_dummy() {
+            return None;
+        }
+        let file = id_to_file.get(&meta.span.file_id)?;
+        Some(Location::of_file_name(file, meta.span.beg, meta.span.end))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub code: Code,
+    pub level: Level,
+    pub message: String,
+    /// The name of the declaration we failed to translate, if we know it
+    pub decl: Option<String>,
+    pub location: Option<Location>,
+}
+
+impl Diagnostic {
+    /// Print the diagnostic the way the compiler prints its errors
+    pub fn render(&self) -> String {
+        let level = match self.level {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        };
+        let mut out = format!("{level}[{}]: {}\n", self.code.id(), self.message);
+        if let Some(loc) = &self.location {
+            // The columns of the compiler are 1-based
+            let (line, col) = (loc.beg.line, loc.beg.col + 1);
+            out.push_str(&format!("  --> {}:{line}:{col}\n", loc.file));
+        }
+        if let Some(decl) = &self.decl {
+            out.push_str(&format!("   = note: in `{decl}`\n"));
+        }
+        out.push_str(&format!("   = note: {}", self.code.description()));
+        out
+    }
+}
+
+/// The diagnostics of the crate we extract. The micro-passes run in
+/// parallel, hence the mutex.
+pub struct Diagnostics {
+    format: DiagnosticsFormat,
+    diags: Mutex<Vec<Diagnostic>>,
+}
+
+impl Diagnostics {
+    pub fn new(format: DiagnosticsFormat) -> Self {
+        Diagnostics {
+            format,
+            diags: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Report an unsupported feature at a location in the source code. We
+    /// emit it through the compiler session, which prints it and fails the
+    /// compilation in case of error.
+    pub fn span_report(
+        &self,
+        sess: &Session,
+        level: Level,
+        code: Code,
+        span: rustc_span::Span,
+        msg: &str,
+    ) {
+        let id = DiagnosticId::Error(code.id());
+        match level {
+            Level::Error => {
+                log::error!("{}:\n{}", crate::meta::span_to_string(sess, span), msg);
+                sess.span_err_with_code(span, msg, id);
+            }
+            Level::Warning => {
+                log::warn!("{}:\n{}", crate::meta::span_to_string(sess, span), msg);
+                sess.span_warn_with_code(span, msg, id);
+            }
+        }
+        self.diags.lock().unwrap().push(Diagnostic {
+            code,
+            level,
+            message: msg.to_string(),
+            decl: None,
+            location: Location::from_rspan(sess, span),
+        });
+    }
+
+    /// Report a diagnostic about the translated code (once we don't have
+    /// the compiler spans anymore)
+    pub fn report(&self, diag: Diagnostic) {
+        if self.format == DiagnosticsFormat::Human {
+            eprintln!("{}\n", diag.render());
+        }
+        self.diags.lock().unwrap().push(diag);
+    }
+
+    pub fn num_errors(&self) -> usize {
+        let diags = self.diags.lock().unwrap();
+        diags.iter().filter(|d| d.level == Level::Error).count()
+    }
+
+    pub fn format(&self) -> DiagnosticsFormat {
+        self.format
+    }
+
+    /// The diagnostics, in the order in which we reported them
+    pub fn to_vec(&self) -> Vec<Diagnostic> {
+        self.diags.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostics::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_render_diagnostic() {
+        let file_id = FileId::Id::LocalId(crate::meta::LocalFileId::ZERO);
+        let id_to_file = HashMap::from([(file_id, FileName::Local(PathBuf::from("src/lib.rs")))]);
+        let mut meta = Meta::dummy();
+        meta.span.file_id = file_id;
+        meta.span.beg = Loc { line: 3, col: 4 };
+        meta.span.end = Loc { line: 3, col: 12 };
+        let diag = Diagnostic {
+            code: Code::UnexpectedShape,
+            level: Level::Error,
+            message: "the divisor is not checked to be non-zero".to_string(),
+            decl: Some("test::f".to_string()),
+            location: Location::from_meta(&id_to_file, &meta),
+        };
+        assert!(
+            diag.render()
+                == "error[CHARON-0100]: the divisor is not checked to be non-zero\n  \
+                    --> src/lib.rs:3:5\n   = note: in `test::f`\n   \
+                    = note: unexpected shape of a body"
+        );
+
+        let json = serde_json::to_value(&diag).unwrap();
+        assert!(json["code"]["id"] == "CHARON-0100" && json["level"] == "error");
+        assert!(json["location"]["file"] == "src/lib.rs" && json["location"]["beg"]["line"] == 3);
+
+        // The dummy spans have no location
+        assert!(Location::from_meta(&id_to_file, &Meta::dummy()).is_none());
+    }
+}
@@ -8,6 +8,7 @@ use std::iter::FromIterator;
 fn statement_diverges(divergent: &HashMap<ast::FunDeclId::Id, bool>, st: &llbc::Statement) -> bool {
     match &st.content {
         RawStatement::Assign(_, _)
+        | RawStatement::CompoundAssign(_, _, _)
         | RawStatement::FakeRead(_)
         | RawStatement::SetDiscriminant(_, _)
         | RawStatement::Drop(_)
@@ -18,19 +19,42 @@ fn statement_diverges(divergent: &HashMap<ast::FunDeclId::Id, bool>, st: &llbc::
         | RawStatement::Continue(_)
         | RawStatement::Nop => false,
         RawStatement::Call(call) => match &call.func {
-            ast::FunId::Regular(id) => *divergent.get(id).unwrap(),
+            ast::FunId::Regular(id) | ast::FunId::TraitMethod(_, id) => *divergent.get(id).unwrap(),
+            // We don't know which function is called
+            ast::FunId::Virtual(_, _) | ast::FunId::Indirect(_) => false,
             ast::FunId::Assumed(id) => match id {
                 ast::AssumedFunId::Replace
                 | ast::AssumedFunId::BoxNew
                 | ast::AssumedFunId::BoxDeref
                 | ast::AssumedFunId::BoxDerefMut
                 | ast::AssumedFunId::BoxFree
+                | ast::AssumedFunId::RcNew
+                | ast::AssumedFunId::RcClone
+                | ast::AssumedFunId::ArcNew
+                | ast::AssumedFunId::ArcClone
                 | ast::AssumedFunId::VecNew
                 | ast::AssumedFunId::VecPush
                 | ast::AssumedFunId::VecInsert
                 | ast::AssumedFunId::VecLen
                 | ast::AssumedFunId::VecIndex
-                | ast::AssumedFunId::VecIndexMut => false,
+                | ast::AssumedFunId::VecIndexMut
+                | ast::AssumedFunId::OptionUnwrap
+                | ast::AssumedFunId::OptionExpect
+                | ast::AssumedFunId::OptionUnwrapOr
+                | ast::AssumedFunId::OptionIsSome
+                | ast::AssumedFunId::OptionIsNone
+                | ast::AssumedFunId::ResultUnwrap
+                | ast::AssumedFunId::ResultExpect
+                | ast::AssumedFunId::ResultIsOk
+                | ast::AssumedFunId::ResultIsErr
+                | ast::AssumedFunId::TryBranch
+                | ast::AssumedFunId::FromResidual
+                | ast::AssumedFunId::IntoIter
+                | ast::AssumedFunId::IteratorNext
+                // Those call a closure: we don't know which one
+                | ast::AssumedFunId::OptionMap
+                | ast::AssumedFunId::ResultMap
+                | ast::AssumedFunId::ResultMapErr => false,
             },
         },
         RawStatement::Sequence(st1, st2) => {
@@ -40,7 +64,67 @@ fn statement_diverges(divergent: &HashMap<ast::FunDeclId::Id, bool>, st: &llbc::
             let tgts = switch.get_targets();
             tgts.iter().any(|st| statement_diverges(divergent, st))
         }
-        RawStatement::Loop(_) => true,
+        RawStatement::Loop(_, _) | RawStatement::While(_, _, _, _) => true,
+    }
+}
+
+/// Return true if the statement contains a `break` to the loop at depth
+/// `depth` (the current loop being at depth 0).
+fn breaks_to(st: &llbc::Statement, depth: usize) -> bool {
+    match &st.content {
+        RawStatement::Break(i) => *i == depth,
+        RawStatement::Loop(body, _) | RawStatement::While(_, _, body, _) => {
+            breaks_to(body, depth + 1)
+        }
+        RawStatement::Sequence(st1, st2) => breaks_to(st1, depth) || breaks_to(st2, depth),
+        RawStatement::Switch(switch) => switch.get_targets().iter().any(|st| breaks_to(st, depth)),
+        RawStatement::Assign(_, _)
+        | RawStatement::CompoundAssign(_, _, _)
+        | RawStatement::FakeRead(_)
+        | RawStatement::SetDiscriminant(_, _)
+        | RawStatement::Drop(_)
+        | RawStatement::Assert(_)
+        | RawStatement::Call(_)
+        | RawStatement::Panic
+        | RawStatement::Return
+        | RawStatement::Continue(_)
+        | RawStatement::Nop => false,
+    }
+}
+
+/// Return true if the statement never completes normally, that is the control
+/// never reaches the statement which follows it: it always panics, returns,
+/// or breaks (or continues) to an enclosing loop.
+///
+/// Note that this is a different notion from the one computed by
+/// [compute_divergent_functions]: a statement may complete normally and
+/// still diverge by looping forever.
+pub fn statement_never_completes(st: &llbc::Statement) -> bool {
+    match &st.content {
+        RawStatement::Panic
+        | RawStatement::Return
+        | RawStatement::Break(_)
+        | RawStatement::Continue(_) => true,
+        RawStatement::Assign(_, _)
+        | RawStatement::CompoundAssign(_, _, _)
+        | RawStatement::FakeRead(_)
+        | RawStatement::SetDiscriminant(_, _)
+        | RawStatement::Drop(_)
+        | RawStatement::Assert(_)
+        | RawStatement::Call(_)
+        | RawStatement::Nop => false,
+        RawStatement::Sequence(st1, st2) => {
+            statement_never_completes(st1) || statement_never_completes(st2)
+        }
+        RawStatement::Switch(switch) => switch
+            .get_targets()
+            .iter()
+            .all(|st| statement_never_completes(st)),
+        // We can only exit a loop by breaking to it
+        RawStatement::Loop(body, _) => !breaks_to(body, 0),
+        // The statement computing the condition always completes, and the
+        // loop exits when the condition is false
+        RawStatement::While(_, _, _, _) => false,
     }
 }
 
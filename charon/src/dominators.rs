@@ -0,0 +1,229 @@
+//! Compute the dominators of the blocks of a ULLBC body.
+//!
+//! A block `a` dominates a block `b` if all the paths from the entry block
+//! to `b` go through `a`. The analyses which need to know the structure of
+//! the control-flow graph (detection of the loops, of the irreducible
+//! control-flow, etc.) should use this module rather than reimplementing it.
+//!
+//! We use the algorithm described in "A Simple, Fast Dominance Algorithm"
+//! (Cooper, Harvey and Kennedy): we iterate over the blocks in reverse
+//! postorder, and refine the immediate dominators until we reach a fixed
+//! point. In practice, only a couple of iterations are needed.
+
+use crate::ullbc_ast::*;
+use std::collections::{HashMap, HashSet};
+
+/// The dominator tree of a body
+pub struct Dominators {
+    /// The immediate dominator of every block reachable from the entry block.
+    /// By convention, the entry block is its own immediate dominator.
+    idoms: HashMap<BlockId::Id, BlockId::Id>,
+}
+
+impl Dominators {
+    /// Return the immediate dominator of a block, or `None` if the block is
+    /// the entry block or is unreachable.
+    pub fn immediate_dominator(&self, block: BlockId::Id) -> Option<BlockId::Id> {
+        match self.idoms.get(&block) {
+            Some(idom) if *idom != block => Some(*idom),
+            _ => None,
+        }
+    }
+
+    pub fn is_reachable(&self, block: BlockId::Id) -> bool {
+        self.idoms.contains_key(&block)
+    }
+
+    /// Return true if `a` dominates `b`. Every reachable block dominates
+    /// itself, and we consider that the unreachable blocks are not dominated.
+    pub fn dominates(&self, a: BlockId::Id, b: BlockId::Id) -> bool {
+        if !self.is_reachable(b) {
+            return false;
+        }
+        // Walk up the dominator tree
+        let mut b = b;
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.immediate_dominator(b) {
+                Some(idom) => b = idom,
+                None => return false,
+            }
+        }
+    }
+}
+
+fn successors(body: &ExprBody, block: BlockId::Id) -> Vec<BlockId::Id> {
+    body.body
+        .get(block)
+        .unwrap()
+        .terminator
+        .content
+        .successors()
+}
+
+/// Return the blocks reachable from the entry block, in reverse postorder.
+/// We don't use a recursive function, as the bodies may be big.
+fn reverse_postorder(body: &ExprBody) -> Vec<BlockId::Id> {
+    let mut postorder = Vec::new();
+    let mut visited = HashSet::new();
+    visited.insert(START_BLOCK_ID);
+    // The blocks under exploration, with the successors left to explore
+    let mut stack = vec![(START_BLOCK_ID, successors(body, START_BLOCK_ID))];
+    while let Some((block, succs)) = stack.last_mut() {
+        match succs.pop() {
+            Some(succ) => {
+                if visited.insert(succ) {
+                    stack.push((succ, successors(body, succ)));
+                }
+            }
+            None => {
+                postorder.push(*block);
+                stack.pop();
+            }
+        }
+    }
+    postorder.reverse();
+    postorder
+}
+
+/// Find the closest common dominator of two blocks, by walking up the
+/// (partially computed) dominator tree.
+fn intersect(
+    idoms: &HashMap<BlockId::Id, BlockId::Id>,
+    rpo_index: &HashMap<BlockId::Id, usize>,
+    mut a: BlockId::Id,
+    mut b: BlockId::Id,
+) -> BlockId::Id {
+    while a != b {
+        while rpo_index[&a] > rpo_index[&b] {
+            a = idoms[&a];
+        }
+        while rpo_index[&b] > rpo_index[&a] {
+            b = idoms[&b];
+        }
+    }
+    a
+}
+
+pub fn compute_dominators(body: &ExprBody) -> Dominators {
+    let rpo = reverse_postorder(body);
+    let rpo_index: HashMap<BlockId::Id, usize> =
+        rpo.iter().enumerate().map(|(i, b)| (*b, i)).collect();
+
+    // Compute the predecessors (we ignore the unreachable blocks)
+    let mut preds: HashMap<BlockId::Id, Vec<BlockId::Id>> = HashMap::new();
+    for block in &rpo {
+        for succ in successors(body, *block) {
+            preds.entry(succ).or_default().push(*block);
+        }
+    }
+
+    let mut idoms: HashMap<BlockId::Id, BlockId::Id> = HashMap::new();
+    idoms.insert(START_BLOCK_ID, START_BLOCK_ID);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in rpo.iter().skip(1) {
+            // Intersect the dominators of the predecessors we already
+            // processed. Because we explore the blocks in reverse postorder,
+            // there is always at least one of them.
+            let mut new_idom = None;
+            for pred in &preds[block] {
+                if idoms.contains_key(pred) {
+                    new_idom = Some(match new_idom {
+                        None => *pred,
+                        Some(idom) => intersect(&idoms, &rpo_index, *pred, idom),
+                    });
+                }
+            }
+            let new_idom = new_idom.unwrap();
+            if idoms.insert(*block, new_idom) != Some(new_idom) {
+                changed = true;
+            }
+        }
+    }
+
+    Dominators { idoms }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dominators::*;
+    use crate::expressions::{Operand, Place};
+    use crate::test_utils::*;
+    use crate::values::VarId;
+
+    fn mk_if(then_tgt: BlockId::Id, else_tgt: BlockId::Id) -> RawTerminator {
+        RawTerminator::Switch {
+            discr: Operand::Move(Place::new(VarId::Id::new(1))),
+            targets: SwitchTargets::If(then_tgt, else_tgt),
+        }
+    }
+
+    #[test]
+    fn test_diamond() {
+        let bb: Vec<BlockId::Id> = (0..5).map(BlockId::Id::new).collect();
+        // ```
+        // bb0: if _1 then goto bb1 else goto bb2
+        // bb1: goto bb3
+        // bb2: goto bb3
+        // bb3: return
+        // bb4: goto bb3 // unreachable
+        // ```
+        let body = mk_cfg(
+            0,
+            vec![],
+            vec![
+                mk_block(vec![], mk_if(bb[1], bb[2])),
+                mk_block(vec![], RawTerminator::Goto { target: bb[3] }),
+                mk_block(vec![], RawTerminator::Goto { target: bb[3] }),
+                mk_block(vec![], RawTerminator::Return),
+                mk_block(vec![], RawTerminator::Goto { target: bb[3] }),
+            ],
+        );
+        let doms = compute_dominators(&body);
+        assert!(doms.immediate_dominator(bb[0]).is_none());
+        assert!(doms.immediate_dominator(bb[1]) == Some(bb[0]));
+        assert!(doms.immediate_dominator(bb[2]) == Some(bb[0]));
+        assert!(doms.immediate_dominator(bb[3]) == Some(bb[0]));
+        assert!(doms.dominates(bb[0], bb[3]));
+        assert!(doms.dominates(bb[3], bb[3]));
+        assert!(!doms.dominates(bb[1], bb[3]));
+        assert!(!doms.dominates(bb[2], bb[3]));
+        assert!(!doms.is_reachable(bb[4]));
+        assert!(!doms.dominates(bb[0], bb[4]));
+    }
+
+    #[test]
+    fn test_loop() {
+        let bb: Vec<BlockId::Id> = (0..4).map(BlockId::Id::new).collect();
+        // ```
+        // bb0: goto bb1
+        // bb1: if _1 then goto bb2 else goto bb3 // loop header
+        // bb2: goto bb1
+        // bb3: return
+        // ```
+        let body = mk_cfg(
+            0,
+            vec![],
+            vec![
+                mk_block(vec![], RawTerminator::Goto { target: bb[1] }),
+                mk_block(vec![], mk_if(bb[2], bb[3])),
+                mk_block(vec![], RawTerminator::Goto { target: bb[1] }),
+                mk_block(vec![], RawTerminator::Return),
+            ],
+        );
+        let doms = compute_dominators(&body);
+        assert!(doms.immediate_dominator(bb[1]) == Some(bb[0]));
+        assert!(doms.immediate_dominator(bb[2]) == Some(bb[1]));
+        assert!(doms.immediate_dominator(bb[3]) == Some(bb[1]));
+        // The loop header dominates the body of the loop, but not the
+        // other way around
+        assert!(doms.dominates(bb[1], bb[2]));
+        assert!(!doms.dominates(bb[2], bb[1]));
+        assert!(doms.dominates(bb[0], bb[2]));
+        assert!(!doms.dominates(bb[2], bb[3]));
+    }
+}
@@ -1,36 +1,74 @@
 #![allow(dead_code)]
 
+use crate::call_signatures;
+use crate::callgraph::CallGraph;
+use crate::cfg_dot;
+use crate::check_calls;
+use crate::check_cfg;
+use crate::check_places;
 use crate::cli_options;
+use crate::collapse_deref_ref;
+use crate::common_subexpressions;
+use crate::complete_switches;
+use crate::compound_assign;
+use crate::config::Config;
+use crate::decls_index;
+use crate::diagnostics::Diagnostics;
 use crate::divergent;
+use crate::erase_phantoms;
+use crate::expand_box_drops;
 use crate::export;
 use crate::extract_global_assignments;
-use crate::get_mir::MirLevel;
+use crate::get_mir::{boxes_are_desugared, MirLevel};
+use crate::html_report;
+use crate::incremental;
+use crate::index_accesses;
+use crate::inline_functions;
 use crate::insert_assign_return_unit;
+use crate::insert_overflow_checks;
 use crate::llbc_ast::{CtxNames, FunDeclId, GlobalDeclId};
+use crate::match_provenance;
+use crate::monomorphize;
+use crate::packed_fields;
+use crate::passes::{Pass, PassCtx, PassPipeline, PassPosition};
+use crate::propagate_copies;
+use crate::range_loops;
+use crate::recognize_matches;
 use crate::reconstruct_asserts;
+use crate::reconstruct_try;
 use crate::register;
 use crate::regularize_constant_adts;
 use crate::remove_drop_never;
+use crate::remove_fake_reads;
 use crate::remove_read_discriminant;
+use crate::remove_redundant_set_discriminant;
 use crate::remove_unused_locals;
 use crate::reorder_decls;
 use crate::rust_to_local_ids;
 use crate::simplify_ops;
+use crate::single_exit;
+use crate::slice_accesses;
+use crate::tool_attributes;
 use crate::translate_functions_to_ullbc;
+use crate::translate_traits;
 use crate::translate_types;
 use crate::ullbc_to_llbc;
+use crate::while_loops;
 use regex::Regex;
 use rustc_driver::{Callbacks, Compilation};
 use rustc_interface::{interface::Compiler, Queries};
 use rustc_middle::ty::TyCtxt;
 use rustc_session::Session;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
 use std::ops::Deref;
 
 /// The callbacks for Charon
 pub struct CharonCallbacks {
     pub options: cli_options::CliOpts,
+    /// The user passes, to insert in the pipeline of micro-passes (see
+    /// [crate::passes]).
+    pub passes: Vec<(PassPosition, Box<dyn Pass + Send>)>,
 }
 
 impl Callbacks for CharonCallbacks {
@@ -132,6 +170,33 @@ pub fn translate(sess: &Session, tcx: TyCtxt, internal: &CharonCallbacks) -> Res
     );
     trace!("# Crate: {}", crate_name);
 
+    let diagnostics = Diagnostics::new(options.diagnostics_format);
+    let res = translate_crate(sess, tcx, internal, crate_name.clone(), &diagnostics);
+    // We save the diagnostics even if the translation failed
+    if options.diagnostics_format == cli_options::DiagnosticsFormat::Json {
+        export::export_diagnostics(&crate_name, &options.dest_dir, &diagnostics)?;
+    }
+    res
+}
+
+fn translate_crate(
+    sess: &Session,
+    tcx: TyCtxt,
+    internal: &CharonCallbacks,
+    crate_name: String,
+    diagnostics: &Diagnostics,
+) -> Result<(), ()> {
+    let options = &internal.options;
+
+    // Configure the thread pool we use to process the functions in parallel
+    if let Some(jobs) = options.jobs {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(jobs);
+        if let Err(err) = pool.build_global() {
+            error!("Could not configure the thread pool: {}", err);
+            return Err(());
+        }
+    }
+
     // Adjust the level of MIR we extract, depending on the options
     let mir_level = if options.mir_optimized {
         MirLevel::Optimized
@@ -163,11 +228,24 @@ pub fn translate(sess: &Session, tcx: TyCtxt, internal: &CharonCallbacks) -> Res
     // We iterate over the HIR items, and explore their MIR bodies/ADTs/etc.
     // (when those exist - for instance, type aliases don't have MIR translations
     // so we just ignore them).
+    // The opaque modules are given both on the command line and in the
+    // configuration file of the crate (Cargo runs the compiler from the root
+    // of the workspace, so we look up the crate root in the environment).
+    let crate_root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    let config = Config::load(std::path::Path::new(&crate_root))?;
+    let opaque_mods = options
+        .opaque_modules
+        .iter()
+        .chain(config.opaque.modules.iter())
+        .cloned();
     let crate_info = register::CrateInfo {
         crate_name: crate_name.clone(),
-        opaque_mods: HashSet::from_iter(options.opaque_modules.clone().into_iter()),
+        opaque_mods: HashSet::from_iter(opaque_mods),
+        include: options.include.clone(),
+        exclude: options.exclude.clone(),
     };
-    let (files, registered_decls) = register::explore_crate(&crate_info, sess, tcx, mir_level)?;
+    let (files, registered_decls) =
+        register::explore_crate(&crate_info, sess, tcx, mir_level, diagnostics)?;
     // panic!("PATCH registered_decls {:?}", registered_decls);
 
     // # Step 2: reorder the graph of dependencies and compute the strictly
@@ -175,17 +253,55 @@ pub fn translate(sess: &Session, tcx: TyCtxt, internal: &CharonCallbacks) -> Res
     // - compute the order in which to extract the definitions
     // - find the recursive definitions
     // - group the mutually recursive definitions
-    let ordered_decls = reorder_decls::reorder_declarations(&registered_decls)?;
+    let ordered_decls =
+        reorder_decls::reorder_declarations(sess, tcx, &registered_decls, diagnostics)?;
+
+    // For the incremental extraction, compute the hashes of the declarations
+    // (we need the dependencies between them, see [crate::incremental]).
+    let decl_hashes = if options.incremental {
+        incremental::compute_hashes(tcx, mir_level, &registered_decls, &ordered_decls)
+    } else {
+        HashMap::new()
+    };
 
     // # Step 3: generate identifiers for the types and functions, and compute
     // the mappings from rustc identifiers to our own identifiers.
     // Also compute identifiers for the files (we use them for the spans).
-    let ordered_decls = rust_to_local_ids::rust_to_local_ids(&files, &ordered_decls);
+    // We link the external declarations to the crates given with
+    // `--extern-llbc`.
+    let extern_symbols = export::load_extern_symbols(&options.extern_llbc)?;
+    let ordered_decls =
+        rust_to_local_ids::rust_to_local_ids(tcx, &files, &ordered_decls, &extern_symbols);
+
+    // # Step 3 bis: find the functions we can reuse from the previous
+    // extraction, if the user asked for an incremental extraction. We don't
+    // translate their bodies.
+    let incremental = if options.incremental {
+        let pass_names: Vec<&str> = internal.passes.iter().map(|(_, p)| p.name()).collect();
+        incremental::Incremental::new(
+            tcx,
+            options,
+            &pass_names,
+            &crate_name,
+            &decl_hashes,
+            &ordered_decls,
+        )
+    } else {
+        None
+    };
+    let reused_funs = incremental
+        .as_ref()
+        .map_or_else(HashSet::new, |inc| inc.reused_ids());
 
     // # Step 4: translate the types
-    let (types_constraints, type_defs) =
+    let (types_constraints, mut type_defs) =
         translate_types::translate_types(sess, tcx, &ordered_decls)?;
 
+    // # Step 4 bis: translate the traits and the trait implementations the
+    // functions belong to. They only reference the functions: the micro-passes
+    // don't modify them.
+    let (trait_decls, trait_impls) = translate_traits::translate_traits(sess, tcx, &ordered_decls);
+
     // # Step 5: translate the functions to ULLBC (Unstructured LLBC).
     // Note that from now onwards, both type and function definitions have been
     // translated to our internal ASTs: we don't interact with rustc anymore.
@@ -196,8 +312,17 @@ pub fn translate(sess: &Session, tcx: TyCtxt, internal: &CharonCallbacks) -> Res
         &types_constraints,
         &type_defs,
         mir_level,
+        &reused_funs,
     )?;
 
+    // In debug mode, check that the control-flow graphs we generated are
+    // well-formed. We do this again after every pass which modifies them.
+    // We also check that the calls are consistent with the signatures.
+    if cfg!(debug_assertions) {
+        check_cfg::check(&ullbc_funs, &ullbc_globals);
+        check_calls::check(&ullbc_funs, &ullbc_globals);
+    }
+
     //
     // =================
     // **Micro-passes**:
@@ -207,6 +332,18 @@ pub fn translate(sess: &Session, tcx: TyCtxt, internal: &CharonCallbacks) -> Res
     // serializing the result.
     //
 
+    // The micro-passes we apply, in order: we list them in the header of the
+    // generated files.
+    let mut applied_passes: Vec<String> = Vec::new();
+
+    // # Step 5 bis: remove the `PhantomData` fields, if the user asked for it.
+    // This is the only pass which modifies the type declarations: we apply it
+    // before building the formatting context below, which borrows them.
+    if options.erase_phantoms {
+        erase_phantoms::transform(&mut type_defs, &mut ullbc_funs, &mut ullbc_globals);
+        applied_passes.push("erase_phantoms".to_string());
+    }
+
     // Compute the list of function and global names in the context.
     // We need this for pretty-printing (i.e., debugging) purposes.
     // We could use the [FunDecls] and [GlobalDecls] contexts, but we often
@@ -222,64 +359,201 @@ pub fn translate(sess: &Session, tcx: TyCtxt, internal: &CharonCallbacks) -> Res
     // # Step 6: replace constant ([OperandConstantValue]) ADTs by regular
     // (Aggregated) ADTs.
     regularize_constant_adts::transform(&fmt_ctx, &mut ullbc_funs, &mut ullbc_globals);
+    applied_passes.push("regularize_constant_adts".to_string());
 
     // # Step 7: extract statics and constant globals from operands (put them in
     // a let binding). This pass relies on the absence of constant ADTs from
     // the previous step: it does not inspect them (and would thus miss globals
     // in constant ADTs).
     extract_global_assignments::transform(&fmt_ctx, &mut ullbc_funs, &mut ullbc_globals);
+    applied_passes.push("extract_global_assignments".to_string());
+
+    if cfg!(debug_assertions) {
+        check_cfg::check(&ullbc_funs, &ullbc_globals);
+    }
+
+    // # Step 8: remove the fake reads immediately followed by a storage dead
+    // of the same local, unless the user wants to keep them.
+    if !options.keep_fake_reads {
+        remove_fake_reads::transform(&fmt_ctx, &mut ullbc_funs, &mut ullbc_globals);
+        applied_passes.push("remove_fake_reads".to_string());
+        if cfg!(debug_assertions) {
+            check_cfg::check(&ullbc_funs, &ullbc_globals);
+        }
+    }
 
-    // # Step 8:
+    // # Step 9: remove the discriminant updates which immediately follow the
+    // construction of an aggregate of the same variant.
+    remove_redundant_set_discriminant::transform(&fmt_ctx, &mut ullbc_funs, &mut ullbc_globals);
+    applied_passes.push("remove_redundant_set_discriminant".to_string());
+    if cfg!(debug_assertions) {
+        check_cfg::check(&ullbc_funs, &ullbc_globals);
+    }
+
+    // # Step 9 bis: in the non-erased mode, annotate the calls with the
+    // signatures of their callees. We do it on the ULLBC, so that the
+    // information is present in both outputs.
+    if options.keep_regions {
+        call_signatures::compute(&fmt_ctx, &mut ullbc_funs, &mut ullbc_globals);
+        applied_passes.push("call_signatures".to_string());
+    }
+
+    // Emit the index of the declarations, if the user asked for it (the names
+    // and the spans of the declarations don't change after this point)
+    if options.index {
+        let index = decls_index::build_index(&type_defs, &ullbc_funs, &ullbc_globals);
+        export::export_index(
+            crate_name.clone(),
+            &ordered_decls,
+            &index,
+            &options.dest_dir,
+        )?;
+    }
+
+    // Dump the control-flow graphs, if the user asked for it. We do it after
+    // the ULLBC micro-passes, so that the graphs are the ones the
+    // control-flow reconstruction works on.
+    if options.dump_cfg {
+        cfg_dot::dump(&fmt_ctx, &crate_name, &options.dest_dir, &ullbc_funs)?;
+    }
+
+    // # Step 10:
     // There are two options:
     // - either the user wants the unstructured LLBC, in which case we stop there
     // - or they want the structured LLBC, in which case we reconstruct the
-    //   control-flow and apply micro-passes
-
-    if options.ullbc {
+    //   control-flow and apply micro-passes (if they want both, we keep the
+    //   unstructured bodies to export them next to the structured ones)
+    let emit = options.emit();
+    let crate_hash = tcx.crate_hash(rustc_span::def_id::LOCAL_CRATE).to_string();
+    if emit == cli_options::Emit::Ullbc {
         // # Extract the files
+        if options.call_graph {
+            let graph = CallGraph::from_ullbc(&ullbc_funs);
+            export::export_call_graph(&crate_name, &graph, &options.dest_dir)?;
+        }
+        let header = export::Header::new(crate_name, crate_hash, applied_passes);
         export::export_ullbc(
-            crate_name,
+            &header,
             &ordered_decls,
             &type_defs,
             &ullbc_funs,
             &ullbc_globals,
+            &trait_decls,
+            &trait_impls,
             &options.dest_dir,
+            options.output_format,
         )?;
     } else {
         // # Go from ULLBC to LLBC (Low-Level Borrow Calculus) by reconstructing
         // the control flow.
+        // We skip the functions that the user asked us not to simplify, either
+        // with an attribute or on the command line: those are exported in ULLBC,
+        // without applying any of the micro-passes below.
+        let no_simplify: HashSet<FunDeclId::Id> = ordered_decls
+            .fun_rid_to_id
+            .iter()
+            .filter(|(rid, id)| {
+                tool_attributes::has_attribute(tcx, **rid, tool_attributes::NO_SIMPLIFY)
+                    || options.no_simplify.contains(fun_names.get(**id).unwrap())
+            })
+            .map(|(_, id)| *id)
+            .collect();
+        let unstructured_funs = ullbc_to_llbc::unstructured_functions(&ullbc_funs, &no_simplify);
         let (mut llbc_funs, mut llbc_globals) = ullbc_to_llbc::translate_functions(
             options.no_code_duplication,
+            &no_simplify,
             &type_defs,
             &ullbc_funs,
             &ullbc_globals,
         );
+        applied_passes.push("ullbc_to_llbc".to_string());
+
+        // The overflow checks are reinserted in the functions annotated with
+        // `#[charon::overflow_checks]` (see Step 12).
+        let overflow_checked: HashSet<FunDeclId::Id> = ordered_decls
+            .fun_rid_to_id
+            .iter()
+            .filter(|(rid, _)| {
+                tool_attributes::has_attribute(tcx, **rid, tool_attributes::OVERFLOW_CHECKS)
+            })
+            .map(|(_, id)| *id)
+            .collect();
 
-        // # Step 9: simplify the calls to unops and binops
-        // Note that we assume that the sequences have been flattened.
-        simplify_ops::simplify(options.release, &fmt_ctx, &mut llbc_funs, &mut llbc_globals);
-
-        for def in &llbc_funs {
-            trace!(
-                "# After binop simplification:\n{}\n",
-                def.fmt_with_decls(&type_defs, &llbc_funs, &llbc_globals)
-            );
-        }
-
-        // # Step 10: reconstruct the asserts
-        reconstruct_asserts::transform(&fmt_ctx, &mut llbc_funs, &mut llbc_globals);
-
-        for def in &llbc_funs {
-            trace!(
-                "# After asserts reconstruction:\n{}\n",
-                def.fmt_with_decls(&type_defs, &llbc_funs, &llbc_globals)
-            );
-        }
-
-        // # Step 11: Remove the discriminant reads (merge them with the switches)
-        remove_read_discriminant::transform(&fmt_ctx, &mut llbc_funs, &mut llbc_globals);
+        // Register the micro-passes. Note that the user passes may refer to
+        // the names below.
+        let mut passes = PassPipeline::new();
+        passes.check_after_each_pass(options.check);
 
-        // # Step 12: add the missing assignments to the return value.
+        // # Step 11: simplify the calls to unops and binops
+        // Note that we assume that the sequences have been flattened.
+        passes.register_fn("simplify_ops", |ctx, funs, globals| {
+            simplify_ops::simplify(
+                options.release,
+                options.warn_simplified_binops,
+                ctx.fmt_ctx,
+                funs,
+                globals,
+            )
+        });
+
+        // # Step 12: reinsert the overflow checks in the functions annotated
+        // with `#[charon::overflow_checks]` (this is the inverse of the previous
+        // step, for users who want explicit overflow obligations in some
+        // functions).
+        passes.register_fn("insert_overflow_checks", |ctx, funs, _| {
+            insert_overflow_checks::transform(ctx.fmt_ctx, ctx.type_defs, funs, &overflow_checked)
+        });
+
+        // # Step 13: reconstruct the asserts
+        passes.register_fn("reconstruct_asserts", |ctx, funs, globals| {
+            reconstruct_asserts::transform(ctx.fmt_ctx, funs, globals)
+        });
+
+        // # Step 14: reconstruct the slicing operations (`&v[a..b]`), which go
+        // through calls to `Index::index` with a range argument. We must do
+        // this before normalizing the other indexing operations.
+        passes.register_fn("slice_accesses", |ctx, funs, globals| {
+            slice_accesses::transform(ctx.fmt_ctx, ctx.type_defs, funs, globals)
+        });
+
+        // # Step 15: normalize the indexing operations: the indexing of vectors
+        // (which goes through calls to `Vec::index`) is rewritten to use
+        // bounds-checked index projections, like arrays and slices.
+        passes.register_fn("index_accesses", |ctx, funs, globals| {
+            index_accesses::transform(ctx.fmt_ctx, ctx.type_defs, funs, globals)
+        });
+
+        // # Step 16: Remove the discriminant reads (merge them with the switches)
+        passes.register_fn("remove_read_discriminant", |ctx, funs, globals| {
+            remove_read_discriminant::transform(ctx.fmt_ctx, funs, globals)
+        });
+
+        // # Step 17: normalize the matches introduced by the `?` operator
+        // (the matches over the result of `Try::branch`).
+        passes.register_fn("reconstruct_try", |ctx, funs, globals| {
+            reconstruct_try::transform(ctx.fmt_ctx, funs, globals)
+        });
+
+        // # Step 18: rewrite the `for` loops over ranges of integers to
+        // counter loops (this must be done after the matches over the results
+        // of `Iterator::next` have been introduced).
+        passes.register_fn("range_loops", |ctx, funs, globals| {
+            range_loops::transform(ctx.fmt_ctx, ctx.type_defs, funs, globals)
+        });
+
+        // # Step 19: replace the matches which simply compute a boolean
+        // (`matches!(x, ...)`) with variant tests.
+        passes.register_fn("recognize_matches", |ctx, funs, globals| {
+            recognize_matches::transform(ctx.fmt_ctx, funs, globals)
+        });
+
+        // # Step 19 bis: rewrite the switches over booleans to `if`s, and
+        // remove the matches with a single branch covering all the variants.
+        passes.register_fn("complete_switches", |ctx, funs, globals| {
+            complete_switches::transform(ctx.fmt_ctx, ctx.type_defs, funs, globals)
+        });
+
+        // # Step 20: add the missing assignments to the return value.
         // When the function return type is unit, the generated MIR doesn't
         // set the return value to `()`. This can be a concern: in the case
         // of Aeneas, it means the return variable contains ⊥ upon returning.
@@ -287,34 +561,256 @@ pub fn translate(sess: &Session, tcx: TyCtxt, internal: &CharonCallbacks) -> Res
         // an extra assignment just before returning.
         // This also applies to globals (for checking or executing code before
         // the main or at compile-time).
-        insert_assign_return_unit::transform(&fmt_ctx, &mut llbc_funs, &mut llbc_globals);
+        passes.register_fn("insert_assign_return_unit", |ctx, funs, globals| {
+            insert_assign_return_unit::transform(ctx.fmt_ctx, funs, globals)
+        });
+
+        // # Step 21: remove the drops of locals whose type is `Never` (`!`). This
+        // is in preparation of the removal of the unused locals.
+        passes.register_fn("remove_drop_never", |ctx, funs, globals| {
+            remove_drop_never::transform(ctx.fmt_ctx, funs, globals)
+        });
+
+        // # Step 22: expand the drops of nested boxes, so that the inner boxes
+        // are freed before the outer ones (rustc already did it if the boxes
+        // are desugared).
+        passes.register_fn_if(
+            "expand_box_drops",
+            !boxes_are_desugared(mir_level),
+            |ctx, funs, globals| {
+                expand_box_drops::transform(ctx.fmt_ctx, ctx.type_defs, funs, globals)
+            },
+        );
+
+        // # Step 23: collapse the dereferences of temporary references,
+        // which are artifacts of the lowering of aggregates (`*(&x)` becomes
+        // `x`). This leaves the temporaries unused: they get removed when we
+        // remove the unused locals.
+        passes.register_fn("collapse_deref_ref", |ctx, funs, globals| {
+            collapse_deref_ref::transform(ctx.fmt_ctx, funs, globals)
+        });
+
+        // # Step 24: inline the calls to the small functions and to the
+        // functions marked `#[inline(always)]`, if the user asked for it.
+        passes.register_fn_if(
+            "inline_functions",
+            options.inline_threshold.is_some() || options.inline_always,
+            |ctx, funs, globals| {
+                inline_functions::transform(
+                    ctx.fmt_ctx,
+                    options.inline_threshold,
+                    options.inline_always,
+                    funs,
+                    globals,
+                )
+            },
+        );
+
+        // # Step 25: eliminate the common subexpressions inside the blocks
+        // (for instance, the lengths computed by several bounds checks). The
+        // computations we replace with copies may leave some locals unused.
+        passes.register_fn("common_subexpressions", |ctx, funs, globals| {
+            common_subexpressions::transform(ctx.fmt_ctx, funs, globals)
+        });
+
+        // # Step 26: propagate the copies of locals and the constants inside
+        // the blocks, if the user asked for it. The temporaries holding the
+        // copies are then removed by the next step.
+        passes.register_fn_if(
+            "propagate_copies",
+            options.propagate_copies,
+            |ctx, funs, globals| propagate_copies::transform(ctx.fmt_ctx, funs, globals),
+        );
 
-        // # Step 13: remove the drops of locals whose type is `Never` (`!`). This
-        // is in preparation of the next transformation.
-        remove_drop_never::transform(&fmt_ctx, &mut llbc_funs, &mut llbc_globals);
+        // # Step 27: remove the assignments to the locals which are never read,
+        // then the locals which are never used. After doing so, we check that
+        // there are no remaining locals with type `Never`.
+        passes.register_fn("remove_unused_locals", |ctx, funs, globals| {
+            remove_unused_locals::transform(ctx.fmt_ctx, funs, globals)
+        });
+
+        // Reject the references to (potentially) under-aligned fields of
+        // packed structures.
+        passes.register_fn("packed_fields", |ctx, funs, globals| {
+            packed_fields::check(ctx.type_defs, funs, globals)
+        });
+
+        // # Step 28: recognize the compound assignments (`x += y`), if the
+        // user asked for it.
+        passes.register_fn_if(
+            "compound_assign",
+            options.compound_assignments,
+            |ctx, funs, globals| compound_assign::transform(ctx.fmt_ctx, funs, globals),
+        );
 
-        // # Step 14: remove the locals which are never used. After doing so, we
-        // check that there are no remaining locals with type `Never`.
-        remove_unused_locals::transform(&fmt_ctx, &mut llbc_funs, &mut llbc_globals);
+        // # Step 29: move the returns to the end of the bodies, so that the
+        // functions have a single exit point, if the user asked for it.
+        passes.register_fn_if("single_exit", options.single_exit, |ctx, funs, globals| {
+            single_exit::transform(ctx.fmt_ctx, funs, globals)
+        });
+
+        // # Step 30: rewrite the loops which start by testing a condition to
+        // `while` loops, unless the user prefers the uniform encoding of the
+        // loops.
+        passes.register_fn_if(
+            "while_loops",
+            !options.no_while_loops,
+            |ctx, funs, globals| while_loops::transform(ctx.fmt_ctx, funs, globals),
+        );
+
+        // # Step 31: annotate the match branches with their provenance, if the
+        // user asked for it. The passes which rebuild the matches drop this
+        // information: this pass must thus come last.
+        passes.register_fn_if(
+            "match_provenance",
+            options.match_provenance,
+            |ctx, funs, globals| match_provenance::transform(ctx.fmt_ctx, funs, globals),
+        );
 
-        // # Step 15: compute which functions are potentially divergent. A function
+        // Insert the user passes, then apply the pipeline
+        for (position, pass) in &internal.passes {
+            if let Err(err) = passes.register_at(position, Box::new(&**pass)) {
+                error!("Could not register the pass {}: {err}", pass.name());
+                return Err(());
+            }
+        }
+        let ctx = PassCtx {
+            fmt_ctx: &fmt_ctx,
+            type_defs: &type_defs,
+            id_to_file: &ordered_decls.id_to_file,
+            diagnostics,
+        };
+        let num_failed = passes.run(
+            &ctx,
+            &mut llbc_funs,
+            &mut llbc_globals,
+            options.errors_as_warnings,
+        )?;
+        applied_passes.extend(passes.enabled_names().into_iter().map(String::from));
+
+        // Put back the functions we reused from the previous extraction
+        if let Some(incremental) = &incremental {
+            incremental.restore(&mut llbc_funs);
+        }
+
+        // In debug mode, check that the micro-passes preserved the invariant
+        // on the field projections: the projections into enumerations must
+        // follow a downcast.
+        if cfg!(debug_assertions) {
+            check_places::check(&type_defs, &mut llbc_funs, &mut llbc_globals);
+        }
+
+        // # Step 31 bis: monomorphize the program, if the user asked for it.
+        // This replaces all the declarations. The functions we don't simplify
+        // are exported in ULLBC: we can't monomorphize them, and drop them.
+        let monomorphized;
+        let (ordered_decls, type_defs, llbc_funs, llbc_globals, trait_decls, trait_impls) =
+            if options.monomorphize {
+                monomorphized = monomorphize::monomorphize(
+                    &ordered_decls,
+                    &type_defs,
+                    &llbc_funs,
+                    &llbc_globals,
+                    &trait_decls,
+                    &trait_impls,
+                )?;
+                (
+                    &monomorphized.ordered_decls,
+                    &monomorphized.type_defs,
+                    &monomorphized.funs,
+                    &monomorphized.globals,
+                    &monomorphized.trait_decls,
+                    &monomorphized.trait_impls,
+                )
+            } else {
+                (
+                    &ordered_decls,
+                    &type_defs,
+                    &llbc_funs,
+                    &llbc_globals,
+                    &trait_decls,
+                    &trait_impls,
+                )
+            };
+        let mut unstructured_funs = unstructured_funs;
+        if options.monomorphize && !unstructured_funs.is_empty() {
+            log::warn!("Dropping the functions we don't simplify: they can't be monomorphized");
+            unstructured_funs.clear();
+        }
+
+        // # Step 32: compute which functions are potentially divergent. A function
         // is potentially divergent if it is recursive, contains a loop or transitively
         // calls a potentially divergent function.
         // Note that in the future, we may complement this basic analysis with a
         // finer analysis to detect recursive functions which are actually total
         // by construction.
         // Because we don't have loops, constants are not yet touched.
-        let _divergent = divergent::compute_divergent_functions(&ordered_decls, &llbc_funs);
-
-        // # Step 16: generate the files.
-        export::export_llbc(
-            crate_name,
-            &ordered_decls,
-            &type_defs,
-            &llbc_funs,
-            &llbc_globals,
-            &options.dest_dir,
-        )?;
+        let _divergent = divergent::compute_divergent_functions(ordered_decls, llbc_funs);
+
+        // # Step 33: generate the files.
+        if options.print_llbc {
+            export::export_llbc_text(
+                &crate_name,
+                ordered_decls,
+                type_defs,
+                llbc_funs,
+                llbc_globals,
+                &options.dest_dir,
+            )?;
+        }
+        if options.call_graph {
+            let graph = CallGraph::from_llbc(llbc_funs);
+            export::export_call_graph(&crate_name, &graph, &options.dest_dir)?;
+        }
+        if options.html_report {
+            html_report::export_report(
+                &crate_name,
+                &ordered_decls.id_to_file,
+                type_defs,
+                llbc_funs,
+                llbc_globals,
+                &options.dest_dir,
+            )?;
+        }
+        let header = export::Header::new(crate_name, crate_hash, applied_passes);
+        if emit == cli_options::Emit::Both {
+            export::export_llbc_with_ullbc(
+                &header,
+                ordered_decls,
+                type_defs,
+                llbc_funs,
+                llbc_globals,
+                &ullbc_funs,
+                &ullbc_globals,
+                trait_decls,
+                trait_impls,
+                &unstructured_funs,
+                &options.dest_dir,
+                options.output_format,
+            )?;
+        } else {
+            export::export_llbc(
+                &header,
+                ordered_decls,
+                type_defs,
+                llbc_funs,
+                llbc_globals,
+                trait_decls,
+                trait_impls,
+                &unstructured_funs,
+                &options.dest_dir,
+                options.output_format,
+            )?;
+        }
+        // We don't cache the output if we dropped some bodies: the next
+        // extraction must translate them again
+        match incremental {
+            Some(incremental) if num_failed == 0 => incremental.save(options)?,
+            Some(_) => log::warn!(
+                "Not saving the incremental cache: we failed to transform {num_failed} declarations"
+            ),
+            None => (),
+        }
     }
     trace!("Done");
 
@@ -0,0 +1,517 @@
+//! Remove the `PhantomData` fields from the structures and the enumerations
+//! (see the `--erase-phantoms` option). Those fields are zero-sized, and are
+//! only used to constrain the type parameters: the backends don't need them.
+//!
+//! We update the bodies accordingly:
+//! - the indices of the remaining fields change in the projections;
+//! - the aggregates and the constant ADTs don't contain the values of the
+//!   removed fields anymore;
+//! - the reads of the removed fields become zero-sized constants
+//!   ([OperandConstantValue::ZST]), and the borrows of those fields (which
+//!   appear for instance in the derived implementations of `Clone` or
+//!   `Debug`) borrow a fresh variable holding such a constant;
+//! - the writes to the removed fields are removed.
+//!
+//! We need the types of the removed fields to build the zero-sized constants,
+//! so we update the bodies before the type declarations.
+
+use crate::assumed;
+use crate::expressions::*;
+use crate::meta::Meta;
+use crate::types::*;
+use crate::ullbc_ast::{
+    iter_function_bodies, iter_global_bodies, make_locals_generator, BlockData, ExprBody, FunDecls,
+    FunId, GlobalDecls, RawStatement, RawTerminator, Statement,
+};
+use std::collections::{HashMap, HashSet};
+
+/// The removed fields, for every structure (whose variant is `None`) and every
+/// variant of an enumeration
+type ErasedFields = HashMap<(TypeDeclId::Id, Option<VariantId::Id>), HashSet<FieldId::Id>>;
+
+fn is_phantom_data(type_defs: &TypeDecls, ty: &RTy) -> bool {
+    match ty {
+        Ty::Adt(TypeId::Adt(id), _, _) => type_defs
+            .get_type_def(*id)
+            .unwrap()
+            .name
+            .equals_ref_name(&assumed::PHANTOM_DATA_NAME),
+        _ => false,
+    }
+}
+
+fn compute_erased_fields(type_defs: &TypeDecls) -> ErasedFields {
+    let mut erased = HashMap::new();
+    let mut register = |id, variant_id, fields: &FieldId::Vector<Field>| {
+        let phantoms: HashSet<FieldId::Id> = fields
+            .iter_indexed_values()
+            .filter(|(_, f)| is_phantom_data(type_defs, &f.ty))
+            .map(|(field_id, _)| field_id)
+            .collect();
+        if !phantoms.is_empty() {
+            erased.insert((id, variant_id), phantoms);
+        }
+    };
+    for def in type_defs.types.iter() {
+        match &def.kind {
+            TypeDeclKind::Struct(fields) => register(def.def_id, None, fields),
+            TypeDeclKind::Enum(variants) => {
+                for (variant_id, variant) in variants.iter_indexed_values() {
+                    register(def.def_id, Some(variant_id), &variant.fields)
+                }
+            }
+            // The fields of a union overlap: we don't touch them
+            TypeDeclKind::Union(_) | TypeDeclKind::Opaque => (),
+        }
+    }
+    erased
+}
+
+fn is_erased_field(
+    erased: &ErasedFields,
+    id: TypeDeclId::Id,
+    variant_id: Option<VariantId::Id>,
+    field_id: FieldId::Id,
+) -> bool {
+    erased
+        .get(&(id, variant_id))
+        .map_or(false, |fields| fields.contains(&field_id))
+}
+
+/// The index of a field, once the fields which precede it are removed
+fn new_field_id(
+    erased: &ErasedFields,
+    id: TypeDeclId::Id,
+    variant_id: Option<VariantId::Id>,
+    field_id: FieldId::Id,
+) -> FieldId::Id {
+    match erased.get(&(id, variant_id)) {
+        None => field_id,
+        Some(fields) => {
+            let removed = fields.iter().filter(|f| **f < field_id).count();
+            FieldId::Id::new(field_id.to_usize() - removed)
+        }
+    }
+}
+
+struct Eraser<'a> {
+    type_defs: &'a TypeDecls,
+    erased: &'a ErasedFields,
+}
+
+impl<'a> Eraser<'a> {
+    /// Return `true` if the place goes through a removed field
+    fn is_erased_place(&self, p: &Place) -> bool {
+        p.projection.iter().any(|pe| match pe {
+            ProjectionElem::Field(FieldProjKind::Adt(id, variant_id), field_id) => {
+                is_erased_field(self.erased, *id, *variant_id, *field_id)
+            }
+            _ => false,
+        })
+    }
+
+    fn renumber_place(&self, p: &mut Place) {
+        for pe in p.projection.iter_mut() {
+            if let ProjectionElem::Field(FieldProjKind::Adt(id, variant_id), field_id) = pe {
+                *field_id = new_field_id(self.erased, *id, *variant_id, *field_id);
+            }
+        }
+    }
+
+    /// Remove the values of the removed fields from a constant ADT
+    fn transform_constant(&self, ty: &ETy, cv: &mut OperandConstantValue) {
+        if let OperandConstantValue::Adt(variant_id, values) = cv {
+            let field_tys: Vec<ETy> = match ty {
+                Ty::Adt(TypeId::Adt(id), _, tys) => {
+                    let def = self.type_defs.get_type_def(*id).unwrap();
+                    if def.kind.is_opaque() {
+                        return;
+                    }
+                    def.get_erased_regions_instantiated_field_types(*variant_id, tys)
+                        .into_iter()
+                        .collect()
+                }
+                Ty::Adt(TypeId::Tuple, _, tys) => tys.iter().cloned().collect(),
+                _ => return,
+            };
+            for (field_ty, value) in field_tys.iter().zip(values.iter_mut()) {
+                self.transform_constant(field_ty, value)
+            }
+            if let Ty::Adt(TypeId::Adt(id), _, _) = ty {
+                let mut field_id = FieldId::ZERO;
+                values.retain(|_| {
+                    let keep = !is_erased_field(self.erased, *id, *variant_id, field_id);
+                    field_id.incr();
+                    keep
+                });
+            }
+        }
+    }
+
+    fn transform_operand(&self, b: &ExprBody, op: &mut Operand) {
+        match op {
+            Operand::Copy(p) | Operand::Move(p) => {
+                if self.is_erased_place(p) {
+                    let ty = b.place_type(self.type_defs, p);
+                    *op = Operand::Const(ty.clone(), OperandConstantValue::ZST(ty));
+                } else {
+                    self.renumber_place(p)
+                }
+            }
+            Operand::Const(ty, cv) => self.transform_constant(ty, cv),
+        }
+    }
+
+    fn transform_rvalue(
+        &self,
+        b: &mut ExprBody,
+        meta: &Meta,
+        nst: &mut Vec<Statement>,
+        rv: &mut Rvalue,
+    ) {
+        // Remove the operands of the removed fields from the aggregates
+        if let Rvalue::Aggregate(AggregateKind::Adt(id, variant_id, _, _), ops) = rv {
+            let mut field_id = FieldId::ZERO;
+            ops.retain(|_| {
+                let keep = !is_erased_field(self.erased, *id, *variant_id, field_id);
+                field_id.incr();
+                keep
+            });
+        }
+
+        BlockData::transform_rvalue_operands(meta, nst, rv, &mut |_, _, op| {
+            self.transform_operand(b, op)
+        });
+
+        // We can't borrow a removed field: we borrow a fresh variable instead
+        if let Rvalue::Ref(p, _) | Rvalue::RawPtr(p, _) = rv {
+            if self.is_erased_place(p) {
+                let ty = b.place_type(self.type_defs, p);
+                let var = make_locals_generator(&mut b.locals)(ty.clone());
+                nst.push(Statement::new(
+                    *meta,
+                    RawStatement::Assign(
+                        Place::new(var),
+                        Rvalue::Use(Operand::Const(ty.clone(), OperandConstantValue::ZST(ty))),
+                    ),
+                ));
+                *p = Place::new(var);
+            }
+        }
+        rv.transform_places(&mut |p| self.renumber_place(p));
+    }
+
+    fn transform_block(&self, b: &mut ExprBody, block: &mut BlockData) {
+        let mut nst = Vec::new();
+        for mut st in std::mem::take(&mut block.statements) {
+            // Remove the writes to the removed fields
+            let erased = match &st.content {
+                RawStatement::Assign(p, _)
+                | RawStatement::FakeRead(p)
+                | RawStatement::SetDiscriminant(p, _)
+                | RawStatement::Deinit(p) => self.is_erased_place(p),
+                RawStatement::StorageDead(_) => false,
+            };
+            if erased {
+                continue;
+            }
+
+            let meta = st.meta;
+            match &mut st.content {
+                RawStatement::Assign(p, rv) => {
+                    self.transform_rvalue(b, &meta, &mut nst, rv);
+                    self.renumber_place(p);
+                }
+                RawStatement::FakeRead(p)
+                | RawStatement::SetDiscriminant(p, _)
+                | RawStatement::Deinit(p) => self.renumber_place(p),
+                RawStatement::StorageDead(_) => (),
+            }
+            nst.push(st);
+        }
+        block.statements = nst;
+
+        let mut dropped_phantom = None;
+        match &mut block.terminator.content {
+            RawTerminator::Switch { discr, .. } => self.transform_operand(b, discr),
+            RawTerminator::Call {
+                func, args, dest, ..
+            } => {
+                if let FunId::Indirect(op) = func {
+                    self.transform_operand(b, op);
+                }
+                for arg in args.iter_mut() {
+                    self.transform_operand(b, arg);
+                }
+                // The result of the call is ignored
+                if self.is_erased_place(dest) {
+                    let ty = b.place_type(self.type_defs, dest);
+                    *dest = Place::new(make_locals_generator(&mut b.locals)(ty));
+                } else {
+                    self.renumber_place(dest)
+                }
+            }
+            RawTerminator::Assert { cond, .. } => self.transform_operand(b, cond),
+            RawTerminator::Drop { place, target } => {
+                if self.is_erased_place(place) {
+                    dropped_phantom = Some(*target);
+                } else {
+                    self.renumber_place(place)
+                }
+            }
+            RawTerminator::Goto { .. }
+            | RawTerminator::Panic
+            | RawTerminator::Return
+            | RawTerminator::Unreachable => (),
+        }
+        // Dropping a `PhantomData` does nothing
+        if let Some(target) = dropped_phantom {
+            block.terminator.content = RawTerminator::Goto { target };
+        }
+    }
+
+    fn transform_body(&self, b: &mut ExprBody) {
+        // We need the body (its locals) to compute the types of the places:
+        // we temporarily move the blocks out of it.
+        let mut blocks = std::mem::take(&mut b.body);
+        for block in blocks.iter_mut() {
+            self.transform_block(b, block);
+        }
+        b.body = blocks;
+    }
+}
+
+fn erase_fields(
+    erased: &ErasedFields,
+    id: TypeDeclId::Id,
+    variant_id: Option<VariantId::Id>,
+    fields: &mut FieldId::Vector<Field>,
+) {
+    if let Some(phantoms) = erased.get(&(id, variant_id)) {
+        *fields = FieldId::Vector::from_iter(
+            fields
+                .iter_indexed_values()
+                .filter(|(field_id, _)| !phantoms.contains(field_id))
+                .map(|(_, f)| f.clone()),
+        );
+    }
+}
+
+/// Remove the fields from the type declarations, and update the indices of
+/// the recursive fields
+fn transform_type_decls(erased: &ErasedFields, type_defs: &mut TypeDecls) {
+    for def in type_defs.types.iter_mut() {
+        let id = def.def_id;
+        match &mut def.kind {
+            TypeDeclKind::Struct(fields) => erase_fields(erased, id, None, fields),
+            TypeDeclKind::Enum(variants) => {
+                for (variant_id, variant) in variants.iter_mut().enumerate() {
+                    let variant_id = Some(VariantId::Id::new(variant_id));
+                    erase_fields(erased, id, variant_id, &mut variant.fields)
+                }
+            }
+            TypeDeclKind::Union(_) | TypeDeclKind::Opaque => (),
+        }
+        def.recursive_fields = std::mem::take(&mut def.recursive_fields)
+            .into_iter()
+            .filter(|(variant_id, field_id)| !is_erased_field(erased, id, *variant_id, *field_id))
+            .map(|(variant_id, field_id)| {
+                (variant_id, new_field_id(erased, id, variant_id, field_id))
+            })
+            .collect();
+    }
+}
+
+pub fn transform(type_defs: &mut TypeDecls, funs: &mut FunDecls, globals: &mut GlobalDecls) {
+    let erased = compute_erased_fields(type_defs);
+    if erased.is_empty() {
+        return;
+    }
+
+    let eraser = Eraser {
+        type_defs,
+        erased: &erased,
+    };
+    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+        trace!("# About to erase the phantom fields in: {name}");
+        eraser.transform_body(b);
+    }
+    transform_type_decls(&erased, type_defs);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::erase_phantoms::*;
+    use crate::meta::AttrInfo;
+    use crate::names::Name;
+    use crate::regions_hierarchy::RegionGroups;
+    use crate::ullbc_ast::{BlockId, Terminator, Var};
+    use crate::values::VarId;
+
+    fn mk_type_decl(def_id: TypeDeclId::Id, name: &[&str], kind: TypeDeclKind) -> TypeDecl {
+        TypeDecl {
+            def_id,
+            meta: Meta::dummy(),
+            name: Name::from(name.iter().map(|s| s.to_string()).collect::<Vec<String>>()),
+            region_params: RegionVarId::Vector::new(),
+            type_params: TypeVarId::Vector::from(vec![TypeVar::new(
+                TypeVarId::ZERO,
+                "T".to_string(),
+            )]),
+            const_generic_params: ConstGenericVarId::Vector::new(),
+            trait_clauses: Vec::new(),
+            regions_hierarchy: RegionGroups::new(),
+            kind,
+            repr_packed: None,
+            under_aligned_fields: Vec::new(),
+            recursive_fields: Vec::new(),
+            attr_info: AttrInfo::default(),
+        }
+    }
+
+    fn mk_field(name: &str, ty: RTy) -> Field {
+        Field {
+            meta: Meta::dummy(),
+            name: Some(name.to_string()),
+            ty,
+        }
+    }
+
+    fn mk_adt<R: Clone + Eq>(id: TypeDeclId::Id, arg: Ty<R>) -> Ty<R> {
+        Ty::Adt(TypeId::Adt(id), im::Vector::new(), im::vector![arg])
+    }
+
+    fn mk_assign(p: Place, rv: Rvalue) -> Statement {
+        Statement::new(Meta::dummy(), RawStatement::Assign(p, rv))
+    }
+
+    #[test]
+    fn test_erase_phantoms() {
+        // ```
+        // struct Wrapper<T> { marker: PhantomData<T>, value: u32 }
+        // ```
+        let phantom_id = TypeDeclId::ZERO;
+        let wrapper_id = TypeDeclId::ONE;
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let fields = vec![
+            mk_field("marker", mk_adt(phantom_id, Ty::TypeVar(TypeVarId::ZERO))),
+            mk_field("value", Ty::Integer(IntegerTy::U32)),
+        ];
+        let mut type_defs = TypeDecls::new();
+        type_defs.types.push_back(mk_type_decl(
+            phantom_id,
+            &assumed::PHANTOM_DATA_NAME,
+            TypeDeclKind::Opaque,
+        ));
+        type_defs.types.push_back(mk_type_decl(
+            wrapper_id,
+            &["test", "Wrapper"],
+            TypeDeclKind::Struct(FieldId::Vector::from(fields)),
+        ));
+        let erased = compute_erased_fields(&type_defs);
+        assert!(erased.len() == 1);
+
+        // ```
+        // w := Wrapper { marker: const zst, value: copy x };
+        // y := copy w.value;
+        // m := move w.marker;
+        // r := &w.marker;
+        // w.marker := move m;
+        // return
+        // ```
+        let phantom_ty = mk_adt(phantom_id, u32_ty.clone());
+        let wrapper_ty = mk_adt(wrapper_id, u32_ty.clone());
+        let zst = Operand::Const(
+            phantom_ty.clone(),
+            OperandConstantValue::ZST(phantom_ty.clone()),
+        );
+        let local_tys = vec![
+            Ty::Adt(TypeId::Tuple, im::Vector::new(), im::Vector::new()),
+            u32_ty.clone(),
+            wrapper_ty,
+            u32_ty,
+            phantom_ty.clone(),
+            Ty::Ref(ErasedRegion::Erased, Box::new(phantom_ty), RefKind::Shared),
+        ];
+        let locals = local_tys
+            .into_iter()
+            .enumerate()
+            .map(|(i, ty)| Var {
+                index: VarId::Id::new(i),
+                name: None,
+                ty,
+            })
+            .collect();
+        let var = |i| Place::new(VarId::Id::new(i));
+        let field = |i| {
+            let mut p = var(2);
+            p.projection.push_back(ProjectionElem::Field(
+                FieldProjKind::Adt(wrapper_id, None),
+                FieldId::Id::new(i),
+            ));
+            p
+        };
+        let kind = AggregateKind::Adt(wrapper_id, None, vec![], vec![Ty::Integer(IntegerTy::U32)]);
+        let statements = vec![
+            mk_assign(
+                var(2),
+                Rvalue::Aggregate(kind, vec![zst.clone(), Operand::Copy(var(1))]),
+            ),
+            mk_assign(var(3), Rvalue::Use(Operand::Copy(field(1)))),
+            mk_assign(var(4), Rvalue::Use(Operand::Move(field(0)))),
+            mk_assign(var(5), Rvalue::Ref(field(0), BorrowKind::Shared)),
+            mk_assign(field(0), Rvalue::Use(Operand::Move(var(4)))),
+        ];
+        let mut body = ExprBody {
+            meta: Meta::dummy(),
+            arg_count: 1,
+            return_local: VarId::ZERO,
+            locals,
+            body: BlockId::Vector::from(vec![BlockData {
+                statements,
+                terminator: Terminator::new(Meta::dummy(), RawTerminator::Return),
+            }]),
+        };
+        let eraser = Eraser {
+            type_defs: &type_defs,
+            erased: &erased,
+        };
+        eraser.transform_body(&mut body);
+
+        // ```
+        // w := Wrapper { value: copy x };
+        // y := copy w.value; // the field now has index 0
+        // m := const zst;
+        // tmp := const zst;
+        // r := &tmp;
+        // return
+        // ```
+        let sts: Vec<&RawStatement> = body
+            .body
+            .get(BlockId::ZERO)
+            .unwrap()
+            .statements
+            .iter()
+            .map(|st| &st.content)
+            .collect();
+        assert!(sts.len() == 5);
+        assert!(
+            matches!(sts[0], RawStatement::Assign(_, Rvalue::Aggregate(_, ops)) if *ops == vec![Operand::Copy(var(1))])
+        );
+        assert!(
+            matches!(sts[1], RawStatement::Assign(_, Rvalue::Use(Operand::Copy(p))) if *p == field(0))
+        );
+        assert!(matches!(sts[2], RawStatement::Assign(_, Rvalue::Use(op)) if *op == zst));
+        assert!(
+            matches!(sts[3], RawStatement::Assign(p, Rvalue::Use(op)) if *p == var(6) && *op == zst)
+        );
+        assert!(matches!(sts[4], RawStatement::Assign(_, Rvalue::Ref(p, _)) if *p == var(6)));
+        assert!(body.locals.len() == 7);
+
+        transform_type_decls(&erased, &mut type_defs);
+        let wrapper = type_defs.get_type_def(wrapper_id).unwrap();
+        let fields = wrapper.kind.as_struct();
+        assert!(fields.len() == 1);
+        assert!(fields.get(FieldId::ZERO).unwrap().name.as_deref() == Some("value"));
+    }
+}
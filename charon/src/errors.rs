@@ -0,0 +1,60 @@
+//! The errors we raise when a declaration has a shape we don't expect.
+//!
+//! Those errors are local to a declaration: the micro-passes report them
+//! with the identifiers of the declarations they failed to transform (see
+//! [crate::passes::Pass]), instead of panicking. By default, the driver
+//! then stops, but with `--errors-as-warnings` it only reports the errors
+//! and exports the offending declarations without their bodies.
+#![allow(dead_code)]
+
+use crate::meta::Meta;
+use crate::ullbc_ast::{FunDeclId, GlobalDeclId};
+
+/// An error in the transformation of a body
+#[derive(Debug, Clone)]
+pub struct TranslationError {
+    /// The location of the statement we failed to transform
+    pub meta: Meta,
+    pub msg: String,
+}
+
+impl TranslationError {
+    pub fn new(meta: Meta, msg: impl Into<String>) -> Self {
+        TranslationError {
+            meta,
+            msg: msg.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for TranslationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let beg = &self.meta.span.beg;
+        write!(f, "at line {}, column {}: {}", beg.line, beg.col, self.msg)
+    }
+}
+
+pub type TransResult<T> = std::result::Result<T, TranslationError>;
+
+/// The errors of a pass, for the declarations it failed to transform
+#[derive(Debug, Default)]
+pub struct DeclErrors {
+    pub funs: Vec<(FunDeclId::Id, TranslationError)>,
+    pub globals: Vec<(GlobalDeclId::Id, TranslationError)>,
+}
+
+impl DeclErrors {
+    pub fn is_empty(&self) -> bool {
+        self.funs.is_empty() && self.globals.is_empty()
+    }
+
+    pub fn into_result(self) -> PassResult {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+pub type PassResult = std::result::Result<(), DeclErrors>;
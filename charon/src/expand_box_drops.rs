@@ -0,0 +1,192 @@
+//! Expand the drops of nested boxes.
+//!
+//! Dropping a value of type `Box<Box<T>>` frees two allocations, which must
+//! be freed from the innermost to the outermost: the inner box lives inside
+//! the allocation of the outer box, and can't be accessed anymore once the
+//! outer box has been freed. Recursive data structures built with boxes (lists,
+//! trees) rely on this ordering. We make it explicit by rewriting:
+//! ```text
+//! drop x; // x: Box<Box<T>>
+//! ```
+//! to:
+//! ```text
+//! drop (deref_box (x));             // drops the inner box (and its `T`)
+//! tmp := box_free<Box<T>>(move x);  // only frees the outer allocation
+//! ```
+//! The outer boxes are freed with `box_free` rather than dropped: dropping
+//! them would drop their content a second time.
+//!
+//! This is only valid if the boxes are not desugared (i.e., if
+//! [ProjectionElem::DerefBox] gives access to the boxed value): in optimized
+//! MIR, the drops are already elaborated by rustc.
+
+use crate::expressions::*;
+use crate::llbc_ast::{
+    chain_statements, transform_statements, Call, CtxNames, ExprBody, FunDecls, GlobalDecls,
+    RawStatement, Statement,
+};
+use crate::types::*;
+use crate::ullbc_ast::{
+    iter_function_bodies, iter_global_bodies, make_locals_generator, AssumedFunId, FunId,
+};
+use crate::values::VarId;
+use take_mut::take;
+
+fn is_box(ty: &ETy) -> bool {
+    matches!(ty, Ty::Adt(TypeId::Assumed(AssumedTy::Box), _, _))
+}
+
+/// If the statement drops a box containing a box, expand the drop.
+fn transform_st<F: FnMut(ETy) -> VarId::Id>(
+    type_defs: &TypeDecls,
+    body: &ExprBody,
+    make_new_var: &mut F,
+    st: Statement,
+) -> Statement {
+    let p = match &st.content {
+        RawStatement::Drop(p) => p,
+        _ => return st,
+    };
+
+    // Compute the places of the boxes, from the outermost to the innermost
+    let mut boxes = Vec::new();
+    let mut box_place = p.clone();
+    while is_box(&body.place_type(type_defs, &box_place)) {
+        boxes.push(box_place.clone());
+        box_place.projection.push_back(ProjectionElem::DerefBox);
+    }
+    if boxes.len() < 2 {
+        return st;
+    }
+
+    // Drop the innermost box first, then free the outer boxes from the
+    // innermost to the outermost
+    let meta = st.meta;
+    let innermost = boxes.pop().unwrap();
+    let mut sts = vec![Statement::new(meta, RawStatement::Drop(innermost))];
+    for p in boxes.into_iter().rev() {
+        let boxed_ty = match body.place_type(type_defs, &p) {
+            Ty::Adt(_, _, tys) => tys[0].clone(),
+            _ => unreachable!(),
+        };
+        let call = Call {
+            func: FunId::Assumed(AssumedFunId::BoxFree),
+            region_args: vec![],
+            type_args: vec![boxed_ty],
+            args: vec![Operand::Move(p)],
+            dest: Place::new(make_new_var(Ty::mk_unit())),
+            sig: None,
+        };
+        sts.push(Statement::new(meta, RawStatement::Call(call)));
+    }
+    let last = sts.pop().unwrap();
+    chain_statements(sts, last)
+}
+
+fn transform_body(type_defs: &TypeDecls, body: &mut ExprBody) {
+    // We need to compute the types of the places while generating new locals
+    // (the destinations of the calls to `box_free`): the new locals have type
+    // unit, so we can use a copy of the locals (we don't need the statements
+    // to compute the types)
+    let locals_body = ExprBody {
+        meta: body.meta,
+        arg_count: body.arg_count,
+        return_local: body.return_local,
+        locals: body.locals.clone(),
+        body: Statement::new(body.meta, RawStatement::Nop),
+    };
+    let mut make_new_var = make_locals_generator(&mut body.locals);
+    take(&mut body.body, |st| {
+        transform_statements(
+            &mut |st| transform_st(type_defs, &locals_body, &mut make_new_var, st),
+            st,
+        )
+    });
+}
+
+/// `fmt_ctx` is used for pretty-printing purposes.
+pub fn transform(
+    fmt_ctx: &CtxNames<'_>,
+    type_defs: &TypeDecls,
+    funs: &mut FunDecls,
+    globals: &mut GlobalDecls,
+) {
+    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+        trace!(
+            "# About to expand the drops of nested boxes in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        transform_body(type_defs, b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expand_box_drops::*;
+    use crate::test_utils::*;
+    use crate::values::VarId;
+
+    fn mk_box(ty: ETy) -> ETy {
+        Ty::Adt(
+            TypeId::Assumed(AssumedTy::Box),
+            im::Vector::new(),
+            im::vector![ty],
+        )
+    }
+
+    fn deref_box(mut p: Place) -> Place {
+        p.projection.push_back(ProjectionElem::DerefBox);
+        p
+    }
+
+    #[test]
+    fn test_nested_box_deref_and_drop() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let ret = VarId::Id::new(0);
+        let x = VarId::Id::new(1);
+        // ```
+        // @return := copy deref_box (deref_box (x)); // x: Box<Box<u32>>
+        // drop x;
+        // return
+        // ```
+        let inner = deref_box(deref_box(Place::new(x)));
+        let mut body = mk_body(
+            1,
+            vec![
+                mk_var(0, None, u32_ty.clone()),
+                mk_var(1, None, mk_box(mk_box(u32_ty.clone()))),
+            ],
+            vec![
+                RawStatement::Assign(Place::new(ret), Rvalue::Use(Operand::Copy(inner.clone()))),
+                RawStatement::Drop(Place::new(x)),
+                RawStatement::Return,
+            ],
+        );
+
+        // Type resolution
+        let type_defs = TypeDecls::new();
+        assert!(body.place_type(&type_defs, &inner) == u32_ty);
+        assert!(body.place_type(&type_defs, &deref_box(Place::new(x))) == mk_box(u32_ty.clone()));
+
+        // Drop ordering: the inner box is dropped before the outer one is
+        // freed (without dropping its content again)
+        transform_body(&type_defs, &mut body);
+        let sts = flatten(&body.body);
+        assert!(sts.len() == 4);
+        assert!(matches!(sts[1], RawStatement::Drop(p) if *p == deref_box(Place::new(x))));
+        assert!(matches!(
+            sts[2],
+            RawStatement::Call(Call {
+                func: FunId::Assumed(AssumedFunId::BoxFree),
+                type_args,
+                args,
+                dest,
+                ..
+            }) if type_args[0] == mk_box(u32_ty.clone())
+                && matches!(&args[0], Operand::Move(p) if *p == Place::new(x))
+                && dest.var_id == VarId::Id::new(2)
+        ));
+        assert!(body.locals.get(VarId::Id::new(2)).unwrap().ty == Ty::mk_unit());
+        assert!(sts[3].is_return());
+    }
+}
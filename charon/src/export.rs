@@ -1,13 +1,22 @@
+use crate::callgraph::CallGraph;
+use crate::cli_options::OutputFormat;
 use crate::common::*;
+use crate::decls_index::IndexEntry;
+use crate::diagnostics::{Diagnostic, Diagnostics};
 use crate::llbc_ast;
 use crate::meta::{FileId, FileName};
+use crate::reorder_decls::GDeclarationGroup;
 use crate::rust_to_local_ids::*;
 use crate::types::*;
 use crate::ullbc_ast;
-use crate::ullbc_ast::{FunDeclId, GlobalDeclId};
-use serde::{Serialize, Serializer};
+use crate::ullbc_ast::{FunDeclId, GlobalDeclId, TraitDecls, TraitImpls};
+use serde::de::{DeserializeOwned, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
 use std::fs::File;
-use std::path::PathBuf;
+use std::io::{BufReader, BufWriter};
+use std::iter::FromIterator;
+use std::path::{Path, PathBuf};
 
 /// Serialization wrapper for vectors
 pub struct VecSW<'a, T> {
@@ -29,6 +38,136 @@ impl<'a, T: Serialize> Serialize for VecSW<'a, T> {
     }
 }
 
+/// The version of the format of the exported files.
+///
+/// We bump the major version whenever we change the shape of the AST in an
+/// incompatible manner (for instance, if we remove or reorder variants), and
+/// the minor version when we only add things (for instance, new variants at
+/// the end of an enumeration). The consumers should reject the files whose
+/// major version is not the one they expect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormatVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+pub const FORMAT_VERSION: FormatVersion = FormatVersion { major: 2, minor: 0 };
+
+/// The header of the exported files, which allows the consumers to detect
+/// which AST shape they are reading
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Header {
+    pub format_version: FormatVersion,
+    /// The version of Charon which generated the file
+    pub charon_version: String,
+    /// The micro-passes which were applied, in order
+    pub passes: Vec<String>,
+    pub crate_name: String,
+    /// The hash of the crate, as computed by rustc (in hexadecimal)
+    pub crate_hash: String,
+}
+
+impl Header {
+    pub fn new(crate_name: String, crate_hash: String, passes: Vec<String>) -> Self {
+        Header {
+            format_version: FORMAT_VERSION,
+            charon_version: env!("CARGO_PKG_VERSION").to_string(),
+            passes,
+            crate_name,
+            crate_hash,
+        }
+    }
+
+    /// Check that we can read a file with this header
+    pub fn check_version(&self) -> std::result::Result<(), String> {
+        if self.format_version.major == FORMAT_VERSION.major {
+            Ok(())
+        } else {
+            Err(format!(
+                "unsupported format version: {}.{} (expected {}.x), the file was generated by \
+                 Charon {}",
+                self.format_version.major,
+                self.format_version.minor,
+                FORMAT_VERSION.major,
+                self.charon_version
+            ))
+        }
+    }
+}
+
+/// The content of the exported files: the header, followed by the crate
+#[derive(Serialize)]
+pub struct Exported<T> {
+    pub header: Header,
+    #[serde(rename = "crate")]
+    pub krate: T,
+}
+
+/// We check the version as soon as we have read the header, so that we
+/// report a version mismatch rather than the first unexpected AST node.
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Exported<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        #[derive(Deserialize)]
+        #[serde(field_identifier, rename_all = "lowercase")]
+        enum Field {
+            Header,
+            Crate,
+        }
+
+        struct ExportedVisitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for ExportedVisitor<T> {
+            type Value = Exported<T>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a header followed by a crate")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                let header: Header = seq
+                    .next_element()?
+                    .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+                header.check_version().map_err(A::Error::custom)?;
+                let krate = seq
+                    .next_element()?
+                    .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+                Ok(Exported { header, krate })
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                // We always serialize the header first
+                let header: Header = match map.next_key()? {
+                    Some(Field::Header) => map.next_value()?,
+                    _ => return Err(A::Error::missing_field("header")),
+                };
+                header.check_version().map_err(A::Error::custom)?;
+                let krate = match map.next_key()? {
+                    Some(Field::Crate) => map.next_value()?,
+                    _ => return Err(A::Error::missing_field("crate")),
+                };
+                Ok(Exported { header, krate })
+            }
+        }
+
+        deserializer.deserialize_struct(
+            "Exported",
+            &["header", "crate"],
+            ExportedVisitor(std::marker::PhantomData),
+        )
+    }
+}
+
 /// An auxiliary type used for serialization of declaration groups
 type DeclarationsSerializer<'a> = VecSW<'a, DeclarationGroup>;
 
@@ -37,56 +176,58 @@ type DeclarationsSerializer<'a> = VecSW<'a, DeclarationGroup>;
 #[serde(rename = "Crate")]
 struct GCrateSerializer<'a, FD: Serialize + Clone, GD: Serialize + Clone> {
     name: String,
-    /// The `id_to_file` map is serialized as a vector.
+    /// The `id_to_file` map is serialized as a vector (sorted by file id, to
+    /// make the serialized file as stable as possible).
     /// We use this map for the spans: the spans only store the file ids, not
     /// the file names, in order to save space.
-    id_to_file: VecSW<'a, (FileId::Id, FileName)>,
+    #[serde(serialize_with = "serialize_sorted_map")]
+    id_to_file: &'a HashMap<FileId::Id, FileName>,
     declarations: DeclarationsSerializer<'a>,
+    /// Additional information about the declarations (visibility, etc.),
+    /// listed in the same order as the declarations.
+    declarations_info: VecSW<'a, (AnyDeclId, DeclInfo)>,
     types: &'a TypeDeclId::Vector<TypeDecl>,
     functions: &'a FunDeclId::Vector<FD>,
     globals: &'a GlobalDeclId::Vector<GD>,
+    /// The traits and the trait implementations the functions belong to:
+    /// they only list the methods, which are in `functions`.
+    trait_decls: &'a TraitDecls,
+    trait_impls: &'a TraitImpls,
+    /// The functions for which we skipped the control-flow reconstruction and
+    /// the micro-passes (see the `--no-simplify` option): we export their
+    /// unstructured bodies here, while their bodies in `functions` are left
+    /// empty. This is always empty when exporting ULLBC.
+    unstructured_functions: VecSW<'a, ullbc_ast::FunDecl>,
 }
 
-/// Export the translated definitions to a JSON file.
-///
-/// This is a generic function, used both for LLBC and ULLBC.
-pub fn gexport<FD: Serialize + Clone, GD: Serialize + Clone>(
-    crate_name: String,
-    ordered_decls: &OrderedDecls,
-    type_defs: &TypeDecls,
-    fun_defs: &FunDeclId::Vector<FD>,
-    global_defs: &GlobalDeclId::Vector<GD>,
+/// Serialize a value to a file in the given format
+fn serialize_to<T: Serialize>(
+    outfile: &File,
+    format: OutputFormat,
+    value: &T,
+) -> std::result::Result<(), String> {
+    let writer = BufWriter::new(outfile);
+    match format {
+        OutputFormat::Json => serde_json::to_writer(writer, value).map_err(|e| e.to_string()),
+        OutputFormat::Cbor => {
+            // The packed format uses the indices of the variants and of the
+            // fields rather than their names
+            let mut serializer =
+                serde_cbor::Serializer::new(serde_cbor::ser::IoWrite::new(writer)).packed_format();
+            value.serialize(&mut serializer).map_err(|e| e.to_string())
+        }
+        OutputFormat::Bincode => bincode::serialize_into(writer, value).map_err(|e| e.to_string()),
+    }
+}
+
+/// Serialize a value to a file, creating the destination directory if
+/// necessary.
+fn write_file<T: Serialize>(
+    target_filename: &Path,
     dest_dir: &Option<PathBuf>,
-    extension: &str,
+    format: OutputFormat,
+    value: &T,
 ) -> Result<()> {
-    // Generate the destination file - we use the crate name for the file name
-    let mut target_filename = dest_dir
-        .as_deref()
-        .map_or_else(PathBuf::new, |d| d.to_path_buf());
-    target_filename.push(format!("{crate_name}.{extension}"));
-
-    trace!("Target file: {:?}", target_filename);
-
-    // Transform the map file id -> file into a vector.
-    // Sort the vector to make the serialized file as stable as possible.
-    let mut file_ids: Vec<FileId::Id> = ordered_decls.id_to_file.keys().copied().collect();
-    file_ids.sort();
-    let id_to_file: Vec<(FileId::Id, FileName)> = file_ids
-        .into_iter()
-        .map(|id| (id, ordered_decls.id_to_file.get(&id).unwrap().clone()))
-        .collect();
-    let id_to_file = VecSW::new(&id_to_file);
-
-    // Serialize
-    let crate_serializer = GCrateSerializer {
-        name: crate_name,
-        id_to_file,
-        declarations: VecSW::new(&ordered_decls.decls),
-        types: &type_defs.types,
-        functions: fun_defs,
-        globals: global_defs,
-    };
-
     // Create the directory, if necessary (note that if the target directory
     // is not specified, there is no need to create it: otherwise we
     // couldn't have read the input file in the first place).
@@ -102,8 +243,8 @@ pub fn gexport<FD: Serialize + Clone, GD: Serialize + Clone>(
     };
 
     // Write to the file
-    match File::create(target_filename.clone()) {
-        std::io::Result::Ok(outfile) => match serde_json::to_writer(&outfile, &crate_serializer) {
+    match File::create(target_filename) {
+        std::io::Result::Ok(outfile) => match serialize_to(&outfile, format, value) {
             std::result::Result::Ok(()) => {
                 // We canonicalize (i.e., make absolute) the path before printing it:
                 // this makes it clearer to the user where to find the file.
@@ -111,8 +252,8 @@ pub fn gexport<FD: Serialize + Clone, GD: Serialize + Clone>(
                 info!("Generated the file: {}", path.to_str().unwrap());
                 Ok(())
             }
-            std::result::Result::Err(_) => {
-                error!("Could not write to: {:?}", target_filename);
+            std::result::Result::Err(err) => {
+                error!("Could not write to {:?}: {}", target_filename, err);
                 Err(())
             }
         },
@@ -123,42 +264,747 @@ pub fn gexport<FD: Serialize + Clone, GD: Serialize + Clone>(
     }
 }
 
-/// Export the translated ULLBC definitions to a JSON file.
+/// Compute the path of the file to generate: we use the crate name for the
+/// file name.
+fn target_filename(crate_name: &str, dest_dir: &Option<PathBuf>, extension: &str) -> PathBuf {
+    let mut target_filename = dest_dir
+        .as_deref()
+        .map_or_else(PathBuf::new, |d| d.to_path_buf());
+    target_filename.push(format!("{crate_name}.{extension}"));
+    target_filename
+}
+
+/// Export the translated definitions to a file, in the given format.
+///
+/// This is a generic function, used both for LLBC and ULLBC.
+pub fn gexport<FD: Serialize + Clone, GD: Serialize + Clone>(
+    header: &Header,
+    ordered_decls: &OrderedDecls,
+    type_defs: &TypeDecls,
+    fun_defs: &FunDeclId::Vector<FD>,
+    global_defs: &GlobalDeclId::Vector<GD>,
+    trait_decls: &TraitDecls,
+    trait_impls: &TraitImpls,
+    unstructured_fun_defs: &Vec<ullbc_ast::FunDecl>,
+    dest_dir: &Option<PathBuf>,
+    format: OutputFormat,
+    language: &str,
+) -> Result<()> {
+    // Generate the destination file
+    let target_filename =
+        target_filename(&header.crate_name, dest_dir, &format.extension(language));
+
+    trace!("Target file: {:?}", target_filename);
+
+    // Retrieve the declarations information, in the order of the declarations
+    let decls_info: Vec<(AnyDeclId, DeclInfo)> = ordered_decls
+        .decls
+        .iter()
+        .flat_map(|group| group.get_ids())
+        .map(|id| {
+            let info = ordered_decls.decls_info.get(&id).unwrap().clone();
+            (id, info)
+        })
+        .collect();
+
+    // Serialize
+    let crate_serializer = GCrateSerializer {
+        name: header.crate_name.clone(),
+        id_to_file: &ordered_decls.id_to_file,
+        declarations: VecSW::new(&ordered_decls.decls),
+        declarations_info: VecSW::new(&decls_info),
+        types: &type_defs.types,
+        functions: fun_defs,
+        globals: global_defs,
+        trait_decls,
+        trait_impls,
+        unstructured_functions: VecSW::new(unstructured_fun_defs),
+    };
+
+    let exported = Exported {
+        header: header.clone(),
+        krate: crate_serializer,
+    };
+    write_file(&target_filename, dest_dir, format, &exported)
+}
+
+/// Export the translated ULLBC definitions to a file.
 pub fn export_ullbc(
-    crate_name: String,
+    header: &Header,
     ordered_decls: &OrderedDecls,
     type_defs: &TypeDecls,
     fun_defs: &FunDeclId::Vector<ullbc_ast::FunDecl>,
     global_defs: &GlobalDeclId::Vector<ullbc_ast::GlobalDecl>,
+    trait_decls: &TraitDecls,
+    trait_impls: &TraitImpls,
     dest_dir: &Option<PathBuf>,
+    format: OutputFormat,
 ) -> Result<()> {
     gexport(
-        crate_name,
+        header,
         ordered_decls,
         type_defs,
         fun_defs,
         global_defs,
+        trait_decls,
+        trait_impls,
+        &Vec::new(),
         dest_dir,
+        format,
         "ullbc",
     )
 }
 
-/// Export the translated LLBC definitions to a JSON file.
+/// Export the translated LLBC definitions to a file.
+///
+/// `unstructured_fun_defs` contains the ULLBC definitions of the functions
+/// for which we skipped the control-flow reconstruction.
 pub fn export_llbc(
-    crate_name: String,
+    header: &Header,
     ordered_decls: &OrderedDecls,
     type_defs: &TypeDecls,
     fun_defs: &FunDeclId::Vector<llbc_ast::FunDecl>,
     global_defs: &GlobalDeclId::Vector<llbc_ast::GlobalDecl>,
+    trait_decls: &TraitDecls,
+    trait_impls: &TraitImpls,
+    unstructured_fun_defs: &Vec<ullbc_ast::FunDecl>,
     dest_dir: &Option<PathBuf>,
+    format: OutputFormat,
 ) -> Result<()> {
     gexport(
-        crate_name,
+        header,
         ordered_decls,
         type_defs,
         fun_defs,
         global_defs,
+        trait_decls,
+        trait_impls,
+        unstructured_fun_defs,
+        dest_dir,
+        format,
+        "llbc",
+    )
+}
+
+/// A structured declaration, together with the unstructured body its
+/// control-flow was reconstructed from. The fields of the declaration are
+/// serialized as they are, with the additional field `ullbc_body`.
+#[derive(Serialize, Clone)]
+struct TwoLevelDecl<'a, D: Serialize + Clone> {
+    #[serde(flatten)]
+    decl: &'a D,
+    ullbc_body: Option<&'a ullbc_ast::ExprBody>,
+}
+
+/// Export the translated LLBC definitions to a file, like
+/// [export_llbc], but the function and global declarations also carry their
+/// ULLBC bodies (see the `--emit=both` option), so that the consumers can
+/// cross-check the control-flow reconstruction.
+pub fn export_llbc_with_ullbc(
+    header: &Header,
+    ordered_decls: &OrderedDecls,
+    type_defs: &TypeDecls,
+    fun_defs: &FunDeclId::Vector<llbc_ast::FunDecl>,
+    global_defs: &GlobalDeclId::Vector<llbc_ast::GlobalDecl>,
+    ullbc_fun_defs: &FunDeclId::Vector<ullbc_ast::FunDecl>,
+    ullbc_global_defs: &GlobalDeclId::Vector<ullbc_ast::GlobalDecl>,
+    trait_decls: &TraitDecls,
+    trait_impls: &TraitImpls,
+    unstructured_fun_defs: &Vec<ullbc_ast::FunDecl>,
+    dest_dir: &Option<PathBuf>,
+    format: OutputFormat,
+) -> Result<()> {
+    let fun_defs = FunDeclId::Vector::from_iter(fun_defs.iter().map(|d| TwoLevelDecl {
+        decl: d,
+        ullbc_body: ullbc_fun_defs.get(d.def_id).and_then(|d| d.body.as_ref()),
+    }));
+    let global_defs = GlobalDeclId::Vector::from_iter(global_defs.iter().map(|d| {
+        TwoLevelDecl {
+            decl: d,
+            ullbc_body: ullbc_global_defs
+                .get(d.def_id)
+                .and_then(|d| d.body.as_ref()),
+        }
+    }));
+    gexport(
+        header,
+        ordered_decls,
+        type_defs,
+        &fun_defs,
+        &global_defs,
+        trait_decls,
+        trait_impls,
+        unstructured_fun_defs,
         dest_dir,
+        format,
         "llbc",
     )
 }
+
+/// The information about a declaration, as it is exported: this is
+/// [DeclInfo] without the Rust identifier, which is meaningless outside of
+/// the compiler session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedDeclInfo {
+    pub is_transparent: bool,
+    pub visibility: Visibility,
+    pub trait_impl: Option<TraitImplInfo>,
+    /// Absent from the files generated before the version 1.1 of the format
+    #[serde(default)]
+    pub extern_ref: Option<ExternRef>,
+}
+
+/// A crate loaded from a `.llbc` or `.ullbc` file. This has the same shape
+/// as [GCrateSerializer], but owns its content, so that the external tools
+/// can load a file, transform the declarations and export them again.
+///
+/// Note that the files generated with `--emit=both` can be loaded as LLBC
+/// crates: the `ullbc_body` fields are then ignored.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Crate")]
+pub struct GCrate<FD: Clone, GD: Clone> {
+    pub name: String,
+    /// Sorted by file id
+    pub id_to_file: Vec<(FileId::Id, FileName)>,
+    pub declarations: Vec<DeclarationGroup>,
+    pub declarations_info: Vec<(AnyDeclId, ExportedDeclInfo)>,
+    pub types: TypeDeclId::Vector<TypeDecl>,
+    pub functions: FunDeclId::Vector<FD>,
+    pub globals: GlobalDeclId::Vector<GD>,
+    pub trait_decls: TraitDecls,
+    pub trait_impls: TraitImpls,
+    pub unstructured_functions: Vec<ullbc_ast::FunDecl>,
+}
+
+pub type LlbcCrate = GCrate<llbc_ast::FunDecl, llbc_ast::GlobalDecl>;
+pub type UllbcCrate = GCrate<ullbc_ast::FunDecl, ullbc_ast::GlobalDecl>;
+
+impl<FD: Clone + Serialize + DeserializeOwned, GD: Clone + Serialize + DeserializeOwned>
+    GCrate<FD, GD>
+{
+    /// Load a crate from a file generated by [gexport], in the given format.
+    /// We fail if the file was generated with an incompatible version of the
+    /// format (see [FormatVersion]).
+    pub fn import(filename: &Path, format: OutputFormat) -> Result<(Header, Self)> {
+        let file = match File::open(filename) {
+            std::io::Result::Ok(file) => file,
+            std::io::Result::Err(_) => {
+                error!("Could not open: {:?}", filename);
+                return Err(());
+            }
+        };
+        let reader = BufReader::new(file);
+        let krate: std::result::Result<Exported<Self>, String> = match format {
+            OutputFormat::Json => serde_json::from_reader(reader).map_err(|e| e.to_string()),
+            OutputFormat::Cbor => serde_cbor::from_reader(reader).map_err(|e| e.to_string()),
+            OutputFormat::Bincode => bincode::deserialize_from(reader).map_err(|e| e.to_string()),
+        };
+        match krate {
+            std::result::Result::Ok(Exported { header, krate }) => Ok((header, krate)),
+            std::result::Result::Err(err) => {
+                error!("Could not parse {:?}: {}", filename, err);
+                Err(())
+            }
+        }
+    }
+
+    /// Export the crate to a file, like [gexport] (`language` should be
+    /// `llbc` or `ullbc`). The tools which transform the crate should add
+    /// their own passes to the header.
+    pub fn export(
+        &self,
+        header: &Header,
+        dest_dir: &Option<PathBuf>,
+        format: OutputFormat,
+        language: &str,
+    ) -> Result<()> {
+        let target_filename = target_filename(&self.name, dest_dir, &format.extension(language));
+        trace!("Target file: {:?}", target_filename);
+        let exported = Exported {
+            header: header.clone(),
+            krate: self,
+        };
+        write_file(&target_filename, dest_dir, format, &exported)
+    }
+}
+
+/// Parse an argument of the `--extern-llbc` option: `name=path`, where `name`
+/// is the name of an extracted crate and `path` the generated file.
+pub fn parse_extern_llbc(arg: &str) -> std::result::Result<(String, PathBuf), String> {
+    match arg.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+            std::result::Result::Ok((name.to_string(), PathBuf::from(path)))
+        }
+        _ => std::result::Result::Err(format!(
+            "invalid argument for --extern-llbc: `{arg}` (expected `name=path`)"
+        )),
+    }
+}
+
+/// Load the symbol tables of the crates given with `--extern-llbc`. The files
+/// must contain LLBC, but they can have been generated with `--emit=both`. We
+/// infer their formats from their extensions.
+pub fn load_extern_symbols(args: &[String]) -> Result<SymbolTable> {
+    let mut symbols = SymbolTable::new();
+    for arg in args {
+        let (name, path) = match parse_extern_llbc(arg) {
+            std::result::Result::Ok(x) => x,
+            std::result::Result::Err(msg) => {
+                error!("{}", msg);
+                return Err(());
+            }
+        };
+        let (header, krate) = LlbcCrate::import(&path, OutputFormat::of_path(&path))?;
+        if header.crate_name != name {
+            log::warn!(
+                "The file {:?} was generated for the crate {}, not {}",
+                path,
+                header.crate_name,
+                name
+            );
+        }
+        let types = krate
+            .types
+            .iter()
+            .map(|d| (&d.name, AnyDeclId::Type(d.def_id)));
+        let funs = krate
+            .functions
+            .iter()
+            .map(|d| (&d.name, AnyDeclId::Fun(d.def_id)));
+        let globals = krate
+            .globals
+            .iter()
+            .map(|d| (&d.name, AnyDeclId::Global(d.def_id)));
+        symbols.add_crate(&name, types.chain(funs).chain(globals));
+        info!(
+            "Loaded the declarations of the crate {} from {:?}",
+            name, path
+        );
+    }
+    Ok(symbols)
+}
+
+/// The index of the declarations, which implements the [Serialize] trait
+#[derive(Serialize)]
+#[serde(rename = "Index")]
+struct IndexSerializer<'a> {
+    name: String,
+    /// See [GCrateSerializer]: the spans of the entries refer to this map.
+    #[serde(serialize_with = "serialize_sorted_map")]
+    id_to_file: &'a HashMap<FileId::Id, FileName>,
+    entries: VecSW<'a, IndexEntry>,
+}
+
+/// Pretty-print the declarations of a crate, in the order of the declaration
+/// groups. The recursive groups are delimited by comments.
+///
+/// This function is generic in the representation of the bodies: `fmt_fun`
+/// and `fmt_global` print the function and global declarations.
+fn gfmt_crate<FD, GD>(
+    crate_name: &str,
+    decls: &[DeclarationGroup],
+    type_defs: &TypeDecls,
+    fun_defs: &FunDeclId::Vector<FD>,
+    global_defs: &GlobalDeclId::Vector<GD>,
+    fmt_fun: &dyn Fn(&FD) -> String,
+    fmt_global: &dyn Fn(&GD) -> String,
+) -> String {
+    fn fmt_group<Id: Copy>(
+        out: &mut Vec<String>,
+        group: &GDeclarationGroup<Id>,
+        fmt: &dyn Fn(Id) -> String,
+    ) {
+        match group {
+            GDeclarationGroup::NonRec(id) => out.push(fmt(*id)),
+            GDeclarationGroup::Rec(ids) => {
+                out.push("// Recursive group: begin".to_string());
+                out.extend(ids.iter().map(|id| fmt(*id)));
+                out.push("// Recursive group: end".to_string());
+            }
+        }
+    }
+
+    let mut out = vec![format!("// Crate: {crate_name}")];
+    for group in decls {
+        match group {
+            DeclarationGroup::Type(group) => fmt_group(&mut out, group, &|id| {
+                type_defs
+                    .get_type_def(id)
+                    .unwrap()
+                    .fmt_with_decls(type_defs)
+            }),
+            DeclarationGroup::Fun(group) => {
+                fmt_group(&mut out, group, &|id| fmt_fun(fun_defs.get(id).unwrap()))
+            }
+            DeclarationGroup::Global(group) => fmt_group(&mut out, group, &|id| {
+                fmt_global(global_defs.get(id).unwrap())
+            }),
+        }
+    }
+    let mut out = out.join("\n\n");
+    out.push('\n');
+    out
+}
+
+/// Pretty-print the LLBC declarations of a crate, with the names of the
+/// declarations they refer to (see [export_llbc_text]).
+pub fn fmt_llbc_crate(
+    crate_name: &str,
+    ordered_decls: &OrderedDecls,
+    type_defs: &TypeDecls,
+    fun_defs: &FunDeclId::Vector<llbc_ast::FunDecl>,
+    global_defs: &GlobalDeclId::Vector<llbc_ast::GlobalDecl>,
+) -> String {
+    let fun_names = FunDeclId::Vector::from_iter(fun_defs.iter().map(|d| d.name.to_string()));
+    let global_names =
+        GlobalDeclId::Vector::from_iter(global_defs.iter().map(|d| d.name.to_string()));
+    let ctx = llbc_ast::CtxNames::new(type_defs, &fun_names, &global_names);
+    gfmt_crate(
+        crate_name,
+        &ordered_decls.decls,
+        type_defs,
+        fun_defs,
+        global_defs,
+        &|d| d.fmt_with_ctx_names(&ctx),
+        &|d| d.fmt_with_ctx_names(&ctx),
+    )
+}
+
+/// Write a human-readable dump of the LLBC declarations to
+/// `{crate}.llbc.txt` (see the `--print-llbc` option). This is meant for
+/// debugging: the file can't be read back.
+pub fn export_llbc_text(
+    crate_name: &str,
+    ordered_decls: &OrderedDecls,
+    type_defs: &TypeDecls,
+    fun_defs: &FunDeclId::Vector<llbc_ast::FunDecl>,
+    global_defs: &GlobalDeclId::Vector<llbc_ast::GlobalDecl>,
+    dest_dir: &Option<PathBuf>,
+) -> Result<()> {
+    let target_filename = target_filename(crate_name, dest_dir, "llbc.txt");
+    let text = fmt_llbc_crate(crate_name, ordered_decls, type_defs, fun_defs, global_defs);
+    if let Some(dest_dir) = dest_dir {
+        if std::fs::create_dir_all(dest_dir).is_err() {
+            error!("Could not create the directory: {:?}", dest_dir);
+            return Err(());
+        }
+    }
+    match std::fs::write(&target_filename, text) {
+        std::result::Result::Ok(()) => {
+            info!("Generated the file: {:?}", target_filename);
+            Ok(())
+        }
+        std::result::Result::Err(err) => {
+            error!("Could not write to {:?}: {}", target_filename, err);
+            Err(())
+        }
+    }
+}
+
+/// Export the index of the declarations (see [crate::decls_index]) to a JSON
+/// file, alongside the translated crate.
+pub fn export_index(
+    crate_name: String,
+    ordered_decls: &OrderedDecls,
+    index: &Vec<IndexEntry>,
+    dest_dir: &Option<PathBuf>,
+) -> Result<()> {
+    let target_filename = target_filename(&crate_name, dest_dir, "index.json");
+    trace!("Target file: {:?}", target_filename);
+    let index_serializer = IndexSerializer {
+        name: crate_name,
+        id_to_file: &ordered_decls.id_to_file,
+        entries: VecSW::new(index),
+    };
+    // The index is meant to be read by the IDEs and the scripts: we always
+    // export it to JSON
+    write_file(
+        &target_filename,
+        dest_dir,
+        OutputFormat::Json,
+        &index_serializer,
+    )
+}
+
+/// Export the call graph (see [crate::callgraph]) to `{crate}.callgraph.json`,
+/// and to `{crate}.callgraph.dot` in the Graphviz DOT format.
+pub fn export_call_graph(
+    crate_name: &str,
+    graph: &CallGraph,
+    dest_dir: &Option<PathBuf>,
+) -> Result<()> {
+    let target_filename = target_filename(crate_name, dest_dir, "callgraph.json");
+    write_file(&target_filename, dest_dir, OutputFormat::Json, graph)?;
+
+    // The directory was created when writing the JSON file
+    let target_filename = target_filename(crate_name, dest_dir, "callgraph.dot");
+    if std::fs::write(&target_filename, graph.to_dot(crate_name)).is_err() {
+        error!("Could not write to {:?}", target_filename);
+        return Err(());
+    }
+    info!("Generated the call graph in: {:?}", target_filename);
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct DiagnosticsReport<'a> {
+    crate_name: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Export the diagnostics (see [crate::diagnostics]) to a JSON file,
+/// alongside the translated crate.
+pub fn export_diagnostics(
+    crate_name: &str,
+    dest_dir: &Option<PathBuf>,
+    diagnostics: &Diagnostics,
+) -> Result<()> {
+    let target_filename = target_filename(crate_name, dest_dir, "diagnostics.json");
+    let report = DiagnosticsReport {
+        crate_name,
+        diagnostics: diagnostics.to_vec(),
+    };
+    write_file(&target_filename, dest_dir, OutputFormat::Json, &report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::*;
+
+    #[derive(Serialize, Clone)]
+    struct Decl {
+        name: String,
+        body: Option<u32>,
+    }
+
+    #[test]
+    fn test_two_level_decl() {
+        // The fields of the declaration are not nested
+        let decl = Decl {
+            name: "f".to_string(),
+            body: Some(0),
+        };
+        let value = serde_json::to_value(TwoLevelDecl {
+            decl: &decl,
+            ullbc_body: None,
+        })
+        .unwrap();
+        assert!(value == serde_json::json!({ "name": "f", "body": 0, "ullbc_body": null }));
+    }
+
+    #[test]
+    fn test_parse_extern_llbc() {
+        let (name, path) = parse_extern_llbc("utils=out/utils.llbc.cbor").unwrap();
+        assert!(name == "utils" && path == PathBuf::from("out/utils.llbc.cbor"));
+        assert!(OutputFormat::of_path(&path) == OutputFormat::Cbor);
+        assert!(OutputFormat::of_path(Path::new("utils.llbc")) == OutputFormat::Json);
+        assert!(parse_extern_llbc("utils").is_err());
+        assert!(parse_extern_llbc("=utils.llbc").is_err());
+    }
+
+    #[test]
+    fn test_sorted_files() {
+        use crate::meta::LocalFileId;
+
+        // The maps use different random states: their iteration orders differ
+        let mk_files = |ids: &mut dyn Iterator<Item = usize>| -> HashMap<FileId::Id, FileName> {
+            HashMap::from_iter(ids.map(|i| {
+                let id = FileId::Id::LocalId(LocalFileId::Id::new(i));
+                (id, FileName::NotReal(i.to_string()))
+            }))
+        };
+        let files0 = mk_files(&mut (0..32));
+        let files1 = mk_files(&mut (0..32).rev());
+        let (decls, decls_info, unstructured) = (Vec::new(), Vec::new(), Vec::new());
+        let serialize = |id_to_file: &HashMap<FileId::Id, FileName>| {
+            let crate_serializer = GCrateSerializer::<llbc_ast::FunDecl, llbc_ast::GlobalDecl> {
+                name: "test".to_string(),
+                id_to_file,
+                declarations: VecSW::new(&decls),
+                declarations_info: VecSW::new(&decls_info),
+                types: &TypeDeclId::Vector::new(),
+                functions: &FunDeclId::Vector::new(),
+                globals: &GlobalDeclId::Vector::new(),
+                trait_decls: &TraitDecls::new(),
+                trait_impls: &TraitImpls::new(),
+                unstructured_functions: VecSW::new(&unstructured),
+            };
+            serde_json::to_value(&crate_serializer).unwrap()
+        };
+        let value = serialize(&files0);
+        assert!(value == serialize(&files1));
+        // The files are sorted by identifier
+        let files = value["id_to_file"].as_array().unwrap();
+        assert!(files.len() == 32);
+        for (i, file) in files.iter().enumerate() {
+            assert!(file[0] == serde_json::json!({ "LocalId": i }));
+        }
+    }
+
+    #[test]
+    fn test_index() {
+        use crate::meta::{LocalFileId, Meta, VirtualFileId};
+
+        // The local files come first
+        let mut id_to_file = HashMap::new();
+        let b = FileId::Id::VirtualId(VirtualFileId::ZERO);
+        id_to_file.insert(b, FileName::Virtual(PathBuf::from("b.rs")));
+        let a = FileId::Id::LocalId(LocalFileId::ZERO);
+        id_to_file.insert(a, FileName::Local(PathBuf::from("a.rs")));
+        let entries = vec![IndexEntry {
+            path: "test::f".to_string(),
+            id: AnyDeclId::Fun(FunDeclId::ZERO),
+            meta: Meta::dummy(),
+        }];
+        let index = IndexSerializer {
+            name: "test".to_string(),
+            id_to_file: &id_to_file,
+            entries: VecSW::new(&entries),
+        };
+        let value = serde_json::to_value(&index).unwrap();
+        assert!(
+            value["id_to_file"]
+                == serde_json::json!([
+                    [{ "LocalId": 0 }, { "Local": "a.rs" }],
+                    [{ "VirtualId": 0 }, { "Virtual": "b.rs" }]
+                ])
+        );
+        assert!(value["entries"][0]["path"] == "test::f");
+    }
+
+    #[test]
+    fn test_import_crate() {
+        use crate::meta::{LocalFileId, VirtualFileId};
+        use crate::reorder_decls::GDeclarationGroup;
+
+        let mut id_to_file = HashMap::new();
+        let a = FileId::Id::LocalId(LocalFileId::ZERO);
+        let b = FileId::Id::VirtualId(VirtualFileId::ZERO);
+        id_to_file.insert(b, FileName::NotReal("b".to_string()));
+        id_to_file.insert(a, FileName::Local(PathBuf::from("a.rs")));
+        let decls = vec![
+            DeclarationGroup::Type(GDeclarationGroup::NonRec(TypeDeclId::ZERO)),
+            DeclarationGroup::Fun(GDeclarationGroup::Rec(vec![
+                FunDeclId::ZERO,
+                FunDeclId::Id::new(1),
+            ])),
+        ];
+        let (decls_info, unstructured) = (Vec::new(), Vec::new());
+        let crate_serializer = GCrateSerializer::<llbc_ast::FunDecl, llbc_ast::GlobalDecl> {
+            name: "test".to_string(),
+            id_to_file: &id_to_file,
+            declarations: VecSW::new(&decls),
+            declarations_info: VecSW::new(&decls_info),
+            types: &TypeDeclId::Vector::new(),
+            functions: &FunDeclId::Vector::new(),
+            globals: &GlobalDeclId::Vector::new(),
+            trait_decls: &TraitDecls::new(),
+            trait_impls: &TraitImpls::new(),
+            unstructured_functions: VecSW::new(&unstructured),
+        };
+
+        // We can load what we export, and export it again
+        let s = serde_json::to_string(&crate_serializer).unwrap();
+        let krate: LlbcCrate = serde_json::from_str(&s).unwrap();
+        assert!(krate.name == "test");
+        assert!(krate.id_to_file[0].0 == a && krate.id_to_file[1].0 == b);
+        assert!(matches!(
+            &krate.declarations[1],
+            DeclarationGroup::Fun(GDeclarationGroup::Rec(ids)) if ids.len() == 2
+        ));
+        assert!(serde_json::to_string(&krate).unwrap() == s);
+    }
+
+    #[test]
+    fn test_header() {
+        let header = Header::new("test".to_string(), "0123".to_string(), vec![]);
+        let exported = Exported {
+            header,
+            krate: vec![1, 2],
+        };
+        let s = serde_json::to_string(&exported).unwrap();
+        assert!(s.starts_with("{\"header\":{\"format_version\":{\"major\":2,"));
+        let exported: Exported<Vec<u32>> = serde_json::from_str(&s).unwrap();
+        assert!(exported.header.crate_name == "test" && exported.krate == vec![1, 2]);
+        let bytes = bincode::serialize(&exported).unwrap();
+        assert!(bincode::deserialize::<Exported<Vec<u32>>>(&bytes).is_ok());
+
+        // We reject the files with another major version, even if we can't
+        // parse their content
+        let mut header = exported.header;
+        header.format_version.major += 1;
+        let s = serde_json::to_string(&Exported {
+            header,
+            krate: "unexpected",
+        })
+        .unwrap();
+        let err = serde_json::from_str::<Exported<Vec<u32>>>(&s)
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("unsupported format version: 3.0"));
+    }
+
+    #[test]
+    fn test_binary_formats() {
+        use crate::expressions::{AggregateKind, OperandConstantValue};
+        use crate::values::{PrimitiveValue, ScalarValue};
+
+        // The variants are encoded with their indices (as `u32` with bincode)
+        let ty: ETy = Ty::Bool;
+        assert!(bincode::serialize(&ty).unwrap() == vec![2, 0, 0, 0]);
+        let kind = AggregateKind::Tuple;
+        assert!(bincode::serialize(&kind).unwrap() == vec![0, 0, 0, 0]);
+        assert!(serde_cbor::ser::to_vec_packed(&ty).unwrap() == vec![2]);
+
+        // The constants are tagged in the binary formats
+        let cv = OperandConstantValue::Array(vec![OperandConstantValue::PrimitiveValue(
+            PrimitiveValue::Scalar(ScalarValue::U8(3)),
+        )]);
+        let bytes = bincode::serialize(&cv).unwrap();
+        assert!(bincode::deserialize::<OperandConstantValue>(&bytes).unwrap() == cv);
+        let bytes = serde_cbor::ser::to_vec_packed(&cv).unwrap();
+        assert!(serde_cbor::from_slice::<OperandConstantValue>(&bytes).unwrap() == cv);
+
+        let ty: ETy = Ty::Adt(
+            TypeId::Tuple,
+            im::Vector::from(vec![ErasedRegion::Erased]),
+            im::Vector::from(vec![Ty::Integer(IntegerTy::I64), Ty::Str]),
+        );
+        let bytes = bincode::serialize(&ty).unwrap();
+        assert!(bincode::deserialize::<ETy>(&bytes).unwrap() == ty);
+        let bytes = serde_cbor::ser::to_vec_packed(&ty).unwrap();
+        assert!(serde_cbor::from_slice::<ETy>(&bytes).unwrap() == ty);
+    }
+
+    #[test]
+    fn test_fmt_crate() {
+        let funs = FunDeclId::Vector::from(vec!["fn f".to_string(), "fn g".to_string()]);
+        let globals = GlobalDeclId::Vector::from(vec!["global C".to_string()]);
+        let (f, g) = (FunDeclId::ZERO, FunDeclId::Id::new(1));
+        let decls = vec![
+            DeclarationGroup::Global(GDeclarationGroup::NonRec(GlobalDeclId::ZERO)),
+            DeclarationGroup::Fun(GDeclarationGroup::Rec(vec![g, f])),
+        ];
+        let text = gfmt_crate(
+            "test",
+            &decls,
+            &TypeDecls::new(),
+            &funs,
+            &globals,
+            &|d| d.clone(),
+            &|d| d.clone(),
+        );
+        // The declarations are printed in the order of the groups
+        let expected = [
+            "// Crate: test",
+            "global C",
+            "// Recursive group: begin",
+            "fn g",
+            "fn f",
+            "// Recursive group: end",
+        ];
+        assert!(text == format!("{}\n", expected.join("\n\n")));
+    }
+}
@@ -1,17 +1,18 @@
 //! Implements expressions: paths, operands, rvalues, lvalues
 
 pub use crate::expressions_utils::*;
+use crate::gast::{FunDeclId, TraitDeclId};
 use crate::types::*;
 use crate::values::*;
 use im::Vector; // TODO: im::Vector is not necessary anymore
 use macros::generate_index_type;
 use macros::{EnumAsGetters, EnumIsA, EnumToGetters, VariantIndexArity, VariantName};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::vec::Vec;
 
 generate_index_type!(GlobalDeclId);
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Place {
     pub var_id: VarId::Id,
     pub projection: Projection,
@@ -27,7 +28,7 @@ pub type Projection = Vector<ProjectionElem>;
 /// `((_0 as Right).0: T2) = move _1;`
 /// In MIR, downcasts always happen before field projections: in our internal
 /// language, we thus merge downcasts and field projections.
-#[derive(Debug, PartialEq, Eq, Clone, VariantName, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, VariantName, Serialize, Deserialize)]
 pub enum ProjectionElem {
     /// Dereference a shared/mutable reference.
     Deref,
@@ -44,6 +45,11 @@ pub enum ProjectionElem {
     DerefPtrUnique,
     /// Dereference a non-null pointer. See the comments for [crate::types::Ty::RawPtr].
     DerefPtrNonNull,
+    /// Dereference a shared pointer ([crate::types::AssumedTy::Rc] or
+    /// [crate::types::AssumedTy::Arc]). In MIR, those dereferences are calls
+    /// to `Deref::deref`: `r := Deref::deref(move p)` (where `p: &Rc<T>`)
+    /// is translated to `r := &deref_rc (*p)`.
+    DerefRc,
     /// Projection from ADTs (variants, structures).
     /// We allow projections to be used as left-values and right-values.
     /// We should never have projections to fields of symbolic variants (they
@@ -53,21 +59,51 @@ pub enum ProjectionElem {
     /// (for pretty printing for instance). We retrieve it through
     /// type-checking.
     Field(FieldProjKind, FieldId::Id),
+    /// Index an array, a slice or a vector. As in MIR, the index is given by
+    /// a local variable.
+    /// The accesses are always bounds-checked: the projection is preceded by
+    /// an assertion of kind [crate::gast::AssertKind::BoundsCheck] (see
+    /// [crate::index_accesses]).
+    Index(VarId::Id),
+    /// Index an array or a slice with a constant offset. Those projections
+    /// come from the slice patterns (`[x, y, ..]`): contrary to
+    /// [ProjectionElem::Index], they are not bounds-checked, because the
+    /// pattern matching checks that the length is at least `min_length`.
+    /// If `from_end` is true, we access the element at position
+    /// `len - offset` (for instance, `z` in `[.., z]` is at position
+    /// `len - 1`).
+    ConstantIndex {
+        offset: u64,
+        min_length: u64,
+        from_end: bool,
+    },
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, EnumAsGetters, Serialize)]
+#[derive(
+    Debug, PartialEq, Eq, Hash, Copy, Clone, EnumIsA, EnumAsGetters, Serialize, Deserialize,
+)]
 pub enum FieldProjKind {
     #[serde(rename = "ProjAdt")]
     Adt(TypeDeclId::Id, Option<VariantId::Id>),
     /// The option type is assumed (it comes from the standard library)
     #[serde(rename = "ProjOption")]
     Option(VariantId::Id),
+    /// The `ControlFlow` type is assumed as well (see [crate::reconstruct_try])
+    #[serde(rename = "ProjControlFlow")]
+    ControlFlow(VariantId::Id),
     /// If we project from a tuple, the projection kind gives the arity of the
     #[serde(rename = "ProjTuple")]
     Tuple(usize),
+    /// A field of a union. Note that the reads of union fields are unsafe:
+    /// we record them in the function declarations (see
+    /// [crate::gast::UnsafeOperation]).
+    #[serde(rename = "ProjUnion")]
+    Union(TypeDeclId::Id),
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, EnumAsGetters, Serialize)]
+#[derive(
+    Debug, PartialEq, Eq, Hash, Copy, Clone, EnumIsA, EnumAsGetters, Serialize, Deserialize,
+)]
 pub enum BorrowKind {
     Shared,
     Mut,
@@ -83,24 +119,48 @@ pub enum BorrowKind {
 }
 
 /// Unary operation
-#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, VariantName, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, EnumIsA, VariantName, Serialize, Deserialize)]
 pub enum UnOp {
     Not,
     /// This can overflow. In practice, rust introduces an assert before
     /// (in debug mode) to check that it is not equal to the minimum integer
-    /// value (for the proper type).
+    /// value (for the proper type). The negation of a float never fails.
     Neg,
-    /// Casts are rvalues in MIR, but we treat them as unops. For now, we
-    /// only support for integer to integer, but we can also do from integers/booleans
-    /// to integers/booleans. For now, we don't handle pointer casts.
+}
+
+/// The kinds of the casts (`x as u64`), see [Rvalue::Cast]. The pointer casts
+/// are [Rvalue::Unsize] and [Rvalue::FnPtr].
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, EnumIsA, VariantName, Serialize, Deserialize)]
+pub enum CastKind {
+    /// Between two integer types. The value is truncated or extended (with
+    /// its sign, if the source type is signed): the cast never fails.
+    IntToInt,
+    /// From a boolean to an integer (`false` is 0, `true` is 1)
+    BoolToInt,
+    /// From a character to an integer: this gives the code point of the
+    /// character (truncated if the target type has less than 32 bits)
+    CharToInt,
+    /// From a `u8` to a character (`0x41 as char`)
+    U8ToChar,
+    /// From a fieldless enumeration to an integer, which gives the
+    /// discriminant of the variant.
     ///
-    /// The first integer type gives the source type, the second one gives
-    /// the destination type.
-    Cast(IntegerTy, IntegerTy),
+    /// Not present in MIR, which reads the discriminant then casts it with
+    /// [CastKind::IntToInt]: we introduce it in [crate::remove_read_discriminant].
+    /// The operand is the enumeration, and the source type is the type of its
+    /// discriminant (`isize`, unless the enumeration has a `repr` attribute).
+    EnumToInt,
+    /// From a float to an integer. The value is rounded toward zero, and
+    /// saturates if it doesn't fit in the target type (`NaN` gives 0).
+    FloatToInt,
+    /// From an integer to a float (rounded to the nearest float)
+    IntToFloat,
+    /// Between two float types (`x as f64`)
+    FloatToFloat,
 }
 
 /// Binary operations.
-#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, VariantName, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, EnumIsA, VariantName, Serialize, Deserialize)]
 pub enum BinOp {
     BitXor,
     BitAnd,
@@ -125,11 +185,23 @@ pub enum BinOp {
     Shl,
     /// Can fail if the shift is too big
     Shr,
-    // No Offset binary operation: this is an operation on raw pointers
+    /// Offset a raw pointer by a number of elements (`ptr.offset(n)`). We
+    /// don't check that the resulting pointer is in bounds.
+    Offset,
 }
 
 #[derive(
-    Debug, PartialEq, Eq, Clone, EnumIsA, EnumToGetters, EnumAsGetters, VariantName, Serialize,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Clone,
+    EnumIsA,
+    EnumToGetters,
+    EnumAsGetters,
+    VariantName,
+    Serialize,
+    Deserialize,
 )]
 pub enum Operand {
     Copy(Place),
@@ -155,7 +227,9 @@ pub enum Operand {
 /// `Identifier` and `Static` case:
 /// Match constant variables. We later desugar those to separate statements,
 /// see [extract_global_assignments.rs].
-#[derive(Debug, PartialEq, Eq, Clone, VariantName, EnumIsA, EnumAsGetters, VariantIndexArity)]
+#[derive(
+    Debug, PartialEq, Eq, Hash, Clone, VariantName, EnumIsA, EnumAsGetters, VariantIndexArity,
+)]
 pub enum OperandConstantValue {
     PrimitiveValue(PrimitiveValue),
     ///
@@ -166,6 +240,12 @@ pub enum OperandConstantValue {
     /// Less frequently: arbitrary ADT values.
     Adt(Option<VariantId::Id>, Vec<OperandConstantValue>),
     ///
+    /// A constant array, given by the list of its elements (for instance:
+    /// `const ARR: [u8; 3] = [1, 2, 3];`).
+    /// Contrary to the ADTs, we don't desugar those: the elements are always
+    /// primitive values or arrays.
+    Array(Vec<OperandConstantValue>),
+    ///
     /// The case when the constant is elsewhere.
     /// The MIR seems to forbid more complex expressions like paths :
     /// Reading the constant a.b is translated to { _1 = const a; _2 = (_1.0) }.
@@ -173,14 +253,40 @@ pub enum OperandConstantValue {
     ///
     /// Same as for constants, except that statics are accessed through references.
     StaticId(GlobalDeclId::Id),
+    ///
+    /// An associated constant of a trait, whose implementation we can't
+    /// determine statically (`T::CONST`, or `<T as Trait>::CONST`):
+    /// `TraitConst(trait_id, type_args, name)`.
+    /// Like for [Ty::TraitProjection], the type arguments are the arguments
+    /// of the trait, the first one being the `Self` type. The regions are
+    /// erased.
+    /// Contrary to [OperandConstantValue::ConstantId], this case remains in
+    /// the final LLBC.
+    TraitConst(TraitDeclId::Id, Vec<ETy>, String),
+    /// The value of a zero-sized type other than unit (for instance
+    /// `PhantomData<T>`, or a structure without fields but with type
+    /// parameters). We keep the type, because those values may appear
+    /// inside constant ADTs and arrays, where we don't have the type of the
+    /// operand.
+    ZST(ETy),
+    ///
+    /// A constant array of bytes (`[u8; N]`). This is a compact version of
+    /// [OperandConstantValue::Array], which we use because those arrays can
+    /// be big (see [OperandConstantValue::mk_array]).
+    Bytes(Vec<u8>),
 }
 
 /// TODO: we could factor out [Rvalue] and function calls (for LLBC, not ULLBC).
 /// We can also factor out the unops, binops with the function calls.
-#[derive(Debug, Clone, Serialize, EnumToGetters, EnumIsA)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize, EnumToGetters, EnumIsA)]
 pub enum Rvalue<R> {
     Use(Operand),
     Ref(Place, BorrowKind),
+    /// Create a raw pointer to a place (`&raw const p`, `&raw mut p`, or
+    /// `std::ptr::addr_of!(p)`). Contrary to [Rvalue::Ref], this doesn't
+    /// create a borrow: the backends may treat the code which manipulates
+    /// raw pointers as unverified.
+    RawPtr(Place, RefKind),
     /// Unary operation (not, neg)
     UnaryOp(UnOp, Operand),
     /// Binary operations (note that we merge "checked" and "unchecked" binops)
@@ -190,6 +296,9 @@ pub enum Rvalue<R> {
     ///
     /// This case is filtered in [crate::remove_read_discriminant]
     Discriminant(Place),
+    /// The length of an array, a slice or a vector (it has type `usize`).
+    /// This is used for the bounds checks of the indexing operations.
+    Len(Place),
     /// Creates an aggregate value, like a tuple, a struct or an enum:
     /// ```text
     /// l = List::Cons { value:x, tail:tl };
@@ -209,9 +318,50 @@ pub enum Rvalue<R> {
     /// Not present in MIR: we introduce it when replacing constant variables
     /// in operands in [extract_global_assignments.rs]
     Global(GlobalDeclId::Id),
+    /// A pointer to a static whose value may be updated. Contrary to [Rvalue::Global],
+    /// which reads the value of a global, this gives access to the static
+    /// itself. This is either a raw pointer to a mutable static (`static mut`,
+    /// [RefKind::Mut]), or a shared borrow of a static with interior
+    /// mutability ([RefKind::Shared]).
+    /// We introduce it in [crate::extract_global_assignments].
+    GlobalRef(GlobalDeclId::Id, RefKind),
+    /// Test if an enumeration value is one of the given variants.
+    ///
+    /// Not present in MIR: we introduce it in [crate::recognize_matches] to
+    /// replace the matches which simply compute a boolean (`matches!(x, ...)`).
+    IsVariant(Place, Vec<VariantId::Id>),
+    /// Borrow the sub-slice `base[from..to]`, where `base` is an array, a
+    /// slice or a vector. This operation panics if `from > to` or if `to` is
+    /// greater than the length of `base`.
+    ///
+    /// Not present in MIR, where slicing (`&v[a..b]`) is performed by a call
+    /// to an `Index` trait method: we introduce it in [crate::slice_accesses].
+    Slice {
+        base: Place,
+        from: Operand,
+        to: Operand,
+        kind: BorrowKind,
+    },
+    /// An unsizing coercion `Unsize(op, src_ty, tgt_ty)`, which converts a
+    /// pointer to a sized value to a pointer to an unsized value: a pointer to
+    /// an array to a pointer to a slice (`&[T; N]` to `&[T]`), or a pointer to
+    /// a value whose type implements a trait to a trait object (`&T` or
+    /// `Box<T>` to `&dyn Trait` or `Box<dyn Trait>`, see [Ty::DynTrait]).
+    ///
+    /// `src_ty` and `tgt_ty` are the types of the pointers.
+    Unsize(Operand, ETy, ETy),
+    /// The address of a function (`let f: fn(u32) -> u32 = g;`), whose value
+    /// has type [Ty::FnPtr]. The functions called through such pointers are
+    /// called with [crate::gast::FunId::Indirect].
+    ///
+    /// We only support the pointers to monomorphic functions.
+    FnPtr(FunDeclId::Id),
+    /// A cast `Cast(kind, op, src_ty, tgt_ty)` (`op as tgt_ty`) between
+    /// scalar types, see [CastKind].
+    Cast(CastKind, Operand, ETy, ETy),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum AggregateKind {
     Tuple,
     // TODO: treat Option in a general manner (we should extract the definitions
@@ -224,4 +374,21 @@ pub enum AggregateKind {
         Vec<ErasedRegion>,
         Vec<ETy>,
     ),
+    /// The environment of a closure, whose body is given by the function.
+    /// The operands of the aggregate are the captured variables (or the
+    /// references to them), and we record how each of them is captured, in
+    /// the same order. See [crate::closures].
+    Closure(FunDeclId::Id, Vec<CaptureMode>),
+}
+
+/// How a closure captures a variable of its environment
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, EnumIsA, Serialize, Deserialize)]
+pub enum CaptureMode {
+    /// Capture by shared reference (`&x`)
+    ByRef,
+    /// Capture by mutable reference (`&mut x`)
+    ByMutRef,
+    /// Move the variable into the environment (`move` closures, or variables
+    /// which are consumed by the closure)
+    ByValue,
 }
@@ -5,13 +5,14 @@ use crate::assumed;
 use crate::common::*;
 use crate::expressions::*;
 use crate::formatter::Formatter;
+use crate::gast::{FunDeclId, TraitDeclId};
 use crate::types::*;
 use crate::ullbc_ast::GlobalDeclId;
 use crate::values;
 use crate::values::*;
 use serde::ser::SerializeStruct;
 use serde::ser::SerializeTupleVariant;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 impl Place {
     pub fn new(var_id: VarId::Id) -> Place {
@@ -35,6 +36,25 @@ impl Serialize for Place {
     }
 }
 
+impl<'de> Deserialize<'de> for Place {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct SerializedPlace {
+            var_id: VarId::Id,
+            #[serde(deserialize_with = "deserialize_vector")]
+            projection: Projection,
+        }
+        let p = SerializedPlace::deserialize(deserializer)?;
+        Ok(Place {
+            var_id: p.var_id,
+            projection: p.projection,
+        })
+    }
+}
+
 impl std::fmt::Display for BorrowKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         match self {
@@ -51,7 +71,6 @@ impl std::string::ToString for UnOp {
         match self {
             UnOp::Not => "~".to_string(),
             UnOp::Neg => "-".to_string(),
-            UnOp::Cast(src, tgt) => format!("cast<{src},{tgt}>"),
         }
     }
 }
@@ -75,6 +94,7 @@ impl std::string::ToString for BinOp {
             BinOp::Mul => "*".to_string(),
             BinOp::Shl => "<<".to_string(),
             BinOp::Shr => ">>".to_string(),
+            BinOp::Offset => "offset".to_string(),
         }
     }
 }
@@ -103,6 +123,20 @@ impl Place {
                 ProjectionElem::DerefPtrNonNull => {
                     out = format!("deref_ptr_non_null ({out})");
                 }
+                ProjectionElem::DerefRc => {
+                    out = format!("deref_rc ({out})");
+                }
+                ProjectionElem::Index(var_id) => {
+                    out = format!("{out}[{}]", ctx.format_object(*var_id));
+                }
+                ProjectionElem::ConstantIndex {
+                    offset,
+                    min_length,
+                    from_end,
+                } => {
+                    let from_end = if *from_end { "-" } else { "" };
+                    out = format!("{out}[{from_end}{offset} of {min_length}]");
+                }
                 ProjectionElem::Field(proj_kind, field_id) => match proj_kind {
                     FieldProjKind::Adt(adt_id, opt_variant_id) => {
                         let field_name = ctx.format_object((*adt_id, *opt_variant_id, *field_id));
@@ -112,12 +146,19 @@ impl Place {
                         };
                         out = format!("({out}{downcast}).{field_name}");
                     }
+                    FieldProjKind::Union(adt_id) => {
+                        let field_name = ctx.format_object((*adt_id, None, *field_id));
+                        out = format!("({out}).{field_name}");
+                    }
                     FieldProjKind::Tuple(_) => {
                         out = format!("({out}).{field_id}");
                     }
                     FieldProjKind::Option(_) => {
                         out = format!("({out}).{field_id}");
                     }
+                    FieldProjKind::ControlFlow(variant_id) => {
+                        out = format!("({out} as variant @{variant_id}).{field_id}");
+                    }
                 },
             }
         }
@@ -126,7 +167,7 @@ impl Place {
     }
 
     /// Perform a type substitution - actually simply clone the object
-    pub fn substitute(&self, _subst: &ETypeSubst) -> Self {
+    pub fn substitute(&self, _subst: &ETypeSubst, _cgsubst: &ConstGenericSubst) -> Self {
         self.clone()
     }
 }
@@ -155,8 +196,63 @@ impl OperandConstantValue {
                 let values: Vec<String> = values.iter().map(|v| v.fmt_with_ctx(ctx)).collect();
                 format!("ConstAdt {} [{}]", variant_id, values.join(", "))
             }
+            OperandConstantValue::Array(values) => {
+                // The type of the elements is given by the operand type: we
+                // don't repeat it for every element.
+                let values: Vec<String> = values
+                    .iter()
+                    .map(|v| match v {
+                        OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(v)) => {
+                            if v.is_int() {
+                                v.as_int().unwrap().to_string()
+                            } else {
+                                v.as_uint().unwrap().to_string()
+                            }
+                        }
+                        _ => v.fmt_with_ctx(ctx),
+                    })
+                    .collect();
+                format!("[{}]", values.join(", "))
+            }
             OperandConstantValue::ConstantId(id) => ctx.format_object(*id),
             OperandConstantValue::StaticId(id) => format!("alloc: &{}", ctx.format_object(*id)),
+            OperandConstantValue::TraitConst(trait_id, type_args, name) => {
+                // The first type argument is the `Self` type. We don't have
+                // the context to format the types: we print the identifiers.
+                let self_ty = type_args[0].to_string();
+                let args: Vec<String> = type_args[1..].iter().map(|ty| ty.to_string()).collect();
+                if args.is_empty() {
+                    format!("<{self_ty} as @Trait{trait_id}>::{name}")
+                } else {
+                    format!(
+                        "<{self_ty} as @Trait{trait_id}<{}>>::{name}",
+                        args.join(", ")
+                    )
+                }
+            }
+            OperandConstantValue::ZST(ty) => format!("zst : {ty}"),
+            OperandConstantValue::Bytes(bytes) => {
+                let bytes: Vec<String> = bytes.iter().map(|b| b.to_string()).collect();
+                format!("[{}]", bytes.join(", "))
+            }
+        }
+    }
+
+    /// Build a constant array. The arrays of bytes are stored in a compact
+    /// manner (see [OperandConstantValue::Bytes]).
+    pub fn mk_array(values: Vec<OperandConstantValue>) -> OperandConstantValue {
+        let bytes: Option<Vec<u8>> = values
+            .iter()
+            .map(|v| match v {
+                OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(ScalarValue::U8(
+                    b,
+                ))) => Some(*b),
+                _ => None,
+            })
+            .collect();
+        match bytes {
+            Some(bytes) if !values.is_empty() => OperandConstantValue::Bytes(bytes),
+            _ => OperandConstantValue::Array(values),
         }
     }
 }
@@ -183,9 +279,17 @@ impl Operand {
     }
 
     /// Perform a type substitution - actually simply clone the object
-    pub fn substitute(&self, _subst: &ETypeSubst) -> Self {
+    pub fn substitute(&self, _subst: &ETypeSubst, _cgsubst: &ConstGenericSubst) -> Self {
         self.clone()
     }
+
+    /// Apply a function to the place of the operand, if there is one
+    pub fn transform_places(&mut self, f: &mut dyn FnMut(&mut Place)) {
+        match self {
+            Operand::Copy(p) | Operand::Move(p) => f(p),
+            Operand::Const(_, _) => (),
+        }
+    }
 }
 
 impl std::string::ToString for Operand {
@@ -215,6 +319,10 @@ impl Rvalue {
                 }
                 BorrowKind::Shallow => format!("&shallow {}", place.fmt_with_ctx(ctx)),
             },
+            Rvalue::RawPtr(place, kind) => match kind {
+                RefKind::Shared => format!("&raw const {}", place.fmt_with_ctx(ctx)),
+                RefKind::Mut => format!("&raw mut {}", place.fmt_with_ctx(ctx)),
+            },
             Rvalue::UnaryOp(unop, x) => {
                 format!("{}({})", unop.to_string(), x.fmt_with_ctx(ctx))
             }
@@ -257,16 +365,115 @@ impl Rvalue {
                         };
                         format!("{} {{ {} }}", variant, fields.join(", "))
                     }
+                    AggregateKind::Closure(fun_id, _) => {
+                        format!("@Closure(@Fun{}, {})", fun_id, ops_s.join(", "))
+                    }
                 }
             }
             Rvalue::Global(gid) => ctx.format_object(*gid),
+            Rvalue::GlobalRef(gid, kind) => match kind {
+                RefKind::Mut => format!("&raw mut {}", ctx.format_object(*gid)),
+                RefKind::Shared => format!("&{}", ctx.format_object(*gid)),
+            },
+            Rvalue::Len(p) => format!("len({})", p.fmt_with_ctx(ctx)),
+            Rvalue::IsVariant(p, variant_ids) => {
+                let variant_ids: Vec<String> = variant_ids.iter().map(|v| v.to_string()).collect();
+                format!(
+                    "@is_variant({}, {})",
+                    p.fmt_with_ctx(ctx),
+                    variant_ids.join(" | ")
+                )
+            }
+            Rvalue::Slice {
+                base,
+                from,
+                to,
+                kind,
+            } => {
+                let borrow = match kind {
+                    BorrowKind::Mut | BorrowKind::TwoPhaseMut => "&mut ",
+                    BorrowKind::Shared | BorrowKind::Shallow => "&",
+                };
+                format!(
+                    "{}{}[{}..{}]",
+                    borrow,
+                    base.fmt_with_ctx(ctx),
+                    from.fmt_with_ctx(ctx),
+                    to.fmt_with_ctx(ctx)
+                )
+            }
+            Rvalue::Unsize(x, _, _) => format!("@unsize({})", x.fmt_with_ctx(ctx)),
+            Rvalue::FnPtr(fun_id) => format!("@FnPtr(@Fun{fun_id})"),
+            Rvalue::Cast(kind, x, src, tgt) => {
+                let x = match kind {
+                    CastKind::EnumToInt => format!("@discriminant({})", x.fmt_with_ctx(ctx)),
+                    _ => x.fmt_with_ctx(ctx),
+                };
+                format!(
+                    "cast<{},{}>({})",
+                    src.fmt_with_ctx(ctx),
+                    tgt.fmt_with_ctx(ctx),
+                    x
+                )
+            }
         }
     }
 
     /// Perform a type substitution - actually simply clone the object
-    pub fn substitute(&self, _subst: &ETypeSubst) -> Self {
+    pub fn substitute(&self, _subst: &ETypeSubst, _cgsubst: &ConstGenericSubst) -> Self {
         self.clone()
     }
+
+    /// Apply a function to all the places appearing in the rvalue
+    pub fn transform_places(&mut self, f: &mut dyn FnMut(&mut Place)) {
+        match self {
+            Rvalue::Use(op)
+            | Rvalue::UnaryOp(_, op)
+            | Rvalue::Unsize(op, _, _)
+            | Rvalue::Cast(_, op, _, _) => op.transform_places(f),
+            Rvalue::BinaryOp(_, op1, op2) => {
+                op1.transform_places(f);
+                op2.transform_places(f);
+            }
+            Rvalue::Ref(p, _)
+            | Rvalue::RawPtr(p, _)
+            | Rvalue::Discriminant(p)
+            | Rvalue::Len(p)
+            | Rvalue::IsVariant(p, _) => f(p),
+            Rvalue::Slice { base, from, to, .. } => {
+                f(base);
+                from.transform_places(f);
+                to.transform_places(f);
+            }
+            Rvalue::Aggregate(_, ops) => {
+                for op in ops {
+                    op.transform_places(f);
+                }
+            }
+            Rvalue::Global(_) | Rvalue::GlobalRef(_, _) | Rvalue::FnPtr(_) => (),
+        }
+    }
+
+    /// The operands of the rvalue, from left to right
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Rvalue::Use(op)
+            | Rvalue::UnaryOp(_, op)
+            | Rvalue::Unsize(op, _, _)
+            | Rvalue::Cast(_, op, _, _) => vec![op],
+            Rvalue::BinaryOp(_, op1, op2) => vec![op1, op2],
+            Rvalue::Slice { from, to, .. } => vec![from, to],
+            Rvalue::Aggregate(_, ops) => ops.iter().collect(),
+            Rvalue::Ref(_, _)
+            | Rvalue::RawPtr(_, _)
+            | Rvalue::Discriminant(_)
+            | Rvalue::Len(_)
+            | Rvalue::IsVariant(_, _)
+            | Rvalue::Global(_)
+            | Rvalue::GlobalRef(_, _)
+            | Rvalue::FnPtr(_) => Vec::new(),
+        }
+    }
 }
 
 impl std::string::ToString for Rvalue {
@@ -275,6 +482,18 @@ impl std::string::ToString for Rvalue {
     }
 }
 
+impl CaptureMode {
+    /// The kind of the reference stored in the closure environment, if the
+    /// variable is captured by reference.
+    pub fn ref_kind(&self) -> Option<RefKind> {
+        match self {
+            CaptureMode::ByRef => Some(RefKind::Shared),
+            CaptureMode::ByMutRef => Some(RefKind::Mut),
+            CaptureMode::ByValue => None,
+        }
+    }
+}
+
 impl Serialize for AggregateKind {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
@@ -284,9 +503,14 @@ impl Serialize for AggregateKind {
         // Also, it seems the "standard" way of doing is the following (this is
         // consistent with what the automatically generated serializer does):
         // - if the arity is > 0, use `serialize_tuple_variant`
-        // - otherwise simply serialize a string with the variant name
+        // - otherwise use `serialize_unit_variant`
+        // The variant indices are used by the binary formats (see
+        // [crate::cli_options::OutputFormat]): they must match the order of
+        // the variants.
         match self {
-            AggregateKind::Tuple => "AggregatedTuple".serialize(serializer),
+            AggregateKind::Tuple => {
+                serializer.serialize_unit_variant("AggregateKind", 0, "AggregatedTuple")
+            }
             AggregateKind::Option(variant_id, ty) => {
                 let mut vs = serializer.serialize_tuple_variant(
                     "AggregateKind",
@@ -302,7 +526,7 @@ impl Serialize for AggregateKind {
             }
             AggregateKind::Adt(def_id, opt_variant_id, regions, tys) => {
                 let mut vs =
-                    serializer.serialize_tuple_variant("AggregateKind", 1, "AggregatedAdt", 4)?;
+                    serializer.serialize_tuple_variant("AggregateKind", 2, "AggregatedAdt", 4)?;
 
                 vs.serialize_field(def_id)?;
                 vs.serialize_field(opt_variant_id)?;
@@ -311,22 +535,392 @@ impl Serialize for AggregateKind {
                 let tys = VecSerializer::new(tys);
                 vs.serialize_field(&tys)?;
 
+                vs.end()
+            }
+            AggregateKind::Closure(fun_id, capture_modes) => {
+                let mut vs = serializer.serialize_tuple_variant(
+                    "AggregateKind",
+                    3,
+                    "AggregatedClosure",
+                    2,
+                )?;
+
+                vs.serialize_field(fun_id)?;
+                let capture_modes = VecSerializer::new(capture_modes);
+                vs.serialize_field(&capture_modes)?;
+
                 vs.end()
             }
         }
     }
 }
 
+impl<'de> Deserialize<'de> for AggregateKind {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(rename = "AggregateKind")]
+        enum SerializedAggregateKind {
+            AggregatedTuple,
+            AggregatedOption(VariantId::Id, ETy),
+            AggregatedAdt(
+                TypeDeclId::Id,
+                Option<VariantId::Id>,
+                Vec<ErasedRegion>,
+                Vec<ETy>,
+            ),
+            AggregatedClosure(FunDeclId::Id, Vec<CaptureMode>),
+        }
+
+        use SerializedAggregateKind as S;
+        Ok(match S::deserialize(deserializer)? {
+            S::AggregatedTuple => AggregateKind::Tuple,
+            S::AggregatedOption(variant_id, ty) => AggregateKind::Option(variant_id, ty),
+            S::AggregatedAdt(def_id, opt_variant_id, regions, tys) => {
+                AggregateKind::Adt(def_id, opt_variant_id, regions, tys)
+            }
+            S::AggregatedClosure(fun_id, capture_modes) => {
+                AggregateKind::Closure(fun_id, capture_modes)
+            }
+        })
+    }
+}
+
 impl Serialize for OperandConstantValue {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
+        // The binary formats can't distinguish the untagged primitive values
+        // from the arrays (see [crate::cli_options::OutputFormat]): in this
+        // case, we tag all the values.
+        if !serializer.is_human_readable() {
+            return match self {
+                OperandConstantValue::PrimitiveValue(cv) => serializer.serialize_newtype_variant(
+                    "OperandConstantValue",
+                    0,
+                    "PrimitiveValue",
+                    cv,
+                ),
+                OperandConstantValue::Array(values) => serializer.serialize_newtype_variant(
+                    "OperandConstantValue",
+                    2,
+                    "Array",
+                    &VecSerializer::new(values),
+                ),
+                OperandConstantValue::TraitConst(trait_id, type_args, name) => serializer
+                    .serialize_newtype_variant(
+                        "OperandConstantValue",
+                        5,
+                        "TraitConst",
+                        &(trait_id, type_args, name),
+                    ),
+                OperandConstantValue::ZST(ty) => {
+                    serializer.serialize_newtype_variant("OperandConstantValue", 6, "ZST", ty)
+                }
+                OperandConstantValue::Bytes(bytes) => {
+                    serializer.serialize_newtype_variant("OperandConstantValue", 7, "Bytes", bytes)
+                }
+                _ => unreachable!("unexpected `{:?}`: `OperandConstantValue` fields other than `ConstantValue` are temporary and should not occur in serialized LLBC", self),
+            };
+        }
+
         match self {
             // [OperandConstantValue] exists only to handle temporary cases inherited from the MIR:
             // for the final (U)LLBC format, we simply export the underlying constant value.
             OperandConstantValue::PrimitiveValue(cv) => cv.serialize(serializer),
+            OperandConstantValue::Array(values) => serializer.serialize_newtype_variant(
+                "OperandConstantValue",
+                2,
+                "Array",
+                &VecSerializer::new(values),
+            ),
+            OperandConstantValue::TraitConst(trait_id, type_args, name) => serializer
+                .serialize_newtype_variant(
+                    "OperandConstantValue",
+                    5,
+                    "TraitConst",
+                    &(trait_id, type_args, name),
+                ),
+            OperandConstantValue::ZST(ty) => {
+                serializer.serialize_newtype_variant("OperandConstantValue", 6, "ZST", ty)
+            }
+            // The arrays of bytes can be big: we serialize them in a compact
+            // manner, as a list of numbers.
+            OperandConstantValue::Bytes(bytes) => {
+                serializer.serialize_newtype_variant("OperandConstantValue", 7, "Bytes", bytes)
+            }
             _ => unreachable!("unexpected `{:?}`: `OperandConstantValue` fields other than `ConstantValue` are temporary and should not occur in serialized LLBC", self),
         }
     }
 }
+
+impl<'de> Deserialize<'de> for OperandConstantValue {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if !deserializer.is_human_readable() {
+            // The `Adt`, `ConstantId` and `StaticId` variants are never
+            // serialized: they are only here so that the indices of the
+            // variants are the same as in the serializer.
+            #[derive(Deserialize)]
+            #[serde(rename = "OperandConstantValue")]
+            enum BinaryOperandConstantValue {
+                PrimitiveValue(PrimitiveValue),
+                Adt(()),
+                Array(Vec<OperandConstantValue>),
+                ConstantId(()),
+                StaticId(()),
+                TraitConst((TraitDeclId::Id, Vec<ETy>, String)),
+                ZST(ETy),
+                Bytes(Vec<u8>),
+            }
+
+            use BinaryOperandConstantValue as B;
+            return match B::deserialize(deserializer)? {
+                B::PrimitiveValue(cv) => Ok(OperandConstantValue::PrimitiveValue(cv)),
+                B::Array(values) => Ok(OperandConstantValue::Array(values)),
+                B::TraitConst((trait_id, type_args, name)) => {
+                    Ok(OperandConstantValue::TraitConst(trait_id, type_args, name))
+                }
+                B::ZST(ty) => Ok(OperandConstantValue::ZST(ty)),
+                B::Bytes(bytes) => Ok(OperandConstantValue::Bytes(bytes)),
+                B::Adt(()) | B::ConstantId(()) | B::StaticId(()) => Err(serde::de::Error::custom(
+                    "unexpected constant in serialized LLBC",
+                )),
+            };
+        }
+
+        // The primitive values are serialized directly, while the arrays are
+        // wrapped in a `Bytes` or `Array` variant (see the serializer).
+        #[derive(Deserialize)]
+        enum SerializedArray {
+            Bytes(Vec<u8>),
+            Array(Vec<OperandConstantValue>),
+        }
+
+        #[derive(Deserialize)]
+        enum SerializedTraitConst {
+            TraitConst(TraitDeclId::Id, Vec<ETy>, String),
+        }
+
+        #[derive(Deserialize)]
+        enum SerializedZST {
+            ZST(ETy),
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum SerializedOperandConstantValue {
+            PrimitiveValue(PrimitiveValue),
+            Array(SerializedArray),
+            TraitConst(SerializedTraitConst),
+            ZST(SerializedZST),
+        }
+
+        use SerializedOperandConstantValue as S;
+        Ok(match S::deserialize(deserializer)? {
+            S::PrimitiveValue(cv) => OperandConstantValue::PrimitiveValue(cv),
+            S::Array(SerializedArray::Bytes(bytes)) => OperandConstantValue::Bytes(bytes),
+            S::Array(SerializedArray::Array(values)) => OperandConstantValue::Array(values),
+            S::TraitConst(SerializedTraitConst::TraitConst(trait_id, type_args, name)) => {
+                OperandConstantValue::TraitConst(trait_id, type_args, name)
+            }
+            S::ZST(SerializedZST::ZST(ty)) => OperandConstantValue::ZST(ty),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::*;
+    use crate::types::*;
+    use crate::values::*;
+
+    fn mk_array(values: Vec<ScalarValue>) -> OperandConstantValue {
+        OperandConstantValue::mk_array(
+            values
+                .into_iter()
+                .map(|v| OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(v)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_place_serialization() {
+        // `(*(x.0))[i]`
+        let mut p = Place::new(VarId::Id::new(1));
+        p.projection.push_back(ProjectionElem::Field(
+            FieldProjKind::Tuple(2),
+            FieldId::ZERO,
+        ));
+        p.projection.push_back(ProjectionElem::Deref);
+        p.projection
+            .push_back(ProjectionElem::Index(VarId::Id::new(2)));
+
+        // The projection elements are serialized in the order in which they
+        // are applied to the variable
+        let s = serde_json::to_string(&p).unwrap();
+        assert!(
+            s == "{\"var_id\":1,\"projection\":[{\"Field\":[{\"ProjTuple\":2},0]},\"Deref\",{\"Index\":2}]}"
+        );
+        let p1: Place = serde_json::from_str(&s).unwrap();
+        assert!(p1 == p);
+        assert!(p1.projection.iter().eq(p.projection.iter()));
+    }
+
+    #[test]
+    fn test_constant_array() {
+        // `const ARR: [u8; 3] = [1, 2, 3];`
+        let bytes = mk_array(vec![
+            ScalarValue::U8(1),
+            ScalarValue::U8(2),
+            ScalarValue::U8(3),
+        ]);
+        assert!(bytes == OperandConstantValue::Bytes(vec![1, 2, 3]));
+        assert!(bytes.to_string() == "[1, 2, 3]");
+        assert!(serde_json::to_string(&bytes).unwrap() == "{\"Bytes\":[1,2,3]}");
+        let bin = bincode::serialize(&bytes).unwrap();
+        assert!(bincode::deserialize::<OperandConstantValue>(&bin).unwrap() == bytes);
+
+        // `const ARR: [i32; 3] = [1, -2, 3];`
+        let ints = mk_array(vec![
+            ScalarValue::I32(1),
+            ScalarValue::I32(-2),
+            ScalarValue::I32(3),
+        ]);
+        assert!(ints.to_string() == "[1, -2, 3]");
+        assert!(
+            serde_json::to_string(&ints).unwrap()
+                == "{\"Array\":[{\"Scalar\":{\"I32\":[\"1\"]}},{\"Scalar\":{\"I32\":[\"-2\"]}},{\"Scalar\":{\"I32\":[\"3\"]}}]}"
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        // `'a'` and `"hello\n"`
+        let c = OperandConstantValue::PrimitiveValue(PrimitiveValue::Char('a'));
+        assert!(c.to_string() == "'a'");
+        assert!(serde_json::to_string(&c).unwrap() == "{\"Char\":\"a\"}");
+        let s = OperandConstantValue::PrimitiveValue(PrimitiveValue::String("hello\n".to_string()));
+        assert!(s.to_string() == "\"hello\\n\"");
+        assert!(serde_json::to_string(&s).unwrap() == "{\"String\":\"hello\\n\"}");
+    }
+
+    #[test]
+    fn test_float_literals() {
+        // `1.5f32` and `-0.1f64`
+        let x = PrimitiveValue::Float(FloatValue::F32(1.5f32.to_bits()));
+        assert!(x.to_string() == "1.5 : f32");
+        assert!(serde_json::to_string(&x).unwrap() == "{\"Float\":{\"F32\":\"1.5\"}}");
+        let y = PrimitiveValue::Float(FloatValue::F64((-0.1f64).to_bits()));
+        assert!(y.to_string() == "-0.1 : f64");
+        // The bit patterns distinguish `0.0` and `-0.0`
+        assert!(FloatValue::F64(0.0f64.to_bits()) != FloatValue::F64((-0.0f64).to_bits()));
+        assert!(Ty::<ErasedRegion>::Float(FloatTy::F64).to_string() == "f64");
+    }
+
+    #[test]
+    fn test_raw_pointers() {
+        let (x, n) = (Place::new(VarId::Id::new(1)), Place::new(VarId::Id::new(2)));
+        // `p = &raw mut x;`
+        let rv = Rvalue::RawPtr(x.clone(), RefKind::Mut);
+        assert!(
+            serde_json::to_string(&rv).unwrap()
+                == "{\"RawPtr\":[{\"var_id\":1,\"projection\":[]},\"Mut\"]}"
+        );
+        // `q = p.offset(n);`
+        let rv = Rvalue::BinaryOp(BinOp::Offset, Operand::Copy(x), Operand::Copy(n));
+        assert!(
+            serde_json::to_string(&rv).unwrap()
+                == "{\"BinaryOp\":[\"Offset\",{\"Copy\":{\"var_id\":1,\"projection\":[]}},{\"Copy\":{\"var_id\":2,\"projection\":[]}}]}"
+        );
+    }
+
+    #[test]
+    fn test_deserialization() {
+        fn round_trip<T: serde::Serialize + serde::de::DeserializeOwned>(x: &T) -> T {
+            serde_json::from_str(&serde_json::to_string(x).unwrap()).unwrap()
+        }
+
+        // The arrays of bytes are serialized in a compact manner
+        let bytes = mk_array(vec![ScalarValue::U8(1), ScalarValue::U8(255)]);
+        assert!(round_trip(&bytes) == bytes);
+        let ints = mk_array(vec![ScalarValue::I128(i128::MIN), ScalarValue::I128(3)]);
+        assert!(round_trip(&ints) == ints);
+        let c = OperandConstantValue::PrimitiveValue(PrimitiveValue::Char('a'));
+        assert!(round_trip(&c) == c);
+
+        // The scalars are serialized to strings, which we parse back
+        let x = ScalarValue::U128(u128::MAX);
+        assert!(round_trip(&x) == x);
+        assert!(serde_json::from_str::<ScalarValue>("{\"U8\":[\"256\"]}").is_err());
+        let y = FloatValue::F64((-0.1f64).to_bits());
+        assert!(round_trip(&y) == y);
+        let z = FloatValue::F32(f32::INFINITY.to_bits());
+        assert!(round_trip(&z) == z);
+
+        // `&'a mut (u32, T)`
+        let ty: RTy = Ty::Ref(
+            Region::Var(RegionVarId::ZERO),
+            Box::new(Ty::Adt(
+                TypeId::Tuple,
+                im::Vector::new(),
+                im::Vector::from(vec![
+                    Ty::Integer(IntegerTy::U32),
+                    Ty::TypeVar(TypeVarId::ZERO),
+                ]),
+            )),
+            RefKind::Mut,
+        );
+        assert!(round_trip(&ty) == ty);
+
+        let kind = AggregateKind::Adt(
+            TypeDeclId::ZERO,
+            Some(VariantId::ZERO),
+            vec![ErasedRegion::Erased],
+            vec![Ty::Bool],
+        );
+        assert!(round_trip(&kind) == kind);
+        let rv = Rvalue::Aggregate(kind, vec![Operand::Const(Ty::Bool, c)]);
+        assert!(round_trip(&rv) == rv);
+    }
+
+    #[test]
+    fn test_trait_const() {
+        // `<T as Shape>::SIDES`
+        let sides = OperandConstantValue::TraitConst(
+            crate::gast::TraitDeclId::ZERO,
+            vec![Ty::TypeVar(TypeVarId::ZERO)],
+            "SIDES".to_string(),
+        );
+        assert!(sides.to_string() == "<@T0 as @Trait0>::SIDES");
+        let s = serde_json::to_string(&sides).unwrap();
+        assert!(s == "{\"TraitConst\":[0,[{\"TypeVar\":[0]}],\"SIDES\"]}");
+        assert!(serde_json::from_str::<OperandConstantValue>(&s).unwrap() == sides);
+        let bytes = bincode::serialize(&sides).unwrap();
+        assert!(bincode::deserialize::<OperandConstantValue>(&bytes).unwrap() == sides);
+    }
+
+    #[test]
+    fn test_zst() {
+        // `PhantomData<T>`
+        let phantom = OperandConstantValue::ZST(Ty::Adt(
+            TypeId::Adt(TypeDeclId::ZERO),
+            im::Vector::new(),
+            im::Vector::from(vec![Ty::TypeVar(TypeVarId::ZERO)]),
+        ));
+        let s = serde_json::to_string(&phantom).unwrap();
+        assert!(s.starts_with("{\"ZST\":{\"Adt\":"));
+        assert!(serde_json::from_str::<OperandConstantValue>(&s).unwrap() == phantom);
+        let bytes = bincode::serialize(&phantom).unwrap();
+        assert!(bincode::deserialize::<OperandConstantValue>(&bytes).unwrap() == phantom);
+        // The zero-sized values may appear inside constant arrays
+        let arr = OperandConstantValue::Array(vec![phantom.clone(), phantom]);
+        let s = serde_json::to_string(&arr).unwrap();
+        assert!(serde_json::from_str::<OperandConstantValue>(&s).unwrap() == arr);
+    }
+}
@@ -7,16 +7,23 @@
 //! to treat them as globals in (U)LLBC.
 //! To do this, we add a new variable to reference the static: they are accessed
 //! by reference in MIR, whereas globals are accessed by value.
+//!
+//! The mutable statics are different: their value may change, so we can't read
+//! it once and for all. We directly bind a pointer to the static itself
+//! ([Rvalue::GlobalRef]), through which all the reads and writes go. The same
+//! goes for the statics with interior mutability (`static X: AtomicUsize`),
+//! which can be updated through shared borrows.
 
 use crate::expressions::*;
 use crate::meta::Meta;
 use crate::types::*;
 use crate::ullbc_ast::{
-    iter_function_bodies, iter_global_bodies, make_locals_generator, CtxNames, FunDecls,
+    iter_function_bodies, iter_global_bodies, make_locals_generator, CtxNames, ExprBody, FunDecls,
     GlobalDecls, RawStatement, Statement,
 };
 use crate::ullbc_ast_utils::body_transform_operands;
 use crate::values::VarId;
+use std::collections::HashSet;
 
 fn deref_static_type(ref_ty: &ETy) -> &ETy {
     match ref_ty {
@@ -42,7 +49,15 @@ fn deref_static_type(ref_ty: &ETy) -> &ETy {
 /// `let x0 = X;`
 /// `let x1 = &X;`
 /// `... move x1 ...`
+///
+/// If the operand is a mutable static, or a static with interior mutability,
+/// we don't read its value:
+/// `... const X ...`
+/// becomes
+/// `let x0 = &raw mut X;` (resp. `let x0 = &X;`)
+/// `... move x0 ...`
 fn extract_operand_global_var<F: FnMut(ETy) -> VarId::Id>(
+    mutable_statics: &HashSet<GlobalDeclId::Id>,
     meta: &Meta,
     nst: &mut Vec<Statement>,
     op: &mut Operand,
@@ -55,7 +70,11 @@ fn extract_operand_global_var<F: FnMut(ETy) -> VarId::Id>(
     };
 
     let var = match *c {
-        OperandConstantValue::PrimitiveValue(_) => return,
+        OperandConstantValue::PrimitiveValue(_)
+        | OperandConstantValue::Array(_)
+        | OperandConstantValue::TraitConst(_, _, _)
+        | OperandConstantValue::ZST(_)
+        | OperandConstantValue::Bytes(_) => return,
         OperandConstantValue::Adt(_, _) => {
             unreachable!("Constant ADTs should have been replaced by now")
         }
@@ -67,6 +86,21 @@ fn extract_operand_global_var<F: FnMut(ETy) -> VarId::Id>(
             ));
             var
         }
+        OperandConstantValue::StaticId(global_id) if mutable_statics.contains(&global_id) => {
+            // The mutable statics are accessed through raw pointers, the
+            // statics with interior mutability through shared borrows
+            let kind = match ty {
+                Ty::RawPtr(_, RefKind::Mut) => RefKind::Mut,
+                Ty::Ref(ErasedRegion::Erased, _, RefKind::Shared) => RefKind::Shared,
+                _ => unreachable!("expected a pointer to a static, got {:?}", ty),
+            };
+            let var_ptr = make_new_var(ty.clone());
+            nst.push(Statement::new(
+                *meta,
+                RawStatement::Assign(Place::new(var_ptr), Rvalue::GlobalRef(global_id, kind)),
+            ));
+            var_ptr
+        }
         OperandConstantValue::StaticId(global_id) => {
             let var = make_new_var(deref_static_type(ty).clone());
             let var_ref = make_new_var(ty.clone());
@@ -85,16 +119,149 @@ fn extract_operand_global_var<F: FnMut(ETy) -> VarId::Id>(
     *op = Operand::Move(Place::new(var));
 }
 
+fn transform_body(mutable_statics: &HashSet<GlobalDeclId::Id>, b: &mut ExprBody) {
+    let mut f = make_locals_generator(&mut b.locals);
+    body_transform_operands(&mut b.body, &mut |meta, nst, op| {
+        extract_operand_global_var(mutable_statics, meta, nst, op, &mut f)
+    });
+}
+
 pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
+    let mutable_statics: HashSet<GlobalDeclId::Id> = globals
+        .iter()
+        .filter(|g| g.is_mutable || g.interior_mutability)
+        .map(|g| g.def_id)
+        .collect();
     for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
         trace!(
             "# About to extract global assignments: {name}:\n{}",
             b.fmt_with_ctx_names(fmt_ctx)
         );
+        transform_body(&mutable_statics, b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extract_global_assignments::*;
+    use crate::meta::AttrInfo;
+    use crate::names::Name;
+    use crate::ullbc_ast::{BlockData, BlockId, GlobalDecl, RawTerminator, Terminator, Var};
+
+    #[test]
+    fn test_static_mut() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let ptr_ty = Ty::RawPtr(Box::new(u32_ty.clone()), RefKind::Mut);
+        // `static mut COUNTER: u32 = 0;`
+        let counter = GlobalDecl {
+            def_id: GlobalDeclId::ZERO,
+            meta: Meta::dummy(),
+            name: Name::from(vec!["COUNTER".to_string()]),
+            ty: u32_ty.clone(),
+            is_mutable: true,
+            interior_mutability: false,
+            attr_info: AttrInfo::default(),
+            body: None,
+        };
+        let globals = GlobalDeclId::Vector::from(vec![counter]);
+        let mutable_statics: HashSet<GlobalDeclId::Id> = globals
+            .iter()
+            .filter(|g| g.is_mutable)
+            .map(|g| g.def_id)
+            .collect();
+        assert!(mutable_statics.contains(&GlobalDeclId::ZERO));
+
+        // `unsafe { COUNTER += 1 }` accesses the static through a raw pointer:
+        // ```
+        // p := const COUNTER; // p: *mut u32
+        // return
+        // ```
+        let p = VarId::Id::new(1);
+        let mut body = ExprBody {
+            meta: Meta::dummy(),
+            arg_count: 0,
+            return_local: VarId::ZERO,
+            locals: VarId::Vector::from(vec![
+                Var {
+                    index: VarId::ZERO,
+                    name: None,
+                    ty: Ty::Adt(TypeId::Tuple, im::Vector::new(), im::Vector::new()),
+                },
+                Var {
+                    index: p,
+                    name: None,
+                    ty: ptr_ty.clone(),
+                },
+            ]),
+            body: BlockId::Vector::from(vec![BlockData {
+                statements: vec![Statement::new(
+                    Meta::dummy(),
+                    RawStatement::Assign(
+                        Place::new(p),
+                        Rvalue::Use(Operand::Const(
+                            ptr_ty,
+                            OperandConstantValue::StaticId(GlobalDeclId::ZERO),
+                        )),
+                    ),
+                )],
+                terminator: Terminator::new(Meta::dummy(), RawTerminator::Return),
+            }]),
+        };
+        transform_body(&mutable_statics, &mut body);
 
-        let mut f = make_locals_generator(&mut b.locals);
-        body_transform_operands(&mut b.body, &mut |meta, nst, op| {
-            extract_operand_global_var(meta, nst, op, &mut f)
-        });
+        // We don't copy the value of the static, but take a pointer to it:
+        // ```
+        // tmp := &raw mut COUNTER;
+        // p := move tmp;
+        // return
+        // ```
+        let sts = &body.body.get(BlockId::ZERO).unwrap().statements;
+        assert!(sts.len() == 2);
+        let tmp = match &sts[0].content {
+            RawStatement::Assign(tmp, Rvalue::GlobalRef(id, RefKind::Mut))
+                if *id == GlobalDeclId::ZERO =>
+            {
+                tmp
+            }
+            _ => panic!(),
+        };
+        assert!(
+            matches!(&sts[1].content, RawStatement::Assign(p1, Rvalue::Use(Operand::Move(tmp1))) if *p1 == Place::new(p) && tmp1 == tmp)
+        );
+    }
+    #[test]
+    fn test_interior_mutability() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let ref_ty = Ty::Ref(
+            ErasedRegion::Erased,
+            Box::new(u32_ty.clone()),
+            RefKind::Shared,
+        );
+        // `x := const COUNTER`, where `COUNTER` has interior mutability
+        // (`static COUNTER: AtomicU32`): we must not copy its value
+        let x = VarId::Id::new(1);
+        let mut nst = Vec::new();
+        let mut op = Operand::Const(
+            ref_ty.clone(),
+            OperandConstantValue::StaticId(GlobalDeclId::ZERO),
+        );
+        let mutable_statics = HashSet::from([GlobalDeclId::ZERO]);
+        extract_operand_global_var(
+            &mutable_statics,
+            &Meta::dummy(),
+            &mut nst,
+            &mut op,
+            &mut |ty| {
+                assert!(ty == ref_ty);
+                x
+            },
+        );
+        assert!(nst.len() == 1);
+        assert!(matches!(
+            &nst[0].content,
+            RawStatement::Assign(p, Rvalue::GlobalRef(id, RefKind::Shared))
+                if *p == Place::new(x) && *id == GlobalDeclId::ZERO
+        ));
+        assert!(op == Operand::Move(Place::new(x)));
     }
 }
@@ -1,25 +1,30 @@
 //! Definitions common to [crate::ullbc_ast] and [crate::llbc_ast]
 #![allow(dead_code)]
 
+use crate::contracts::FunContract;
 pub use crate::expressions::GlobalDeclId;
+use crate::expressions::Operand;
 pub use crate::gast_utils::*;
-use crate::meta::Meta;
+use crate::meta::{AttrInfo, Meta};
 use crate::names::FunName;
 use crate::names::GlobalName;
+use crate::names::TraitName;
 use crate::regions_hierarchy::RegionGroups;
 use crate::types::*;
 use crate::values::*;
 use macros::generate_index_type;
 use macros::{EnumAsGetters, EnumIsA, VariantName};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // TODO: move this definition
 pub static TAB_INCR: &str = "    ";
 
 generate_index_type!(FunDeclId);
+generate_index_type!(TraitDeclId);
+generate_index_type!(TraitImplId);
 
 /// A variable
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Var {
     /// Unique index identifying the variable
     pub index: VarId::Id,
@@ -30,13 +35,47 @@ pub struct Var {
     pub ty: ETy,
 }
 
+/// The kind of an assertion, i.e., the reason why rustc introduced it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, EnumIsA, Serialize, Deserialize)]
+pub enum AssertKind {
+    /// Check that an index is in the bounds of an array, a slice or a vector
+    BoundsCheck,
+    /// Check that an arithmetic operation doesn't overflow
+    Overflow,
+    /// Check that a divisor is not zero
+    DivisionByZero,
+    /// The remaining assertions (for instance, the ones we reconstruct from
+    /// the conditional panics in [crate::reconstruct_asserts])
+    Other,
+}
+
+/// An approximation performed when translating a function: the translated
+/// body doesn't exactly model the original code.
+#[derive(Debug, Clone, Copy, EnumIsA, Serialize, Deserialize)]
+pub enum PrecisionLoss {
+    /// We don't model the inline assembly code: we consider that it has no
+    /// effect, and simply jump to its continuation.
+    InlineAsm(Meta),
+}
+
+/// An unsafe operation performed by a function body. Those operations are
+/// not visible in the body itself (for instance, a read of a union field is
+/// a regular read): we record them so that the analyses can treat them
+/// specially.
+#[derive(Debug, Clone, Copy, EnumIsA, Serialize, Deserialize)]
+pub enum UnsafeOperation {
+    /// A read of a union field (which reinterprets the content of the union
+    /// with the type of the field), or a borrow of a union field.
+    UnionFieldRead(Meta),
+}
+
 /// A function signature.
 /// Note that a signature uses unerased lifetimes, while function bodies (and
 /// execution) use erased lifetimes.
 /// We need the functions' signatures *with* the region parameters in order
 /// to correctly abstract those functions (number and signature of the backward
 /// functions) - we only use regions for this purpose.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunSig {
     pub region_params: RegionVarId::Vector<RegionVar>,
     /// The region parameters contain early bound and late bound parameters.
@@ -54,6 +93,11 @@ pub struct FunSig {
     /// The lifetime's hierarchy between the different regions.
     pub regions_hierarchy: RegionGroups,
     pub type_params: TypeVarId::Vector<TypeVar>,
+    pub const_generic_params: ConstGenericVarId::Vector<ConstGenericVar>,
+    /// The bounds on the generic parameters, including the ones of the
+    /// `impl` block or of the trait, for the methods. The trait methods have
+    /// a clause `Self: Trait`.
+    pub trait_clauses: Vec<TraitClause>,
     pub inputs: Vec<RTy>,
     pub output: RTy,
 }
@@ -61,11 +105,14 @@ pub struct FunSig {
 /// An expression body.
 /// TODO: arg_count should be stored in GFunDecl below. But then,
 ///       the print is obfuscated and Aeneas may need some refactoring.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GExprBody<T: std::fmt::Debug + Clone + Serialize> {
     pub meta: Meta,
     /// The number of local variables used for the input arguments.
     pub arg_count: usize,
+    /// The local variable used for the return value (by convention, this
+    /// is the first local, and the arguments come right after it).
+    pub return_local: VarId::Id,
     /// The local variables.
     /// We always have:
     /// - the local used for the return value
@@ -76,7 +123,7 @@ pub struct GExprBody<T: std::fmt::Debug + Clone + Serialize> {
 }
 
 /// A function definition
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GFunDecl<T: std::fmt::Debug + Clone + Serialize> {
     pub def_id: FunDeclId::Id,
     /// The meta data associated with the declaration.
@@ -89,25 +136,99 @@ pub struct GFunDecl<T: std::fmt::Debug + Clone + Serialize> {
     /// Opaque functions are: external functions, or local functions tagged
     /// as opaque.
     pub body: Option<GExprBody<T>>,
+    /// The approximations we performed when translating the body (see
+    /// [crate::gast_utils::imprecise_functions]).
+    pub precision_losses: Vec<PrecisionLoss>,
+    /// The unsafe operations performed by the body, in the order in which
+    /// they appear in the MIR.
+    pub unsafe_operations: Vec<UnsafeOperation>,
+    /// The doc comments and the attributes of the function
+    pub attr_info: AttrInfo,
+    /// The pre and postconditions of the function
+    pub contract: FunContract,
 }
 
 /// A global variable definition, either opaque or transparent.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GGlobalDecl<T: std::fmt::Debug + Clone + Serialize> {
     pub def_id: GlobalDeclId::Id,
     /// The meta data associated with the declaration.
     pub meta: Meta,
     pub name: GlobalName,
     pub ty: ETy,
+    /// `true` if the global is a mutable static (`static mut`). Contrary to
+    /// the other globals, its value may change during the execution: it is
+    /// always accessed through [crate::expressions::Rvalue::GlobalRef].
+    pub is_mutable: bool,
+    /// `true` if the global is a static with interior mutability, that is
+    /// whose type contains an `UnsafeCell` (`Cell`, `AtomicUsize`, etc.).
+    /// Its value may be updated through shared borrows: we access it through
+    /// [crate::expressions::Rvalue::GlobalRef], like the mutable statics.
+    /// Beware that the backends which assume the shared borrows point to
+    /// immutable values are unsound for such globals.
+    pub interior_mutability: bool,
     pub body: Option<GExprBody<T>>,
+    pub attr_info: AttrInfo,
+}
+
+/// A trait declaration.
+///
+/// For now, we only list the methods of the trait: the required methods
+/// (which don't have a default implementation) are translated to opaque
+/// functions, while the provided methods are translated to regular functions
+/// (see [crate::translate_traits]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraitDecl {
+    pub def_id: TraitDeclId::Id,
+    /// The meta data associated with the declaration.
+    pub meta: Meta,
+    pub name: TraitName,
+    /// The methods of the trait, with the function declarations we generated
+    /// for them (`None` if the method is never used by the translated crate).
+    pub methods: Vec<(String, Option<FunDeclId::Id>)>,
+}
+
+/// An implementation of a trait (`impl Trait for Type { ... }`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraitImpl {
+    pub def_id: TraitImplId::Id,
+    /// The meta data associated with the declaration.
+    pub meta: Meta,
+    /// The implemented trait
+    pub trait_id: TraitDeclId::Id,
+    /// The type for which the trait is implemented, if it is an ADT of the
+    /// translated crate
+    pub self_type: Option<TypeDeclId::Id>,
+    /// The methods defined in the implementation block (for the external
+    /// implementations, only the ones used by the translated crate). The
+    /// provided methods of the trait which are not overridden don't appear here.
+    pub methods: Vec<(String, FunDeclId::Id)>,
 }
 
 /// A function identifier. See [crate::ullbc_ast::Terminator]
-#[derive(Debug, Clone, EnumIsA, EnumAsGetters, VariantName, Serialize)]
+#[derive(Debug, Clone, EnumIsA, EnumAsGetters, VariantName, Serialize, Deserialize)]
 pub enum FunId {
     /// A "regular" function (function local to the crate, external function
     /// not treated as a primitive one).
     Regular(FunDeclId::Id),
+    /// A trait method, resolved to the method of an implementation of the
+    /// trait: `TraitMethod(impl_id, fun_id)`, where `fun_id` is the function
+    /// we generated for the method defined in the implementation `impl_id`.
+    ///
+    /// The calls to trait methods which can't be resolved statically (for
+    /// instance, because the `Self` type is a type parameter) or which resolve
+    /// to a provided method of the trait are calls to regular functions.
+    TraitMethod(TraitImplId::Id, FunDeclId::Id),
+    /// A call to a trait method through a trait object (`x.f()` where `x` has
+    /// type `&dyn Trait`), which is dispatched at runtime:
+    /// `Virtual(trait_id, fun_id)`, where `fun_id` is the function we generated
+    /// for the method declared in the trait `trait_id`. The first argument of
+    /// the call is the (pointer to the) trait object.
+    Virtual(TraitDeclId::Id, FunDeclId::Id),
+    /// A call through a function pointer (see [crate::types::Ty::FnPtr]):
+    /// the operand evaluates to the address of the function to call. The
+    /// calls through a function pointer have no type arguments.
+    Indirect(Operand),
     /// A primitive function, coming from a standard library (for instance:
     /// `alloc::boxed::Box::new`).
     /// TODO: rename to "Primitive"
@@ -116,11 +237,13 @@ pub enum FunId {
 
 /// An assumed function identifier, identifying a function coming from a
 /// standard library.
-#[derive(Debug, Clone, Copy, EnumIsA, EnumAsGetters, Serialize)]
+#[derive(Debug, Clone, Copy, EnumIsA, EnumAsGetters, Serialize, Deserialize)]
 pub enum AssumedFunId {
     /// `core::mem::replace`
     Replace,
-    /// `alloc::boxed::Box::new`
+    /// `alloc::boxed::Box::new`. We also use it for the other ways of
+    /// allocating a box: `Box::pin`, `Box::from` and the boxes initialized
+    /// in place (`ShallowInitBox` in MIR).
     BoxNew,
     /// `core::ops::deref::Deref::<alloc::boxed::Box<T>>::deref`
     BoxDeref,
@@ -144,6 +267,16 @@ pub enum AssumedFunId {
     ///
     /// Also see the comments in [crate::assumed::type_to_used_params].
     BoxFree,
+    /// `alloc::rc::Rc::new`
+    RcNew,
+    /// `core::clone::Clone::<alloc::rc::Rc<T>>::clone`: the type argument is
+    /// `T`. Note that there is no function to dereference the shared pointers:
+    /// see [crate::expressions::ProjectionElem::DerefRc].
+    RcClone,
+    /// `alloc::sync::Arc::new`
+    ArcNew,
+    /// `core::clone::Clone::<alloc::sync::Arc<T>>::clone`
+    ArcClone,
     /// `alloc::vec::Vec::new`
     VecNew,
     /// `alloc::vec::Vec::push`
@@ -156,4 +289,43 @@ pub enum AssumedFunId {
     VecIndex,
     /// `core::ops::index::IndexMut::index_mut<alloc::vec::Vec<T>, usize>`
     VecIndexMut,
+    /// `core::option::Option::unwrap`
+    OptionUnwrap,
+    /// `core::option::Option::expect`
+    OptionExpect,
+    /// `core::option::Option::unwrap_or`
+    OptionUnwrapOr,
+    /// `core::option::Option::is_some`
+    OptionIsSome,
+    /// `core::option::Option::is_none`
+    OptionIsNone,
+    /// `core::option::Option::map<T, U, F>`: the last type argument is
+    /// the type of the closure
+    OptionMap,
+    /// `core::result::Result::unwrap`
+    ResultUnwrap,
+    /// `core::result::Result::expect`
+    ResultExpect,
+    /// `core::result::Result::is_ok`
+    ResultIsOk,
+    /// `core::result::Result::is_err`
+    ResultIsErr,
+    /// `core::result::Result::map<T, E, U, F>`
+    ResultMap,
+    /// `core::result::Result::map_err<T, E, F, O>`
+    ResultMapErr,
+    /// `core::ops::try_trait::Try::branch`, introduced by the `?` operator.
+    /// The type argument is the type on which we use `?` (`Option<T>` or
+    /// `Result<T, E>`).
+    TryBranch,
+    /// `core::ops::try_trait::FromResidual::from_residual`, introduced by
+    /// the `?` operator to convert the residual (the early return).
+    FromResidual,
+    /// `core::iter::traits::collect::IntoIterator::into_iter`. The type
+    /// argument is the `Self` type.
+    IntoIter,
+    /// `core::iter::traits::iterator::Iterator::next`. The `for` loops over
+    /// the ranges are rewritten to counter loops (see [crate::range_loops]):
+    /// we only keep the calls to `next` for the other iterators.
+    IteratorNext,
 }
@@ -1,15 +1,19 @@
 //! Implementations for [crate::gast]
 #![allow(dead_code)]
 
+use crate::assumed;
+use crate::errors::{DeclErrors, TransResult};
 use crate::expressions::*;
 use crate::formatter::Formatter;
 use crate::gast::*;
+use crate::id_vector::ToUsize;
 use crate::names::Name;
 use crate::types::*;
 use crate::values::*;
 use serde::Serialize;
 use std::cmp::max;
 use std::fmt::Debug;
+use std::sync::Mutex;
 
 /// Iterate on the declarations' non-empty bodies with their corresponding name and type.
 pub fn iter_function_bodies<T: Debug + Clone + Serialize>(
@@ -32,6 +36,60 @@ pub fn iter_global_bodies<T: Debug + Clone + Serialize>(
     })
 }
 
+/// Apply `f` to the non-empty bodies of the functions and of the globals, in
+/// parallel. This is the parallel version of a loop over
+/// [iter_function_bodies] and [iter_global_bodies]: the micro-passes which
+/// transform the bodies independently of each other should use it.
+pub fn par_iter_bodies<T: Debug + Clone + Serialize + Send + Sync>(
+    funs: &mut FunDeclId::Vector<GFunDecl<T>>,
+    globals: &mut GlobalDeclId::Vector<GGlobalDecl<T>>,
+    f: impl Fn(&Name, &mut GExprBody<T>) + Sync + Send,
+) {
+    funs.par_for_each_mut(|d| {
+        if let Some(b) = &mut d.body {
+            f(&d.name, b)
+        }
+    });
+    globals.par_for_each_mut(|d| {
+        if let Some(b) = &mut d.body {
+            f(&d.name, b)
+        }
+    });
+}
+
+/// Same as [par_iter_bodies], for the transformations which can fail: we
+/// collect the errors with the identifiers of the declarations they come
+/// from. The transformation of the other bodies is not interrupted.
+///
+/// The workers push their errors as they finish: we sort them by declaration
+/// identifier, so that the errors (and the diagnostics generated from them)
+/// are reported in the same order from one run to the other.
+pub fn par_try_iter_bodies<T: Debug + Clone + Serialize + Send + Sync>(
+    funs: &mut FunDeclId::Vector<GFunDecl<T>>,
+    globals: &mut GlobalDeclId::Vector<GGlobalDecl<T>>,
+    f: impl Fn(&Name, &mut GExprBody<T>) -> TransResult<()> + Sync + Send,
+) -> DeclErrors {
+    let errors = Mutex::new(DeclErrors::default());
+    funs.par_for_each_mut(|d| {
+        if let Some(b) = &mut d.body {
+            if let Err(err) = f(&d.name, b) {
+                errors.lock().unwrap().funs.push((d.def_id, err))
+            }
+        }
+    });
+    globals.par_for_each_mut(|d| {
+        if let Some(b) = &mut d.body {
+            if let Err(err) = f(&d.name, b) {
+                errors.lock().unwrap().globals.push((d.def_id, err))
+            }
+        }
+    });
+    let mut errors = errors.into_inner().unwrap();
+    errors.funs.sort_by_key(|(id, _)| *id);
+    errors.globals.sort_by_key(|(id, _)| *id);
+    errors
+}
+
 /// Makes a lambda that generates a new variable id, pushes a new variable in
 /// the body locals with the given type and returns its id.
 pub fn make_locals_generator(locals: &mut VarId::Vector<Var>) -> impl FnMut(ETy) -> VarId::Id + '_ {
@@ -63,11 +121,11 @@ impl std::string::ToString for Var {
 impl Var {
     /// Substitute the region parameters and type variables and return
     /// the resulting variable
-    pub fn substitute(&self, subst: &ETypeSubst) -> Var {
+    pub fn substitute(&self, subst: &ETypeSubst, cgsubst: &ConstGenericSubst) -> Var {
         Var {
             index: self.index,
             name: self.name.clone(),
-            ty: self.ty.substitute_types(subst),
+            ty: self.ty.substitute_types(subst, cgsubst),
         }
     }
 }
@@ -102,7 +160,15 @@ where
     let args = args.join(", ");
 
     let f = match func {
-        FunId::Regular(def_id) => format!("{}{}", ctx.format_object(*def_id), rt_args),
+        // The name of the method of the implementation is enough to identify it
+        FunId::Regular(def_id) | FunId::TraitMethod(_, def_id) => {
+            format!("{}{}", ctx.format_object(*def_id), rt_args)
+        }
+        // The method is dispatched at runtime
+        FunId::Virtual(_, def_id) => {
+            format!("@virtual({}){}", ctx.format_object(*def_id), rt_args)
+        }
+        FunId::Indirect(op) => format!("(*{})", op.fmt_with_ctx(ctx)),
         FunId::Assumed(assumed) => match assumed {
             AssumedFunId::Replace => format!("core::mem::replace{rt_args}"),
             AssumedFunId::BoxNew => format!("alloc::boxed::Box{rt_args}::new"),
@@ -113,6 +179,14 @@ where
                 format!("core::ops::deref::DerefMut<alloc::boxed::Box{rt_args}>::deref_mut",)
             }
             AssumedFunId::BoxFree => format!("alloc::alloc::box_free{rt_args}"),
+            AssumedFunId::RcNew => format!("alloc::rc::Rc{rt_args}::new"),
+            AssumedFunId::RcClone => {
+                format!("core::clone::Clone<alloc::rc::Rc{rt_args}>::clone")
+            }
+            AssumedFunId::ArcNew => format!("alloc::sync::Arc{rt_args}::new"),
+            AssumedFunId::ArcClone => {
+                format!("core::clone::Clone<alloc::sync::Arc{rt_args}>::clone")
+            }
             AssumedFunId::VecNew => format!("alloc::vec::Vec{rt_args}::new"),
             AssumedFunId::VecPush => format!("alloc::vec::Vec{rt_args}::push"),
             AssumedFunId::VecInsert => format!("alloc::vec::Vec{rt_args}::insert"),
@@ -123,6 +197,28 @@ where
             AssumedFunId::VecIndexMut => {
                 format!("core::ops::index::IndexMut<alloc::vec::Vec{rt_args}>::index_mut",)
             }
+            AssumedFunId::OptionUnwrap => format!("core::option::Option::unwrap{rt_args}"),
+            AssumedFunId::OptionExpect => format!("core::option::Option::expect{rt_args}"),
+            AssumedFunId::OptionUnwrapOr => format!("core::option::Option::unwrap_or{rt_args}"),
+            AssumedFunId::OptionIsSome => format!("core::option::Option::is_some{rt_args}"),
+            AssumedFunId::OptionIsNone => format!("core::option::Option::is_none{rt_args}"),
+            AssumedFunId::OptionMap => format!("core::option::Option::map{rt_args}"),
+            AssumedFunId::ResultUnwrap => format!("core::result::Result::unwrap{rt_args}"),
+            AssumedFunId::ResultExpect => format!("core::result::Result::expect{rt_args}"),
+            AssumedFunId::ResultIsOk => format!("core::result::Result::is_ok{rt_args}"),
+            AssumedFunId::ResultIsErr => format!("core::result::Result::is_err{rt_args}"),
+            AssumedFunId::ResultMap => format!("core::result::Result::map{rt_args}"),
+            AssumedFunId::ResultMapErr => format!("core::result::Result::map_err{rt_args}"),
+            AssumedFunId::TryBranch => format!("core::ops::try_trait::Try{rt_args}::branch"),
+            AssumedFunId::FromResidual => {
+                format!("core::ops::try_trait::FromResidual{rt_args}::from_residual")
+            }
+            AssumedFunId::IntoIter => {
+                format!("core::iter::traits::collect::IntoIterator{rt_args}::into_iter")
+            }
+            AssumedFunId::IteratorNext => {
+                format!("core::iter::traits::iterator::Iterator{rt_args}::next")
+            }
         },
     };
 
@@ -130,6 +226,100 @@ where
 }
 
 impl<T: Debug + Clone + Serialize> GExprBody<T> {
+    /// Compute the type of a place.
+    ///
+    /// Note that the meaning of [ProjectionElem::DerefBox] depends on the MIR
+    /// level: if the boxes are not desugared it gives access to the boxed
+    /// value, otherwise it projects the box to its inner `std::ptr::Unique`
+    /// pointer (and is then followed by [ProjectionElem::DerefPtrUnique]).
+    /// We disambiguate by looking at the next projection element, which allows
+    /// us to properly handle chains of dereferences of nested boxes like
+    /// `deref_box (deref_box (x))` (with `x: Box<Box<T>>`).
+    ///
+    /// The projections of a place of type `!` (which can only appear in dead
+    /// code) have type `!`.
+    pub fn place_type(&self, type_defs: &TypeDecls, p: &Place) -> ETy {
+        let mut ty = self.locals.get(p.var_id).unwrap().ty.clone();
+        let mut projection = p.projection.iter().peekable();
+        while let Some(pe) = projection.next() {
+            ty = match (pe, ty) {
+                (_, Ty::Never) => Ty::Never,
+                (ProjectionElem::Deref, Ty::Ref(_, ty, _))
+                | (ProjectionElem::DerefRawPtr, Ty::RawPtr(ty, _)) => *ty,
+                (
+                    ProjectionElem::DerefBox,
+                    Ty::Adt(TypeId::Assumed(AssumedTy::Box), regions, mut tys),
+                ) => {
+                    let ty = tys.pop_front().unwrap();
+                    if projection.peek() == Some(&&ProjectionElem::DerefPtrUnique) {
+                        Ty::Adt(
+                            TypeId::Assumed(AssumedTy::PtrUnique),
+                            regions,
+                            im::vector![ty],
+                        )
+                    } else {
+                        ty
+                    }
+                }
+                (
+                    ProjectionElem::DerefPtrUnique,
+                    Ty::Adt(TypeId::Assumed(AssumedTy::PtrUnique), regions, tys),
+                ) => Ty::Adt(TypeId::Assumed(AssumedTy::PtrNonNull), regions, tys),
+                (
+                    ProjectionElem::DerefPtrNonNull,
+                    Ty::Adt(TypeId::Assumed(AssumedTy::PtrNonNull), _, mut tys),
+                ) => Ty::RawPtr(Box::new(tys.pop_front().unwrap()), RefKind::Shared),
+                (
+                    ProjectionElem::DerefRc,
+                    Ty::Adt(TypeId::Assumed(AssumedTy::Rc | AssumedTy::Arc), _, mut tys),
+                ) => tys.pop_front().unwrap(),
+                (
+                    ProjectionElem::Field(FieldProjKind::Adt(type_id, variant_id), field_id),
+                    Ty::Adt(TypeId::Adt(_), _, tys),
+                ) => type_defs
+                    .get_type_def(*type_id)
+                    .unwrap()
+                    .get_erased_regions_instantiated_field_type(*variant_id, &tys, *field_id),
+                (
+                    ProjectionElem::Field(FieldProjKind::Union(type_id), field_id),
+                    Ty::Adt(TypeId::Adt(_), _, tys),
+                ) => type_defs
+                    .get_type_def(*type_id)
+                    .unwrap()
+                    .get_erased_regions_instantiated_field_type(None, &tys, *field_id),
+                (
+                    ProjectionElem::Field(FieldProjKind::Tuple(_), field_id),
+                    Ty::Adt(TypeId::Tuple, _, tys),
+                ) => tys.get(field_id.to_usize()).unwrap().clone(),
+                (
+                    ProjectionElem::Field(FieldProjKind::Option(_), _),
+                    Ty::Adt(TypeId::Assumed(AssumedTy::Option), _, mut tys),
+                ) => tys.pop_front().unwrap(),
+                // `ControlFlow<B, C>`: `Continue(C)` or `Break(B)`
+                (
+                    ProjectionElem::Field(FieldProjKind::ControlFlow(variant_id), _),
+                    Ty::Adt(TypeId::Assumed(AssumedTy::ControlFlow), _, mut tys),
+                ) => {
+                    if *variant_id == assumed::CONTROL_FLOW_CONTINUE_VARIANT_ID {
+                        tys.pop_back().unwrap()
+                    } else {
+                        tys.pop_front().unwrap()
+                    }
+                }
+                (
+                    ProjectionElem::Index(_) | ProjectionElem::ConstantIndex { .. },
+                    Ty::Array(ty, _) | Ty::Slice(ty),
+                ) => *ty,
+                (
+                    ProjectionElem::Index(_),
+                    Ty::Adt(TypeId::Assumed(AssumedTy::Vec), _, mut tys),
+                ) => tys.pop_front().unwrap(),
+                (pe, ty) => unreachable!("Ill-typed projection: {:?} over {:?}", pe, ty),
+            };
+        }
+        ty
+    }
+
     /// This is an auxiliary function for printing definitions. One may wonder
     /// why we require a formatter to format, for instance, (type) var ids,
     /// because the function definition already has the information to print
@@ -152,7 +342,6 @@ impl<T: Debug + Clone + Serialize> GExprBody<T> {
         // Format the local variables
         let mut locals: Vec<String> = Vec::new();
         for v in &self.locals {
-            use crate::id_vector::ToUsize;
             let index = v.index.to_usize();
             let comment = if index == 0 {
                 "// return".to_string()
@@ -520,30 +709,8 @@ impl<'ctx, FD, GD> Formatter<(TypeDeclId::Id, Option<VariantId::Id>, FieldId::Id
 {
     fn format_object(&self, id: (TypeDeclId::Id, Option<VariantId::Id>, FieldId::Id)) -> String {
         let (def_id, opt_variant_id, field_id) = id;
-        let ctx = self.type_context;
-        let gen_def = ctx.get_type_def(def_id).unwrap();
-        match (&gen_def.kind, opt_variant_id) {
-            (TypeDeclKind::Enum(variants), Some(variant_id)) => {
-                let field = variants
-                    .get(variant_id)
-                    .unwrap()
-                    .fields
-                    .get(field_id)
-                    .unwrap();
-                match &field.name {
-                    Option::Some(name) => name.clone(),
-                    Option::None => field_id.to_string(),
-                }
-            }
-            (TypeDeclKind::Struct(fields), None) => {
-                let field = fields.get(field_id).unwrap();
-                match &field.name {
-                    Option::Some(name) => name.clone(),
-                    Option::None => field_id.to_string(),
-                }
-            }
-            _ => unreachable!(),
-        }
+        let def = self.type_context.get_type_def(def_id).unwrap();
+        def.get_field_name(opt_variant_id, field_id)
     }
 }
 
@@ -632,3 +799,195 @@ impl<'ctx> Formatter<GlobalDeclId::Id> for GlobalNamesFormatter<'ctx> {
         self.decls.get(id).unwrap().clone()
     }
 }
+
+/// Return the functions whose translation is not fully precise, together with
+/// the approximations we performed when translating them.
+pub fn imprecise_functions<T: Debug + Clone + Serialize>(
+    decls: &FunDeclId::Vector<GFunDecl<T>>,
+) -> Vec<(FunDeclId::Id, Vec<PrecisionLoss>)> {
+    decls
+        .iter()
+        .filter(|decl| !decl.precision_losses.is_empty())
+        .map(|decl| (decl.def_id, decl.precision_losses.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gast_utils::*;
+    use crate::llbc_ast::{FunDecl, RawStatement};
+    use crate::meta::{AttrInfo, Meta};
+    use crate::regions_hierarchy::RegionGroups;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_serialize_arg_count_and_return_local() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let locals: Vec<Var> = (0..3).map(|i| mk_var(i, None, u32_ty.clone())).collect();
+        // A function with two arguments: `fn f(x: u32, y: u32) -> u32`
+        let body = mk_body(2, locals, vec![RawStatement::Return]);
+        let json = serde_json::to_string(&body).unwrap();
+        assert!(json.contains("\"arg_count\":2,\"return_local\":0,"));
+    }
+
+    /// A function `fn name() { return }`
+    fn mk_unit_fun(def_id: usize, name: &str, precision_losses: Vec<PrecisionLoss>) -> FunDecl {
+        let locals = vec![mk_var(0, None, Ty::mk_unit())];
+        let body = mk_body(0, locals, vec![RawStatement::Return]);
+        let mut decl = mk_fun_decl(
+            def_id,
+            &[name],
+            mk_sig(Vec::new(), Ty::mk_unit()),
+            Some(body),
+        );
+        decl.precision_losses = precision_losses;
+        decl
+    }
+
+    #[test]
+    fn test_imprecise_inline_asm() {
+        // `g` contains inline assembly (for instance: `asm!("nop")`), which
+        // we ignored during the translation
+        let asm_meta = Meta::dummy();
+        let decls = FunDeclId::Vector::from(vec![
+            mk_unit_fun(0, "f", Vec::new()),
+            mk_unit_fun(1, "g", vec![PrecisionLoss::InlineAsm(asm_meta)]),
+        ]);
+        let imprecise = imprecise_functions(&decls);
+        assert!(imprecise.len() == 1);
+        let (id, losses) = &imprecise[0];
+        assert!(*id == FunDeclId::Id::new(1));
+        assert!(losses.len() == 1 && losses[0].is_inline_asm());
+    }
+
+    fn mk_struct(def_id: usize, name: &str, field_names: Vec<Option<&str>>) -> TypeDecl {
+        let fields: Vec<Field> = field_names
+            .into_iter()
+            .map(|name| Field {
+                meta: Meta::dummy(),
+                name: name.map(|n| n.to_string()),
+                ty: Ty::Integer(IntegerTy::U32),
+            })
+            .collect();
+        TypeDecl {
+            def_id: TypeDeclId::Id::new(def_id),
+            meta: Meta::dummy(),
+            name: Name::from(vec![name.to_string()]),
+            region_params: RegionVarId::Vector::new(),
+            type_params: TypeVarId::Vector::new(),
+            const_generic_params: ConstGenericVarId::Vector::new(),
+            trait_clauses: Vec::new(),
+            regions_hierarchy: RegionGroups::new(),
+            kind: TypeDeclKind::Struct(FieldId::Vector::from(fields)),
+            repr_packed: None,
+            under_aligned_fields: Vec::new(),
+            recursive_fields: Vec::new(),
+            attr_info: AttrInfo::default(),
+        }
+    }
+
+    fn mk_field_place(var_id: VarId::Id, def_id: TypeDeclId::Id, field_id: usize) -> Place {
+        let mut p = Place::new(var_id);
+        p.projection.push_back(ProjectionElem::Field(
+            FieldProjKind::Adt(def_id, None),
+            FieldId::Id::new(field_id),
+        ));
+        p
+    }
+
+    #[test]
+    fn test_fmt_field_names() {
+        // `struct Pair(u32, u32);` and `struct Point { x: u32, y: u32 }`
+        let pair_id = TypeDeclId::Id::new(0);
+        let point_id = TypeDeclId::Id::new(1);
+        let type_defs = TypeDecls {
+            types: TypeDeclId::Vector::from(vec![
+                mk_struct(0, "Pair", vec![None, None]),
+                mk_struct(1, "Point", vec![Some("x"), Some("y")]),
+            ]),
+        };
+        let ctx = GAstFormatter::new(&type_defs, &(), &(), None, None);
+        let x = VarId::Id::new(1);
+
+        // The fields of the tuple structs are positional
+        let p = mk_field_place(x, pair_id, 1);
+        assert!(p.fmt_with_ctx(&ctx) == "(var@1).1");
+        // The other fields are printed by name
+        let p = mk_field_place(x, point_id, 1);
+        assert!(p.fmt_with_ctx(&ctx) == "(var@1).y");
+    }
+
+    #[test]
+    fn test_never_place_type() {
+        // ```
+        // fn f() -> u32 {
+        //     let x: ! = core::panicking::panic(...);
+        //     ...
+        // }
+        // ```
+        let locals = vec![
+            mk_var(0, None, Ty::Integer(IntegerTy::U32)),
+            mk_var(1, Some("x"), Ty::Never),
+        ];
+        let body = mk_body(0, locals, vec![RawStatement::Panic]);
+        let x = Place::new(VarId::ONE);
+        assert!(body.place_type(&TypeDecls::new(), &x).is_never());
+        // Projecting the local (in dead code) doesn't trip the type resolution
+        let mut p = x;
+        p.projection.push_back(ProjectionElem::Deref);
+        p.projection.push_back(ProjectionElem::Field(
+            FieldProjKind::Tuple(2),
+            FieldId::ZERO,
+        ));
+        assert!(body.place_type(&TypeDecls::new(), &p).is_never());
+    }
+
+    #[test]
+    fn test_constant_index() {
+        // `fn f(s: &[u32]) -> u32 { match s { [.., z] => z, ... } }`
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let s_ty = Ty::Ref(
+            ErasedRegion::Erased,
+            Box::new(Ty::Slice(Box::new(u32_ty.clone()))),
+            RefKind::Shared,
+        );
+        let locals = vec![mk_var(0, None, u32_ty.clone()), mk_var(1, Some("s"), s_ty)];
+        let body = mk_body(1, locals, vec![RawStatement::Return]);
+        // `(*s)[-1 of 1]`
+        let mut z = Place::new(VarId::ONE);
+        z.projection.push_back(ProjectionElem::Deref);
+        z.projection.push_back(ProjectionElem::ConstantIndex {
+            offset: 1,
+            min_length: 1,
+            from_end: true,
+        });
+        assert!(body.place_type(&TypeDecls::new(), &z) == u32_ty);
+        assert!(z.to_string().ends_with("[-1 of 1]"));
+    }
+
+    #[test]
+    fn test_union_field() {
+        // `union U { i: u32, j: u32 }` and `fn f(u: U) -> u32 { unsafe { u.j } }`
+        let mut u_decl = mk_struct(0, "U", vec![Some("i"), Some("j")]);
+        u_decl.kind = TypeDeclKind::Union(u_decl.kind.as_struct().clone());
+        let type_defs = TypeDecls {
+            types: TypeDeclId::Vector::from(vec![u_decl]),
+        };
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let u_ty = Ty::Adt(
+            TypeId::Adt(TypeDeclId::ZERO),
+            im::Vector::new(),
+            im::Vector::new(),
+        );
+        let locals = vec![mk_var(0, None, u32_ty.clone()), mk_var(1, Some("u"), u_ty)];
+        let body = mk_body(1, locals, vec![RawStatement::Return]);
+        let mut p = Place::new(VarId::ONE);
+        p.projection.push_back(ProjectionElem::Field(
+            FieldProjKind::Union(TypeDeclId::ZERO),
+            FieldId::ONE,
+        ));
+        assert!(body.place_type(&type_defs, &p) == u32_ty);
+        let ctx = GAstFormatter::new(&type_defs, &(), &(), None, None);
+        assert!(p.fmt_with_ctx(&ctx) == "(var@1).j");
+    }
+}
@@ -0,0 +1,366 @@
+//! Generate an HTML report of the LLBC functions (see the `--html-report`
+//! option), to help debug the translation.
+//!
+//! Every function is shown side by side with its Rust source. We use the
+//! spans of the statements (see [crate::meta::Meta]) to link the two views:
+//! hovering over an LLBC statement highlights the source lines it comes from,
+//! and hovering over a source line highlights the statements which come from
+//! this line.
+//!
+//! Note that we use the `span` field of the meta information, and not the
+//! `generated_from_span` one: the statements which come from a macro
+//! expansion are linked to the call to the macro.
+
+use crate::common::*;
+use crate::gast::TAB_INCR;
+use crate::gast_utils::{FunNamesFormatter, GAstFormatter, GlobalNamesFormatter};
+use crate::llbc_ast::{
+    CtxNames, FunDecl, FunDeclId, FunDecls, GlobalDeclId, GlobalDecls, RawStatement, Statement,
+    Switch,
+};
+use crate::meta::{FileId, FileName, Meta};
+use crate::types::TypeDecls;
+use std::collections::HashMap;
+use std::iter::FromIterator;
+use std::path::PathBuf;
+
+type Ctx<'a> = GAstFormatter<'a, FunNamesFormatter<'a>, GlobalNamesFormatter<'a>>;
+
+/// A line of the pretty-printed LLBC body
+struct Line {
+    /// The meta information of the statement printed on this line, if it
+    /// comes from the same file as the function.
+    meta: Option<Meta>,
+    text: String,
+}
+
+impl Line {
+    fn new(meta: Option<Meta>, text: String) -> Self {
+        Line { meta, text }
+    }
+}
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; }
+.cols { display: flex; gap: 1em; }
+.cols pre { flex: 1; overflow-x: auto; background: #f6f6f6; padding: 0.5em; }
+.lnum { color: #999; user-select: none; }
+.hl { background: #ffe58a; }
+"#;
+
+const SCRIPT: &str = r#"
+function toggle(elems, on) {
+  elems.forEach(e => e.classList.toggle('hl', on));
+}
+document.querySelectorAll('.fun').forEach(fun => {
+  const sts = Array.from(fun.querySelectorAll('.st[data-beg]'));
+  const lines = Array.from(fun.querySelectorAll('.line'));
+  const covers = (st, n) => +st.dataset.beg <= n && n <= +st.dataset.end;
+  sts.forEach(st => {
+    const related = [st].concat(lines.filter(l => covers(st, +l.dataset.line)));
+    st.addEventListener('mouseenter', () => toggle(related, true));
+    st.addEventListener('mouseleave', () => toggle(related, false));
+  });
+  lines.forEach(l => {
+    const related = [l].concat(sts.filter(st => covers(st, +l.dataset.line)));
+    l.addEventListener('mouseenter', () => toggle(related, true));
+    l.addEventListener('mouseleave', () => toggle(related, false));
+  });
+});
+"#;
+
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Pretty-print a statement, one line per leaf statement (the structure of
+/// the statements is the same as for [Statement::fmt_with_ctx]).
+///
+/// `file_id` is the file of the function: we drop the meta information of
+/// the statements which come from another file, as we can't link them.
+fn push_lines(ctx: &Ctx<'_>, file_id: FileId::Id, tab: &str, st: &Statement, out: &mut Vec<Line>) {
+    let meta = if st.meta.span.file_id == file_id && !st.meta.is_dummy() {
+        Some(st.meta)
+    } else {
+        None
+    };
+    let inner_tab = format!("{tab}{TAB_INCR}");
+    match &st.content {
+        RawStatement::Sequence(st1, st2) => {
+            push_lines(ctx, file_id, tab, st1, out);
+            push_lines(ctx, file_id, tab, st2, out);
+        }
+        RawStatement::Loop(body, _) => {
+            out.push(Line::new(meta, format!("{tab}loop {{")));
+            push_lines(ctx, file_id, &inner_tab, body, out);
+            out.push(Line::new(None, format!("{tab}}}")));
+        }
+        RawStatement::While(cond, op, body, _) => {
+            out.push(Line::new(meta, format!("{tab}while {{")));
+            push_lines(ctx, file_id, &inner_tab, cond, out);
+            let op = op.fmt_with_ctx(ctx);
+            out.push(Line::new(None, format!("{inner_tab}{op}")));
+            out.push(Line::new(None, format!("{tab}}} {{")));
+            push_lines(ctx, file_id, &inner_tab, body, out);
+            out.push(Line::new(None, format!("{tab}}}")));
+        }
+        RawStatement::Switch(switch) => {
+            let (header, branches): (String, Vec<(String, &Statement)>) = match switch {
+                Switch::If(discr, true_st, false_st) => (
+                    format!("if {}", discr.fmt_with_ctx(ctx)),
+                    vec![
+                        ("true".to_string(), &**true_st),
+                        ("false".to_string(), &**false_st),
+                    ],
+                ),
+                Switch::SwitchInt(discr, _, maps, otherwise) => (
+                    format!("switch {}", discr.fmt_with_ctx(ctx)),
+                    maps.iter()
+                        .map(|(pvl, st)| {
+                            let pvl: Vec<String> = pvl.iter().map(|v| v.to_string()).collect();
+                            (pvl.join(" | "), st)
+                        })
+                        .chain(std::iter::once(("_".to_string(), &**otherwise)))
+                        .collect(),
+                ),
+                Switch::Match(discr, maps, otherwise, _) => (
+                    format!("match {}", discr.fmt_with_ctx(ctx)),
+                    maps.iter()
+                        .map(|(pvl, st)| {
+                            let pvl: Vec<String> = pvl.iter().map(|v| v.to_string()).collect();
+                            (pvl.join(" | "), st)
+                        })
+                        .chain(std::iter::once(("_".to_string(), &**otherwise)))
+                        .collect(),
+                ),
+            };
+            out.push(Line::new(meta, format!("{tab}{header} {{")));
+            let branch_tab = format!("{inner_tab}{TAB_INCR}");
+            for (pat, branch) in branches {
+                out.push(Line::new(None, format!("{inner_tab}{pat} => {{")));
+                push_lines(ctx, file_id, &branch_tab, branch, out);
+                out.push(Line::new(None, format!("{inner_tab}}}")));
+            }
+            out.push(Line::new(None, format!("{tab}}}")));
+        }
+        _ => out.push(Line::new(meta, st.fmt_with_ctx(tab, ctx))),
+    }
+}
+
+/// Generate the HTML section of a function. `source` gives the lines of the
+/// file the function comes from, if we could read it.
+fn fmt_fun(ctx_names: &CtxNames<'_>, decl: &FunDecl, source: Option<&[String]>) -> String {
+    let body = decl.body.as_ref().unwrap();
+    let fun_ctx = FunNamesFormatter::new(ctx_names.fun_context);
+    let global_ctx = GlobalNamesFormatter::new(ctx_names.global_context);
+    let ctx = GAstFormatter::new(
+        ctx_names.type_context,
+        &fun_ctx,
+        &global_ctx,
+        Some(&decl.signature.type_params),
+        Some(&body.locals),
+    );
+    let mut lines = Vec::new();
+    push_lines(&ctx, decl.meta.span.file_id, "", &body.body, &mut lines);
+
+    let llbc: Vec<String> = lines
+        .iter()
+        .map(|line| match line.meta {
+            Some(meta) => format!(
+                "<span class=\"st\" data-beg=\"{}\" data-end=\"{}\">{}</span>",
+                meta.span.beg.line,
+                meta.span.end.line,
+                escape_html(&line.text)
+            ),
+            None => format!("<span>{}</span>", escape_html(&line.text)),
+        })
+        .collect();
+
+    let src = match source {
+        Some(source) => {
+            // The line numbers are 1-based (and 0 for the dummy spans)
+            let (beg, end) = (decl.meta.span.beg.line.max(1), decl.meta.span.end.line);
+            let src: Vec<String> = (beg..=end.min(source.len()))
+                .map(|n| {
+                    format!(
+                        "<span class=\"line\" data-line=\"{n}\">\
+                         <span class=\"lnum\">{n:>5} </span>{}</span>",
+                        escape_html(&source[n - 1])
+                    )
+                })
+                .collect();
+            src.join("\n")
+        }
+        None => "<span>(source unavailable)</span>".to_string(),
+    };
+
+    let name = escape_html(&decl.name.to_string());
+    format!(
+        "<section class=\"fun\" id=\"fun{}\">\n<h2>{name}</h2>\n\
+         <div class=\"cols\">\n<pre class=\"src\">{src}</pre>\n\
+         <pre class=\"llbc\">{}</pre>\n</div>\n</section>",
+        decl.def_id,
+        llbc.join("\n")
+    )
+}
+
+/// Read the source files, for the spans of the functions. We only read the
+/// local files: the virtual files (the standard library, etc.) may not be
+/// available on the disk.
+fn read_sources(
+    id_to_file: &HashMap<FileId::Id, FileName>,
+    funs: &FunDecls,
+) -> HashMap<FileId::Id, Vec<String>> {
+    let mut sources = HashMap::new();
+    for decl in funs.iter().filter(|d| d.body.is_some()) {
+        let file_id = decl.meta.span.file_id;
+        if sources.contains_key(&file_id) {
+            continue;
+        }
+        if let Some(FileName::Local(path)) = id_to_file.get(&file_id) {
+            match std::fs::read_to_string(path) {
+                std::result::Result::Ok(content) => {
+                    let _ = sources.insert(file_id, content.lines().map(String::from).collect());
+                }
+                std::result::Result::Err(_) => {
+                    log::warn!("Could not read the source file: {:?}", path);
+                }
+            }
+        }
+    }
+    sources
+}
+
+/// Generate the HTML report of the functions of a crate.
+pub fn fmt_report(
+    crate_name: &str,
+    type_defs: &TypeDecls,
+    funs: &FunDecls,
+    globals: &GlobalDecls,
+    sources: &HashMap<FileId::Id, Vec<String>>,
+) -> String {
+    // We only show the functions, but they may refer to the globals
+    let fun_names = FunDeclId::Vector::from_iter(funs.iter().map(|d| d.name.to_string()));
+    let global_names = GlobalDeclId::Vector::from_iter(globals.iter().map(|d| d.name.to_string()));
+    let ctx = CtxNames::new(type_defs, &fun_names, &global_names);
+
+    let sections: Vec<String> = funs
+        .iter()
+        .filter(|d| d.body.is_some())
+        .map(|d| {
+            let source = sources.get(&d.meta.span.file_id).map(|s| s.as_slice());
+            fmt_fun(&ctx, d, source)
+        })
+        .collect();
+    let crate_name = escape_html(crate_name);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{crate_name}</title>\n<style>{STYLE}</style>\n</head>\n\
+         <body>\n<h1>{crate_name}</h1>\n{}\n<script>{SCRIPT}</script>\n</body>\n</html>\n",
+        sections.join("\n")
+    )
+}
+
+/// Write the HTML report to `{dest_dir}/{crate}.report.html`.
+pub fn export_report(
+    crate_name: &str,
+    id_to_file: &HashMap<FileId::Id, FileName>,
+    type_defs: &TypeDecls,
+    funs: &FunDecls,
+    globals: &GlobalDecls,
+    dest_dir: &Option<PathBuf>,
+) -> Result<()> {
+    let sources = read_sources(id_to_file, funs);
+    let report = fmt_report(crate_name, type_defs, funs, globals, &sources);
+
+    let mut target_filename = dest_dir
+        .as_deref()
+        .map_or_else(PathBuf::new, |d| d.to_path_buf());
+    target_filename.push(format!("{crate_name}.report.html"));
+    if let Some(dest_dir) = dest_dir {
+        if std::fs::create_dir_all(dest_dir).is_err() {
+            error!("Could not create the directory: {:?}", dest_dir);
+            return Err(());
+        }
+    }
+    match std::fs::write(&target_filename, report) {
+        std::result::Result::Ok(()) => {
+            info!("Generated the file: {:?}", target_filename);
+            Ok(())
+        }
+        std::result::Result::Err(err) => {
+            error!("Could not write to {:?}: {}", target_filename, err);
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::*;
+    use crate::html_report::*;
+    use crate::llbc_builder::{Block, BodyBuilder};
+    use crate::meta::{Loc, LocalFileId, Span};
+    use crate::types::*;
+
+    fn mk_meta(beg: usize, end: usize) -> Meta {
+        Meta {
+            span: Span {
+                file_id: FileId::Id::LocalId(LocalFileId::ZERO),
+                beg: Loc { line: beg, col: 0 },
+                end: Loc { line: end, col: 0 },
+            },
+            generated_from_span: None,
+        }
+    }
+
+    #[test]
+    fn test_push_lines() {
+        // `if x { @return := 0 } else { panic }`
+        let mut b = BodyBuilder::new(Ty::Bool, vec![Ty::Bool]);
+        let x = b.arg(0);
+        let ret = b.ret();
+        let then_branch = Block::new()
+            .with_meta(mk_meta(2, 2))
+            .assign(Place::new(ret), Rvalue::Use(Operand::Copy(Place::new(x))))
+            .ret();
+        let body = b.build(Switch::if_then_else(
+            Operand::Copy(Place::new(x)),
+            then_branch,
+            Block::new().panic(),
+        ));
+
+        let type_defs = TypeDecls::new();
+        let fun_names = FunDeclId::Vector::new();
+        let global_names = GlobalDeclId::Vector::new();
+        let fun_ctx = FunNamesFormatter::new(&fun_names);
+        let global_ctx = GlobalNamesFormatter::new(&global_names);
+        let ctx = GAstFormatter::new(&type_defs, &fun_ctx, &global_ctx, None, Some(&body.locals));
+        let mut lines = Vec::new();
+        let file_id = FileId::Id::LocalId(LocalFileId::ZERO);
+        push_lines(&ctx, file_id, "", &body.body, &mut lines);
+
+        let texts: Vec<&str> = lines.iter().map(|l| l.text.trim()).collect();
+        assert!(texts[0].starts_with("if ") && texts[1] == "true => {");
+        assert!(texts.contains(&"panic") && texts.contains(&"return"));
+        // Only the assignment has (non-dummy) meta information
+        let with_meta: Vec<&Line> = lines.iter().filter(|l| l.meta.is_some()).collect();
+        assert!(with_meta.len() == 2);
+        assert!(with_meta.iter().all(|l| l.meta.unwrap().span.beg.line == 2));
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert!(escape_html("Vec<&T>") == "Vec&lt;&amp;T&gt;");
+    }
+}
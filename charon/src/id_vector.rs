@@ -8,7 +8,8 @@
 //! Note that this data structure is implemented by using persistent vectors.
 //! This makes the clone operation almost a no-op.
 
-use serde::{Serialize, Serializer};
+use rayon::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::iter::{FromIterator, IntoIterator};
 
 pub use std::collections::hash_map::Iter as IterAll;
@@ -180,6 +181,17 @@ where
     pub fn iter_mut(&mut self) -> im::vector::IterMut<T> {
         self.vector.iter_mut()
     }
+
+    /// Apply `f` to all the elements, in parallel. We move the elements out
+    /// of the persistent vector, then move them back in the same order.
+    pub fn par_for_each_mut(&mut self, f: impl Fn(&mut T) + Sync + Send)
+    where
+        T: Send,
+    {
+        let mut elems: Vec<T> = std::mem::take(&mut self.vector).into_iter().collect();
+        elems.par_iter_mut().for_each(f);
+        self.vector = im::Vector::from(elems);
+    }
 }
 
 impl<I: ToUsize, T: Clone> Default for Vector<I, T> {
@@ -268,3 +280,13 @@ impl<I: ToUsize, T: Clone + Serialize> Serialize for Vector<I, T> {
         seq.end()
     }
 }
+
+impl<'de, I: ToUsize, T: Clone + Deserialize<'de>> Deserialize<'de> for Vector<I, T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let v: Vec<T> = Deserialize::deserialize(deserializer)?;
+        Ok(Vector::from(v))
+    }
+}
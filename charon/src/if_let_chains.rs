@@ -0,0 +1,208 @@
+//! Recognize the `if let ... else if let ...` chains, like:
+//! ```text
+//! if let Some(y) = x {
+//!   ...
+//! } else if let Ok(w) = z {
+//!   ...
+//! } else {
+//!   ...
+//! }
+//! ```
+//! An `if let` is desugared by rustc to a match with a single branch, and the
+//! chains are desugared to nested matches in the `otherwise` branches. Once
+//! the control-flow is reconstructed, we get:
+//! ```text
+//! match x {
+//!   1 => {
+//!     y := move (x as variant @1).0;
+//!     ...
+//!   }
+//!   _ => {
+//!     match z {
+//!       0 => {
+//!         w := move (z as variant @0).0;
+//!         ...
+//!       }
+//!       _ => {
+//!         ...
+//!       }
+//!     }
+//!   }
+//! }
+//! ```
+//! The analysis below flattens those nested matches, so that backends can
+//! present them the way they appear in the source. We also use it when
+//! printing the LLBC, which gives for the example above:
+//! ```text
+//! if let @1 { 0: y } = x {
+//!   y := move (x as variant @1).0;
+//!   ...
+//! }
+//! else if let @0 { 0: w } = z {
+//!   w := move (z as variant @0).0;
+//!   ...
+//! }
+//! else {
+//!   ...
+//! }
+//! ```
+
+use crate::expressions::Place;
+use crate::llbc_ast::{RawStatement, Statement, Switch};
+use crate::match_bindings::{get_arm_bindings, ArmBindings};
+
+/// A link of an `if let` chain: `if let pattern = value { body }`
+#[derive(Debug, Clone)]
+pub struct IfLetArm<'a> {
+    /// The place we match over
+    pub value: &'a Place,
+    /// The variant of the pattern, and the variables it binds. Those
+    /// variables are only in scope in `body`.
+    pub pattern_bindings: ArmBindings,
+    /// The branch taken if the value matches the pattern (it starts with the
+    /// assignments to the bound variables)
+    pub body: &'a Statement,
+}
+
+/// An `if let ... else if let ... else ...` chain
+#[derive(Debug, Clone)]
+pub struct IfLetChain<'a> {
+    /// The links of the chain, in the order in which they are tested
+    pub arms: Vec<IfLetArm<'a>>,
+    /// The branch taken if none of the values match their pattern
+    pub else_branch: &'a Statement,
+}
+
+/// Check if a switch comes from an `if let`: it must be a match with exactly
+/// one branch, for exactly one variant. Return the branch and the `otherwise`
+/// branch.
+fn get_if_let(switch: &Switch) -> Option<(IfLetArm<'_>, &Statement)> {
+    match switch {
+        Switch::Match(scrutinee, targets, otherwise, _) => {
+            let (variants, body) = match targets.as_slice() {
+                [(variants, body)] => (variants, body),
+                _ => return None,
+            };
+            let pattern_bindings = get_arm_bindings(scrutinee, variants, body)?;
+            let arm = IfLetArm {
+                value: scrutinee,
+                pattern_bindings,
+                body,
+            };
+            Some((arm, otherwise.as_ref()))
+        }
+        Switch::If(_, _, _) | Switch::SwitchInt(_, _, _, _) => None,
+    }
+}
+
+/// Flatten a chain of `if let`: we follow the `otherwise` branches as long as
+/// they are exactly an `if let`. Note that a single `if let` gives a chain
+/// with one link.
+pub fn get_if_let_chain(switch: &Switch) -> Option<IfLetChain<'_>> {
+    let (arm, mut else_branch) = get_if_let(switch)?;
+    let mut arms = vec![arm];
+    while let RawStatement::Switch(switch) = &else_branch.content {
+        match get_if_let(switch) {
+            Some((arm, otherwise)) => {
+                arms.push(arm);
+                else_branch = otherwise;
+            }
+            None => break,
+        }
+    }
+    Some(IfLetChain { arms, else_branch })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::*;
+    use crate::if_let_chains::*;
+    use crate::llbc_ast::{
+        chain_statements, FunDecls, FunDeclsFormatter, GlobalDecls, GlobalDeclsFormatter,
+    };
+    use crate::meta::Meta;
+    use crate::types::*;
+    use crate::ullbc_ast::GAstFormatter;
+    use crate::values::*;
+
+    /// `dest := move (scrutinee as variant @variant_id).0; nop`
+    fn mk_arm(scrutinee: &Place, variant_id: VariantId::Id, dest: VarId::Id) -> Statement {
+        let mut downcast = scrutinee.clone();
+        downcast.projection.push_back(ProjectionElem::Field(
+            FieldProjKind::Option(variant_id),
+            FieldId::ZERO,
+        ));
+        chain_statements(
+            vec![Statement::new(
+                Meta::dummy(),
+                RawStatement::Assign(Place::new(dest), Rvalue::Use(Operand::Move(downcast))),
+            )],
+            Statement::new(Meta::dummy(), RawStatement::Nop),
+        )
+    }
+
+    fn mk_match(
+        scrutinee: &Place,
+        variant_id: VariantId::Id,
+        arm: Statement,
+        otherwise: Statement,
+    ) -> Statement {
+        Statement::new(
+            Meta::dummy(),
+            RawStatement::Switch(Switch::Match(
+                scrutinee.clone(),
+                vec![(vec![variant_id], arm)],
+                Box::new(otherwise),
+                None,
+            )),
+        )
+    }
+
+    #[test]
+    fn test_if_let_chain() {
+        let (x, y, z, w) = (
+            VarId::Id::new(1),
+            VarId::Id::new(2),
+            VarId::Id::new(3),
+            VarId::Id::new(4),
+        );
+        let some = VariantId::Id::new(1);
+        let (x_place, z_place) = (Place::new(x), Place::new(z));
+        // ```
+        // if let Some(y) = x { nop }
+        // else if let Some(w) = z { nop }
+        // else { return }
+        // ```
+        let else_branch = Statement::new(Meta::dummy(), RawStatement::Return);
+        let inner = mk_match(&z_place, some, mk_arm(&z_place, some, w), else_branch);
+        let outer = mk_match(&x_place, some, mk_arm(&x_place, some, y), inner);
+        let chain = get_if_let_chain(outer.content.as_switch()).unwrap();
+
+        assert!(chain.arms.len() == 2);
+        assert!(*chain.arms[0].value == x_place);
+        assert!(chain.arms[0].pattern_bindings == (some, vec![(FieldId::ZERO, y)]));
+        assert!(*chain.arms[1].value == z_place);
+        // The bindings of the second link don't leak into the first one
+        assert!(chain.arms[1].pattern_bindings == (some, vec![(FieldId::ZERO, w)]));
+        assert!(chain.else_branch.content.is_return());
+
+        // The chain is printed flattened
+        let (type_decls, funs, globals) = (TypeDecls::new(), FunDecls::new(), GlobalDecls::new());
+        let fun_ctx = FunDeclsFormatter::new(&funs);
+        let global_ctx = GlobalDeclsFormatter::new(&globals);
+        let ctx = GAstFormatter::new(&type_decls, &fun_ctx, &global_ctx, None, None);
+        let expected = "\
+if let @1 { 0: var@2 } = var@1 {
+    var@2 := move ((var@1).0)
+    nop
+}
+else if let @1 { 0: var@4 } = var@3 {
+    var@4 := move ((var@3).0)
+    nop
+}
+else {
+    return
+}";
+        assert!(outer.fmt_with_ctx("", &ctx) == expected);
+    }
+}
@@ -0,0 +1,397 @@
+//! Incremental extraction (see the `--incremental` option).
+//!
+//! We remember, next to the generated file, a hash of every function of the
+//! crate (`{crate}.cache.json`). When we extract the crate again, we only
+//! translate the bodies of the functions whose hashes changed: the other
+//! function declarations are loaded from the previous output.
+//!
+//! The hashes are content-addressed: the hash of a declaration combines the
+//! hash of its MIR (or of its definition, for the types) with the hashes of
+//! the declarations it depends on. Changing a function thus invalidates the
+//! functions which (transitively) call it, which is necessary because their
+//! translations may depend on it (for instance, on its signature).
+//!
+//! The translated declarations refer to the other declarations (and to the
+//! files, for the spans) through their identifiers: we discard the whole
+//! cache if the identifiers changed, that is if declarations were added,
+//! removed or reordered. We also discard it if the options or the version of
+//! Charon changed.
+//!
+//! We only use the cache when generating LLBC, and not when inlining
+//! functions: the inlining pass reads the bodies of the callees, that we
+//! don't translate for the reused functions.
+
+use crate::cli_options::{CliOpts, Emit};
+use crate::common::*;
+use crate::export;
+use crate::get_mir::{get_mir_for_def_id_and_level, MirLevel};
+use crate::llbc_ast;
+use crate::names_utils::function_def_id_to_name;
+use crate::register::{DeclKind, Declaration, RegisteredDeclarations};
+use crate::reorder_decls as rd;
+use crate::rust_to_local_ids::OrderedDecls;
+use crate::tool_attributes;
+use crate::ullbc_ast::FunDeclId;
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::{TyCtxt, TyKind};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+pub const CACHE_FILE_EXTENSION: &str = "cache.json";
+
+/// The content of the cache file
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cache {
+    /// The hash of the options and of the version of Charon
+    pub fingerprint: u64,
+    /// The paths of the declarations and the files, in the order of their
+    /// identifiers
+    pub layout: Vec<String>,
+    /// The hash of the file generated with this cache: we discard the cache
+    /// if the file was regenerated without it
+    pub output_hash: u64,
+    /// The hashes of the functions, indexed by their paths. We use an ordered
+    /// map, so that the cache files of two runs can be compared.
+    pub functions: BTreeMap<String, u64>,
+}
+
+fn hash_of<T: Hash + ?Sized>(x: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    x.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Cache {
+    /// The functions of `current` we can reuse from the run which generated
+    /// `self`.
+    pub fn reusable(&self, current: &Cache) -> HashSet<String> {
+        if self.fingerprint != current.fingerprint || self.layout != current.layout {
+            return HashSet::new();
+        }
+        current
+            .functions
+            .iter()
+            .filter(|(path, hash)| self.functions.get(*path) == Some(*hash))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    fn load(path: &Path) -> Option<Cache> {
+        let content = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+}
+
+/// The hash of a declaration, without its dependencies
+fn own_hash(tcx: TyCtxt, mir_level: MirLevel, decl: &Declaration) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    tcx.def_path_str(decl.id).hash(&mut hasher);
+    // The spans appear in the translated declarations
+    format!("{:?}", tcx.def_span(decl.id)).hash(&mut hasher);
+    if !decl.id.is_local() {
+        // The external declarations can only change with their crates
+        tcx.crate_hash(decl.id.krate).to_string().hash(&mut hasher);
+        return hasher.finish();
+    }
+    match decl.kind {
+        DeclKind::Type => match tcx.type_of(decl.id).kind() {
+            TyKind::Adt(adt, _) => {
+                format!("{:?}", adt.repr()).hash(&mut hasher);
+                for variant in adt.variants() {
+                    format!("{} {:?}", variant.name, variant.discr).hash(&mut hasher);
+                    for field in &variant.fields {
+                        let ty = tcx.type_of(field.did);
+                        format!("{}: {:?}", field.name, ty).hash(&mut hasher);
+                    }
+                }
+            }
+            ty => format!("{ty:?}").hash(&mut hasher),
+        },
+        DeclKind::Fun | DeclKind::Global => {
+            if decl.kind == DeclKind::Fun && !tcx.is_closure(decl.id) {
+                format!("{:?}", tcx.fn_sig(decl.id)).hash(&mut hasher);
+            } else {
+                format!("{:?}", tcx.type_of(decl.id)).hash(&mut hasher);
+            }
+            format!("{:?}", tcx.predicates_of(decl.id)).hash(&mut hasher);
+            // The opaque declarations don't have dependencies
+            if decl.deps.is_some() {
+                let body = get_mir_for_def_id_and_level(tcx, decl.id.expect_local(), mir_level);
+                format!("{body:?}").hash(&mut hasher);
+            }
+        }
+    }
+    hasher.finish()
+}
+
+/// Compute the hashes of the registered declarations (see the module
+/// documentation). We explore the groups in the order computed by
+/// [rd::reorder_declarations], so that the dependencies of a group are
+/// hashed before it. The declarations of a recursive group share the same
+/// hash.
+pub fn compute_hashes(
+    tcx: TyCtxt,
+    mir_level: MirLevel,
+    registered: &RegisteredDeclarations,
+    groups: &rd::DeclarationsGroups<DefId, DefId, DefId>,
+) -> HashMap<DefId, u64> {
+    let mut hashes: HashMap<DefId, u64> = HashMap::new();
+    for group in &groups.decls {
+        let ids: Vec<DefId> = group
+            .get_ids()
+            .into_iter()
+            .map(|id| match id {
+                rd::AnyDeclId::Type(id) | rd::AnyDeclId::Fun(id) | rd::AnyDeclId::Global(id) => id,
+            })
+            .collect();
+        let mut hasher = DefaultHasher::new();
+        for id in &ids {
+            own_hash(tcx, mir_level, &registered[id]).hash(&mut hasher);
+        }
+        for id in &ids {
+            for dep in registered[id].deps.iter().flatten() {
+                if !ids.contains(dep) {
+                    hashes.get(dep).hash(&mut hasher);
+                }
+            }
+        }
+        let hash = hasher.finish();
+        hashes.extend(ids.into_iter().map(|id| (id, hash)));
+    }
+    hashes
+}
+
+/// The paths of the declarations and of the files, in the order of their
+/// identifiers
+fn compute_layout(tcx: TyCtxt, ordered: &OrderedDecls) -> Vec<String> {
+    fn sorted<Id: Ord + Copy>(map: &HashMap<DefId, Id>) -> Vec<DefId> {
+        let mut ids: Vec<(Id, DefId)> = map.iter().map(|(rid, id)| (*id, *rid)).collect();
+        ids.sort_by_key(|(id, _)| *id);
+        ids.into_iter().map(|(_, rid)| rid).collect()
+    }
+    let decls = [
+        ("type", sorted(&ordered.type_rid_to_id)),
+        ("fun", sorted(&ordered.fun_rid_to_id)),
+        ("global", sorted(&ordered.global_rid_to_id)),
+        ("trait", sorted(&ordered.trait_rid_to_id)),
+        ("impl", sorted(&ordered.trait_impl_rid_to_id)),
+    ];
+    let mut layout: Vec<String> = decls
+        .iter()
+        .flat_map(|(kind, rids)| {
+            rids.iter()
+                .map(move |rid| format!("{kind} {}", tcx.def_path_str(*rid)))
+        })
+        .collect();
+    layout.extend(ordered.files.iter().map(|file| format!("file {file:?}")));
+    layout
+}
+
+fn output_path(crate_name: &str, options: &CliOpts) -> PathBuf {
+    let mut path = options
+        .dest_dir
+        .as_deref()
+        .map_or_else(PathBuf::new, |d| d.to_path_buf());
+    path.push(format!(
+        "{crate_name}.{}",
+        options.output_format.extension("llbc")
+    ));
+    path
+}
+
+fn cache_path(crate_name: &str, options: &CliOpts) -> PathBuf {
+    let mut path = options
+        .dest_dir
+        .as_deref()
+        .map_or_else(PathBuf::new, |d| d.to_path_buf());
+    path.push(format!("{crate_name}.{CACHE_FILE_EXTENSION}"));
+    path
+}
+
+/// The state of an incremental extraction
+pub struct Incremental {
+    crate_name: String,
+    /// The cache of the current run
+    cache: Cache,
+    /// The functions we reuse, loaded from the previous output
+    reused: HashMap<FunDeclId::Id, llbc_ast::FunDecl>,
+}
+
+impl Incremental {
+    /// Compute the hashes of the crate and load the functions we can reuse.
+    /// Returns `None` if we can't extract the crate incrementally with the
+    /// given options. `passes` gives the names of the user passes, which are
+    /// part of the fingerprint.
+    pub fn new(
+        tcx: TyCtxt,
+        options: &CliOpts,
+        passes: &[&str],
+        crate_name: &str,
+        hashes: &HashMap<DefId, u64>,
+        ordered: &OrderedDecls,
+    ) -> Option<Incremental> {
+        if options.emit() != Emit::Llbc
+            || options.inline_threshold.is_some()
+            || options.inline_always
+            || options.monomorphize
+        {
+            log::warn!(
+                "Ignoring --incremental: it is only supported when emitting LLBC without \
+                 inlining nor monomorphizing functions"
+            );
+            return None;
+        }
+
+        // We don't reuse the functions which are not simplified: they are
+        // exported in ULLBC, which we don't load
+        let is_reusable = |rid: DefId| {
+            let name = function_def_id_to_name(tcx, rid).to_string();
+            !tool_attributes::has_attribute(tcx, rid, tool_attributes::NO_SIMPLIFY)
+                && !options.no_simplify.contains(&name)
+        };
+        let fingerprint = (
+            env!("CARGO_PKG_VERSION"),
+            serde_json::to_string(options).unwrap(),
+            passes,
+        );
+        let cache = Cache {
+            fingerprint: hash_of(&fingerprint),
+            layout: compute_layout(tcx, ordered),
+            output_hash: 0,
+            functions: ordered
+                .fun_rid_to_id
+                .keys()
+                .filter(|rid| rid.is_local() && is_reusable(**rid))
+                .filter_map(|rid| Some((tcx.def_path_str(*rid), *hashes.get(rid)?)))
+                .collect(),
+        };
+
+        let mut reused = HashMap::new();
+        let output = output_path(crate_name, options);
+        let previous = Cache::load(&cache_path(crate_name, options)).filter(|previous| {
+            std::fs::read(&output)
+                .map_or(false, |content| hash_of(&content) == previous.output_hash)
+        });
+        let reusable = match previous {
+            Some(previous) => previous.reusable(&cache),
+            None => {
+                info!(
+                    "No valid cache for the crate {}: translating everything",
+                    crate_name
+                );
+                HashSet::new()
+            }
+        };
+        if !reusable.is_empty() {
+            match export::LlbcCrate::import(&output, options.output_format) {
+                std::result::Result::Ok((_, krate)) => {
+                    for (rid, id) in &ordered.fun_rid_to_id {
+                        if reusable.contains(&tcx.def_path_str(*rid)) {
+                            if let Some(decl) = krate.functions.get(*id) {
+                                reused.insert(*id, decl.clone());
+                            }
+                        }
+                    }
+                }
+                std::result::Result::Err(()) => {
+                    log::warn!("Could not load the previous output: translating everything");
+                }
+            }
+        }
+        info!(
+            "Reusing {} of the {} functions of the crate",
+            reused.len(),
+            cache.functions.len()
+        );
+        Some(Incremental {
+            crate_name: crate_name.to_string(),
+            cache,
+            reused,
+        })
+    }
+
+    /// The functions we don't need to translate
+    pub fn reused_ids(&self) -> HashSet<FunDeclId::Id> {
+        self.reused.keys().copied().collect()
+    }
+
+    /// Replace the reused functions with their declarations from the previous
+    /// output. We translate them as opaque functions: this must be done after
+    /// the micro-passes.
+    pub fn restore(&self, funs: &mut llbc_ast::FunDecls) {
+        for (id, decl) in &self.reused {
+            *funs.get_mut(*id).unwrap() = decl.clone();
+        }
+    }
+
+    /// Save the cache, once the crate has been exported
+    pub fn save(mut self, options: &CliOpts) -> Result<()> {
+        let output = output_path(&self.crate_name, options);
+        let content = match std::fs::read(&output) {
+            std::result::Result::Ok(content) => content,
+            std::result::Result::Err(_) => {
+                error!("Could not read the generated file: {:?}", output);
+                return Err(());
+            }
+        };
+        self.cache.output_hash = hash_of(&content);
+        let path = cache_path(&self.crate_name, options);
+        match std::fs::write(&path, serde_json::to_string(&self.cache).unwrap()) {
+            std::result::Result::Ok(()) => {
+                trace!("Saved the cache: {:?}", path);
+                Ok(())
+            }
+            std::result::Result::Err(err) => {
+                error!("Could not write to {:?}: {}", path, err);
+                Err(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::incremental::*;
+    use std::iter::FromIterator;
+
+    #[test]
+    fn test_reusable() {
+        let mk_cache = |layout: &[&str], functions: &[(&str, u64)]| Cache {
+            fingerprint: 1,
+            layout: layout.iter().map(|s| s.to_string()).collect(),
+            output_hash: 0,
+            functions: functions.iter().map(|(f, h)| (f.to_string(), *h)).collect(),
+        };
+        let layout = ["type Node", "fun f", "fun g"];
+        let previous = mk_cache(&layout, &[("f", 1), ("g", 2)]);
+        let current = mk_cache(&layout, &[("f", 1), ("g", 3)]);
+        assert!(previous.reusable(&current) == HashSet::from_iter(vec!["f".to_string()]));
+
+        // We discard the cache if the identifiers changed
+        let current = mk_cache(&["type Node", "fun f", "fun h", "fun g"], &[("f", 1)]);
+        assert!(previous.reusable(&current).is_empty());
+        // Or if the options changed
+        let current = Cache {
+            fingerprint: 2,
+            ..mk_cache(&layout, &[("f", 1), ("g", 2)])
+        };
+        assert!(previous.reusable(&current).is_empty());
+    }
+
+    #[test]
+    fn test_cache_serialization() {
+        let cache = Cache {
+            fingerprint: 1,
+            layout: vec!["fun f".to_string()],
+            output_hash: 2,
+            functions: BTreeMap::from_iter(vec![("g".to_string(), 4), ("f".to_string(), 3)]),
+        };
+        let json = serde_json::to_string(&cache).unwrap();
+        assert!(serde_json::from_str::<Cache>(&json).unwrap() == cache);
+        // The functions are written in a deterministic order
+        assert!(json.contains(r#""functions":{"f":3,"g":4}"#));
+    }
+}
@@ -0,0 +1,428 @@
+//! Normalize the indexing operations (`v[i]`) into bounds-checked index
+//! projections.
+//!
+//! Depending on the type of the indexed value, rustc generates different code.
+//! For arrays and slices, the MIR directly uses an index projection, guarded
+//! by a bounds check:
+//! ```text
+//! len := len(*s);
+//! b := copy i < move len;
+//! assert(move b == true); // bounds check
+//! x := copy (*s)[i];
+//! ```
+//! For vectors, the indexing goes through a call to the `Index` (or
+//! `IndexMut`) trait method:
+//! ```text
+//! r := &v;
+//! tmp := alloc::vec::Vec::index(move r, copy i);
+//! x := copy *tmp;
+//! ```
+//! We rewrite the second form so that it has the same shape as the first one,
+//! and backends only have to handle one kind of indexing:
+//! ```text
+//! r := &v;
+//! len := len(*r);
+//! b := copy i < move len;
+//! assert(move b == true); // bounds check
+//! tmp := &(*r)[i];
+//! x := copy *tmp;
+//! ```
+//!
+//! The indexings with a range (`&v[1..3]`) are reconstructed beforehand (see
+//! [crate::slice_accesses]): we reject the remaining indexings whose index
+//! is not a `usize`.
+
+use crate::assumed;
+use crate::errors::{TransResult, TranslationError};
+use crate::expressions::*;
+use crate::gast_utils::par_try_iter_bodies;
+use crate::llbc_ast::{
+    chain_statements, transform_statements, visit_statements, Assert, AssertKind, Call, CtxNames,
+    ExprBody, FunDecls, GlobalDecls, RawStatement, Statement,
+};
+use crate::meta::Meta;
+use crate::passes::PassResult;
+use crate::types::*;
+use crate::ullbc_ast::{make_locals_generator, AssumedFunId, FunId};
+use crate::values::*;
+use take_mut::take;
+
+/// If the call is an indexing of a vector, return the borrow kind of the
+/// resulting reference.
+pub fn get_vec_index_borrow_kind(call: &Call) -> Option<BorrowKind> {
+    match &call.func {
+        FunId::Assumed(AssumedFunId::VecIndex) => Some(BorrowKind::Shared),
+        FunId::Assumed(AssumedFunId::VecIndexMut) => Some(BorrowKind::Mut),
+        _ => None,
+    }
+}
+
+/// Check that the calls to `Vec::index` and `Vec::index_mut` are given a
+/// `usize` index, which is the only kind of index the index projections
+/// support.
+fn check_index_types(type_defs: &TypeDecls, body: &ExprBody) -> TransResult<()> {
+    let mut error = None;
+    visit_statements(
+        &mut |st| {
+            let call = match &st.content {
+                RawStatement::Call(call) if get_vec_index_borrow_kind(call).is_some() => call,
+                _ => return,
+            };
+            let index_ty = match &call.args[1] {
+                Operand::Move(p) | Operand::Copy(p) => body.place_type(type_defs, p),
+                Operand::Const(ty, _) => ty.clone(),
+            };
+            let msg = match &index_ty {
+                Ty::Integer(IntegerTy::Usize) => return,
+                Ty::Adt(TypeId::Adt(id), _, _)
+                    if assumed::is_range_name(&type_defs.get_type_def(*id).unwrap().name) =>
+                {
+                    "unsupported slicing operation: the range must be built right before the indexing"
+                }
+                _ => "unsupported indexing: the index must be a `usize`",
+            };
+            error.get_or_insert(TranslationError::new(st.meta, msg));
+        },
+        &body.body,
+    );
+    match error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+/// Transform a call to `Vec::index` or `Vec::index_mut` into a bounds-checked
+/// index projection.
+fn transform_call<F: FnMut(ETy) -> VarId::Id>(
+    make_new_var: &mut F,
+    meta: Meta,
+    mut call: Call,
+    borrow_kind: BorrowKind,
+) -> Statement {
+    assert!(call.args.len() == 2);
+    let index = call.args.pop().unwrap();
+    let vec_ref = call.args.pop().unwrap();
+    let mut vec = match vec_ref {
+        Operand::Move(p) | Operand::Copy(p) => p,
+        Operand::Const(_, _) => unreachable!(),
+    };
+    vec.projection.push_back(ProjectionElem::Deref);
+
+    // The index projection requires the index to be a local variable
+    let mut sts = Vec::new();
+    let index_var = match &index {
+        Operand::Move(p) | Operand::Copy(p) if p.projection.is_empty() => p.var_id,
+        _ => {
+            let var_id = make_new_var(Ty::Integer(IntegerTy::Usize));
+            sts.push(RawStatement::Assign(Place::new(var_id), Rvalue::Use(index)));
+            var_id
+        }
+    };
+
+    // Introduce the bounds check
+    let len = make_new_var(Ty::Integer(IntegerTy::Usize));
+    let cond = make_new_var(Ty::Bool);
+    sts.push(RawStatement::Assign(
+        Place::new(len),
+        Rvalue::Len(vec.clone()),
+    ));
+    sts.push(RawStatement::Assign(
+        Place::new(cond),
+        Rvalue::BinaryOp(
+            BinOp::Lt,
+            Operand::Copy(Place::new(index_var)),
+            Operand::Move(Place::new(len)),
+        ),
+    ));
+    sts.push(RawStatement::Assert(Assert {
+        cond: Operand::Move(Place::new(cond)),
+        expected: true,
+        kind: AssertKind::BoundsCheck,
+    }));
+
+    // Borrow the indexed element
+    let mut elem = vec;
+    elem.projection.push_back(ProjectionElem::Index(index_var));
+    let borrow = RawStatement::Assign(call.dest, Rvalue::Ref(elem, borrow_kind));
+
+    let sts = sts.into_iter().map(|st| Statement::new(meta, st)).collect();
+    chain_statements(sts, Statement::new(meta, borrow))
+}
+
+fn transform_st<F: FnMut(ETy) -> VarId::Id>(make_new_var: &mut F, st: Statement) -> Statement {
+    match st.content {
+        RawStatement::Call(call) => match get_vec_index_borrow_kind(&call) {
+            Some(borrow_kind) => transform_call(make_new_var, st.meta, call, borrow_kind),
+            None => Statement::new(st.meta, RawStatement::Call(call)),
+        },
+        content => Statement::new(st.meta, content),
+    }
+}
+
+/// `fmt_ctx` is used for pretty-printing purposes.
+pub fn transform(
+    fmt_ctx: &CtxNames<'_>,
+    type_defs: &TypeDecls,
+    funs: &mut FunDecls,
+    globals: &mut GlobalDecls,
+) -> PassResult {
+    par_try_iter_bodies(funs, globals, |name, b| {
+        trace!(
+            "# About to normalize the index accesses in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        check_index_types(type_defs, b)?;
+        let mut make_new_var = make_locals_generator(&mut b.locals);
+        take(&mut b.body, |st| {
+            transform_statements(&mut |st| transform_st(&mut make_new_var, st), st)
+        });
+        Ok(())
+    })
+    .into_result()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index_accesses::*;
+    use crate::llbc_ast::ExprBody;
+    use crate::test_utils::*;
+
+    fn mk_ref(ty: ETy) -> ETy {
+        Ty::Ref(ErasedRegion::Erased, Box::new(ty), RefKind::Shared)
+    }
+
+    fn deref(var_id: VarId::Id) -> Place {
+        let mut p = Place::new(var_id);
+        p.projection.push_back(ProjectionElem::Deref);
+        p
+    }
+
+    fn transform_body(body: &mut ExprBody) {
+        let mut make_new_var = make_locals_generator(&mut body.locals);
+        take(&mut body.body, |st| {
+            transform_statements(&mut |st| transform_st(&mut make_new_var, st), st)
+        });
+    }
+
+    /// Check that the statements are of the shape:
+    /// ```text
+    /// len := len(*v);
+    /// b := copy i < move len;
+    /// assert(move b == true);
+    /// ... (*v)[i] ...
+    /// ```
+    fn is_bounds_checked_access(sts: &[&RawStatement], v: VarId::Id, i: VarId::Id) -> bool {
+        let len = match sts[0] {
+            RawStatement::Assign(len, Rvalue::Len(p)) if *p == deref(v) => len,
+            _ => return false,
+        };
+        let b = match sts[1] {
+            RawStatement::Assign(
+                b,
+                Rvalue::BinaryOp(BinOp::Lt, Operand::Copy(i1), Operand::Move(len1)),
+            ) if *i1 == Place::new(i) && len1 == len => b,
+            _ => return false,
+        };
+        let mut elem = deref(v);
+        elem.projection.push_back(ProjectionElem::Index(i));
+        let accessed = match sts[3] {
+            RawStatement::Assign(_, Rvalue::Use(Operand::Copy(p)))
+            | RawStatement::Assign(_, Rvalue::Ref(p, _)) => p,
+            _ => return false,
+        };
+        matches!(sts[2], RawStatement::Assert(Assert { cond: Operand::Move(b1), expected: true, kind: AssertKind::BoundsCheck }) if b1 == b)
+            && *accessed == elem
+    }
+
+    #[test]
+    fn test_slice_index() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let usize_ty = Ty::Integer(IntegerTy::Usize);
+        let x = VarId::Id::new(0);
+        let s = VarId::Id::new(1);
+        let i = VarId::Id::new(2);
+        let len = VarId::Id::new(3);
+        let b = VarId::Id::new(4);
+        let mut elem = deref(s);
+        elem.projection.push_back(ProjectionElem::Index(i));
+        // `let x = s[i];` with `s: &[u32]`:
+        // ```
+        // len := len(*s);
+        // b := copy i < move len;
+        // assert(move b == true);
+        // x := copy (*s)[i];
+        // return
+        // ```
+        let mut body = mk_body(
+            2,
+            vec![
+                mk_var(0, None, u32_ty.clone()),
+                mk_var(1, None, mk_ref(Ty::Slice(Box::new(u32_ty.clone())))),
+                mk_var(2, None, usize_ty.clone()),
+                mk_var(3, None, usize_ty),
+                mk_var(4, None, Ty::Bool),
+            ],
+            vec![
+                RawStatement::Assign(Place::new(len), Rvalue::Len(deref(s))),
+                RawStatement::Assign(
+                    Place::new(b),
+                    Rvalue::BinaryOp(
+                        BinOp::Lt,
+                        Operand::Copy(Place::new(i)),
+                        Operand::Move(Place::new(len)),
+                    ),
+                ),
+                RawStatement::Assert(Assert {
+                    cond: Operand::Move(Place::new(b)),
+                    expected: true,
+                    kind: AssertKind::BoundsCheck,
+                }),
+                RawStatement::Assign(Place::new(x), Rvalue::Use(Operand::Copy(elem.clone()))),
+                RawStatement::Return,
+            ],
+        );
+        let type_defs = TypeDecls::new();
+        assert!(body.place_type(&type_defs, &elem) == u32_ty);
+
+        // The access already has the proper shape
+        transform_body(&mut body);
+        let sts = flatten(&body.body);
+        assert!(sts.len() == 5);
+        assert!(is_bounds_checked_access(&sts, s, i));
+    }
+
+    #[test]
+    fn test_vec_index() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let vec_ty = Ty::Adt(
+            TypeId::Assumed(AssumedTy::Vec),
+            im::Vector::new(),
+            im::vector![u32_ty.clone()],
+        );
+        let x = VarId::Id::new(0);
+        let r = VarId::Id::new(1);
+        let i = VarId::Id::new(2);
+        let tmp = VarId::Id::new(3);
+        // `let x = v[i];` with `v: Vec<u32>`:
+        // ```
+        // tmp := alloc::vec::Vec::index(move r, copy i); // r := &v
+        // x := copy *tmp;
+        // return
+        // ```
+        let mut body = mk_body(
+            2,
+            vec![
+                mk_var(0, None, u32_ty.clone()),
+                mk_var(1, None, mk_ref(vec_ty)),
+                mk_var(2, None, Ty::Integer(IntegerTy::Usize)),
+                mk_var(3, None, mk_ref(u32_ty.clone())),
+            ],
+            vec![
+                RawStatement::Call(Call {
+                    func: FunId::Assumed(AssumedFunId::VecIndex),
+                    region_args: vec![ErasedRegion::Erased],
+                    type_args: vec![u32_ty.clone()],
+                    args: vec![Operand::Move(Place::new(r)), Operand::Copy(Place::new(i))],
+                    dest: Place::new(tmp),
+                    sig: None,
+                }),
+                RawStatement::Assign(Place::new(x), Rvalue::Use(Operand::Copy(deref(tmp)))),
+                RawStatement::Return,
+            ],
+        );
+        assert!(check_index_types(&TypeDecls::new(), &body).is_ok());
+        transform_body(&mut body);
+
+        // ```
+        // len := len(*r);
+        // b := copy i < move len;
+        // assert(move b == true);
+        // tmp := &(*r)[i];
+        // x := copy *tmp;
+        // return
+        // ```
+        let sts = flatten(&body.body);
+        assert!(sts.len() == 6);
+        assert!(is_bounds_checked_access(&sts, r, i));
+        assert!(
+            matches!(sts[3], RawStatement::Assign(p, Rvalue::Ref(_, BorrowKind::Shared)) if *p == Place::new(tmp))
+        );
+        let mut elem = deref(r);
+        elem.projection.push_back(ProjectionElem::Index(i));
+        assert!(body.place_type(&TypeDecls::new(), &elem) == u32_ty);
+    }
+
+    #[test]
+    fn test_vec_index_with_range() {
+        use crate::meta::AttrInfo;
+        use crate::regions_hierarchy::RegionGroups;
+
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let vec_ty = Ty::Adt(
+            TypeId::Assumed(AssumedTy::Vec),
+            im::Vector::new(),
+            im::vector![u32_ty.clone()],
+        );
+        let range_id = TypeDeclId::ZERO;
+        let range_decl = TypeDecl {
+            def_id: range_id,
+            meta: Meta::dummy(),
+            name: TypeName::from(
+                assumed::RANGE_NAME
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<String>>(),
+            ),
+            region_params: RegionVarId::Vector::new(),
+            type_params: TypeVarId::Vector::new(),
+            const_generic_params: ConstGenericVarId::Vector::new(),
+            trait_clauses: Vec::new(),
+            regions_hierarchy: RegionGroups::new(),
+            kind: TypeDeclKind::Opaque,
+            repr_packed: None,
+            under_aligned_fields: Vec::new(),
+            recursive_fields: Vec::new(),
+            attr_info: AttrInfo::default(),
+        };
+        let type_defs = TypeDecls {
+            types: TypeDeclId::Vector::from(vec![range_decl]),
+        };
+        let range_ty = Ty::Adt(
+            TypeId::Adt(range_id),
+            im::Vector::new(),
+            im::vector![Ty::Integer(IntegerTy::Usize)],
+        );
+        let r = VarId::Id::new(1);
+        let range = VarId::Id::new(2);
+        // `let s = &v[range];` with `v: Vec<u32>`, where the range is not
+        // built right before the indexing:
+        // ```
+        // s := alloc::vec::Vec::index(move r, move range); // r := &v
+        // return
+        // ```
+        let body = mk_body(
+            2,
+            vec![
+                mk_var(0, None, mk_ref(Ty::Slice(Box::new(u32_ty.clone())))),
+                mk_var(1, None, mk_ref(vec_ty)),
+                mk_var(2, None, range_ty),
+            ],
+            vec![
+                RawStatement::Call(Call {
+                    func: FunId::Assumed(AssumedFunId::VecIndex),
+                    region_args: vec![ErasedRegion::Erased],
+                    type_args: vec![u32_ty],
+                    args: vec![
+                        Operand::Move(Place::new(r)),
+                        Operand::Move(Place::new(range)),
+                    ],
+                    dest: Place::new(VarId::ZERO),
+                    sig: None,
+                }),
+                RawStatement::Return,
+            ],
+        );
+        // We can't turn this call into an index projection
+        assert!(check_index_types(&type_defs, &body).is_err());
+    }
+}
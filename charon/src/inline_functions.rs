@@ -0,0 +1,463 @@
+//! Inline the calls to the small functions, for the backends which benefit
+//! from having fewer function boundaries (for instance, the verification of
+//! code with many one-line accessors or wrappers).
+//!
+//! We only inline the functions whose bodies are a straight sequence of
+//! simple statements (assignments, asserts, calls, etc.) followed by a
+//! return: inlining them doesn't require introducing control-flow. We inline
+//! the functions which contain at most a given number of statements, and the
+//! functions marked `#[inline(always)]` if the user asks for it. For
+//! instance:
+//! ```text
+//! fn add_one(x: u32) -> u32 {
+//!   ret := copy x + const 1;
+//!   return;
+//! }
+//!
+//! y := add_one(copy z);
+//! ```
+//! becomes:
+//! ```text
+//! x' := copy z;
+//! ret' := copy x' + const 1;
+//! y := move ret';
+//! ```
+//! where `x'` and `ret'` are fresh locals of the caller. The inlined
+//! statements keep track of where they come from (see
+//! [Meta::generated_from_span]).
+//!
+//! The functions we inline may themselves call functions we inline (this is
+//! typically the case of the wrappers): we inline the callees first. We
+//! don't inline the (mutually) recursive functions.
+//!
+//! We only inline the direct calls to functions of the crate: the calls
+//! through function pointers or trait objects are not resolved. We also
+//! leave the generic functions untouched, as this would require substituting
+//! the types inside their bodies.
+
+use crate::expressions::*;
+use crate::llbc_ast::{
+    chain_statements, transform_statements, Call, CtxNames, ExprBody, FunDecl, FunDecls,
+    GlobalDecls, RawStatement, Statement,
+};
+use crate::meta::Meta;
+use crate::types::*;
+use crate::ullbc_ast::{
+    iter_function_bodies, iter_global_bodies, make_locals_generator, FunDeclId, FunId,
+};
+use crate::values::*;
+use petgraph::algo::tarjan_scc;
+use petgraph::graphmap::DiGraphMap;
+use std::collections::HashMap;
+use take_mut::take;
+
+/// Return true if the statement doesn't contain any control-flow
+fn is_simple_statement(st: &Statement) -> bool {
+    match &st.content {
+        RawStatement::Assign(_, _)
+        | RawStatement::CompoundAssign(_, _, _)
+        | RawStatement::FakeRead(_)
+        | RawStatement::SetDiscriminant(_, _)
+        | RawStatement::Drop(_)
+        | RawStatement::Assert(_)
+        | RawStatement::Call(_)
+        | RawStatement::Nop => true,
+        RawStatement::Panic
+        | RawStatement::Return
+        | RawStatement::Break(_)
+        | RawStatement::Continue(_)
+        | RawStatement::Sequence(_, _)
+        | RawStatement::Switch(_)
+        | RawStatement::Loop(_, _)
+        | RawStatement::While(_, _, _, _) => false,
+    }
+}
+
+/// If the body is a sequence of simple statements followed by a return,
+/// return those statements (without the return).
+fn get_straight_line_statements(body: &Statement) -> Option<Vec<&Statement>> {
+    let mut sts = Vec::new();
+    let mut st = body;
+    loop {
+        match &st.content {
+            RawStatement::Sequence(st1, st2) => {
+                if !is_simple_statement(st1) {
+                    return None;
+                }
+                sts.push(&**st1);
+                st = st2;
+            }
+            RawStatement::Return => return Some(sts),
+            _ => return None,
+        }
+    }
+}
+
+/// Return true if the function is marked `#[inline(always)]`
+fn is_inline_always(decl: &FunDecl) -> bool {
+    decl.attr_info.attributes.iter().any(|attr| {
+        attr.path == ["inline"] && attr.args.as_deref().map(str::trim) == Some("always")
+    })
+}
+
+/// Return true if we may inline the calls to the function: it must be a
+/// non-generic function whose body is straight-line code, and which either
+/// contains at most `threshold` statements or is marked `#[inline(always)]`
+/// (if `inline_always`).
+fn is_candidate(decl: &FunDecl, threshold: Option<usize>, inline_always: bool) -> bool {
+    let sts = match &decl.body {
+        Some(body) if decl.signature.type_params.is_empty() => {
+            get_straight_line_statements(&body.body)
+        }
+        _ => None,
+    };
+    match sts {
+        None => false,
+        Some(sts) => {
+            matches!(threshold, Some(threshold) if sts.len() <= threshold)
+                || (inline_always && is_inline_always(decl))
+        }
+    }
+}
+
+/// The functions of the crate called by a straight-line body
+fn get_callees(body: &ExprBody) -> Vec<FunDeclId::Id> {
+    get_straight_line_statements(&body.body)
+        .unwrap()
+        .into_iter()
+        .filter_map(|st| match &st.content {
+            RawStatement::Call(Call {
+                func: FunId::Regular(id) | FunId::TraitMethod(_, id),
+                ..
+            }) => Some(*id),
+            _ => None,
+        })
+        .collect()
+}
+
+fn rename_place(locals: &HashMap<VarId::Id, VarId::Id>, p: &mut Place) {
+    p.var_id = *locals.get(&p.var_id).unwrap();
+    for pe in p.projection.iter_mut() {
+        if let ProjectionElem::Index(var_id) = pe {
+            *var_id = *locals.get(var_id).unwrap();
+        }
+    }
+}
+
+/// Substitute the body of the callee at the call site: we introduce fresh
+/// locals for the locals of the callee, bind the arguments, and assign the
+/// return value to the destination of the call.
+///
+/// The inlined statements are located at the call, and generated from the
+/// statements of the callee.
+fn substitute_body<F: FnMut(ETy) -> VarId::Id>(
+    make_new_var: &mut F,
+    meta: Meta,
+    callee: &ExprBody,
+    call: Call,
+) -> Statement {
+    let locals: HashMap<VarId::Id, VarId::Id> = callee
+        .locals
+        .iter()
+        .map(|v| (v.index, make_new_var(v.ty.clone())))
+        .collect();
+
+    // Bind the arguments (they come right after the return value)
+    assert!(call.args.len() == callee.arg_count);
+    let mut sts = Vec::new();
+    for (i, arg) in call.args.into_iter().enumerate() {
+        let var = *locals.get(&VarId::Id::new(i + 1)).unwrap();
+        let st = RawStatement::Assign(Place::new(var), Rvalue::Use(arg));
+        sts.push(Statement::new(meta, st));
+    }
+
+    // Copy the body
+    for st in get_straight_line_statements(&callee.body).unwrap() {
+        let mut st = st.clone();
+        st.transform_shallow_places(&mut |p| rename_place(&locals, p));
+        let inlined_meta = Meta {
+            span: meta.span,
+            generated_from_span: Some(st.meta.generated_from_span.unwrap_or(st.meta.span)),
+        };
+        sts.push(Statement::new(inlined_meta, st.content));
+    }
+
+    // Retrieve the return value
+    let ret = Place::new(*locals.get(&callee.return_local).unwrap());
+    let assign_dest = RawStatement::Assign(call.dest, Rvalue::Use(Operand::Move(ret)));
+    chain_statements(sts, Statement::new(meta, assign_dest))
+}
+
+fn transform_st<F: FnMut(ETy) -> VarId::Id>(
+    inlinable: &HashMap<FunDeclId::Id, ExprBody>,
+    make_new_var: &mut F,
+    st: Statement,
+) -> Statement {
+    match st.content {
+        RawStatement::Call(call) => {
+            let callee = match &call.func {
+                FunId::Regular(id) | FunId::TraitMethod(_, id) => inlinable.get(id),
+                // We can't inline the calls dispatched at runtime
+                FunId::Virtual(_, _) | FunId::Indirect(_) | FunId::Assumed(_) => None,
+            };
+            match callee {
+                Some(callee) => substitute_body(make_new_var, st.meta, callee, call),
+                None => Statement::new(st.meta, RawStatement::Call(call)),
+            }
+        }
+        content => Statement::new(st.meta, content),
+    }
+}
+
+fn inline_calls(inlinable: &HashMap<FunDeclId::Id, ExprBody>, b: &mut ExprBody) {
+    let mut make_new_var = make_locals_generator(&mut b.locals);
+    take(&mut b.body, |st| {
+        transform_statements(&mut |st| transform_st(inlinable, &mut make_new_var, st), st)
+    });
+}
+
+/// Compute the bodies of the functions we inline, in which we already
+/// inlined the calls to the other functions we inline.
+fn compute_inlinable(
+    funs: &FunDecls,
+    threshold: Option<usize>,
+    inline_always: bool,
+) -> HashMap<FunDeclId::Id, ExprBody> {
+    let candidates: HashMap<FunDeclId::Id, &ExprBody> = funs
+        .iter()
+        .filter(|d| is_candidate(d, threshold, inline_always))
+        .map(|d| (d.def_id, d.body.as_ref().unwrap()))
+        .collect();
+
+    // The call graph between the candidates
+    let mut graph = DiGraphMap::<FunDeclId::Id, ()>::new();
+    for d in funs.iter() {
+        if let Some(body) = candidates.get(&d.def_id) {
+            graph.add_node(d.def_id);
+            for callee in get_callees(body) {
+                if candidates.contains_key(&callee) {
+                    graph.add_edge(d.def_id, callee, ());
+                }
+            }
+        }
+    }
+
+    // The SCCs are listed in reverse topological order: we explore the
+    // callees before their callers
+    let mut inlinable = HashMap::new();
+    for scc in tarjan_scc(&graph) {
+        let id = scc[0];
+        let is_recursive = scc.len() > 1 || graph.contains_edge(id, id);
+        if !is_recursive {
+            let mut body = (*candidates.get(&id).unwrap()).clone();
+            inline_calls(&inlinable, &mut body);
+            inlinable.insert(id, body);
+        }
+    }
+    inlinable
+}
+
+/// `threshold`: the maximal number of statements of the functions we inline.
+/// `inline_always`: also inline the functions marked `#[inline(always)]`,
+/// whatever their size.
+///
+/// `fmt_ctx` is used for pretty-printing purposes.
+pub fn transform(
+    fmt_ctx: &CtxNames<'_>,
+    threshold: Option<usize>,
+    inline_always: bool,
+    funs: &mut FunDecls,
+    globals: &mut GlobalDecls,
+) {
+    let inlinable = compute_inlinable(funs, threshold, inline_always);
+
+    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+        trace!(
+            "# About to inline the small functions in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        inline_calls(&inlinable, b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::id_vector::ToUsize;
+    use crate::inline_functions::*;
+    use crate::meta::Attribute;
+    use crate::test_utils::*;
+
+    /// A function `fn name(x: u32) -> u32 { sts; return }`
+    fn mk_u32_fun(def_id: FunDeclId::Id, name: &str, mut sts: Vec<RawStatement>) -> FunDecl {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let locals = vec![mk_var(0, None, u32_ty.clone()), mk_var(1, None, u32_ty)];
+        sts.push(RawStatement::Return);
+        let sig = mk_sig(
+            vec![Ty::Integer(IntegerTy::U32)],
+            Ty::Integer(IntegerTy::U32),
+        );
+        mk_fun_decl(
+            def_id.to_usize(),
+            &[name],
+            sig,
+            Some(mk_body(1, locals, sts)),
+        )
+    }
+
+    /// `dest := func(copy arg)`
+    fn call(func: FunDeclId::Id, arg: VarId::Id, dest: VarId::Id) -> RawStatement {
+        RawStatement::Call(mk_call(
+            FunId::Regular(func),
+            vec![Operand::Copy(Place::new(arg))],
+            Place::new(dest),
+        ))
+    }
+
+    #[test]
+    fn test_inline_add_one() {
+        let one = Operand::Const(
+            Ty::Integer(IntegerTy::U32),
+            OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(ScalarValue::U32(1))),
+        );
+        // ```
+        // fn add_one(x: u32) -> u32 {
+        //   ret := copy x + const 1;
+        //   return;
+        // }
+        // ```
+        let add_one_id = FunDeclId::Id::new(0);
+        let ret = VarId::Id::new(0);
+        let x = VarId::Id::new(1);
+        let add_one = mk_u32_fun(
+            add_one_id,
+            "add_one",
+            vec![RawStatement::Assign(
+                Place::new(ret),
+                Rvalue::BinaryOp(BinOp::Add, Operand::Copy(Place::new(x)), one.clone()),
+            )],
+        );
+        // ```
+        // fn f(y: u32) -> u32 {
+        //   ret := add_one(copy y);
+        //   return;
+        // }
+        // ```
+        let y = VarId::Id::new(1);
+        let f = mk_u32_fun(FunDeclId::Id::new(1), "f", vec![call(add_one_id, y, ret)]);
+        let mut funs = FunDeclId::Vector::from(vec![add_one, f]);
+        let mut globals = GlobalDeclId::Vector::new();
+        let type_defs = TypeDecls::new();
+        let fun_names = FunDeclId::Vector::new();
+        let global_names = GlobalDeclId::Vector::new();
+        let fmt_ctx = CtxNames::new(&type_defs, &fun_names, &global_names);
+        transform(&fmt_ctx, Some(1), false, &mut funs, &mut globals);
+
+        // ```
+        // fn f(y: u32) -> u32 {
+        //   x' := copy y;
+        //   ret' := copy x' + const 1;
+        //   ret := move ret';
+        //   return;
+        // }
+        // ```
+        let body = funs
+            .get(FunDeclId::Id::new(1))
+            .unwrap()
+            .body
+            .as_ref()
+            .unwrap();
+        // Two locals were introduced: `ret'` and `x'`
+        assert!(body.locals.len() == 4);
+        let ret1 = VarId::Id::new(2);
+        let x1 = VarId::Id::new(3);
+        let sts = flatten(&body.body);
+        assert!(sts.len() == 4);
+        assert!(
+            matches!(sts[0], RawStatement::Assign(p, Rvalue::Use(Operand::Copy(p1))) if *p == Place::new(x1) && *p1 == Place::new(y))
+        );
+        assert!(
+            matches!(sts[1], RawStatement::Assign(p, Rvalue::BinaryOp(BinOp::Add, Operand::Copy(p1), c)) if *p == Place::new(ret1) && *p1 == Place::new(x1) && *c == one)
+        );
+        assert!(
+            matches!(sts[2], RawStatement::Assign(p, Rvalue::Use(Operand::Move(p1))) if *p == Place::new(ret) && *p1 == Place::new(ret1))
+        );
+        assert!(sts[3].is_return());
+
+        // `add_one` itself is left unchanged
+        let body = funs.get(add_one_id).unwrap().body.as_ref().unwrap();
+        assert!(flatten(&body.body).len() == 2);
+    }
+
+    #[test]
+    fn test_inline_wrappers() {
+        let ret = VarId::Id::new(0);
+        let x = VarId::Id::new(1);
+        let id = FunDeclId::Id::new(0);
+        let wrapper = FunDeclId::Id::new(1);
+        let f = FunDeclId::Id::new(2);
+        let rec = FunDeclId::Id::new(3);
+        // ```
+        // fn id(x: u32) -> u32 { ret := copy x; return; }
+        // fn wrapper(x: u32) -> u32 { ret := id(copy x); return; }
+        // fn f(x: u32) -> u32 { ret := wrapper(copy x); return; }
+        // fn rec(x: u32) -> u32 { ret := rec(copy x); return; }
+        // ```
+        let copy_x =
+            RawStatement::Assign(Place::new(ret), Rvalue::Use(Operand::Copy(Place::new(x))));
+        let mut funs = FunDeclId::Vector::from(vec![
+            mk_u32_fun(id, "id", vec![copy_x]),
+            mk_u32_fun(wrapper, "wrapper", vec![call(id, x, ret)]),
+            mk_u32_fun(f, "f", vec![call(wrapper, x, ret)]),
+            mk_u32_fun(rec, "rec", vec![call(rec, x, ret)]),
+        ]);
+        let mut globals = GlobalDeclId::Vector::new();
+        let type_defs = TypeDecls::new();
+        let fun_names = FunDeclId::Vector::new();
+        let global_names = GlobalDeclId::Vector::new();
+        let fmt_ctx = CtxNames::new(&type_defs, &fun_names, &global_names);
+        transform(&fmt_ctx, Some(1), false, &mut funs, &mut globals);
+
+        // `f` doesn't perform any call anymore: we inlined `wrapper`, in
+        // which we inlined `id`
+        let body = funs.get(f).unwrap().body.as_ref().unwrap();
+        assert!(flatten(&body.body).iter().all(|st| !st.is_call()));
+
+        // We don't inline the recursive functions
+        let body = funs.get(rec).unwrap().body.as_ref().unwrap();
+        assert!(flatten(&body.body)[0].is_call());
+    }
+
+    #[test]
+    fn test_inline_always() {
+        let ret = VarId::Id::new(0);
+        let x = VarId::Id::new(1);
+        let id = FunDeclId::Id::new(0);
+        let f = FunDeclId::Id::new(1);
+        let copy_x =
+            RawStatement::Assign(Place::new(ret), Rvalue::Use(Operand::Copy(Place::new(x))));
+        let mut id_decl = mk_u32_fun(id, "id", vec![copy_x]);
+        let mut funs = FunDeclId::Vector::from(vec![
+            id_decl.clone(),
+            mk_u32_fun(f, "f", vec![call(id, x, ret)]),
+        ]);
+        let mut globals = GlobalDeclId::Vector::new();
+        let type_defs = TypeDecls::new();
+        let fun_names = FunDeclId::Vector::new();
+        let global_names = GlobalDeclId::Vector::new();
+        let fmt_ctx = CtxNames::new(&type_defs, &fun_names, &global_names);
+
+        // `id` is not marked `#[inline(always)]`
+        transform(&fmt_ctx, None, true, &mut funs, &mut globals);
+        let body = funs.get(f).unwrap().body.as_ref().unwrap();
+        assert!(flatten(&body.body)[0].is_call());
+
+        id_decl.attr_info.attributes.push(Attribute {
+            path: vec!["inline".to_string()],
+            args: Some("always".to_string()),
+        });
+        let mut funs = FunDeclId::Vector::from(vec![id_decl, funs.get(f).unwrap().clone()]);
+        transform(&fmt_ctx, None, true, &mut funs, &mut globals);
+        let body = funs.get(f).unwrap().body.as_ref().unwrap();
+        assert!(flatten(&body.body).iter().all(|st| !st.is_call()));
+    }
+}
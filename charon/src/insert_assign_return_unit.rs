@@ -27,6 +27,7 @@ fn transform_st(mut st: Statement) -> Statement {
             RawStatement::Sequence(Box::new(assign_st), Box::new(ret_st))
         }
         RawStatement::Assign(p, rv) => RawStatement::Assign(p, rv),
+        RawStatement::CompoundAssign(p, op, rhs) => RawStatement::CompoundAssign(p, op, rhs),
         RawStatement::FakeRead(p) => RawStatement::FakeRead(p),
         RawStatement::SetDiscriminant(p, vid) => RawStatement::SetDiscriminant(p, vid),
         RawStatement::Drop(p) => RawStatement::Drop(p),
@@ -49,15 +50,21 @@ fn transform_st(mut st: Statement) -> Statement {
                 let switch = Switch::SwitchInt(op, int_ty, targets, otherwise);
                 RawStatement::Switch(switch)
             }
-            Switch::Match(op, targets, mut otherwise) => {
+            Switch::Match(op, targets, mut otherwise, provenance) => {
                 let targets =
                     Vec::from_iter(targets.into_iter().map(|(v, e)| (v, transform_st(e))));
                 *otherwise = transform_st(*otherwise);
-                let switch = Switch::Match(op, targets, otherwise);
+                let switch = Switch::Match(op, targets, otherwise, provenance);
                 RawStatement::Switch(switch)
             }
         },
-        RawStatement::Loop(loop_body) => RawStatement::Loop(Box::new(transform_st(*loop_body))),
+        RawStatement::Loop(loop_body, invariants) => {
+            RawStatement::Loop(Box::new(transform_st(*loop_body)), invariants)
+        }
+        RawStatement::While(cond, op, body, invariants) => {
+            // The statement computing the condition doesn't contain returns
+            RawStatement::While(cond, op, Box::new(transform_st(*body)), invariants)
+        }
         RawStatement::Sequence(st1, st2) => {
             RawStatement::Sequence(Box::new(transform_st(*st1)), Box::new(transform_st(*st2)))
         }
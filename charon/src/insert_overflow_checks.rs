@@ -0,0 +1,241 @@
+//! Reinsert the overflow checks after the arithmetic operations of the
+//! functions annotated with `#[charon::overflow_checks]`.
+//!
+//! [crate::simplify_ops] removes the overflow checks introduced by rustc,
+//! because most backends model the arithmetic operations as failing on
+//! overflow. When mixing proof styles across a crate, it is however useful to
+//! keep explicit overflow obligations in some functions. For those functions,
+//! we perform the inverse transformation, that is we rewrite:
+//! ```text
+//! dest := copy x + copy y; // Possibly a different binop
+//! ```
+//! to:
+//! ```text
+//! tmp := copy x + copy y; // `tmp` has type `(T, bool)`
+//! assert(move (tmp.1) == false);
+//! dest := move (tmp.0);
+//! ```
+
+use crate::expressions::*;
+use crate::llbc_ast::{
+    chain_statements, transform_statements, Assert, AssertKind, CtxNames, ExprBody, FunDecls,
+    RawStatement, Statement,
+};
+use crate::simplify_ops::binop_requires_assert_after;
+use crate::types::*;
+use crate::ullbc_ast::{make_locals_generator, FunDeclId};
+use crate::values::*;
+use std::collections::HashSet;
+use take_mut::take;
+
+/// Compute the type of an operand
+fn get_operand_type(type_defs: &TypeDecls, body: &ExprBody, op: &Operand) -> ETy {
+    match op {
+        Operand::Const(ty, _) => ty.clone(),
+        Operand::Copy(p) | Operand::Move(p) => body.place_type(type_defs, p),
+    }
+}
+
+fn mk_tuple_field(var_id: VarId::Id, field_id: usize) -> Place {
+    let mut p = Place::new(var_id);
+    p.projection.push_back(ProjectionElem::Field(
+        FieldProjKind::Tuple(2),
+        FieldId::Id::new(field_id),
+    ));
+    p
+}
+
+fn transform_st<F: FnMut(ETy) -> VarId::Id>(
+    type_defs: &TypeDecls,
+    body: &ExprBody,
+    make_new_var: &mut F,
+    st: Statement,
+) -> Statement {
+    let ty = match &st.content {
+        RawStatement::Assign(_, Rvalue::BinaryOp(binop, op1, _))
+            if binop_requires_assert_after(*binop) =>
+        {
+            // Note that the type of the result is the type of the first operand
+            // (this is true for the shifts too). The operations on the floats
+            // never overflow.
+            Some(get_operand_type(type_defs, body, op1)).filter(|ty| !ty.is_float())
+        }
+        _ => None,
+    };
+    let ty = match ty {
+        Some(ty) => ty,
+        None => return st,
+    };
+
+    let (dest, rv) = st.content.to_assign();
+    let pair_ty = Ty::Adt(
+        TypeId::Tuple,
+        im::Vector::new(),
+        im::Vector::from(vec![ty, Ty::Bool]),
+    );
+    let tmp = make_new_var(pair_ty);
+    let meta = st.meta;
+    let checked_op = Statement::new(meta, RawStatement::Assign(Place::new(tmp), rv));
+    let assert = Statement::new(
+        meta,
+        RawStatement::Assert(Assert {
+            cond: Operand::Move(mk_tuple_field(tmp, 1)),
+            expected: false,
+            kind: AssertKind::Overflow,
+        }),
+    );
+    let assign = Statement::new(
+        meta,
+        RawStatement::Assign(dest, Rvalue::Use(Operand::Move(mk_tuple_field(tmp, 0)))),
+    );
+    chain_statements(vec![checked_op, assert], assign)
+}
+
+fn transform_body(type_defs: &TypeDecls, body: &mut ExprBody) {
+    // We need to compute the types of the places while generating new locals:
+    // the new locals are only used for the pairs, so we can use a copy of the
+    // locals (we don't need the statements to compute the types)
+    let locals_body = ExprBody {
+        meta: body.meta,
+        arg_count: body.arg_count,
+        return_local: body.return_local,
+        locals: body.locals.clone(),
+        body: Statement::new(body.meta, RawStatement::Nop),
+    };
+    let mut make_new_var = make_locals_generator(&mut body.locals);
+    take(&mut body.body, |st| {
+        transform_statements(
+            &mut |st| transform_st(type_defs, &locals_body, &mut make_new_var, st),
+            st,
+        )
+    });
+}
+
+/// `annotated`: the functions annotated with `#[charon::overflow_checks]`
+pub fn transform(
+    fmt_ctx: &CtxNames<'_>,
+    type_defs: &TypeDecls,
+    funs: &mut FunDecls,
+    annotated: &HashSet<FunDeclId::Id>,
+) {
+    for f in funs.iter_mut() {
+        if !annotated.contains(&f.def_id) {
+            continue;
+        }
+        if let Some(b) = &mut f.body {
+            trace!(
+                "# About to insert the overflow checks in decl: {}:\n{}",
+                f.name,
+                b.fmt_with_ctx_names(fmt_ctx)
+            );
+            transform_body(type_defs, b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::insert_overflow_checks::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_insert_overflow_checks() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let ret = VarId::Id::new(0);
+        let x = VarId::Id::new(1);
+        let y = VarId::Id::new(2);
+        // ```
+        // @return := copy x + copy y;
+        // return
+        // ```
+        let mut body = mk_body(
+            2,
+            vec![
+                mk_var(0, None, u32_ty.clone()),
+                mk_var(1, None, u32_ty.clone()),
+                mk_var(2, None, u32_ty),
+            ],
+            vec![
+                RawStatement::Assign(
+                    Place::new(ret),
+                    Rvalue::BinaryOp(
+                        BinOp::Add,
+                        Operand::Copy(Place::new(x)),
+                        Operand::Copy(Place::new(y)),
+                    ),
+                ),
+                RawStatement::Return,
+            ],
+        );
+        transform_body(&TypeDecls::new(), &mut body);
+
+        // ```
+        // tmp := copy x + copy y;
+        // assert(move (tmp.1) == false);
+        // @return := move (tmp.0);
+        // return
+        // ```
+        let tmp = VarId::Id::new(3);
+        assert!(body.locals.len() == 4);
+        let sts = flatten(&body.body);
+        assert!(sts.len() == 4);
+        assert!(
+            matches!(sts[0], RawStatement::Assign(p, Rvalue::BinaryOp(BinOp::Add, _, _)) if *p == Place::new(tmp))
+        );
+        assert!(
+            matches!(sts[1], RawStatement::Assert(Assert { cond: Operand::Move(p), expected: false, kind: AssertKind::Overflow }) if *p == mk_tuple_field(tmp, 1))
+        );
+        assert!(
+            matches!(sts[2], RawStatement::Assign(p, Rvalue::Use(Operand::Move(p1))) if *p == Place::new(ret) && *p1 == mk_tuple_field(tmp, 0))
+        );
+        assert!(sts[3].is_return());
+    }
+
+    #[test]
+    fn test_insert_overflow_checks_field() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let pair_ty = Ty::Adt(
+            TypeId::Tuple,
+            im::Vector::new(),
+            im::vector![u32_ty.clone(), u32_ty.clone()],
+        );
+        let ret = VarId::Id::new(0);
+        let p = VarId::Id::new(1);
+        // ```
+        // @return := copy (p.0) * copy (p.1);
+        // return
+        // ```
+        let mut body = mk_body(
+            1,
+            vec![mk_var(0, None, u32_ty), mk_var(1, None, pair_ty)],
+            vec![
+                RawStatement::Assign(
+                    Place::new(ret),
+                    Rvalue::BinaryOp(
+                        BinOp::Mul,
+                        Operand::Copy(mk_tuple_field(p, 0)),
+                        Operand::Copy(mk_tuple_field(p, 1)),
+                    ),
+                ),
+                RawStatement::Return,
+            ],
+        );
+        transform_body(&TypeDecls::new(), &mut body);
+
+        // The operands are projected: we insert the check all the same, and
+        // the temporary pair has type `(u32, bool)`
+        let tmp = VarId::Id::new(2);
+        assert!(body.locals.len() == 3);
+        assert!(
+            body.locals.get(tmp).unwrap().ty
+                == Ty::Adt(
+                    TypeId::Tuple,
+                    im::Vector::new(),
+                    im::vector![Ty::Integer(IntegerTy::U32), Ty::Bool],
+                )
+        );
+        let sts = flatten(&body.body);
+        assert!(sts.len() == 4);
+        assert!(sts[1].is_assert());
+    }
+}
@@ -0,0 +1,787 @@
+//! A reference interpreter for the LLBC functions.
+//!
+//! We concretely execute closed functions, whose values are booleans,
+//! integers and ADTs (structures, enumerations and tuples): the references,
+//! the arrays and the floats are not supported. This allows us to compare
+//! the behaviour of the generated code with the behaviour of the program
+//! compiled by `rustc`, and to check that a micro-pass preserves the
+//! semantics of the bodies.
+//!
+//! We interpret the bodies once they have been simplified by
+//! [crate::simplify_ops]: the arithmetic operations panic in case of
+//! overflow. The moves invalidate the moved places, so that reading a moved
+//! value is an error.
+#![allow(dead_code)]
+
+use crate::expressions::*;
+use crate::id_vector::ToUsize;
+use crate::llbc_ast::*;
+use crate::types::*;
+use crate::ullbc_ast::FunId;
+use crate::values::*;
+use std::convert::TryFrom;
+
+/// The number of statements we execute before giving up, by default
+pub const DEFAULT_FUEL: usize = 1_000_000;
+
+/// The maximal number of nested calls
+const MAX_CALL_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    Scalar(ScalarValue),
+    /// A structure, a tuple (in which case the variant is `None`) or an
+    /// enumeration value. The closures are also structures, whose fields
+    /// are the captured values.
+    Adt(Option<VariantId::Id>, Vec<Value>),
+    /// An uninitialized or moved value
+    Bottom,
+}
+
+/// The outcome of the execution of a function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Return(Value),
+    Panic,
+}
+
+/// The reasons why we fail to execute a function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterpError {
+    /// The function uses a feature we don't support
+    Unsupported(String),
+    /// The function is ill-formed: this is a bug in Charon
+    IllFormed(String),
+    /// We executed too many statements (the function may not terminate)
+    OutOfFuel,
+    /// There are too many nested calls
+    StackOverflow,
+}
+
+impl std::fmt::Display for InterpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            InterpError::Unsupported(msg) => write!(f, "unsupported: {msg}"),
+            InterpError::IllFormed(msg) => write!(f, "ill-formed code: {msg}"),
+            InterpError::OutOfFuel => write!(f, "out of fuel"),
+            InterpError::StackOverflow => write!(f, "too many nested calls"),
+        }
+    }
+}
+
+/// Why we stop executing the current function: we propagate the panics like
+/// the errors
+enum Stop {
+    Panic,
+    Error(InterpError),
+}
+
+impl From<InterpError> for Stop {
+    fn from(err: InterpError) -> Self {
+        Stop::Error(err)
+    }
+}
+
+type Eval<T> = std::result::Result<T, Stop>;
+
+fn unsupported<T>(msg: impl Into<String>) -> Eval<T> {
+    Err(Stop::Error(InterpError::Unsupported(msg.into())))
+}
+
+fn ill_formed<T>(msg: impl Into<String>) -> Eval<T> {
+    Err(Stop::Error(InterpError::IllFormed(msg.into())))
+}
+
+/// How the execution of a statement ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    /// Go on with the next statement
+    Next,
+    Break(usize),
+    Continue(usize),
+    Return,
+}
+
+/// The number of bits of the integers of a given type
+fn num_bits(ty: IntegerTy) -> u32 {
+    (ty.size() * 8) as u32
+}
+
+/// The two's complement representation of an integer (on 128 bits: the
+/// signed integers are sign-extended)
+fn to_bits(v: &ScalarValue) -> u128 {
+    if v.is_int() {
+        v.as_int().unwrap() as u128
+    } else {
+        v.as_uint().unwrap()
+    }
+}
+
+/// The integer of type `ty` whose two's complement representation is given
+/// by the low bits of `bits` (we truncate the other bits)
+fn from_bits(ty: IntegerTy, bits: u128) -> ScalarValue {
+    let n = num_bits(ty);
+    let bits = if n == 128 {
+        bits
+    } else {
+        bits & ((1 << n) - 1)
+    };
+    if ty.is_signed() {
+        // Sign-extend
+        let v = if n < 128 && bits >> (n - 1) == 1 {
+            bits as i128 - (1 << n)
+        } else {
+            bits as i128
+        };
+        ScalarValue::from_unchecked_int(ty, v)
+    } else {
+        ScalarValue::from_unchecked_uint(ty, bits)
+    }
+}
+
+/// Apply an integer operation. Returns `None` if the operation panics
+/// (overflow, division by zero, etc.).
+fn eval_int_binop(binop: BinOp, v1: &ScalarValue, v2: &ScalarValue) -> Eval<Option<ScalarValue>> {
+    let ty = v1.get_integer_ty();
+    if matches!(binop, BinOp::Shl | BinOp::Shr) {
+        // The shift amount can have any integer type
+        let amount = if v2.is_int() {
+            match u32::try_from(v2.as_int().unwrap()) {
+                std::result::Result::Ok(amount) => amount,
+                std::result::Result::Err(_) => return Ok(None),
+            }
+        } else {
+            u32::try_from(v2.as_uint().unwrap()).unwrap_or(u32::MAX)
+        };
+        if amount >= num_bits(ty) {
+            return Ok(None);
+        }
+        let bits = to_bits(v1);
+        // The shift to the right is arithmetic for the signed integers
+        // (`to_bits` sign-extends them)
+        let bits = if binop == BinOp::Shl {
+            bits << amount
+        } else if ty.is_signed() {
+            ((bits as i128) >> amount) as u128
+        } else {
+            bits >> amount
+        };
+        return Ok(Some(from_bits(ty, bits)));
+    }
+
+    if v2.get_integer_ty() != ty {
+        return ill_formed(format!(
+            "binary operation {:?} over values of types {:?} and {:?}",
+            binop,
+            ty,
+            v2.get_integer_ty()
+        ));
+    }
+    let res = if ty.is_signed() {
+        let (x, y) = (v1.as_int().unwrap(), v2.as_int().unwrap());
+        let res = match binop {
+            BinOp::BitXor => Some(x ^ y),
+            BinOp::BitAnd => Some(x & y),
+            BinOp::BitOr => Some(x | y),
+            BinOp::Add => x.checked_add(y),
+            BinOp::Sub => x.checked_sub(y),
+            BinOp::Mul => x.checked_mul(y),
+            BinOp::Div => x.checked_div(y),
+            // `MIN % -1` overflows, like `MIN / -1`
+            BinOp::Rem if y == -1 && v1.is_min() => None,
+            BinOp::Rem => x.checked_rem(y),
+            _ => return unsupported(format!("the binary operation {binop:?} over integers")),
+        };
+        res.and_then(|v| ScalarValue::from_int(ty, v).ok())
+    } else {
+        let (x, y) = (v1.as_uint().unwrap(), v2.as_uint().unwrap());
+        let res = match binop {
+            BinOp::BitXor => Some(x ^ y),
+            BinOp::BitAnd => Some(x & y),
+            BinOp::BitOr => Some(x | y),
+            BinOp::Add => x.checked_add(y),
+            BinOp::Sub => x.checked_sub(y),
+            BinOp::Mul => x.checked_mul(y),
+            BinOp::Div => x.checked_div(y),
+            BinOp::Rem => x.checked_rem(y),
+            _ => return unsupported(format!("the binary operation {binop:?} over integers")),
+        };
+        res.and_then(|v| ScalarValue::from_uint(ty, v).ok())
+    };
+    Ok(res)
+}
+
+fn compare_scalars(v1: &ScalarValue, v2: &ScalarValue) -> Eval<std::cmp::Ordering> {
+    if v1.get_integer_ty() != v2.get_integer_ty() {
+        return ill_formed(format!(
+            "comparison of values of types {:?} and {:?}",
+            v1.get_integer_ty(),
+            v2.get_integer_ty()
+        ));
+    }
+    if v1.is_int() {
+        Ok(v1.as_int().unwrap().cmp(&v2.as_int().unwrap()))
+    } else {
+        Ok(v1.as_uint().unwrap().cmp(&v2.as_uint().unwrap()))
+    }
+}
+
+fn expect_bool(v: Value) -> Eval<bool> {
+    match v {
+        Value::Bool(b) => Ok(b),
+        v => ill_formed(format!("expected a boolean, got {v:?}")),
+    }
+}
+
+/// The variant of the values a field projection applies to
+fn field_variant(kind: &FieldProjKind) -> Eval<Option<VariantId::Id>> {
+    match kind {
+        FieldProjKind::Adt(_, variant_id) => Ok(*variant_id),
+        FieldProjKind::Option(variant_id) | FieldProjKind::ControlFlow(variant_id) => {
+            Ok(Some(*variant_id))
+        }
+        FieldProjKind::Tuple(_) => Ok(None),
+        FieldProjKind::Union(_) => unsupported("the unions"),
+    }
+}
+
+fn field_proj(pe: &ProjectionElem) -> Eval<(&FieldProjKind, FieldId::Id)> {
+    match pe {
+        ProjectionElem::Field(kind, field_id) => Ok((kind, *field_id)),
+        ProjectionElem::Index(_) | ProjectionElem::ConstantIndex { .. } => {
+            unsupported("the arrays and the slices")
+        }
+        _ => unsupported("the references and the pointers"),
+    }
+}
+
+/// Read the value of a place
+fn read_place(locals: &[Value], p: &Place) -> Eval<Value> {
+    let mut v = &locals[p.var_id.to_usize()];
+    for pe in p.projection.iter() {
+        let (kind, field_id) = field_proj(pe)?;
+        let variant_id = field_variant(kind)?;
+        v = match v {
+            Value::Adt(id, fields) if *id == variant_id => match fields.get(field_id.to_usize()) {
+                Some(v) => v,
+                None => return ill_formed(format!("read of the missing field {field_id}")),
+            },
+            v => return ill_formed(format!("projection {pe:?} over the value {v:?}")),
+        };
+    }
+    match v {
+        Value::Bottom => ill_formed(format!("read of the uninitialized place {:?}", p)),
+        v => Ok(v.clone()),
+    }
+}
+
+/// Retrieve a place to update it. We initialize the fields we write to: the
+/// MIR sometimes initializes the ADTs field by field.
+fn place_mut<'v>(locals: &'v mut [Value], p: &Place) -> Eval<&'v mut Value> {
+    let mut v = &mut locals[p.var_id.to_usize()];
+    for pe in p.projection.iter() {
+        let (kind, field_id) = field_proj(pe)?;
+        let variant_id = field_variant(kind)?;
+        if !matches!(v, Value::Adt(id, _) if *id == variant_id) {
+            *v = Value::Adt(variant_id, Vec::new());
+        }
+        let fields = match v {
+            Value::Adt(_, fields) => fields,
+            _ => unreachable!(),
+        };
+        if fields.len() <= field_id.to_usize() {
+            fields.resize(field_id.to_usize() + 1, Value::Bottom);
+        }
+        v = &mut fields[field_id.to_usize()];
+    }
+    Ok(v)
+}
+
+/// The interpreter. It can execute several functions, but the fuel is
+/// shared between the executions.
+pub struct Interpreter<'a> {
+    funs: &'a FunDecls,
+    globals: &'a GlobalDecls,
+    /// The number of statements we can still execute
+    fuel: usize,
+    /// The number of nested calls
+    depth: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(funs: &'a FunDecls, globals: &'a GlobalDecls) -> Self {
+        Interpreter {
+            funs,
+            globals,
+            fuel: DEFAULT_FUEL,
+            depth: 0,
+        }
+    }
+
+    pub fn with_fuel(mut self, fuel: usize) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    /// Execute a function with the given arguments
+    pub fn eval_function(
+        &mut self,
+        id: FunDeclId::Id,
+        args: Vec<Value>,
+    ) -> std::result::Result<Outcome, InterpError> {
+        match self.call(id, args) {
+            Ok(v) => Ok(Outcome::Return(v)),
+            Err(Stop::Panic) => Ok(Outcome::Panic),
+            Err(Stop::Error(err)) => Err(err),
+        }
+    }
+
+    fn call(&mut self, id: FunDeclId::Id, args: Vec<Value>) -> Eval<Value> {
+        let decl = match self.funs.get(id) {
+            Some(decl) => decl,
+            None => return ill_formed(format!("call to the unknown function @Fun{id}")),
+        };
+        match &decl.body {
+            Some(body) => self.eval_body(body, args),
+            None => unsupported(format!(
+                "call to the opaque function {}",
+                decl.name.to_string()
+            )),
+        }
+    }
+
+    fn eval_global(&mut self, id: GlobalDeclId::Id) -> Eval<Value> {
+        let decl = match self.globals.get(id) {
+            Some(decl) => decl,
+            None => return ill_formed(format!("unknown global @Global{id}")),
+        };
+        match &decl.body {
+            Some(body) => self.eval_body(body, Vec::new()),
+            None => unsupported(format!("the opaque global {}", decl.name.to_string())),
+        }
+    }
+
+    fn eval_body(&mut self, body: &ExprBody, args: Vec<Value>) -> Eval<Value> {
+        if args.len() != body.arg_count {
+            return ill_formed(format!(
+                "call with {} argument(s), expected {}",
+                args.len(),
+                body.arg_count
+            ));
+        }
+        if self.depth == MAX_CALL_DEPTH {
+            return Err(Stop::Error(InterpError::StackOverflow));
+        }
+        let mut locals = vec![Value::Bottom; body.locals.len()];
+        for (i, arg) in args.into_iter().enumerate() {
+            locals[i + 1] = arg;
+        }
+        self.depth += 1;
+        let res = self.eval_statement(&mut locals, &body.body);
+        self.depth -= 1;
+        match res? {
+            Control::Return => Ok(std::mem::replace(
+                &mut locals[body.return_local.to_usize()],
+                Value::Bottom,
+            )),
+            ctrl => ill_formed(format!("the body ends with {ctrl:?}")),
+        }
+    }
+
+    fn eval_constant(&mut self, cv: &OperandConstantValue) -> Eval<Value> {
+        match cv {
+            OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(v)) => {
+                Ok(Value::Scalar(v.clone()))
+            }
+            OperandConstantValue::PrimitiveValue(PrimitiveValue::Bool(b)) => Ok(Value::Bool(*b)),
+            OperandConstantValue::Adt(variant_id, fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|cv| self.eval_constant(cv))
+                    .collect::<Eval<Vec<Value>>>()?;
+                Ok(Value::Adt(*variant_id, fields))
+            }
+            OperandConstantValue::ConstantId(id) => self.eval_global(*id),
+            cv => unsupported(format!("the constant {cv:?}")),
+        }
+    }
+
+    fn eval_operand(&mut self, locals: &mut [Value], op: &Operand) -> Eval<Value> {
+        match op {
+            Operand::Copy(p) => read_place(locals, p),
+            Operand::Move(p) => {
+                let v = read_place(locals, p)?;
+                *place_mut(locals, p)? = Value::Bottom;
+                Ok(v)
+            }
+            Operand::Const(_, cv) => self.eval_constant(cv),
+        }
+    }
+
+    fn eval_binop(&mut self, binop: BinOp, v1: Value, v2: Value) -> Eval<Value> {
+        use std::cmp::Ordering;
+        let cmp = |ord: Ordering| match binop {
+            BinOp::Lt => Some(ord == Ordering::Less),
+            BinOp::Le => Some(ord != Ordering::Greater),
+            BinOp::Gt => Some(ord == Ordering::Greater),
+            BinOp::Ge => Some(ord != Ordering::Less),
+            _ => None,
+        };
+        match (binop, v1, v2) {
+            (BinOp::Eq, v1, v2) => Ok(Value::Bool(v1 == v2)),
+            (BinOp::Ne, v1, v2) => Ok(Value::Bool(v1 != v2)),
+            (BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge, Value::Bool(b1), Value::Bool(b2)) => {
+                Ok(Value::Bool(cmp(b1.cmp(&b2)).unwrap()))
+            }
+            (
+                BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge,
+                Value::Scalar(v1),
+                Value::Scalar(v2),
+            ) => Ok(Value::Bool(cmp(compare_scalars(&v1, &v2)?).unwrap())),
+            (BinOp::BitXor, Value::Bool(b1), Value::Bool(b2)) => Ok(Value::Bool(b1 ^ b2)),
+            (BinOp::BitAnd, Value::Bool(b1), Value::Bool(b2)) => Ok(Value::Bool(b1 & b2)),
+            (BinOp::BitOr, Value::Bool(b1), Value::Bool(b2)) => Ok(Value::Bool(b1 | b2)),
+            (binop, Value::Scalar(v1), Value::Scalar(v2)) => {
+                match eval_int_binop(binop, &v1, &v2)? {
+                    Some(v) => Ok(Value::Scalar(v)),
+                    None => Err(Stop::Panic),
+                }
+            }
+            (binop, v1, v2) => ill_formed(format!("{binop:?} over {v1:?} and {v2:?}")),
+        }
+    }
+
+    fn eval_rvalue(&mut self, locals: &mut [Value], rv: &Rvalue) -> Eval<Value> {
+        match rv {
+            Rvalue::Use(op) => self.eval_operand(locals, op),
+            Rvalue::UnaryOp(unop, op) => match (unop, self.eval_operand(locals, op)?) {
+                (UnOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+                (UnOp::Not, Value::Scalar(v)) => {
+                    Ok(Value::Scalar(from_bits(v.get_integer_ty(), !to_bits(&v))))
+                }
+                (UnOp::Neg, Value::Scalar(v)) if v.is_int() => {
+                    let ty = v.get_integer_ty();
+                    let v = v.as_int().unwrap().checked_neg();
+                    match v.and_then(|v| ScalarValue::from_int(ty, v).ok()) {
+                        Some(v) => Ok(Value::Scalar(v)),
+                        None => Err(Stop::Panic),
+                    }
+                }
+                (unop, v) => ill_formed(format!("{unop:?} over {v:?}")),
+            },
+            Rvalue::BinaryOp(binop, op1, op2) => {
+                let v1 = self.eval_operand(locals, op1)?;
+                let v2 = self.eval_operand(locals, op2)?;
+                self.eval_binop(*binop, v1, v2)
+            }
+            Rvalue::Aggregate(kind, ops) => {
+                let fields = ops
+                    .iter()
+                    .map(|op| self.eval_operand(locals, op))
+                    .collect::<Eval<Vec<Value>>>()?;
+                let variant_id = match kind {
+                    AggregateKind::Tuple | AggregateKind::Closure(..) => None,
+                    AggregateKind::Option(variant_id, _) => Some(*variant_id),
+                    AggregateKind::Adt(_, variant_id, _, _) => *variant_id,
+                };
+                Ok(Value::Adt(variant_id, fields))
+            }
+            Rvalue::Global(id) => self.eval_global(*id),
+            Rvalue::IsVariant(p, variants) => match read_place(locals, p)? {
+                Value::Adt(Some(variant_id), _) => Ok(Value::Bool(variants.contains(&variant_id))),
+                v => ill_formed(format!("variant test over {v:?}")),
+            },
+            Rvalue::Cast(kind, op, _, tgt) => {
+                let int_ty = match tgt {
+                    Ty::Integer(int_ty) => *int_ty,
+                    _ => return unsupported(format!("the cast to {}", tgt.to_string())),
+                };
+                match (kind, self.eval_operand(locals, op)?) {
+                    (CastKind::IntToInt, Value::Scalar(v)) => {
+                        Ok(Value::Scalar(from_bits(int_ty, to_bits(&v))))
+                    }
+                    (CastKind::BoolToInt, Value::Bool(b)) => {
+                        Ok(Value::Scalar(from_bits(int_ty, b as u128)))
+                    }
+                    (kind, v) => unsupported(format!("the cast {kind:?} of {v:?}")),
+                }
+            }
+            Rvalue::Discriminant(_) => unsupported("the discriminants"),
+            Rvalue::Len(_) | Rvalue::Slice { .. } => unsupported("the arrays and the slices"),
+            Rvalue::Ref(..)
+            | Rvalue::RawPtr(..)
+            | Rvalue::GlobalRef(..)
+            | Rvalue::Unsize(..)
+            | Rvalue::FnPtr(_) => unsupported("the references and the pointers"),
+        }
+    }
+
+    fn eval_call(&mut self, locals: &mut [Value], call: &Call) -> Eval<()> {
+        let id = match &call.func {
+            FunId::Regular(id) | FunId::TraitMethod(_, id) => *id,
+            FunId::Virtual(..) | FunId::Indirect(_) => {
+                return unsupported("the calls through trait objects or function pointers")
+            }
+            FunId::Assumed(fid) => return unsupported(format!("the primitive function {fid:?}")),
+        };
+        let args = call
+            .args
+            .iter()
+            .map(|op| self.eval_operand(locals, op))
+            .collect::<Eval<Vec<Value>>>()?;
+        let v = self.call(id, args)?;
+        *place_mut(locals, &call.dest)? = v;
+        Ok(())
+    }
+
+    fn eval_switch(&mut self, locals: &mut [Value], switch: &Switch) -> Eval<Control> {
+        let target: &Statement = match switch {
+            Switch::If(op, st1, st2) => {
+                if expect_bool(self.eval_operand(locals, op)?)? {
+                    st1
+                } else {
+                    st2
+                }
+            }
+            Switch::SwitchInt(op, _, targets, otherwise) => match self.eval_operand(locals, op)? {
+                Value::Scalar(v) => targets
+                    .iter()
+                    .find(|(values, _)| values.contains(&v))
+                    .map_or(&**otherwise, |(_, st)| st),
+                v => return ill_formed(format!("switch over {v:?}")),
+            },
+            Switch::Match(p, targets, otherwise, _) => match read_place(locals, p)? {
+                Value::Adt(Some(variant_id), _) => targets
+                    .iter()
+                    .find(|(variants, _)| variants.contains(&variant_id))
+                    .map_or(&**otherwise, |(_, st)| st),
+                v => return ill_formed(format!("match over {v:?}")),
+            },
+        };
+        self.eval_statement(locals, target)
+    }
+
+    fn eval_statement(&mut self, locals: &mut [Value], st: &Statement) -> Eval<Control> {
+        // We explore the sequences iteratively: they can be very long
+        let mut st = st;
+        while let RawStatement::Sequence(st1, st2) = &st.content {
+            match self.eval_statement(locals, st1)? {
+                Control::Next => st = &**st2,
+                ctrl => return Ok(ctrl),
+            }
+        }
+
+        if self.fuel == 0 {
+            return Err(Stop::Error(InterpError::OutOfFuel));
+        }
+        self.fuel -= 1;
+        match &st.content {
+            RawStatement::Assign(p, rv) => {
+                let v = self.eval_rvalue(locals, rv)?;
+                *place_mut(locals, p)? = v;
+            }
+            RawStatement::CompoundAssign(p, binop, op) => {
+                let v2 = self.eval_operand(locals, op)?;
+                let v1 = read_place(locals, p)?;
+                let v = self.eval_binop(*binop, v1, v2)?;
+                *place_mut(locals, p)? = v;
+            }
+            RawStatement::SetDiscriminant(p, variant_id) => {
+                let v = place_mut(locals, p)?;
+                match v {
+                    Value::Adt(id, _) => *id = Some(*variant_id),
+                    _ => *v = Value::Adt(Some(*variant_id), Vec::new()),
+                }
+            }
+            RawStatement::Assert(assert) => {
+                let cond = expect_bool(self.eval_operand(locals, &assert.cond)?)?;
+                if cond != assert.expected {
+                    return Err(Stop::Panic);
+                }
+            }
+            RawStatement::Call(call) => self.eval_call(locals, call)?,
+            RawStatement::Panic => return Err(Stop::Panic),
+            RawStatement::Return => return Ok(Control::Return),
+            RawStatement::Break(i) => return Ok(Control::Break(*i)),
+            RawStatement::Continue(i) => return Ok(Control::Continue(*i)),
+            RawStatement::FakeRead(_) | RawStatement::Drop(_) | RawStatement::Nop => (),
+            RawStatement::Switch(switch) => return self.eval_switch(locals, switch),
+            RawStatement::Loop(body, _) => loop {
+                // Reaching the end of the body is the same as continuing
+                match self.eval_statement(locals, body)? {
+                    Control::Next | Control::Continue(0) => (),
+                    Control::Break(0) => return Ok(Control::Next),
+                    Control::Break(i) => return Ok(Control::Break(i - 1)),
+                    Control::Continue(i) => return Ok(Control::Continue(i - 1)),
+                    Control::Return => return Ok(Control::Return),
+                }
+            },
+            RawStatement::While(cond, op, body, _) => loop {
+                // The statement computing the condition can't exit the loop
+                match self.eval_statement(locals, cond)? {
+                    Control::Next => (),
+                    _ => unreachable!(),
+                }
+                if !expect_bool(self.eval_operand(locals, op)?)? {
+                    return Ok(Control::Next);
+                }
+                match self.eval_statement(locals, body)? {
+                    Control::Next | Control::Continue(0) => (),
+                    Control::Break(0) => return Ok(Control::Next),
+                    Control::Break(i) => return Ok(Control::Break(i - 1)),
+                    Control::Continue(i) => return Ok(Control::Continue(i - 1)),
+                    Control::Return => return Ok(Control::Return),
+                }
+            },
+            RawStatement::Sequence(..) => unreachable!(),
+        }
+        Ok(Control::Next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interp::*;
+    use crate::llbc_builder::*;
+    use crate::test_utils::*;
+
+    /// A function `f` whose signature is given by the locals of its body
+    fn mk_fun(body: ExprBody) -> FunDecl {
+        let inputs = body
+            .locals
+            .iter()
+            .skip(1)
+            .take(body.arg_count)
+            .map(|v| v.ty.clone())
+            .collect();
+        let output = body.locals.get(VarId::ZERO).unwrap().ty.clone();
+        mk_fun_decl(0, &["f"], mk_sig(inputs, output), Some(body))
+    }
+
+    fn run(body: ExprBody, args: Vec<Value>) -> std::result::Result<Outcome, InterpError> {
+        let funs = FunDecls::from(vec![mk_fun(body)]);
+        let globals = GlobalDecls::new();
+        Interpreter::new(&funs, &globals).eval_function(FunDeclId::ZERO, args)
+    }
+
+    fn u32_const(v: u32) -> Operand {
+        let v = ScalarValue::U32(v);
+        Operand::Const(
+            Ty::Integer(IntegerTy::U32),
+            OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(v)),
+        )
+    }
+
+    fn u32_value(v: u32) -> Value {
+        Value::Scalar(ScalarValue::U32(v))
+    }
+
+    #[test]
+    fn test_factorial() {
+        // ```
+        // fn fact(n: u32) -> u32 {
+        //   @0 := const 1;
+        //   i := copy n;
+        //   loop {
+        //     b := copy i == const 0;
+        //     if copy b { break 0 } else { nop };
+        //     @0 := copy @0 * copy i;
+        //     i := copy i - const 1;
+        //     continue 0
+        //   };
+        //   return
+        // }
+        // ```
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let mut b = BodyBuilder::new(u32_ty.clone(), vec![u32_ty.clone()]);
+        let (ret, n) = (Place::new(b.ret()), Place::new(b.arg(0)));
+        let i = Place::new(b.fresh_var(Some("i".to_string()), u32_ty));
+        let cond = Place::new(b.fresh_var(None, Ty::Bool));
+        let binop = |binop, p: &Place, op| Rvalue::BinaryOp(binop, Operand::Copy(p.clone()), op);
+        let loop_body = Block::new()
+            .assign(cond.clone(), binop(BinOp::Eq, &i, u32_const(0)))
+            .push_statement(Switch::if_then_else(
+                Operand::Copy(cond),
+                Block::new().brk(0),
+                Block::new().end(),
+            ))
+            .assign(
+                ret.clone(),
+                binop(BinOp::Mul, &ret, Operand::Copy(i.clone())),
+            )
+            .assign(i.clone(), binop(BinOp::Sub, &i, u32_const(1)))
+            .cont(0);
+        let body = b.build(
+            Block::new()
+                .assign(ret, Rvalue::Use(u32_const(1)))
+                .assign(i, Rvalue::Use(Operand::Copy(n)))
+                .push_statement(loop_(loop_body))
+                .ret(),
+        );
+
+        let res = run(body.clone(), vec![u32_value(5)]);
+        assert!(res == Ok(Outcome::Return(u32_value(120))));
+        // `13!` doesn't fit in a `u32`
+        assert!(run(body.clone(), vec![u32_value(13)]) == Ok(Outcome::Panic));
+        assert!(run(body, vec![Value::Bool(true)]).is_err());
+    }
+
+    #[test]
+    fn test_match_option() {
+        // ```
+        // fn f(x: Option<u32>) -> u32 {
+        //   match x { Some => @0 := move (x as Some).0, _ => @0 := const 0 };
+        //   return
+        // }
+        // ```
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let option_ty = Ty::Adt(
+            TypeId::Assumed(AssumedTy::Option),
+            im::Vector::new(),
+            im::vector![u32_ty.clone()],
+        );
+        let mut b = BodyBuilder::new(u32_ty, vec![option_ty]);
+        let (ret, x) = (Place::new(b.ret()), Place::new(b.arg(0)));
+        let mut x_0 = x.clone();
+        let kind = FieldProjKind::Option(crate::assumed::OPTION_SOME_VARIANT_ID);
+        x_0.projection
+            .push_back(ProjectionElem::Field(kind, FieldId::ZERO));
+        let some = Block::new()
+            .assign(ret.clone(), Rvalue::Use(Operand::Move(x_0)))
+            .end();
+        let none = Block::new().assign(ret, Rvalue::Use(u32_const(0))).end();
+        let some_id = crate::assumed::OPTION_SOME_VARIANT_ID;
+        let body = b.build(
+            Block::new()
+                .push_statement(Switch::match_variants(x, vec![(vec![some_id], some)], none))
+                .ret(),
+        );
+
+        let some_3 = Value::Adt(Some(some_id), vec![u32_value(3)]);
+        assert!(run(body.clone(), vec![some_3]) == Ok(Outcome::Return(u32_value(3))));
+        let none = Value::Adt(Some(crate::assumed::OPTION_NONE_VARIANT_ID), Vec::new());
+        assert!(run(body, vec![none]) == Ok(Outcome::Return(u32_value(0))));
+    }
+
+    #[test]
+    fn test_int_ops() {
+        let i8_value = |v: i8| ScalarValue::I8(v);
+        assert!(from_bits(IntegerTy::I8, 0xff) == i8_value(-1));
+        assert!(from_bits(IntegerTy::U8, to_bits(&i8_value(-1))) == ScalarValue::U8(255));
+        assert!(from_bits(IntegerTy::I64, to_bits(&i8_value(-2))) == ScalarValue::I64(-2));
+
+        let eval = |binop, v1, v2| match eval_int_binop(binop, &v1, &v2) {
+            Ok(v) => v,
+            Err(_) => panic!(),
+        };
+        assert!(eval(BinOp::Shl, i8_value(1), ScalarValue::U32(7)) == Some(i8_value(-128)));
+        assert!(eval(BinOp::Shr, i8_value(-128), ScalarValue::U32(7)) == Some(i8_value(-1)));
+        assert!(eval(BinOp::Shl, i8_value(1), ScalarValue::U32(8)).is_none());
+        assert!(eval(BinOp::Add, i8_value(127), i8_value(1)).is_none());
+        assert!(eval(BinOp::Rem, i8_value(-128), i8_value(-1)).is_none());
+        assert!(eval(BinOp::Div, i8_value(7), i8_value(-2)) == Some(i8_value(-3)));
+        let u32_div = eval(BinOp::Div, ScalarValue::U32(1), ScalarValue::U32(0));
+        assert!(u32_div.is_none());
+    }
+}
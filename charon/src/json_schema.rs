@@ -0,0 +1,929 @@
+//! Generate a JSON Schema (draft-07) describing the serialized ULLBC
+//! declarations, for the consumers of the generated files which are not
+//! written in Rust.
+//!
+//! The schema describes the shapes produced by the [serde::Serialize]
+//! implementations, be they derived or written by hand (for instance, for
+//! [crate::expressions::Place] or [crate::ullbc_ast::SwitchTargets]). It must
+//! be updated whenever one of those implementations changes: the test below
+//! checks that a serialized function declaration is accepted by the schema.
+//!
+//! The definitions follow the conventions of serde:
+//! - the unit variants are serialized as strings
+//! - the newtype variants are serialized as `{ "Variant": x }`
+//! - the tuple variants are serialized as `{ "Variant": [x, y, ...] }`
+//! - the struct variants are serialized as `{ "Variant": { "f": x, ... } }`
+//!
+//! The identifiers (variable ids, block ids, etc.) are serialized as
+//! integers.
+#![allow(dead_code)]
+
+use serde_json::{json, Map, Value};
+
+/// Reference to a definition of the schema
+fn def(name: &str) -> Value {
+    json!({ "$ref": format!("#/definitions/{name}") })
+}
+
+fn id() -> Value {
+    json!({ "type": "integer", "minimum": 0 })
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+/// A tuple, serialized as an array of fixed length
+fn tuple(fields: Vec<Value>) -> Value {
+    let len = fields.len();
+    json!({ "type": "array", "items": fields, "minItems": len, "maxItems": len })
+}
+
+fn option(v: Value) -> Value {
+    json!({ "anyOf": [{ "type": "null" }, v] })
+}
+
+/// A structure: all the fields are required, and we don't allow additional
+/// fields.
+fn object(fields: Vec<(&str, Value)>) -> Value {
+    let required: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+    let properties: Map<String, Value> = fields
+        .into_iter()
+        .map(|(name, v)| (name.to_string(), v))
+        .collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+/// Add optional fields to a structure: we don't serialize those fields when
+/// they have their default value
+fn with_optional_fields(mut obj: Value, fields: Vec<(&str, Value)>) -> Value {
+    for (name, v) in fields {
+        obj["properties"][name] = v;
+    }
+    obj
+}
+
+/// A variant with arguments (newtype, tuple or struct variant)
+fn variant(name: &str, content: Value) -> Value {
+    object(vec![(name, content)])
+}
+
+/// A group of unit variants
+fn unit_variants(names: &[&str]) -> Value {
+    json!({ "type": "string", "enum": names })
+}
+
+fn one_of(alternatives: Vec<Value>) -> Value {
+    json!({ "oneOf": alternatives })
+}
+
+const INTEGER_TYPES: [&str; 12] = [
+    "Isize", "I8", "I16", "I32", "I64", "I128", "Usize", "U8", "U16", "U32", "U64", "U128",
+];
+
+/// The types are parameterized by the kind of regions they use
+/// (see [crate::types::Ty]).
+fn ty(region: &str, ty: &str) -> Value {
+    one_of(vec![
+        unit_variants(&["Bool", "Char", "Never", "Str"]),
+        variant(
+            "Adt",
+            tuple(vec![def("TypeId"), array(def(region)), array(def(ty))]),
+        ),
+        variant("TypeVar", tuple(vec![id()])),
+        variant("Integer", tuple(vec![def("IntegerTy")])),
+        variant("Float", tuple(vec![def("FloatTy")])),
+        variant("Array", tuple(vec![def(ty), def("ConstGeneric")])),
+        variant("Slice", tuple(vec![def(ty)])),
+        variant("Ref", tuple(vec![def(region), def(ty), def("RefKind")])),
+        variant("RawPtr", tuple(vec![def(ty), def("RefKind")])),
+        variant("ConstGeneric", tuple(vec![def("ConstGeneric")])),
+        variant("DynTrait", tuple(vec![id()])),
+        variant("FnPtr", tuple(vec![array(def(ty)), def(ty)])),
+        variant("Opaque", tuple(vec![option(def(ty)), array(id())])),
+        variant(
+            "TraitProjection",
+            tuple(vec![id(), array(def(region)), array(def(ty)), string()]),
+        ),
+    ])
+}
+
+fn meta_definitions() -> Vec<(&'static str, Value)> {
+    let loc = object(vec![("line", id()), ("col", id())]);
+    let file_id = one_of(vec![variant("LocalId", id()), variant("VirtualId", id())]);
+    let span = object(vec![
+        ("file_id", def("FileId")),
+        ("beg", def("Loc")),
+        ("end", def("Loc")),
+    ]);
+    let meta = object(vec![
+        ("span", def("Span")),
+        ("generated_from_span", option(def("Span"))),
+    ]);
+    let expr_metas = object(vec![
+        ("expr", option(def("Meta"))),
+        ("operands", array(option(def("Meta")))),
+    ]);
+    let attribute = object(vec![("path", array(string())), ("args", option(string()))]);
+    let attr_info = object(vec![
+        ("doc_comments", array(string())),
+        ("attributes", array(def("Attribute"))),
+    ]);
+    let path_elem = one_of(vec![
+        variant("Ident", string()),
+        variant("Disambiguator", id()),
+    ]);
+    vec![
+        ("Loc", loc),
+        ("FileId", file_id),
+        ("Span", span),
+        ("Meta", meta),
+        ("ExprMetas", expr_metas),
+        ("Attribute", attribute),
+        ("AttrInfo", attr_info),
+        ("PathElem", path_elem),
+        ("Name", array(def("PathElem"))),
+    ]
+}
+
+fn type_definitions() -> Vec<(&'static str, Value)> {
+    let assumed_ty = unit_variants(&[
+        "Box",
+        "Vec",
+        "Option",
+        "ControlFlow",
+        "PtrUnique",
+        "PtrNonNull",
+        "Rc",
+        "Arc",
+    ]);
+    let type_id = one_of(vec![
+        variant("Adt", id()),
+        unit_variants(&["Tuple"]),
+        variant("Assumed", def("AssumedTy")),
+    ]);
+    let region = one_of(vec![unit_variants(&["Static"]), variant("Var", id())]);
+    let type_var = object(vec![("index", id()), ("name", string())]);
+    let region_var = object(vec![("index", id()), ("name", option(string()))]);
+    let const_generic_var = object(vec![
+        ("index", id()),
+        ("name", string()),
+        ("ty", def("ETy")),
+    ]);
+    let const_generic = one_of(vec![
+        variant("Var", id()),
+        variant("Value", def("PrimitiveValue")),
+    ]);
+    let region_group = object(vec![
+        ("id", id()),
+        ("regions", array(id())),
+        ("parents", array(id())),
+    ]);
+    let trait_clause = object(vec![
+        ("trait_id", id()),
+        ("region_args", array(def("Region"))),
+        ("type_args", array(def("RTy"))),
+    ]);
+    vec![
+        ("IntegerTy", unit_variants(&INTEGER_TYPES)),
+        ("FloatTy", unit_variants(&["F32", "F64"])),
+        ("RefKind", unit_variants(&["Mut", "Shared"])),
+        ("AssumedTy", assumed_ty),
+        ("TypeId", type_id),
+        ("ErasedRegion", unit_variants(&["Erased"])),
+        ("Region", region),
+        ("ETy", ty("ErasedRegion", "ETy")),
+        ("RTy", ty("Region", "RTy")),
+        ("TypeVar", type_var),
+        ("RegionVar", region_var),
+        ("ConstGenericVar", const_generic_var),
+        ("ConstGeneric", const_generic),
+        ("RegionGroup", region_group),
+        ("TraitClause", trait_clause),
+    ]
+}
+
+fn value_definitions() -> Vec<(&'static str, Value)> {
+    // The scalar values are serialized as strings, because they may not fit
+    // in the integers of the consumers
+    let scalar_value = one_of(
+        INTEGER_TYPES
+            .iter()
+            .map(|int_ty| variant(int_ty, tuple(vec![string()])))
+            .collect(),
+    );
+    let primitive_value = one_of(vec![
+        variant("Scalar", def("ScalarValue")),
+        variant("Bool", json!({ "type": "boolean" })),
+        variant("Char", string()),
+        variant("String", string()),
+        variant("Float", def("FloatValue")),
+    ]);
+    // Like the scalar values, the floats are serialized as strings
+    let float_value = one_of(vec![variant("F32", string()), variant("F64", string())]);
+    // See the implementation of [serde::Serialize] for
+    // [crate::expressions::OperandConstantValue]
+    let constant_value = one_of(vec![
+        def("PrimitiveValue"),
+        variant(
+            "Bytes",
+            array(json!({ "type": "integer", "minimum": 0, "maximum": 255 })),
+        ),
+        variant("Array", array(def("ConstantValue"))),
+        variant("TraitConst", tuple(vec![id(), array(def("ETy")), string()])),
+        variant("ZST", def("ETy")),
+    ]);
+    vec![
+        ("ScalarValue", scalar_value),
+        ("FloatValue", float_value),
+        ("PrimitiveValue", primitive_value),
+        ("ConstantValue", constant_value),
+    ]
+}
+
+fn expression_definitions() -> Vec<(&'static str, Value)> {
+    let field_proj_kind = one_of(vec![
+        variant("ProjAdt", tuple(vec![id(), option(id())])),
+        variant("ProjOption", id()),
+        variant("ProjControlFlow", id()),
+        variant("ProjTuple", id()),
+        variant("ProjUnion", id()),
+    ]);
+    let projection_elem = one_of(vec![
+        unit_variants(&[
+            "Deref",
+            "DerefBox",
+            "DerefRawPtr",
+            "DerefPtrUnique",
+            "DerefPtrNonNull",
+            "DerefRc",
+        ]),
+        variant("Field", tuple(vec![def("FieldProjKind"), id()])),
+        variant("Index", id()),
+        variant(
+            "ConstantIndex",
+            object(vec![
+                ("offset", id()),
+                ("min_length", id()),
+                ("from_end", json!({ "type": "boolean" })),
+            ]),
+        ),
+    ]);
+    let place = object(vec![
+        ("var_id", id()),
+        ("projection", array(def("ProjectionElem"))),
+    ]);
+    let borrow_kind = unit_variants(&["Shared", "Mut", "TwoPhaseMut", "Shallow"]);
+    let unop = unit_variants(&["Not", "Neg"]);
+    let cast_kind = unit_variants(&[
+        "IntToInt",
+        "BoolToInt",
+        "CharToInt",
+        "U8ToChar",
+        "EnumToInt",
+        "FloatToInt",
+        "IntToFloat",
+        "FloatToFloat",
+    ]);
+    let binop = unit_variants(&[
+        "BitXor", "BitAnd", "BitOr", "Eq", "Lt", "Le", "Ne", "Ge", "Gt", "Div", "Rem", "Add",
+        "Sub", "Mul", "Shl", "Shr", "Offset",
+    ]);
+    let operand = one_of(vec![
+        variant("Copy", def("Place")),
+        variant("Move", def("Place")),
+        variant("Const", tuple(vec![def("ETy"), def("ConstantValue")])),
+    ]);
+    let aggregate_kind = one_of(vec![
+        unit_variants(&["AggregatedTuple"]),
+        variant("AggregatedOption", tuple(vec![id(), def("ETy")])),
+        variant(
+            "AggregatedAdt",
+            tuple(vec![
+                id(),
+                option(id()),
+                array(def("ErasedRegion")),
+                array(def("ETy")),
+            ]),
+        ),
+        variant(
+            "AggregatedClosure",
+            tuple(vec![id(), array(def("CaptureMode"))]),
+        ),
+    ]);
+    let rvalue = one_of(vec![
+        variant("Use", def("Operand")),
+        variant("Ref", tuple(vec![def("Place"), def("BorrowKind")])),
+        variant("RawPtr", tuple(vec![def("Place"), def("RefKind")])),
+        variant("UnaryOp", tuple(vec![def("UnOp"), def("Operand")])),
+        variant(
+            "BinaryOp",
+            tuple(vec![def("BinOp"), def("Operand"), def("Operand")]),
+        ),
+        variant("Discriminant", def("Place")),
+        variant("Len", def("Place")),
+        variant(
+            "Aggregate",
+            tuple(vec![def("AggregateKind"), array(def("Operand"))]),
+        ),
+        variant("Global", id()),
+        variant("GlobalRef", tuple(vec![id(), def("RefKind")])),
+        variant("IsVariant", tuple(vec![def("Place"), array(id())])),
+        variant(
+            "Unsize",
+            tuple(vec![def("Operand"), def("ETy"), def("ETy")]),
+        ),
+        variant("FnPtr", id()),
+        variant(
+            "Cast",
+            tuple(vec![
+                def("CastKind"),
+                def("Operand"),
+                def("ETy"),
+                def("ETy"),
+            ]),
+        ),
+        variant(
+            "Slice",
+            object(vec![
+                ("base", def("Place")),
+                ("from", def("Operand")),
+                ("to", def("Operand")),
+                ("kind", def("BorrowKind")),
+            ]),
+        ),
+    ]);
+    vec![
+        ("FieldProjKind", field_proj_kind),
+        ("ProjectionElem", projection_elem),
+        ("Place", place),
+        ("BorrowKind", borrow_kind),
+        ("UnOp", unop),
+        ("CastKind", cast_kind),
+        ("BinOp", binop),
+        ("Operand", operand),
+        (
+            "CaptureMode",
+            unit_variants(&["ByRef", "ByMutRef", "ByValue"]),
+        ),
+        ("AggregateKind", aggregate_kind),
+        ("Rvalue", rvalue),
+    ]
+}
+
+/// See [crate::contracts]
+fn contract_definitions() -> Vec<(&'static str, Value)> {
+    let boxed = def("SpecExpr");
+    let path = array(string());
+    let spec_expr = one_of(vec![
+        variant("Var", id()),
+        variant("Bool", json!({ "type": "boolean" })),
+        variant("Int", tuple(vec![string(), option(def("IntegerTy"))])),
+        variant("UnaryOp", tuple(vec![def("UnOp"), boxed.clone()])),
+        variant(
+            "BinaryOp",
+            tuple(vec![def("BinOp"), boxed.clone(), boxed.clone()]),
+        ),
+        variant("And", tuple(vec![boxed.clone(), boxed.clone()])),
+        variant("Or", tuple(vec![boxed.clone(), boxed.clone()])),
+        variant("Deref", boxed.clone()),
+        variant("Field", tuple(vec![boxed.clone(), string()])),
+        variant("Index", tuple(vec![boxed.clone(), boxed.clone()])),
+        variant("Path", path.clone()),
+        variant("Call", tuple(vec![path, array(boxed.clone())])),
+        variant(
+            "MethodCall",
+            tuple(vec![boxed.clone(), string(), array(boxed)]),
+        ),
+    ]);
+    let spec = object(vec![("source", string()), ("expr", def("SpecExpr"))]);
+    let fun_contract = object(vec![
+        ("requires", array(def("Spec"))),
+        ("ensures", array(def("Spec"))),
+    ]);
+    vec![
+        ("SpecExpr", spec_expr),
+        ("Spec", spec),
+        ("FunContract", fun_contract),
+    ]
+}
+
+fn ullbc_definitions() -> Vec<(&'static str, Value)> {
+    let assert_kind = unit_variants(&["BoundsCheck", "Overflow", "DivisionByZero", "Other"]);
+    let assumed_fun_id = unit_variants(&[
+        "Replace",
+        "BoxNew",
+        "BoxDeref",
+        "BoxDerefMut",
+        "BoxFree",
+        "RcNew",
+        "RcClone",
+        "ArcNew",
+        "ArcClone",
+        "VecNew",
+        "VecPush",
+        "VecInsert",
+        "VecLen",
+        "VecIndex",
+        "VecIndexMut",
+        "OptionUnwrap",
+        "OptionExpect",
+        "OptionUnwrapOr",
+        "OptionIsSome",
+        "OptionIsNone",
+        "OptionMap",
+        "ResultUnwrap",
+        "ResultExpect",
+        "ResultIsOk",
+        "ResultIsErr",
+        "ResultMap",
+        "ResultMapErr",
+        "TryBranch",
+        "FromResidual",
+        "IntoIter",
+        "IteratorNext",
+    ]);
+    let fun_id = one_of(vec![
+        variant("Regular", id()),
+        variant("TraitMethod", tuple(vec![id(), id()])),
+        variant("Virtual", tuple(vec![id(), id()])),
+        variant("Indirect", def("Operand")),
+        variant("Assumed", def("AssumedFunId")),
+    ]);
+    let raw_statement = one_of(vec![
+        variant("Assign", tuple(vec![def("Place"), def("Rvalue")])),
+        variant("FakeRead", def("Place")),
+        variant("SetDiscriminant", tuple(vec![def("Place"), id()])),
+        variant("StorageDead", id()),
+        variant("Deinit", def("Place")),
+    ]);
+    let statement = with_optional_fields(
+        object(vec![
+            ("meta", def("Meta")),
+            ("content", def("RawStatement")),
+        ]),
+        vec![("expr_metas", def("ExprMetas"))],
+    );
+    // The targets of a `SwitchInt` are serialized as a list of pairs
+    let switch_targets = one_of(vec![
+        variant("If", tuple(vec![id(), id()])),
+        variant(
+            "SwitchInt",
+            tuple(vec![
+                def("IntegerTy"),
+                array(tuple(vec![def("ScalarValue"), id()])),
+                id(),
+            ]),
+        ),
+    ]);
+    let raw_terminator = one_of(vec![
+        unit_variants(&["Panic", "Return", "Unreachable"]),
+        variant("Goto", object(vec![("target", id())])),
+        variant(
+            "Switch",
+            object(vec![
+                ("discr", def("Operand")),
+                ("targets", def("SwitchTargets")),
+            ]),
+        ),
+        variant(
+            "Drop",
+            object(vec![("place", def("Place")), ("target", id())]),
+        ),
+        variant(
+            "Call",
+            object(vec![
+                ("func", def("FunId")),
+                ("region_args", array(def("ErasedRegion"))),
+                ("type_args", array(def("ETy"))),
+                ("args", array(def("Operand"))),
+                ("dest", def("Place")),
+                ("sig", option(def("FunSig"))),
+                ("target", id()),
+            ]),
+        ),
+        variant(
+            "Assert",
+            object(vec![
+                ("cond", def("Operand")),
+                ("expected", json!({ "type": "boolean" })),
+                ("kind", def("AssertKind")),
+                ("target", id()),
+            ]),
+        ),
+    ]);
+    let terminator = with_optional_fields(
+        object(vec![
+            ("meta", def("Meta")),
+            ("content", def("RawTerminator")),
+        ]),
+        vec![
+            ("expr_metas", def("ExprMetas")),
+            ("loop_invariants", array(def("Spec"))),
+        ],
+    );
+    let block_data = object(vec![
+        ("statements", array(def("Statement"))),
+        ("terminator", def("Terminator")),
+    ]);
+    vec![
+        ("AssertKind", assert_kind),
+        ("AssumedFunId", assumed_fun_id),
+        ("FunId", fun_id),
+        ("RawStatement", raw_statement),
+        ("Statement", statement),
+        ("SwitchTargets", switch_targets),
+        ("RawTerminator", raw_terminator),
+        ("Terminator", terminator),
+        ("BlockData", block_data),
+    ]
+}
+
+fn declaration_definitions() -> Vec<(&'static str, Value)> {
+    let var = object(vec![
+        ("index", id()),
+        ("name", option(string())),
+        ("ty", def("ETy")),
+    ]);
+    let expr_body = object(vec![
+        ("meta", def("Meta")),
+        ("arg_count", id()),
+        ("return_local", id()),
+        ("locals", array(def("Var"))),
+        ("body", array(def("BlockData"))),
+    ]);
+    let fun_sig = object(vec![
+        ("region_params", array(def("RegionVar"))),
+        ("num_early_bound_regions", id()),
+        ("regions_hierarchy", array(def("RegionGroup"))),
+        ("type_params", array(def("TypeVar"))),
+        ("const_generic_params", array(def("ConstGenericVar"))),
+        ("trait_clauses", array(def("TraitClause"))),
+        ("inputs", array(def("RTy"))),
+        ("output", def("RTy")),
+    ]);
+    let precision_loss = variant("InlineAsm", def("Meta"));
+    let unsafe_operation = variant("UnionFieldRead", def("Meta"));
+    let fun_decl = object(vec![
+        ("def_id", id()),
+        ("meta", def("Meta")),
+        ("name", def("Name")),
+        ("signature", def("FunSig")),
+        ("body", option(def("ExprBody"))),
+        ("precision_losses", array(def("PrecisionLoss"))),
+        ("unsafe_operations", array(def("UnsafeOperation"))),
+        ("attr_info", def("AttrInfo")),
+        ("contract", def("FunContract")),
+    ]);
+    let global_decl = object(vec![
+        ("def_id", id()),
+        ("meta", def("Meta")),
+        ("name", def("Name")),
+        ("ty", def("ETy")),
+        ("is_mutable", json!({ "type": "boolean" })),
+        ("interior_mutability", json!({ "type": "boolean" })),
+        ("body", option(def("ExprBody"))),
+        ("attr_info", def("AttrInfo")),
+    ]);
+    let trait_decl = object(vec![
+        ("def_id", id()),
+        ("meta", def("Meta")),
+        ("name", def("Name")),
+        ("methods", array(tuple(vec![string(), option(id())]))),
+    ]);
+    let trait_impl = object(vec![
+        ("def_id", id()),
+        ("meta", def("Meta")),
+        ("trait_id", id()),
+        ("self_type", option(id())),
+        ("methods", array(tuple(vec![string(), id()]))),
+    ]);
+    vec![
+        ("Var", var),
+        ("ExprBody", expr_body),
+        ("FunSig", fun_sig),
+        ("PrecisionLoss", precision_loss),
+        ("UnsafeOperation", unsafe_operation),
+        ("FunDecl", fun_decl),
+        ("GlobalDecl", global_decl),
+        ("TraitDecl", trait_decl),
+        ("TraitImpl", trait_impl),
+    ]
+}
+
+/// Return a JSON Schema describing the serialized ULLBC declarations.
+///
+/// The schema itself doesn't constrain the root value: the consumers should
+/// refer to its definitions, for instance `#/definitions/FunDecl`.
+pub fn emit_json_schema() -> Value {
+    let definitions: Map<String, Value> = meta_definitions()
+        .into_iter()
+        .chain(type_definitions())
+        .chain(value_definitions())
+        .chain(expression_definitions())
+        .chain(contract_definitions())
+        .chain(ullbc_definitions())
+        .chain(declaration_definitions())
+        .map(|(name, v)| (name.to_string(), v))
+        .collect();
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "ULLBC",
+        "definitions": definitions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::contracts::{Spec, SpecExpr};
+    use crate::expressions::*;
+    use crate::json_schema::*;
+    use crate::meta::{ExprMetas, Meta};
+    use crate::test_utils::*;
+    use crate::types::*;
+    use crate::ullbc_ast::*;
+    use crate::values::*;
+    use hashlink::linked_hash_map::LinkedHashMap;
+
+    /// A minimal validator, which supports the subset of JSON Schema we use
+    fn validate(schema: &Value, s: &Value, v: &Value) -> bool {
+        if let Some(Value::String(r)) = s.get("$ref") {
+            let name = r.strip_prefix("#/definitions/").unwrap();
+            return validate(schema, &schema["definitions"][name], v);
+        }
+        if let Some(Value::String(ty)) = s.get("type") {
+            let ok = match ty.as_str() {
+                "null" => v.is_null(),
+                "boolean" => v.is_boolean(),
+                "integer" => v.is_u64() || v.is_i64(),
+                "string" => v.is_string(),
+                "array" => v.is_array(),
+                "object" => v.is_object(),
+                _ => unreachable!(),
+            };
+            if !ok {
+                return false;
+            }
+        }
+        if let Some(Value::Array(values)) = s.get("enum") {
+            if !values.contains(v) {
+                return false;
+            }
+        }
+        if let (Some(min), Some(n)) = (s.get("minimum"), v.as_i64()) {
+            if n < min.as_i64().unwrap() {
+                return false;
+            }
+        }
+        if let (Some(max), Some(n)) = (s.get("maximum"), v.as_i64()) {
+            if n > max.as_i64().unwrap() {
+                return false;
+            }
+        }
+        if let Some(Value::Array(alts)) = s.get("oneOf") {
+            if alts.iter().filter(|alt| validate(schema, alt, v)).count() != 1 {
+                return false;
+            }
+        }
+        if let Some(Value::Array(alts)) = s.get("anyOf") {
+            if !alts.iter().any(|alt| validate(schema, alt, v)) {
+                return false;
+            }
+        }
+        if let Value::Object(fields) = v {
+            if let Some(Value::Array(required)) = s.get("required") {
+                if !required
+                    .iter()
+                    .all(|f| fields.contains_key(f.as_str().unwrap()))
+                {
+                    return false;
+                }
+            }
+            let properties = s.get("properties").and_then(|p| p.as_object());
+            for (name, field) in fields {
+                match properties.and_then(|p| p.get(name)) {
+                    Some(fs) => {
+                        if !validate(schema, fs, field) {
+                            return false;
+                        }
+                    }
+                    None => {
+                        if s.get("additionalProperties") == Some(&Value::Bool(false)) {
+                            return false;
+                        }
+                    }
+                }
+            }
+        }
+        if let Value::Array(elems) = v {
+            if let Some(min) = s.get("minItems") {
+                if elems.len() < min.as_u64().unwrap() as usize {
+                    return false;
+                }
+            }
+            if let Some(max) = s.get("maxItems") {
+                if elems.len() > max.as_u64().unwrap() as usize {
+                    return false;
+                }
+            }
+            match s.get("items") {
+                Some(Value::Array(items)) => {
+                    if !elems.iter().zip(items).all(|(e, i)| validate(schema, i, e)) {
+                        return false;
+                    }
+                }
+                Some(items) => {
+                    if !elems.iter().all(|e| validate(schema, items, e)) {
+                        return false;
+                    }
+                }
+                None => (),
+            }
+        }
+        true
+    }
+
+    fn mk_pair<R: Clone + Eq>(ty: Ty<R>) -> Ty<R> {
+        Ty::Adt(
+            TypeId::Tuple,
+            im::Vector::new(),
+            im::Vector::from(vec![ty.clone(), ty]),
+        )
+    }
+
+    /// ```text
+    /// fn f<'a>(x: u32, b: &'a mut (u8, u8)) -> u32 {
+    ///   bb0: switch move x { 0 => bb1, otherwise => bb2 }
+    ///   bb1: ret := const 0; (*b).0 := const 1; return
+    ///   bb2: tmp := copy x - const 1; ret := f(move tmp, move b) -> bb3
+    ///   bb3: return
+    /// }
+    /// ```
+    fn mk_rec_fun() -> FunDecl {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let u8_ty = Ty::Integer(IntegerTy::U8);
+        let b_ty = Ty::Ref(
+            ErasedRegion::Erased,
+            Box::new(mk_pair(u8_ty.clone())),
+            RefKind::Mut,
+        );
+        let mk_u32 =
+            |n| OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(ScalarValue::U32(n)));
+        let (ret, x, b, tmp) = (0, 1, 2, 3);
+        let place = |i| Place::new(VarId::Id::new(i));
+        let bb: Vec<BlockId::Id> = (0..4).map(BlockId::Id::new).collect();
+
+        let mut targets = LinkedHashMap::new();
+        targets.insert(ScalarValue::U32(0), bb[1]);
+        let field = Place {
+            var_id: VarId::Id::new(b),
+            projection: im::Vector::from(vec![
+                ProjectionElem::Deref,
+                ProjectionElem::Field(FieldProjKind::Tuple(2), FieldId::ZERO),
+            ]),
+        };
+        let body = mk_cfg(
+            2,
+            vec![
+                mk_var(ret, None, u32_ty.clone()),
+                mk_var(x, Some("x"), u32_ty.clone()),
+                mk_var(b, Some("b"), b_ty.clone()),
+                mk_var(tmp, None, u32_ty.clone()),
+            ],
+            vec![
+                mk_block(
+                    vec![],
+                    RawTerminator::Switch {
+                        discr: Operand::Move(place(x)),
+                        targets: SwitchTargets::SwitchInt(IntegerTy::U32, targets, bb[2]),
+                    },
+                ),
+                mk_block(
+                    vec![
+                        RawStatement::Assign(
+                            place(ret),
+                            Rvalue::Use(Operand::Const(u32_ty.clone(), mk_u32(0))),
+                        ),
+                        RawStatement::Assign(
+                            field,
+                            Rvalue::Use(Operand::Const(
+                                u8_ty,
+                                OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(
+                                    ScalarValue::U8(1),
+                                )),
+                            )),
+                        ),
+                    ],
+                    RawTerminator::Return,
+                ),
+                mk_block(
+                    vec![RawStatement::Assign(
+                        place(tmp),
+                        Rvalue::BinaryOp(
+                            BinOp::Sub,
+                            Operand::Copy(place(x)),
+                            Operand::Const(u32_ty.clone(), mk_u32(1)),
+                        ),
+                    )],
+                    RawTerminator::Call {
+                        func: FunId::Regular(FunDeclId::ZERO),
+                        region_args: vec![ErasedRegion::Erased],
+                        type_args: vec![],
+                        args: vec![Operand::Move(place(tmp)), Operand::Move(place(b))],
+                        dest: place(ret),
+                        sig: None,
+                        target: bb[3],
+                    },
+                ),
+                mk_block(vec![], RawTerminator::Return),
+            ],
+        );
+
+        let u32_rty = Ty::Integer(IntegerTy::U32);
+        let rid = RegionVarId::ZERO;
+        let mut sig = mk_sig(
+            vec![
+                u32_rty.clone(),
+                Ty::Ref(
+                    Region::Var(rid),
+                    Box::new(mk_pair(Ty::Integer(IntegerTy::U8))),
+                    RefKind::Mut,
+                ),
+            ],
+            u32_rty,
+        );
+        sig.region_params = RegionVarId::Vector::from(vec![RegionVar {
+            index: rid,
+            name: None,
+        }]);
+        mk_fun_decl(0, &["test", "f"], sig, Some(body))
+    }
+
+    #[test]
+    fn test_validate_fun_decl() {
+        let schema = emit_json_schema();
+        let fun_decl = serde_json::to_value(mk_rec_fun()).unwrap();
+        assert!(validate(&schema, &def("FunDecl"), &fun_decl));
+
+        // The spans of the expressions are only serialized if we know them
+        assert!(fun_decl["body"]["body"][2]["statements"][0]
+            .get("expr_metas")
+            .is_none());
+        let mut fun_decl1 = mk_rec_fun();
+        let body = fun_decl1.body.as_mut().unwrap();
+        let st = &mut body.body.get_mut(BlockId::Id::new(2)).unwrap().statements[0];
+        st.expr_metas = ExprMetas {
+            expr: Some(Meta::dummy()),
+            operands: vec![None, Some(Meta::dummy())],
+        };
+        let fun_decl1 = serde_json::to_value(fun_decl1).unwrap();
+        assert!(
+            fun_decl1["body"]["body"][2]["statements"][0]["expr_metas"]["operands"][0].is_null()
+        );
+        assert!(validate(&schema, &def("FunDecl"), &fun_decl1));
+
+        // `#[charon::requires(a.0 < 10u8)]`
+        let mut fun_decl2 = mk_rec_fun();
+        let field = SpecExpr::Field(Box::new(SpecExpr::Var(VarId::Id::new(1))), "0".to_string());
+        let bound = SpecExpr::Int("10".to_string(), Some(IntegerTy::U8));
+        fun_decl2.contract.requires.push(Spec {
+            source: "a.0 < 10u8".to_string(),
+            expr: SpecExpr::BinaryOp(BinOp::Lt, Box::new(field), Box::new(bound)),
+        });
+        let fun_decl2 = serde_json::to_value(fun_decl2).unwrap();
+        assert!(validate(&schema, &def("FunDecl"), &fun_decl2));
+
+        // Sanity check: the schema rejects ill-formed declarations
+        let mut missing_field = fun_decl.clone();
+        missing_field.as_object_mut().unwrap().remove("signature");
+        assert!(!validate(&schema, &def("FunDecl"), &missing_field));
+        let mut wrong_id = fun_decl;
+        wrong_id["body"]["locals"][0]["index"] = json!("0");
+        assert!(!validate(&schema, &def("FunDecl"), &wrong_id));
+    }
+
+    #[test]
+    fn test_validate_trait_impl() {
+        let schema = emit_json_schema();
+        // `impl Clone for S { fn clone(&self) -> S { ... } }`
+        let trait_impl = TraitImpl {
+            def_id: TraitImplId::ZERO,
+            meta: Meta::dummy(),
+            trait_id: TraitDeclId::ZERO,
+            self_type: Some(TypeDeclId::ZERO),
+            methods: vec![("clone".to_string(), FunDeclId::ZERO)],
+        };
+        let trait_impl = serde_json::to_value(trait_impl).unwrap();
+        assert!(validate(&schema, &def("TraitImpl"), &trait_impl));
+        assert!(!validate(&schema, &def("TraitDecl"), &trait_impl));
+    }
+}
@@ -0,0 +1,118 @@
+//! Recognize the `let ... else` bindings, like:
+//! ```text
+//! let Some(x) = opt else { return; };
+//! ```
+//! Those are desugared by rustc to a match whose failure branch diverges.
+//! Once the control-flow is reconstructed, we get:
+//! ```text
+//! match opt {
+//!   1 => {
+//!     x := move (opt as variant @1).0;
+//!     ...
+//!   }
+//!   _ => {
+//!     return
+//!   }
+//! }
+//! ```
+//! The analysis below recovers the binding, so that backends can model the
+//! fact that after the check the pattern is irrefutable.
+#![allow(dead_code)]
+
+use crate::divergent::statement_never_completes;
+use crate::expressions::Place;
+use crate::llbc_ast::{Statement, Switch};
+use crate::match_bindings::{get_arm_bindings, ArmBindings};
+
+/// A `let ... else` binding
+#[derive(Debug, Clone)]
+pub struct LetElse<'a> {
+    /// The variant of the pattern, and the variables it binds
+    pub pattern_bindings: ArmBindings,
+    /// The place we match over
+    pub value: &'a Place,
+    /// The branch taken if the value matches the pattern (it starts with the
+    /// assignments to the bound variables)
+    pub then_branch: &'a Statement,
+    /// The branch taken if the value doesn't match the pattern: it never
+    /// completes normally.
+    pub else_branch: &'a Statement,
+}
+
+/// Check if a switch comes from a `let ... else`: it must be a match with
+/// exactly one branch, for exactly one variant, and an `otherwise` branch
+/// which never completes normally.
+pub fn get_let_else(switch: &Switch) -> Option<LetElse<'_>> {
+    match switch {
+        Switch::Match(scrutinee, targets, otherwise, _) => {
+            let (variants, then_branch) = match targets.as_slice() {
+                [(variants, then_branch)] => (variants, then_branch),
+                _ => return None,
+            };
+            if !statement_never_completes(otherwise) {
+                return None;
+            }
+            let pattern_bindings = get_arm_bindings(scrutinee, variants, then_branch)?;
+            Some(LetElse {
+                pattern_bindings,
+                value: scrutinee,
+                then_branch,
+                else_branch: otherwise,
+            })
+        }
+        Switch::If(_, _, _) | Switch::SwitchInt(_, _, _, _) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::*;
+    use crate::let_else::*;
+    use crate::llbc_ast::{chain_statements, RawStatement};
+    use crate::meta::Meta;
+    use crate::types::*;
+    use crate::values::*;
+
+    fn mk_match(scrutinee: &Place, arm: Statement, otherwise: RawStatement) -> Switch {
+        let some = VariantId::Id::new(1);
+        Switch::Match(
+            scrutinee.clone(),
+            vec![(vec![some], arm)],
+            Box::new(Statement::new(Meta::dummy(), otherwise)),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_let_else() {
+        let opt = VarId::Id::new(1);
+        let x = VarId::Id::new(2);
+        let some = VariantId::Id::new(1);
+        let field = FieldId::Id::new(0);
+        let scrutinee = Place::new(opt);
+        let mut downcast = Place::new(opt);
+        downcast
+            .projection
+            .push_back(ProjectionElem::Field(FieldProjKind::Option(some), field));
+
+        // ```
+        // let Some(x) = opt else { return; };
+        // nop
+        // ```
+        let arm = chain_statements(
+            vec![Statement::new(
+                Meta::dummy(),
+                RawStatement::Assign(Place::new(x), Rvalue::Use(Operand::Move(downcast))),
+            )],
+            Statement::new(Meta::dummy(), RawStatement::Nop),
+        );
+        let switch = mk_match(&scrutinee, arm.clone(), RawStatement::Return);
+        let let_else = get_let_else(&switch).unwrap();
+        assert!(let_else.pattern_bindings == (some, vec![(field, x)]));
+        assert!(*let_else.value == scrutinee);
+
+        // The `otherwise` branch doesn't diverge: this is not a `let ... else`
+        let switch = mk_match(&scrutinee, arm, RawStatement::Nop);
+        assert!(get_let_else(&switch).is_none());
+    }
+}
@@ -44,9 +44,30 @@ extern crate take_mut;
 #[macro_use]
 pub mod common;
 pub mod assumed;
+pub mod call_index;
+pub mod call_signatures;
+pub mod callgraph;
+pub mod cfg_dot;
+pub mod check;
+pub mod check_calls;
+pub mod check_cfg;
+pub mod check_places;
+pub mod dominators;
+pub mod collapse_deref_ref;
+pub mod common_subexpressions;
+pub mod complete_switches;
+pub mod compound_assign;
+pub mod config;
+pub mod contracts;
+pub mod decls_index;
+pub mod diagnostics;
+pub mod closures;
 pub mod cli_options;
 pub mod divergent;
 pub mod driver;
+pub mod erase_phantoms;
+pub mod errors;
+pub mod expand_box_drops;
 pub mod export;
 pub mod expressions;
 pub mod expressions_utils;
@@ -57,26 +78,59 @@ pub mod gast_utils;
 pub mod generics;
 pub mod get_mir;
 pub mod graphs;
+pub mod html_report;
 pub mod id_vector;
+pub mod if_let_chains;
+pub mod incremental;
+pub mod index_accesses;
+pub mod inline_functions;
 pub mod insert_assign_return_unit;
+pub mod insert_overflow_checks;
+pub mod interp;
+pub mod json_schema;
+pub mod let_else;
 pub mod llbc_ast;
 pub mod llbc_ast_utils;
+pub mod llbc_builder;
+#[cfg(test)]
+pub mod llbc_gen;
+pub mod llbc_visitor;
 pub mod logger;
+pub mod match_bindings;
+pub mod match_provenance;
 pub mod meta;
 pub mod meta_utils;
+pub mod monomorphize;
 pub mod names;
 pub mod names_utils;
+pub mod packed_fields;
+pub mod passes;
+pub mod propagate_copies;
+pub mod range_loops;
+pub mod reaching_defs;
+pub mod recognize_matches;
+pub mod recursive_types;
 pub mod reconstruct_asserts;
+pub mod reconstruct_try;
 pub mod regions_hierarchy;
 pub mod register;
 pub mod regularize_constant_adts;
 pub mod remove_drop_never;
+pub mod remove_fake_reads;
 pub mod remove_read_discriminant;
+pub mod remove_redundant_set_discriminant;
 pub mod remove_unused_locals;
+pub mod relocate_decls;
 pub mod reorder_decls;
 pub mod rust_to_local_ids;
 pub mod simplify_ops;
+pub mod single_exit;
+pub mod slice_accesses;
+#[cfg(test)]
+pub mod test_utils;
+pub mod tool_attributes;
 pub mod translate_functions_to_ullbc;
+pub mod translate_traits;
 pub mod translate_types;
 pub mod types;
 pub mod types_utils;
@@ -85,3 +139,5 @@ pub mod ullbc_ast_utils;
 pub mod ullbc_to_llbc;
 pub mod values;
 pub mod values_utils;
+pub mod while_loops;
+pub mod workspace;
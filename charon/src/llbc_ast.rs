@@ -7,24 +7,26 @@
 //! from MIR to use Statement only.
 
 #![allow(dead_code)]
+use crate::contracts::Spec;
 use crate::expressions::*;
 pub use crate::llbc_ast_utils::*;
-use crate::meta::Meta;
+use crate::meta::{ExprMetas, Meta};
 use crate::types::*;
 use crate::ullbc_ast::*;
-pub use crate::ullbc_ast::{CtxNames, FunDeclId, GlobalDeclId, Var};
+pub use crate::ullbc_ast::{AssertKind, CtxNames, FunDeclId, GlobalDeclId, Var};
 use crate::values::*;
 use macros::{EnumAsGetters, EnumIsA, EnumToGetters, VariantIndexArity, VariantName};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Assert {
     pub cond: Operand,
     pub expected: bool,
+    pub kind: AssertKind,
 }
 
 /// TODO: factor out with [Rvalue]
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Call {
     pub func: FunId,
     /// Technically this is useless, but we still keep it because we might
@@ -32,16 +34,25 @@ pub struct Call {
     /// is as simple as possible - and in MIR we also have a vector of erased
     /// regions).
     pub region_args: Vec<ErasedRegion>,
+    /// The const generic arguments (if there are) come after the type
+    /// arguments, as [Ty::ConstGeneric].
     pub type_args: Vec<ETy>,
     pub args: Vec<Operand>,
     pub dest: Place,
+    /// The signature of the callee, if we preserve the regions (see the
+    /// corresponding field in [crate::ullbc_ast::RawTerminator::Call]).
+    pub sig: Option<Box<FunSig>>,
 }
 
 /// A raw statement: a statement without meta data.
-#[derive(Debug, Clone, EnumIsA, EnumToGetters, EnumAsGetters, Serialize)]
+#[derive(Debug, Clone, EnumIsA, EnumToGetters, EnumAsGetters, Serialize, Deserialize)]
 pub enum RawStatement<R> where
 R: Clone + std::cmp::Eq, {
     Assign(Place, Rvalue<R>),
+    /// A compound assignment `p op= rhs` (for instance: `x += 1`).
+    /// It is equivalent to `p := copy p op rhs`, and is only introduced
+    /// (by [crate::compound_assign]) if the user asks for it.
+    CompoundAssign(Place, BinOp, Operand),
     FakeRead(Place),
     SetDiscriminant(Place, VariantId::Id),
     Drop(Place),
@@ -70,15 +81,29 @@ R: Clone + std::cmp::Eq, {
     /// To ensure that, use [crate::llbc_ast_utils::new_sequence] to build sequences.
     Sequence(Box<Statement<R>>, Box<Statement<R>>),
     Switch(Switch<R>),
-    Loop(Box<Statement<R>>),
+    /// A loop, with the invariants the user gave for it (see
+    /// [crate::contracts])
+    Loop(Box<Statement<R>>, Vec<Spec>),
+    /// A `while` loop: `while { st; op } { body }`. The first statement
+    /// computes the condition (it is often `Nop`), then the operand is
+    /// evaluated: the loop exits if it is false, and executes the body
+    /// otherwise. The statement computing the condition doesn't contain
+    /// `break`, `continue` nor `return`: the `break`s and `continue`s of the
+    /// body count the `while` as a loop, like [RawStatement::Loop] (and
+    /// `continue 0` evaluates the condition again).
+    ///
+    /// We introduce it in [crate::while_loops], unless the user disables it.
+    While(Box<Statement<R>>, Operand, Box<Statement<R>>, Vec<Spec>),
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Statement<R> where
   R: Clone + std::cmp::Eq,
 {
     pub meta: Meta,
     pub content: RawStatement<R>,
+    #[serde(default, skip_serializing_if = "ExprMetas::is_empty")]
+    pub expr_metas: ExprMetas,
 }
 
 #[derive(Debug, Clone, EnumIsA, EnumToGetters, EnumAsGetters, VariantName, VariantIndexArity)]
@@ -110,7 +135,32 @@ pub enum Switch<R> {
     /// The match statement is introduced in [crate::remove_read_discriminant]
     /// (whenever we find a discriminant read, we merge it with the subsequent
     /// switch into a match)
-    Match(Place, Vec<(Vec<VariantId::Id>, Statement<R>)>, Box<Statement<R>>),
+    ///
+    /// The last field gives, for every branch, where it comes from in the
+    /// source. We only compute this information if the user asks for it (see
+    /// [crate::match_provenance]).
+    Match(
+        Place,
+        Vec<(Vec<VariantId::Id>, Statement<R>)>,
+        Box<Statement<R>>,
+        Option<Vec<ArmProvenance>>,
+    ),
+}
+
+/// The provenance of a branch of a [Switch::Match].
+///
+/// Note that an or-pattern (`A | B => ...`) gives a branch with several
+/// variants.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArmProvenance {
+    /// The span of the pattern. We recover it from the statements which bind
+    /// the variables of the pattern: it is `None` if the pattern doesn't bind
+    /// any variable.
+    pub pattern_meta: Option<Meta>,
+    /// `true` if the arm has a guard (`Some(x) if x > 0 => ...`). Once the
+    /// control-flow is reconstructed, the guard is an `if` nested in the
+    /// branch, whose `else` branch contains the code of the next arms.
+    pub guard: bool,
 }
 
 pub type ExprBody<R> = GExprBody<Statement<R>>;
@@ -4,20 +4,24 @@
 use std::ops::DerefMut;
 
 use crate::common::*;
+use crate::expressions::{Operand, Place};
 use crate::formatter::Formatter;
+use crate::if_let_chains::get_if_let_chain;
 use crate::llbc_ast::{
-    Call, ExprBody, FunDecl, FunDecls, GlobalDecl, GlobalDecls, RawStatement, Statement, Switch,
+    ArmProvenance, Call, ExprBody, FunDecl, FunDecls, GlobalDecl, GlobalDecls, RawStatement,
+    Statement, Switch,
 };
+use crate::llbc_visitor::{walk_statement_mut, AstMutVisitor};
 use crate::meta;
-use crate::meta::Meta;
+use crate::meta::{ExprMetas, Meta};
 use crate::types::*;
 use crate::ullbc_ast::{
-    fmt_call, CtxNames, FunDeclId, FunNamesFormatter, FunSigFormatter, GAstFormatter, GlobalDeclId,
-    GlobalNamesFormatter, TAB_INCR,
+    fmt_call, CtxNames, FunDeclId, FunId, FunNamesFormatter, FunSigFormatter, GAstFormatter,
+    GlobalDeclId, GlobalNamesFormatter, TAB_INCR,
 };
 use crate::values::*;
 use serde::ser::SerializeTupleVariant;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use take_mut::take;
 
 /// Goes from e.g. `(A; B; C) ; D` to `(A; (B; (C; D)))`.
@@ -74,7 +78,7 @@ pub fn combine_switch_targets_meta(targets: &Switch) -> Meta {
             let mbranches = meta::combine_meta_iter(branches);
             meta::combine_meta(&mbranches, &otherwise.meta)
         }
-        Switch::Match(_, branches, otherwise) => {
+        Switch::Match(_, branches, otherwise, _) => {
             let branches = branches.iter().map(|b| &b.1.meta);
             let mbranches = meta::combine_meta_iter(branches);
             meta::combine_meta(&mbranches, &otherwise.meta)
@@ -105,18 +109,19 @@ pub fn transform_statements<F: FnMut(Statement) -> Statement>(
                     *otherwise = transform_statements(f, *otherwise);
                     Switch::SwitchInt(op, int_ty, branches, otherwise)
                 }
-                Switch::Match(op, branches, mut otherwise) => {
+                Switch::Match(op, branches, mut otherwise, provenance) => {
                     let branches: Vec<(Vec<VariantId::Id>, Statement)> = branches
                         .into_iter()
                         .map(|x| (x.0, transform_statements(f, x.1)))
                         .collect();
                     *otherwise = transform_statements(f, *otherwise);
-                    Switch::Match(op, branches, otherwise)
+                    Switch::Match(op, branches, otherwise, provenance)
                 }
             };
             RawStatement::Switch(switch)
         }
         RawStatement::Assign(p, r) => RawStatement::Assign(p, r),
+        RawStatement::CompoundAssign(p, op, rhs) => RawStatement::CompoundAssign(p, op, rhs),
         RawStatement::Call(c) => RawStatement::Call(c),
         RawStatement::Assert(a) => RawStatement::Assert(a),
         RawStatement::FakeRead(p) => RawStatement::FakeRead(p),
@@ -132,9 +137,14 @@ pub fn transform_statements<F: FnMut(Statement) -> Statement>(
             let st2 = transform_statements(f, *st2);
             new_sequence(st1, st2).content
         }
-        RawStatement::Loop(mut st) => {
+        RawStatement::Loop(mut st, invariants) => {
             *st = transform_statements(f, *st);
-            RawStatement::Loop(st)
+            RawStatement::Loop(st, invariants)
+        }
+        RawStatement::While(mut cond, op, mut body, invariants) => {
+            *cond = transform_statements(f, *cond);
+            *body = transform_statements(f, *body);
+            RawStatement::While(cond, op, body, invariants)
         }
     };
 
@@ -142,6 +152,68 @@ pub fn transform_statements<F: FnMut(Statement) -> Statement>(
     f(st)
 }
 
+/// Apply a visitor on statements, in a top-down manner (the visitor is
+/// applied on a statement before being applied on its sub-statements).
+/// This is the read-only counterpart of [transform_statements].
+pub fn visit_statements<F: FnMut(&Statement)>(f: &mut F, st: &Statement) {
+    f(st);
+    match &st.content {
+        RawStatement::Switch(switch) => {
+            for st in switch.get_targets() {
+                visit_statements(f, st);
+            }
+        }
+        RawStatement::Sequence(st1, st2) => {
+            visit_statements(f, st1);
+            visit_statements(f, st2);
+        }
+        RawStatement::Loop(st, _) => visit_statements(f, st),
+        RawStatement::While(cond, _, body, _) => {
+            visit_statements(f, cond);
+            visit_statements(f, body);
+        }
+        RawStatement::Assign(_, _)
+        | RawStatement::CompoundAssign(_, _, _)
+        | RawStatement::Call(_)
+        | RawStatement::Assert(_)
+        | RawStatement::FakeRead(_)
+        | RawStatement::SetDiscriminant(_, _)
+        | RawStatement::Drop(_)
+        | RawStatement::Panic
+        | RawStatement::Return
+        | RawStatement::Break(_)
+        | RawStatement::Continue(_)
+        | RawStatement::Nop => (),
+    }
+}
+
+/// Apply a function to all the places (see [Statement::visit_places_mut])
+struct PlacesMutVisitor<'a>(&'a mut dyn FnMut(&mut Place));
+
+impl AstMutVisitor for PlacesMutVisitor<'_> {
+    fn visit_place_mut(&mut self, p: &mut Place) {
+        (self.0)(p)
+    }
+}
+
+/// Apply a transformer on the statements, bottom-up (see [Statement::transform])
+struct StatementTransformer<'a>(&'a mut dyn FnMut(&mut Statement));
+
+impl AstMutVisitor for StatementTransformer<'_> {
+    fn visit_statement_mut(&mut self, st: &mut Statement) {
+        walk_statement_mut(self, st);
+        if let RawStatement::Sequence(st1, _) = &st.content {
+            if st1.content.is_sequence() {
+                take(&mut st.content, |content| {
+                    let (st1, st2) = content.to_sequence();
+                    new_sequence(*st1, *st2).content
+                });
+            }
+        }
+        (self.0)(st)
+    }
+}
+
 impl Switch {
     pub fn get_targets(&self) -> Vec<&Statement> {
         match self {
@@ -156,7 +228,7 @@ impl Switch {
                 out.push(otherwise);
                 out
             }
-            Switch::Match(_, targets, otherwise) => {
+            Switch::Match(_, targets, otherwise, _) => {
                 let mut out: Vec<&Statement> = vec![];
                 for (_, tgt) in targets {
                     out.push(tgt);
@@ -199,7 +271,7 @@ impl Serialize for Switch {
                 vs.serialize_field(&targets)?;
                 vs.serialize_field(otherwise)?;
             }
-            Switch::Match(p, targets, otherwise) => {
+            Switch::Match(p, targets, otherwise, provenance) => {
                 vs.serialize_field(p)?;
                 let targets: Vec<(VecSerializer<VariantId::Id>, &Statement)> = targets
                     .iter()
@@ -208,15 +280,122 @@ impl Serialize for Switch {
                 let targets = VecSerializer::new(&targets);
                 vs.serialize_field(&targets)?;
                 vs.serialize_field(otherwise)?;
+                vs.serialize_field(provenance)?;
             }
         }
         vs.end()
     }
 }
 
+impl<'de> Deserialize<'de> for Switch {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Same shape as [Switch]: we only need a mirror because the
+        // serializer is custom.
+        #[derive(Deserialize)]
+        #[serde(rename = "Switch")]
+        enum SerializedSwitch {
+            If(Operand, Box<Statement>, Box<Statement>),
+            SwitchInt(
+                Operand,
+                IntegerTy,
+                Vec<(Vec<ScalarValue>, Statement)>,
+                Box<Statement>,
+            ),
+            Match(
+                Place,
+                Vec<(Vec<VariantId::Id>, Statement)>,
+                Box<Statement>,
+                Option<Vec<ArmProvenance>>,
+            ),
+        }
+
+        use SerializedSwitch as S;
+        Ok(match S::deserialize(deserializer)? {
+            S::If(op, e1, e2) => Switch::If(op, e1, e2),
+            S::SwitchInt(op, int_ty, targets, otherwise) => {
+                Switch::SwitchInt(op, int_ty, targets, otherwise)
+            }
+            S::Match(p, targets, otherwise, provenance) => {
+                Switch::Match(p, targets, otherwise, provenance)
+            }
+        })
+    }
+}
+
 impl Statement {
     pub fn new(meta: Meta, content: RawStatement) -> Self {
-        Statement { meta, content }
+        Statement {
+            meta,
+            content,
+            expr_metas: ExprMetas::default(),
+        }
+    }
+
+    /// Attach the spans of the expressions of the statement (see [ExprMetas])
+    pub fn with_expr_metas(mut self, expr_metas: ExprMetas) -> Self {
+        self.expr_metas = expr_metas;
+        self
+    }
+
+    /// Apply a transformer on the statement and its sub-statements, in a
+    /// bottom-up manner. This is the in-place counterpart of
+    /// [transform_statements]: the statements are updated rather than
+    /// rebuilt, which saves a lot of allocations when most of them are left
+    /// unchanged.
+    ///
+    /// If the transformer introduces sequences, we re-associate them so that
+    /// the left statements of the sequences are not sequences themselves.
+    pub fn transform(&mut self, f: &mut dyn FnMut(&mut Statement)) {
+        StatementTransformer(f).visit_statement_mut(self)
+    }
+
+    /// Apply a function to all the places which appear in the statement,
+    /// including in its sub-statements, in evaluation order.
+    pub fn visit_places_mut(&mut self, f: &mut dyn FnMut(&mut Place)) {
+        PlacesMutVisitor(f).visit_statement_mut(self)
+    }
+
+    /// Apply a function to the places which appear in the statement itself,
+    /// but not in its sub-statements (this is meant to be used with
+    /// [transform_statements], which takes care of the recursion).
+    pub fn transform_shallow_places(&mut self, f: &mut dyn FnMut(&mut Place)) {
+        match &mut self.content {
+            RawStatement::Assign(p, rv) => {
+                rv.transform_places(f);
+                f(p);
+            }
+            RawStatement::CompoundAssign(p, _, rhs) => {
+                rhs.transform_places(f);
+                f(p);
+            }
+            RawStatement::FakeRead(p)
+            | RawStatement::SetDiscriminant(p, _)
+            | RawStatement::Drop(p) => f(p),
+            RawStatement::Assert(assert) => assert.cond.transform_places(f),
+            RawStatement::Call(call) => {
+                if let FunId::Indirect(op) = &mut call.func {
+                    op.transform_places(f);
+                }
+                for arg in &mut call.args {
+                    arg.transform_places(f);
+                }
+                f(&mut call.dest);
+            }
+            RawStatement::Switch(Switch::If(op, _, _))
+            | RawStatement::Switch(Switch::SwitchInt(op, _, _, _)) => op.transform_places(f),
+            RawStatement::Switch(Switch::Match(p, _, _, _)) => f(p),
+            RawStatement::While(_, op, _, _) => op.transform_places(f),
+            RawStatement::Panic
+            | RawStatement::Return
+            | RawStatement::Break(_)
+            | RawStatement::Continue(_)
+            | RawStatement::Nop
+            | RawStatement::Sequence(_, _)
+            | RawStatement::Loop(_, _) => (),
+        }
     }
 
     pub fn fmt_with_ctx<'a, 'b, 'c, T>(&'a self, tab: &'b str, ctx: &'c T) -> String
@@ -237,6 +416,13 @@ impl Statement {
                 place.fmt_with_ctx(ctx),
                 rvalue.fmt_with_ctx(ctx),
             ),
+            RawStatement::CompoundAssign(place, binop, rhs) => format!(
+                "{}{} {}= {}",
+                tab,
+                place.fmt_with_ctx(ctx),
+                binop.to_string(),
+                rhs.fmt_with_ctx(ctx),
+            ),
             RawStatement::FakeRead(place) => {
                 format!("{}@fake_read({})", tab, place.fmt_with_ctx(ctx))
             }
@@ -262,6 +448,7 @@ impl Statement {
                     type_args,
                     args,
                     dest,
+                    sig: _,
                 } = call;
                 let call = fmt_call(ctx, func, region_args, type_args, args);
                 format!("{}{} := {}", tab, dest.fmt_with_ctx(ctx), call)
@@ -279,14 +466,35 @@ impl Statement {
             RawStatement::Switch(switch) => match switch {
                 Switch::If(discr, true_st, false_st) => {
                     let inner_tab = format!("{tab}{TAB_INCR}");
-                    format!(
-                        "{}if {} {{\n{}\n{}}}\n{}else {{\n{}\n{}}}",
+                    let mut out = format!(
+                        "{}if {} {{\n{}\n{}}}",
                         tab,
                         discr.fmt_with_ctx(ctx),
                         true_st.fmt_with_ctx(&inner_tab, ctx),
                         tab,
+                    );
+                    // If the `else` branch is itself a `if`, we print an
+                    // `else if` rather than nesting it (the reconstructed
+                    // `else if` chains would otherwise drift to the right)
+                    let mut else_st: &Statement = false_st;
+                    while let RawStatement::Switch(Switch::If(discr, true_st, false_st)) =
+                        &else_st.content
+                    {
+                        out = format!(
+                            "{}\n{}else if {} {{\n{}\n{}}}",
+                            out,
+                            tab,
+                            discr.fmt_with_ctx(ctx),
+                            true_st.fmt_with_ctx(&inner_tab, ctx),
+                            tab,
+                        );
+                        else_st = false_st;
+                    }
+                    format!(
+                        "{}\n{}else {{\n{}\n{}}}",
+                        out,
                         tab,
-                        false_st.fmt_with_ctx(&inner_tab, ctx),
+                        else_st.fmt_with_ctx(&inner_tab, ctx),
                         tab,
                     )
                 }
@@ -323,7 +531,47 @@ impl Statement {
                         tab
                     )
                 }
-                Switch::Match(discr, maps, otherwise) => {
+                Switch::Match(discr, maps, otherwise, _) => {
+                    // The `if let ... else if let ...` chains are desugared
+                    // to nested matches: we print them the way they appear
+                    // in the source (see [crate::if_let_chains])
+                    if let Some(chain) = get_if_let_chain(switch).filter(|c| c.arms.len() > 1) {
+                        let inner_tab = format!("{tab}{TAB_INCR}");
+                        let arms: Vec<String> = chain
+                            .arms
+                            .iter()
+                            .map(|arm| {
+                                let (variant_id, bindings) = &arm.pattern_bindings;
+                                let bindings: Vec<String> = bindings
+                                    .iter()
+                                    .map(|(field_id, var_id)| {
+                                        format!("{}: {}", field_id, ctx.format_object(*var_id))
+                                    })
+                                    .collect();
+                                let pattern = if bindings.is_empty() {
+                                    format!("@{variant_id}")
+                                } else {
+                                    format!("@{} {{ {} }}", variant_id, bindings.join(", "))
+                                };
+                                format!(
+                                    "if let {} = {} {{\n{}\n{}}}",
+                                    pattern,
+                                    arm.value.fmt_with_ctx(ctx),
+                                    arm.body.fmt_with_ctx(&inner_tab, ctx),
+                                    tab,
+                                )
+                            })
+                            .collect();
+                        return format!(
+                            "{}{}\n{}else {{\n{}\n{}}}",
+                            tab,
+                            arms.join(&format!("\n{tab}else ")),
+                            tab,
+                            chain.else_branch.fmt_with_ctx(&inner_tab, ctx),
+                            tab,
+                        );
+                    }
+
                     let inner_tab1 = format!("{tab}{TAB_INCR}");
                     let inner_tab2 = format!("{inner_tab1}{TAB_INCR}");
                     let mut maps: Vec<String> = maps
@@ -357,15 +605,47 @@ impl Statement {
                     )
                 }
             },
-            RawStatement::Loop(body) => {
+            RawStatement::Loop(body, invariants) => {
                 let inner_tab = format!("{tab}{TAB_INCR}");
+                let invariants: Vec<String> = invariants
+                    .iter()
+                    .map(|inv| format!("{tab}@invariant({})\n", inv.source))
+                    .collect();
                 format!(
-                    "{}loop {{\n{}\n{}}}",
+                    "{}{}loop {{\n{}\n{}}}",
+                    invariants.join(""),
                     tab,
                     body.fmt_with_ctx(&inner_tab, ctx),
                     tab
                 )
             }
+            RawStatement::While(cond, op, body, invariants) => {
+                let inner_tab = format!("{tab}{TAB_INCR}");
+                let invariants: Vec<String> = invariants
+                    .iter()
+                    .map(|inv| format!("{tab}@invariant({})\n", inv.source))
+                    .collect();
+                // We only print the block computing the condition if there is one
+                let cond = if cond.content.is_nop() {
+                    op.fmt_with_ctx(ctx)
+                } else {
+                    format!(
+                        "{{\n{}\n{}{}\n{}}}",
+                        cond.fmt_with_ctx(&inner_tab, ctx),
+                        inner_tab,
+                        op.fmt_with_ctx(ctx),
+                        tab
+                    )
+                };
+                format!(
+                    "{}{}while {} {{\n{}\n{}}}",
+                    invariants.join(""),
+                    tab,
+                    cond,
+                    body.fmt_with_ctx(&inner_tab, ctx),
+                    tab
+                )
+            }
         }
     }
 }
@@ -415,6 +695,11 @@ where
 }
 
 impl ExprBody {
+    /// Apply a function to all the places of the body
+    pub fn visit_places_mut(&mut self, f: &mut dyn FnMut(&mut Place)) {
+        self.body.visit_places_mut(f)
+    }
+
     pub fn fmt_with_decls<'ctx>(
         &self,
         ty_ctx: &'ctx TypeDecls,
@@ -547,3 +832,89 @@ impl GlobalDecl {
         self.fmt_with_names(ctx.type_context, ctx.fun_context, ctx.global_context)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::*;
+    use crate::llbc_ast::*;
+    use crate::meta::Meta;
+    use crate::types::TypeDecls;
+    use crate::ullbc_ast::{FunNamesFormatter, GAstFormatter, GlobalNamesFormatter};
+    use crate::values::VarId;
+
+    fn mk_if(discr: VarId::Id, st1: RawStatement, st2: RawStatement) -> RawStatement {
+        RawStatement::Switch(Switch::If(
+            Operand::Move(Place::new(discr)),
+            Box::new(Statement::new(Meta::dummy(), st1)),
+            Box::new(Statement::new(Meta::dummy(), st2)),
+        ))
+    }
+
+    #[test]
+    fn test_fmt_else_if() {
+        let b1 = VarId::Id::new(1);
+        let b2 = VarId::Id::new(2);
+        let st = Statement::new(
+            Meta::dummy(),
+            mk_if(
+                b1,
+                RawStatement::Return,
+                mk_if(b2, RawStatement::Panic, RawStatement::Nop),
+            ),
+        );
+
+        let type_defs = TypeDecls::new();
+        let fun_names = FunDeclId::Vector::new();
+        let global_names = GlobalDeclId::Vector::new();
+        let fun_ctx = FunNamesFormatter::new(&fun_names);
+        let global_ctx = GlobalNamesFormatter::new(&global_names);
+        let ctx = GAstFormatter::new(&type_defs, &fun_ctx, &global_ctx, None, None);
+
+        let expected = "\
+if move (var@1) {
+    return
+}
+else if move (var@2) {
+    panic
+}
+else {
+    nop
+}";
+        assert!(st.fmt_with_ctx("", &ctx) == expected);
+    }
+
+    #[test]
+    fn test_transform_in_place() {
+        let mk = |content| Statement::new(Meta::dummy(), content);
+        let x = VarId::Id::new(1);
+        let y = VarId::Id::new(2);
+        // `if move x { drop(x) } else { nop }; return`
+        let mut st = new_sequence(
+            mk(mk_if(
+                x,
+                RawStatement::Drop(Place::new(x)),
+                RawStatement::Nop,
+            )),
+            mk(RawStatement::Return),
+        );
+
+        // Rename `x` to `y` everywhere
+        st.visit_places_mut(&mut |p| p.var_id = y);
+        // Replace the `if` with `drop(y); nop`: the sequence must be
+        // re-associated
+        st.transform(&mut |st| {
+            if let RawStatement::Switch(Switch::If(_, st1, st2)) = &mut st.content {
+                let st1 = std::mem::replace(&mut **st1, mk(RawStatement::Nop));
+                let st2 = std::mem::replace(&mut **st2, mk(RawStatement::Nop));
+                st.content = RawStatement::Sequence(Box::new(st1), Box::new(st2));
+            }
+        });
+
+        // `drop(y); nop; return`
+        let (st1, st2) = st.content.to_sequence();
+        assert!(matches!(&st1.content, RawStatement::Drop(p) if p.var_id == y));
+        let (st2, st3) = st2.content.to_sequence();
+        assert!(st2.content.is_nop());
+        assert!(st3.content.is_return());
+    }
+}
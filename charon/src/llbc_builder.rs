@@ -0,0 +1,260 @@
+//! Helpers to build LLBC bodies programmatically, for instance to test the
+//! micro-passes or to synthesize stubs. For instance:
+//! ```text
+//! let mut b = BodyBuilder::new(Ty::Integer(IntegerTy::U32), vec![Ty::Bool]);
+//! let x = b.arg(0);
+//! let body = b.build(Switch::if_then_else(
+//!     Operand::Copy(Place::new(x)),
+//!     Block::new().assign(Place::new(b.ret()), Rvalue::Use(one)).ret(),
+//!     Block::new().panic(),
+//! ));
+//! ```
+//! The statements we build have dummy meta information (see [Meta::dummy]),
+//! unless stated otherwise, and the sequences are always right-nested (see
+//! [crate::llbc_ast::RawStatement::Sequence]).
+
+#![allow(dead_code)]
+use crate::expressions::*;
+use crate::llbc_ast::{
+    new_sequence, Assert, AssertKind, Call, ExprBody, RawStatement, Statement, Switch, Var,
+};
+use crate::meta::Meta;
+use crate::types::*;
+use crate::values::*;
+
+/// Build a function body: the builder takes care of generating the locals.
+pub struct BodyBuilder {
+    meta: Meta,
+    arg_count: usize,
+    locals: VarId::Vector<Var>,
+    var_counter: VarId::Generator,
+}
+
+impl BodyBuilder {
+    /// Create the return variable and the input arguments
+    pub fn new(output: ETy, inputs: Vec<ETy>) -> Self {
+        let mut b = BodyBuilder {
+            meta: Meta::dummy(),
+            arg_count: inputs.len(),
+            locals: VarId::Vector::new(),
+            var_counter: VarId::Generator::new(),
+        };
+        b.fresh_var(None, output);
+        for ty in inputs {
+            b.fresh_var(None, ty);
+        }
+        b
+    }
+
+    pub fn with_meta(mut self, meta: Meta) -> Self {
+        self.meta = meta;
+        self
+    }
+
+    /// The local used for the return value
+    pub fn ret(&self) -> VarId::Id {
+        VarId::ZERO
+    }
+
+    /// The local of the i-th input argument (starting at 0)
+    pub fn arg(&self, i: usize) -> VarId::Id {
+        assert!(i < self.arg_count);
+        VarId::Id::new(i + 1)
+    }
+
+    pub fn fresh_var(&mut self, name: Option<String>, ty: ETy) -> VarId::Id {
+        let index = self.var_counter.fresh_id();
+        self.locals.push_back(Var { index, name, ty });
+        index
+    }
+
+    pub fn build(self, body: Statement) -> ExprBody {
+        ExprBody {
+            meta: self.meta,
+            arg_count: self.arg_count,
+            return_local: VarId::ZERO,
+            locals: self.locals,
+            body,
+        }
+    }
+}
+
+/// Build a block of statements, which is terminated by one of [Block::ret],
+/// [Block::panic], [Block::brk], [Block::cont], [Block::then] or
+/// [Block::end].
+pub struct Block {
+    meta: Meta,
+    statements: Vec<Statement>,
+}
+
+impl Default for Block {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Block {
+    pub fn new() -> Self {
+        Block {
+            meta: Meta::dummy(),
+            statements: Vec::new(),
+        }
+    }
+
+    /// The meta information used for the statements pushed from now on
+    pub fn with_meta(mut self, meta: Meta) -> Self {
+        self.meta = meta;
+        self
+    }
+
+    pub fn push(mut self, content: RawStatement) -> Self {
+        self.statements.push(Statement::new(self.meta, content));
+        self
+    }
+
+    /// Push a statement which was built independently (a switch, a loop,
+    /// etc.)
+    pub fn push_statement(mut self, st: Statement) -> Self {
+        self.statements.push(st);
+        self
+    }
+
+    pub fn assign(self, p: Place, rv: Rvalue) -> Self {
+        self.push(RawStatement::Assign(p, rv))
+    }
+
+    pub fn call(self, call: Call) -> Self {
+        self.push(RawStatement::Call(call))
+    }
+
+    pub fn assert(self, cond: Operand, expected: bool) -> Self {
+        self.push(RawStatement::Assert(Assert {
+            cond,
+            expected,
+            kind: AssertKind::Other,
+        }))
+    }
+
+    pub fn drop(self, p: Place) -> Self {
+        self.push(RawStatement::Drop(p))
+    }
+
+    /// Terminate the block with the given statement
+    pub fn then(self, last: Statement) -> Statement {
+        // We fold with [new_sequence] rather than using
+        // [crate::llbc_ast::chain_statements], because the statements which
+        // were pushed may themselves be sequences.
+        self.statements
+            .into_iter()
+            .rev()
+            .fold(last, |cont, st| new_sequence(st, cont))
+    }
+
+    fn terminate(self, content: RawStatement) -> Statement {
+        let last = Statement::new(self.meta, content);
+        self.then(last)
+    }
+
+    pub fn ret(self) -> Statement {
+        self.terminate(RawStatement::Return)
+    }
+
+    pub fn panic(self) -> Statement {
+        self.terminate(RawStatement::Panic)
+    }
+
+    pub fn brk(self, i: usize) -> Statement {
+        self.terminate(RawStatement::Break(i))
+    }
+
+    pub fn cont(self, i: usize) -> Statement {
+        self.terminate(RawStatement::Continue(i))
+    }
+
+    /// Terminate the block with a `nop`, so that the execution continues
+    /// after it (for instance, for the branches of an `if`).
+    pub fn end(self) -> Statement {
+        self.terminate(RawStatement::Nop)
+    }
+}
+
+impl Switch {
+    pub fn if_then_else(
+        cond: Operand,
+        then_branch: Statement,
+        else_branch: Statement,
+    ) -> Statement {
+        let switch = Switch::If(cond, Box::new(then_branch), Box::new(else_branch));
+        Statement::new(Meta::dummy(), RawStatement::Switch(switch))
+    }
+
+    /// A match over the variants of an enumeration
+    pub fn match_variants(
+        scrutinee: Place,
+        branches: Vec<(Vec<VariantId::Id>, Statement)>,
+        otherwise: Statement,
+    ) -> Statement {
+        let switch = Switch::Match(scrutinee, branches, Box::new(otherwise), None);
+        Statement::new(Meta::dummy(), RawStatement::Switch(switch))
+    }
+
+    pub fn switch_int(
+        discr: Operand,
+        int_ty: IntegerTy,
+        branches: Vec<(Vec<ScalarValue>, Statement)>,
+        otherwise: Statement,
+    ) -> Statement {
+        let switch = Switch::SwitchInt(discr, int_ty, branches, Box::new(otherwise));
+        Statement::new(Meta::dummy(), RawStatement::Switch(switch))
+    }
+}
+
+/// Build a loop
+pub fn loop_(body: Statement) -> Statement {
+    Statement::new(
+        Meta::dummy(),
+        RawStatement::Loop(Box::new(body), Vec::new()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::id_vector::ToUsize;
+    use crate::llbc_builder::*;
+
+    #[test]
+    fn test_builder() {
+        let mut b = BodyBuilder::new(Ty::Integer(IntegerTy::U32), vec![Ty::Bool]);
+        let x = b.arg(0);
+        let y = b.fresh_var(Some("y".to_string()), Ty::Integer(IntegerTy::U32));
+        assert!(y.to_usize() == 2);
+
+        // The blocks pushed in a block are flattened
+        let inner = Block::new().drop(Place::new(y)).end();
+        let st = Block::new()
+            .push_statement(inner)
+            .assign(
+                Place::new(b.ret()),
+                Rvalue::Use(Operand::Move(Place::new(y))),
+            )
+            .ret();
+        let body = b.build(Switch::if_then_else(
+            Operand::Copy(Place::new(x)),
+            st,
+            Block::new().panic(),
+        ));
+        assert!(body.arg_count == 1 && body.locals.len() == 3);
+
+        // `drop(y); nop; @return := move y; return`
+        let (_, then_branch, _) = body.body.content.to_switch().to_if();
+        let mut sts = Vec::new();
+        let mut st = *then_branch;
+        while let RawStatement::Sequence(st1, st2) = st.content {
+            assert!(!st1.content.is_sequence());
+            sts.push(st1.content);
+            st = *st2;
+        }
+        assert!(sts.len() == 3 && sts[0].is_drop() && sts[1].is_nop());
+        assert!(st.content.is_return());
+    }
+}
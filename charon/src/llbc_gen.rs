@@ -0,0 +1,598 @@
+//! Random generation of well-typed LLBC bodies, for the property-based tests.
+//!
+//! We generate functions with a fixed set of locals, of type `u32`, `bool`
+//! and `(u32, bool)` (the values supported by the interpreter, see
+//! [crate::interp]), whose bodies assign those locals, test them and loop
+//! over them. The generated bodies don't use the references nor call other
+//! functions.
+//!
+//! We also lower the LLBC bodies to ULLBC (see [lower_body]), to test the
+//! control-flow reconstruction: reconstructing the control-flow of a lowered
+//! body must give a body with the same semantics.
+#![allow(dead_code)]
+
+use crate::contracts::FunContract;
+use crate::expressions::*;
+use crate::id_vector::ToUsize;
+use crate::llbc_ast::*;
+use crate::meta::{AttrInfo, Meta};
+use crate::test_utils::{mk_fun_decl, mk_sig};
+use crate::types::*;
+use crate::ullbc_ast as src;
+use crate::values::*;
+use hashlink::linked_hash_map::LinkedHashMap;
+use proptest::prelude::*;
+
+/// The maximal nesting of the switches and the loops
+const MAX_DEPTH: u32 = 3;
+
+fn u32_ty() -> ETy {
+    Ty::Integer(IntegerTy::U32)
+}
+
+fn pair_ty() -> ETy {
+    Ty::Adt(
+        TypeId::Tuple,
+        im::Vector::new(),
+        im::vector![u32_ty(), Ty::Bool],
+    )
+}
+
+/// The locals of the generated functions: `@0: u32`, the arguments `a: u32`
+/// and `b: bool`, then `x: u32`, `y: u32`, `c: bool` and `p: (u32, bool)`
+fn locals() -> VarId::Vector<Var> {
+    let tys = [
+        (None, u32_ty()),
+        (Some("a"), u32_ty()),
+        (Some("b"), Ty::Bool),
+        (Some("x"), u32_ty()),
+        (Some("y"), u32_ty()),
+        (Some("c"), Ty::Bool),
+        (Some("p"), pair_ty()),
+    ];
+    let locals: Vec<Var> = tys
+        .iter()
+        .enumerate()
+        .map(|(i, (name, ty))| Var {
+            index: VarId::Id::new(i),
+            name: name.map(String::from),
+            ty: ty.clone(),
+        })
+        .collect();
+    VarId::Vector::from(locals)
+}
+
+const PAIR_LOCAL: usize = 6;
+
+fn pair_field(field_id: usize) -> Place {
+    let mut p = Place::new(VarId::Id::new(PAIR_LOCAL));
+    let kind = FieldProjKind::Tuple(2);
+    p.projection
+        .push_back(ProjectionElem::Field(kind, FieldId::Id::new(field_id)));
+    p
+}
+
+fn int_place() -> impl Strategy<Value = Place> {
+    prop_oneof![
+        prop::sample::select(vec![0, 1, 3, 4]).prop_map(|i| Place::new(VarId::Id::new(i))),
+        Just(pair_field(0)),
+    ]
+}
+
+fn bool_place() -> impl Strategy<Value = Place> {
+    prop_oneof![
+        prop::sample::select(vec![2, 5]).prop_map(|i| Place::new(VarId::Id::new(i))),
+        Just(pair_field(1)),
+    ]
+}
+
+fn int_const(v: u32) -> Operand {
+    let v = PrimitiveValue::Scalar(ScalarValue::U32(v));
+    Operand::Const(u32_ty(), OperandConstantValue::PrimitiveValue(v))
+}
+
+fn bool_const(b: bool) -> Operand {
+    let b = PrimitiveValue::Bool(b);
+    Operand::Const(Ty::Bool, OperandConstantValue::PrimitiveValue(b))
+}
+
+fn int_operand() -> impl Strategy<Value = Operand> {
+    // We favor the small constants, which don't overflow
+    prop_oneof![
+        int_place().prop_map(Operand::Copy),
+        (0u32..8).prop_map(int_const),
+        any::<u32>().prop_map(int_const),
+    ]
+}
+
+fn bool_operand() -> impl Strategy<Value = Operand> {
+    prop_oneof![
+        bool_place().prop_map(Operand::Copy),
+        any::<bool>().prop_map(bool_const),
+    ]
+}
+
+fn int_rvalue() -> impl Strategy<Value = Rvalue> {
+    let binop = prop::sample::select(vec![
+        BinOp::Add,
+        BinOp::Sub,
+        BinOp::Mul,
+        BinOp::Div,
+        BinOp::Rem,
+        BinOp::BitAnd,
+        BinOp::BitOr,
+        BinOp::BitXor,
+        BinOp::Shl,
+        BinOp::Shr,
+    ]);
+    prop_oneof![
+        int_operand().prop_map(Rvalue::Use),
+        (binop, int_operand(), int_operand())
+            .prop_map(|(binop, op1, op2)| Rvalue::BinaryOp(binop, op1, op2)),
+    ]
+}
+
+fn bool_rvalue() -> impl Strategy<Value = Rvalue> {
+    let cmp = prop::sample::select(vec![
+        BinOp::Eq,
+        BinOp::Ne,
+        BinOp::Lt,
+        BinOp::Le,
+        BinOp::Gt,
+        BinOp::Ge,
+    ]);
+    let logical = prop::sample::select(vec![BinOp::BitAnd, BinOp::BitOr, BinOp::BitXor]);
+    prop_oneof![
+        bool_operand().prop_map(Rvalue::Use),
+        bool_operand().prop_map(|op| Rvalue::UnaryOp(UnOp::Not, op)),
+        (cmp, int_operand(), int_operand())
+            .prop_map(|(binop, op1, op2)| Rvalue::BinaryOp(binop, op1, op2)),
+        (logical, bool_operand(), bool_operand())
+            .prop_map(|(binop, op1, op2)| Rvalue::BinaryOp(binop, op1, op2)),
+    ]
+}
+
+fn mk(content: RawStatement) -> Statement {
+    Statement::new(Meta::dummy(), content)
+}
+
+/// A statement which doesn't contain other statements. `loops` is the number
+/// of loops around the statement.
+fn leaf_statement(loops: usize) -> BoxedStrategy<Statement> {
+    let pair = (int_operand(), bool_operand()).prop_map(|(op1, op2)| {
+        let p = Place::new(VarId::Id::new(PAIR_LOCAL));
+        RawStatement::Assign(p, Rvalue::Aggregate(AggregateKind::Tuple, vec![op1, op2]))
+    });
+    let assert = (bool_operand(), any::<bool>()).prop_map(|(cond, expected)| {
+        RawStatement::Assert(Assert {
+            cond,
+            expected,
+            kind: AssertKind::Other,
+        })
+    });
+    let mut leaves = vec![
+        (int_place(), int_rvalue())
+            .prop_map(|(p, rv)| RawStatement::Assign(p, rv))
+            .boxed(),
+        (bool_place(), bool_rvalue())
+            .prop_map(|(p, rv)| RawStatement::Assign(p, rv))
+            .boxed(),
+        pair.boxed(),
+        assert.boxed(),
+        Just(RawStatement::Return).boxed(),
+        Just(RawStatement::Panic).boxed(),
+    ];
+    if loops > 0 {
+        leaves.push((0..loops).prop_map(RawStatement::Break).boxed());
+        leaves.push((0..loops).prop_map(RawStatement::Continue).boxed());
+    }
+    prop::strategy::Union::new(leaves).prop_map(mk).boxed()
+}
+
+/// A statement, whose switches and loops are nested at most `depth` times
+fn statement(loops: usize, depth: u32) -> BoxedStrategy<Statement> {
+    if depth == 0 {
+        return leaf_statement(loops);
+    }
+    let if_then_else = (
+        bool_operand(),
+        block(loops, depth - 1),
+        block(loops, depth - 1),
+    )
+        .prop_map(|(cond, st1, st2)| {
+            RawStatement::Switch(Switch::If(cond, Box::new(st1), Box::new(st2)))
+        });
+    let targets = prop::collection::btree_set(0u32..8, 1..4).prop_flat_map(move |values| {
+        let values: Vec<u32> = values.into_iter().collect();
+        let branches = prop::collection::vec(block(loops, depth - 1), values.len());
+        (Just(values), branches)
+    });
+    let switch_int = (int_operand(), targets, block(loops, depth - 1)).prop_map(
+        |(discr, (values, branches), otherwise)| {
+            let targets = values
+                .into_iter()
+                .zip(branches.into_iter())
+                .map(|(v, st)| (vec![ScalarValue::U32(v)], st))
+                .collect();
+            let switch = Switch::SwitchInt(discr, IntegerTy::U32, targets, Box::new(otherwise));
+            RawStatement::Switch(switch)
+        },
+    );
+    let loop_ =
+        block(loops + 1, depth - 1).prop_map(|st| RawStatement::Loop(Box::new(st), Vec::new()));
+    prop_oneof![
+        4 => leaf_statement(loops),
+        1 => if_then_else.prop_map(mk),
+        1 => switch_int.prop_map(mk),
+        1 => loop_.prop_map(mk),
+    ]
+    .boxed()
+}
+
+/// A sequence of statements, ending with a `nop`
+fn block(loops: usize, depth: u32) -> BoxedStrategy<Statement> {
+    prop::collection::vec(statement(loops, depth), 0..4)
+        .prop_map(|sts| chain_statements(sts, mk(RawStatement::Nop)))
+        .boxed()
+}
+
+/// A function `f(a: u32, b: bool) -> u32`. Its body initializes all the
+/// locals, so that the generated statements can read them.
+///
+/// We only generate bodies which we can lower to ULLBC (see [lower_body]):
+/// the statements above don't contain compound assignments nor matches, and
+/// we filter the bodies to make this restriction explicit.
+pub fn fun_decl() -> impl Strategy<Value = FunDecl> {
+    (
+        any::<u32>(),
+        prop::collection::vec(statement(0, MAX_DEPTH), 0..6),
+    )
+        .prop_map(|(init, sts)| {
+            let mut body = vec![
+                RawStatement::Assign(Place::new(VarId::ZERO), Rvalue::Use(int_const(init))),
+                RawStatement::Assign(Place::new(VarId::Id::new(3)), Rvalue::Use(int_const(0))),
+                RawStatement::Assign(Place::new(VarId::Id::new(4)), Rvalue::Use(int_const(1))),
+                RawStatement::Assign(
+                    Place::new(VarId::Id::new(5)),
+                    Rvalue::Use(bool_const(false)),
+                ),
+                RawStatement::Assign(
+                    Place::new(VarId::Id::new(PAIR_LOCAL)),
+                    Rvalue::Aggregate(AggregateKind::Tuple, vec![int_const(0), bool_const(true)]),
+                ),
+            ]
+            .into_iter()
+            .map(mk)
+            .collect::<Vec<Statement>>();
+            body.extend(sts);
+            let sig = mk_sig(
+                vec![Ty::Integer(IntegerTy::U32), Ty::Bool],
+                Ty::Integer(IntegerTy::U32),
+            );
+            let body = ExprBody {
+                meta: Meta::dummy(),
+                arg_count: 2,
+                return_local: VarId::ZERO,
+                locals: locals(),
+                body: chain_statements(body, mk(RawStatement::Return)),
+            };
+            mk_fun_decl(0, &["test", "f"], sig, Some(body))
+        })
+        .prop_filter("the body can be lowered to ULLBC", |fun| {
+            fun.body.as_ref().map_or(true, |b| lower_body(b).is_some())
+        })
+}
+
+/// Lower an LLBC body to a control-flow graph: we give one block to every
+/// statement. See [lower_body].
+struct Lowering {
+    blocks: Vec<Option<src::BlockData>>,
+}
+
+impl Lowering {
+    fn reserve(&mut self) -> src::BlockId::Id {
+        self.blocks.push(None);
+        src::BlockId::Id::new(self.blocks.len() - 1)
+    }
+
+    fn set(
+        &mut self,
+        id: src::BlockId::Id,
+        statements: Vec<src::Statement>,
+        t: src::RawTerminator,
+    ) {
+        let terminator = src::Terminator::new(Meta::dummy(), t);
+        self.blocks[id.to_usize()] = Some(src::BlockData {
+            statements,
+            terminator,
+        });
+    }
+
+    fn push(&mut self, statements: Vec<src::Statement>, t: src::RawTerminator) -> src::BlockId::Id {
+        let id = self.reserve();
+        self.set(id, statements, t);
+        id
+    }
+
+    /// Lower a statement, whose execution continues with the block `next`.
+    /// `loops` gives the entries and the exits of the enclosing loops, the
+    /// innermost loop last. Returns the entry block of the statement, or
+    /// `None` if the statement has no counterpart in ULLBC (the compound
+    /// assignments and the matches).
+    fn lower(
+        &mut self,
+        st: &Statement,
+        next: src::BlockId::Id,
+        loops: &mut Vec<(src::BlockId::Id, src::BlockId::Id)>,
+    ) -> Option<src::BlockId::Id> {
+        let goto = src::RawTerminator::Goto { target: next };
+        let statement = |content| vec![src::Statement::new(Meta::dummy(), content)];
+        let entry = match &st.content {
+            RawStatement::Assign(p, rv) => self.push(
+                statement(src::RawStatement::Assign(p.clone(), rv.clone())),
+                goto,
+            ),
+            RawStatement::FakeRead(p) => {
+                self.push(statement(src::RawStatement::FakeRead(p.clone())), goto)
+            }
+            RawStatement::SetDiscriminant(p, variant_id) => {
+                let content = src::RawStatement::SetDiscriminant(p.clone(), *variant_id);
+                self.push(statement(content), goto)
+            }
+            RawStatement::CompoundAssign(..) => return None,
+            RawStatement::Drop(p) => self.push(
+                Vec::new(),
+                src::RawTerminator::Drop {
+                    place: p.clone(),
+                    target: next,
+                },
+            ),
+            RawStatement::Assert(assert) => self.push(
+                Vec::new(),
+                src::RawTerminator::Assert {
+                    cond: assert.cond.clone(),
+                    expected: assert.expected,
+                    kind: assert.kind,
+                    target: next,
+                },
+            ),
+            RawStatement::Call(call) => self.push(
+                Vec::new(),
+                src::RawTerminator::Call {
+                    func: call.func.clone(),
+                    region_args: call.region_args.clone(),
+                    type_args: call.type_args.clone(),
+                    args: call.args.clone(),
+                    dest: call.dest.clone(),
+                    sig: call.sig.clone(),
+                    target: next,
+                },
+            ),
+            RawStatement::Panic => self.push(Vec::new(), src::RawTerminator::Panic),
+            RawStatement::Return => self.push(Vec::new(), src::RawTerminator::Return),
+            RawStatement::Break(i) => loops[loops.len() - 1 - i].1,
+            RawStatement::Continue(i) => loops[loops.len() - 1 - i].0,
+            RawStatement::Nop => next,
+            RawStatement::Sequence(st1, st2) => {
+                let next = self.lower(st2, next, loops)?;
+                self.lower(st1, next, loops)?
+            }
+            RawStatement::Switch(Switch::If(cond, st1, st2)) => {
+                let then_tgt = self.lower(st1, next, loops)?;
+                let else_tgt = self.lower(st2, next, loops)?;
+                let targets = src::SwitchTargets::If(then_tgt, else_tgt);
+                let discr = cond.clone();
+                self.push(Vec::new(), src::RawTerminator::Switch { discr, targets })
+            }
+            RawStatement::Switch(Switch::SwitchInt(discr, int_ty, branches, otherwise)) => {
+                let mut targets = LinkedHashMap::new();
+                for (values, st) in branches {
+                    let target = self.lower(st, next, loops)?;
+                    for v in values {
+                        targets.insert(v.clone(), target);
+                    }
+                }
+                let otherwise = self.lower(otherwise, next, loops)?;
+                let targets = src::SwitchTargets::SwitchInt(*int_ty, targets, otherwise);
+                let discr = discr.clone();
+                self.push(Vec::new(), src::RawTerminator::Switch { discr, targets })
+            }
+            RawStatement::Switch(Switch::Match(..)) => return None,
+            RawStatement::Loop(body, _) => {
+                // The entry of the loop jumps to its body: we need the entry
+                // to lower the body
+                let entry = self.reserve();
+                loops.push((entry, next));
+                let body = self.lower(body, entry, loops)?;
+                loops.pop();
+                self.set(entry, Vec::new(), src::RawTerminator::Goto { target: body });
+                entry
+            }
+            RawStatement::While(cond, op, body, _) => {
+                // Same as for the loops: the entry evaluates the condition
+                let entry = self.reserve();
+                loops.push((entry, next));
+                let body = self.lower(body, entry, loops)?;
+                loops.pop();
+                let targets = src::SwitchTargets::If(body, next);
+                let discr = op.clone();
+                let test = self.push(Vec::new(), src::RawTerminator::Switch { discr, targets });
+                let cond = self.lower(cond, test, loops)?;
+                self.set(entry, Vec::new(), src::RawTerminator::Goto { target: cond });
+                entry
+            }
+        };
+        Some(entry)
+    }
+}
+
+fn terminator_targets(t: &src::RawTerminator) -> Vec<src::BlockId::Id> {
+    match t {
+        src::RawTerminator::Goto { target }
+        | src::RawTerminator::Drop { target, .. }
+        | src::RawTerminator::Call { target, .. }
+        | src::RawTerminator::Assert { target, .. } => vec![*target],
+        src::RawTerminator::Switch { targets, .. } => match targets {
+            src::SwitchTargets::If(then_tgt, else_tgt) => vec![*then_tgt, *else_tgt],
+            src::SwitchTargets::SwitchInt(_, targets, otherwise) => {
+                let mut tgts: Vec<src::BlockId::Id> = targets.values().copied().collect();
+                tgts.push(*otherwise);
+                tgts
+            }
+        },
+        src::RawTerminator::Panic
+        | src::RawTerminator::Return
+        | src::RawTerminator::Unreachable => Vec::new(),
+    }
+}
+
+fn rename_targets(
+    t: &mut src::RawTerminator,
+    rename: &dyn Fn(src::BlockId::Id) -> src::BlockId::Id,
+) {
+    match t {
+        src::RawTerminator::Goto { target }
+        | src::RawTerminator::Drop { target, .. }
+        | src::RawTerminator::Call { target, .. }
+        | src::RawTerminator::Assert { target, .. } => *target = rename(*target),
+        src::RawTerminator::Switch { targets, .. } => match targets {
+            src::SwitchTargets::If(then_tgt, else_tgt) => {
+                *then_tgt = rename(*then_tgt);
+                *else_tgt = rename(*else_tgt);
+            }
+            src::SwitchTargets::SwitchInt(_, targets, otherwise) => {
+                for target in targets.values_mut() {
+                    *target = rename(*target);
+                }
+                *otherwise = rename(*otherwise);
+            }
+        },
+        src::RawTerminator::Panic
+        | src::RawTerminator::Return
+        | src::RawTerminator::Unreachable => {}
+    }
+}
+
+/// Lower an LLBC body to ULLBC. The body must end with a `return` (or a
+/// `panic`). Returns `None` if the body contains compound assignments or
+/// matches, which don't exist in ULLBC.
+///
+/// We remove the unreachable blocks (for instance, the exits of the loops
+/// which never break) and number the blocks in the order in which we reach
+/// them, so that the entry block is the block 0.
+pub fn lower_body(body: &ExprBody) -> Option<src::ExprBody> {
+    let mut lowering = Lowering { blocks: Vec::new() };
+    let exit = lowering.push(Vec::new(), src::RawTerminator::Unreachable);
+    let entry = lowering.lower(&body.body, exit, &mut Vec::new())?;
+    let blocks: Vec<src::BlockData> = lowering.blocks.into_iter().map(Option::unwrap).collect();
+
+    // Explore the reachable blocks, depth-first
+    let mut order: Vec<src::BlockId::Id> = Vec::new();
+    let mut stack = vec![entry];
+    while let Some(id) = stack.pop() {
+        if order.contains(&id) {
+            continue;
+        }
+        order.push(id);
+        let targets = terminator_targets(&blocks[id.to_usize()].terminator.content);
+        stack.extend(targets.into_iter().rev());
+    }
+    let rename = |id: src::BlockId::Id| {
+        src::BlockId::Id::new(order.iter().position(|id1| *id1 == id).unwrap())
+    };
+    let mut new_blocks = Vec::new();
+    for id in &order {
+        let mut block = blocks[id.to_usize()].clone();
+        rename_targets(&mut block.terminator.content, &rename);
+        new_blocks.push(block);
+    }
+    Some(src::ExprBody {
+        meta: body.meta,
+        arg_count: body.arg_count,
+        return_local: body.return_local,
+        locals: body.locals.clone(),
+        body: src::BlockId::Vector::from(new_blocks),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interp::{InterpError, Interpreter, Outcome, Value};
+    use crate::llbc_gen::*;
+    use crate::ullbc_to_llbc;
+    use std::collections::HashSet;
+
+    /// The fuel we give to the interpreter: the generated loops often don't
+    /// terminate
+    const FUEL: usize = 10_000;
+
+    fn eval(
+        fun: &FunDecl,
+        fuel: usize,
+        args: &[Value],
+    ) -> std::result::Result<Outcome, InterpError> {
+        let funs = FunDecls::from(vec![fun.clone()]);
+        let globals = GlobalDecls::new();
+        Interpreter::new(&funs, &globals)
+            .with_fuel(fuel)
+            .eval_function(FunDeclId::ZERO, args.to_vec())
+    }
+
+    /// Reconstruct the control-flow of the lowered body of a function
+    fn reconstruct(fun: &FunDecl) -> FunDecl {
+        let src_fun = src::FunDecl {
+            def_id: fun.def_id,
+            meta: fun.meta,
+            name: fun.name.clone(),
+            signature: fun.signature.clone(),
+            body: fun.body.as_ref().map(|b| lower_body(b).unwrap()),
+            precision_losses: Vec::new(),
+            unsafe_operations: Vec::new(),
+            attr_info: AttrInfo::default(),
+            contract: FunContract::default(),
+        };
+        let (funs, _) = ullbc_to_llbc::translate_functions(
+            false,
+            &HashSet::new(),
+            &TypeDecls::new(),
+            &FunDeclId::Vector::from(vec![src_fun]),
+            &GlobalDeclId::Vector::new(),
+        );
+        funs.get(FunDeclId::ZERO).unwrap().clone()
+    }
+
+    fn fmt(fun: &FunDecl) -> String {
+        let funs = FunDecls::from(vec![fun.clone()]);
+        fun.fmt_with_decls(&TypeDecls::new(), &funs, &GlobalDecls::new())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(128))]
+
+        #[test]
+        fn test_round_trips(fun in fun_decl(), a in 0u32..16, b in any::<bool>()) {
+            let args = [Value::Scalar(ScalarValue::U32(a)), Value::Bool(b)];
+            let outcome = eval(&fun, FUEL, &args);
+
+            // Serialization
+            let json = serde_json::to_string(&fun).unwrap();
+            let fun1: FunDecl = serde_json::from_str(&json).unwrap();
+            prop_assert!(serde_json::to_string(&fun1).unwrap() == json);
+            prop_assert!(fmt(&fun1) == fmt(&fun));
+            prop_assert!(eval(&fun1, FUEL, &args) == outcome);
+
+            // Control-flow reconstruction. The reconstructed body may execute
+            // more statements: we only compare the outcomes when the original
+            // function terminates.
+            if outcome != Err(InterpError::OutOfFuel) {
+                let fun2 = reconstruct(&fun);
+                prop_assert!(
+                    eval(&fun2, 10 * FUEL, &args) == outcome,
+                    "{}\n\nreconstructed as:\n\n{}",
+                    fmt(&fun),
+                    fmt(&fun2)
+                );
+            }
+        }
+    }
+}
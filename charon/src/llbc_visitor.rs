@@ -0,0 +1,413 @@
+//! Visitors over the LLBC statements and expressions.
+//!
+//! The passes which only need to look at (or update) some nodes of the
+//! bodies can implement [AstVisitor] (or [AstMutVisitor]) and override the
+//! methods for those nodes: the default methods explore the whole tree. An
+//! overriding method can call the corresponding `walk_*` function to keep
+//! exploring the sub-nodes. For instance, to collect the locals used by a
+//! statement:
+//! ```text
+//! struct UsedLocals(HashSet<VarId::Id>);
+//!
+//! impl AstVisitor for UsedLocals {
+//!     fn visit_var_id(&mut self, id: &VarId::Id) {
+//!         self.0.insert(*id);
+//!     }
+//! }
+//! ```
+//!
+//! The nodes are explored in evaluation order: for instance, in an
+//! assignment, we visit the rvalue before the assigned place.
+//!
+//! The mutable visitors update the nodes in place: they can't change the
+//! shape of the statements (see [crate::llbc_ast::transform_statements] for
+//! this).
+
+#![allow(dead_code)]
+use crate::expressions::*;
+use crate::llbc_ast::{Call, RawStatement, Statement, Switch};
+use crate::ullbc_ast::FunId;
+use crate::values::*;
+
+pub trait AstVisitor {
+    fn visit_statement(&mut self, st: &Statement) {
+        walk_statement(self, st)
+    }
+
+    fn visit_switch(&mut self, switch: &Switch) {
+        walk_switch(self, switch)
+    }
+
+    fn visit_call(&mut self, call: &Call) {
+        walk_call(self, call)
+    }
+
+    fn visit_rvalue(&mut self, rv: &Rvalue) {
+        walk_rvalue(self, rv)
+    }
+
+    fn visit_operand(&mut self, op: &Operand) {
+        walk_operand(self, op)
+    }
+
+    fn visit_place(&mut self, p: &Place) {
+        walk_place(self, p)
+    }
+
+    /// Called on the locals of the places, including the locals used as
+    /// indices in the projections.
+    fn visit_var_id(&mut self, _id: &VarId::Id) {}
+}
+
+pub fn walk_statement<V: AstVisitor + ?Sized>(v: &mut V, st: &Statement) {
+    match &st.content {
+        RawStatement::Assign(p, rv) => {
+            v.visit_rvalue(rv);
+            v.visit_place(p);
+        }
+        RawStatement::CompoundAssign(p, _, rhs) => {
+            v.visit_operand(rhs);
+            v.visit_place(p);
+        }
+        RawStatement::FakeRead(p) | RawStatement::SetDiscriminant(p, _) | RawStatement::Drop(p) => {
+            v.visit_place(p)
+        }
+        RawStatement::Assert(assert) => v.visit_operand(&assert.cond),
+        RawStatement::Call(call) => v.visit_call(call),
+        RawStatement::Switch(switch) => v.visit_switch(switch),
+        RawStatement::Sequence(st1, st2) => {
+            v.visit_statement(st1);
+            v.visit_statement(st2);
+        }
+        RawStatement::Loop(st, invariants) => {
+            // The invariants hold at the entry of the loop
+            for inv in invariants {
+                inv.expr.for_each_var(&mut |id| v.visit_var_id(id));
+            }
+            v.visit_statement(st)
+        }
+        RawStatement::While(cond, op, body, invariants) => {
+            for inv in invariants {
+                inv.expr.for_each_var(&mut |id| v.visit_var_id(id));
+            }
+            v.visit_statement(cond);
+            v.visit_operand(op);
+            v.visit_statement(body)
+        }
+        RawStatement::Panic
+        | RawStatement::Return
+        | RawStatement::Break(_)
+        | RawStatement::Continue(_)
+        | RawStatement::Nop => (),
+    }
+}
+
+pub fn walk_switch<V: AstVisitor + ?Sized>(v: &mut V, switch: &Switch) {
+    match switch {
+        Switch::If(op, st1, st2) => {
+            v.visit_operand(op);
+            v.visit_statement(st1);
+            v.visit_statement(st2);
+        }
+        Switch::SwitchInt(op, _, targets, otherwise) => {
+            v.visit_operand(op);
+            for (_, st) in targets {
+                v.visit_statement(st);
+            }
+            v.visit_statement(otherwise);
+        }
+        Switch::Match(p, targets, otherwise, _) => {
+            v.visit_place(p);
+            for (_, st) in targets {
+                v.visit_statement(st);
+            }
+            v.visit_statement(otherwise);
+        }
+    }
+}
+
+pub fn walk_call<V: AstVisitor + ?Sized>(v: &mut V, call: &Call) {
+    if let FunId::Indirect(op) = &call.func {
+        v.visit_operand(op);
+    }
+    for arg in &call.args {
+        v.visit_operand(arg);
+    }
+    v.visit_place(&call.dest);
+}
+
+pub fn walk_rvalue<V: AstVisitor + ?Sized>(v: &mut V, rv: &Rvalue) {
+    match rv {
+        Rvalue::Use(op)
+        | Rvalue::UnaryOp(_, op)
+        | Rvalue::Unsize(op, _, _)
+        | Rvalue::Cast(_, op, _, _) => v.visit_operand(op),
+        Rvalue::BinaryOp(_, op1, op2) => {
+            v.visit_operand(op1);
+            v.visit_operand(op2);
+        }
+        Rvalue::Ref(p, _)
+        | Rvalue::RawPtr(p, _)
+        | Rvalue::Discriminant(p)
+        | Rvalue::Len(p)
+        | Rvalue::IsVariant(p, _) => v.visit_place(p),
+        Rvalue::Slice { base, from, to, .. } => {
+            v.visit_place(base);
+            v.visit_operand(from);
+            v.visit_operand(to);
+        }
+        Rvalue::Aggregate(_, ops) => {
+            for op in ops {
+                v.visit_operand(op);
+            }
+        }
+        Rvalue::Global(_) | Rvalue::GlobalRef(_, _) | Rvalue::FnPtr(_) => (),
+    }
+}
+
+pub fn walk_operand<V: AstVisitor + ?Sized>(v: &mut V, op: &Operand) {
+    match op {
+        Operand::Copy(p) | Operand::Move(p) => v.visit_place(p),
+        Operand::Const(_, _) => (),
+    }
+}
+
+pub fn walk_place<V: AstVisitor + ?Sized>(v: &mut V, p: &Place) {
+    v.visit_var_id(&p.var_id);
+    for pe in p.projection.iter() {
+        if let ProjectionElem::Index(var_id) = pe {
+            v.visit_var_id(var_id);
+        }
+    }
+}
+
+/// The mutable counterpart of [AstVisitor]
+pub trait AstMutVisitor {
+    fn visit_statement_mut(&mut self, st: &mut Statement) {
+        walk_statement_mut(self, st)
+    }
+
+    fn visit_switch_mut(&mut self, switch: &mut Switch) {
+        walk_switch_mut(self, switch)
+    }
+
+    fn visit_call_mut(&mut self, call: &mut Call) {
+        walk_call_mut(self, call)
+    }
+
+    fn visit_rvalue_mut(&mut self, rv: &mut Rvalue) {
+        walk_rvalue_mut(self, rv)
+    }
+
+    fn visit_operand_mut(&mut self, op: &mut Operand) {
+        walk_operand_mut(self, op)
+    }
+
+    fn visit_place_mut(&mut self, p: &mut Place) {
+        walk_place_mut(self, p)
+    }
+
+    fn visit_var_id_mut(&mut self, _id: &mut VarId::Id) {}
+}
+
+pub fn walk_statement_mut<V: AstMutVisitor + ?Sized>(v: &mut V, st: &mut Statement) {
+    match &mut st.content {
+        RawStatement::Assign(p, rv) => {
+            v.visit_rvalue_mut(rv);
+            v.visit_place_mut(p);
+        }
+        RawStatement::CompoundAssign(p, _, rhs) => {
+            v.visit_operand_mut(rhs);
+            v.visit_place_mut(p);
+        }
+        RawStatement::FakeRead(p) | RawStatement::SetDiscriminant(p, _) | RawStatement::Drop(p) => {
+            v.visit_place_mut(p)
+        }
+        RawStatement::Assert(assert) => v.visit_operand_mut(&mut assert.cond),
+        RawStatement::Call(call) => v.visit_call_mut(call),
+        RawStatement::Switch(switch) => v.visit_switch_mut(switch),
+        RawStatement::Sequence(st1, st2) => {
+            v.visit_statement_mut(st1);
+            v.visit_statement_mut(st2);
+        }
+        RawStatement::Loop(st, invariants) => {
+            for inv in invariants {
+                inv.expr.for_each_var_mut(&mut |id| v.visit_var_id_mut(id));
+            }
+            v.visit_statement_mut(st)
+        }
+        RawStatement::While(cond, op, body, invariants) => {
+            for inv in invariants {
+                inv.expr.for_each_var_mut(&mut |id| v.visit_var_id_mut(id));
+            }
+            v.visit_statement_mut(cond);
+            v.visit_operand_mut(op);
+            v.visit_statement_mut(body)
+        }
+        RawStatement::Panic
+        | RawStatement::Return
+        | RawStatement::Break(_)
+        | RawStatement::Continue(_)
+        | RawStatement::Nop => (),
+    }
+}
+
+pub fn walk_switch_mut<V: AstMutVisitor + ?Sized>(v: &mut V, switch: &mut Switch) {
+    match switch {
+        Switch::If(op, st1, st2) => {
+            v.visit_operand_mut(op);
+            v.visit_statement_mut(st1);
+            v.visit_statement_mut(st2);
+        }
+        Switch::SwitchInt(op, _, targets, otherwise) => {
+            v.visit_operand_mut(op);
+            for (_, st) in targets {
+                v.visit_statement_mut(st);
+            }
+            v.visit_statement_mut(otherwise);
+        }
+        Switch::Match(p, targets, otherwise, _) => {
+            v.visit_place_mut(p);
+            for (_, st) in targets {
+                v.visit_statement_mut(st);
+            }
+            v.visit_statement_mut(otherwise);
+        }
+    }
+}
+
+pub fn walk_call_mut<V: AstMutVisitor + ?Sized>(v: &mut V, call: &mut Call) {
+    if let FunId::Indirect(op) = &mut call.func {
+        v.visit_operand_mut(op);
+    }
+    for arg in &mut call.args {
+        v.visit_operand_mut(arg);
+    }
+    v.visit_place_mut(&mut call.dest);
+}
+
+pub fn walk_rvalue_mut<V: AstMutVisitor + ?Sized>(v: &mut V, rv: &mut Rvalue) {
+    match rv {
+        Rvalue::Use(op)
+        | Rvalue::UnaryOp(_, op)
+        | Rvalue::Unsize(op, _, _)
+        | Rvalue::Cast(_, op, _, _) => v.visit_operand_mut(op),
+        Rvalue::BinaryOp(_, op1, op2) => {
+            v.visit_operand_mut(op1);
+            v.visit_operand_mut(op2);
+        }
+        Rvalue::Ref(p, _)
+        | Rvalue::RawPtr(p, _)
+        | Rvalue::Discriminant(p)
+        | Rvalue::Len(p)
+        | Rvalue::IsVariant(p, _) => v.visit_place_mut(p),
+        Rvalue::Slice { base, from, to, .. } => {
+            v.visit_place_mut(base);
+            v.visit_operand_mut(from);
+            v.visit_operand_mut(to);
+        }
+        Rvalue::Aggregate(_, ops) => {
+            for op in ops {
+                v.visit_operand_mut(op);
+            }
+        }
+        Rvalue::Global(_) | Rvalue::GlobalRef(_, _) | Rvalue::FnPtr(_) => (),
+    }
+}
+
+pub fn walk_operand_mut<V: AstMutVisitor + ?Sized>(v: &mut V, op: &mut Operand) {
+    match op {
+        Operand::Copy(p) | Operand::Move(p) => v.visit_place_mut(p),
+        Operand::Const(_, _) => (),
+    }
+}
+
+pub fn walk_place_mut<V: AstMutVisitor + ?Sized>(v: &mut V, p: &mut Place) {
+    v.visit_var_id_mut(&mut p.var_id);
+    for pe in p.projection.iter_mut() {
+        if let ProjectionElem::Index(var_id) = pe {
+            v.visit_var_id_mut(var_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::id_vector::ToUsize;
+    use crate::llbc_ast::chain_statements;
+    use crate::llbc_visitor::*;
+    use crate::meta::Meta;
+    use crate::types::*;
+
+    fn mk(content: RawStatement) -> Statement {
+        Statement::new(Meta::dummy(), content)
+    }
+
+    /// Count the places and the locals
+    #[derive(Default)]
+    struct Counter {
+        places: usize,
+        vars: Vec<VarId::Id>,
+    }
+
+    impl AstVisitor for Counter {
+        fn visit_place(&mut self, p: &Place) {
+            self.places += 1;
+            walk_place(self, p)
+        }
+
+        fn visit_var_id(&mut self, id: &VarId::Id) {
+            self.vars.push(*id)
+        }
+    }
+
+    struct Shift;
+
+    impl AstMutVisitor for Shift {
+        fn visit_var_id_mut(&mut self, id: &mut VarId::Id) {
+            *id = VarId::Id::new(id.to_usize() + 10)
+        }
+    }
+
+    #[test]
+    fn test_visitors() {
+        let x = VarId::Id::new(1);
+        let i = VarId::Id::new(2);
+        let y = VarId::Id::new(3);
+        // ```
+        // if copy x {
+        //   y := copy x[i] + const 1;
+        // } else {
+        //   nop
+        // };
+        // return
+        // ```
+        let mut x_i = Place::new(x);
+        x_i.projection.push_back(ProjectionElem::Index(i));
+        let one = Operand::Const(
+            Ty::Integer(IntegerTy::U32),
+            OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(ScalarValue::U32(1))),
+        );
+        let assign = mk(RawStatement::Assign(
+            Place::new(y),
+            Rvalue::BinaryOp(BinOp::Add, Operand::Copy(x_i), one),
+        ));
+        let switch = mk(RawStatement::Switch(Switch::If(
+            Operand::Copy(Place::new(x)),
+            Box::new(assign),
+            Box::new(mk(RawStatement::Nop)),
+        )));
+        let mut st = chain_statements(vec![switch], mk(RawStatement::Return));
+
+        let mut counter = Counter::default();
+        counter.visit_statement(&st);
+        assert!(counter.places == 3);
+        assert!(counter.vars == vec![x, x, i, y]);
+
+        Shift.visit_statement_mut(&mut st);
+        let mut counter = Counter::default();
+        counter.visit_statement(&st);
+        let vars: Vec<usize> = counter.vars.iter().map(|v| v.to_usize()).collect();
+        assert!(vars == vec![11, 11, 12, 13]);
+    }
+}
@@ -36,9 +36,10 @@ extern crate rustc_tools_util;
 
 mod cli_options;
 mod logger;
+mod workspace;
 
-use cli_options::{CliOpts, CHARON_ARGS};
-use log::trace;
+use cli_options::{CliOpts, Emit, OutputFormat, CHARON_ARGS};
+use log::{error, trace};
 use std::env;
 use std::path::PathBuf;
 use std::process::Command;
@@ -60,11 +61,38 @@ pub fn main() {
         "Can't use --lib and --bin at the same time"
     );
 
+    assert!(
+        !options.ullbc || options.emit.is_none(),
+        "Can't use --ullbc and --emit at the same time"
+    );
+
+    // The `--emit=both` declarations are serialized as maps whose length is
+    // not known in advance, which bincode doesn't support
+    assert!(
+        options.emit() != Emit::Both || options.output_format != OutputFormat::Bincode,
+        "Can't use --emit=both and --output-format=bincode at the same time"
+    );
+
+    assert!(
+        !options.monomorphize || options.emit() == Emit::Llbc,
+        "Can't use --monomorphize when emitting ULLBC"
+    );
+
     assert!(
         !options.mir_promoted || !options.mir_optimized,
         "Can't use --mir_promoted and --mir_optimized at the same time"
     );
 
+    // In workspace mode, we choose the targets and the names of the crates
+    assert!(
+        !options.workspace
+            || (!options.lib
+                && options.bin.is_none()
+                && options.crate_name.is_none()
+                && options.input_file.is_none()),
+        "Can't use --workspace with --lib, --bin, --crate or --input"
+    );
+
     if let Err(code) = process(&options) {
         std::process::exit(code);
     }
@@ -82,21 +110,28 @@ fn path() -> PathBuf {
     path
 }
 
+/// The command to call cargo, with the proper version of Rust
+fn cargo_command(options: &CliOpts) -> Command {
+    let mut cmd = Command::new("cargo");
+    if !options.cargo_no_rust_version {
+        cmd.arg(RUST_VERSION);
+    }
+    cmd
+}
+
 fn process(options: &CliOpts) -> Result<(), i32> {
+    if options.workspace {
+        return process_workspace(options);
+    }
+
     // Compute the arguments of the command to call cargo
     //let cargo_subcommand = "build";
     let cargo_subcommand = "rustc";
 
-    let rust_version = RUST_VERSION;
-
-    let mut cmd = Command::new("cargo");
+    let mut cmd = cargo_command(options);
     cmd.env("RUSTC_WORKSPACE_WRAPPER", path());
     cmd.env(CHARON_ARGS, serde_json::to_string(&options).unwrap());
 
-    if !options.cargo_no_rust_version {
-        cmd.arg(rust_version);
-    }
-
     cmd.arg(cargo_subcommand);
 
     if options.lib {
@@ -112,6 +147,10 @@ fn process(options: &CliOpts) -> Result<(), i32> {
         cmd.arg("--release");
     }
 
+    run(cmd)
+}
+
+fn run(mut cmd: Command) -> Result<(), i32> {
     let exit_status = cmd
         .spawn()
         .expect("could not run cargo")
@@ -124,3 +163,39 @@ fn process(options: &CliOpts) -> Result<(), i32> {
         Err(exit_status.code().unwrap_or(-1))
     }
 }
+
+/// Extract all the members of the workspace (see [workspace])
+fn process_workspace(options: &CliOpts) -> Result<(), i32> {
+    let mut cmd = cargo_command(options);
+    cmd.args(["metadata", "--format-version", "1", "--no-deps"]);
+    let output = cmd.output().expect("could not run cargo");
+    if !output.status.success() {
+        error!(
+            "cargo metadata failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(output.status.code().unwrap_or(-1));
+    }
+    let metadata: serde_json::Value = match serde_json::from_slice(&output.stdout) {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            error!("Could not parse the output of cargo metadata: {err}");
+            return Err(1);
+        }
+    };
+
+    workspace::process(options, &metadata, &|member, options| {
+        let mut cmd = cargo_command(options);
+        cmd.env("RUSTC_WORKSPACE_WRAPPER", path());
+        cmd.env(CHARON_ARGS, serde_json::to_string(&options).unwrap());
+        cmd.args(["rustc", "-p", member.package.as_str()]);
+        match &member.target {
+            workspace::Target::Lib => cmd.arg("--lib"),
+            workspace::Target::Bin(bin) => cmd.args(["--bin", bin.as_str()]),
+        };
+        if options.release {
+            cmd.arg("--release");
+        }
+        run(cmd)
+    })
+}
@@ -0,0 +1,160 @@
+//! Retrieve the variables bound by the branches of a [Switch::Match].
+//!
+//! When matching `Some(y)`, the MIR doesn't bind `y` as part of the match:
+//! the body of the branch starts with assignments from the fields of the
+//! downcasted scrutinee, like below:
+//! ```text
+//! map x {
+//!   1 => {
+//!     y := move (x as variant @1).0;
+//!     ...
+//!   }
+//!   ...
+//! }
+//! ```
+//! The analysis below recovers those bindings, so that backends can present
+//! the match with named patterns (i.e., `Some(y) => ...`).
+#![allow(dead_code)]
+
+use crate::expressions::*;
+use crate::llbc_ast::{RawStatement, Statement, Switch};
+use crate::types::*;
+use crate::values::*;
+
+/// The bindings introduced by a match branch: the variant of the branch,
+/// and the variables initialized from the fields of this variant.
+pub type ArmBindings = (VariantId::Id, Vec<(FieldId::Id, VarId::Id)>);
+
+/// If `p` is a field of the scrutinee, downcasted to the variant `variant_id`,
+/// return the field id.
+fn get_downcast_field(
+    scrutinee: &Place,
+    variant_id: VariantId::Id,
+    p: &Place,
+) -> Option<FieldId::Id> {
+    if p.var_id != scrutinee.var_id || p.projection.len() != scrutinee.projection.len() + 1 {
+        return None;
+    }
+    let mut projection = p.projection.clone();
+    let last = projection.pop_back().unwrap();
+    if projection != scrutinee.projection {
+        return None;
+    }
+    match last {
+        ProjectionElem::Field(FieldProjKind::Adt(_, Some(vid)), field_id)
+        | ProjectionElem::Field(FieldProjKind::Option(vid), field_id)
+        | ProjectionElem::Field(FieldProjKind::ControlFlow(vid), field_id)
+            if vid == variant_id =>
+        {
+            Some(field_id)
+        }
+        _ => None,
+    }
+}
+
+/// If the statement is of the shape `y := move (x as variant @v).f`, return
+/// the field and the variable.
+fn get_binding(
+    scrutinee: &Place,
+    variant_id: VariantId::Id,
+    st: &Statement,
+) -> Option<(FieldId::Id, VarId::Id)> {
+    match &st.content {
+        RawStatement::Assign(dest, Rvalue::Use(Operand::Move(p) | Operand::Copy(p)))
+            if dest.projection.is_empty() =>
+        {
+            get_downcast_field(scrutinee, variant_id, p).map(|fid| (fid, dest.var_id))
+        }
+        _ => None,
+    }
+}
+
+/// Compute the bindings introduced at the beginning of a match branch, by
+/// looking at the leading assignments from the fields of the scrutinee.
+///
+/// Return `None` if the branch doesn't match exactly one variant (because
+/// the bound variables would then depend on the variant).
+pub fn get_arm_bindings(
+    scrutinee: &Place,
+    variants: &[VariantId::Id],
+    arm: &Statement,
+) -> Option<ArmBindings> {
+    let variant_id = match variants {
+        [variant_id] => *variant_id,
+        _ => return None,
+    };
+
+    // Explore the leading statements (remember that the sequences are
+    // nested to the right)
+    let mut bindings = Vec::new();
+    let mut st = arm;
+    loop {
+        let (first, next) = match &st.content {
+            RawStatement::Sequence(st1, st2) => (st1.as_ref(), Some(st2.as_ref())),
+            _ => (st, None),
+        };
+        match get_binding(scrutinee, variant_id, first) {
+            Some(binding) => bindings.push(binding),
+            None => break,
+        }
+        match next {
+            Some(next) => st = next,
+            None => break,
+        }
+    }
+
+    Some((variant_id, bindings))
+}
+
+/// Compute the bindings for all the branches of a match (the `otherwise`
+/// branch doesn't bind anything and is thus ignored).
+pub fn get_match_bindings(switch: &Switch) -> Vec<Option<ArmBindings>> {
+    match switch {
+        Switch::Match(scrutinee, targets, _, _) => targets
+            .iter()
+            .map(|(variants, arm)| get_arm_bindings(scrutinee, variants, arm))
+            .collect(),
+        Switch::If(_, _, _) | Switch::SwitchInt(_, _, _, _) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llbc_ast::chain_statements;
+    use crate::match_bindings::*;
+    use crate::meta::Meta;
+
+    #[test]
+    fn test_get_arm_bindings() {
+        let x = VarId::Id::new(1);
+        let y = VarId::Id::new(2);
+        let some = VariantId::Id::new(1);
+        let field = FieldId::Id::new(0);
+        let scrutinee = Place::new(x);
+        let mut downcast = Place::new(x);
+        downcast.projection.push_back(ProjectionElem::Field(
+            FieldProjKind::Adt(TypeDeclId::Id::new(0), Some(some)),
+            field,
+        ));
+
+        // ```
+        // y := move (x as variant @1).0;
+        // return
+        // ```
+        let arm = chain_statements(
+            vec![Statement::new(
+                Meta::dummy(),
+                RawStatement::Assign(Place::new(y), Rvalue::Use(Operand::Move(downcast))),
+            )],
+            Statement::new(Meta::dummy(), RawStatement::Return),
+        );
+
+        let bindings = get_arm_bindings(&scrutinee, &[some], &arm).unwrap();
+        assert!(bindings.0 == some);
+        assert!(bindings.1 == vec![(field, y)]);
+
+        // The bindings would depend on the variant: we don't compute them
+        let none = VariantId::Id::new(0);
+        assert!(get_arm_bindings(&scrutinee, &[none, some], &arm).is_none());
+    }
+}
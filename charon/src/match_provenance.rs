@@ -0,0 +1,221 @@
+//! Annotate the branches of the matches with their provenance (see
+//! [crate::llbc_ast::ArmProvenance]), so that the backends can print them
+//! close to the original source.
+//!
+//! When reconstructing the control-flow, we lose the distinction between a
+//! guarded arm and an arm whose body starts with an `if`. For instance, the
+//! following match:
+//! ```text
+//! match x {
+//!   Some(y) if y > 0 => ...,
+//!   _ => ...,
+//! }
+//! ```
+//! becomes:
+//! ```text
+//! map x {
+//!   1 => {
+//!     y_ref := &(x as variant @1).0;
+//!     b := copy (*y_ref) > const 0;
+//!     @fake_read(x);
+//!     @fake_read(y_ref);
+//!     if move b {
+//!       y := copy (x as variant @1).0;
+//!       ...
+//!     }
+//!     else {
+//!       ...
+//!     }
+//!   },
+//!   _ => ...
+//! }
+//! ```
+//! The MIR reads the scrutinee (`@fake_read(x)`) just before testing a guard,
+//! to prevent the guard from modifying it: we use those reads to identify the
+//! guards. Note that the fake reads which come from the guards are not
+//! followed by a storage dead, and are thus preserved by
+//! [crate::remove_fake_reads].
+//!
+//! This pass must be applied last: the passes which rebuild the matches
+//! don't preserve the provenance information.
+
+use crate::expressions::*;
+use crate::llbc_ast::{
+    transform_statements, ArmProvenance, CtxNames, FunDecls, GlobalDecls, RawStatement, Statement,
+    Switch,
+};
+use crate::meta::{combine_meta_iter, Meta};
+use crate::types::*;
+use crate::ullbc_ast::{iter_function_bodies, iter_global_bodies};
+use take_mut::take;
+
+/// Flatten a sequence of statements (remember that the sequences are nested
+/// to the right)
+fn flatten(st: &Statement) -> Vec<&Statement> {
+    match &st.content {
+        RawStatement::Sequence(st1, st2) => {
+            let mut sts = vec![st1.as_ref()];
+            sts.append(&mut flatten(st2));
+            sts
+        }
+        _ => vec![st],
+    }
+}
+
+/// Return true if `p` is a field (or a sub-field) of the scrutinee, downcast
+/// to one of the variants of the branch.
+fn is_pattern_field(scrutinee: &Place, variants: &[VariantId::Id], p: &Place) -> bool {
+    let len = scrutinee.projection.len();
+    if p.var_id != scrutinee.var_id
+        || p.projection.len() <= len
+        || p.projection.clone().take(len) != scrutinee.projection
+    {
+        return false;
+    }
+    match &p.projection[len] {
+        ProjectionElem::Field(FieldProjKind::Adt(_, Some(vid)), _)
+        | ProjectionElem::Field(FieldProjKind::Option(vid), _)
+        | ProjectionElem::Field(FieldProjKind::ControlFlow(vid), _) => variants.contains(vid),
+        _ => false,
+    }
+}
+
+/// Return true if the statement binds a variable of the pattern, by value
+/// (`y := move (x as variant @1).0`) or by reference, before a guard
+/// (`y_ref := &(x as variant @1).0`).
+fn is_pattern_binding(scrutinee: &Place, variants: &[VariantId::Id], st: &Statement) -> bool {
+    match &st.content {
+        RawStatement::Assign(
+            _,
+            Rvalue::Use(Operand::Move(p) | Operand::Copy(p)) | Rvalue::Ref(p, _),
+        ) => is_pattern_field(scrutinee, variants, p),
+        _ => false,
+    }
+}
+
+/// Compute the provenance of a branch
+fn compute_arm_provenance(
+    scrutinee: &Place,
+    variants: &[VariantId::Id],
+    arm: &Statement,
+) -> ArmProvenance {
+    let sts = flatten(arm);
+
+    // The leading statements bind the variables of the pattern
+    let bindings: Vec<&Meta> = sts
+        .iter()
+        .take_while(|st| is_pattern_binding(scrutinee, variants, st))
+        .map(|st| &st.meta)
+        .collect();
+    let pattern_meta = if bindings.is_empty() {
+        None
+    } else {
+        Some(combine_meta_iter(bindings.into_iter()))
+    };
+
+    // The guard is the first `if` after the fake read of the scrutinee
+    let guard = sts
+        .iter()
+        .skip_while(|st| !matches!(&st.content, RawStatement::FakeRead(p) if p == scrutinee))
+        .any(|st| matches!(&st.content, RawStatement::Switch(Switch::If(_, _, _))));
+
+    ArmProvenance {
+        pattern_meta,
+        guard,
+    }
+}
+
+fn transform_st(mut st: Statement) -> Statement {
+    if let RawStatement::Switch(Switch::Match(scrutinee, targets, _, provenance)) = &mut st.content
+    {
+        *provenance = Some(
+            targets
+                .iter()
+                .map(|(variants, arm)| compute_arm_provenance(scrutinee, variants, arm))
+                .collect(),
+        );
+    }
+    st
+}
+
+/// `fmt_ctx` is used for pretty-printing purposes.
+pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
+    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+        trace!(
+            "# About to compute the provenance of the match arms in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        take(&mut b.body, |st| {
+            transform_statements(&mut transform_st, st)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llbc_ast::chain_statements;
+    use crate::match_provenance::*;
+    use crate::values::*;
+
+    fn mk(content: RawStatement) -> Statement {
+        Statement::new(Meta::dummy(), content)
+    }
+
+    fn mk_assign(dest: VarId::Id, rv: Rvalue) -> Statement {
+        mk(RawStatement::Assign(Place::new(dest), rv))
+    }
+
+    #[test]
+    fn test_guard() {
+        let (x, y_ref, b, y) = (
+            VarId::Id::new(1),
+            VarId::Id::new(2),
+            VarId::Id::new(3),
+            VarId::Id::new(4),
+        );
+        let some = VariantId::Id::new(1);
+        let scrutinee = Place::new(x);
+        let mut field = scrutinee.clone();
+        field.projection.push_back(ProjectionElem::Field(
+            FieldProjKind::Option(some),
+            FieldId::ZERO,
+        ));
+
+        // `Some(y) if ... => nop`
+        let guarded_arm = chain_statements(
+            vec![
+                mk_assign(y_ref, Rvalue::Ref(field.clone(), BorrowKind::Shared)),
+                mk_assign(b, Rvalue::Use(Operand::Copy(Place::new(y_ref)))),
+                mk(RawStatement::FakeRead(scrutinee.clone())),
+                mk(RawStatement::FakeRead(Place::new(y_ref))),
+            ],
+            mk(RawStatement::Switch(Switch::If(
+                Operand::Move(Place::new(b)),
+                Box::new(mk_assign(y, Rvalue::Use(Operand::Copy(field.clone())))),
+                Box::new(mk(RawStatement::Nop)),
+            ))),
+        );
+        let provenance = compute_arm_provenance(&scrutinee, &[some], &guarded_arm);
+        assert!(provenance.guard);
+        assert!(provenance.pattern_meta.is_some());
+
+        // `Some(y) => if b { nop } else { nop }`: the `if` is not a guard
+        let arm = chain_statements(
+            vec![mk_assign(y, Rvalue::Use(Operand::Move(field)))],
+            mk(RawStatement::Switch(Switch::If(
+                Operand::Move(Place::new(b)),
+                Box::new(mk(RawStatement::Nop)),
+                Box::new(mk(RawStatement::Nop)),
+            ))),
+        );
+        let provenance = compute_arm_provenance(&scrutinee, &[some], &arm);
+        assert!(!provenance.guard);
+        assert!(provenance.pattern_meta.is_some());
+
+        // `None => nop`: no bindings
+        let none = VariantId::ZERO;
+        let provenance = compute_arm_provenance(&scrutinee, &[none], &mk(RawStatement::Nop));
+        assert!(!provenance.guard);
+        assert!(provenance.pattern_meta.is_none());
+    }
+}
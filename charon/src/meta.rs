@@ -2,7 +2,7 @@
 
 pub use crate::meta_utils::*;
 use macros::{generate_index_type, EnumAsGetters, EnumIsA};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 generate_index_type!(LocalFileId);
@@ -13,7 +13,18 @@ pub mod FileId {
     use crate::meta::*;
 
     #[derive(
-        Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, EnumIsA, EnumAsGetters, Serialize,
+        Debug,
+        Clone,
+        Copy,
+        Hash,
+        PartialEq,
+        Eq,
+        PartialOrd,
+        Ord,
+        EnumIsA,
+        EnumAsGetters,
+        Serialize,
+        Deserialize,
     )]
     pub enum Id {
         LocalId(LocalFileId::Id),
@@ -21,7 +32,7 @@ pub mod FileId {
     }
 }
 
-#[derive(Debug, Copy, Clone, Serialize)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Loc {
     /// The (1-based) line number.
     pub line: usize,
@@ -30,7 +41,7 @@ pub struct Loc {
 }
 
 /// Span information
-#[derive(Debug, Copy, Clone, Serialize)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Span {
     pub file_id: FileId::Id,
     pub beg: Loc,
@@ -38,7 +49,7 @@ pub struct Span {
 }
 
 /// Meta information about a piece of code (block, statement, etc.)
-#[derive(Debug, Copy, Clone, Serialize)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Meta {
     /// The source code span.
     ///
@@ -64,11 +75,59 @@ pub struct Meta {
     pub generated_from_span: Option<Span>,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+/// The spans of the expressions of a statement (or of a terminator).
+///
+/// The [Meta] of a statement gives the span of its source scope, which often
+/// covers a whole block of code. When MIR gives them, we also keep the spans
+/// of the expressions the statement evaluates, so that the consumers of the
+/// generated files can report precise errors. Note that the passes which
+/// rebuild a statement don't preserve those spans.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExprMetas {
+    /// The span of the expression evaluated by the statement: the
+    /// right-hand side of an assignment, the call of a function (with its
+    /// arguments), the condition of an assertion or the scrutinee of a
+    /// switch.
+    pub expr: Option<Meta>,
+    /// The spans of the operands of the statement, in the order in which
+    /// [crate::ullbc_ast::BlockData::transform_operands] explores them.
+    /// MIR only gives the spans of the constants: the other operands don't
+    /// have a span. This vector is empty if no operand has a span.
+    pub operands: Vec<Option<Meta>>,
+}
+
+/// An attribute of a declaration, like `#[inline(always)]` or
+/// `#[charon::opaque]`
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Attribute {
+    /// The path of the attribute: `["charon", "opaque"]` for
+    /// `#[charon::opaque]`
+    pub path: Vec<String>,
+    /// The arguments of the attribute, as written in the source code (without
+    /// the delimiters): `x > 0` for `#[charon::requires(x > 0)]`. We don't
+    /// have them for the declarations of the external crates.
+    pub args: Option<String>,
+}
+
+/// The documentation and the attributes of a declaration.
+///
+/// The verification frontends read their specifications from those
+/// attributes, rather than from the source files. We only keep the `charon`
+/// tool attributes and a few builtin attributes (see
+/// [crate::tool_attributes::KEPT_BUILTIN_ATTRIBUTES]).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AttrInfo {
+    /// The doc comments, one element per line (`/// ...`) or per
+    /// `#[doc = "..."]` attribute, without the leading `///`
+    pub doc_comments: Vec<String>,
+    pub attributes: Vec<Attribute>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub struct FileInfo {}
 
 /// A filename.
-#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum FileName {
     /// A remapped path (namely paths into stdlib)
     Virtual(PathBuf),
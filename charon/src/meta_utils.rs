@@ -43,6 +43,39 @@ impl Loc {
     }
 }
 
+impl Meta {
+    /// Meta information which doesn't point to any actual source code.
+    /// Useful for code we generate from scratch (and for testing).
+    pub fn dummy() -> Meta {
+        let loc = Loc { line: 0, col: 0 };
+        Meta {
+            span: Span {
+                file_id: FileId::Id::LocalId(LocalFileId::ZERO),
+                beg: loc,
+                end: loc,
+            },
+            generated_from_span: None,
+        }
+    }
+
+    /// Return true if the meta information doesn't point to actual source
+    /// code (see [Meta::dummy]): the line numbers start at 1.
+    pub fn is_dummy(&self) -> bool {
+        self.span.beg.line == 0
+    }
+}
+
+impl ExprMetas {
+    pub fn is_empty(&self) -> bool {
+        self.expr.is_none() && self.operands.is_empty()
+    }
+
+    /// The span of the `i`-th operand of the statement, if we know it
+    pub fn operand(&self, i: usize) -> Option<Meta> {
+        self.operands.get(i).copied().flatten()
+    }
+}
+
 /// Combine some meta information (useful when we need to compute the
 /// meta-information of, say, a sequence).
 pub fn combine_meta(m0: &Meta, m1: &Meta) -> Meta {
@@ -162,6 +195,55 @@ pub fn translate_span(
     }
 }
 
+/// Translate a span, if it points to one of the files we registered.
+///
+/// Contrary to the spans of the statements, the spans of the expressions
+/// may point to files we don't know (for instance, the files of the macros
+/// of the standard library).
+pub fn try_translate_span(
+    sess: &Session,
+    filename_to_id: &HashMap<FileName, FileId::Id>,
+    rspan: rustc_span::Span,
+) -> Option<Span> {
+    let (beg, end) = sess.source_map().is_valid_span(rspan).ok()?;
+    let file_id = filename_to_id.get(&convert_filename(&beg.file.name))?;
+    Some(Span {
+        file_id: *file_id,
+        beg: convert_loc(beg),
+        end: convert_loc(end),
+    })
+}
+
+/// Compute the meta data of an expression, from its span and from the scope
+/// of the statement which contains it (see [ExprMetas]).
+///
+/// As in [get_meta_from_source_info], if the expression was inlined, the
+/// span is the span of the top-most inlined parent scope and the span of the
+/// expression is the span it was generated from.
+pub fn get_expr_meta(
+    sess: &Session,
+    filename_to_id: &HashMap<FileName, FileId::Id>,
+    source_scopes: &IndexVec<SourceScope, SourceScopeData<'_>>,
+    scope: SourceScope,
+    rspan: rustc_span::Span,
+) -> Option<Meta> {
+    let span = try_translate_span(sess, filename_to_id, rspan)?;
+    let mut scope_data = source_scopes.get(scope).unwrap();
+    if scope_data.inlined_parent_scope.is_none() {
+        return Some(Meta {
+            span,
+            generated_from_span: None,
+        });
+    }
+    while let Some(parent_scope) = scope_data.inlined_parent_scope {
+        scope_data = source_scopes.get(parent_scope).unwrap();
+    }
+    Some(Meta {
+        span: try_translate_span(sess, filename_to_id, scope_data.span)?,
+        generated_from_span: Some(span),
+    })
+}
+
 /// Compute meta data from a Rust span
 pub fn get_meta_from_rspan(
     sess: &Session,
@@ -0,0 +1,1284 @@
+//! Monomorphize the program, for the backends which don't support
+//! polymorphism.
+//!
+//! Starting from the public non-generic functions of the crate and from the
+//! globals, we explore the declarations they use, and instantiate the generic
+//! types and functions at the arguments they are used with:
+//! ```text
+//! struct Wrapper<T> { x: T }
+//!
+//! fn get<T>(w: Wrapper<T>) -> T { w.x }
+//!
+//! pub fn f(w: Wrapper<u32>) -> u32 { get::<u32>(w) }
+//! ```
+//! becomes:
+//! ```text
+//! struct Wrapper_u32 { x: u32 }
+//!
+//! fn get_u32(w: Wrapper_u32) -> u32 { w.x }
+//!
+//! pub fn f(w: Wrapper_u32) -> u32 { get_u32(w) }
+//! ```
+//! The instances are new declarations, whose names are the names of the
+//! generic declarations followed by their (mangled) arguments. The
+//! declarations which are not reachable from the entry points are dropped,
+//! and we group the declarations again.
+//!
+//! Note that:
+//! - the assumed types (`Box`, `Vec`, `Option`, etc.) and functions are
+//!   primitives of the backends: we leave them polymorphic.
+//! - the type arguments don't carry regions (they are erased in the bodies):
+//!   the references they contain become `'static` references in the
+//!   signatures and the type definitions of the instances.
+//! - the closures and the function pointers don't record the type arguments
+//!   of the functions they refer to. When those functions are generic, we
+//!   instantiate them with the arguments of the function in which they
+//!   appear: this is what we want for the closures, which inherit the
+//!   generics of their parent.
+//! - the calls to trait methods which we resolved statically refer to the
+//!   method of the implementation, and are given the arguments of this
+//!   method (see [crate::translate_traits::resolve_trait_method]): we
+//!   instantiate them like the other functions.
+//! - the polymorphic recursion (`fn f<T>(x: T) { f::<Box<T>>(..) }`)
+//!   requires infinitely many instances: we report an error once the
+//!   arguments of an instance are nested more than [MAX_ARGS_DEPTH] times.
+//! - the traits and their implementations are not part of the declaration
+//!   groups: we keep them, but they only refer to the instances of the
+//!   non-generic methods.
+
+use crate::expressions::*;
+use crate::llbc_ast::{Call, ExprBody, FunDecl, FunDecls, GlobalDecl, GlobalDecls, Statement};
+use crate::llbc_visitor::{
+    walk_call_mut, walk_operand_mut, walk_place_mut, walk_rvalue_mut, AstMutVisitor,
+};
+use crate::names::{Name, PathElem};
+use crate::reorder_decls::GDeclarationGroup;
+use crate::rust_to_local_ids::{AnyDeclId, DeclInfo, DeclarationGroup, OrderedDecls, Visibility};
+use crate::types::*;
+use crate::ullbc_ast::{
+    FunDeclId, FunId, FunSig, GlobalDeclId, RawStatement, TraitDecl, TraitDecls, TraitImpl,
+    TraitImpls, Var,
+};
+use petgraph::algo::tarjan_scc;
+use petgraph::graphmap::DiGraphMap;
+use std::collections::{HashMap, HashSet};
+
+/// The arguments of an instance: the type arguments followed by the const
+/// generic arguments (as in [Call::type_args]). They refer to the
+/// declarations of the original program.
+type Args = Vec<ETy>;
+
+/// The maximal nesting of the arguments of an instance (see the comments at
+/// the top of the file)
+const MAX_ARGS_DEPTH: usize = 32;
+
+/// The nesting of a type
+fn ty_depth<R>(ty: &Ty<R>) -> usize {
+    match ty {
+        Ty::Adt(_, _, args) | Ty::TraitProjection(_, _, args, _) => 1 + max_ty_depth(args.iter()),
+        Ty::Array(ty, _) | Ty::Slice(ty) | Ty::Ref(_, ty, _) | Ty::RawPtr(ty, _) => {
+            1 + ty_depth(ty)
+        }
+        Ty::FnPtr(inputs, output) => {
+            1 + max_ty_depth(inputs.iter().chain(std::iter::once(output.as_ref())))
+        }
+        Ty::Opaque(Some(ty), _) => 1 + ty_depth(ty),
+        Ty::Opaque(None, _)
+        | Ty::TypeVar(_)
+        | Ty::Bool
+        | Ty::Char
+        | Ty::Never
+        | Ty::Integer(_)
+        | Ty::Float(_)
+        | Ty::Str
+        | Ty::ConstGeneric(_)
+        | Ty::DynTrait(_) => 1,
+    }
+}
+
+fn max_ty_depth<'a, R: 'a>(tys: impl Iterator<Item = &'a Ty<R>>) -> usize {
+    tys.map(ty_depth).max().unwrap_or(0)
+}
+
+/// A substitution of the generic parameters of a declaration. The variables
+/// which are not bound by the substitution are left unchanged (this only
+/// happens if the declaration is used with the wrong number of arguments,
+/// which we report).
+struct Subst {
+    types: TypeSubst<ErasedRegion>,
+    const_generics: ConstGenericSubst,
+}
+
+impl Subst {
+    fn new(
+        type_params: &TypeVarId::Vector<TypeVar>,
+        const_generic_params: &ConstGenericVarId::Vector<ConstGenericVar>,
+        args: &Args,
+    ) -> Self {
+        let num_types = args.iter().take_while(|ty| !ty.is_const_generic()).count();
+        let types = type_params
+            .iter()
+            .map(|x| x.index)
+            .zip(args[..num_types].iter().cloned())
+            .collect();
+        let const_generics = const_generic_params
+            .iter()
+            .map(|x| x.index)
+            .zip(
+                args[num_types..]
+                    .iter()
+                    .map(|ty| ty.as_const_generic().clone()),
+            )
+            .collect();
+        Subst {
+            types,
+            const_generics,
+        }
+    }
+
+    fn const_generic(&self, id: &ConstGenericVarId::Id) -> ConstGeneric {
+        self.const_generics
+            .get(id)
+            .cloned()
+            .unwrap_or(ConstGeneric::Var(*id))
+    }
+
+    fn ety(&self, ty: &ETy) -> ETy {
+        ty.substitute(
+            &|r| *r,
+            &|id| self.types.get(id).cloned().unwrap_or(Ty::TypeVar(*id)),
+            &|id| self.const_generic(id),
+        )
+    }
+
+    fn rty(&self, ty: &RTy) -> RTy {
+        ty.substitute(
+            &|r| *r,
+            &|id| match self.types.get(id) {
+                Some(ty) => ty.substitute(&|_| Region::Static, &|id| Ty::TypeVar(*id), &|id| {
+                    ConstGeneric::Var(*id)
+                }),
+                None => Ty::TypeVar(*id),
+            },
+            &|id| self.const_generic(id),
+        )
+    }
+}
+
+/// Replace the characters which can't appear in an identifier
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+struct Monomorphizer<'a> {
+    type_defs: &'a TypeDecls,
+    funs: &'a FunDecls,
+    /// The identifiers of the instances in the monomorphized program
+    type_instances: HashMap<(TypeDeclId::Id, Args), TypeDeclId::Id>,
+    fun_instances: HashMap<(FunDeclId::Id, Args), FunDeclId::Id>,
+    /// The instances, in the order of their identifiers. We generate them
+    /// in this order: the instances we have yet to generate are at the end.
+    type_queue: Vec<(TypeDeclId::Id, Args)>,
+    fun_queue: Vec<(FunDeclId::Id, Args)>,
+    /// The names we already used (we make sure the mangled names are unique)
+    names: HashSet<String>,
+    /// The dependencies of the declaration we are generating
+    deps: Vec<AnyDeclId>,
+    num_errors: usize,
+}
+
+/// The declarations of the monomorphized program
+struct Instances {
+    types: TypeDecls,
+    funs: FunDecls,
+    globals: GlobalDecls,
+    deps: HashMap<AnyDeclId, Vec<AnyDeclId>>,
+}
+
+impl<'a> Monomorphizer<'a> {
+    fn new(type_defs: &'a TypeDecls, funs: &'a FunDecls) -> Self {
+        let names = type_defs
+            .types
+            .iter()
+            .map(|d| d.name.to_string())
+            .chain(funs.iter().map(|d| d.name.to_string()))
+            .collect();
+        Monomorphizer {
+            type_defs,
+            funs,
+            type_instances: HashMap::new(),
+            fun_instances: HashMap::new(),
+            type_queue: Vec::new(),
+            fun_queue: Vec::new(),
+            names,
+            deps: Vec::new(),
+            num_errors: 0,
+        }
+    }
+
+    fn type_instance(&mut self, id: TypeDeclId::Id, args: Args) -> TypeDeclId::Id {
+        let key = (id, args);
+        let new_id = match self.type_instances.get(&key) {
+            Some(new_id) => *new_id,
+            None => {
+                let new_id = TypeDeclId::Id::new(self.type_queue.len());
+                self.type_queue.push(key.clone());
+                self.type_instances.insert(key, new_id);
+                new_id
+            }
+        };
+        self.deps.push(AnyDeclId::Type(new_id));
+        new_id
+    }
+
+    fn fun_instance(&mut self, id: FunDeclId::Id, args: Args) -> FunDeclId::Id {
+        let key = (id, args);
+        let new_id = match self.fun_instances.get(&key) {
+            Some(new_id) => *new_id,
+            None => {
+                let new_id = FunDeclId::Id::new(self.fun_queue.len());
+                self.fun_queue.push(key.clone());
+                self.fun_instances.insert(key, new_id);
+                new_id
+            }
+        };
+        self.deps.push(AnyDeclId::Fun(new_id));
+        new_id
+    }
+
+    /// Replace the generic types with their instances. The type must not
+    /// contain type variables.
+    fn mono_ty<R: Copy + Eq>(&mut self, ty: &Ty<R>) -> Ty<R> {
+        match ty {
+            Ty::Adt(TypeId::Adt(id), regions, args) => {
+                let args = args.iter().map(|ty| ty.erase_regions()).collect();
+                let id = self.type_instance(*id, args);
+                Ty::Adt(TypeId::Adt(id), regions.clone(), im::Vector::new())
+            }
+            Ty::Adt(id, regions, args) => Ty::Adt(
+                id.clone(),
+                regions.clone(),
+                args.iter().map(|ty| self.mono_ty(ty)).collect(),
+            ),
+            Ty::Array(ty, len) => Ty::Array(Box::new(self.mono_ty(ty)), len.clone()),
+            Ty::Slice(ty) => Ty::Slice(Box::new(self.mono_ty(ty))),
+            Ty::Ref(r, ty, kind) => Ty::Ref(*r, Box::new(self.mono_ty(ty)), *kind),
+            Ty::RawPtr(ty, kind) => Ty::RawPtr(Box::new(self.mono_ty(ty)), *kind),
+            Ty::FnPtr(inputs, output) => Ty::FnPtr(
+                inputs.iter().map(|ty| self.mono_ty(ty)).collect(),
+                Box::new(self.mono_ty(output)),
+            ),
+            Ty::Opaque(hidden_ty, bounds) => Ty::Opaque(
+                hidden_ty.as_ref().map(|ty| Box::new(self.mono_ty(ty))),
+                bounds.clone(),
+            ),
+            Ty::TraitProjection(trait_id, regions, args, name) => Ty::TraitProjection(
+                *trait_id,
+                regions.clone(),
+                args.iter().map(|ty| self.mono_ty(ty)).collect(),
+                name.clone(),
+            ),
+            Ty::TypeVar(_)
+            | Ty::Bool
+            | Ty::Char
+            | Ty::Never
+            | Ty::Integer(_)
+            | Ty::Float(_)
+            | Ty::Str
+            | Ty::ConstGeneric(_)
+            | Ty::DynTrait(_) => ty.clone(),
+        }
+    }
+
+    fn mangle_ty(&self, ty: &ETy) -> String {
+        let with_args = |head: &str, args: &im::Vector<ETy>| {
+            let mut s = head.to_string();
+            for arg in args {
+                s.push('_');
+                s.push_str(&self.mangle_ty(arg));
+            }
+            s
+        };
+        match ty {
+            Ty::Adt(TypeId::Adt(id), _, args) => {
+                let name = &self.type_defs.get_type_def(*id).unwrap().name;
+                with_args(&last_ident(name), args)
+            }
+            Ty::Adt(TypeId::Tuple, _, args) if args.is_empty() => "unit".to_string(),
+            Ty::Adt(TypeId::Tuple, _, args) => with_args("tuple", args),
+            Ty::Adt(TypeId::Assumed(aty), _, args) => with_args(&format!("{aty:?}"), args),
+            Ty::TypeVar(_) => "T".to_string(),
+            Ty::Bool => "bool".to_string(),
+            Ty::Char => "char".to_string(),
+            Ty::Never => "never".to_string(),
+            Ty::Str => "str".to_string(),
+            Ty::Integer(ity) => format!("{ity:?}").to_lowercase(),
+            Ty::Float(fty) => format!("{fty:?}").to_lowercase(),
+            Ty::Array(ty, len) => format!("array_{}_{}", self.mangle_ty(ty), mangle_cg(len)),
+            Ty::Slice(ty) => format!("slice_{}", self.mangle_ty(ty)),
+            Ty::Ref(_, ty, RefKind::Shared) => format!("ref_{}", self.mangle_ty(ty)),
+            Ty::Ref(_, ty, RefKind::Mut) => format!("mut_ref_{}", self.mangle_ty(ty)),
+            Ty::RawPtr(ty, RefKind::Shared) => format!("ptr_{}", self.mangle_ty(ty)),
+            Ty::RawPtr(ty, RefKind::Mut) => format!("mut_ptr_{}", self.mangle_ty(ty)),
+            Ty::ConstGeneric(cg) => mangle_cg(cg),
+            Ty::DynTrait(_) => "dyn".to_string(),
+            Ty::FnPtr(_, _) => "fn".to_string(),
+            Ty::Opaque(_, _) => "opaque".to_string(),
+            Ty::TraitProjection(_, _, _, name) => format!("assoc_{name}"),
+        }
+    }
+
+    /// The name of an instance: we append the mangled arguments to the name
+    /// of the declaration
+    fn mangle_name(&mut self, name: &Name, args: &Args) -> Name {
+        if args.is_empty() {
+            return name.clone();
+        }
+        let suffix: Vec<String> = args.iter().map(|ty| self.mangle_ty(ty)).collect();
+        let base = format!("{}_{}", last_ident(name), suffix.join("_"));
+        let mut name = name.clone();
+        let last = name.name.iter().rposition(PathElem::is_ident).unwrap();
+        let mut i = 0;
+        loop {
+            let ident = if i == 0 {
+                base.clone()
+            } else {
+                format!("{base}_{i}")
+            };
+            name.name[last] = PathElem::Ident(ident);
+            if self.names.insert(name.to_string()) {
+                return name;
+            }
+            i += 1;
+        }
+    }
+
+    fn check_arity(&mut self, name: &Name, num_params: usize, args: &Args) {
+        if num_params != args.len() {
+            error!(
+                "Can't monomorphize {name}: it has {num_params} generic parameters, but it is \
+                 used with {} arguments",
+                args.len()
+            );
+            self.num_errors += 1;
+        }
+    }
+
+    /// Check that the arguments of an instance are not nested too deeply.
+    /// If they are, we don't instantiate the body (or the fields) of the
+    /// declaration, so that we don't generate new instances.
+    fn check_depth(&mut self, name: &Name, args: &Args) -> bool {
+        let depth = max_ty_depth(args.iter());
+        if depth > MAX_ARGS_DEPTH {
+            error!(
+                "Can't monomorphize {name}: its arguments are nested more than {MAX_ARGS_DEPTH} \
+                 times (it is probably used with polymorphic recursion)"
+            );
+            self.num_errors += 1;
+            false
+        } else {
+            true
+        }
+    }
+
+    fn instantiate_sig(&mut self, sig: &FunSig, subst: &Subst) -> FunSig {
+        FunSig {
+            type_params: TypeVarId::Vector::new(),
+            const_generic_params: ConstGenericVarId::Vector::new(),
+            trait_clauses: Vec::new(),
+            inputs: sig
+                .inputs
+                .iter()
+                .map(|ty| self.mono_ty(&subst.rty(ty)))
+                .collect(),
+            output: self.mono_ty(&subst.rty(&sig.output)),
+            ..sig.clone()
+        }
+    }
+
+    fn instantiate_body(&mut self, body: &ExprBody, subst: &Subst, args: &Args) -> ExprBody {
+        // We need the types of the places in the original program, to find
+        // the instances of the types we project from
+        let types = ExprBody {
+            meta: body.meta,
+            arg_count: body.arg_count,
+            return_local: body.return_local,
+            locals: body
+                .locals
+                .iter()
+                .map(|v| Var {
+                    ty: subst.ety(&v.ty),
+                    ..v.clone()
+                })
+                .collect(),
+            body: Statement::new(body.meta, RawStatement::Nop),
+        };
+        let mut st = body.body.clone();
+        let mut visitor = MonoBody {
+            mono: self,
+            subst,
+            args,
+            types: &types,
+        };
+        visitor.visit_statement_mut(&mut st);
+        let locals = types
+            .locals
+            .iter()
+            .map(|v| Var {
+                ty: self.mono_ty(&v.ty),
+                ..v.clone()
+            })
+            .collect();
+        ExprBody {
+            meta: body.meta,
+            arg_count: body.arg_count,
+            return_local: body.return_local,
+            locals,
+            body: st,
+        }
+    }
+
+    fn gen_type(&mut self, id: TypeDeclId::Id, args: &Args, new_id: TypeDeclId::Id) -> TypeDecl {
+        let type_defs = self.type_defs;
+        let decl = type_defs.get_type_def(id).unwrap();
+        let num_params = decl.type_params.len() + decl.const_generic_params.len();
+        self.check_arity(&decl.name, num_params, args);
+        let subst = Subst::new(&decl.type_params, &decl.const_generic_params, args);
+        let within_depth = self.check_depth(&decl.name, args);
+        let mut mono_field = |f: &Field| Field {
+            ty: self.mono_ty(&subst.rty(&f.ty)),
+            ..f.clone()
+        };
+        let kind = match &decl.kind {
+            _ if !within_depth => TypeDeclKind::Opaque,
+            TypeDeclKind::Struct(fields) => {
+                TypeDeclKind::Struct(fields.iter().map(&mut mono_field).collect())
+            }
+            TypeDeclKind::Union(fields) => {
+                TypeDeclKind::Union(fields.iter().map(&mut mono_field).collect())
+            }
+            TypeDeclKind::Enum(variants) => TypeDeclKind::Enum(
+                variants
+                    .iter()
+                    .map(|v| Variant {
+                        fields: v.fields.iter().map(&mut mono_field).collect(),
+                        ..v.clone()
+                    })
+                    .collect(),
+            ),
+            TypeDeclKind::Opaque => TypeDeclKind::Opaque,
+        };
+        TypeDecl {
+            def_id: new_id,
+            name: self.mangle_name(&decl.name, args),
+            type_params: TypeVarId::Vector::new(),
+            const_generic_params: ConstGenericVarId::Vector::new(),
+            trait_clauses: Vec::new(),
+            kind,
+            ..decl.clone()
+        }
+    }
+
+    fn gen_fun(&mut self, id: FunDeclId::Id, args: &Args, new_id: FunDeclId::Id) -> FunDecl {
+        let funs = self.funs;
+        let decl = funs.get(id).unwrap();
+        let sig = &decl.signature;
+        let num_params = sig.type_params.len() + sig.const_generic_params.len();
+        self.check_arity(&decl.name, num_params, args);
+        let subst = Subst::new(&sig.type_params, &sig.const_generic_params, args);
+        let signature = self.instantiate_sig(sig, &subst);
+        let body = if self.check_depth(&decl.name, args) {
+            decl.body
+                .as_ref()
+                .map(|body| self.instantiate_body(body, &subst, args))
+        } else {
+            None
+        };
+        FunDecl {
+            def_id: new_id,
+            meta: decl.meta,
+            name: self.mangle_name(&decl.name, args),
+            signature,
+            body,
+            precision_losses: decl.precision_losses.clone(),
+            unsafe_operations: decl.unsafe_operations.clone(),
+            attr_info: decl.attr_info.clone(),
+            contract: decl.contract.clone(),
+        }
+    }
+
+    fn gen_global(&mut self, decl: &GlobalDecl) -> GlobalDecl {
+        let subst = Subst::new(
+            &TypeVarId::Vector::new(),
+            &ConstGenericVarId::Vector::new(),
+            &Vec::new(),
+        );
+        GlobalDecl {
+            def_id: decl.def_id,
+            meta: decl.meta,
+            name: decl.name.clone(),
+            ty: self.mono_ty(&decl.ty),
+            is_mutable: decl.is_mutable,
+            interior_mutability: decl.interior_mutability,
+            body: decl
+                .body
+                .as_ref()
+                .map(|body| self.instantiate_body(body, &subst, &Vec::new())),
+            attr_info: decl.attr_info.clone(),
+        }
+    }
+
+    /// Generate the instances which are reachable from the entry points and
+    /// from the globals. The globals keep their identifiers.
+    fn run(&mut self, entry_points: &[FunDeclId::Id], globals: &GlobalDecls) -> Instances {
+        for id in entry_points {
+            self.fun_instance(*id, Vec::new());
+        }
+
+        let mut deps = HashMap::new();
+        let mut gen_globals = Vec::new();
+        for decl in globals.iter() {
+            self.deps.clear();
+            gen_globals.push(self.gen_global(decl));
+            deps.insert(
+                AnyDeclId::Global(decl.def_id),
+                std::mem::take(&mut self.deps),
+            );
+        }
+
+        // Generating an instance may require new instances: we stop once we
+        // generated all of them
+        let mut gen_types = Vec::new();
+        let mut gen_funs = Vec::new();
+        loop {
+            self.deps.clear();
+            if gen_types.len() < self.type_queue.len() {
+                let (id, args) = self.type_queue[gen_types.len()].clone();
+                let new_id = TypeDeclId::Id::new(gen_types.len());
+                gen_types.push(self.gen_type(id, &args, new_id));
+                deps.insert(AnyDeclId::Type(new_id), std::mem::take(&mut self.deps));
+            } else if gen_funs.len() < self.fun_queue.len() {
+                let (id, args) = self.fun_queue[gen_funs.len()].clone();
+                let new_id = FunDeclId::Id::new(gen_funs.len());
+                gen_funs.push(self.gen_fun(id, &args, new_id));
+                deps.insert(AnyDeclId::Fun(new_id), std::mem::take(&mut self.deps));
+            } else {
+                break;
+            }
+        }
+
+        Instances {
+            types: TypeDecls {
+                types: TypeDeclId::Vector::from(gen_types),
+            },
+            funs: FunDeclId::Vector::from(gen_funs),
+            globals: GlobalDeclId::Vector::from(gen_globals),
+            deps,
+        }
+    }
+}
+
+fn last_ident(name: &Name) -> String {
+    name.name
+        .iter()
+        .rev()
+        .find_map(|elem| match elem {
+            PathElem::Ident(s) => Some(s.clone()),
+            PathElem::Disambiguator(_) => None,
+        })
+        .unwrap()
+}
+
+fn mangle_cg(cg: &ConstGeneric) -> String {
+    // We don't keep the types of the scalars (`3 : usize`)
+    let s = cg.to_string();
+    sanitize(s.split(" : ").next().unwrap())
+}
+
+/// The globals referred to by a constant
+fn constant_deps(value: &OperandConstantValue, deps: &mut Vec<AnyDeclId>) {
+    match value {
+        OperandConstantValue::ConstantId(id) | OperandConstantValue::StaticId(id) => {
+            deps.push(AnyDeclId::Global(*id))
+        }
+        OperandConstantValue::Adt(_, values) | OperandConstantValue::Array(values) => {
+            for value in values {
+                constant_deps(value, deps)
+            }
+        }
+        OperandConstantValue::PrimitiveValue(_)
+        | OperandConstantValue::TraitConst(_, _, _)
+        | OperandConstantValue::ZST(_)
+        | OperandConstantValue::Bytes(_) => (),
+    }
+}
+
+/// Instantiate the statements of a body
+struct MonoBody<'m, 'a> {
+    mono: &'m mut Monomorphizer<'a>,
+    subst: &'m Subst,
+    /// The arguments of the function we instantiate
+    args: &'m Args,
+    /// The locals, with their types in the original program
+    types: &'m ExprBody,
+}
+
+impl MonoBody<'_, '_> {
+    fn mono_ty(&mut self, ty: &mut ETy) {
+        *ty = self.mono.mono_ty(&self.subst.ety(ty));
+    }
+
+    /// The instance of a function referred to by a closure or a function
+    /// pointer (see the comments at the top of the file)
+    fn fun_without_args(&mut self, id: FunDeclId::Id) -> FunDeclId::Id {
+        let sig = &self.mono.funs.get(id).unwrap().signature;
+        let args = if sig.type_params.is_empty() && sig.const_generic_params.is_empty() {
+            Vec::new()
+        } else {
+            self.args.clone()
+        };
+        self.mono.fun_instance(id, args)
+    }
+}
+
+impl AstMutVisitor for MonoBody<'_, '_> {
+    fn visit_call_mut(&mut self, call: &mut Call) {
+        let args: Args = call.type_args.iter().map(|ty| self.subst.ety(ty)).collect();
+        match &mut call.func {
+            FunId::Regular(id) | FunId::TraitMethod(_, id) | FunId::Virtual(_, id) => {
+                let funs = self.mono.funs;
+                let callee_sig = &funs.get(*id).unwrap().signature;
+                if let Some(sig) = &mut call.sig {
+                    let subst = Subst::new(
+                        &callee_sig.type_params,
+                        &callee_sig.const_generic_params,
+                        &args,
+                    );
+                    **sig = self.mono.instantiate_sig(callee_sig, &subst);
+                }
+                *id = self.mono.fun_instance(*id, args);
+                call.type_args = Vec::new();
+            }
+            FunId::Indirect(_) | FunId::Assumed(_) => {
+                call.type_args = args.iter().map(|ty| self.mono.mono_ty(ty)).collect();
+            }
+        }
+        walk_call_mut(self, call)
+    }
+
+    fn visit_rvalue_mut(&mut self, rv: &mut Rvalue) {
+        match rv {
+            Rvalue::Aggregate(AggregateKind::Option(_, ty), _) => self.mono_ty(ty),
+            Rvalue::Aggregate(AggregateKind::Adt(id, _, _, args), _) => {
+                let args = std::mem::take(args)
+                    .iter()
+                    .map(|ty| self.subst.ety(ty))
+                    .collect();
+                *id = self.mono.type_instance(*id, args);
+            }
+            Rvalue::Aggregate(AggregateKind::Closure(id, _), _) | Rvalue::FnPtr(id) => {
+                *id = self.fun_without_args(*id)
+            }
+            Rvalue::Unsize(_, ty1, ty2) | Rvalue::Cast(_, _, ty1, ty2) => {
+                self.mono_ty(ty1);
+                self.mono_ty(ty2);
+            }
+            Rvalue::Global(id) | Rvalue::GlobalRef(id, _) => {
+                self.mono.deps.push(AnyDeclId::Global(*id))
+            }
+            _ => (),
+        }
+        walk_rvalue_mut(self, rv)
+    }
+
+    fn visit_operand_mut(&mut self, op: &mut Operand) {
+        if let Operand::Const(ty, value) = op {
+            self.mono_ty(ty);
+            match value {
+                OperandConstantValue::TraitConst(_, args, _) => {
+                    for ty in args.iter_mut() {
+                        self.mono_ty(ty)
+                    }
+                }
+                OperandConstantValue::ZST(ty) => self.mono_ty(ty),
+                _ => (),
+            }
+            constant_deps(value, &mut self.mono.deps);
+        }
+        walk_operand_mut(self, op)
+    }
+
+    fn visit_place_mut(&mut self, p: &mut Place) {
+        // The projections into the ADTs refer to the generic type: we look up
+        // the type we project from to find its instance
+        let type_defs = self.mono.type_defs;
+        let mut prefix = Place::new(p.var_id);
+        for pe in p.projection.iter_mut() {
+            let original = pe.clone();
+            match pe {
+                ProjectionElem::Field(FieldProjKind::Adt(id, _), _)
+                | ProjectionElem::Field(FieldProjKind::Union(id), _) => {
+                    if let Ty::Adt(TypeId::Adt(_), _, args) =
+                        self.types.place_type(type_defs, &prefix)
+                    {
+                        *id = self.mono.type_instance(*id, args.into_iter().collect());
+                    }
+                }
+                _ => (),
+            }
+            prefix.projection.push_back(original);
+        }
+        walk_place_mut(self, p)
+    }
+}
+
+fn mk_group<Id: Copy>(is_rec: bool, ids: Vec<Id>) -> GDeclarationGroup<Id> {
+    if is_rec {
+        GDeclarationGroup::Rec(ids)
+    } else {
+        GDeclarationGroup::NonRec(ids[0])
+    }
+}
+
+/// Group the declarations of the monomorphized program: the declarations
+/// come after their dependencies, and the mutually recursive declarations
+/// are grouped together.
+fn group_declarations(
+    ids: &[AnyDeclId],
+    deps: &HashMap<AnyDeclId, Vec<AnyDeclId>>,
+) -> Vec<DeclarationGroup> {
+    // The graph library requires the nodes to be ordered: we use the indices
+    // of the declarations
+    let index: HashMap<AnyDeclId, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    let mut graph = DiGraphMap::<usize, ()>::new();
+    for i in 0..ids.len() {
+        graph.add_node(i);
+    }
+    for (i, id) in ids.iter().enumerate() {
+        for dep in deps.get(id).unwrap() {
+            graph.add_edge(i, *index.get(dep).unwrap(), ());
+        }
+    }
+
+    // The SCCs are listed in reverse topological order: the dependencies
+    // come first
+    let mut groups = Vec::new();
+    for mut scc in tarjan_scc(&graph) {
+        scc.sort();
+        let is_rec = scc.len() > 1 || graph.contains_edge(scc[0], scc[0]);
+        // A global and a function may depend on each other: we split the
+        // SCCs by kind of declaration
+        let mut types = Vec::new();
+        let mut funs = Vec::new();
+        let mut globals = Vec::new();
+        for i in scc {
+            match ids[i] {
+                AnyDeclId::Type(id) => types.push(id),
+                AnyDeclId::Fun(id) => funs.push(id),
+                AnyDeclId::Global(id) => globals.push(id),
+            }
+        }
+        if !types.is_empty() {
+            groups.push(DeclarationGroup::Type(mk_group(is_rec, types)));
+        }
+        if !funs.is_empty() {
+            groups.push(DeclarationGroup::Fun(mk_group(is_rec, funs)));
+        }
+        if !globals.is_empty() {
+            groups.push(DeclarationGroup::Global(mk_group(is_rec, globals)));
+        }
+    }
+    groups
+}
+
+/// The monomorphized program
+pub struct Monomorphized {
+    pub ordered_decls: OrderedDecls,
+    pub type_defs: TypeDecls,
+    pub funs: FunDecls,
+    pub globals: GlobalDecls,
+    pub trait_decls: TraitDecls,
+    pub trait_impls: TraitImpls,
+}
+
+/// Monomorphize the program. The entry points are the public non-generic
+/// functions of the crate.
+pub fn monomorphize(
+    ordered_decls: &OrderedDecls,
+    type_defs: &TypeDecls,
+    funs: &FunDecls,
+    globals: &GlobalDecls,
+    trait_decls: &TraitDecls,
+    trait_impls: &TraitImpls,
+) -> Result<Monomorphized, ()> {
+    let entry_points: Vec<FunDeclId::Id> = funs
+        .iter()
+        .filter(|d| {
+            let info = &ordered_decls.decls_info[&AnyDeclId::Fun(d.def_id)];
+            info.is_local()
+                && info.visibility == Visibility::Public
+                && d.signature.type_params.is_empty()
+                && d.signature.const_generic_params.is_empty()
+        })
+        .map(|d| d.def_id)
+        .collect();
+    let mut mono = Monomorphizer::new(type_defs, funs);
+    let instances = mono.run(&entry_points, globals);
+    if mono.num_errors > 0 {
+        return Err(());
+    }
+
+    // The information about the instances is the one of their declarations
+    let instance_info = |id: AnyDeclId, args: &Args| -> DeclInfo {
+        let mut info = ordered_decls.decls_info[&id].clone();
+        // The declarations of the external crates are not instantiated
+        if !args.is_empty() {
+            info.extern_ref = None;
+        }
+        info
+    };
+    let mut ids = Vec::new();
+    let mut decls_info = HashMap::new();
+    for (i, (id, args)) in mono.type_queue.iter().enumerate() {
+        let new_id = AnyDeclId::Type(TypeDeclId::Id::new(i));
+        ids.push(new_id);
+        decls_info.insert(new_id, instance_info(AnyDeclId::Type(*id), args));
+    }
+    for (i, (id, args)) in mono.fun_queue.iter().enumerate() {
+        let new_id = AnyDeclId::Fun(FunDeclId::Id::new(i));
+        ids.push(new_id);
+        decls_info.insert(new_id, instance_info(AnyDeclId::Fun(*id), args));
+    }
+    for decl in globals.iter() {
+        let id = AnyDeclId::Global(decl.def_id);
+        ids.push(id);
+        decls_info.insert(id, ordered_decls.decls_info[&id].clone());
+    }
+    let decls = group_declarations(&ids, &instances.deps);
+
+    // The declarations which are not generic keep their Rust identifiers
+    let type_instance = |id: TypeDeclId::Id| mono.type_instances.get(&(id, Vec::new())).copied();
+    let fun_instance = |id: FunDeclId::Id| mono.fun_instances.get(&(id, Vec::new())).copied();
+    let ordered_decls = OrderedDecls {
+        files: ordered_decls.files.clone(),
+        decls,
+        decls_info,
+        file_to_id: ordered_decls.file_to_id.clone(),
+        id_to_file: ordered_decls.id_to_file.clone(),
+        type_rid_to_id: ordered_decls
+            .type_rid_to_id
+            .iter()
+            .filter_map(|(rid, id)| Some((*rid, type_instance(*id)?)))
+            .collect(),
+        fun_rid_to_id: ordered_decls
+            .fun_rid_to_id
+            .iter()
+            .filter_map(|(rid, id)| Some((*rid, fun_instance(*id)?)))
+            .collect(),
+        global_rid_to_id: ordered_decls.global_rid_to_id.clone(),
+        trait_rid_to_id: ordered_decls.trait_rid_to_id.clone(),
+        trait_impl_rid_to_id: ordered_decls.trait_impl_rid_to_id.clone(),
+        type_path_to_id: ordered_decls
+            .type_path_to_id
+            .iter()
+            .filter_map(|(path, id)| Some((path.clone(), type_instance(*id)?)))
+            .collect(),
+        fun_path_to_id: ordered_decls
+            .fun_path_to_id
+            .iter()
+            .filter_map(|(path, id)| Some((path.clone(), fun_instance(*id)?)))
+            .collect(),
+        global_path_to_id: ordered_decls.global_path_to_id.clone(),
+        trait_path_to_id: ordered_decls.trait_path_to_id.clone(),
+        trait_impl_path_to_id: ordered_decls.trait_impl_path_to_id.clone(),
+    };
+
+    let trait_decls = trait_decls
+        .iter()
+        .map(|d| TraitDecl {
+            methods: d
+                .methods
+                .iter()
+                .map(|(name, id)| (name.clone(), id.and_then(fun_instance)))
+                .collect(),
+            ..d.clone()
+        })
+        .collect();
+    let trait_impls = trait_impls
+        .iter()
+        .map(|d| TraitImpl {
+            self_type: d.self_type.and_then(type_instance),
+            methods: d
+                .methods
+                .iter()
+                .filter_map(|(name, id)| Some((name.clone(), fun_instance(*id)?)))
+                .collect(),
+            ..d.clone()
+        })
+        .collect();
+
+    Ok(Monomorphized {
+        ordered_decls,
+        type_defs: instances.types,
+        funs: instances.funs,
+        globals: instances.globals,
+        trait_decls,
+        trait_impls,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::meta::{AttrInfo, Meta};
+    use crate::monomorphize::*;
+    use crate::regions_hierarchy::RegionGroups;
+    use crate::test_utils::*;
+    use crate::ullbc_ast::TraitImplId;
+    use crate::values::VarId;
+
+    /// The signature of a function with type parameters `T0`, `T1`, ...
+    fn mk_generic_sig(type_params: usize, inputs: Vec<RTy>, output: RTy) -> FunSig {
+        let mut sig = mk_sig(inputs, output);
+        sig.type_params = (0..type_params)
+            .map(|i| TypeVar::new(TypeVarId::Id::new(i), format!("T{i}")))
+            .collect();
+        sig
+    }
+
+    /// A function whose locals are the return value and its inputs
+    fn mk_fun(id: usize, name: &str, sig: FunSig, st: RawStatement) -> FunDecl {
+        let locals = std::iter::once(&sig.output)
+            .chain(sig.inputs.iter())
+            .enumerate()
+            .map(|(i, ty)| mk_var(i, None, ty.erase_regions()))
+            .collect();
+        let body = mk_body(sig.inputs.len(), locals, vec![st, RawStatement::Return]);
+        mk_fun_decl(id, &["test", name], sig, Some(body))
+    }
+
+    /// `Wrapper<arg>`
+    fn wrapper(arg: RTy) -> RTy {
+        Ty::Adt(
+            TypeId::Adt(TypeDeclId::ZERO),
+            im::Vector::new(),
+            im::vector![arg],
+        )
+    }
+
+    /// The instance of a type without generic arguments
+    fn instance_ty(id: usize) -> RTy {
+        Ty::Adt(
+            TypeId::Adt(TypeDeclId::Id::new(id)),
+            im::Vector::new(),
+            im::Vector::new(),
+        )
+    }
+
+    /// ```text
+    /// struct Wrapper<T> { x: T }
+    /// ```
+    fn wrapper_type_defs() -> TypeDecls {
+        TypeDecls {
+            types: TypeDeclId::Vector::from(vec![TypeDecl {
+                def_id: TypeDeclId::ZERO,
+                meta: Meta::dummy(),
+                name: Name::from(vec!["test".to_string(), "Wrapper".to_string()]),
+                region_params: RegionVarId::Vector::new(),
+                type_params: TypeVarId::Vector::from(vec![TypeVar::new(
+                    TypeVarId::ZERO,
+                    "T".to_string(),
+                )]),
+                const_generic_params: ConstGenericVarId::Vector::new(),
+                trait_clauses: Vec::new(),
+                regions_hierarchy: RegionGroups::new(),
+                kind: TypeDeclKind::Struct(FieldId::Vector::from(vec![Field {
+                    meta: Meta::dummy(),
+                    name: Some("x".to_string()),
+                    ty: Ty::TypeVar(TypeVarId::ZERO),
+                }])),
+                repr_packed: None,
+                under_aligned_fields: Vec::new(),
+                recursive_fields: Vec::new(),
+                attr_info: AttrInfo::default(),
+            }]),
+        }
+    }
+
+    /// ```text
+    /// fn get<T>(w: Wrapper<T>) -> T { ret := move w.x }
+    /// ```
+    fn mk_get(id: usize) -> FunDecl {
+        let t = Ty::TypeVar(TypeVarId::ZERO);
+        let mut w_x = Place::new(VarId::Id::new(1));
+        w_x.projection.push_back(ProjectionElem::Field(
+            FieldProjKind::Adt(TypeDeclId::ZERO, None),
+            FieldId::ZERO,
+        ));
+        mk_fun(
+            id,
+            "get",
+            mk_generic_sig(1, vec![wrapper(t.clone())], t),
+            RawStatement::Assign(Place::new(VarId::ZERO), Rvalue::Use(Operand::Move(w_x))),
+        )
+    }
+
+    /// The call in a body generated by [mk_fun]
+    fn body_call(decl: &FunDecl) -> &Call {
+        let body = decl.body.as_ref().unwrap();
+        body.body.content.as_sequence().0.content.as_call()
+    }
+
+    #[test]
+    fn test_monomorphize() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let t = Ty::TypeVar(TypeVarId::ZERO);
+        let type_defs = wrapper_type_defs();
+
+        // ```
+        // fn f(w: Wrapper<u32>) -> u32 { ret := get::<u32>(move w) }
+        // fn get<T>(w: Wrapper<T>) -> T { ret := move w.x }
+        // fn unused<T>(x: T) -> T { ret := move x }
+        // ```
+        let ret = Place::new(VarId::ZERO);
+        let w = Place::new(VarId::Id::new(1));
+        let get_id = FunDeclId::Id::new(1);
+        let f = mk_fun(
+            0,
+            "f",
+            mk_generic_sig(0, vec![wrapper(u32_ty.clone())], u32_ty.clone()),
+            RawStatement::Call(Call {
+                func: FunId::Regular(get_id),
+                region_args: Vec::new(),
+                type_args: vec![Ty::Integer(IntegerTy::U32)],
+                args: vec![Operand::Move(w.clone())],
+                dest: ret.clone(),
+                sig: None,
+            }),
+        );
+        let get = mk_get(1);
+        let unused = mk_fun(
+            2,
+            "unused",
+            mk_generic_sig(1, vec![t.clone()], t),
+            RawStatement::Assign(ret, Rvalue::Use(Operand::Move(w))),
+        );
+        let funs = FunDeclId::Vector::from(vec![f, get, unused]);
+
+        let mut mono = Monomorphizer::new(&type_defs, &funs);
+        let instances = mono.run(&[FunDeclId::ZERO], &GlobalDeclId::Vector::new());
+        assert!(mono.num_errors == 0);
+
+        // ```
+        // struct Wrapper_u32 { x: u32 }
+        // ```
+        assert!(instances.types.types.len() == 1);
+        let wrapper_u32 = instances.types.get_type_def(TypeDeclId::ZERO).unwrap();
+        assert!(wrapper_u32.name.to_string() == "test::Wrapper_u32");
+        assert!(wrapper_u32.type_params.is_empty());
+        assert!(wrapper_u32.kind.as_struct().get(FieldId::ZERO).unwrap().ty == u32_ty);
+        let wrapper_u32_ty = instance_ty(0);
+
+        // ```
+        // fn f(w: Wrapper_u32) -> u32 { ret := get_u32(move w) }
+        // fn get_u32(w: Wrapper_u32) -> u32 { ret := move w.x }
+        // ```
+        // We don't instantiate `unused`
+        assert!(instances.funs.len() == 2);
+        let f = instances.funs.get(FunDeclId::ZERO).unwrap();
+        assert!(f.signature.inputs == vec![wrapper_u32_ty.clone()]);
+        let call = body_call(f);
+        assert!(call.type_args.is_empty());
+        let get_u32_id = *call.func.as_regular();
+        let get_u32 = instances.funs.get(get_u32_id).unwrap();
+        assert!(get_u32.name.to_string() == "test::get_u32");
+        assert!(get_u32.signature.type_params.is_empty());
+        assert!(get_u32.signature.output == u32_ty);
+        let body = get_u32.body.as_ref().unwrap();
+        assert!(body.locals.get(VarId::Id::new(1)).unwrap().ty == wrapper_u32_ty.erase_regions());
+        let (_, rv) = body.body.content.as_sequence().0.content.as_assign();
+        let field =
+            ProjectionElem::Field(FieldProjKind::Adt(TypeDeclId::ZERO, None), FieldId::ZERO);
+        assert!(matches!(rv, Rvalue::Use(Operand::Move(p)) if p.projection[0] == field));
+
+        // The type comes before the functions, and `get_u32` before `f`
+        let ids: Vec<AnyDeclId> = vec![
+            AnyDeclId::Type(TypeDeclId::ZERO),
+            AnyDeclId::Fun(FunDeclId::ZERO),
+            AnyDeclId::Fun(get_u32_id),
+        ];
+        let groups = group_declarations(&ids, &instances.deps);
+        let order: Vec<AnyDeclId> = groups.iter().flat_map(|g| g.get_ids()).collect();
+        assert!(order == vec![ids[0], ids[2], ids[1]]);
+    }
+
+    #[test]
+    fn test_trait_method() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let type_defs = wrapper_type_defs();
+
+        // The calls to trait methods which we resolved refer to the method of
+        // the implementation, with the arguments of this method:
+        // ```
+        // impl<T> Get<T> for Wrapper<T> { fn get(w: Wrapper<T>) -> T { ... } }
+        //
+        // fn f(w: Wrapper<u32>) -> u32 {
+        //   ret := <Wrapper<u32> as Get<u32>>::get::<u32>(move w)
+        // }
+        // ```
+        let get_id = FunDeclId::Id::new(1);
+        let f = mk_fun(
+            0,
+            "f",
+            mk_generic_sig(0, vec![wrapper(u32_ty.clone())], u32_ty.clone()),
+            RawStatement::Call(Call {
+                func: FunId::TraitMethod(TraitImplId::ZERO, get_id),
+                region_args: Vec::new(),
+                type_args: vec![Ty::Integer(IntegerTy::U32)],
+                args: vec![Operand::Move(Place::new(VarId::Id::new(1)))],
+                dest: Place::new(VarId::ZERO),
+                sig: None,
+            }),
+        );
+        let funs = FunDeclId::Vector::from(vec![f, mk_get(1)]);
+
+        let mut mono = Monomorphizer::new(&type_defs, &funs);
+        let instances = mono.run(&[FunDeclId::ZERO], &GlobalDeclId::Vector::new());
+        assert!(mono.num_errors == 0);
+
+        // We call the instance of the method, which is still a method of the
+        // implementation
+        assert!(instances.funs.len() == 2);
+        let call = body_call(instances.funs.get(FunDeclId::ZERO).unwrap());
+        assert!(call.type_args.is_empty());
+        let (impl_id, get_u32_id) = match &call.func {
+            FunId::TraitMethod(impl_id, id) => (*impl_id, *id),
+            _ => panic!("unexpected callee: {:?}", call.func),
+        };
+        assert!(impl_id == TraitImplId::ZERO);
+        let get_u32 = instances.funs.get(get_u32_id).unwrap();
+        assert!(get_u32.name.to_string() == "test::get_u32");
+        assert!(get_u32.signature.inputs == vec![instance_ty(0)]);
+        assert!(get_u32.signature.output == u32_ty);
+    }
+
+    #[test]
+    fn test_nested_generics() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let type_defs = wrapper_type_defs();
+
+        // ```
+        // fn f(w: Wrapper<Wrapper<u32>>) -> Wrapper<u32> {
+        //   ret := get::<Wrapper<u32>>(move w)
+        // }
+        // ```
+        let f = mk_fun(
+            0,
+            "f",
+            mk_generic_sig(
+                0,
+                vec![wrapper(wrapper(u32_ty.clone()))],
+                wrapper(u32_ty.clone()),
+            ),
+            RawStatement::Call(Call {
+                func: FunId::Regular(FunDeclId::Id::new(1)),
+                region_args: Vec::new(),
+                type_args: vec![wrapper(u32_ty.clone()).erase_regions()],
+                args: vec![Operand::Move(Place::new(VarId::Id::new(1)))],
+                dest: Place::new(VarId::ZERO),
+                sig: None,
+            }),
+        );
+        let funs = FunDeclId::Vector::from(vec![f, mk_get(1)]);
+
+        let mut mono = Monomorphizer::new(&type_defs, &funs);
+        let instances = mono.run(&[FunDeclId::ZERO], &GlobalDeclId::Vector::new());
+        assert!(mono.num_errors == 0);
+
+        // ```
+        // struct Wrapper_Wrapper_u32 { x: Wrapper_u32 }
+        // struct Wrapper_u32 { x: u32 }
+        // ```
+        assert!(instances.types.types.len() == 2);
+        let outer = instances.types.get_type_def(TypeDeclId::ZERO).unwrap();
+        assert!(outer.name.to_string() == "test::Wrapper_Wrapper_u32");
+        assert!(outer.kind.as_struct().get(FieldId::ZERO).unwrap().ty == instance_ty(1));
+        let inner = instances
+            .types
+            .get_type_def(TypeDeclId::Id::new(1))
+            .unwrap();
+        assert!(inner.name.to_string() == "test::Wrapper_u32");
+        assert!(inner.kind.as_struct().get(FieldId::ZERO).unwrap().ty == u32_ty);
+
+        // ```
+        // fn get_Wrapper_u32(w: Wrapper_Wrapper_u32) -> Wrapper_u32 {
+        //   ret := move w.x
+        // }
+        // ```
+        let call = body_call(instances.funs.get(FunDeclId::ZERO).unwrap());
+        let get = instances.funs.get(*call.func.as_regular()).unwrap();
+        assert!(get.name.to_string() == "test::get_Wrapper_u32");
+        assert!(get.signature.inputs == vec![instance_ty(0)]);
+        assert!(get.signature.output == instance_ty(1));
+        let body = get.body.as_ref().unwrap();
+        let (_, rv) = body.body.content.as_sequence().0.content.as_assign();
+        let field =
+            ProjectionElem::Field(FieldProjKind::Adt(TypeDeclId::ZERO, None), FieldId::ZERO);
+        assert!(matches!(rv, Rvalue::Use(Operand::Move(p)) if p.projection[0] == field));
+    }
+
+    #[test]
+    fn test_polymorphic_recursion() {
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        let boxed = |ty: ETy| {
+            Ty::Adt(
+                TypeId::Assumed(AssumedTy::Box),
+                im::Vector::new(),
+                im::vector![ty],
+            )
+        };
+
+        // ```
+        // fn f() -> u32 { ret := g::<u32>() }
+        // fn g<T>() -> u32 { ret := g::<Box<T>>() }
+        // ```
+        let g_id = FunDeclId::Id::new(1);
+        let call = |type_arg: ETy| {
+            RawStatement::Call(Call {
+                func: FunId::Regular(g_id),
+                region_args: Vec::new(),
+                type_args: vec![type_arg],
+                args: Vec::new(),
+                dest: Place::new(VarId::ZERO),
+                sig: None,
+            })
+        };
+        let f = mk_fun(
+            0,
+            "f",
+            mk_generic_sig(0, Vec::new(), u32_ty.clone()),
+            call(u32_ty.clone()),
+        );
+        let g = mk_fun(
+            1,
+            "g",
+            mk_generic_sig(1, Vec::new(), u32_ty),
+            call(boxed(Ty::TypeVar(TypeVarId::ZERO))),
+        );
+        let funs = FunDeclId::Vector::from(vec![f, g]);
+
+        // We stop once the arguments are too deeply nested, instead of
+        // generating infinitely many instances
+        let type_defs = TypeDecls::new();
+        let mut mono = Monomorphizer::new(&type_defs, &funs);
+        let instances = mono.run(&[FunDeclId::ZERO], &GlobalDeclId::Vector::new());
+        assert!(mono.num_errors == 1);
+        // `f`, then `g` instantiated with `u32`, `Box<u32>`, ..., up to the
+        // instance whose arguments are too deeply nested
+        assert!(instances.funs.len() == MAX_ARGS_DEPTH + 2);
+        let last = instances.funs.iter().last().unwrap();
+        assert!(last.body.is_none());
+        assert!(instances.funs.iter().filter(|d| d.body.is_none()).count() == 1);
+    }
+}
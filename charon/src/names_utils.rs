@@ -9,7 +9,7 @@ use rustc_hir::def_id::DefId;
 use rustc_hir::definitions::DefPathData;
 use rustc_hir::{Item, ItemKind};
 use rustc_middle::ty::TyCtxt;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashSet;
 
 impl PathElem {
@@ -85,17 +85,49 @@ impl Name {
         self.prefix_is_same(&[krate, module])
     }
 
-    /// Similar to [Name::is_in_module]
+    /// Similar to [Name::is_in_module]. The modules are given by their paths
+    /// relative to the crate root: `module` or `module::submodule`.
     pub fn is_in_modules(&self, krate: &String, modules: &HashSet<String>) -> bool {
-        if self.len() >= 2 {
-            match (&self.name[0], &self.name[1]) {
-                (PathElem::Ident(s0), PathElem::Ident(s1)) => s0 == krate && modules.contains(s1),
-                _ => false,
-            }
+        modules.iter().any(|module| {
+            let mut path: Vec<&str> = vec![krate];
+            path.extend(module.split("::"));
+            self.prefix_is_same(&path)
+        })
+    }
+
+    /// Return `true` if the name (printed as `krate::module::f`) matches the
+    /// glob pattern. In the pattern, `*` matches any sequence of characters
+    /// (including `::`) and `?` matches exactly one character.
+    pub fn matches_glob(&self, pattern: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let name: Vec<char> = self.to_string().chars().collect();
+        glob_match(&pattern, &name)
+    }
+}
+
+/// Match a string against a glob pattern (see [Name::matches_glob]).
+fn glob_match(pattern: &[char], s: &[char]) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // The position of the last `*` we saw in the pattern, and the position in
+    // the string from which it matches: if we fail to match the rest of the
+    // pattern, we backtrack by making this `*` consume one more character.
+    let mut star: Option<(usize, usize)> = None;
+    while i < s.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == s[i]) {
+            p += 1;
+            i += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, i));
+            p += 1;
+        } else if let Some((sp, si)) = star {
+            star = Some((sp, si + 1));
+            p = sp + 1;
+            i = si + 1;
         } else {
-            false
+            return false;
         }
     }
+    pattern[p..].iter().all(|c| *c == '*')
 }
 
 impl std::fmt::Display for Name {
@@ -116,6 +148,16 @@ impl Serialize for Name {
     }
 }
 
+impl<'de> Deserialize<'de> for Name {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name: Vec<PathElem> = Deserialize::deserialize(deserializer)?;
+        Ok(Name { name })
+    }
+}
+
 /// Retrieve an item name from a `DefId`.
 pub fn item_def_id_to_name(tcx: TyCtxt, def_id: DefId) -> ItemName {
     trace!("{:?}", def_id);
@@ -228,6 +270,15 @@ pub fn item_def_id_to_name(tcx: TyCtxt, def_id: DefId) -> ItemName {
                       format!("PathElem")}
                 }));
             }
+            DefPathData::ClosureExpr => {
+                // The closures are anonymous: we name them after the function
+                // which defines them, and use the disambiguator to distinguish
+                // the closures defined in the same function
+                name.push(PathElem::Disambiguator(Disambiguator::Id::new(
+                    data.disambiguator as usize,
+                )));
+                name.push(PathElem::Ident("closure".to_string()));
+            }
             DefPathData::ImplTrait => {
                 // TODO: this should work the same as for `Impl`
                 unimplemented!();
@@ -287,7 +338,7 @@ pub fn global_def_id_to_name(tcx: TyCtxt, def_id: DefId) -> GlobalName {
     item_def_id_to_name(tcx, def_id)
 }
 
-pub fn trait_def_id_to_name(tcx: TyCtxt, def_id: DefId) -> FunName {
+pub fn trait_def_id_to_name(tcx: TyCtxt, def_id: DefId) -> TraitName {
     item_def_id_to_name(tcx, def_id)
 }
 
@@ -305,7 +356,6 @@ pub fn hir_item_to_name(tcx: TyCtxt, item: &Item) -> Option<HirItemName> {
     // satisfying below
     match &item.kind {
         ItemKind::OpaqueTy(_) => unimplemented!(),
-        ItemKind::Union(_, _) => unimplemented!(),
         ItemKind::ExternCrate(_) => {
             // We ignore this -
             // TODO: investigate when extern crates appear, and why
@@ -318,8 +368,10 @@ pub fn hir_item_to_name(tcx: TyCtxt, item: &Item) -> Option<HirItemName> {
         }
         ItemKind::Enum(_, _)
         | ItemKind::Struct(_, _)
+        | ItemKind::Union(_, _)
         | ItemKind::Fn(_, _, _)
         | ItemKind::Impl(_)
+        | ItemKind::Trait(_, _, _, _, _)
         | ItemKind::Mod(_)
         | ItemKind::Const(_, _)
         | ItemKind::Static(_, _, _)
@@ -329,3 +381,34 @@ pub fn hir_item_to_name(tcx: TyCtxt, item: &Item) -> Option<HirItemName> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::names_utils::*;
+    use std::iter::FromIterator;
+
+    #[test]
+    fn test_matches_glob() {
+        let name = |s: &str| Name::from(s.split("::").map(String::from).collect());
+        let pattern = "mycrate::parser::*";
+        assert!(name("mycrate::parser::parse").matches_glob(pattern));
+        assert!(name("mycrate::parser::lexer::next").matches_glob(pattern));
+        assert!(!name("mycrate::parser").matches_glob(pattern));
+        assert!(!name("mycrate::printer::print").matches_glob(pattern));
+        assert!(name("mycrate::a::test_f").matches_glob("*::test_*"));
+        assert!(name("mycrate::f1").matches_glob("mycrate::f?"));
+        assert!(!name("mycrate::f12").matches_glob("mycrate::f?"));
+    }
+
+    #[test]
+    fn test_is_in_modules() {
+        let name = |s: &str| Name::from(s.split("::").map(String::from).collect());
+        let krate = "mycrate".to_string();
+        let modules = HashSet::from_iter(vec!["a".to_string(), "b::c".to_string()]);
+        assert!(name("mycrate::a::f").is_in_modules(&krate, &modules));
+        assert!(name("mycrate::a").is_in_modules(&krate, &modules));
+        assert!(name("mycrate::b::c::d::f").is_in_modules(&krate, &modules));
+        assert!(!name("mycrate::b::f").is_in_modules(&krate, &modules));
+        assert!(!name("othercrate::a::f").is_in_modules(&krate, &modules));
+    }
+}
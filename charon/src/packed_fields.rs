@@ -0,0 +1,160 @@
+//! Detect the references to the fields of `#[repr(packed)]` structures.
+//!
+//! The fields of a packed structure may not be properly aligned: creating a
+//! reference to such a field is undefined behavior if the alignment required
+//! by the type of the field is greater than the packing alignment. Rustc
+//! rejects such borrows in safe code, but they can still appear in unsafe code
+//! (or through macros), and the backends should not assume that references
+//! are always aligned. We thus reject them.
+
+use crate::errors::TranslationError;
+use crate::expressions::*;
+use crate::gast_utils::par_try_iter_bodies;
+use crate::llbc_ast::{visit_statements, ExprBody, FunDecls, GlobalDecls, RawStatement};
+use crate::meta::Meta;
+use crate::passes::PassResult;
+use crate::types::*;
+
+/// Return true if the field of the ADT may be under-aligned, i.e., if the ADT
+/// is packed and the field requires a bigger alignment than the packing one
+/// (or if we can't compute this alignment). The alignments were computed
+/// during the translation (see [TypeDecl::under_aligned_fields]).
+fn is_under_aligned_field(type_defs: &TypeDecls, id: TypeDeclId::Id, fid: FieldId::Id) -> bool {
+    match type_defs.get_type_def(id) {
+        Some(def) => def.under_aligned_fields.contains(&fid),
+        None => false,
+    }
+}
+
+/// Return true if the place may be under-aligned, because it is a (transitive)
+/// field of a packed structure.
+pub fn is_under_aligned_place(type_defs: &TypeDecls, p: &Place) -> bool {
+    // Only the projections after the last dereference matter: a dereference
+    // leads to a new allocation, which is properly aligned
+    let mut under_aligned = false;
+    for pe in p.projection.iter() {
+        match pe {
+            ProjectionElem::Deref
+            | ProjectionElem::DerefBox
+            | ProjectionElem::DerefRawPtr
+            | ProjectionElem::DerefPtrUnique
+            | ProjectionElem::DerefPtrNonNull
+            | ProjectionElem::DerefRc => under_aligned = false,
+            ProjectionElem::Field(FieldProjKind::Adt(id, None) | FieldProjKind::Union(id), fid) => {
+                under_aligned = under_aligned || is_under_aligned_field(type_defs, *id, *fid)
+            }
+            ProjectionElem::Field(_, _)
+            | ProjectionElem::Index(_)
+            | ProjectionElem::ConstantIndex { .. } => (),
+        }
+    }
+    under_aligned
+}
+
+/// Return the locations of the borrows of under-aligned places in a body.
+pub fn check_refs_to_packed_fields(type_defs: &TypeDecls, body: &ExprBody) -> Vec<Meta> {
+    let mut refs = Vec::new();
+    visit_statements(
+        &mut |st| {
+            if let RawStatement::Assign(_, Rvalue::Ref(p, _)) = &st.content {
+                if is_under_aligned_place(type_defs, p) {
+                    refs.push(st.meta);
+                }
+            }
+        },
+        &body.body,
+    );
+    refs
+}
+
+/// Reject the borrows of under-aligned fields, in all the bodies: creating
+/// such a reference is undefined behavior.
+pub fn check(type_defs: &TypeDecls, funs: &mut FunDecls, globals: &mut GlobalDecls) -> PassResult {
+    par_try_iter_bodies(funs, globals, |_, b| {
+        match check_refs_to_packed_fields(type_defs, b).first() {
+            Some(meta) => Err(TranslationError::new(
+                *meta,
+                "reference to a field of a packed structure, which may be under-aligned",
+            )),
+            None => Ok(()),
+        }
+    })
+    .into_result()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llbc_ast::{new_sequence, Statement};
+    use crate::meta::AttrInfo;
+    use crate::packed_fields::*;
+    use crate::regions_hierarchy::RegionGroups;
+    use crate::values::VarId;
+
+    fn mk_packed_struct(field_ty: RTy, under_aligned: bool) -> TypeDecl {
+        let field = Field {
+            meta: Meta::dummy(),
+            name: Some("x".to_string()),
+            ty: field_ty,
+        };
+        TypeDecl {
+            def_id: TypeDeclId::ZERO,
+            meta: Meta::dummy(),
+            name: TypeName::from(vec!["Packed".to_string()]),
+            region_params: RegionVarId::Vector::new(),
+            type_params: TypeVarId::Vector::new(),
+            const_generic_params: ConstGenericVarId::Vector::new(),
+            trait_clauses: Vec::new(),
+            regions_hierarchy: RegionGroups::new(),
+            kind: TypeDeclKind::Struct(FieldId::Vector::from(vec![field])),
+            repr_packed: Some(1),
+            under_aligned_fields: if under_aligned {
+                vec![FieldId::ZERO]
+            } else {
+                Vec::new()
+            },
+            recursive_fields: Vec::new(),
+            attr_info: AttrInfo::default(),
+        }
+    }
+
+    #[test]
+    fn test_ref_to_packed_field() {
+        let mut type_defs = TypeDecls::new();
+        type_defs
+            .types
+            .push_back(mk_packed_struct(Ty::Integer(IntegerTy::U32), true));
+
+        // ```
+        // r := &s.x; // `s: Packed`
+        // return
+        // ```
+        let s = VarId::Id::new(1);
+        let r = VarId::Id::new(2);
+        let mut field = Place::new(s);
+        field.projection.push_back(ProjectionElem::Field(
+            FieldProjKind::Adt(TypeDeclId::ZERO, None),
+            FieldId::ZERO,
+        ));
+        let body = ExprBody {
+            meta: Meta::dummy(),
+            arg_count: 0,
+            return_local: VarId::ZERO,
+            locals: VarId::Vector::new(),
+            body: new_sequence(
+                Statement::new(
+                    Meta::dummy(),
+                    RawStatement::Assign(Place::new(r), Rvalue::Ref(field, BorrowKind::Shared)),
+                ),
+                Statement::new(Meta::dummy(), RawStatement::Return),
+            ),
+        };
+        assert!(check_refs_to_packed_fields(&type_defs, &body).len() == 1);
+
+        // A `u8` is never under-aligned
+        type_defs.types = TypeDeclId::Vector::new();
+        type_defs
+            .types
+            .push_back(mk_packed_struct(Ty::Integer(IntegerTy::U8), false));
+        assert!(check_refs_to_packed_fields(&type_defs, &body).is_empty());
+    }
+}
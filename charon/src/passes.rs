@@ -0,0 +1,474 @@
+//! The pipeline of the micro-passes we apply on the LLBC definitions, once
+//! the control-flow has been reconstructed.
+//!
+//! The built-in micro-passes are registered by the driver (see
+//! [crate::driver::translate]). Users can insert their own transformations
+//! in the pipeline, without forking Charon: they need to implement the [Pass]
+//! trait, and to give the pass (together with its position relative to the
+//! built-in passes) to the driver through [crate::driver::CharonCallbacks].
+//! The passes can use the utilities of [crate::llbc_ast_utils] (for instance
+//! [crate::llbc_ast_utils::transform_statements]) to explore the bodies.
+//!
+//! A pass which fails to transform some bodies reports the errors with the
+//! identifiers of the declarations (see [crate::errors]), rather than
+//! panicking.
+//!
+//! With `--check`, we type-check the bodies after every pass (see
+//! [crate::check]): the pass which introduced an ill-typed statement fails
+//! on the offending declarations.
+#![allow(dead_code)]
+
+use crate::check;
+use crate::diagnostics::{Code, Diagnostic, Diagnostics, Level, Location};
+use crate::errors::TranslationError;
+pub use crate::errors::{DeclErrors, PassResult};
+use crate::llbc_ast::{CtxNames, FunDecls, GlobalDecls};
+use crate::meta::{FileId, FileName};
+use crate::types::TypeDecls;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+/// The context given to the passes
+pub struct PassCtx<'a> {
+    /// Used for pretty-printing purposes
+    pub fmt_ctx: &'a CtxNames<'a>,
+    pub type_defs: &'a TypeDecls,
+    /// Used to locate the errors of the passes
+    pub id_to_file: &'a HashMap<FileId::Id, FileName>,
+    pub diagnostics: &'a Diagnostics,
+}
+
+/// A transformation of the LLBC function and global definitions.
+///
+/// Note that this trait is object-safe: the pipeline stores the passes as
+/// `Box<dyn Pass>`.
+pub trait Pass {
+    /// The name of the pass, which we use to refer to it when inserting
+    /// other passes in the pipeline. The names must be unique.
+    fn name(&self) -> &str;
+
+    /// The disabled passes stay in the pipeline, so that the other passes
+    /// can still be positioned relatively to them, but we don't apply them.
+    fn is_enabled(&self) -> bool {
+        true
+    }
+
+    fn transform(
+        &self,
+        ctx: &PassCtx<'_>,
+        funs: &mut FunDecls,
+        globals: &mut GlobalDecls,
+    ) -> PassResult;
+}
+
+/// We need this to insert in the pipeline the passes we only borrow (for
+/// instance, the user passes which are owned by the driver callbacks).
+impl<P: Pass + ?Sized> Pass for &P {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn is_enabled(&self) -> bool {
+        (**self).is_enabled()
+    }
+
+    fn transform(
+        &self,
+        ctx: &PassCtx<'_>,
+        funs: &mut FunDecls,
+        globals: &mut GlobalDecls,
+    ) -> PassResult {
+        (**self).transform(ctx, funs, globals)
+    }
+}
+
+/// The result of a pass defined by a closure: this allows the passes which
+/// can't fail to return `()`.
+pub trait IntoPassResult {
+    fn into_pass_result(self) -> PassResult;
+}
+
+impl IntoPassResult for () {
+    fn into_pass_result(self) -> PassResult {
+        Ok(())
+    }
+}
+
+impl IntoPassResult for PassResult {
+    fn into_pass_result(self) -> PassResult {
+        self
+    }
+}
+
+/// A pass defined by a closure. We use it for the built-in passes.
+pub struct FnPass<F, R> {
+    name: &'static str,
+    enabled: bool,
+    transform: F,
+    result: PhantomData<fn() -> R>,
+}
+
+impl<F: Fn(&PassCtx<'_>, &mut FunDecls, &mut GlobalDecls) -> R, R: IntoPassResult> FnPass<F, R> {
+    pub fn new(name: &'static str, transform: F) -> Self {
+        FnPass {
+            name,
+            enabled: true,
+            transform,
+            result: PhantomData,
+        }
+    }
+}
+
+impl<F, R> Pass for FnPass<F, R>
+where
+    F: Fn(&PassCtx<'_>, &mut FunDecls, &mut GlobalDecls) -> R,
+    R: IntoPassResult,
+{
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn transform(
+        &self,
+        ctx: &PassCtx<'_>,
+        funs: &mut FunDecls,
+        globals: &mut GlobalDecls,
+    ) -> PassResult {
+        (self.transform)(ctx, funs, globals).into_pass_result()
+    }
+}
+
+/// Where to insert a pass in the pipeline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PassPosition {
+    /// Just before the pass with the given name
+    Before(String),
+    /// Just after the pass with the given name
+    After(String),
+    /// At the end of the pipeline
+    Last,
+}
+
+/// An error while registering a pass
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PassError {
+    /// The position refers to a pass which is not in the pipeline
+    UnknownPass(String),
+    /// There is already a pass with the same name in the pipeline
+    DuplicateName(String),
+}
+
+impl std::fmt::Display for PassError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            PassError::UnknownPass(name) => write!(f, "there is no pass named `{name}`"),
+            PassError::DuplicateName(name) => {
+                write!(f, "there is already a pass named `{name}`")
+            }
+        }
+    }
+}
+
+/// An ordered list of passes
+pub struct PassPipeline<'a> {
+    passes: Vec<Box<dyn Pass + 'a>>,
+    /// Type-check the bodies after every pass
+    check: bool,
+}
+
+impl<'a> PassPipeline<'a> {
+    pub fn new() -> Self {
+        PassPipeline {
+            passes: Vec::new(),
+            check: false,
+        }
+    }
+
+    /// Type-check the bodies after every pass (see [crate::check])
+    pub fn check_after_each_pass(&mut self, check: bool) {
+        self.check = check;
+    }
+
+    fn position_of(&self, name: &str) -> Option<usize> {
+        self.passes.iter().position(|pass| pass.name() == name)
+    }
+
+    /// Add a pass at the end of the pipeline
+    pub fn register(&mut self, pass: Box<dyn Pass + 'a>) -> Result<(), PassError> {
+        self.register_at(&PassPosition::Last, pass)
+    }
+
+    /// Add a pass defined by a closure at the end of the pipeline. We use this
+    /// for the built-in passes, whose names are unique.
+    pub fn register_fn<F, R>(&mut self, name: &'static str, transform: F)
+    where
+        F: Fn(&PassCtx<'_>, &mut FunDecls, &mut GlobalDecls) -> R + 'a,
+        R: IntoPassResult + 'a,
+    {
+        self.register_fn_if(name, true, transform)
+    }
+
+    /// Same as [PassPipeline::register_fn], for a pass which the user can
+    /// disable (or enable) with an option
+    pub fn register_fn_if<F, R>(&mut self, name: &'static str, enabled: bool, transform: F)
+    where
+        F: Fn(&PassCtx<'_>, &mut FunDecls, &mut GlobalDecls) -> R + 'a,
+        R: IntoPassResult + 'a,
+    {
+        let pass = FnPass {
+            name,
+            enabled,
+            transform,
+            result: PhantomData,
+        };
+        self.register(Box::new(pass)).unwrap()
+    }
+
+    /// Insert a pass in the pipeline, relatively to the passes already
+    /// registered
+    pub fn register_at(
+        &mut self,
+        position: &PassPosition,
+        pass: Box<dyn Pass + 'a>,
+    ) -> Result<(), PassError> {
+        if self.position_of(pass.name()).is_some() {
+            return Err(PassError::DuplicateName(pass.name().to_string()));
+        }
+        let index = match position {
+            PassPosition::Before(name) => self.position_of(name),
+            PassPosition::After(name) => self.position_of(name).map(|i| i + 1),
+            PassPosition::Last => Some(self.passes.len()),
+        };
+        match (index, position) {
+            (Some(index), _) => {
+                self.passes.insert(index, pass);
+                Ok(())
+            }
+            (None, PassPosition::Before(name) | PassPosition::After(name)) => {
+                Err(PassError::UnknownPass(name.clone()))
+            }
+            (None, PassPosition::Last) => unreachable!(),
+        }
+    }
+
+    /// The names of the passes, in the order in which we apply them
+    pub fn names(&self) -> Vec<&str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// The names of the passes we actually apply (see [Pass::is_enabled])
+    pub fn enabled_names(&self) -> Vec<&str> {
+        self.passes
+            .iter()
+            .filter(|pass| pass.is_enabled())
+            .map(|pass| pass.name())
+            .collect()
+    }
+
+    /// Apply the passes, in order.
+    ///
+    /// If a pass fails to transform some declarations (or, when checking the
+    /// bodies, generates ill-typed code), we report the errors.
+    /// If `errors_as_warnings` is true, we then remove the bodies of those
+    /// declarations (the following passes ignore them, and we export them as
+    /// opaque declarations) and continue. Otherwise, we stop.
+    ///
+    /// Return the number of declarations whose bodies we removed.
+    pub fn run(
+        &self,
+        ctx: &PassCtx<'_>,
+        funs: &mut FunDecls,
+        globals: &mut GlobalDecls,
+        errors_as_warnings: bool,
+    ) -> Result<usize, ()> {
+        let mut num_failed = 0;
+        for pass in self.passes.iter().filter(|pass| pass.is_enabled()) {
+            let res = match pass.transform(ctx, funs, globals) {
+                Err(errors) => Err((Code::UnexpectedShape, "failed", errors)),
+                Ok(()) if self.check => check::check_decls(ctx.type_defs, funs, globals)
+                    .into_result()
+                    .map_err(|errors| (Code::IllFormedLlbc, "generated ill-typed code", errors)),
+                Ok(()) => Ok(()),
+            };
+            if let Err((code, what, errors)) = res {
+                let report = |name: String, err: &TranslationError| {
+                    trace!("The pass {} {what} on {name}, {err}", pass.name());
+                    let (level, message) = if errors_as_warnings {
+                        let msg = format!("{} (we ignore the body)", err.msg);
+                        (Level::Warning, msg)
+                    } else {
+                        (Level::Error, err.msg.clone())
+                    };
+                    ctx.diagnostics.report(Diagnostic {
+                        code,
+                        level,
+                        message: format!("the pass `{}` {what}: {message}", pass.name()),
+                        decl: Some(name),
+                        location: Location::from_meta(ctx.id_to_file, &err.meta),
+                    });
+                };
+                for (id, err) in &errors.funs {
+                    let decl = funs.get_mut(*id).unwrap();
+                    report(decl.name.to_string(), err);
+                    decl.body = None;
+                }
+                for (id, err) in &errors.globals {
+                    let decl = globals.get_mut(*id).unwrap();
+                    report(decl.name.to_string(), err);
+                    decl.body = None;
+                }
+                if !errors_as_warnings {
+                    return Err(());
+                }
+                num_failed += errors.funs.len() + errors.globals.len();
+            }
+            for def in funs.iter() {
+                trace!(
+                    "# After the pass {}:\n{}\n",
+                    pass.name(),
+                    def.fmt_with_decls(ctx.type_defs, funs, globals)
+                );
+            }
+        }
+        Ok(num_failed)
+    }
+}
+
+impl<'a> Default for PassPipeline<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli_options::DiagnosticsFormat;
+    use crate::llbc_ast::{FunDeclId, GlobalDeclId};
+    use crate::passes::*;
+    use std::cell::RefCell;
+
+    /// A user pass which doesn't modify the definitions, but logs its name
+    struct NoOpPass<'a> {
+        log: &'a RefCell<Vec<String>>,
+    }
+
+    impl<'a> Pass for NoOpPass<'a> {
+        fn name(&self) -> &str {
+            "no_op"
+        }
+
+        fn transform(&self, _: &PassCtx<'_>, _: &mut FunDecls, _: &mut GlobalDecls) -> PassResult {
+            self.log.borrow_mut().push(self.name().to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_user_pass() {
+        let log = &RefCell::new(Vec::new());
+        let mk_builtin = |name: &'static str| {
+            Box::new(FnPass::new(
+                name,
+                move |_: &PassCtx<'_>, _: &mut FunDecls, _: &mut GlobalDecls| {
+                    log.borrow_mut().push(name.to_string())
+                },
+            ))
+        };
+        let mut pipeline = PassPipeline::new();
+        pipeline
+            .register(mk_builtin("reconstruct_asserts"))
+            .unwrap();
+        pipeline.register(mk_builtin("index_accesses")).unwrap();
+
+        // The disabled passes are kept in the pipeline, but not applied
+        pipeline.register_fn_if("single_exit", false, |_, _, _| unreachable!());
+
+        let user_pass = Box::new(NoOpPass { log });
+        let position = PassPosition::After("reconstruct_asserts".to_string());
+        pipeline.register_at(&position, user_pass).unwrap();
+        assert!(
+            pipeline.names()
+                == vec![
+                    "reconstruct_asserts",
+                    "no_op",
+                    "index_accesses",
+                    "single_exit"
+                ]
+        );
+        assert!(pipeline.enabled_names() == vec!["reconstruct_asserts", "no_op", "index_accesses"]);
+
+        // We can't refer to a pass which doesn't exist, nor register a pass twice
+        let position = PassPosition::Before("unknown".to_string());
+        let res = pipeline.register_at(&position, Box::new(NoOpPass { log }));
+        assert!(res == Err(PassError::UnknownPass("unknown".to_string())));
+        let res = pipeline.register(Box::new(NoOpPass { log }));
+        assert!(res == Err(PassError::DuplicateName("no_op".to_string())));
+
+        // Run the pipeline
+        let type_defs = TypeDecls::new();
+        let (fun_names, global_names) = (FunDeclId::Vector::new(), GlobalDeclId::Vector::new());
+        let fmt_ctx = CtxNames::new(&type_defs, &fun_names, &global_names);
+        let (id_to_file, diagnostics) = (HashMap::new(), Diagnostics::new(DiagnosticsFormat::Json));
+        let ctx = PassCtx {
+            fmt_ctx: &fmt_ctx,
+            type_defs: &type_defs,
+            id_to_file: &id_to_file,
+            diagnostics: &diagnostics,
+        };
+        let res = pipeline.run(&ctx, &mut FunDecls::new(), &mut GlobalDecls::new(), false);
+        assert!(res.is_ok());
+        assert!(*log.borrow() == vec!["reconstruct_asserts", "no_op", "index_accesses"]);
+    }
+
+    #[test]
+    fn test_failing_pass() {
+        use crate::errors::TranslationError;
+        use crate::llbc_ast::{FunDecl, RawStatement};
+        use crate::meta::Meta;
+        use crate::test_utils::*;
+        use crate::types::*;
+
+        let mk_fun = |id: usize, name: &str| -> FunDecl {
+            let body = mk_body(0, Vec::new(), vec![RawStatement::Return]);
+            mk_fun_decl(id, &[name], mk_sig(Vec::new(), Ty::Bool), Some(body))
+        };
+        // A pass which fails on the function `g`
+        let mut pipeline = PassPipeline::new();
+        pipeline.register_fn("fail_on_g", |_, funs, _| {
+            let mut errors = DeclErrors::default();
+            for f in funs.iter().filter(|f| f.name.to_string() == "g") {
+                let err = TranslationError::new(Meta::dummy(), "unexpected statement");
+                errors.funs.push((f.def_id, err));
+            }
+            errors.into_result()
+        });
+
+        let type_defs = TypeDecls::new();
+        let (fun_names, global_names) = (FunDeclId::Vector::new(), GlobalDeclId::Vector::new());
+        let fmt_ctx = CtxNames::new(&type_defs, &fun_names, &global_names);
+        let (id_to_file, diagnostics) = (HashMap::new(), Diagnostics::new(DiagnosticsFormat::Json));
+        let ctx = PassCtx {
+            fmt_ctx: &fmt_ctx,
+            type_defs: &type_defs,
+            id_to_file: &id_to_file,
+            diagnostics: &diagnostics,
+        };
+        let mut funs = FunDecls::from(vec![mk_fun(0, "f"), mk_fun(1, "g")]);
+        let mut globals = GlobalDecls::new();
+        assert!(pipeline
+            .run(&ctx, &mut funs.clone(), &mut globals, false)
+            .is_err());
+        // With `--errors-as-warnings`, we only drop the body of `g`
+        assert!(pipeline.run(&ctx, &mut funs, &mut globals, true) == Ok(1));
+        // We reported the error, then the warning
+        let diags = diagnostics.to_vec();
+        assert!(diags.len() == 2 && diagnostics.num_errors() == 1);
+        assert!(diags[0].decl.as_deref() == Some("g") && diags[1].level == Level::Warning);
+        assert!(funs.get(FunDeclId::Id::new(0)).unwrap().body.is_some());
+        assert!(funs.get(FunDeclId::Id::new(1)).unwrap().body.is_none());
+    }
+}
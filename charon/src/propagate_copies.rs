@@ -0,0 +1,233 @@
+//! Propagate the copies of locals and the constants inside the blocks of
+//! statements.
+//!
+//! The MIR introduces many temporaries which only hold a copy of another
+//! local, or a constant, for the time of a single statement:
+//! ```text
+//! tmp := copy x;
+//! y := move tmp + const 1;
+//! ```
+//! We replace the reads of those temporaries with the copied operand:
+//! ```text
+//! tmp := copy x;
+//! y := copy x + const 1;
+//! ```
+//! The temporaries are then never read: [crate::remove_unused_locals] removes
+//! them.
+//!
+//! We only propagate the copies of places which we can read several times
+//! (see [crate::common_subexpressions::is_stable_place]), and only replace the
+//! operands which read a whole local. As for the common subexpressions, we
+//! are conservative: we forget the copies when we exit a block (at the
+//! switches and the loops), at the function calls, and whenever we write
+//! through a pointer.
+
+use crate::common_subexpressions::{is_stable_place, kill_statement, operand_uses_var, BlockFacts};
+use crate::expressions::*;
+use crate::llbc_ast::{
+    new_sequence, CtxNames, FunDecls, GlobalDecls, RawStatement, Statement, Switch,
+};
+use crate::llbc_visitor::AstMutVisitor;
+use crate::ullbc_ast::par_iter_bodies;
+use crate::values::*;
+use std::collections::HashMap;
+use take_mut::take;
+
+/// The locals which currently hold a copy of an operand
+type Copies = HashMap<VarId::Id, Operand>;
+
+impl BlockFacts for Copies {
+    fn kill_var(&mut self, var_id: VarId::Id) {
+        self.retain(|copy, op| *copy != var_id && !operand_uses_var(op, var_id));
+    }
+
+    fn kill_all(&mut self) {
+        self.clear()
+    }
+}
+
+/// Replace the reads of the copies with the copied operands
+struct Propagate<'a>(&'a Copies);
+
+impl AstMutVisitor for Propagate<'_> {
+    fn visit_operand_mut(&mut self, op: &mut Operand) {
+        let copied = match op {
+            Operand::Copy(p) | Operand::Move(p) if p.projection.is_empty() => self.0.get(&p.var_id),
+            _ => None,
+        };
+        if let Some(copied) = copied {
+            *op = copied.clone();
+        }
+    }
+}
+
+/// Return the operand we can propagate, if the statement is of the shape
+/// `x := copy p` or `x := const c`
+fn as_copy(st: &RawStatement) -> Option<(VarId::Id, &Operand)> {
+    match st {
+        RawStatement::Assign(dest, Rvalue::Use(op)) if dest.projection.is_empty() => {
+            let propagate = match op {
+                Operand::Copy(p) => is_stable_place(p),
+                Operand::Const(_, _) => true,
+                Operand::Move(_) => false,
+            };
+            if propagate && !operand_uses_var(op, dest.var_id) {
+                Some((dest.var_id, op))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Transform a statement which doesn't contain sub-statements
+fn transform_leaf(copies: &mut Copies, st: &mut Statement) {
+    Propagate(copies).visit_statement_mut(st);
+    kill_statement(copies, &st.content);
+    if let Some((dest, op)) = as_copy(&st.content) {
+        copies.insert(dest, op.clone());
+    }
+}
+
+fn transform_st(copies: &mut Copies, mut st: Statement) -> Statement {
+    st.content = match st.content {
+        RawStatement::Sequence(st1, st2) => {
+            let st1 = transform_st(copies, *st1);
+            let st2 = transform_st(copies, *st2);
+            new_sequence(st1, st2).content
+        }
+        RawStatement::Switch(switch) => {
+            // The discriminant is evaluated in the current block, but the
+            // branches are separate blocks
+            let mut propagate = Propagate(copies);
+            let switch = match switch {
+                Switch::If(mut op, st1, st2) => {
+                    propagate.visit_operand_mut(&mut op);
+                    Switch::If(
+                        op,
+                        Box::new(transform_st(&mut Copies::new(), *st1)),
+                        Box::new(transform_st(&mut Copies::new(), *st2)),
+                    )
+                }
+                Switch::SwitchInt(mut op, int_ty, branches, otherwise) => {
+                    propagate.visit_operand_mut(&mut op);
+                    Switch::SwitchInt(
+                        op,
+                        int_ty,
+                        branches
+                            .into_iter()
+                            .map(|(v, st)| (v, transform_st(&mut Copies::new(), st)))
+                            .collect(),
+                        Box::new(transform_st(&mut Copies::new(), *otherwise)),
+                    )
+                }
+                Switch::Match(p, branches, otherwise, provenance) => Switch::Match(
+                    p,
+                    branches
+                        .into_iter()
+                        .map(|(v, st)| (v, transform_st(&mut Copies::new(), st)))
+                        .collect(),
+                    Box::new(transform_st(&mut Copies::new(), *otherwise)),
+                    provenance,
+                ),
+            };
+            copies.clear();
+            RawStatement::Switch(switch)
+        }
+        RawStatement::Loop(st, invariants) => {
+            let st = transform_st(&mut Copies::new(), *st);
+            copies.clear();
+            RawStatement::Loop(Box::new(st), invariants)
+        }
+        RawStatement::While(cond, mut op, body, invariants) => {
+            // The condition is evaluated right after the statement computing
+            // it, in the same block
+            let mut cond_copies = Copies::new();
+            let cond = transform_st(&mut cond_copies, *cond);
+            Propagate(&cond_copies).visit_operand_mut(&mut op);
+            let body = transform_st(&mut Copies::new(), *body);
+            copies.clear();
+            RawStatement::While(Box::new(cond), op, Box::new(body), invariants)
+        }
+        content => {
+            let mut leaf = Statement::new(st.meta, content);
+            transform_leaf(copies, &mut leaf);
+            leaf.content
+        }
+    };
+    st
+}
+
+/// `fmt_ctx` is used for pretty-printing purposes.
+pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
+    par_iter_bodies(funs, globals, |name, b| {
+        trace!(
+            "# About to propagate the copies in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        take(&mut b.body, |st| transform_st(&mut Copies::new(), st));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::propagate_copies::*;
+    use crate::test_utils::*;
+    use crate::types::*;
+
+    fn copy(v: VarId::Id) -> Operand {
+        Operand::Copy(Place::new(v))
+    }
+
+    #[test]
+    fn test_propagate_copies() {
+        let x = VarId::Id::new(1);
+        let tmp1 = VarId::Id::new(2);
+        let tmp2 = VarId::Id::new(3);
+        let y = VarId::Id::new(4);
+        let one = Operand::Const(
+            Ty::Integer(IntegerTy::U32),
+            OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(ScalarValue::U32(1))),
+        );
+        // ```
+        // tmp1 := copy x;
+        // tmp2 := const 1;
+        // y := move tmp1 + move tmp2;
+        // x := copy y;
+        // y := move tmp1;
+        // return
+        // ```
+        // The last read of `tmp1` comes after `x` is updated: we can't
+        // replace it.
+        let st = mk_statements(vec![
+            RawStatement::Assign(Place::new(tmp1), Rvalue::Use(copy(x))),
+            RawStatement::Assign(Place::new(tmp2), Rvalue::Use(one.clone())),
+            RawStatement::Assign(
+                Place::new(y),
+                Rvalue::BinaryOp(
+                    BinOp::Add,
+                    Operand::Move(Place::new(tmp1)),
+                    Operand::Move(Place::new(tmp2)),
+                ),
+            ),
+            RawStatement::Assign(Place::new(x), Rvalue::Use(copy(y))),
+            RawStatement::Assign(Place::new(y), Rvalue::Use(Operand::Move(Place::new(tmp1)))),
+            RawStatement::Return,
+        ]);
+        let st = transform_st(&mut Copies::new(), st);
+        let sts = flatten(&st);
+        match sts[2] {
+            RawStatement::Assign(_, Rvalue::BinaryOp(BinOp::Add, op1, op2)) => {
+                assert!(*op1 == copy(x) && *op2 == one)
+            }
+            _ => panic!(),
+        }
+        match sts[4] {
+            RawStatement::Assign(_, Rvalue::Use(op)) => {
+                assert!(*op == Operand::Move(Place::new(tmp1)))
+            }
+            _ => panic!(),
+        }
+    }
+}
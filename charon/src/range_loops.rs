@@ -0,0 +1,511 @@
+//! Reconstruct the `for` loops over ranges. rustc desugars
+//! `for i in a..b { ... }` to calls to the methods of the iterator traits.
+//! Once the control-flow is reconstructed, we get:
+//! ```text
+//! r := core::ops::range::Range { start: move a, end: move b };
+//! it := core::iter::traits::collect::IntoIterator<Range<u32>>::into_iter(move r);
+//! iter := move it;
+//! loop {
+//!   tmp := &mut iter;
+//!   o := core::iter::traits::iterator::Iterator<Range<u32>>::next(move tmp);
+//!   match o {
+//!     0 => {
+//!       break 0
+//!     }
+//!     1 => {
+//!       i := copy (o as variant @1).0;
+//!       ...
+//!       continue 0
+//!     }
+//!     _ => {
+//!       panic
+//!     }
+//!   }
+//! }
+//! ```
+//! We rewrite this to a counter loop with an integer bound:
+//! ```text
+//! ctr := move a;
+//! end := move b;
+//! loop {
+//!   c := copy ctr < copy end;
+//!   if move c {
+//!     v := copy ctr;
+//!     ctr := copy ctr + const 1;
+//!     i := copy v;
+//!     ...
+//!     continue 0
+//!   }
+//!   else {
+//!     break 0
+//!   }
+//! }
+//! ```
+//! The loops over the other iterators are left unchanged: the calls to
+//! `next` are calls to an assumed function (see
+//! [crate::ullbc_ast::AssumedFunId::IteratorNext]). The locals we don't use
+//! anymore (the range, the iterator, etc.) are removed by
+//! [crate::remove_unused_locals].
+
+use crate::assumed;
+use crate::contracts::Spec;
+use crate::expressions::*;
+use crate::llbc_ast::{
+    chain_statements, new_sequence, transform_statements, Call, CtxNames, FunDecls, GlobalDecls,
+    RawStatement, Statement, Switch,
+};
+use crate::meta::Meta;
+use crate::types::*;
+use crate::ullbc_ast::{
+    iter_function_bodies, iter_global_bodies, make_locals_generator, AssumedFunId, FunId,
+};
+use crate::values::*;
+use take_mut::take;
+
+/// A `for` loop over a range (see the module documentation)
+struct RangeLoop<'a> {
+    int_ty: IntegerTy,
+    start: &'a Operand,
+    end: &'a Operand,
+    range_meta: Meta,
+    loop_meta: Meta,
+    next_meta: Meta,
+    /// The invariants of the loop
+    invariants: &'a Vec<Spec>,
+    /// The variable storing the result of `next`
+    o: VarId::Id,
+    none_branch: &'a Statement,
+    some_branch: &'a Statement,
+    /// The statements following the loop
+    rest: Option<&'a Statement>,
+}
+
+/// Split a statement into its first statement and the statements which
+/// follow (if there are).
+fn split_sequence(st: &Statement) -> (&Statement, Option<&Statement>) {
+    match &st.content {
+        RawStatement::Sequence(st1, st2) => (st1, Some(st2)),
+        _ => (st, None),
+    }
+}
+
+/// If the statement is of the shape `dest := f(move src)`, where `f` is an
+/// assumed function which satisfies `is_fid`, return `dest`.
+fn get_assumed_call(
+    st: &Statement,
+    is_fid: fn(&AssumedFunId) -> bool,
+    src: VarId::Id,
+) -> Option<VarId::Id> {
+    match &st.content {
+        RawStatement::Call(Call {
+            func: FunId::Assumed(fid),
+            args,
+            dest,
+            ..
+        }) if is_fid(fid) && dest.projection.is_empty() => match args.as_slice() {
+            [Operand::Move(p) | Operand::Copy(p)] if *p == Place::new(src) => Some(dest.var_id),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// If the statement is of the shape `dest := rv`, return `dest`
+fn get_assign_to_var(st: &Statement, rv: &Rvalue) -> Option<VarId::Id> {
+    match &st.content {
+        RawStatement::Assign(dest, rv1) if dest.projection.is_empty() && rv1 == rv => {
+            Some(dest.var_id)
+        }
+        _ => None,
+    }
+}
+
+/// Check if the statement is a `for` loop over a range of integers
+fn get_range_loop<'a>(type_defs: &TypeDecls, st: &'a Statement) -> Option<RangeLoop<'a>> {
+    // `r := Range { start, end }`
+    let (range_st, next) = split_sequence(st);
+    let (p, tys, ops) = match &range_st.content {
+        RawStatement::Assign(p, Rvalue::Aggregate(AggregateKind::Adt(id, None, _, tys), ops))
+            if p.projection.is_empty()
+                && type_defs
+                    .get_type_def(*id)
+                    .unwrap()
+                    .name
+                    .equals_ref_name(&assumed::RANGE_NAME) =>
+        {
+            (p, tys, ops)
+        }
+        _ => return None,
+    };
+    let int_ty = match tys.as_slice() {
+        [Ty::Integer(int_ty)] => *int_ty,
+        _ => return None,
+    };
+
+    // `it := into_iter(move r)`, possibly followed by `iter := move it`
+    let (st, next) = split_sequence(next?);
+    let it = get_assumed_call(st, AssumedFunId::is_into_iter, p.var_id)?;
+    let (st, next) = split_sequence(next?);
+    let (iter, loop_st, rest) =
+        match get_assign_to_var(st, &Rvalue::Use(Operand::Move(Place::new(it)))) {
+            Some(iter) => {
+                let (loop_st, rest) = split_sequence(next?);
+                (iter, loop_st, rest)
+            }
+            None => (it, st, next),
+        };
+    let (body, invariants) = match &loop_st.content {
+        RawStatement::Loop(body, invariants) => (body, invariants),
+        _ => return None,
+    };
+
+    // The loop body: `tmp := &mut iter; o := next(move tmp); match o { ... }`
+    let (st, next) = split_sequence(body);
+    let tmp = get_assign_to_var(st, &Rvalue::Ref(Place::new(iter), BorrowKind::Mut))?;
+    let (next_st, next) = split_sequence(next?);
+    let o = get_assumed_call(next_st, AssumedFunId::is_iterator_next, tmp)?;
+    let (targets, otherwise) = match &next?.content {
+        RawStatement::Switch(Switch::Match(p, targets, otherwise, _)) if *p == Place::new(o) => {
+            (targets, otherwise)
+        }
+        _ => return None,
+    };
+
+    // Retrieve the branches (one of them may be in the `otherwise` position)
+    let mut none_branch = None;
+    let mut some_branch = None;
+    for (variants, branch) in targets {
+        match variants.as_slice() {
+            [vid] if *vid == assumed::OPTION_SOME_VARIANT_ID => some_branch = Some(branch),
+            [vid] if *vid == assumed::OPTION_NONE_VARIANT_ID => none_branch = Some(branch),
+            _ => return None,
+        }
+    }
+    let (none_branch, some_branch) = match (none_branch, some_branch) {
+        (Some(n), Some(s)) => (n, s),
+        (Some(n), None) => (n, otherwise.as_ref()),
+        (None, Some(s)) => (otherwise.as_ref(), s),
+        (None, None) => return None,
+    };
+
+    Some(RangeLoop {
+        int_ty,
+        start: &ops[0],
+        end: &ops[1],
+        range_meta: range_st.meta,
+        loop_meta: loop_st.meta,
+        next_meta: next_st.meta,
+        invariants,
+        o,
+        none_branch,
+        some_branch,
+        rest,
+    })
+}
+
+/// Replace the reads of `(o as Some).0` with reads of `v`. Return `None` if
+/// `o` is used in another way.
+fn replace_some_field(o: VarId::Id, v: VarId::Id, mut st: Statement) -> Option<Statement> {
+    let mut ok = true;
+    st.visit_places_mut(&mut |p: &mut Place| {
+        if p.var_id == o {
+            match p.projection.pop_front() {
+                Some(ProjectionElem::Field(FieldProjKind::Option(vid), field_id))
+                    if vid == assumed::OPTION_SOME_VARIANT_ID && field_id == FieldId::ZERO =>
+                {
+                    p.var_id = v
+                }
+                _ => ok = false,
+            }
+        }
+    });
+    if ok {
+        Some(st)
+    } else {
+        None
+    }
+}
+
+fn transform_st<F: FnMut(ETy) -> VarId::Id>(
+    type_defs: &TypeDecls,
+    make_new_var: &mut F,
+    st: Statement,
+) -> Statement {
+    let range_loop = match get_range_loop(type_defs, &st) {
+        Some(range_loop) => range_loop,
+        None => return st,
+    };
+    let RangeLoop {
+        int_ty,
+        range_meta,
+        loop_meta,
+        next_meta,
+        o,
+        ..
+    } = range_loop;
+
+    // Substitute the value given by the iterator. Note that if this fails,
+    // the variable we introduce for the value is simply removed later.
+    let v = make_new_var(Ty::Integer(int_ty));
+    let some_branch = replace_some_field(o, v, range_loop.some_branch.clone());
+    let none_branch = replace_some_field(o, v, range_loop.none_branch.clone());
+    let (some_branch, none_branch) = match (some_branch, none_branch) {
+        (Some(s), Some(n)) => (s, n),
+        _ => return st,
+    };
+
+    let ctr = Place::new(make_new_var(Ty::Integer(int_ty)));
+    let end = Place::new(make_new_var(Ty::Integer(int_ty)));
+    let cond = Place::new(make_new_var(Ty::Bool));
+    let one = if int_ty.is_signed() {
+        ScalarValue::from_unchecked_int(int_ty, 1)
+    } else {
+        ScalarValue::from_unchecked_uint(int_ty, 1)
+    };
+    let one = Operand::Const(
+        Ty::Integer(int_ty),
+        OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(one)),
+    );
+    let mk = |meta, content| Statement::new(meta, content);
+
+    // ```
+    // v := copy ctr;
+    // ctr := copy ctr + const 1;
+    // ...
+    // ```
+    let some_branch = chain_statements(
+        vec![
+            mk(
+                next_meta,
+                RawStatement::Assign(Place::new(v), Rvalue::Use(Operand::Copy(ctr.clone()))),
+            ),
+            mk(
+                next_meta,
+                RawStatement::Assign(
+                    ctr.clone(),
+                    Rvalue::BinaryOp(BinOp::Add, Operand::Copy(ctr.clone()), one),
+                ),
+            ),
+        ],
+        some_branch,
+    );
+
+    // ```
+    // loop {
+    //   c := copy ctr < copy end;
+    //   if move c { ... } else { ... }
+    // }
+    // ```
+    let test = mk(
+        next_meta,
+        RawStatement::Assign(
+            cond.clone(),
+            Rvalue::BinaryOp(
+                BinOp::Lt,
+                Operand::Copy(ctr.clone()),
+                Operand::Copy(end.clone()),
+            ),
+        ),
+    );
+    let switch = Switch::If(
+        Operand::Move(cond),
+        Box::new(some_branch),
+        Box::new(none_branch),
+    );
+    let body = new_sequence(test, mk(next_meta, RawStatement::Switch(switch)));
+    let invariants = range_loop.invariants.clone();
+    let loop_st = mk(loop_meta, RawStatement::Loop(Box::new(body), invariants));
+
+    // ```
+    // ctr := start;
+    // end := end;
+    // loop { ... }
+    // ```
+    let init = vec![
+        mk(
+            range_meta,
+            RawStatement::Assign(ctr, Rvalue::Use(range_loop.start.clone())),
+        ),
+        mk(
+            range_meta,
+            RawStatement::Assign(end, Rvalue::Use(range_loop.end.clone())),
+        ),
+    ];
+    let st = chain_statements(init, loop_st);
+    match range_loop.rest {
+        Some(rest) => new_sequence(st, rest.clone()),
+        None => st,
+    }
+}
+
+/// `fmt_ctx` is used for pretty-printing purposes.
+pub fn transform(
+    fmt_ctx: &CtxNames<'_>,
+    type_defs: &TypeDecls,
+    funs: &mut FunDecls,
+    globals: &mut GlobalDecls,
+) {
+    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+        trace!(
+            "# About to reconstruct the loops over ranges in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        let mut make_new_var = make_locals_generator(&mut b.locals);
+        take(&mut b.body, |st| {
+            transform_statements(&mut |st| transform_st(type_defs, &mut make_new_var, st), st)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::meta::AttrInfo;
+    use crate::names::Name;
+    use crate::range_loops::*;
+    use crate::regions_hierarchy::RegionGroups;
+    use crate::test_utils::mk_call;
+
+    fn mk(content: RawStatement) -> Statement {
+        Statement::new(Meta::dummy(), content)
+    }
+
+    /// `dest := fid(move src)`
+    fn call(fid: AssumedFunId, src: VarId::Id, dest: VarId::Id) -> Statement {
+        mk(RawStatement::Call(mk_call(
+            FunId::Assumed(fid),
+            vec![Operand::Move(Place::new(src))],
+            Place::new(dest),
+        )))
+    }
+
+    #[test]
+    fn test_range_loop() {
+        let mut type_defs = TypeDecls::new();
+        type_defs.types.push_back(TypeDecl {
+            def_id: TypeDeclId::ZERO,
+            meta: Meta::dummy(),
+            name: Name::from(assumed::RANGE_NAME.iter().map(|s| s.to_string()).collect()),
+            region_params: RegionVarId::Vector::new(),
+            type_params: TypeVarId::Vector::new(),
+            const_generic_params: ConstGenericVarId::Vector::new(),
+            trait_clauses: Vec::new(),
+            regions_hierarchy: RegionGroups::new(),
+            kind: TypeDeclKind::Opaque,
+            repr_packed: None,
+            under_aligned_fields: Vec::new(),
+            recursive_fields: Vec::new(),
+            attr_info: AttrInfo::default(),
+        });
+        let [n, r, it, iter, tmp, o, i] = [1, 2, 3, 4, 5, 6, 7].map(VarId::Id::new);
+        let mut num_vars = 8;
+        let mut make_new_var = |_| {
+            num_vars += 1;
+            VarId::Id::new(num_vars - 1)
+        };
+
+        // `for i in 0..n { nop }`
+        let zero = Operand::Const(
+            Ty::Integer(IntegerTy::U32),
+            OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(ScalarValue::U32(0))),
+        );
+        let range = Rvalue::Aggregate(
+            AggregateKind::Adt(
+                TypeDeclId::ZERO,
+                None,
+                Vec::new(),
+                vec![Ty::Integer(IntegerTy::U32)],
+            ),
+            vec![zero.clone(), Operand::Move(Place::new(n))],
+        );
+        let mut field = Place::new(o);
+        field.projection.push_back(ProjectionElem::Field(
+            FieldProjKind::Option(assumed::OPTION_SOME_VARIANT_ID),
+            FieldId::ZERO,
+        ));
+        let some_branch = chain_statements(
+            vec![mk(RawStatement::Assign(
+                Place::new(i),
+                Rvalue::Use(Operand::Copy(field)),
+            ))],
+            mk(RawStatement::Continue(0)),
+        );
+        let body = chain_statements(
+            vec![
+                mk(RawStatement::Assign(
+                    Place::new(tmp),
+                    Rvalue::Ref(Place::new(iter), BorrowKind::Mut),
+                )),
+                call(AssumedFunId::IteratorNext, tmp, o),
+            ],
+            mk(RawStatement::Switch(Switch::Match(
+                Place::new(o),
+                vec![
+                    (
+                        vec![assumed::OPTION_NONE_VARIANT_ID],
+                        mk(RawStatement::Break(0)),
+                    ),
+                    (vec![assumed::OPTION_SOME_VARIANT_ID], some_branch),
+                ],
+                Box::new(mk(RawStatement::Panic)),
+                None,
+            ))),
+        );
+        let st = chain_statements(
+            vec![
+                mk(RawStatement::Assign(Place::new(r), range)),
+                call(AssumedFunId::IntoIter, r, it),
+                mk(RawStatement::Assign(
+                    Place::new(iter),
+                    Rvalue::Use(Operand::Move(Place::new(it))),
+                )),
+                mk(RawStatement::Loop(Box::new(body), Vec::new())),
+            ],
+            mk(RawStatement::Return),
+        );
+        let st = transform_statements(
+            &mut |st| transform_st(&type_defs, &mut make_new_var, st),
+            st,
+        );
+
+        // ```
+        // ctr := const 0;
+        // end := move n;
+        // loop {
+        //   c := copy ctr < copy end;
+        //   if move c { v := copy ctr; ctr := copy ctr + const 1; i := copy v; continue 0 }
+        //   else { break 0 }
+        // };
+        // return
+        // ```
+        let [v, ctr, end, c] = [8, 9, 10, 11].map(|i| Place::new(VarId::Id::new(i)));
+        let (init_ctr, st) = st.content.to_sequence();
+        assert!(
+            matches!(&init_ctr.content, RawStatement::Assign(p, Rvalue::Use(op)) if *p == ctr && *op == zero)
+        );
+        let (init_end, st) = st.content.to_sequence();
+        assert!(
+            matches!(&init_end.content, RawStatement::Assign(p, Rvalue::Use(Operand::Move(op))) if *p == end && *op == Place::new(n))
+        );
+        let (loop_st, rest) = st.content.to_sequence();
+        assert!(rest.content.is_return());
+        let (test, switch) = loop_st.content.to_loop().0.content.to_sequence();
+        assert!(
+            matches!(&test.content, RawStatement::Assign(p, Rvalue::BinaryOp(BinOp::Lt, Operand::Copy(x), Operand::Copy(y))) if *p == c && *x == ctr && *y == end)
+        );
+        let (cond, st1, st2) = switch.content.to_switch().to_if();
+        assert!(cond == Operand::Move(c));
+        assert!(st2.content.is_break());
+        let (read, st1) = st1.content.to_sequence();
+        assert!(
+            matches!(&read.content, RawStatement::Assign(p, Rvalue::Use(Operand::Copy(x))) if *p == v && *x == ctr)
+        );
+        let (incr, st1) = st1.content.to_sequence();
+        assert!(
+            matches!(&incr.content, RawStatement::Assign(p, Rvalue::BinaryOp(BinOp::Add, _, _)) if *p == ctr)
+        );
+        let (bind, _) = st1.content.to_sequence();
+        assert!(
+            matches!(&bind.content, RawStatement::Assign(p, Rvalue::Use(Operand::Copy(x))) if *p == Place::new(i) && *x == v)
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! Compute the reaching definitions of a ULLBC body: for every use of a
+//! local, the set of definitions (assignments, etc.) which may have produced
+//! the value we read.
+//!
+//! This is a standard forward data-flow analysis: a definition reaches a
+//! program point if there is a path from the definition to this point along
+//! which the local is not redefined. At the blocks where several paths join,
+//! we take the union of the incoming definitions.
+//!
+//! We work at the level of the locals:
+//! - an assignment to a local (`x := ...`) kills the previous definitions
+//! - an assignment to a part of a local (`x.0 := ...`, `x[i] := ...`) is a
+//!   new definition, but doesn't kill the previous ones (the other fields
+//!   keep their values)
+//! - an assignment through a reference or a pointer (`*x := ...`) is a *use*
+//!   of `x`, not a definition: we don't track what the pointers point to.
+//!   Note that we consider the boxes as owned by the local (`*b := ...` is a
+//!   partial definition of `b`).
+#![allow(dead_code)]
+
+use crate::expressions::*;
+use crate::ullbc_ast::*;
+use crate::values::VarId;
+use std::collections::{BTreeSet, HashMap};
+
+/// A location in a body: the statement at position `index` in `block`, or
+/// the terminator of `block` if `index` is the number of statements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Location {
+    pub block: BlockId::Id,
+    pub index: usize,
+}
+
+/// The site of a definition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DefSite {
+    /// The input arguments are defined when we enter the function
+    Arg,
+    Location(Location),
+}
+
+/// The definitions which may reach a program point, for every local
+type Defs = HashMap<VarId::Id, BTreeSet<DefSite>>;
+
+pub struct ReachingDefs {
+    /// For every location, and every local used at this location, the
+    /// definitions which may reach this use.
+    uses: HashMap<Location, HashMap<VarId::Id, BTreeSet<DefSite>>>,
+}
+
+impl ReachingDefs {
+    /// Return the definitions which may reach the use of a local at a given
+    /// location, or `None` if the local is not used at this location (or if
+    /// the location is unreachable).
+    pub fn get(&self, loc: Location, var_id: VarId::Id) -> Option<&BTreeSet<DefSite>> {
+        self.uses.get(&loc).and_then(|uses| uses.get(&var_id))
+    }
+}
+
+fn is_deref(pe: &ProjectionElem) -> bool {
+    match pe {
+        ProjectionElem::Deref
+        | ProjectionElem::DerefRawPtr
+        | ProjectionElem::DerefPtrUnique
+        | ProjectionElem::DerefPtrNonNull
+        | ProjectionElem::DerefRc => true,
+        ProjectionElem::DerefBox
+        | ProjectionElem::Field(_, _)
+        | ProjectionElem::Index(_)
+        | ProjectionElem::ConstantIndex { .. } => false,
+    }
+}
+
+/// The analysis state, while we explore a block
+struct Visitor<'a> {
+    defs: Defs,
+    /// Called on every use of a local, with the definitions which reach it
+    on_use: &'a mut dyn FnMut(Location, VarId::Id, &BTreeSet<DefSite>),
+}
+
+impl<'a> Visitor<'a> {
+    fn use_var(&mut self, loc: Location, var_id: VarId::Id) {
+        let defs = self.defs.get(&var_id).cloned().unwrap_or_default();
+        (self.on_use)(loc, var_id, &defs);
+    }
+
+    /// The local variables used as indices in a projection
+    fn use_indices(&mut self, loc: Location, p: &Place) {
+        for pe in p.projection.iter() {
+            if let ProjectionElem::Index(var_id) = pe {
+                self.use_var(loc, *var_id);
+            }
+        }
+    }
+
+    fn read_place(&mut self, loc: Location, p: &Place) {
+        self.use_var(loc, p.var_id);
+        self.use_indices(loc, p);
+    }
+
+    fn read_operand(&mut self, loc: Location, op: &Operand) {
+        match op {
+            Operand::Copy(p) | Operand::Move(p) => self.read_place(loc, p),
+            Operand::Const(_, _) => (),
+        }
+    }
+
+    fn read_rvalue(&mut self, loc: Location, rv: &Rvalue) {
+        match rv {
+            Rvalue::Use(op)
+            | Rvalue::UnaryOp(_, op)
+            | Rvalue::Unsize(op, _, _)
+            | Rvalue::Cast(_, op, _, _) => self.read_operand(loc, op),
+            Rvalue::BinaryOp(_, op1, op2) => {
+                self.read_operand(loc, op1);
+                self.read_operand(loc, op2);
+            }
+            Rvalue::Ref(p, _)
+            | Rvalue::RawPtr(p, _)
+            | Rvalue::Discriminant(p)
+            | Rvalue::Len(p)
+            | Rvalue::IsVariant(p, _) => self.read_place(loc, p),
+            Rvalue::Slice { base, from, to, .. } => {
+                self.read_place(loc, base);
+                self.read_operand(loc, from);
+                self.read_operand(loc, to);
+            }
+            Rvalue::Aggregate(_, ops) => {
+                for op in ops {
+                    self.read_operand(loc, op);
+                }
+            }
+            Rvalue::Global(_) | Rvalue::GlobalRef(_, _) | Rvalue::FnPtr(_) => (),
+        }
+    }
+
+    /// The place is written to
+    fn write_place(&mut self, loc: Location, p: &Place) {
+        self.use_indices(loc, p);
+        if p.projection.iter().any(is_deref) {
+            // We write through a pointer: this is a use of the pointer
+            self.use_var(loc, p.var_id);
+        } else {
+            let defs = self.defs.entry(p.var_id).or_default();
+            if p.projection.is_empty() {
+                defs.clear();
+            }
+            defs.insert(DefSite::Location(loc));
+        }
+    }
+
+    fn kill_var(&mut self, var_id: VarId::Id) {
+        self.defs.remove(&var_id);
+    }
+
+    fn visit_statement(&mut self, loc: Location, st: &Statement) {
+        match &st.content {
+            RawStatement::Assign(p, rv) => {
+                self.read_rvalue(loc, rv);
+                self.write_place(loc, p);
+            }
+            RawStatement::FakeRead(p) => self.read_place(loc, p),
+            RawStatement::SetDiscriminant(p, _) => self.write_place(loc, p),
+            RawStatement::StorageDead(var_id) => self.kill_var(*var_id),
+            RawStatement::Deinit(p) => {
+                if p.projection.is_empty() {
+                    self.kill_var(p.var_id)
+                }
+            }
+        }
+    }
+
+    fn visit_terminator(&mut self, loc: Location, terminator: &Terminator) {
+        match &terminator.content {
+            RawTerminator::Switch { discr, .. } => self.read_operand(loc, discr),
+            RawTerminator::Drop { place, .. } => self.read_place(loc, place),
+            RawTerminator::Call {
+                func, args, dest, ..
+            } => {
+                if let FunId::Indirect(op) = func {
+                    self.read_operand(loc, op);
+                }
+                for arg in args {
+                    self.read_operand(loc, arg);
+                }
+                self.write_place(loc, dest);
+            }
+            RawTerminator::Assert { cond, .. } => self.read_operand(loc, cond),
+            RawTerminator::Goto { .. }
+            | RawTerminator::Panic
+            | RawTerminator::Return
+            | RawTerminator::Unreachable => (),
+        }
+    }
+
+    fn visit_block(&mut self, block: BlockId::Id, data: &BlockData) {
+        for (index, st) in data.statements.iter().enumerate() {
+            self.visit_statement(Location { block, index }, st);
+        }
+        let index = data.statements.len();
+        self.visit_terminator(Location { block, index }, &data.terminator);
+    }
+}
+
+/// Add the incoming definitions to the definitions at the entry of a block,
+/// and return true if the latter changed
+fn join(entry: &mut Defs, incoming: &Defs) -> bool {
+    let mut changed = false;
+    for (var_id, defs) in incoming {
+        let entry_defs = entry.entry(*var_id).or_default();
+        for def in defs {
+            changed |= entry_defs.insert(*def);
+        }
+    }
+    changed
+}
+
+pub fn reaching_definitions(body: &ExprBody) -> ReachingDefs {
+    // The definitions which reach the entry of every block: we compute them
+    // with a worklist algorithm, until we reach a fixed point. The blocks
+    // which are not in this map are unreachable.
+    let mut entries: HashMap<BlockId::Id, Defs> = HashMap::new();
+    let args = (1..=body.arg_count).map(|i| (VarId::Id::new(i), BTreeSet::from([DefSite::Arg])));
+    entries.insert(START_BLOCK_ID, args.collect());
+    let mut worklist = vec![START_BLOCK_ID];
+    while let Some(block) = worklist.pop() {
+        let data = body.body.get(block).unwrap();
+        let mut visitor = Visitor {
+            defs: entries[&block].clone(),
+            on_use: &mut |_, _, _| (),
+        };
+        visitor.visit_block(block, data);
+        for succ in data.terminator.content.successors() {
+            let changed = match entries.get_mut(&succ) {
+                Some(entry) => join(entry, &visitor.defs),
+                None => {
+                    entries.insert(succ, visitor.defs.clone());
+                    true
+                }
+            };
+            if changed && !worklist.contains(&succ) {
+                worklist.push(succ);
+            }
+        }
+    }
+
+    // Explore the blocks a last time, to register the uses
+    let mut uses: HashMap<Location, HashMap<VarId::Id, BTreeSet<DefSite>>> = HashMap::new();
+    for (block, defs) in entries {
+        let mut visitor = Visitor {
+            defs,
+            on_use: &mut |loc, var_id, defs| {
+                let reaching = uses.entry(loc).or_default().entry(var_id).or_default();
+                reaching.extend(defs.iter().copied());
+            },
+        };
+        visitor.visit_block(block, body.body.get(block).unwrap());
+    }
+    ReachingDefs { uses }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reaching_defs::*;
+    use crate::test_utils::*;
+    use crate::types::*;
+    use crate::values::*;
+
+    fn mk_const_assign(var_id: VarId::Id, v: u32) -> RawStatement {
+        let v = OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(ScalarValue::U32(v)));
+        RawStatement::Assign(
+            Place::new(var_id),
+            Rvalue::Use(Operand::Const(Ty::Integer(IntegerTy::U32), v)),
+        )
+    }
+
+    #[test]
+    fn test_diamond() {
+        let bb: Vec<BlockId::Id> = (0..4).map(BlockId::Id::new).collect();
+        let (b, x) = (VarId::Id::new(1), VarId::Id::new(2));
+        // `fn f(b: bool) -> u32`:
+        // ```
+        // bb0: if move b then goto bb1 else goto bb2
+        // bb1: x := const 1; goto bb3
+        // bb2: x := const 2; goto bb3
+        // bb3: _0 := copy x; return
+        // ```
+        let blocks = vec![
+            mk_block(
+                vec![],
+                RawTerminator::Switch {
+                    discr: Operand::Move(Place::new(b)),
+                    targets: SwitchTargets::If(bb[1], bb[2]),
+                },
+            ),
+            mk_block(
+                vec![mk_const_assign(x, 1)],
+                RawTerminator::Goto { target: bb[3] },
+            ),
+            mk_block(
+                vec![mk_const_assign(x, 2)],
+                RawTerminator::Goto { target: bb[3] },
+            ),
+            mk_block(
+                vec![RawStatement::Assign(
+                    Place::new(VarId::ZERO),
+                    Rvalue::Use(Operand::Copy(Place::new(x))),
+                )],
+                RawTerminator::Return,
+            ),
+        ];
+        let body = mk_cfg(1, vec![], blocks);
+        let defs = reaching_definitions(&body);
+
+        // The use of `b` is reached by the argument
+        let loc = |block: usize, index: usize| Location {
+            block: bb[block],
+            index,
+        };
+        assert!(defs.get(loc(0, 0), b) == Some(&BTreeSet::from([DefSite::Arg])));
+        // The use of `x` after the join is reached by both assignments
+        assert!(
+            defs.get(loc(3, 0), x)
+                == Some(&BTreeSet::from([
+                    DefSite::Location(loc(1, 0)),
+                    DefSite::Location(loc(2, 0)),
+                ]))
+        );
+        // `x` is not used in the branches
+        assert!(defs.get(loc(1, 0), x).is_none());
+    }
+}
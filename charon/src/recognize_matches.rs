@@ -0,0 +1,165 @@
+//! Recognize the matches which simply compute a boolean, like the ones
+//! generated by the `matches!` macro. For instance, `b = matches!(o, Some(_))`
+//! gives:
+//! ```text
+//! match o {
+//!   1 => {
+//!     b := const true
+//!   },
+//!   _ => {
+//!     b := const false
+//!   }
+//! }
+//! ```
+//! which we rewrite to:
+//! ```text
+//! b := @is_variant(o, 1)
+//! ```
+//!
+//! rustc sometimes puts the `true` case in the otherwise branch (for instance
+//! if the pattern covers most of the variants). In this case, we negate the
+//! test:
+//! ```text
+//! b := @is_variant(o, 0);
+//! b := ~(copy b)
+//! ```
+//!
+//! We need to perform this transformation after [crate::remove_read_discriminant],
+//! which introduces the matches.
+
+use crate::expressions::*;
+use crate::llbc_ast::{
+    new_sequence, transform_statements, CtxNames, FunDecls, GlobalDecls, RawStatement, Statement,
+    Switch,
+};
+use crate::types::*;
+use crate::ullbc_ast::par_iter_bodies;
+use crate::values::*;
+use take_mut::take;
+
+/// If the statement is of the shape `p := const b` where `b` is a boolean,
+/// return `(p, b)`.
+fn get_bool_assign(st: &Statement) -> Option<(&Place, bool)> {
+    match &st.content {
+        RawStatement::Assign(
+            p,
+            Rvalue::Use(Operand::Const(
+                Ty::Bool,
+                OperandConstantValue::PrimitiveValue(PrimitiveValue::Bool(b)),
+            )),
+        ) => Some((p, *b)),
+        _ => None,
+    }
+}
+
+fn transform_st(st: Statement) -> Statement {
+    if let RawStatement::Switch(Switch::Match(p, branches, otherwise, _)) = &st.content {
+        if let Some((dest, otherwise_value)) = get_bool_assign(otherwise) {
+            // All the branches must assign the opposite value to the same place
+            let is_test = branches.iter().all(|(_, branch)| {
+                matches!(get_bool_assign(branch), Some((dest1, b)) if dest1 == dest && b != otherwise_value)
+            });
+            if is_test {
+                let variant_ids = branches
+                    .iter()
+                    .flat_map(|(variant_ids, _)| variant_ids.iter().copied())
+                    .collect();
+                let test = Statement::new(
+                    st.meta,
+                    RawStatement::Assign(dest.clone(), Rvalue::IsVariant(p.clone(), variant_ids)),
+                );
+                return if otherwise_value {
+                    let negate = Statement::new(
+                        st.meta,
+                        RawStatement::Assign(
+                            dest.clone(),
+                            Rvalue::UnaryOp(UnOp::Not, Operand::Copy(dest.clone())),
+                        ),
+                    );
+                    new_sequence(test, negate)
+                } else {
+                    test
+                };
+            }
+        }
+    }
+    st
+}
+
+/// `fmt_ctx` is used for pretty-printing purposes.
+pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
+    par_iter_bodies(funs, globals, |name, b| {
+        trace!(
+            "# About to recognize the boolean matches in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        take(&mut b.body, |st| {
+            transform_statements(&mut transform_st, st)
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::meta::Meta;
+    use crate::recognize_matches::*;
+
+    fn mk_bool_assign(p: &Place, b: bool) -> Statement {
+        Statement::new(
+            Meta::dummy(),
+            RawStatement::Assign(
+                p.clone(),
+                Rvalue::Use(Operand::Const(
+                    Ty::Bool,
+                    OperandConstantValue::PrimitiveValue(PrimitiveValue::Bool(b)),
+                )),
+            ),
+        )
+    }
+
+    fn mk_match(o: &Place, b: &Place, some_value: bool) -> Statement {
+        Statement::new(
+            Meta::dummy(),
+            RawStatement::Switch(Switch::Match(
+                o.clone(),
+                vec![(vec![VariantId::Id::new(1)], mk_bool_assign(b, some_value))],
+                Box::new(mk_bool_assign(b, !some_value)),
+                None,
+            )),
+        )
+    }
+
+    #[test]
+    fn test_matches_some() {
+        let o = Place::new(VarId::Id::new(1));
+        let b = Place::new(VarId::Id::new(2));
+        // `let b = matches!(o, Some(_));`:
+        // ```
+        // match o { 1 => { b := const true }, _ => { b := const false } }
+        // ```
+        let st = transform_statements(&mut transform_st, mk_match(&o, &b, true));
+        // ```
+        // b := @is_variant(o, 1)
+        // ```
+        assert!(
+            matches!(&st.content, RawStatement::Assign(p, Rvalue::IsVariant(p1, variant_ids)) if *p == b && *p1 == o && *variant_ids == vec![VariantId::Id::new(1)])
+        );
+
+        // The negated test:
+        // ```
+        // match o { 1 => { b := const false }, _ => { b := const true } }
+        // ```
+        let st = transform_statements(&mut transform_st, mk_match(&o, &b, false));
+        // ```
+        // b := @is_variant(o, 1);
+        // b := ~(copy b)
+        // ```
+        let (st1, st2) = st.content.as_sequence();
+        assert!(
+            matches!(&st1.content, RawStatement::Assign(p, Rvalue::IsVariant(p1, _)) if *p == b && *p1 == o)
+        );
+        assert!(
+            matches!(&st2.content, RawStatement::Assign(p, Rvalue::UnaryOp(UnOp::Not, Operand::Copy(p1))) if *p == b && *p1 == b)
+        );
+    }
+}
@@ -3,17 +3,18 @@
 //! introduce `if ... then { panic!(...) } else { ...}`.
 //! This pass introduces `assert` instead in order to make the code shorter.
 
-use take_mut::take;
-
 use crate::{
-    llbc_ast::{Assert, CtxNames, FunDecls, GlobalDecls, RawStatement, Statement, Switch},
-    ullbc_ast::{iter_function_bodies, iter_global_bodies},
+    errors::{PassResult, TransResult, TranslationError},
+    llbc_ast::{
+        Assert, AssertKind, CtxNames, FunDecls, GlobalDecls, RawStatement, Statement, Switch,
+    },
+    ullbc_ast::par_try_iter_bodies,
 };
-use std::iter::FromIterator;
 
-fn transform_st(mut st: Statement) -> Statement {
+fn transform_st(mut st: Statement) -> TransResult<Statement> {
     st.content = match st.content {
         RawStatement::Assign(p, rv) => RawStatement::Assign(p, rv),
+        RawStatement::CompoundAssign(p, op, rhs) => RawStatement::CompoundAssign(p, op, rhs),
         RawStatement::FakeRead(p) => RawStatement::FakeRead(p),
         RawStatement::SetDiscriminant(p, vid) => RawStatement::SetDiscriminant(p, vid),
         RawStatement::Drop(p) => RawStatement::Drop(p),
@@ -27,7 +28,7 @@ fn transform_st(mut st: Statement) -> Statement {
         RawStatement::Switch(switch) => {
             match switch {
                 Switch::If(op, st1, st2) => {
-                    let st2 = Box::new(transform_st(*st2));
+                    let st2 = Box::new(transform_st(*st2)?);
 
                     // Check if the first statement is a panic: if yes, replace
                     // the if .. then ... else ... by an assertion.
@@ -37,44 +38,64 @@ fn transform_st(mut st: Statement) -> Statement {
                             RawStatement::Assert(Assert {
                                 cond: op,
                                 expected: false,
+                                kind: AssertKind::Other,
                             }),
                         );
                         let st1 = Box::new(st1);
 
                         RawStatement::Sequence(st1, st2)
                     } else {
-                        let switch = Switch::If(op, Box::new(transform_st(*st1)), st2);
+                        let switch = Switch::If(op, Box::new(transform_st(*st1)?), st2);
                         RawStatement::Switch(switch)
                     }
                 }
                 Switch::SwitchInt(op, int_ty, targets, mut otherwise) => {
-                    let targets =
-                        Vec::from_iter(targets.into_iter().map(|(v, e)| (v, transform_st(e))));
-                    *otherwise = transform_st(*otherwise);
+                    let targets = targets
+                        .into_iter()
+                        .map(|(v, e)| Ok((v, transform_st(e)?)))
+                        .collect::<TransResult<Vec<_>>>()?;
+                    *otherwise = transform_st(*otherwise)?;
                     let switch = Switch::SwitchInt(op, int_ty, targets, otherwise);
                     RawStatement::Switch(switch)
                 }
-                Switch::Match(_, _, _) => {
+                Switch::Match(_, _, _, _) => {
                     // This variant is introduced in a subsequent pass
-                    unreachable!();
+                    let msg = "unexpected match before the reconstruction of the matches";
+                    return Err(TranslationError::new(st.meta, msg));
                 }
             }
         }
-        RawStatement::Loop(loop_body) => RawStatement::Loop(Box::new(transform_st(*loop_body))),
+        RawStatement::Loop(loop_body, invariants) => {
+            RawStatement::Loop(Box::new(transform_st(*loop_body)?), invariants)
+        }
+        RawStatement::While(_, _, _, _) => {
+            // This variant is introduced in a subsequent pass
+            let msg = "unexpected while loop before the reconstruction of the loops";
+            return Err(TranslationError::new(st.meta, msg));
+        }
         RawStatement::Sequence(st1, st2) => {
-            RawStatement::Sequence(Box::new(transform_st(*st1)), Box::new(transform_st(*st2)))
+            RawStatement::Sequence(Box::new(transform_st(*st1)?), Box::new(transform_st(*st2)?))
         }
     };
 
-    st
+    Ok(st)
 }
 
-pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
-    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+pub fn transform(
+    fmt_ctx: &CtxNames<'_>,
+    funs: &mut FunDecls,
+    globals: &mut GlobalDecls,
+) -> PassResult {
+    let errors = par_try_iter_bodies(funs, globals, |name, b| {
         trace!(
             "# About to reconstruct asserts in decl: {name}\n{}",
             b.fmt_with_ctx_names(fmt_ctx)
         );
-        take(&mut b.body, transform_st);
-    }
+        // If we fail, the driver drops the whole body: we don't need to
+        // restore it
+        let body = std::mem::replace(&mut b.body, Statement::new(b.body.meta, RawStatement::Nop));
+        b.body = transform_st(body)?;
+        Ok(())
+    });
+    errors.into_result()
 }
@@ -0,0 +1,192 @@
+//! Reconstruct the `?` operator. rustc desugars `x?` to a call to
+//! `Try::branch`, followed by a match over the returned `ControlFlow`. Once
+//! the discriminant reads have been merged with the switches (see
+//! [crate::remove_read_discriminant]), we get:
+//! ```text
+//! cf := core::ops::try_trait::Try<Result<T, E>>::branch(move x);
+//! match cf {
+//!   0 => {
+//!     v := move (cf as variant @0).0;
+//!     ...
+//!   }
+//!   1 => {
+//!     r := move (cf as variant @1).0;
+//!     @return := core::ops::try_trait::FromResidual<...>::from_residual(move r);
+//!     return
+//!   }
+//!   _ => {
+//!     panic
+//!   }
+//! }
+//! ```
+//! Depending on the MIR, one of the two branches may be in the `otherwise`
+//! position. We normalize the match so that it always has exactly the two
+//! branches `Continue` and `Break`, in this order, and an unreachable
+//! `otherwise` branch.
+
+use take_mut::take;
+
+use crate::assumed::{CONTROL_FLOW_BREAK_VARIANT_ID, CONTROL_FLOW_CONTINUE_VARIANT_ID};
+use crate::expressions::*;
+use crate::llbc_ast::{
+    transform_statements, Call, CtxNames, FunDecls, GlobalDecls, RawStatement, Statement, Switch,
+};
+use crate::ullbc_ast::{par_iter_bodies, AssumedFunId, FunId};
+
+/// If the statement is a call to `Try::branch`, return the destination.
+fn get_try_branch_dest(st: &Statement) -> Option<&Place> {
+    match &st.content {
+        RawStatement::Call(Call {
+            func: FunId::Assumed(AssumedFunId::TryBranch),
+            dest,
+            ..
+        }) => Some(dest),
+        _ => None,
+    }
+}
+
+/// Normalize a match over `cf`, the result of `Try::branch`. We leave the
+/// statement unchanged if it doesn't have the expected shape.
+fn normalize_match(cf: &Place, st: Statement) -> Statement {
+    let (scrutinee, targets, otherwise) = match st.content {
+        RawStatement::Switch(Switch::Match(scrutinee, targets, otherwise, None))
+            if scrutinee == *cf
+                && !targets.is_empty()
+                && targets.iter().all(|(variants, _)| variants.len() == 1) =>
+        {
+            (scrutinee, targets, otherwise)
+        }
+        content => return Statement::new(st.meta, content),
+    };
+
+    let mut continue_branch = None;
+    let mut break_branch = None;
+    for (variants, branch) in targets {
+        if variants[0] == CONTROL_FLOW_CONTINUE_VARIANT_ID {
+            continue_branch = Some(branch);
+        } else {
+            break_branch = Some(branch);
+        }
+    }
+
+    // If one of the branches is missing, it is the `otherwise` branch
+    let otherwise = *otherwise;
+    let unreachable = Statement::new(otherwise.meta, RawStatement::Panic);
+    let (continue_branch, break_branch) = match (continue_branch, break_branch) {
+        (Some(c), Some(b)) => (c, b),
+        (Some(c), None) => (c, otherwise),
+        (None, Some(b)) => (otherwise, b),
+        (None, None) => unreachable!(),
+    };
+
+    let targets = vec![
+        (vec![CONTROL_FLOW_CONTINUE_VARIANT_ID], continue_branch),
+        (vec![CONTROL_FLOW_BREAK_VARIANT_ID], break_branch),
+    ];
+    let switch = Switch::Match(scrutinee, targets, Box::new(unreachable), None);
+    Statement::new(st.meta, RawStatement::Switch(switch))
+}
+
+fn transform_st(mut st: Statement) -> Statement {
+    if let RawStatement::Sequence(call, next) = &mut st.content {
+        if let Some(cf) = get_try_branch_dest(call) {
+            // The match may be followed by other statements
+            let cf = cf.clone();
+            take(next.as_mut(), |next| match next.content {
+                RawStatement::Sequence(switch, rest) => {
+                    let switch = Box::new(normalize_match(&cf, *switch));
+                    Statement::new(next.meta, RawStatement::Sequence(switch, rest))
+                }
+                content => normalize_match(&cf, Statement::new(next.meta, content)),
+            });
+        }
+    }
+    st
+}
+
+/// `fmt_ctx` is used for pretty-printing purposes.
+pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
+    par_iter_bodies(funs, globals, |name, b| {
+        trace!(
+            "# About to reconstruct the `?` operators in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        take(&mut b.body, |st| {
+            transform_statements(&mut transform_st, st)
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llbc_ast::{chain_statements, new_sequence};
+    use crate::meta::Meta;
+    use crate::reconstruct_try::*;
+
+    fn mk(content: RawStatement) -> Statement {
+        Statement::new(Meta::dummy(), content)
+    }
+
+    #[test]
+    fn test_try() {
+        let x = Place::new(VarId::Id::new(1));
+        let cf = Place::new(VarId::Id::new(2));
+        let branch = mk(RawStatement::Call(Call {
+            func: FunId::Assumed(AssumedFunId::TryBranch),
+            region_args: vec![],
+            type_args: vec![],
+            args: vec![Operand::Move(x)],
+            dest: cf.clone(),
+            sig: None,
+        }));
+        // The `Break` branch is in the `otherwise` position:
+        // ```
+        // cf := branch(move x);
+        // match cf { 0 => { nop }, _ => { return } };
+        // nop
+        // ```
+        let switch = mk(RawStatement::Switch(Switch::Match(
+            cf.clone(),
+            vec![(
+                vec![CONTROL_FLOW_CONTINUE_VARIANT_ID],
+                mk(RawStatement::Nop),
+            )],
+            Box::new(mk(RawStatement::Return)),
+            None,
+        )));
+        let st = chain_statements(vec![branch, switch], mk(RawStatement::Nop));
+        let st = transform_statements(&mut transform_st, st);
+
+        // ```
+        // cf := branch(move x);
+        // match cf { 0 => { nop }, 1 => { return }, _ => { panic } };
+        // nop
+        // ```
+        let (_, next) = st.content.to_sequence();
+        let (switch, rest) = next.content.to_sequence();
+        assert!(rest.content.is_nop());
+        let (p, targets, otherwise, _) = switch.content.to_switch().to_match();
+        assert!(p == cf);
+        assert!(targets.len() == 2);
+        assert!(targets[0].0 == vec![CONTROL_FLOW_CONTINUE_VARIANT_ID]);
+        assert!(targets[0].1.content.is_nop());
+        assert!(targets[1].0 == vec![CONTROL_FLOW_BREAK_VARIANT_ID]);
+        assert!(targets[1].1.content.is_return());
+        assert!(otherwise.content.is_panic());
+
+        // The matches over other values are left unchanged
+        let st = new_sequence(
+            mk(RawStatement::Nop),
+            mk(RawStatement::Switch(Switch::Match(
+                cf,
+                vec![(vec![CONTROL_FLOW_BREAK_VARIANT_ID], mk(RawStatement::Nop))],
+                Box::new(mk(RawStatement::Return)),
+                None,
+            ))),
+        );
+        let st = transform_statements(&mut transform_st, st);
+        let (_, switch) = st.content.to_sequence();
+        let (_, targets, _, _) = switch.content.to_switch().to_match();
+        assert!(targets.len() == 1);
+    }
+}
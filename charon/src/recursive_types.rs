@@ -0,0 +1,114 @@
+//! Analyze the groups of mutually recursive types, to find out which fields
+//! cause the recursion. The backends often need this information: for
+//! instance, some of them require the recursive occurrences to go through a
+//! `Box`, or need to generate measures for the recursive fields only.
+//!
+//! For instance, in:
+//! ```text
+//! enum List {
+//!   Cons(u32, Box<List>),
+//!   Nil,
+//! }
+//! ```
+//! the second field of `Cons` is recursive.
+
+use crate::types::*;
+use im::OrdSet;
+use std::iter::FromIterator;
+
+/// Compute the fields of a type which refer to one of the types in `group`
+fn compute_recursive_fields(
+    group: &OrdSet<TypeDeclId::Id>,
+    def: &TypeDecl,
+) -> Vec<(Option<VariantId::Id>, FieldId::Id)> {
+    let variants: Vec<(Option<VariantId::Id>, &FieldId::Vector<Field>)> = match &def.kind {
+        TypeDeclKind::Struct(fields) | TypeDeclKind::Union(fields) => vec![(None, fields)],
+        TypeDeclKind::Enum(variants) => variants
+            .iter_indexed_values()
+            .map(|(variant_id, variant)| (Some(variant_id), &variant.fields))
+            .collect(),
+        // We can't look inside the opaque types
+        TypeDeclKind::Opaque => Vec::new(),
+    };
+    variants
+        .into_iter()
+        .flat_map(|(variant_id, fields)| {
+            fields
+                .iter_indexed_values()
+                .filter(|(_, field)| field.ty.contains_adt(group))
+                .map(move |(field_id, _)| (variant_id, field_id))
+        })
+        .collect()
+}
+
+/// Compute the recursive fields of the types in a group of mutually
+/// recursive types, and update their definitions.
+pub fn compute_recursive_fields_for_type_decl_group(types: &mut TypeDecls, ids: &[TypeDeclId::Id]) {
+    let group = OrdSet::from_iter(ids.iter().copied());
+    for id in ids {
+        let type_def = types.types.get_mut(*id).unwrap();
+        type_def.recursive_fields = compute_recursive_fields(&group, type_def);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::meta::{AttrInfo, Meta};
+    use crate::names::TypeName;
+    use crate::recursive_types::*;
+    use crate::regions_hierarchy::RegionGroups;
+
+    fn mk_field(ty: RTy) -> Field {
+        Field {
+            meta: Meta::dummy(),
+            name: None,
+            ty,
+        }
+    }
+
+    fn mk_variant(name: &str, fields: Vec<Field>) -> Variant {
+        Variant {
+            meta: Meta::dummy(),
+            name: name.to_string(),
+            fields: FieldId::Vector::from(fields),
+        }
+    }
+
+    #[test]
+    fn test_list() {
+        // `enum List { Cons(u32, Box<List>), Nil }`
+        let list_id = TypeDeclId::ZERO;
+        let list_ty = Ty::Adt(TypeId::Adt(list_id), im::Vector::new(), im::Vector::new());
+        let box_list_ty = Ty::Adt(
+            TypeId::Assumed(AssumedTy::Box),
+            im::Vector::new(),
+            im::Vector::from(vec![list_ty]),
+        );
+        let cons = mk_variant(
+            "Cons",
+            vec![mk_field(Ty::Integer(IntegerTy::U32)), mk_field(box_list_ty)],
+        );
+        let nil = mk_variant("Nil", Vec::new());
+        let mut types = TypeDecls::new();
+        types.types.push_back(TypeDecl {
+            def_id: list_id,
+            meta: Meta::dummy(),
+            name: TypeName::from(vec!["List".to_string()]),
+            region_params: RegionVarId::Vector::new(),
+            type_params: TypeVarId::Vector::new(),
+            const_generic_params: ConstGenericVarId::Vector::new(),
+            trait_clauses: Vec::new(),
+            regions_hierarchy: RegionGroups::new(),
+            kind: TypeDeclKind::Enum(VariantId::Vector::from(vec![cons, nil])),
+            repr_packed: None,
+            under_aligned_fields: Vec::new(),
+            recursive_fields: Vec::new(),
+            attr_info: AttrInfo::default(),
+        });
+
+        compute_recursive_fields_for_type_decl_group(&mut types, &[list_id]);
+        // Only the tail of `Cons` is recursive
+        let list = types.get_type_def(list_id).unwrap();
+        assert!(list.recursive_fields == vec![(Some(VariantId::ZERO), FieldId::ONE)]);
+    }
+}
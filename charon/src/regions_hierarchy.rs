@@ -15,7 +15,7 @@ use macros::generate_index_type;
 use petgraph::algo::tarjan_scc;
 use petgraph::graphmap::DiGraphMap;
 use petgraph::Direction;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
 
@@ -39,7 +39,7 @@ type LifetimeConstraints = DiGraphMap<Region<RegionVarId::Id>, ()>;
 ///
 /// Is used to group regions with the same lifetime together, and express
 /// the lifetime hierarchy between different groups of regions.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegionGroup {
     /// The region group identifier
     pub id: RegionGroupId::Id,
@@ -296,8 +296,10 @@ fn compute_full_regions_constraints_for_ty(
                         );
                     }
 
-                    // Explore the types given as parameters
-                    let types: TypeVarId::Vector<&RTy> = TypeVarId::Vector::from_iter(types.iter());
+                    // Explore the types given as parameters (the const generic
+                    // arguments don't contain regions)
+                    let types = types.iter().filter(|ty| !ty.is_const_generic());
+                    let types: TypeVarId::Vector<&RTy> = TypeVarId::Vector::from_iter(types);
                     for (type_param_id, fty) in types.iter_indexed_values() {
                         // Retrieve the (non-instantiated) parent regions for this type param
                         let type_param_constraints = adt_constraints
@@ -331,8 +333,11 @@ fn compute_full_regions_constraints_for_ty(
                     AssumedTy::Box
                     | AssumedTy::Vec
                     | AssumedTy::Option
+                    | AssumedTy::ControlFlow
                     | AssumedTy::PtrUnique
-                    | AssumedTy::PtrNonNull,
+                    | AssumedTy::PtrNonNull
+                    | AssumedTy::Rc
+                    | AssumedTy::Arc,
                 ) => {
                     // Explore the types given as parameters
                     for fty in types {
@@ -348,10 +353,17 @@ fn compute_full_regions_constraints_for_ty(
                 }
             }
         }
-        Ty::Bool | Ty::Char | Ty::Never | Ty::Integer(_) | Ty::Str => {
+        Ty::Bool
+        | Ty::Char
+        | Ty::Never
+        | Ty::Integer(_)
+        | Ty::Float(_)
+        | Ty::Str
+        | Ty::ConstGeneric(_)
+        | Ty::DynTrait(_) => {
             // Nothing to do
         }
-        Ty::Array(_aty) => {
+        Ty::Array(_aty, _len) => {
             unimplemented!();
         }
         Ty::Slice(_sty) => {
@@ -390,6 +402,56 @@ fn compute_full_regions_constraints_for_ty(
                 ptr_ty,
             );
         }
+        Ty::Opaque(None, _) => {
+            // Nothing to do
+        }
+        Ty::Opaque(Some(hidden_ty), _) => {
+            // Dive in
+            compute_full_regions_constraints_for_ty(
+                updated,
+                constraints_map,
+                acc_constraints,
+                type_def_constraints,
+                parent_regions,
+                hidden_ty,
+            );
+        }
+        Ty::TraitProjection(_, regions, types, _) => {
+            // We don't know how the associated type uses the arguments of
+            // the trait: we consider that it may contain all of them
+            for region in regions.iter() {
+                add_region_constraints(
+                    updated,
+                    acc_constraints,
+                    type_def_constraints,
+                    *region,
+                    &parent_regions,
+                );
+            }
+            for ty in types.iter().filter(|ty| !ty.is_const_generic()) {
+                compute_full_regions_constraints_for_ty(
+                    updated,
+                    constraints_map,
+                    acc_constraints,
+                    type_def_constraints,
+                    parent_regions.clone(),
+                    ty,
+                );
+            }
+        }
+        Ty::FnPtr(inputs, output) => {
+            // Dive in
+            for ty in inputs.iter().chain(std::iter::once(output.as_ref())) {
+                compute_full_regions_constraints_for_ty(
+                    updated,
+                    constraints_map,
+                    acc_constraints,
+                    type_def_constraints,
+                    parent_regions.clone(),
+                    ty,
+                );
+            }
+        }
         Ty::TypeVar(var_id) => {
             // Add the parent regions in the set of parent regions for the type variable
             match type_def_constraints {
@@ -10,7 +10,10 @@
 //! also perform a check upon being called, while the other would not.
 
 use crate::assumed;
+use crate::closures;
 use crate::common::*;
+use crate::contracts;
+use crate::diagnostics::{Code, Diagnostics, Level};
 use crate::generics;
 use crate::get_mir::{extract_constants_at_top_level, get_mir_for_def_id_and_level, MirLevel};
 use crate::meta;
@@ -21,14 +24,19 @@ use crate::names::{
     type_def_id_to_name,
 };
 use crate::translate_functions_to_ullbc;
+use crate::translate_traits;
+use crate::values::VarId;
 use hashlink::LinkedHashMap;
 use im::Vector;
 use linked_hash_set::LinkedHashSet;
 use rustc_hir::{
     def_id::DefId, def_id::LocalDefId, Defaultness, ImplItem, ImplItemKind, Item, ItemKind,
+    TraitFn, TraitItem, TraitItemKind,
 };
 use rustc_middle::mir;
-use rustc_middle::ty::{AdtDef, Ty, TyCtxt, TyKind};
+use rustc_middle::ty::adjustment::PointerCast;
+use rustc_middle::ty::subst::SubstsRef;
+use rustc_middle::ty::{AdtDef, AliasKind, Ty, TyCtxt, TyKind};
 use rustc_session::Session;
 use rustc_span::Span;
 use std::collections::{HashMap, HashSet};
@@ -42,12 +50,26 @@ pub(crate) fn stack_to_string(stack: &Vector<DefId>) -> String {
 pub struct CrateInfo {
     pub crate_name: String,
     pub opaque_mods: HashSet<String>,
+    /// The glob patterns given with `--include`: if not empty, we only
+    /// translate the declarations which match one of those patterns.
+    pub include: Vec<String>,
+    /// The glob patterns given with `--exclude`
+    pub exclude: Vec<String>,
 }
 
 impl CrateInfo {
     fn has_opaque_decl(&self, name: &Name) -> bool {
         name.is_in_modules(&self.crate_name, &self.opaque_mods)
     }
+
+    /// Return `true` if the declaration is filtered out by the `--include`
+    /// and `--exclude` options. We don't translate the filtered out
+    /// declarations, unless a translated declaration depends on them: in
+    /// this case we register them as opaque.
+    fn is_filtered_out(&self, name: &Name) -> bool {
+        let included = self.include.is_empty() || self.include.iter().any(|p| name.matches_glob(p));
+        !included || self.exclude.iter().any(|p| name.matches_glob(p))
+    }
 }
 
 /// All kind of supported Rust top-level declarations.
@@ -144,6 +166,20 @@ struct RegisterContext<'a, 'b, 'c> {
     sess: &'b Session,
     crate_info: &'c CrateInfo,
     mir_level: MirLevel,
+    diagnostics: &'b Diagnostics,
+}
+
+impl<'a, 'b, 'c> RegisterContext<'a, 'b, 'c> {
+    /// Report a feature we don't support (see [crate::diagnostics])
+    fn span_err(&self, code: Code, span: Span, msg: &str) {
+        self.diagnostics
+            .span_report(self.sess, Level::Error, code, span, msg)
+    }
+
+    fn span_warn(&self, code: Code, span: Span, msg: &str) {
+        self.diagnostics
+            .span_report(self.sess, Level::Warning, code, span, msg)
+    }
 }
 
 pub type RegisteredDeclarations = LinkedHashMap<DefId, Declaration>;
@@ -181,6 +217,22 @@ impl DeclarationsRegister {
         self.register_file(filename);
     }
 
+    /// Register the files containing the trait, and the trait implementation,
+    /// a method belongs to: we need them for the spans of the trait
+    /// declarations (see [crate::translate_traits]).
+    fn register_trait_files(&mut self, ctx: &RegisterContext, id: DefId) {
+        let tcx = ctx.rustc;
+        if let Some(trait_id) = tcx.trait_of_item(id) {
+            self.register_file_from_def_id(ctx, trait_id);
+        }
+        if let Some(impl_id) = tcx.impl_of_method(id) {
+            if let Some(trait_id) = tcx.trait_id_of_impl(impl_id) {
+                self.register_file_from_def_id(ctx, trait_id);
+                self.register_file_from_def_id(ctx, impl_id);
+            }
+        }
+    }
+
     /// Register a file if it is a "real" file if it was not already registered
     fn register_file(&mut self, filename: FileName) {
         let _ = self.files.insert(filename, FileInfo {});
@@ -245,6 +297,9 @@ impl DeclarationsRegister {
 
         // Register the file
         self.register_file_from_def_id(ctx, id);
+        if kind == DeclKind::Fun {
+            self.register_trait_files(ctx, id);
+        }
 
         if let Some(decl) = new_opaque_declaration(ctx.rustc, id, kind, name) {
             self.add_begin(id);
@@ -281,6 +336,9 @@ impl DeclarationsRegister {
 
         // Register the file
         self.register_file_from_def_id(ctx, id);
+        if kind == DeclKind::Fun {
+            self.register_trait_files(ctx, id);
+        }
 
         let name = get_decl_name(ctx.rustc, kind, id);
 
@@ -292,8 +350,10 @@ impl DeclarationsRegister {
         // TODO: we check this here and in translate_functions_to_ullbc
         check_decl_generics(kind, ctx.rustc, id);
 
-        // We don't explore declarations in opaque modules.
-        if ctx.crate_info.has_opaque_decl(&name) {
+        // We don't explore declarations in opaque modules, nor the
+        // declarations filtered out by the user (we get there if they are
+        // dependencies of declarations we translate).
+        if ctx.crate_info.has_opaque_decl(&name) || ctx.crate_info.is_filtered_out(&name) {
             self.add_end(Declaration::new_opaque(id, kind));
             Ok(())
         } else {
@@ -351,7 +411,7 @@ fn explore_local_hir_type_item(
             trace!("enum");
             unreachable!();
         }
-        ItemKind::Struct(_, _) | ItemKind::Enum(_, _) => {
+        ItemKind::Struct(_, _) | ItemKind::Enum(_, _) | ItemKind::Union(_, _) => {
             trace!("adt");
 
             // Retrieve the MIR adt from the def id and register it, retrieve
@@ -405,7 +465,7 @@ fn explore_local_adt(
         // in case of an enum.
         let hir_variants: &[rustc_hir::Variant] = match &item.kind {
             ItemKind::Enum(enum_def, _) => enum_def.variants,
-            ItemKind::Struct(_, _) => {
+            ItemKind::Struct(_, _) | ItemKind::Union(_, _) => {
                 // Nothing to return
                 &[]
             }
@@ -433,10 +493,62 @@ fn explore_local_adt(
                 explore_mir_ty(ctx, nstack.clone(), decls, var_span, &mut ty_deps, &ty)?;
             }
         }
+        explore_trait_clauses(ctx, nstack, decls, &item.span, &mut ty_deps, adt_did)?;
         Ok(ty_deps)
     })
 }
 
+/// Register the traits of the trait clauses of a declaration (see
+/// [crate::types::TraitClause]), like for the trait objects, and the types
+/// the clauses refer to.
+fn explore_trait_clauses(
+    ctx: &RegisterContext,
+    stack: Vector<DefId>,
+    decls: &mut DeclarationsRegister,
+    span: &Span,
+    deps: &mut DeclDependencies,
+    id: DefId,
+) -> Result<()> {
+    for (trait_id, substs) in translate_traits::get_trait_clauses(ctx.rustc, id) {
+        for (_, method_id) in translate_traits::get_methods(ctx.rustc, trait_id) {
+            let name = function_def_id_to_name(ctx.rustc, method_id);
+            explore_dependency_item(ctx, stack.clone(), decls, method_id, DeclKind::Fun, &name)?;
+        }
+        for ty in substs.types() {
+            explore_mir_ty(ctx, stack.clone(), decls, span, deps, &ty)?;
+        }
+    }
+    Ok(())
+}
+
+/// Register a trait we refer to through one of its associated items (an
+/// associated type or an associated constant). We only translate the traits
+/// the registered functions belong to (see [crate::rust_to_local_ids]): we
+/// register the methods of the trait.
+fn explore_trait(
+    ctx: &RegisterContext,
+    stack: Vector<DefId>,
+    decls: &mut DeclarationsRegister,
+    span: &Span,
+    trait_id: DefId,
+) -> Result<()> {
+    let methods: Vec<(String, DefId)> =
+        translate_traits::get_methods(ctx.rustc, trait_id).collect();
+    if methods.is_empty() {
+        ctx.span_err(
+            Code::TraitWithoutMethods,
+            *span,
+            "The associated items of the traits without methods are not supported",
+        );
+        return Err(());
+    }
+    for (_, method_id) in methods {
+        let name = function_def_id_to_name(ctx.rustc, method_id);
+        explore_dependency_item(ctx, stack.clone(), decls, method_id, DeclKind::Fun, &name)?;
+    }
+    Ok(())
+}
+
 /// Auxiliary function to register a list of type parameters.
 ///
 /// `stack`: see the explanations for [explore_local_hir_item].
@@ -638,12 +750,16 @@ fn explore_mir_ty(
         TyKind::Foreign(_) => {
             // A raw pointer
             trace!("Foreign");
-            span_err(ctx.sess, *span, "FFI types are not supported");
+            ctx.span_err(Code::FfiType, *span, "FFI types are not supported");
             Err(())
         }
         TyKind::Infer(_) => {
             trace!("Infer");
-            span_err(ctx.sess, *span, "Inconsistant state: found an `Infer` type");
+            ctx.span_err(
+                Code::InferType,
+                *span,
+                "Inconsistant state: found an `Infer` type",
+            );
             Err(())
         }
 
@@ -655,38 +771,133 @@ fn explore_mir_ty(
 
         TyKind::FnPtr(sig) => {
             trace!("FnPtr");
-            for param_ty in sig.inputs_and_output().no_bound_vars().unwrap().iter() {
+            // See [crate::types::Ty::FnPtr]
+            let tys = match sig.inputs_and_output().no_bound_vars() {
+                Some(tys) => tys,
+                None => {
+                    ctx.span_err(
+                        Code::HigherRankedFnPtr,
+                        *span,
+                        "Function pointer types which quantify over regions are not supported",
+                    );
+                    return Err(());
+                }
+            };
+            for param_ty in tys.iter() {
                 explore_mir_ty(ctx, stack.clone(), decls, span, ty_deps, &param_ty)?;
             }
             Ok(())
         }
 
-        TyKind::Dynamic(_, _, _) => {
-            // A trait object
+        TyKind::Dynamic(preds, _, _) => {
+            // A trait object: we register the methods of its principal trait,
+            // so that the trait gets translated (see [crate::rust_to_local_ids])
+            // and the virtual calls can refer to its methods.
             trace!("Dynamic");
-            trace!("Patch");
+            let trait_id = match preds.principal_def_id() {
+                Some(trait_id) => trait_id,
+                None => {
+                    ctx.span_err(
+                        Code::TraitObjectWithoutPrincipal,
+                        *span,
+                        "Trait objects without a principal trait are not supported",
+                    );
+                    return Err(());
+                }
+            };
+            for (_, method_id) in translate_traits::get_methods(ctx.rustc, trait_id) {
+                let name = function_def_id_to_name(ctx.rustc, method_id);
+                explore_dependency_item(
+                    ctx,
+                    stack.clone(),
+                    decls,
+                    method_id,
+                    DeclKind::Fun,
+                    &name,
+                )?;
+            }
             Ok(())
         }
-        TyKind::Closure(_, _) => {
+        TyKind::Closure(id, _) => {
             trace!("Closure");
-            trace!("Patch");
-            Ok(())
+            // A closure is translated to a function (its body) and to a type
+            // (its environment): see [crate::closures]. We only register the
+            // function here, the type is introduced when computing the
+            // translation identifiers.
+            // Note that the closure type appears in the closure body itself:
+            // the function shouldn't depend on itself.
+            if stack.last() == Some(id) {
+                return Ok(());
+            }
+            ty_deps.insert(*id);
+            if decls.knows(id) {
+                trace!("Closure already registered");
+                return Ok(());
+            }
+            // We only see the closures of other crates through the
+            // instantiations of their generic functions
+            match id.as_local() {
+                Some(local_id) => {
+                    explore_local_item_with_body(ctx, stack, decls, local_id, DeclKind::Fun)
+                }
+                None => {
+                    ctx.span_err(
+                        Code::ForeignClosure,
+                        *span,
+                        "Closures defined in other crates are not supported",
+                    );
+                    Err(())
+                }
+            }
         }
 
         TyKind::Generator(_, _, _) | TyKind::GeneratorWitness(_) => {
             trace!("Generator");
-            span_err(ctx.sess, *span, "Generators are not supported");
+            ctx.span_err(Code::Generator, *span, "Generators are not supported");
             Err(())
         }
 
-        TyKind::Alias(_, _) => {
-            unimplemented!();
+        TyKind::Alias(AliasKind::Opaque, alias) => {
+            // An `impl Trait` type (see [crate::types::Ty::Opaque]): we
+            // register the traits of the bounds, like for the trait objects,
+            // and the hidden type.
+            trace!("Opaque");
+            for trait_id in translate_traits::get_opaque_type_bounds(ctx.rustc, alias.def_id) {
+                for (_, method_id) in translate_traits::get_methods(ctx.rustc, trait_id) {
+                    let name = function_def_id_to_name(ctx.rustc, method_id);
+                    explore_dependency_item(
+                        ctx,
+                        stack.clone(),
+                        decls,
+                        method_id,
+                        DeclKind::Fun,
+                        &name,
+                    )?;
+                }
+            }
+            match translate_traits::get_opaque_hidden_type(ctx.rustc, alias) {
+                Some(hidden_ty) => explore_mir_ty(ctx, stack, decls, span, ty_deps, &hidden_ty),
+                None => Ok(()),
+            }
+        }
+        TyKind::Alias(AliasKind::Projection, alias) => {
+            // An associated type (see [crate::types::Ty::TraitProjection]):
+            // we register the trait and its arguments
+            trace!("Projection");
+            explore_trait(
+                ctx,
+                stack.clone(),
+                decls,
+                span,
+                alias.trait_def_id(ctx.rustc),
+            )?;
+            explore_mir_substs(ctx, stack, decls, span, ty_deps, None, &alias.substs)
         }
 
         TyKind::Error(_) => {
             trace!("Error");
-            span_err(
-                ctx.sess,
+            ctx.span_err(
+                Code::IllTyped,
                 *span,
                 "Error type found: the code doesn't typecheck",
             );
@@ -711,7 +922,7 @@ fn explore_mir_ty(
 fn get_fun_from_operand<'tcx>(
     op: &mir::Operand<'tcx>,
 ) -> Option<(DefId, rustc_middle::ty::subst::SubstsRef<'tcx>)> {
-    let fun_ty = op.constant().unwrap().literal.ty();
+    let fun_ty = op.constant()?.literal.ty();
     match fun_ty.kind() {
         TyKind::FnDef(def_id, substs) => Some((*def_id, substs)),
         _ => None,
@@ -766,7 +977,7 @@ fn ty_is_shared_borrow_str(ty: &Ty) -> bool {
 /// This function should be called *only if* we extract the constants at the top
 /// level (typically if we extract the built MIR). Otherwise, the constants
 /// should be evaluated away and inlined in the code.
-fn visit_global_dependencies<'tcx, F: FnMut(DefId)>(
+fn visit_global_dependencies<'tcx, F: FnMut((DefId, SubstsRef<'tcx>))>(
     mir_level: MirLevel,
     block: &'tcx mir::BasicBlockData<'tcx>,
     mut f: F,
@@ -781,7 +992,7 @@ fn visit_global_dependencies<'tcx, F: FnMut(DefId)>(
                 // We should get there only if we don't need to evaluate
                 // the constant: in this case we register its id
                 assert!(extract_constants_at_top_level(mir_level));
-                f(cv.def.did);
+                f((cv.def.did, cv.substs));
             }
             rustc_middle::ty::ConstKind::Expr(_)
             | rustc_middle::ty::ConstKind::Param(_)
@@ -803,12 +1014,8 @@ fn visit_global_dependencies<'tcx, F: FnMut(DefId)>(
                 }
                 mir::interpret::ConstValue::Slice { .. } => {
                     trace!("ConstValue::Slice: ty: {:?}", ty);
-                    // For now we support slices only if they are `&str` -
-                    // we should encounter them only through the error messages
-                    // raised when panicking (because we use visitors, we explore
-                    // *everything*, including the arguments of `std::core::panic`
-                    // for instance).
-                    // TODO: change that.
+                    // For now we support slices only if they are `&str`
+                    // (the string literals)
                     assert!(ty_is_shared_borrow_str(&ty));
                 }
                 mir::interpret::ConstValue::ZeroSized { .. } => {
@@ -820,11 +1027,30 @@ fn visit_global_dependencies<'tcx, F: FnMut(DefId)>(
             // We should get there only if we don't need to evaluate
             // the constant: in this case we register its id
             assert!(extract_constants_at_top_level(mir_level));
-            f(cv.def.did);
+            f((cv.def.did, cv.substs));
         }
     });
 }
 
+/// Visit the constants of a block which are not evaluated, with their
+/// instantiation. In optimized MIR, those are the associated constants whose
+/// implementation we can't determine statically (see
+/// [crate::expressions::OperandConstantValue::TraitConst]).
+fn visit_unevaluated_constants<'tcx, F: FnMut((DefId, SubstsRef<'tcx>))>(
+    block: &'tcx mir::BasicBlockData<'tcx>,
+    mut f: F,
+) {
+    visit_globals(block, &mut |c| match c.literal {
+        mir::ConstantKind::Ty(c) => {
+            if let rustc_middle::ty::ConstKind::Unevaluated(cv) = c.kind() {
+                f((cv.def.did, cv.substs))
+            }
+        }
+        mir::ConstantKind::Unevaluated(cv, _) => f((cv.def.did, cv.substs)),
+        mir::ConstantKind::Val(_, _) => (),
+    });
+}
+
 /// `stack`: see the explanations for [explore_local_hir_item].
 fn explore_dependency_item(
     ctx: &RegisterContext,
@@ -853,6 +1079,10 @@ fn explore_dependency_item(
                     trace!("Impl item");
                     explore_local_hir_impl_item(ctx, stack, decls, impl_item)
                 }
+                rustc_hir::Node::TraitItem(trait_item) => {
+                    trace!("Trait item");
+                    explore_local_hir_trait_item(ctx, stack, decls, trait_item)
+                }
                 _ => {
                     unreachable!();
                 }
@@ -879,31 +1109,78 @@ fn explore_body(
 
     trace!("Body: {:?}", body);
 
-    // Visit the global dependencies if the MIR is not optimized.
-    if extract_constants_at_top_level(ctx.mir_level) {
-        // TODO: For now the order of dependencies export depend on the order
-        // in which they are discovered. By storing their metadata, we would be
-        // able to order them properly, without depending on the visit ordering.
+    // Check the loop invariants (see [crate::contracts]). We only need the
+    // names of the variables here, not their identifiers.
+    let vars: Vec<(String, VarId::Id)> = body
+        .var_debug_info
+        .iter()
+        .enumerate()
+        .map(|(i, info)| (info.name.to_ident_string(), VarId::Id::new(i)))
+        .collect();
+    if let Err((span, msg)) = contracts::get_loop_invariants(ctx.rustc, def_id, &vars) {
+        ctx.span_err(Code::IllFormedContract, span, &msg);
+        return Err(());
+    }
+
+    // Visit the global dependencies if the MIR is not optimized, and the
+    // associated constants we can't resolve.
+    // TODO: For now the order of dependencies export depend on the order
+    // in which they are discovered. By storing their metadata, we would be
+    // able to order them properly, without depending on the visit ordering.
+    let mut explore_constant = |(id, substs)| -> Result<()> {
+        // The associated constants whose implementation we can't determine
+        // statically are not evaluated, even in optimized MIR (see
+        // [crate::expressions::OperandConstantValue::TraitConst]): we register
+        // the trait and its arguments.
+        let id = match translate_traits::resolve_trait_const(
+            ctx.rustc,
+            def_id.to_def_id(),
+            id,
+            substs,
+        ) {
+            Some(id) => id,
+            None => {
+                let trait_id = ctx.rustc.trait_of_item(id).unwrap();
+                explore_trait(ctx, stack.clone(), decls, &body.span, trait_id)?;
+                return explore_mir_substs(
+                    ctx,
+                    stack.clone(),
+                    decls,
+                    &body.span,
+                    deps,
+                    None,
+                    &substs,
+                );
+            }
+        };
+
         // Avoid registering globals in optimized MIR (they will be inlined).
-        for b in body.basic_blocks.iter() {
-            propagate_error(
-                |f| visit_global_dependencies(ctx.mir_level, b, f),
-                |id| {
-                    let name = global_def_id_to_name(ctx.rustc, id);
+        if !extract_constants_at_top_level(ctx.mir_level) {
+            return Ok(());
+        }
 
-                    if is_primitive_decl(DeclKind::Global, id, &name) {
-                        return Ok(());
-                    }
-                    if !deps.insert_if_absent(id) {
-                        return Ok(());
-                    }
+        let name = global_def_id_to_name(ctx.rustc, id);
 
-                    trace!("added constant dependency {:?} -> {}", def_id, name);
-                    // The stack already contains the id of the body owner: no
-                    // need to update it.
-                    explore_dependency_item(ctx, stack.clone(), decls, id, DeclKind::Global, &name)
-                },
+        if is_primitive_decl(DeclKind::Global, id, &name) {
+            return Ok(());
+        }
+        if !deps.insert_if_absent(id) {
+            return Ok(());
+        }
+
+        trace!("added constant dependency {:?} -> {}", def_id, name);
+        // The stack already contains the id of the body owner: no
+        // need to update it.
+        explore_dependency_item(ctx, stack.clone(), decls, id, DeclKind::Global, &name)
+    };
+    for b in body.basic_blocks.iter() {
+        if extract_constants_at_top_level(ctx.mir_level) {
+            propagate_error(
+                |f| visit_global_dependencies(ctx.mir_level, b, f),
+                &mut explore_constant,
             )?;
+        } else {
+            propagate_error(|f| visit_unevaluated_constants(b, f), &mut explore_constant)?;
         }
     }
 
@@ -932,8 +1209,39 @@ fn explore_body(
             // defined).
             decls.register_file_from_span(ctx, statement.source_info.span);
             match &statement.kind {
-                mir::StatementKind::Assign(_)
-                | mir::StatementKind::FakeRead(_)
+                mir::StatementKind::Assign(assign) => {
+                    // The functions whose address is taken: see
+                    // [crate::expressions::Rvalue::FnPtr]
+                    if let mir::Rvalue::Cast(
+                        mir::CastKind::Pointer(PointerCast::ReifyFnPointer),
+                        op,
+                        _,
+                    ) = &assign.1
+                    {
+                        let (fid, substs) = get_fun_from_operand(op).unwrap();
+                        let name = function_def_id_to_name(ctx.rustc, fid);
+                        if substs.types().next().is_some()
+                            || is_primitive_decl(DeclKind::Fun, fid, &name)
+                        {
+                            ctx.span_err(
+                                Code::FnPointer,
+                                statement.source_info.span,
+                                "Only the pointers to non-primitive, monomorphic functions are supported",
+                            );
+                            return Err(());
+                        }
+                        deps.insert(fid);
+                        explore_dependency_item(
+                            ctx,
+                            stack.clone(),
+                            decls,
+                            fid,
+                            DeclKind::Fun,
+                            &name,
+                        )?;
+                    }
+                }
+                mir::StatementKind::FakeRead(_)
                 | mir::StatementKind::SetDiscriminant {
                     place: _,
                     variant_index: _,
@@ -1008,33 +1316,82 @@ fn explore_body(
                 trace!("terminator: Call\n{:?}", &terminator);
                 trace!("terminator:Call:func: {:?}", func);
 
-                let (fid, substs) = get_fun_from_operand(func).expect("Expected a function call");
+                // The calls through function pointers: the types of the
+                // arguments are covered by the type of the pointer
+                let (fid, substs) = match get_fun_from_operand(func) {
+                    Some(fun) => fun,
+                    None => {
+                        let ty = func.ty(&body.local_decls, ctx.rustc);
+                        explore_mir_ty(ctx, stack.clone(), decls, fn_span, deps, &ty)?;
+                        continue;
+                    }
+                };
                 trace!("terminator:Call:fid {:?}", fid);
 
+                // The calls to closures become calls to the functions we
+                // generate for their bodies: we don't register the methods of
+                // the `Fn` traits. The closure itself is registered when we
+                // explore the type of the first argument (the environment).
+                if closures::get_closure_call(ctx.rustc, fid, substs).is_some() {
+                    for a in args.iter() {
+                        let ty = a.ty(&body.local_decls, ctx.rustc);
+                        explore_mir_ty(ctx, stack.clone(), decls, fn_span, deps, &ty)?;
+                    }
+                    continue;
+                }
+
                 let name = function_def_id_to_name(ctx.rustc, fid);
                 trace!("called function: name: {:?}", name);
 
                 // We may need to filter the types and arguments, if the type
                 // is considered primitive
-                let (used_types, used_args, is_prim) = if fid.is_local() {
-                    // We probably do not need to check if the function is local...
-                    (Option::None, Option::None, false)
-                } else {
-                    match assumed::function_to_info(&name) {
-                        Option::Some(used) => {
-                            // The function is primitive
-                            (
-                                Option::Some(used.used_type_params),
-                                Option::Some(used.used_args),
-                                true,
-                            )
-                        }
-                        Option::None => {
-                            // The function is non-primitive (i.e., external)
-                            (Option::None, Option::None, false)
+                let (used_types, used_args, is_prim) =
+                    if translate_traits::is_box_from_call(ctx.rustc, fid, substs) {
+                        // `<Box<T> as From<T>>::from` is translated like `Box::new::<T>`
+                        (Option::Some(vec![false, true]), Option::None, true)
+                    } else if translate_traits::is_rc_clone_call(ctx.rustc, fid, substs) {
+                        // `<Rc<T> as Clone>::clone` is primitive: we don't
+                        // resolve it to the implementation
+                        (Option::Some(vec![true]), Option::None, true)
+                    } else if fid.is_local()
+                        || translate_traits::is_local_iterator_call(ctx.rustc, fid, substs)
+                    {
+                        // The local functions, and the iterator methods
+                        // implemented for local types, are not primitive
+                        (Option::None, Option::None, false)
+                    } else {
+                        match assumed::function_to_info(&name) {
+                            Option::Some(used) => {
+                                // The function is primitive
+                                (
+                                    Option::Some(used.used_type_params),
+                                    Option::Some(used.used_args),
+                                    true,
+                                )
+                            }
+                            Option::None => {
+                                // The function is non-primitive (i.e., external)
+                                (Option::None, Option::None, false)
+                            }
                         }
-                    }
+                    };
+
+                // Resolve the calls to trait methods, so that we register the
+                // methods of the implementations rather than the methods of
+                // the traits. Note that the primitive functions are identified
+                // by the names of the trait methods (`Deref::deref`, etc.):
+                // we must do this after the check above.
+                let (fid, substs) = if is_prim {
+                    (fid, substs)
+                } else {
+                    translate_traits::resolve_trait_method(
+                        ctx.rustc,
+                        def_id.to_def_id(),
+                        fid,
+                        substs,
+                    )
                 };
+                let name = function_def_id_to_name(ctx.rustc, fid);
 
                 // Add this function to the list of dependencies, only if
                 // it is non-primitive
@@ -1103,16 +1460,16 @@ fn explore_body(
                 drop: _,
             } => {
                 trace!("terminator: Yield");
-                span_err(
-                    ctx.sess,
+                ctx.span_err(
+                    Code::Generator,
                     terminator.source_info.span,
                     "Yield is not supported",
                 );
             }
             mir::TerminatorKind::GeneratorDrop => {
                 trace!("terminator: GeneratorDrop");
-                span_err(
-                    ctx.sess,
+                ctx.span_err(
+                    Code::Generator,
                     terminator.source_info.span,
                     "Generators are not supported",
                 );
@@ -1126,10 +1483,12 @@ fn explore_body(
                 cleanup: _,
             } => {
                 trace!("terminator: InlineASM");
-                span_err(
-                    ctx.sess,
+                // We don't reject the function: we approximate the assembly
+                // code during the translation (see [crate::gast::PrecisionLoss])
+                ctx.span_warn(
+                    Code::InlineAsm,
                     terminator.source_info.span,
-                    "Inline ASM is not supported",
+                    "Inline ASM is not supported: it will be ignored",
                 );
             }
         }
@@ -1152,9 +1511,22 @@ fn explore_local_item_with_body(
     let mut stack = stack;
     stack.push_back(local_id.to_def_id());
 
+    // Check the contract of the function (see [crate::contracts])
+    if kind == DeclKind::Fun {
+        if let Err((span, msg)) = contracts::get_fun_contract(ctx.rustc, local_id.to_def_id()) {
+            ctx.span_err(Code::IllFormedContract, span, &msg);
+            return Err(());
+        }
+    }
+
     decls.register_local_declaration(ctx, &stack, local_id, kind, |decls| {
         let mut deps = DeclDependencies::new();
         explore_body(ctx, stack.clone(), decls, local_id, &mut deps)?;
+        if kind == DeclKind::Fun {
+            let id = local_id.to_def_id();
+            let span = ctx.rustc.def_span(id);
+            explore_trait_clauses(ctx, stack.clone(), decls, &span, &mut deps, id)?;
+        }
         Ok(deps)
     })
 }
@@ -1194,6 +1566,8 @@ fn explore_local_hir_item(
     // Because of this, we need the following check: if the item is a "top"
     // item (not an item transitively reachable from an item which is not
     // opaque) and inside an opaque module (or sub-module), we ignore it.
+    // Similarly, we ignore the top items filtered out by the user (see the
+    // `--include` and `--exclude` options).
     if top_item {
         match hir_item_to_name(ctx.rustc, item) {
             Option::None => {
@@ -1201,7 +1575,9 @@ fn explore_local_hir_item(
                 return Ok(());
             }
             Option::Some(item_name) => {
-                if ctx.crate_info.has_opaque_decl(&item_name) {
+                if ctx.crate_info.has_opaque_decl(&item_name)
+                    || ctx.crate_info.is_filtered_out(&item_name)
+                {
                     return Ok(());
                 }
             }
@@ -1216,8 +1592,7 @@ fn explore_local_hir_item(
             Ok(())
         }
         ItemKind::OpaqueTy(_) => unimplemented!(),
-        ItemKind::Union(_, _) => unimplemented!(),
-        ItemKind::Enum(_, _) | ItemKind::Struct(_, _) => {
+        ItemKind::Enum(_, _) | ItemKind::Struct(_, _) | ItemKind::Union(_, _) => {
             explore_local_hir_type_item(ctx, stack, decls, item, def_id)
         }
         ItemKind::Fn(_, _, _) => explore_local_item_with_body(
@@ -1262,6 +1637,20 @@ fn explore_local_hir_item(
             }
             Ok(())
         }
+        ItemKind::Trait(_, _, _, _, trait_item_refs) => {
+            trace!("trait");
+            // Update the stack
+            let mut stack = stack;
+            stack.push_back(def_id);
+
+            // Explore the methods
+            let hir_map = ctx.rustc.hir();
+            for trait_item_ref in *trait_item_refs {
+                let trait_item = hir_map.trait_item(trait_item_ref.id);
+                explore_local_hir_trait_item(ctx, stack.clone(), decls, trait_item)?;
+            }
+            Ok(())
+        }
         ItemKind::Use(_, _) => {
             // Ignore
             trace!("use");
@@ -1293,10 +1682,14 @@ fn explore_local_hir_item(
                 stack.push_back(def_id);
 
                 let hir_map = ctx.rustc.hir();
+                // If the module is a top item, so are its items: this way
+                // we filter them (see the `--include` and `--exclude`
+                // options). Note that the items of a module which is not
+                // opaque can't be in an opaque module.
                 for item_id in module.item_ids {
                     // Lookup and register the item
                     let item = hir_map.item(*item_id);
-                    explore_local_hir_item(ctx, stack.clone(), decls, false, item)?;
+                    explore_local_hir_item(ctx, stack.clone(), decls, top_item, item)?;
                 }
                 Ok(())
             }
@@ -1327,10 +1720,26 @@ fn explore_local_hir_impl_item(
 
     // Match on the impl item kind
     match &impl_item.kind {
-        ImplItemKind::Const(_, _) => Ok(()), // patch
+        ImplItemKind::Const(_, _) => {
+            // We translate the associated constants like the other globals,
+            // if they don't depend on the parameters of the implementation:
+            // the references to the other ones are translated to
+            // [crate::expressions::OperandConstantValue::TraitConst] (see
+            // [translate_traits::resolve_trait_const]).
+            if extract_constants_at_top_level(ctx.mir_level)
+                && ctx.rustc.generics_of(def_id).count() == 0
+            {
+                let local_id = def_id.as_local().unwrap();
+                explore_local_item_with_body(ctx, stack, decls, local_id, DeclKind::Global)
+            } else {
+                Ok(())
+            }
+        }
         ImplItemKind::Type(_) => {
-            // Note sure what to do with associated types yet
-            unimplemented!();
+            // We refer to the associated types through projections (see
+            // [crate::types::Ty::TraitProjection]): we don't translate their
+            // definitions
+            Ok(())
         }
         ImplItemKind::Fn(_, _) => {
             let local_id = impl_item.owner_id.to_def_id().as_local().unwrap();
@@ -1339,18 +1748,56 @@ fn explore_local_hir_impl_item(
     }
 }
 
+/// Explore a trait item (an item defined in a `trait` block).
+///
+/// The required methods don't have a body: we register them as opaque
+/// functions. We call them when we can't statically resolve a call to a trait
+/// method (typically, because the `Self` type is a type parameter).
+///
+/// `stack`: see the explanations for [explore_local_hir_item].
+fn explore_local_hir_trait_item(
+    ctx: &RegisterContext,
+    stack: Vector<DefId>,
+    decls: &mut DeclarationsRegister,
+    trait_item: &TraitItem,
+) -> Result<()> {
+    // Check if the item has already been registered
+    let def_id = trait_item.owner_id.to_def_id();
+    if decls.knows(&def_id) {
+        return Ok(());
+    }
+
+    match &trait_item.kind {
+        // Same as for the impl items. Note that the constants declared in a
+        // trait depend on the `Self` parameter: we always refer to them through
+        // [crate::expressions::OperandConstantValue::TraitConst].
+        TraitItemKind::Const(_, _) | TraitItemKind::Type(_, _) => Ok(()),
+        TraitItemKind::Fn(_, TraitFn::Required(_)) => {
+            let name = function_def_id_to_name(ctx.rustc, def_id);
+            decls.register_opaque_declaration(ctx, &stack, def_id, DeclKind::Fun, &name);
+            Ok(())
+        }
+        TraitItemKind::Fn(_, TraitFn::Provided(_)) => {
+            let local_id = def_id.as_local().unwrap();
+            explore_local_item_with_body(ctx, stack, decls, local_id, DeclKind::Fun)
+        }
+    }
+}
+
 /// General function to register the declarations in a crate.
 pub fn explore_crate(
     crate_info: &CrateInfo,
     sess: &Session,
     tcx: TyCtxt,
     mir_level: MirLevel,
+    diagnostics: &Diagnostics,
 ) -> Result<(HashMap<FileName, FileInfo>, RegisteredDeclarations)> {
     let ctx = RegisterContext {
         rustc: tcx,
         crate_info,
         sess,
         mir_level,
+        diagnostics,
     };
     let mut decls = DeclarationsRegister::new();
 
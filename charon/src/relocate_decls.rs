@@ -0,0 +1,307 @@
+//! Relocate the declarations of a crate which is merged into another crate
+//! (see [crate::rust_to_local_ids::merge_ordered_decls]).
+//!
+//! The declarations of the merged crate get new identifiers: we update their
+//! own identifiers, and the identifiers of the declarations they refer to, in
+//! the types, the signatures and the bodies. The files get new identifiers
+//! too: we also update the spans.
+
+#![allow(dead_code)]
+use crate::expressions::*;
+use crate::llbc_ast::{Call, ExprBody, FunDecl, GlobalDecl, Statement, Switch};
+use crate::llbc_visitor::{
+    walk_call_mut, walk_operand_mut, walk_place_mut, walk_rvalue_mut, walk_statement_mut,
+    walk_switch_mut, AstMutVisitor,
+};
+use crate::meta::{ExprMetas, FileId, Meta};
+use crate::rust_to_local_ids::DeclsRelocation;
+use crate::types::*;
+use crate::ullbc_ast::{
+    FunDeclId, FunId, FunSig, GlobalDeclId, PrecisionLoss, TraitDecl, TraitDeclId, TraitImpl,
+    TraitImplId, UnsafeOperation,
+};
+
+impl DeclsRelocation {
+    fn type_id(&self, id: TypeDeclId::Id) -> TypeDeclId::Id {
+        *self.types.get(&id).unwrap()
+    }
+
+    fn fun_id(&self, id: FunDeclId::Id) -> FunDeclId::Id {
+        *self.funs.get(&id).unwrap()
+    }
+
+    fn global_id(&self, id: GlobalDeclId::Id) -> GlobalDeclId::Id {
+        *self.globals.get(&id).unwrap()
+    }
+
+    fn trait_id(&self, id: TraitDeclId::Id) -> TraitDeclId::Id {
+        *self.traits.get(&id).unwrap()
+    }
+
+    fn trait_impl_id(&self, id: TraitImplId::Id) -> TraitImplId::Id {
+        *self.trait_impls.get(&id).unwrap()
+    }
+
+    fn file_id(&self, id: FileId::Id) -> FileId::Id {
+        *self.files.get(&id).unwrap()
+    }
+
+    fn meta(&self, meta: &mut Meta) {
+        meta.span.file_id = self.file_id(meta.span.file_id);
+        if let Some(span) = &mut meta.generated_from_span {
+            span.file_id = self.file_id(span.file_id);
+        }
+    }
+
+    fn expr_metas(&self, metas: &mut ExprMetas) {
+        for meta in metas
+            .expr
+            .iter_mut()
+            .chain(metas.operands.iter_mut().flatten())
+        {
+            self.meta(meta)
+        }
+    }
+
+    fn ty<R: Clone + Eq>(&self, ty: &mut Ty<R>) {
+        match ty {
+            Ty::Adt(id, _, args) => {
+                if let TypeId::Adt(id) = id {
+                    *id = self.type_id(*id);
+                }
+                for ty in args.iter_mut() {
+                    self.ty(ty)
+                }
+            }
+            Ty::Array(ty, _) | Ty::Slice(ty) | Ty::Ref(_, ty, _) | Ty::RawPtr(ty, _) => self.ty(ty),
+            Ty::FnPtr(inputs, output) => {
+                for ty in inputs.iter_mut() {
+                    self.ty(ty)
+                }
+                self.ty(output)
+            }
+            Ty::DynTrait(id) => *id = self.trait_id(*id),
+            Ty::Opaque(hidden_ty, bounds) => {
+                if let Some(ty) = hidden_ty {
+                    self.ty(ty)
+                }
+                for id in bounds.iter_mut() {
+                    *id = self.trait_id(*id)
+                }
+            }
+            Ty::TraitProjection(id, _, args, _) => {
+                *id = self.trait_id(*id);
+                for ty in args.iter_mut() {
+                    self.ty(ty)
+                }
+            }
+            Ty::TypeVar(_)
+            | Ty::Bool
+            | Ty::Char
+            | Ty::Never
+            | Ty::Integer(_)
+            | Ty::Float(_)
+            | Ty::Str
+            | Ty::ConstGeneric(_) => (),
+        }
+    }
+
+    fn trait_clause(&self, clause: &mut TraitClause) {
+        clause.trait_id = self.trait_id(clause.trait_id);
+        for ty in clause.type_args.iter_mut() {
+            self.ty(ty)
+        }
+    }
+
+    fn sig(&self, sig: &mut FunSig) {
+        for clause in sig.trait_clauses.iter_mut() {
+            self.trait_clause(clause)
+        }
+        for ty in sig.inputs.iter_mut() {
+            self.ty(ty)
+        }
+        self.ty(&mut sig.output)
+    }
+
+    fn constant(&self, value: &mut OperandConstantValue) {
+        match value {
+            OperandConstantValue::ConstantId(id) | OperandConstantValue::StaticId(id) => {
+                *id = self.global_id(*id)
+            }
+            OperandConstantValue::Adt(_, values) | OperandConstantValue::Array(values) => {
+                for value in values.iter_mut() {
+                    self.constant(value)
+                }
+            }
+            OperandConstantValue::TraitConst(id, args, _) => {
+                *id = self.trait_id(*id);
+                for ty in args.iter_mut() {
+                    self.ty(ty)
+                }
+            }
+            OperandConstantValue::ZST(ty) => self.ty(ty),
+            OperandConstantValue::PrimitiveValue(_) | OperandConstantValue::Bytes(_) => (),
+        }
+    }
+
+    fn body(&mut self, body: &mut ExprBody) {
+        self.meta(&mut body.meta);
+        for var in body.locals.iter_mut() {
+            self.ty(&mut var.ty)
+        }
+        self.visit_statement_mut(&mut body.body)
+    }
+
+    fn fields(&self, fields: &mut FieldId::Vector<Field>) {
+        for field in fields.iter_mut() {
+            self.meta(&mut field.meta);
+            self.ty(&mut field.ty)
+        }
+    }
+
+    pub fn relocate_type_decl(&self, decl: &mut TypeDecl) {
+        decl.def_id = self.type_id(decl.def_id);
+        self.meta(&mut decl.meta);
+        for clause in decl.trait_clauses.iter_mut() {
+            self.trait_clause(clause)
+        }
+        match &mut decl.kind {
+            TypeDeclKind::Struct(fields) | TypeDeclKind::Union(fields) => self.fields(fields),
+            TypeDeclKind::Enum(variants) => {
+                for variant in variants.iter_mut() {
+                    self.meta(&mut variant.meta);
+                    self.fields(&mut variant.fields)
+                }
+            }
+            TypeDeclKind::Opaque => (),
+        }
+    }
+
+    pub fn relocate_fun_decl(&mut self, decl: &mut FunDecl) {
+        decl.def_id = self.fun_id(decl.def_id);
+        self.meta(&mut decl.meta);
+        self.sig(&mut decl.signature);
+        if let Some(body) = &mut decl.body {
+            self.body(body)
+        }
+        for loss in decl.precision_losses.iter_mut() {
+            match loss {
+                PrecisionLoss::InlineAsm(meta) => self.meta(meta),
+            }
+        }
+        for op in decl.unsafe_operations.iter_mut() {
+            match op {
+                UnsafeOperation::UnionFieldRead(meta) => self.meta(meta),
+            }
+        }
+    }
+
+    pub fn relocate_global_decl(&mut self, decl: &mut GlobalDecl) {
+        decl.def_id = self.global_id(decl.def_id);
+        self.meta(&mut decl.meta);
+        self.ty(&mut decl.ty);
+        if let Some(body) = &mut decl.body {
+            self.body(body)
+        }
+    }
+
+    pub fn relocate_trait_decl(&self, decl: &mut TraitDecl) {
+        decl.def_id = self.trait_id(decl.def_id);
+        self.meta(&mut decl.meta);
+        for (_, id) in decl.methods.iter_mut() {
+            *id = id.map(|id| self.fun_id(id));
+        }
+    }
+
+    pub fn relocate_trait_impl(&self, decl: &mut TraitImpl) {
+        decl.def_id = self.trait_impl_id(decl.def_id);
+        self.meta(&mut decl.meta);
+        decl.trait_id = self.trait_id(decl.trait_id);
+        decl.self_type = decl.self_type.map(|id| self.type_id(id));
+        for (_, id) in decl.methods.iter_mut() {
+            *id = self.fun_id(*id);
+        }
+    }
+}
+
+impl AstMutVisitor for DeclsRelocation {
+    fn visit_statement_mut(&mut self, st: &mut Statement) {
+        self.meta(&mut st.meta);
+        self.expr_metas(&mut st.expr_metas);
+        walk_statement_mut(self, st)
+    }
+
+    fn visit_switch_mut(&mut self, switch: &mut Switch) {
+        if let Switch::Match(_, _, _, Some(provenance)) = switch {
+            for arm in provenance.iter_mut() {
+                if let Some(meta) = &mut arm.pattern_meta {
+                    self.meta(meta)
+                }
+            }
+        }
+        walk_switch_mut(self, switch)
+    }
+
+    fn visit_call_mut(&mut self, call: &mut Call) {
+        match &mut call.func {
+            FunId::Regular(id) => *id = self.fun_id(*id),
+            FunId::TraitMethod(impl_id, id) => {
+                *impl_id = self.trait_impl_id(*impl_id);
+                *id = self.fun_id(*id);
+            }
+            FunId::Virtual(trait_id, id) => {
+                *trait_id = self.trait_id(*trait_id);
+                *id = self.fun_id(*id);
+            }
+            FunId::Indirect(_) | FunId::Assumed(_) => (),
+        }
+        for ty in call.type_args.iter_mut() {
+            self.ty(ty)
+        }
+        if let Some(sig) = &mut call.sig {
+            self.sig(sig)
+        }
+        walk_call_mut(self, call)
+    }
+
+    fn visit_rvalue_mut(&mut self, rv: &mut Rvalue) {
+        match rv {
+            Rvalue::Global(id) | Rvalue::GlobalRef(id, _) => *id = self.global_id(*id),
+            Rvalue::FnPtr(id) | Rvalue::Aggregate(AggregateKind::Closure(id, _), _) => {
+                *id = self.fun_id(*id)
+            }
+            Rvalue::Aggregate(AggregateKind::Adt(id, _, _, args), _) => {
+                *id = self.type_id(*id);
+                for ty in args.iter_mut() {
+                    self.ty(ty)
+                }
+            }
+            Rvalue::Aggregate(AggregateKind::Option(_, ty), _) => self.ty(ty),
+            Rvalue::Unsize(_, ty1, ty2) | Rvalue::Cast(_, _, ty1, ty2) => {
+                self.ty(ty1);
+                self.ty(ty2);
+            }
+            _ => (),
+        }
+        walk_rvalue_mut(self, rv)
+    }
+
+    fn visit_operand_mut(&mut self, op: &mut Operand) {
+        if let Operand::Const(ty, value) = op {
+            self.ty(ty);
+            self.constant(value);
+        }
+        walk_operand_mut(self, op)
+    }
+
+    fn visit_place_mut(&mut self, p: &mut Place) {
+        for pe in p.projection.iter_mut() {
+            if let ProjectionElem::Field(FieldProjKind::Adt(id, _), _)
+            | ProjectionElem::Field(FieldProjKind::Union(id), _) = pe
+            {
+                *id = self.type_id(*id);
+            }
+        }
+        walk_place_mut(self, p)
+    }
+}
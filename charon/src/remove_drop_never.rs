@@ -3,17 +3,13 @@
 //! `drop(v)` where `v` has type `Never` (it can happen - this module does the
 //! filtering). Then, we filter the unused variables ([crate::remove_unused_locals]).
 
-use take_mut::take;
-
-use crate::llbc_ast::{
-    transform_statements, CtxNames, FunDecls, GlobalDecls, RawStatement, Statement, Var,
-};
-use crate::ullbc_ast::{iter_function_bodies, iter_global_bodies};
+use crate::llbc_ast::{CtxNames, FunDecls, GlobalDecls, RawStatement, Statement, Var};
+use crate::ullbc_ast::par_iter_bodies;
 use crate::values::*;
 
 /// Filter the statement by replacing it with `Nop` if it is a `Drop(x)` where
 /// `x` has type `Never`. Otherwise leave it unchanged.
-fn transform_st(locals: &VarId::Vector<Var>, st: Statement) -> Statement {
+fn transform_st(locals: &VarId::Vector<Var>, st: &mut Statement) {
     // Shall we filter the statement?
     let filter = match &st.content {
         RawStatement::Drop(p) => {
@@ -29,15 +25,13 @@ fn transform_st(locals: &VarId::Vector<Var>, st: Statement) -> Statement {
 
     // If we filter the statement, we simply replace it with `nop`
     if filter {
-        Statement::new(st.meta, RawStatement::Nop)
-    } else {
-        st
+        st.content = RawStatement::Nop;
     }
 }
 
 /// `fmt_ctx` is used for pretty-printing purposes.
 pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
-    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+    par_iter_bodies(funs, globals, |name, b| {
         trace!(
             "# About to remove drops of variables with type ! in decl: {name}:\n{}",
             b.fmt_with_ctx_names(fmt_ctx)
@@ -45,9 +39,6 @@ pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut Glob
 
         let locals = &b.locals;
 
-        // Compute the set of local variables
-        take(&mut b.body, |b| {
-            transform_statements(&mut |st| transform_st(locals, st), b)
-        });
-    }
+        b.body.transform(&mut |st| transform_st(locals, st));
+    });
 }
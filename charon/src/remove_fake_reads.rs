@@ -0,0 +1,98 @@
+//! Remove the fake reads which are immediately followed by a `StorageDead` of
+//! the same local, like below:
+//! ```text
+//! fake_read x;
+//! storage_dead x;
+//! ```
+//! Nothing happens to the local between the two statements: the fake read
+//! doesn't constrain anything, and the local dies right away. Those pairs are
+//! pure noise for most backends, so we remove them before reconstructing the
+//! control-flow.
+//!
+//! Note that this pass must not be applied if the consumer tracks the causes
+//! of the fake reads, or the storage liveness of the locals (see the
+//! `--keep-fake-reads` option).
+
+use crate::ullbc_ast::{
+    iter_function_bodies, iter_global_bodies, CtxNames, FunDecls, GlobalDecls, RawStatement,
+    Statement,
+};
+
+/// Return true if the two statements are of the shape:
+/// `fake_read p; storage_dead x` where `p` is a place of the local `x`.
+fn is_redundant_pair(st1: &Statement, st2: &Statement) -> bool {
+    match (&st1.content, &st2.content) {
+        (RawStatement::FakeRead(p), RawStatement::StorageDead(var_id)) => p.var_id == *var_id,
+        _ => false,
+    }
+}
+
+/// Remove the redundant pairs from the statements of a block
+fn remove_in_statements(statements: &mut Vec<Statement>) {
+    let mut nstatements = Vec::with_capacity(statements.len());
+    let mut it = std::mem::take(statements).into_iter().peekable();
+    while let Some(st) = it.next() {
+        match it.peek() {
+            Some(next) if is_redundant_pair(&st, next) => {
+                // Skip the `StorageDead`
+                let _ = it.next();
+            }
+            _ => nstatements.push(st),
+        }
+    }
+    *statements = nstatements;
+}
+
+pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
+    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+        trace!(
+            "# About to remove the redundant fake reads in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+
+        for block in b.body.iter_mut() {
+            remove_in_statements(&mut block.statements);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::*;
+    use crate::meta::Meta;
+    use crate::remove_fake_reads::*;
+    use crate::values::VarId;
+
+    #[test]
+    fn test_remove_fake_read_before_storage_dead() {
+        let x = VarId::Id::new(1);
+        let y = VarId::Id::new(2);
+        let z = VarId::Id::new(3);
+        let mk = |content| Statement::new(Meta::dummy(), content);
+
+        // ```
+        // fake_read x;
+        // storage_dead x;
+        // fake_read y;
+        // z := copy y;
+        // storage_dead y;
+        // ```
+        let mut statements = vec![
+            mk(RawStatement::FakeRead(Place::new(x))),
+            mk(RawStatement::StorageDead(x)),
+            mk(RawStatement::FakeRead(Place::new(y))),
+            mk(RawStatement::Assign(
+                Place::new(z),
+                Rvalue::Use(Operand::Copy(Place::new(y))),
+            )),
+            mk(RawStatement::StorageDead(y)),
+        ];
+        remove_in_statements(&mut statements);
+
+        // Only the first pair is removed: `y` is read in between
+        assert!(statements.len() == 3);
+        assert!(matches!(&statements[0].content, RawStatement::FakeRead(p) if p.var_id == y));
+        assert!(statements[1].content.is_assign());
+        assert!(matches!(statements[2].content, RawStatement::StorageDead(v) if v == y));
+    }
+}
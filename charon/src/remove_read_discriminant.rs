@@ -3,28 +3,86 @@
 //! `drop(v)` where `v` has type `Never` (it can happen - this module does the
 //! filtering). Then, we filter the unused variables ([crate::remove_unused_locals]).
 
-use take_mut::take;
-
+use crate::errors::{PassResult, TransResult, TranslationError};
 use crate::expressions::*;
 use crate::llbc_ast::{
     new_sequence, CtxNames, FunDecls, GlobalDecls, RawStatement, Statement, Switch,
 };
 use crate::meta::combine_meta;
 use crate::types::*;
-use crate::ullbc_ast::{iter_function_bodies, iter_global_bodies};
+use crate::ullbc_ast::par_try_iter_bodies;
+use crate::values::ScalarValue;
 use std::iter::FromIterator;
 
+/// The casts from an enumeration to an integer (`e as u32`) are desugared
+/// to a read of the discriminant followed by a cast:
+/// ```text
+/// dest := @discriminant(e);
+/// x := cast<isize,u32>(move dest);
+/// ```
+/// If `st` is the second statement, return the cast of the enumeration
+/// (`x := cast<isize,u32>(@discriminant(copy e))`, see [CastKind::EnumToInt]).
+fn transform_enum_cast(p: &Place, dest: &Place, st: &Statement) -> Option<RawStatement> {
+    match &st.content {
+        RawStatement::Assign(
+            x,
+            Rvalue::Cast(CastKind::IntToInt, Operand::Move(op_p), src, tgt),
+        ) if op_p == dest => {
+            let rv = Rvalue::Cast(
+                CastKind::EnumToInt,
+                Operand::Copy(p.clone()),
+                src.clone(),
+                tgt.clone(),
+            );
+            Some(RawStatement::Assign(x.clone(), rv))
+        }
+        _ => None,
+    }
+}
+
+/// Check that the statement which follows a read of the discriminant
+/// `dest := @discriminant(p)` is a switch on `dest`, and return its branches
+fn destruct_discriminant_switch(
+    dest: &Place,
+    st: Statement,
+) -> TransResult<(Vec<(Vec<ScalarValue>, Statement)>, Box<Statement>)> {
+    let meta = st.meta;
+    let (op, int_ty, targets, otherwise) = match st.content {
+        RawStatement::Switch(Switch::SwitchInt(op, int_ty, targets, otherwise)) => {
+            (op, int_ty, targets, otherwise)
+        }
+        _ => {
+            let msg = "a read of a discriminant must be followed by a switch or by a cast";
+            return Err(TranslationError::new(meta, msg));
+        }
+    };
+    if !int_ty.is_isize() {
+        let msg = format!("unexpected type for a discriminant: {int_ty:?}");
+        return Err(TranslationError::new(meta, msg));
+    }
+    // The operand should be a [Move] applied to the variable `dest`
+    match &op {
+        Operand::Move(op_p) if op_p.projection.is_empty() && op_p.var_id == dest.var_id => (),
+        _ => {
+            let msg = "the switch doesn't branch on the discriminant we just read";
+            return Err(TranslationError::new(meta, msg));
+        }
+    }
+    Ok((targets, otherwise))
+}
+
 // TODO: don't consume `st`, use mutable borrows
-fn transform_st(st: Statement) -> Statement {
+fn transform_st(st: Statement) -> TransResult<Statement> {
     let content = match st.content {
         RawStatement::Assign(p, rv) => {
             // Check that we never failed to remove a [Discriminant]
             if let Rvalue::Discriminant(_) = &rv {
-                // Should have been filtered
-                unreachable!();
+                let msg = "a read of a discriminant is not followed by a switch or by a cast";
+                return Err(TranslationError::new(st.meta, msg));
             }
             RawStatement::Assign(p, rv)
         }
+        RawStatement::CompoundAssign(p, op, rhs) => RawStatement::CompoundAssign(p, op, rhs),
         RawStatement::FakeRead(p) => RawStatement::FakeRead(p),
         RawStatement::SetDiscriminant(p, vid) => RawStatement::SetDiscriminant(p, vid),
         RawStatement::Drop(p) => RawStatement::Drop(p),
@@ -39,24 +97,34 @@ fn transform_st(st: Statement) -> Statement {
             let switch = match switch {
                 Switch::If(op, st1, st2) => Switch::If(
                     op,
-                    Box::new(transform_st(*st1)),
-                    Box::new(transform_st(*st2)),
+                    Box::new(transform_st(*st1)?),
+                    Box::new(transform_st(*st2)?),
                 ),
                 Switch::SwitchInt(op, int_ty, targets, mut otherwise) => {
-                    let targets =
-                        Vec::from_iter(targets.into_iter().map(|(v, e)| (v, transform_st(e))));
-                    *otherwise = transform_st(*otherwise);
+                    let targets = targets
+                        .into_iter()
+                        .map(|(v, e)| Ok((v, transform_st(e)?)))
+                        .collect::<TransResult<Vec<_>>>()?;
+                    *otherwise = transform_st(*otherwise)?;
                     Switch::SwitchInt(op, int_ty, targets, otherwise)
                 }
-                Switch::Match(_, _, _) => {
-                    // We shouldn't get there: this variant is introduced *during*
-                    // this traversal
-                    unreachable!();
+                Switch::Match(_, _, _, _) => {
+                    // This variant is introduced *during* this traversal
+                    let msg = "unexpected match before the reconstruction of the matches";
+                    return Err(TranslationError::new(st.meta, msg));
                 }
             };
             RawStatement::Switch(switch)
         }
-        RawStatement::Loop(loop_body) => RawStatement::Loop(Box::new(transform_st(*loop_body))),
+        RawStatement::Loop(loop_body, invariants) => {
+            RawStatement::Loop(Box::new(transform_st(*loop_body)?), invariants)
+        }
+        RawStatement::While(_, _, _, _) => {
+            // The `while` loops are introduced at the very end of the
+            // pipeline
+            let msg = "unexpected while loop before the reconstruction of the loops";
+            return Err(TranslationError::new(st.meta, msg));
+        }
         RawStatement::Sequence(st1, st2) => {
             if st1.content.is_assign() {
                 let (_, rv) = st1.content.as_assign();
@@ -65,71 +133,137 @@ fn transform_st(st: Statement) -> Statement {
                     let p = rv.to_discriminant();
 
                     // The destination should be a variable
-                    assert!(dest.projection.is_empty());
+                    if !dest.projection.is_empty() {
+                        let msg = "the discriminant is not read into a variable";
+                        return Err(TranslationError::new(st1.meta, msg));
+                    }
 
-                    // A discriminant read must be immediately followed by a switch int.
-                    // Note that it may be contained in a sequence, of course.
-                    let (meta, switch, st3_opt) = match st2.content {
-                        RawStatement::Sequence(st2, st3) => {
-                            (st2.meta, st2.content.to_switch(), Some(*st3))
-                        }
-                        RawStatement::Switch(switch) => (st2.meta, switch, None),
-                        _ => unreachable!(),
+                    // A discriminant read must be immediately followed by a switch int,
+                    // or by a cast. Note that it may be contained in a sequence, of course.
+                    let st2 = *st2;
+                    let (st2, st3_opt) = match st2.content {
+                        RawStatement::Sequence(st2, st3) => (*st2, Some(*st3)),
+                        content => (Statement::new(st2.meta, content), None),
                     };
-                    let (op, int_ty, targets, otherwise) = switch.to_switch_int();
-                    assert!(int_ty.is_isize());
-                    // The operand should be a [Move] applied to the variable `dest`
-                    let op_p = op.to_move();
-                    assert!(op_p.projection.is_empty() && op_p.var_id == dest.var_id);
+                    if let Some(cast) = transform_enum_cast(&p, &dest, &st2) {
+                        let cast = Statement::new(combine_meta(&st1.meta, &st2.meta), cast);
+                        return Ok(match st3_opt {
+                            Some(st3) => new_sequence(cast, transform_st(st3)?),
+                            None => Statement::new(st.meta, cast.content),
+                        });
+                    }
+                    let meta = st2.meta;
+                    let (targets, otherwise) = destruct_discriminant_switch(&dest, st2)?;
 
-                    let targets = Vec::from_iter(targets.into_iter().map(|(v, e)| {
-                        (
-                            Vec::from_iter(
-                                v.into_iter()
-                                    .map(|x| VariantId::Id::new(*x.as_isize() as usize)),
-                            ),
-                            transform_st(e),
-                        )
-                    }));
-                    let otherwise = Box::new(transform_st(*otherwise));
-                    let switch = RawStatement::Switch(Switch::Match(p, targets, otherwise));
+                    let targets = targets
+                        .into_iter()
+                        .map(|(v, e)| {
+                            let v = v
+                                .into_iter()
+                                .map(|x| VariantId::Id::new(*x.as_isize() as usize));
+                            Ok((Vec::from_iter(v), transform_st(e)?))
+                        })
+                        .collect::<TransResult<Vec<_>>>()?;
+                    let otherwise = Box::new(transform_st(*otherwise)?);
+                    let switch = RawStatement::Switch(Switch::Match(p, targets, otherwise, None));
 
                     // Add the next statement if there is one
                     if let Some(st3) = st3_opt {
                         let meta = combine_meta(&st1.meta, &meta);
-                        let switch = Statement {
-                            meta,
-                            content: switch,
-                        };
+                        let switch = Statement::new(meta, switch);
                         new_sequence(switch, st3).content
                     } else {
                         switch
                     }
                 } else {
-                    let st1 = Box::new(transform_st(*st1));
-                    let st2 = Box::new(transform_st(*st2));
+                    let st1 = Box::new(transform_st(*st1)?);
+                    let st2 = Box::new(transform_st(*st2)?);
                     RawStatement::Sequence(st1, st2)
                 }
             } else {
-                let st1 = Box::new(transform_st(*st1));
-                let st2 = Box::new(transform_st(*st2));
+                let st1 = Box::new(transform_st(*st1)?);
+                let st2 = Box::new(transform_st(*st2)?);
                 RawStatement::Sequence(st1, st2)
             }
         }
     };
 
-    Statement::new(st.meta, content)
+    Ok(Statement::new(st.meta, content))
 }
 
 /// `fmt_ctx` is used for pretty-printing purposes.
-pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
-    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+pub fn transform(
+    fmt_ctx: &CtxNames<'_>,
+    funs: &mut FunDecls,
+    globals: &mut GlobalDecls,
+) -> PassResult {
+    let errors = par_try_iter_bodies(funs, globals, |name, b| {
         trace!(
             "# About to remove [ReadDiscriminant] occurrences in decl: {name}:\n{}",
             b.fmt_with_ctx_names(fmt_ctx)
         );
 
-        // Compute the set of local variables
-        take(&mut b.body, transform_st);
+        // If we fail, the driver drops the whole body: we don't need to
+        // restore it
+        let body = std::mem::replace(&mut b.body, Statement::new(b.body.meta, RawStatement::Nop));
+        b.body = transform_st(body)?;
+        Ok(())
+    });
+    errors.into_result()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llbc_ast::chain_statements;
+    use crate::meta::Meta;
+    use crate::remove_read_discriminant::*;
+
+    fn mk(content: RawStatement) -> Statement {
+        Statement::new(Meta::dummy(), content)
+    }
+
+    #[test]
+    fn test_enum_cast() {
+        // `x := e as u32`
+        let (e, dest, x) = (VarId::Id::new(1), VarId::Id::new(2), VarId::Id::new(3));
+        let (src, tgt) = (Ty::Integer(IntegerTy::Isize), Ty::Integer(IntegerTy::U32));
+        let st = chain_statements(
+            vec![
+                mk(RawStatement::Assign(
+                    Place::new(dest),
+                    Rvalue::Discriminant(Place::new(e)),
+                )),
+                mk(RawStatement::Assign(
+                    Place::new(x),
+                    Rvalue::Cast(
+                        CastKind::IntToInt,
+                        Operand::Move(Place::new(dest)),
+                        src.clone(),
+                        tgt.clone(),
+                    ),
+                )),
+            ],
+            mk(RawStatement::Return),
+        );
+        let st = transform_st(st).unwrap();
+        let (st1, st2) = st.content.to_sequence();
+        assert!(st2.content.is_return());
+        let (p, rv) = st1.content.to_assign();
+        assert!(p == Place::new(x));
+        assert!(rv == Rvalue::Cast(CastKind::EnumToInt, Operand::Copy(Place::new(e)), src, tgt));
+    }
+
+    #[test]
+    fn test_discriminant_not_followed_by_switch() {
+        // `dest := @discriminant(e); return`
+        let (e, dest) = (VarId::Id::new(1), VarId::Id::new(2));
+        let st = chain_statements(
+            vec![mk(RawStatement::Assign(
+                Place::new(dest),
+                Rvalue::Discriminant(Place::new(e)),
+            ))],
+            mk(RawStatement::Return),
+        );
+        assert!(transform_st(st).is_err());
     }
 }
@@ -0,0 +1,110 @@
+//! Remove the `SetDiscriminant` statements which are immediately preceded by
+//! the assignment of an aggregate of the same variant to the same place, like
+//! below:
+//! ```text
+//! x := Option::Some(move y);
+//! set_discriminant(x, 1);
+//! ```
+//! Building the aggregate already sets the discriminant: the second statement
+//! has no effect.
+
+use crate::expressions::{AggregateKind, Rvalue};
+use crate::types::VariantId;
+use crate::ullbc_ast::{
+    iter_function_bodies, iter_global_bodies, CtxNames, FunDecls, GlobalDecls, RawStatement,
+    Statement,
+};
+
+/// Return the variant of an enumeration aggregate
+fn get_aggregate_variant(kind: &AggregateKind) -> Option<VariantId::Id> {
+    match kind {
+        AggregateKind::Adt(_, variant_id, _, _) => *variant_id,
+        AggregateKind::Option(variant_id, _) => Some(*variant_id),
+        AggregateKind::Tuple | AggregateKind::Closure(_, _) => None,
+    }
+}
+
+/// Return true if the two statements are of the shape:
+/// `p := Adt::V(...); set_discriminant(p, V)`.
+fn is_redundant_pair(st1: &Statement, st2: &Statement) -> bool {
+    match (&st1.content, &st2.content) {
+        (
+            RawStatement::Assign(p1, Rvalue::Aggregate(kind, _)),
+            RawStatement::SetDiscriminant(p2, v2),
+        ) => p1 == p2 && get_aggregate_variant(kind) == Some(*v2),
+        _ => false,
+    }
+}
+
+/// Remove the redundant discriminant updates from the statements of a block
+fn remove_in_statements(statements: &mut Vec<Statement>) {
+    let mut nstatements: Vec<Statement> = Vec::with_capacity(statements.len());
+    for st in std::mem::take(statements) {
+        match nstatements.last() {
+            Some(prev) if is_redundant_pair(prev, &st) => (),
+            _ => nstatements.push(st),
+        }
+    }
+    *statements = nstatements;
+}
+
+pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
+    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+        trace!(
+            "# About to remove the redundant discriminant updates in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+
+        for block in b.body.iter_mut() {
+            remove_in_statements(&mut block.statements);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::*;
+    use crate::meta::Meta;
+    use crate::remove_redundant_set_discriminant::*;
+    use crate::types::*;
+    use crate::values::VarId;
+
+    #[test]
+    fn test_remove_redundant_set_discriminant() {
+        let x = VarId::Id::new(1);
+        let y = VarId::Id::new(2);
+        let enum_id = TypeDeclId::ZERO;
+        let v0 = VariantId::Id::new(0);
+        let v1 = VariantId::Id::new(1);
+        let mk = |content| Statement::new(Meta::dummy(), content);
+        let mk_aggregate = |variant_id| {
+            Rvalue::Aggregate(
+                AggregateKind::Adt(enum_id, Some(variant_id), Vec::new(), Vec::new()),
+                Vec::new(),
+            )
+        };
+
+        // ```
+        // x := E::V1;
+        // set_discriminant(x, 1);
+        // y := E::V1;
+        // set_discriminant(y, 0);
+        // ```
+        let mut statements = vec![
+            mk(RawStatement::Assign(Place::new(x), mk_aggregate(v1))),
+            mk(RawStatement::SetDiscriminant(Place::new(x), v1)),
+            mk(RawStatement::Assign(Place::new(y), mk_aggregate(v1))),
+            mk(RawStatement::SetDiscriminant(Place::new(y), v0)),
+        ];
+        remove_in_statements(&mut statements);
+
+        // Only the first discriminant update is removed: the second one
+        // changes the variant
+        assert!(statements.len() == 3);
+        assert!(statements[0].content.is_assign());
+        assert!(statements[1].content.is_assign());
+        assert!(
+            matches!(&statements[2].content, RawStatement::SetDiscriminant(p, v) if *p == Place::new(y) && *v == v0)
+        );
+    }
+}
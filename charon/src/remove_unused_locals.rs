@@ -2,202 +2,111 @@
 //! never used in the function bodies.  This is useful to remove the locals with
 //! type `Never`. We actually check that there are no such local variables
 //! remaining afterwards.
+//!
+//! Before doing so, we remove the dead assignments, that is the assignments
+//! to the locals which are never read, and the fake reads of those locals.
+//! The simplifications leave many of them behind, for instance the
+//! temporaries introduced for the checked binary operations:
+//! ```text
+//! tmp := copy x + copy y; // Never read: removed
+//! z := copy x + copy y;
+//! ```
+//! Note that the LLBC doesn't contain the `StorageDead` statements anymore:
+//! only the assignments and the fake reads mention the dead locals. The
+//! evaluation of an rvalue has no side effects in the LLBC (the checks are
+//! explicit assertions), so we can always remove those assignments. We
+//! keep the calls, though, even if we never read their results.
 
-use crate::expressions::*;
 use crate::id_vector::ToUsize;
-use crate::llbc_ast::{CtxNames, FunDecls, GlobalDecls, RawStatement, Statement, Switch};
-use crate::ullbc_ast::{iter_function_bodies, iter_global_bodies, Var};
+use crate::llbc_ast::{
+    transform_statements, CtxNames, ExprBody, FunDecls, GlobalDecls, RawStatement, Statement,
+};
+use crate::llbc_visitor::{walk_statement, AstMutVisitor, AstVisitor};
+use crate::ullbc_ast::{par_iter_bodies, Var};
 use crate::values::*;
 use std::collections::{HashMap, HashSet};
-use std::iter::FromIterator;
 use take_mut::take;
 
-fn compute_used_locals_in_place(locals: &mut HashSet<VarId::Id>, p: &Place) {
-    locals.insert(p.var_id);
-}
+/// Collect the locals used in a statement
+struct UsedLocals<'a>(&'a mut HashSet<VarId::Id>);
 
-fn compute_used_locals_in_operand(locals: &mut HashSet<VarId::Id>, op: &Operand) {
-    match op {
-        Operand::Copy(p) | Operand::Move(p) => compute_used_locals_in_place(locals, p),
-        Operand::Const(_, _) => (),
+impl AstVisitor for UsedLocals<'_> {
+    fn visit_var_id(&mut self, id: &VarId::Id) {
+        self.0.insert(*id);
     }
 }
 
-fn compute_used_locals_in_operands(locals: &mut HashSet<VarId::Id>, ops: &Vec<Operand>) {
-    for op in ops {
-        compute_used_locals_in_operand(locals, op)
-    }
+fn compute_used_locals_in_statement(locals: &mut HashSet<VarId::Id>, st: &Statement) {
+    UsedLocals(locals).visit_statement(st)
 }
 
-fn compute_used_locals_in_rvalue(locals: &mut HashSet<VarId::Id>, rv: &Rvalue) {
-    match rv {
-        Rvalue::Use(op) => compute_used_locals_in_operand(locals, op),
-        Rvalue::Ref(p, _) => compute_used_locals_in_place(locals, p),
-        Rvalue::UnaryOp(_, op) => compute_used_locals_in_operand(locals, op),
-        Rvalue::BinaryOp(_, op1, op2) => {
-            compute_used_locals_in_operand(locals, op1);
-            compute_used_locals_in_operand(locals, op2);
-        }
-        Rvalue::Discriminant(p) => compute_used_locals_in_place(locals, p),
-        Rvalue::Global(_) => (),
-        Rvalue::Aggregate(_, ops) => {
-            compute_used_locals_in_operands(locals, ops);
-        }
-    }
-}
+/// Collect the locals read by a statement. We don't consider the assignments
+/// to whole locals, nor the fake reads of whole locals, as reads.
+struct ReadLocals<'a>(&'a mut HashSet<VarId::Id>);
 
-fn compute_used_locals_in_statement(locals: &mut HashSet<VarId::Id>, st: &Statement) {
-    match &st.content {
-        RawStatement::Return => (),
-        RawStatement::Assign(p, rv) => {
-            compute_used_locals_in_rvalue(locals, rv);
-            compute_used_locals_in_place(locals, p);
-        }
-        RawStatement::FakeRead(p) => compute_used_locals_in_place(locals, p),
-        RawStatement::SetDiscriminant(p, _) => compute_used_locals_in_place(locals, p),
-        RawStatement::Drop(p) => compute_used_locals_in_place(locals, p),
-        RawStatement::Assert(assert) => compute_used_locals_in_operand(locals, &assert.cond),
-        RawStatement::Call(call) => {
-            compute_used_locals_in_operands(locals, &call.args);
-            compute_used_locals_in_place(locals, &call.dest);
-        }
-        RawStatement::Panic => (),
-        RawStatement::Break(_) => (),
-        RawStatement::Continue(_) => (),
-        RawStatement::Nop => (),
-        RawStatement::Switch(m) => match m {
-            Switch::If(op, st1, st2) => {
-                compute_used_locals_in_operand(locals, op);
-                compute_used_locals_in_statement(locals, st1);
-                compute_used_locals_in_statement(locals, st2);
-            }
-            Switch::SwitchInt(op, _, targets, otherwise) => {
-                compute_used_locals_in_operand(locals, op);
-                compute_used_locals_in_statement(locals, otherwise);
-                for (_, tgt) in targets {
-                    compute_used_locals_in_statement(locals, tgt);
-                }
-            }
-            Switch::Match(p, targets, otherwise) => {
-                compute_used_locals_in_place(locals, p);
-                compute_used_locals_in_statement(locals, otherwise);
-                for (_, tgt) in targets {
-                    compute_used_locals_in_statement(locals, tgt);
-                }
-            }
-        },
-        RawStatement::Loop(loop_body) => compute_used_locals_in_statement(locals, loop_body),
-        RawStatement::Sequence(st1, st2) => {
-            compute_used_locals_in_statement(locals, st1);
-            compute_used_locals_in_statement(locals, st2);
+impl AstVisitor for ReadLocals<'_> {
+    fn visit_statement(&mut self, st: &Statement) {
+        match &st.content {
+            RawStatement::Assign(p, rv) if p.projection.is_empty() => self.visit_rvalue(rv),
+            RawStatement::FakeRead(p) if p.projection.is_empty() => (),
+            _ => walk_statement(self, st),
         }
     }
-}
 
-fn transform_place(vids_map: &HashMap<VarId::Id, VarId::Id>, mut p: Place) -> Place {
-    let nvid = vids_map.get(&p.var_id).unwrap();
-    p.var_id = *nvid;
-    p
+    fn visit_var_id(&mut self, id: &VarId::Id) {
+        self.0.insert(*id);
+    }
 }
 
-fn transform_operand(vids_map: &HashMap<VarId::Id, VarId::Id>, op: Operand) -> Operand {
-    match op {
-        Operand::Copy(p) => Operand::Copy(transform_place(vids_map, p)),
-        Operand::Move(p) => Operand::Move(transform_place(vids_map, p)),
-        Operand::Const(ty, cv) => Operand::Const(ty, cv),
+/// Return true if the statement assigns (or fake reads) a local which is
+/// never read
+fn is_dead(read: &HashSet<VarId::Id>, st: &RawStatement) -> bool {
+    match st {
+        RawStatement::Assign(p, _) | RawStatement::FakeRead(p) => {
+            p.projection.is_empty() && !read.contains(&p.var_id)
+        }
+        _ => false,
     }
 }
 
-fn transform_operands(vids_map: &HashMap<VarId::Id, VarId::Id>, ops: Vec<Operand>) -> Vec<Operand> {
-    ops.into_iter()
-        .map(|op| transform_operand(vids_map, op))
-        .collect()
-}
+/// Remove the dead assignments and the dead fake reads. Removing an
+/// assignment may make other assignments dead (`y := copy x` is the only
+/// read of `x`): we iterate until we reach a fixed point.
+pub fn remove_dead_assignments(body: &mut ExprBody) {
+    loop {
+        // The return value is read when we return
+        let mut read: HashSet<VarId::Id> = HashSet::from([body.return_local]);
+        ReadLocals(&mut read).visit_statement(&body.body);
 
-fn transform_rvalue(vids_map: &HashMap<VarId::Id, VarId::Id>, rv: Rvalue) -> Rvalue {
-    match rv {
-        Rvalue::Use(op) => Rvalue::Use(transform_operand(vids_map, op)),
-        Rvalue::Ref(p, kind) => Rvalue::Ref(transform_place(vids_map, p), kind),
-        Rvalue::UnaryOp(unop, op) => Rvalue::UnaryOp(unop, transform_operand(vids_map, op)),
-        Rvalue::BinaryOp(binop, op1, op2) => {
-            let op1 = transform_operand(vids_map, op1);
-            let op2 = transform_operand(vids_map, op2);
-            Rvalue::BinaryOp(binop, op1, op2)
-        }
-        Rvalue::Global(gid) => Rvalue::Global(gid),
-        Rvalue::Discriminant(p) => Rvalue::Discriminant(transform_place(vids_map, p)),
-        Rvalue::Aggregate(kind, ops) => {
-            let ops = transform_operands(vids_map, ops);
-            Rvalue::Aggregate(kind, ops)
+        let mut changed = false;
+        take(&mut body.body, |st| {
+            transform_statements(
+                &mut |st: Statement| match st.content {
+                    ref content if is_dead(&read, content) => {
+                        changed = true;
+                        Statement::new(st.meta, RawStatement::Nop)
+                    }
+                    // Remove the no-ops from the sequences
+                    RawStatement::Sequence(st1, st2) if st1.content.is_nop() => *st2,
+                    RawStatement::Sequence(st1, st2) if st2.content.is_nop() => *st1,
+                    _ => st,
+                },
+                st,
+            )
+        });
+        if !changed {
+            break;
         }
     }
 }
 
-fn transform_st(vids_map: &HashMap<VarId::Id, VarId::Id>, st: Statement) -> Statement {
-    let st_raw = match st.content {
-        RawStatement::Return => RawStatement::Return,
-        RawStatement::Assign(p, rv) => {
-            RawStatement::Assign(transform_place(vids_map, p), transform_rvalue(vids_map, rv))
-        }
-        RawStatement::FakeRead(p) => RawStatement::FakeRead(transform_place(vids_map, p)),
-        RawStatement::SetDiscriminant(p, variant_id) => {
-            RawStatement::SetDiscriminant(transform_place(vids_map, p), variant_id)
-        }
-        RawStatement::Drop(p) => RawStatement::Drop(transform_place(vids_map, p)),
-        RawStatement::Assert(mut assert) => {
-            assert.cond = transform_operand(vids_map, assert.cond);
-            RawStatement::Assert(assert)
-        }
-        RawStatement::Call(mut call) => {
-            call.args = transform_operands(vids_map, call.args);
-            call.dest = transform_place(vids_map, call.dest);
-            RawStatement::Call(call)
-        }
-        RawStatement::Panic => RawStatement::Panic,
-        RawStatement::Break(i) => RawStatement::Break(i),
-        RawStatement::Continue(i) => RawStatement::Continue(i),
-        RawStatement::Nop => RawStatement::Nop,
-        RawStatement::Switch(switch) => {
-            let switch = match switch {
-                Switch::If(op, st1, st2) => {
-                    let op = transform_operand(vids_map, op);
-                    let st1 = Box::new(transform_st(vids_map, *st1));
-                    let st2 = Box::new(transform_st(vids_map, *st2));
-                    Switch::If(op, st1, st2)
-                }
-                Switch::SwitchInt(op, int_ty, targets, mut otherwise) => {
-                    let op = transform_operand(vids_map, op);
-                    let targets = Vec::from_iter(
-                        targets
-                            .into_iter()
-                            .map(|(v, e)| (v, transform_st(vids_map, e))),
-                    );
-                    *otherwise = transform_st(vids_map, *otherwise);
-                    Switch::SwitchInt(op, int_ty, targets, otherwise)
-                }
-                Switch::Match(p, targets, mut otherwise) => {
-                    let p = transform_place(vids_map, p);
-                    let targets = Vec::from_iter(
-                        targets
-                            .into_iter()
-                            .map(|(v, e)| (v, transform_st(vids_map, e))),
-                    );
-                    *otherwise = transform_st(vids_map, *otherwise);
-                    Switch::Match(p, targets, otherwise)
-                }
-            };
-            RawStatement::Switch(switch)
-        }
-        RawStatement::Loop(loop_body) => {
-            RawStatement::Loop(Box::new(transform_st(vids_map, *loop_body)))
-        }
-        RawStatement::Sequence(st1, st2) => RawStatement::Sequence(
-            Box::new(transform_st(vids_map, *st1)),
-            Box::new(transform_st(vids_map, *st2)),
-        ),
-    };
+/// Remap the locals of a statement
+struct RenameLocals<'a>(&'a HashMap<VarId::Id, VarId::Id>);
 
-    Statement::new(st.meta, st_raw)
+impl AstMutVisitor for RenameLocals<'_> {
+    fn visit_var_id_mut(&mut self, id: &mut VarId::Id) {
+        *id = *self.0.get(id).unwrap();
+    }
 }
 
 fn update_locals(
@@ -230,24 +139,179 @@ fn update_locals(
         }
     }
 
-    // Check there are no remaining variables with type `Never`
-    for v in &locals {
-        assert!(!v.ty.contains_never());
-    }
     (locals, vids_map)
 }
 
+/// Remove the locals which are not referenced by any place in the body, and
+/// remap the remaining ones to a dense range of identifiers, updating the
+/// places accordingly. The return variable and the input arguments keep
+/// their (fixed) indices.
+///
+/// This can be called by any pass which leaves unused locals behind it.
+pub fn compact_locals(body: &mut ExprBody) {
+    take(body, |mut b| {
+        let (locals, vids_map) = update_locals(b.arg_count, b.locals, &b.body);
+        b.locals = locals;
+        RenameLocals(&vids_map).visit_statement_mut(&mut b.body);
+        b
+    });
+}
+
 pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
-    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+    par_iter_bodies(funs, globals, |name, b| {
         trace!(
             "# About to remove unused locals in decl: {name}:\n{}",
             b.fmt_with_ctx_names(fmt_ctx)
         );
-        take(b, |mut b| {
-            let (locals, vids_map) = update_locals(b.arg_count, b.locals, &b.body);
-            b.locals = locals;
-            b.body = transform_st(&vids_map, b.body);
-            b
-        });
+        remove_dead_assignments(b);
+        compact_locals(b);
+
+        // Check there are no remaining variables with type `Never`
+        for v in &b.locals {
+            assert!(!v.ty.contains_never());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::*;
+    use crate::llbc_ast::RawStatement;
+    use crate::remove_unused_locals::*;
+    use crate::test_utils::*;
+    use crate::types::*;
+    use crate::ullbc_ast::{FunDeclId, FunId};
+    use std::iter::FromIterator;
+
+    fn mk_u32_var(index: usize, name: &str) -> Var {
+        mk_var(index, Some(name), Ty::Integer(IntegerTy::U32))
+    }
+
+    #[test]
+    fn test_compact_locals() {
+        let ret = VarId::Id::new(0);
+        let x = VarId::Id::new(1);
+        let z = VarId::Id::new(3);
+        // `y` (`_2`) is never used:
+        // ```
+        // z := copy x;
+        // @return := move z;
+        // return
+        // ```
+        let mut body = mk_body(
+            1,
+            vec![
+                mk_u32_var(0, "ret"),
+                mk_u32_var(1, "x"),
+                mk_u32_var(2, "y"),
+                mk_u32_var(3, "z"),
+            ],
+            vec![
+                RawStatement::Assign(Place::new(z), Rvalue::Use(Operand::Copy(Place::new(x)))),
+                RawStatement::Assign(Place::new(ret), Rvalue::Use(Operand::Move(Place::new(z)))),
+                RawStatement::Return,
+            ],
+        );
+        compact_locals(&mut body);
+
+        // `z` is now `_2`, and the return variable and the argument didn't move
+        let names: Vec<(usize, &str)> = body
+            .locals
+            .iter()
+            .map(|v| (v.index.to_usize(), v.name.as_deref().unwrap()))
+            .collect();
+        assert!(names == vec![(0, "ret"), (1, "x"), (2, "z")]);
+        let mut used = HashSet::new();
+        compute_used_locals_in_statement(&mut used, &body.body);
+        assert!(used == HashSet::from_iter(vec![ret, x, VarId::Id::new(2)]));
+    }
+
+    #[test]
+    fn test_remove_dead_assignments() {
+        let ret = VarId::Id::new(0);
+        let x = VarId::Id::new(1);
+        let y = VarId::Id::new(2);
+        let z = VarId::Id::new(3);
+        let copy = |v| Rvalue::Use(Operand::Copy(Place::new(v)));
+        // `z` is never read, and `y` is only read by the assignment to `z`:
+        // ```
+        // y := copy x;
+        // z := copy y;
+        // @fake_read(z);
+        // @return := copy x;
+        // return
+        // ```
+        let mut body = mk_body(
+            1,
+            vec![
+                mk_u32_var(0, "ret"),
+                mk_u32_var(1, "x"),
+                mk_u32_var(2, "y"),
+                mk_u32_var(3, "z"),
+            ],
+            vec![
+                RawStatement::Assign(Place::new(y), copy(x)),
+                RawStatement::Assign(Place::new(z), copy(y)),
+                RawStatement::FakeRead(Place::new(z)),
+                RawStatement::Assign(Place::new(ret), copy(x)),
+                RawStatement::Return,
+            ],
+        );
+        remove_dead_assignments(&mut body);
+        compact_locals(&mut body);
+
+        // `@return := copy x; return`
+        assert!(body.locals.len() == 2);
+        let (assign, ret_st) = body.body.content.as_sequence();
+        assert!(ret_st.content.is_return());
+        match &assign.content {
+            RawStatement::Assign(p, rv) => assert!(*p == Place::new(ret) && *rv == copy(x)),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_function_pointer() {
+        let ret = VarId::Id::new(0);
+        let x = VarId::Id::new(1);
+        let f = VarId::Id::new(3);
+        let u32_ty = Ty::Integer(IntegerTy::U32);
+        // `y` (`_2`) is never used, and `f` is only used as a callee:
+        // ```
+        // f := @FnPtr(@Fun0);
+        // @return := (*move f)(copy x);
+        // return
+        // ```
+        let mut body = mk_body(
+            1,
+            vec![
+                mk_u32_var(0, "ret"),
+                mk_u32_var(1, "x"),
+                mk_u32_var(2, "y"),
+                mk_var(
+                    3,
+                    Some("f"),
+                    Ty::FnPtr(im::Vector::from(vec![u32_ty.clone()]), Box::new(u32_ty)),
+                ),
+            ],
+            vec![
+                RawStatement::Assign(Place::new(f), Rvalue::FnPtr(FunDeclId::ZERO)),
+                RawStatement::Call(mk_call(
+                    FunId::Indirect(Operand::Move(Place::new(f))),
+                    vec![Operand::Copy(Place::new(x))],
+                    Place::new(ret),
+                )),
+                RawStatement::Return,
+            ],
+        );
+        compact_locals(&mut body);
+
+        // `f` is now `_2`, also in the callee of the call
+        let f = VarId::Id::new(2);
+        assert!(body.locals.len() == 3);
+        assert!(body.locals.get(f).unwrap().ty.is_fn_ptr());
+        let mut used = HashSet::new();
+        compute_used_locals_in_statement(&mut used, &body.body);
+        assert!(used == HashSet::from_iter(vec![ret, x, f]));
     }
 }
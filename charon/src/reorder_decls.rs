@@ -1,4 +1,5 @@
 use crate::common::*;
+use crate::diagnostics::{Code, Diagnostics, Level};
 use crate::graphs::*;
 use crate::register::DeclKind;
 use crate::register::RegisteredDeclarations;
@@ -8,8 +9,10 @@ use macros::{VariantIndexArity, VariantName};
 use petgraph::algo::tarjan_scc;
 use petgraph::graphmap::DiGraphMap;
 use rustc_hir::def_id::DefId;
+use rustc_middle::ty::TyCtxt;
+use rustc_session::Session;
 use serde::ser::SerializeTupleVariant;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
 use std::fmt::{Debug, Display, Error, Formatter};
 use std::vec::Vec;
@@ -35,7 +38,19 @@ pub enum DeclarationGroup<TypeId: Copy, FunId: Copy, GlobalId: Copy> {
     Global(GDeclarationGroup<GlobalId>),
 }
 
-#[derive(PartialEq, Eq, Hash, EnumIsA, EnumAsGetters, VariantName)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    EnumIsA,
+    EnumAsGetters,
+    VariantName,
+    Serialize,
+    Deserialize,
+)]
 pub enum AnyDeclId<TypeId: Copy, FunId: Copy, GlobalId: Copy> {
     Type(TypeId),
     Fun(FunId),
@@ -108,6 +123,26 @@ impl<Id: Copy + Serialize> Serialize for GDeclarationGroup<Id> {
     }
 }
 
+impl<'de, Id: Copy + Deserialize<'de>> Deserialize<'de> for GDeclarationGroup<Id> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // The variants are serialized as tuple variants of arity 1
+        #[derive(Deserialize)]
+        #[serde(rename = "GDeclarationGroup")]
+        enum SerializedGroup<Id> {
+            NonRec((Id,)),
+            Rec((Vec<Id>,)),
+        }
+
+        Ok(match SerializedGroup::deserialize(deserializer)? {
+            SerializedGroup::NonRec((id,)) => GDeclarationGroup::NonRec(id),
+            SerializedGroup::Rec((ids,)) => GDeclarationGroup::Rec(ids),
+        })
+    }
+}
+
 /// We use the [Debug] trait instead of [Display] for the identifiers, because
 /// the rustc [DefId] doesn't implement [Display]...
 impl<TypeId: Copy + Debug, FunId: Copy + Debug, GlobalId: Copy + Debug> Display
@@ -156,6 +191,58 @@ impl<TypeId: Copy + Serialize, FunId: Copy + Serialize, GlobalId: Copy + Seriali
     }
 }
 
+impl<'de, TypeId, FunId, GlobalId> Deserialize<'de> for DeclarationGroup<TypeId, FunId, GlobalId>
+where
+    TypeId: Copy + Deserialize<'de>,
+    FunId: Copy + Deserialize<'de>,
+    GlobalId: Copy + Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(rename = "DeclarationGroup")]
+        enum SerializedGroup<TypeId: Copy, FunId: Copy, GlobalId: Copy> {
+            Type((GDeclarationGroup<TypeId>,)),
+            Fun((GDeclarationGroup<FunId>,)),
+            Global((GDeclarationGroup<GlobalId>,)),
+        }
+
+        Ok(match SerializedGroup::deserialize(deserializer)? {
+            SerializedGroup::Type((group,)) => DeclarationGroup::Type(group),
+            SerializedGroup::Fun((group,)) => DeclarationGroup::Fun(group),
+            SerializedGroup::Global((group,)) => DeclarationGroup::Global(group),
+        })
+    }
+}
+
+impl<Id: Copy> GDeclarationGroup<Id> {
+    pub fn get_ids(&self) -> Vec<Id> {
+        match self {
+            GDeclarationGroup::NonRec(id) => vec![*id],
+            GDeclarationGroup::Rec(ids) => ids.clone(),
+        }
+    }
+}
+
+impl<TypeId: Copy, FunId: Copy, GlobalId: Copy> DeclarationGroup<TypeId, FunId, GlobalId> {
+    /// Return the identifiers of the declarations in the group
+    pub fn get_ids(&self) -> Vec<AnyDeclId<TypeId, FunId, GlobalId>> {
+        match self {
+            DeclarationGroup::Type(group) => {
+                group.get_ids().into_iter().map(AnyDeclId::Type).collect()
+            }
+            DeclarationGroup::Fun(group) => {
+                group.get_ids().into_iter().map(AnyDeclId::Fun).collect()
+            }
+            DeclarationGroup::Global(group) => {
+                group.get_ids().into_iter().map(AnyDeclId::Global).collect()
+            }
+        }
+    }
+}
+
 impl<TypeId: Copy, FunId: Copy, GlobalId: Copy> DeclarationsGroups<TypeId, FunId, GlobalId> {
     #[allow(clippy::new_without_default)]
     pub fn new() -> DeclarationsGroups<TypeId, FunId, GlobalId> {
@@ -228,8 +315,65 @@ impl<'a, TypeId: Copy, FunId: Copy, GlobalId: Copy> std::iter::IntoIterator
     }
 }
 
+/// The kind of the declarations of a strongly connected component of the
+/// dependency graph.
+///
+/// We can't group declarations of different kinds: this happens for instance
+/// with a type whose definition uses a constant computed by a function which
+/// itself uses the type (`struct S([u8; f()])`, where `f` manipulates values
+/// of type `S`). There can't either be several (mutually recursive) globals
+/// in the same group. In those cases, we return the members of the group with
+/// their kinds, to report the cycle.
+fn scc_kind<Id: Copy>(
+    scc: &[Id],
+    kind: &dyn Fn(Id) -> DeclKind,
+) -> std::result::Result<DeclKind, Vec<(Id, DeclKind)>> {
+    let kind0 = kind(scc[0]);
+    let same_kind = scc.iter().all(|id| kind(*id) == kind0);
+    if same_kind && (kind0 != DeclKind::Global || scc.len() == 1) {
+        Ok(kind0)
+    } else {
+        Err(scc.iter().map(|id| (*id, kind(*id))).collect())
+    }
+}
+
+/// Report a cycle of dependencies we can't group (see [scc_kind]), by
+/// listing its members
+fn report_mixed_cycle(
+    sess: &Session,
+    tcx: TyCtxt,
+    diagnostics: &Diagnostics,
+    members: &[(DefId, DeclKind)],
+) {
+    let mut names: Vec<String> = members
+        .iter()
+        .map(|(id, kind)| {
+            let kind = match kind {
+                DeclKind::Type => "type",
+                DeclKind::Fun => "function",
+                DeclKind::Global => "global",
+            };
+            format!("{kind} `{}`", tcx.def_path_str(*id))
+        })
+        .collect();
+    names.sort();
+    let msg = format!(
+        "The following declarations are mutually recursive, but we can't group them \
+         together: {}",
+        names.join(", ")
+    );
+    let span = tcx.def_span(members[0].0);
+    diagnostics.span_report(sess, Level::Error, Code::MixedRecursion, span, &msg);
+}
+
+/// Compute the groups of (mutually recursive) declarations, in the order in
+/// which we must extract them. We report the groups which mix several kinds
+/// of declarations (see [scc_kind]).
 pub fn reorder_declarations(
+    sess: &Session,
+    tcx: TyCtxt,
     decls: &RegisteredDeclarations,
+    diagnostics: &Diagnostics,
 ) -> Result<DeclarationsGroups<DefId, DefId, DefId>> {
     trace!();
 
@@ -270,47 +414,52 @@ pub fn reorder_declarations(
     // Finally, generate the list of declarations
     let mut reordered_decls = DeclarationsGroups::new();
 
-    format!("PATCH: unsafe code comment");
     // Iterate over the SCC ids in the proper order
-    // for scc in reordered_sccs.iter() {
-    //     // Retrieve the SCC
-    //     
-    //     assert!(!scc.is_empty());
-
-    //     // Note that the length of an SCC should be at least 1.
-    //     let mut it = scc.iter();
-    //     let id0 = *it.next().unwrap();
-    //     let decl = &decls[&id0];
-
-    //     // The group should consist of only functions, only types or only one global.
-    //     for id in scc {
-    //         assert!(decls[id].kind == decl.kind);
-    //     }
-    //     if let DeclKind::Global = decl.kind {
-    //         assert!(scc.len() == 1);
-    //     }
-
-    //     // If an SCC has length one, the declaration may be simply recursive:
-    //     // we determine whether it is the case by checking if the def id is in
-    //     // its own set of dependencies.
-    //     let is_mutually_recursive = scc.len() > 1;
-    //     let is_simply_recursive =
-    //         !is_mutually_recursive && decl.deps.as_ref().is_some_and(|deps| deps.contains(&id0));
-
-    //     // Add the declaration.
-    //     // Note that we clone the vectors: it is not optimal, but they should
-    //     // be pretty small.
-    //     let group = if is_mutually_recursive || is_simply_recursive {
-    //         GDeclarationGroup::Rec(scc.clone())
-    //     } else {
-    //         GDeclarationGroup::NonRec(id0)
-    //     };
-    //     reordered_decls.push(match decl.kind {
-    //         DeclKind::Type => DeclarationGroup::Type(group),
-    //         DeclKind::Fun => DeclarationGroup::Fun(group),
-    //         DeclKind::Global => DeclarationGroup::Global(group),
-    //     });
-    // }
+    let mut num_errors = 0;
+    for scc in reordered_sccs.iter() {
+        // Retrieve the SCC
+        assert!(!scc.is_empty());
+
+        // Note that the length of an SCC should be at least 1.
+        let id0 = scc[0];
+        let decl = &decls[&id0];
+
+        // The group should consist of only functions, only types or only one
+        // global: otherwise we report the cycle, and continue to report the
+        // other ones
+        let kind = match scc_kind(scc, &|id| decls[&id].kind) {
+            Ok(kind) => kind,
+            Err(members) => {
+                report_mixed_cycle(sess, tcx, diagnostics, &members);
+                num_errors += 1;
+                continue;
+            }
+        };
+
+        // If an SCC has length one, the declaration may be simply recursive:
+        // we determine whether it is the case by checking if the def id is in
+        // its own set of dependencies.
+        let is_mutually_recursive = scc.len() > 1;
+        let is_simply_recursive =
+            !is_mutually_recursive && decl.deps.as_ref().is_some_and(|deps| deps.contains(&id0));
+
+        // Add the declaration.
+        // Note that we clone the vectors: it is not optimal, but they should
+        // be pretty small.
+        let group = if is_mutually_recursive || is_simply_recursive {
+            GDeclarationGroup::Rec(scc.clone())
+        } else {
+            GDeclarationGroup::NonRec(id0)
+        };
+        reordered_decls.push(match kind {
+            DeclKind::Type => DeclarationGroup::Type(group),
+            DeclKind::Fun => DeclarationGroup::Fun(group),
+            DeclKind::Global => DeclarationGroup::Global(group),
+        });
+    }
+    if num_errors > 0 {
+        return Err(());
+    }
 
     trace!("{}", reordered_decls.to_string());
 
@@ -357,4 +506,22 @@ mod tests {
         assert!(reordered.scc_deps[1] == im::OrdSet::from(vec![0]));
         assert!(reordered.scc_deps[2] == im::OrdSet::from(vec![0, 1]));
     }
+
+    #[test]
+    fn test_scc_kind() {
+        use crate::register::DeclKind;
+        use crate::reorder_decls::scc_kind;
+
+        // 0 and 1 are types, 2 is a function and 3 and 4 are globals
+        let kind = &|x| match x {
+            0 | 1 => DeclKind::Type,
+            2 => DeclKind::Fun,
+            _ => DeclKind::Global,
+        };
+        assert!(scc_kind(&[0, 1], kind) == Ok(DeclKind::Type));
+        assert!(scc_kind(&[3], kind) == Ok(DeclKind::Global));
+        // A type which depends on a function which depends on the type
+        assert!(scc_kind(&[1, 2], kind) == Err(vec![(1, DeclKind::Type), (2, DeclKind::Fun)]));
+        assert!(scc_kind(&[3, 4], kind).is_err());
+    }
 }
@@ -1,13 +1,25 @@
 #![allow(dead_code)]
+use crate::id_vector::Increment;
 use crate::meta::{FileId, FileInfo, FileName, LocalFileId, VirtualFileId};
+use crate::names::{Name, PathElem};
+use crate::names_utils::{
+    function_def_id_to_name, global_def_id_to_name, trait_def_id_to_name, type_def_id_to_name,
+};
 use crate::reorder_decls as rd;
 use crate::types as ty;
 use crate::types::TypeDeclId;
 use crate::ullbc_ast as ast;
 use crate::ullbc_ast::FunDeclId;
 use crate::ullbc_ast::GlobalDeclId;
-use rustc_hir::def_id::DefId;
-use std::collections::HashMap;
+use crate::ullbc_ast::{TraitDeclId, TraitImplId};
+use rustc_hir::def_id::{DefId, CRATE_DEF_INDEX};
+use rustc_middle::ty::TyCtxt;
+use rustc_middle::ty::TyKind;
+use rustc_middle::ty::Visibility as RustVisibility;
+use rustc_span::sym;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
 use std::vec::Vec;
 
 pub type GDeclarationGroup<Id> = rd::GDeclarationGroup<Id>;
@@ -19,19 +31,93 @@ pub type DeclarationGroup =
 pub type AnyDeclRid = rd::AnyDeclId<DefId, DefId, DefId>;
 pub type AnyDeclId = rd::AnyDeclId<ty::TypeDeclId::Id, ast::FunDeclId::Id, ast::GlobalDeclId::Id>;
 
-#[derive(Clone, Copy)]
+/// The visibility of a declaration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Visibility {
+    /// `pub`
+    Public,
+    /// `pub(crate)`
+    Crate,
+    /// Private, or restricted to a module other than the crate root
+    /// (`pub(super)`, `pub(in path)`). Note that in the crate root, `pub(crate)`
+    /// is the same as private.
+    Private,
+}
+
+impl Visibility {
+    /// `parent` is the module containing the declaration: a private
+    /// declaration is restricted to this module, which may be the crate root.
+    /// Note that the def indices are relative to a crate: we must also compare
+    /// the crates to recognize the root of the crate of the declaration.
+    fn from_rustc(vis: RustVisibility<DefId>, parent: DefId) -> Self {
+        match vis {
+            RustVisibility::Public => Visibility::Public,
+            RustVisibility::Restricted(mod_id) => {
+                if mod_id != parent
+                    && mod_id.krate == parent.krate
+                    && mod_id.index == CRATE_DEF_INDEX
+                {
+                    Visibility::Crate
+                } else {
+                    Visibility::Private
+                }
+            }
+        }
+    }
+}
+
+/// The module containing a declaration
+fn parent_module(tcx: TyCtxt, rid: DefId) -> DefId {
+    match rid.as_local() {
+        Some(id) => tcx.parent_module_from_def_id(id).to_def_id(),
+        None => tcx.parent(rid),
+    }
+}
+
+/// Information about a method defined in a trait implementation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraitImplInfo {
+    /// The name of the implemented trait (e.g., `core::clone::Clone`)
+    pub trait_name: Name,
+    /// The name of the method in the trait (e.g., `clone`)
+    pub method_name: String,
+    /// The type for which the trait is implemented, if it is an ADT of the
+    /// translated crate
+    pub self_type: Option<TypeDeclId::Id>,
+    /// True if the implementation was generated by a `#[derive]`
+    pub is_derived: bool,
+}
+
+#[derive(Clone, Serialize)]
 /// Information common to any top-level declaration.
 pub struct DeclInfo {
     /// Its Rust identifier. Indicates if the declaration is local ("external" otherwise).
+    #[serde(skip)]
     pub rid: DefId,
     /// True if the declaration's body is accessible ("opaque" otherwise).
     pub is_transparent: bool,
+    /// The visibility of the declaration, as written in the source
+    pub visibility: Visibility,
+    /// If the declaration is a method of a trait implementation: the trait
+    /// and the type it is implemented for.
+    pub trait_impl: Option<TraitImplInfo>,
+    /// If the declaration is external and comes from a crate we extracted
+    /// beforehand: its identifier in the file of this crate.
+    pub extern_ref: Option<ExternRef>,
 }
 impl DeclInfo {
-    fn new(rid: DefId, info: rd::DeclInfo) -> Self {
+    fn new(
+        rid: DefId,
+        info: rd::DeclInfo,
+        visibility: RustVisibility<DefId>,
+        parent: DefId,
+    ) -> Self {
         DeclInfo {
             rid,
             is_transparent: info.is_transparent,
+            visibility: Visibility::from_rustc(visibility, parent),
+            trait_impl: None,
+            extern_ref: None,
         }
     }
     pub fn is_local(&self) -> bool {
@@ -39,33 +125,168 @@ impl DeclInfo {
     }
 }
 
+/// A declaration of a crate which was extracted separately (see the
+/// `--extern-llbc` option).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternRef {
+    /// The name of the crate
+    pub krate: String,
+    /// The identifier of the declaration in the file generated for the crate
+    pub id: AnyDeclId,
+}
+
+/// The symbol tables of the crates extracted beforehand, which allow to link
+/// the external declarations of the crate we extract to the declarations of
+/// those crates.
+///
+/// The rustc identifiers are not stable across the compiler sessions: we
+/// identify the declarations with their stable paths (i.e., their names,
+/// like `utils::parser::Parser`). Note that a type and a function can have
+/// the same name, which is why we also check the kind of the declarations.
+#[derive(Default)]
+pub struct SymbolTable {
+    crates: HashMap<String, HashMap<String, Vec<AnyDeclId>>>,
+}
+
+/// The crate a name comes from (the first element of a name is always the
+/// crate name)
+fn crate_of_name(name: &Name) -> Option<&str> {
+    match name.name.first() {
+        Some(PathElem::Ident(krate)) => Some(krate),
+        _ => None,
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.crates.is_empty()
+    }
+
+    /// Register the declarations of the crate `krate`. We ignore the
+    /// declarations which come from other crates: the crate only declares
+    /// them as opaque.
+    pub fn add_crate<'a>(
+        &mut self,
+        krate: &str,
+        decls: impl Iterator<Item = (&'a Name, AnyDeclId)>,
+    ) {
+        let symbols = self.crates.entry(krate.to_string()).or_default();
+        for (name, id) in decls {
+            if crate_of_name(name) == Some(krate) {
+                symbols.entry(name.to_string()).or_default().push(id);
+            }
+        }
+    }
+
+    /// Look up a declaration. `id` is the identifier of the declaration in
+    /// the crate we extract: we only use it to know its kind.
+    pub fn resolve(&self, name: &Name, id: AnyDeclId) -> Option<ExternRef> {
+        let krate = crate_of_name(name)?;
+        let candidates = self.crates.get(krate)?.get(&name.to_string())?;
+        let same_kind =
+            |other: &&AnyDeclId| std::mem::discriminant(*other) == std::mem::discriminant(&id);
+        candidates.iter().find(same_kind).map(|other| ExternRef {
+            krate: krate.to_string(),
+            id: *other,
+        })
+    }
+}
+
 // Small helpers.
 fn add_type_info(
+    tcx: TyCtxt,
     src: &HashMap<AnyDeclRid, rd::DeclInfo>,
     dst: &mut HashMap<AnyDeclId, DeclInfo>,
     rid: DefId,
     id: TypeDeclId::Id,
 ) {
     let info = *src.get(&AnyDeclRid::Type(rid)).unwrap();
-    dst.insert(AnyDeclId::Type(id), DeclInfo::new(rid, info));
+    let vis = tcx.visibility(rid);
+    let parent = parent_module(tcx, rid);
+    dst.insert(AnyDeclId::Type(id), DeclInfo::new(rid, info, vis, parent));
 }
 fn add_function_info(
+    tcx: TyCtxt,
     src: &HashMap<AnyDeclRid, rd::DeclInfo>,
     dst: &mut HashMap<AnyDeclId, DeclInfo>,
     rid: DefId,
     id: FunDeclId::Id,
 ) {
     let info = *src.get(&AnyDeclRid::Fun(rid)).unwrap();
-    dst.insert(AnyDeclId::Fun(id), DeclInfo::new(rid, info));
+    let vis = tcx.visibility(rid);
+    let parent = parent_module(tcx, rid);
+    dst.insert(AnyDeclId::Fun(id), DeclInfo::new(rid, info, vis, parent));
 }
 fn add_global_info(
+    tcx: TyCtxt,
     src: &HashMap<AnyDeclRid, rd::DeclInfo>,
     dst: &mut HashMap<AnyDeclId, DeclInfo>,
     rid: DefId,
     id: GlobalDeclId::Id,
 ) {
     let info = *src.get(&AnyDeclRid::Global(rid)).unwrap();
-    dst.insert(AnyDeclId::Global(id), DeclInfo::new(rid, info));
+    let vis = tcx.visibility(rid);
+    let parent = parent_module(tcx, rid);
+    dst.insert(AnyDeclId::Global(id), DeclInfo::new(rid, info, vis, parent));
+}
+
+/// A closure is translated to a function (its body) and to a type (its
+/// environment), which share the same rust identifier (see [crate::closures]).
+/// The registration only knows about the function: we introduce the type
+/// declaration here, just before the function group.
+fn add_closure_env(
+    tcx: TyCtxt,
+    src: &HashMap<AnyDeclRid, rd::DeclInfo>,
+    rid: DefId,
+    type_counter: &mut ty::TypeDeclId::Generator,
+    type_rid_to_id: &mut HashMap<DefId, ty::TypeDeclId::Id>,
+    dst: &mut HashMap<AnyDeclId, DeclInfo>,
+    decls: &mut Vec<DeclarationGroup>,
+) {
+    if !tcx.is_closure(rid) {
+        return;
+    }
+    let id = type_counter.fresh_id();
+    type_rid_to_id.insert(rid, id);
+    let info = *src.get(&AnyDeclRid::Fun(rid)).unwrap();
+    let vis = tcx.visibility(rid);
+    let parent = parent_module(tcx, rid);
+    dst.insert(AnyDeclId::Type(id), DeclInfo::new(rid, info, vis, parent));
+    decls.push(DeclarationGroup::Type(GDeclarationGroup::NonRec(id)));
+}
+
+/// The type an `impl` block is defined for, if it is an ADT of the translated
+/// crate
+pub(crate) fn get_impl_self_type(
+    tcx: TyCtxt,
+    type_rid_to_id: &HashMap<DefId, ty::TypeDeclId::Id>,
+    impl_id: DefId,
+) -> Option<TypeDeclId::Id> {
+    match tcx.type_of(impl_id).kind() {
+        TyKind::Adt(adt, _) => type_rid_to_id.get(&adt.did()).copied(),
+        _ => None,
+    }
+}
+
+/// Compute the trait implementation information of a function, if it is
+/// a method defined in a trait implementation.
+fn get_trait_impl_info(
+    tcx: TyCtxt,
+    type_rid_to_id: &HashMap<DefId, ty::TypeDeclId::Id>,
+    rid: DefId,
+) -> Option<TraitImplInfo> {
+    let impl_id = tcx.impl_of_method(rid)?;
+    let trait_id = tcx.trait_id_of_impl(impl_id)?;
+    Some(TraitImplInfo {
+        trait_name: trait_def_id_to_name(tcx, trait_id),
+        method_name: tcx.item_name(rid).to_string(),
+        self_type: get_impl_self_type(tcx, type_rid_to_id, impl_id),
+        is_derived: tcx.has_attr(impl_id, sym::automatically_derived),
+    })
 }
 
 pub struct OrderedDecls {
@@ -82,12 +303,52 @@ pub struct OrderedDecls {
     pub type_rid_to_id: HashMap<DefId, ty::TypeDeclId::Id>,
     pub fun_rid_to_id: HashMap<DefId, ast::FunDeclId::Id>,
     pub global_rid_to_id: HashMap<DefId, ast::GlobalDeclId::Id>,
+    /// The traits and the trait implementations don't appear in the
+    /// declaration groups: they only group functions (see
+    /// [crate::translate_traits]).
+    pub trait_rid_to_id: HashMap<DefId, TraitDeclId::Id>,
+    pub trait_impl_rid_to_id: HashMap<DefId, TraitImplId::Id>,
+    /// The paths of the declarations (see [def_id_to_path]) to translation
+    /// identifiers. Contrary to the Rust identifiers, which are only
+    /// meaningful in the compiler session which created them, the paths
+    /// identify the declarations across the crates we translate separately
+    /// (see [merge_ordered_decls]).
+    pub type_path_to_id: HashMap<String, ty::TypeDeclId::Id>,
+    pub fun_path_to_id: HashMap<String, ast::FunDeclId::Id>,
+    pub global_path_to_id: HashMap<String, ast::GlobalDeclId::Id>,
+    pub trait_path_to_id: HashMap<String, TraitDeclId::Id>,
+    pub trait_impl_path_to_id: HashMap<String, TraitImplId::Id>,
+}
+
+/// The path of a declaration: the name of its crate, followed by its
+/// definition path (for instance: `core::option::Option` or
+/// `my_crate::{impl#0}::new`).
+pub fn def_id_to_path(tcx: TyCtxt, rid: DefId) -> String {
+    format!(
+        "{}{}",
+        tcx.crate_name(rid.krate),
+        tcx.def_path(rid).to_string_no_crate_verbose()
+    )
+}
+
+fn paths_to_ids<Id: Copy>(tcx: TyCtxt, rid_to_id: &HashMap<DefId, Id>) -> HashMap<String, Id> {
+    rid_to_id
+        .iter()
+        .map(|(rid, id)| (def_id_to_path(tcx, *rid), *id))
+        .collect()
 }
 
 /// Convert the definition ids used by the rust compiler to our own definition ids.
+///
+/// `extern_symbols` gives the declarations of the crates extracted beforehand:
+/// we link the external declarations to them (see [DeclInfo::extern_ref]).
+/// We still declare those declarations as opaque in the current crate, so that
+/// the generated file remains self-contained.
 pub fn rust_to_local_ids(
+    tcx: TyCtxt,
     files_info: &HashMap<FileName, FileInfo>,
     reordered: &rd::DeclarationsGroups<DefId, DefId, DefId>,
+    extern_symbols: &SymbolTable,
 ) -> OrderedDecls {
     let mut decls_info = HashMap::new();
 
@@ -108,7 +369,7 @@ pub fn rust_to_local_ids(
             rd::DeclarationGroup::Type(rd::GDeclarationGroup::NonRec(rid)) => {
                 let id = type_counter.fresh_id();
                 type_rid_to_id.insert(*rid, id);
-                add_type_info(&reordered.decls_info, &mut decls_info, *rid, id);
+                add_type_info(tcx, &reordered.decls_info, &mut decls_info, *rid, id);
                 decls.push(DeclarationGroup::Type(GDeclarationGroup::NonRec(id)));
             }
             rd::DeclarationGroup::Type(rd::GDeclarationGroup::Rec(rids)) => {
@@ -116,23 +377,43 @@ pub fn rust_to_local_ids(
                 for rid in rids {
                     let id = type_counter.fresh_id();
                     type_rid_to_id.insert(*rid, id);
-                    add_type_info(&reordered.decls_info, &mut decls_info, *rid, id);
+                    add_type_info(tcx, &reordered.decls_info, &mut decls_info, *rid, id);
                     ids.push(id);
                 }
                 decls.push(DeclarationGroup::Type(GDeclarationGroup::Rec(ids)));
             }
             rd::DeclarationGroup::Fun(rd::GDeclarationGroup::NonRec(rid)) => {
+                add_closure_env(
+                    tcx,
+                    &reordered.decls_info,
+                    *rid,
+                    &mut type_counter,
+                    &mut type_rid_to_id,
+                    &mut decls_info,
+                    &mut decls,
+                );
                 let id = fun_counter.fresh_id();
                 fun_rid_to_id.insert(*rid, id);
-                add_function_info(&reordered.decls_info, &mut decls_info, *rid, id);
+                add_function_info(tcx, &reordered.decls_info, &mut decls_info, *rid, id);
                 decls.push(DeclarationGroup::Fun(GDeclarationGroup::NonRec(id)));
             }
             rd::DeclarationGroup::Fun(rd::GDeclarationGroup::Rec(rids)) => {
+                for rid in rids {
+                    add_closure_env(
+                        tcx,
+                        &reordered.decls_info,
+                        *rid,
+                        &mut type_counter,
+                        &mut type_rid_to_id,
+                        &mut decls_info,
+                        &mut decls,
+                    );
+                }
                 let mut ids: Vec<ast::FunDeclId::Id> = Vec::new();
                 for rid in rids {
                     let id = fun_counter.fresh_id();
                     fun_rid_to_id.insert(*rid, id);
-                    add_function_info(&reordered.decls_info, &mut decls_info, *rid, id);
+                    add_function_info(tcx, &reordered.decls_info, &mut decls_info, *rid, id);
                     ids.push(id);
                 }
                 decls.push(DeclarationGroup::Fun(GDeclarationGroup::Rec(ids)));
@@ -140,7 +421,7 @@ pub fn rust_to_local_ids(
             rd::DeclarationGroup::Global(rd::GDeclarationGroup::NonRec(rid)) => {
                 let id = global_counter.fresh_id();
                 global_rid_to_id.insert(*rid, id);
-                add_global_info(&reordered.decls_info, &mut decls_info, *rid, id);
+                add_global_info(tcx, &reordered.decls_info, &mut decls_info, *rid, id);
                 decls.push(DeclarationGroup::Global(GDeclarationGroup::NonRec(id)));
             }
             rd::DeclarationGroup::Global(rd::GDeclarationGroup::Rec(rids)) => {
@@ -148,7 +429,7 @@ pub fn rust_to_local_ids(
                 for rid in rids {
                     let id = global_counter.fresh_id();
                     global_rid_to_id.insert(*rid, id);
-                    add_global_info(&reordered.decls_info, &mut decls_info, *rid, id);
+                    add_global_info(tcx, &reordered.decls_info, &mut decls_info, *rid, id);
                     ids.push(id);
                 }
                 decls.push(DeclarationGroup::Global(GDeclarationGroup::Rec(ids)));
@@ -156,6 +437,65 @@ pub fn rust_to_local_ids(
         }
     }
 
+    // Retrieve the trait implementations the functions belong to. We do this
+    // once all the type identifiers have been computed, to be able to
+    // reference the self types.
+    for (rid, id) in &fun_rid_to_id {
+        let info = decls_info.get_mut(&AnyDeclId::Fun(*id)).unwrap();
+        info.trait_impl = get_trait_impl_info(tcx, &type_rid_to_id, *rid);
+    }
+
+    // Link the external declarations to the crates extracted beforehand. The
+    // closures are always local, and their environments don't have names.
+    if !extern_symbols.is_empty() {
+        for (id, info) in decls_info.iter_mut() {
+            if info.is_local() || tcx.is_closure(info.rid) {
+                continue;
+            }
+            let name = match id {
+                AnyDeclId::Type(_) => type_def_id_to_name(tcx, info.rid),
+                AnyDeclId::Fun(_) => function_def_id_to_name(tcx, info.rid),
+                AnyDeclId::Global(_) => global_def_id_to_name(tcx, info.rid),
+            };
+            info.extern_ref = extern_symbols.resolve(&name, *id);
+            if let Some(extern_ref) = &info.extern_ref {
+                trace!("Linked {} to the crate {}", name, extern_ref.krate);
+            }
+        }
+    }
+
+    // Compute the identifiers of the traits and of the trait implementations
+    // the functions belong to. We explore the functions in the order of their
+    // identifiers, to make the numbering deterministic.
+    let mut trait_rid_to_id: HashMap<DefId, TraitDeclId::Id> = HashMap::new();
+    let mut trait_impl_rid_to_id: HashMap<DefId, TraitImplId::Id> = HashMap::new();
+    let mut trait_counter = TraitDeclId::Generator::new();
+    let mut trait_impl_counter = TraitImplId::Generator::new();
+    let mut funs: Vec<(&DefId, &FunDeclId::Id)> = fun_rid_to_id.iter().collect();
+    funs.sort_by_key(|(_, id)| **id);
+    for (rid, _) in funs {
+        let (trait_rid, impl_rid) = match tcx.trait_of_item(*rid) {
+            // A method of a trait declaration
+            Some(trait_rid) => (trait_rid, None),
+            None => match tcx.impl_of_method(*rid) {
+                // A method of a trait implementation
+                Some(impl_rid) => match tcx.trait_id_of_impl(impl_rid) {
+                    Some(trait_rid) => (trait_rid, Some(impl_rid)),
+                    None => continue,
+                },
+                None => continue,
+            },
+        };
+        trait_rid_to_id
+            .entry(trait_rid)
+            .or_insert_with(|| trait_counter.fresh_id());
+        if let Some(impl_rid) = impl_rid {
+            trait_impl_rid_to_id
+                .entry(impl_rid)
+                .or_insert_with(|| trait_impl_counter.fresh_id());
+        }
+    }
+
     // Reorder the files and compute the maps from files to ids and reverse
     let mut files: Vec<FileName> = files_info.keys().cloned().collect();
     files.sort();
@@ -175,6 +515,284 @@ pub fn rust_to_local_ids(
         id_to_file.insert(id, file.clone());
     }
 
+    OrderedDecls {
+        files,
+        decls,
+        decls_info,
+        file_to_id,
+        id_to_file,
+        type_path_to_id: paths_to_ids(tcx, &type_rid_to_id),
+        fun_path_to_id: paths_to_ids(tcx, &fun_rid_to_id),
+        global_path_to_id: paths_to_ids(tcx, &global_rid_to_id),
+        trait_path_to_id: paths_to_ids(tcx, &trait_rid_to_id),
+        trait_impl_path_to_id: paths_to_ids(tcx, &trait_impl_rid_to_id),
+        type_rid_to_id,
+        fun_rid_to_id,
+        global_rid_to_id,
+        trait_rid_to_id,
+        trait_impl_rid_to_id,
+    }
+}
+
+/// The mappings from the identifiers of a crate to the identifiers of the
+/// crate it is merged into (see [merge_ordered_decls]). They are used to
+/// update the translated declarations of the merged crate (see
+/// [crate::relocate_decls]).
+pub struct DeclsRelocation {
+    pub types: HashMap<ty::TypeDeclId::Id, ty::TypeDeclId::Id>,
+    pub funs: HashMap<ast::FunDeclId::Id, ast::FunDeclId::Id>,
+    pub globals: HashMap<ast::GlobalDeclId::Id, ast::GlobalDeclId::Id>,
+    pub traits: HashMap<TraitDeclId::Id, TraitDeclId::Id>,
+    pub trait_impls: HashMap<TraitImplId::Id, TraitImplId::Id>,
+    pub files: HashMap<FileId::Id, FileId::Id>,
+}
+
+/// Relocate the identifiers of the declarations of one kind. The declarations
+/// which are already present in the target crate keep their identifier, the
+/// other ones get fresh identifiers starting from `next_id`.
+fn relocate_ids<Id: Copy + Eq + Hash + Ord + Increment>(
+    target: &HashMap<String, Id>,
+    source: &HashMap<String, Id>,
+    mut next_id: Id,
+) -> HashMap<Id, Id> {
+    // Sort the identifiers, to preserve the order of the declarations
+    let mut ids: Vec<(&String, &Id)> = source.iter().collect();
+    ids.sort_by_key(|(_, id)| **id);
+    ids.into_iter()
+        .map(|(path, id)| match target.get(path) {
+            Some(nid) => (*id, *nid),
+            None => {
+                let nid = next_id;
+                next_id.incr();
+                (*id, nid)
+            }
+        })
+        .collect()
+}
+
+/// Compute how to relocate the identifiers of `source` so that they don't
+/// collide with the ones of `target`.
+pub fn compute_relocation(target: &OrderedDecls, source: &OrderedDecls) -> DeclsRelocation {
+    let types = relocate_ids(
+        &target.type_path_to_id,
+        &source.type_path_to_id,
+        ty::TypeDeclId::Id::new(target.type_path_to_id.len()),
+    );
+    let funs = relocate_ids(
+        &target.fun_path_to_id,
+        &source.fun_path_to_id,
+        ast::FunDeclId::Id::new(target.fun_path_to_id.len()),
+    );
+    let globals = relocate_ids(
+        &target.global_path_to_id,
+        &source.global_path_to_id,
+        ast::GlobalDeclId::Id::new(target.global_path_to_id.len()),
+    );
+    let traits = relocate_ids(
+        &target.trait_path_to_id,
+        &source.trait_path_to_id,
+        TraitDeclId::Id::new(target.trait_path_to_id.len()),
+    );
+    let trait_impls = relocate_ids(
+        &target.trait_impl_path_to_id,
+        &source.trait_impl_path_to_id,
+        TraitImplId::Id::new(target.trait_impl_path_to_id.len()),
+    );
+
+    // The files are identified by their names
+    let num_local_files = target
+        .id_to_file
+        .keys()
+        .filter(|id| id.is_local_id())
+        .count();
+    let num_virtual_files = target.id_to_file.len() - num_local_files;
+    let mut next_local_id = LocalFileId::Id::new(num_local_files);
+    let mut next_virtual_id = VirtualFileId::Id::new(num_virtual_files);
+    let mut files = HashMap::new();
+    for file in &source.files {
+        let id = *source.file_to_id.get(file).unwrap();
+        let nid = match (target.file_to_id.get(file), id) {
+            (Some(nid), _) => *nid,
+            (None, FileId::Id::LocalId(_)) => {
+                let nid = next_local_id;
+                next_local_id.incr();
+                FileId::Id::LocalId(nid)
+            }
+            (None, FileId::Id::VirtualId(_)) => {
+                let nid = next_virtual_id;
+                next_virtual_id.incr();
+                FileId::Id::VirtualId(nid)
+            }
+        };
+        files.insert(id, nid);
+    }
+
+    DeclsRelocation {
+        types,
+        funs,
+        globals,
+        traits,
+        trait_impls,
+        files,
+    }
+}
+
+/// The identifiers (in `source`) of the declarations shared with `target`
+fn shared_ids<Id: Copy + Eq + Hash>(
+    target: &HashMap<String, Id>,
+    source: &HashMap<String, Id>,
+) -> HashSet<Id> {
+    source
+        .iter()
+        .filter(|(path, _)| target.contains_key(*path))
+        .map(|(_, id)| *id)
+        .collect()
+}
+
+/// Relocate the identifiers of a declaration group, and remove the
+/// declarations which are shared with the target crate. Return `None` if
+/// the group becomes empty.
+fn relocate_group<Id: Copy + Eq + Hash>(
+    group: &GDeclarationGroup<Id>,
+    relocation: &HashMap<Id, Id>,
+    shared: &HashSet<Id>,
+) -> Option<GDeclarationGroup<Id>> {
+    match group {
+        GDeclarationGroup::NonRec(id) => {
+            if shared.contains(id) {
+                None
+            } else {
+                Some(GDeclarationGroup::NonRec(*relocation.get(id).unwrap()))
+            }
+        }
+        GDeclarationGroup::Rec(ids) => {
+            let ids: Vec<Id> = ids
+                .iter()
+                .filter(|id| !shared.contains(*id))
+                .map(|id| *relocation.get(id).unwrap())
+                .collect();
+            if ids.is_empty() {
+                None
+            } else {
+                Some(GDeclarationGroup::Rec(ids))
+            }
+        }
+    }
+}
+
+/// Merge the declarations of two separately translated crates.
+///
+/// The identifiers of `b` are relocated after the ones of `a`. The declarations
+/// present in both (typically, the external dependencies used by the two
+/// crates) are identified by their paths, and are kept only once, with the
+/// identifiers they have in `a`.
+///
+/// The two crates are translated in different compiler sessions: the Rust
+/// identifiers of `b` are meaningless in the session of `a` (the local items
+/// of both crates, for instance, belong to the crate number 0). The merged
+/// declarations thus only map the Rust identifiers of `a`, and the
+/// declaration information coming from `b` refers to the Rust identifiers
+/// of `b`'s session.
+///
+/// Use [compute_relocation] and [crate::relocate_decls] to update the
+/// translated declarations of `b`.
+pub fn merge_ordered_decls(a: OrderedDecls, b: OrderedDecls) -> OrderedDecls {
+    let relocation = compute_relocation(&a, &b);
+    let shared_types = shared_ids(&a.type_path_to_id, &b.type_path_to_id);
+    let shared_funs = shared_ids(&a.fun_path_to_id, &b.fun_path_to_id);
+    let shared_globals = shared_ids(&a.global_path_to_id, &b.global_path_to_id);
+
+    let OrderedDecls {
+        mut files,
+        mut decls,
+        mut decls_info,
+        mut file_to_id,
+        mut id_to_file,
+        type_rid_to_id,
+        fun_rid_to_id,
+        global_rid_to_id,
+        trait_rid_to_id,
+        trait_impl_rid_to_id,
+        mut type_path_to_id,
+        mut fun_path_to_id,
+        mut global_path_to_id,
+        mut trait_path_to_id,
+        mut trait_impl_path_to_id,
+    } = a;
+
+    // The declaration groups
+    for group in &b.decls {
+        let group = match group {
+            DeclarationGroup::Type(group) => {
+                relocate_group(group, &relocation.types, &shared_types).map(DeclarationGroup::Type)
+            }
+            DeclarationGroup::Fun(group) => {
+                relocate_group(group, &relocation.funs, &shared_funs).map(DeclarationGroup::Fun)
+            }
+            DeclarationGroup::Global(group) => {
+                relocate_group(group, &relocation.globals, &shared_globals)
+                    .map(DeclarationGroup::Global)
+            }
+        };
+        decls.extend(group);
+    }
+
+    // The declarations information
+    for (id, info) in &b.decls_info {
+        let nid = match id {
+            AnyDeclId::Type(id) => AnyDeclId::Type(*relocation.types.get(id).unwrap()),
+            AnyDeclId::Fun(id) => AnyDeclId::Fun(*relocation.funs.get(id).unwrap()),
+            AnyDeclId::Global(id) => AnyDeclId::Global(*relocation.globals.get(id).unwrap()),
+        };
+        let mut info = info.clone();
+        if let Some(trait_impl) = &mut info.trait_impl {
+            trait_impl.self_type = trait_impl
+                .self_type
+                .map(|id| *relocation.types.get(&id).unwrap());
+        }
+        decls_info.entry(nid).or_insert(info);
+    }
+
+    // The maps from paths
+    for (path, id) in &b.type_path_to_id {
+        type_path_to_id
+            .entry(path.clone())
+            .or_insert(*relocation.types.get(id).unwrap());
+    }
+    for (path, id) in &b.fun_path_to_id {
+        fun_path_to_id
+            .entry(path.clone())
+            .or_insert(*relocation.funs.get(id).unwrap());
+    }
+    for (path, id) in &b.global_path_to_id {
+        global_path_to_id
+            .entry(path.clone())
+            .or_insert(*relocation.globals.get(id).unwrap());
+    }
+    for (path, id) in &b.trait_path_to_id {
+        trait_path_to_id
+            .entry(path.clone())
+            .or_insert(*relocation.traits.get(id).unwrap());
+    }
+    for (path, id) in &b.trait_impl_path_to_id {
+        trait_impl_path_to_id
+            .entry(path.clone())
+            .or_insert(*relocation.trait_impls.get(id).unwrap());
+    }
+
+    // The files
+    for file in b.files {
+        if !file_to_id.contains_key(&file) {
+            let id = *relocation
+                .files
+                .get(b.file_to_id.get(&file).unwrap())
+                .unwrap();
+            file_to_id.insert(file.clone(), id);
+            id_to_file.insert(id, file.clone());
+            files.push(file);
+        }
+    }
+
     OrderedDecls {
         files,
         decls,
@@ -184,5 +802,355 @@ pub fn rust_to_local_ids(
         type_rid_to_id,
         fun_rid_to_id,
         global_rid_to_id,
+        trait_rid_to_id,
+        trait_impl_rid_to_id,
+        type_path_to_id,
+        fun_path_to_id,
+        global_path_to_id,
+        trait_path_to_id,
+        trait_impl_path_to_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reorder_decls as rd;
+    use crate::rust_to_local_ids::*;
+    use rustc_hir::def_id::{CrateNum, DefIndex, LOCAL_CRATE};
+
+    #[test]
+    fn test_decl_info_visibility() {
+        let info = rd::DeclInfo {
+            is_transparent: true,
+        };
+        let module = DefId {
+            krate: LOCAL_CRATE,
+            index: DefIndex::from_u32(1),
+        };
+        let crate_root = DefId {
+            krate: LOCAL_CRATE,
+            index: CRATE_DEF_INDEX,
+        };
+        // `pub fn f() { ... }`
+        let pub_fn = DefId {
+            krate: LOCAL_CRATE,
+            index: DefIndex::from_u32(2),
+        };
+        let pub_fn = DeclInfo::new(pub_fn, info, RustVisibility::Public, module);
+        // `fn g() { ... }` (in `module`)
+        let priv_fn = DefId {
+            krate: LOCAL_CRATE,
+            index: DefIndex::from_u32(3),
+        };
+        let priv_fn = DeclInfo::new(priv_fn, info, RustVisibility::Restricted(module), module);
+        assert!(pub_fn.visibility == Visibility::Public);
+        assert!(priv_fn.visibility == Visibility::Private);
+        assert!(pub_fn.visibility != priv_fn.visibility);
+
+        // `pub(crate) fn h() { ... }` (in `module`)
+        let crate_fn = DefId {
+            krate: LOCAL_CRATE,
+            index: DefIndex::from_u32(4),
+        };
+        let crate_fn = DeclInfo::new(
+            crate_fn,
+            info,
+            RustVisibility::Restricted(crate_root),
+            module,
+        );
+        assert!(crate_fn.visibility == Visibility::Crate);
+
+        // `fn k() { ... }` (in the crate root): it is restricted to the crate
+        // root, but it is private
+        let root_fn = DefId {
+            krate: LOCAL_CRATE,
+            index: DefIndex::from_u32(5),
+        };
+        let root_fn = DeclInfo::new(
+            root_fn,
+            info,
+            RustVisibility::Restricted(crate_root),
+            crate_root,
+        );
+        assert!(root_fn.visibility == Visibility::Private);
+
+        // The root of another crate is not the root of the crate of the
+        // declaration
+        let other_root = DefId {
+            krate: CrateNum::from_u32(1),
+            index: CRATE_DEF_INDEX,
+        };
+        let other_fn = DefId {
+            krate: LOCAL_CRATE,
+            index: DefIndex::from_u32(6),
+        };
+        let other_fn = DeclInfo::new(
+            other_fn,
+            info,
+            RustVisibility::Restricted(other_root),
+            module,
+        );
+        assert!(other_fn.visibility == Visibility::Private);
+    }
+
+    /// Build the declarations of a crate defined in a single file, and
+    /// containing (non-recursive) types and functions, given by their Rust
+    /// identifiers and their paths
+    fn mk_ordered_decls(
+        file: &str,
+        types: &[(DefId, &str)],
+        funs: &[(DefId, &str)],
+    ) -> OrderedDecls {
+        let info = rd::DeclInfo {
+            is_transparent: true,
+        };
+        let mut decls = Vec::new();
+        let mut decls_info = HashMap::new();
+        let mut type_rid_to_id = HashMap::new();
+        let mut fun_rid_to_id = HashMap::new();
+        let mut type_path_to_id = HashMap::new();
+        let mut fun_path_to_id = HashMap::new();
+        let crate_root = DefId {
+            krate: LOCAL_CRATE,
+            index: CRATE_DEF_INDEX,
+        };
+        for (i, (rid, path)) in types.iter().enumerate() {
+            let id = TypeDeclId::Id::new(i);
+            decls.push(DeclarationGroup::Type(GDeclarationGroup::NonRec(id)));
+            let info = DeclInfo::new(*rid, info, RustVisibility::Public, crate_root);
+            decls_info.insert(AnyDeclId::Type(id), info);
+            type_rid_to_id.insert(*rid, id);
+            type_path_to_id.insert(path.to_string(), id);
+        }
+        for (i, (rid, path)) in funs.iter().enumerate() {
+            let id = FunDeclId::Id::new(i);
+            decls.push(DeclarationGroup::Fun(GDeclarationGroup::NonRec(id)));
+            let info = DeclInfo::new(*rid, info, RustVisibility::Public, crate_root);
+            decls_info.insert(AnyDeclId::Fun(id), info);
+            fun_rid_to_id.insert(*rid, id);
+            fun_path_to_id.insert(path.to_string(), id);
+        }
+        let file = FileName::Local(std::path::PathBuf::from(file));
+        let file_id = FileId::Id::LocalId(LocalFileId::ZERO);
+        OrderedDecls {
+            files: vec![file.clone()],
+            decls,
+            decls_info,
+            file_to_id: HashMap::from([(file.clone(), file_id)]),
+            id_to_file: HashMap::from([(file_id, file)]),
+            type_rid_to_id,
+            fun_rid_to_id,
+            global_rid_to_id: HashMap::new(),
+            trait_rid_to_id: HashMap::new(),
+            trait_impl_rid_to_id: HashMap::new(),
+            type_path_to_id,
+            fun_path_to_id,
+            global_path_to_id: HashMap::new(),
+            trait_path_to_id: HashMap::new(),
+            trait_impl_path_to_id: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_merge_ordered_decls() {
+        use crate::expressions::{Operand, Place};
+        use crate::llbc_ast::RawStatement;
+        use crate::test_utils::*;
+        use crate::types::{Ty, TypeId};
+        use crate::ullbc_ast::{FunId, VarId};
+
+        fn adt<R: Clone + Eq>(id: usize) -> Ty<R> {
+            let id = TypeDeclId::Id::new(id);
+            Ty::Adt(TypeId::Adt(id), im::Vector::new(), im::Vector::new())
+        }
+        fn adt_id<R: Clone + Eq>(ty: &Ty<R>) -> TypeDeclId::Id {
+            match ty {
+                Ty::Adt(TypeId::Adt(id), _, _) => *id,
+                _ => unreachable!(),
+            }
+        }
+
+        // The two crates are translated in different compiler sessions: their
+        // local items have the same Rust identifiers, and the crate numbers
+        // of their common dependency differ
+        let local = |index: u32| DefId {
+            krate: LOCAL_CRATE,
+            index: DefIndex::from_u32(index),
+        };
+        let dep = |krate: u32| DefId {
+            krate: CrateNum::from_u32(krate),
+            index: DefIndex::from_u32(7),
+        };
+        // `fn f() { ... }` in the first crate, `struct T` and
+        // `fn g(x: dep::Shared) -> T { ... }` in the second one
+        let a = mk_ordered_decls(
+            "a/lib.rs",
+            &[(dep(2), "dep::Shared")],
+            &[(local(1), "a::f")],
+        );
+        let b = mk_ordered_decls(
+            "b/lib.rs",
+            &[(local(1), "b::T"), (dep(5), "dep::Shared")],
+            &[(local(1), "b::g")],
+        );
+
+        let mut relocation = compute_relocation(&a, &b);
+        let merged = merge_ordered_decls(a, b);
+        let f_id = FunDeclId::Id::new(0);
+        let g_id = FunDeclId::Id::new(1);
+        let shared_id = TypeDeclId::Id::new(0);
+        let t_id = TypeDeclId::Id::new(1);
+        assert!(merged.fun_path_to_id.get("a::f") == Some(&f_id));
+        assert!(merged.fun_path_to_id.get("b::g") == Some(&g_id));
+        assert!(merged.decls_info.contains_key(&AnyDeclId::Fun(g_id)));
+        // Only the Rust identifiers of the first crate are mapped
+        assert!(merged.fun_rid_to_id.len() == 1);
+        assert!(merged.fun_rid_to_id.get(&local(1)) == Some(&f_id));
+        // The shared type is present only once
+        assert!(merged.type_path_to_id.len() == 2);
+        assert!(merged.type_path_to_id.get("dep::Shared") == Some(&shared_id));
+        assert!(merged.type_path_to_id.get("b::T") == Some(&t_id));
+        // The files
+        let b_file = FileId::Id::LocalId(LocalFileId::Id::new(1));
+        assert!(merged.files.len() == 2);
+        assert!(merged.id_to_file.get(&b_file) == Some(&merged.files[1]));
+        // The declaration groups
+        let groups: Vec<AnyDeclId> = merged
+            .decls
+            .iter()
+            .map(|group| match group {
+                DeclarationGroup::Type(GDeclarationGroup::NonRec(id)) => AnyDeclId::Type(*id),
+                DeclarationGroup::Fun(GDeclarationGroup::NonRec(id)) => AnyDeclId::Fun(*id),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert!(
+            groups
+                == vec![
+                    AnyDeclId::Type(shared_id),
+                    AnyDeclId::Fun(f_id),
+                    AnyDeclId::Type(t_id),
+                    AnyDeclId::Fun(g_id),
+                ]
+        );
+
+        // Relocate `g`, which calls itself:
+        // ```text
+        // fn g(x: dep::Shared) -> T { @0 := g(move x) }
+        // ```
+        let sig = mk_sig(vec![adt(1)], adt(0));
+        let call = mk_call(
+            FunId::Regular(FunDeclId::Id::new(0)),
+            vec![Operand::Move(Place::new(VarId::Id::new(1)))],
+            Place::new(VarId::ZERO),
+        );
+        let body = mk_body(
+            1,
+            vec![mk_var(0, None, adt(0)), mk_var(1, Some("x"), adt(1))],
+            vec![RawStatement::Call(call)],
+        );
+        let mut g = mk_fun_decl(0, &["b", "g"], sig, Some(body));
+        relocation.relocate_fun_decl(&mut g);
+        assert!(g.def_id == g_id);
+        assert!(g.meta.span.file_id == b_file);
+        assert!(adt_id(&g.signature.inputs[0]) == shared_id);
+        assert!(adt_id(&g.signature.output) == t_id);
+        let body = g.body.unwrap();
+        assert!(adt_id(&body.locals.get(VarId::ZERO).unwrap().ty) == t_id);
+        assert!(adt_id(&body.locals.get(VarId::Id::new(1)).unwrap().ty) == shared_id);
+        match &body.body.content {
+            RawStatement::Call(call) => assert!(*call.func.as_regular() == g_id),
+            _ => unreachable!(),
+        }
+        assert!(body.body.meta.span.file_id == b_file);
+    }
+
+    #[test]
+    fn test_symbol_table() {
+        let name = |s: &[&str]| Name::from(s.iter().map(|s| s.to_string()).collect());
+        let parser = name(&["utils", "Parser"]);
+        let parse = name(&["utils", "parse"]);
+        let swap = name(&["core", "mem", "swap"]);
+        let ty = AnyDeclId::Type(TypeDeclId::Id::new(2));
+        let fun = AnyDeclId::Fun(FunDeclId::Id::new(5));
+        let mut symbols = SymbolTable::new();
+        assert!(symbols.is_empty());
+        symbols.add_crate(
+            "utils",
+            vec![
+                (&parser, ty),
+                (&parse, fun),
+                (&swap, AnyDeclId::Fun(FunDeclId::Id::new(0))),
+            ]
+            .into_iter(),
+        );
+
+        let local = AnyDeclId::Fun(FunDeclId::Id::new(7));
+        let resolved = symbols.resolve(&parse, local).unwrap();
+        assert!(resolved.krate == "utils" && resolved.id == fun);
+        // The kinds of the declarations must match
+        assert!(symbols.resolve(&parser, local).is_none());
+        // `utils` only declares `swap` as opaque: we don't link it
+        assert!(symbols.resolve(&swap, local).is_none());
+    }
+
+    /// Type-check the crate given by its source and call `f` on the
+    /// resulting context
+    fn with_tcx<T: Send>(src: &str, f: impl for<'tcx> FnOnce(TyCtxt<'tcx>) -> T + Send) -> T {
+        use rustc_session::config::{CrateType, Input, Options};
+        let config = rustc_interface::Config {
+            opts: Options {
+                crate_types: vec![CrateType::Rlib],
+                ..Options::default()
+            },
+            crate_cfg: Default::default(),
+            crate_check_cfg: Default::default(),
+            input: Input::Str {
+                name: rustc_span::FileName::Custom("test".to_string()),
+                input: src.to_string(),
+            },
+            input_path: None,
+            output_dir: None,
+            output_file: None,
+            file_loader: None,
+            lint_caps: Default::default(),
+            parse_sess_created: None,
+            register_lints: None,
+            override_queries: None,
+            make_codegen_backend: None,
+            registry: rustc_errors::registry::Registry::new(&[]),
+        };
+        rustc_interface::run_compiler(config, |compiler| {
+            compiler.enter(|queries| queries.global_ctxt().unwrap().peek_mut().enter(f))
+        })
+    }
+
+    #[test]
+    fn test_trait_impl_info() {
+        let src = "#[derive(Clone, Copy)] pub struct Pair { x: u32, y: u32 }";
+        with_tcx(src, |tcx| {
+            let find = |name: &str| {
+                tcx.hir()
+                    .body_owners()
+                    .map(|id| id.to_def_id())
+                    .chain(tcx.hir().items().map(|item| item.owner_id.to_def_id()))
+                    .find(|id| tcx.opt_item_name(*id).map_or(false, |s| s.as_str() == name))
+                    .unwrap()
+            };
+            let pair = find("Pair");
+            let clone = find("clone");
+            let mut type_rid_to_id = HashMap::new();
+            type_rid_to_id.insert(pair, TypeDeclId::Id::new(3));
+
+            // The derived `clone` is tagged with the `Clone` trait and the
+            // `Pair` type
+            let info = get_trait_impl_info(tcx, &type_rid_to_id, clone).unwrap();
+            assert!(info.trait_name.equals_ref_name(&["core", "clone", "Clone"]));
+            assert!(info.method_name == "clone");
+            assert!(info.self_type == Some(TypeDeclId::Id::new(3)));
+            assert!(info.is_derived);
+            // The structure is not a method
+            assert!(get_trait_impl_info(tcx, &type_rid_to_id, pair).is_none());
+        })
     }
 }
@@ -11,35 +11,69 @@
 //! checks everywhere. When compiling in release mode, it seems it only introduces
 //! checks for division by zero.
 //!
-//! TODO: use [crate::llbc_ast_utils::transform_statements]
-
-use take_mut::take;
+//! If the statements don't have the shape we expect, we fail on the body
+//! with a [TranslationError], which the driver reports (see
+//! [crate::passes::PassPipeline::run]).
+//!
+//! We simplify the bodies in place: we only rebuild the groups of statements
+//! we collapse.
 
+use crate::errors::{TransResult, TranslationError};
 use crate::expressions::*;
 use crate::llbc_ast::{
     new_sequence, Assert, CtxNames, FunDecls, GlobalDecls, RawStatement, Statement, Switch,
 };
-use crate::meta::combine_meta;
+use crate::meta::{combine_meta, Meta};
 use crate::types::*;
-use crate::ullbc_ast::{iter_function_bodies, iter_global_bodies};
+use crate::passes::PassResult;
+use crate::ullbc_ast::par_try_iter_bodies;
 use crate::values::*;
-use std::iter::FromIterator;
+use std::collections::HashSet;
 
 /// Small utility: assert that a boolean is true, or return false
 macro_rules! assert_or_return {
     ($cond:expr $(,)?) => {{
         if !$cond {
-            return false;
+            return Ok(false);
         }
     }};
     ($cond:expr, $($arg:tt)+) => {{
         if !$cond {
             trace!("assert_or_return failed: {}", $arg);
-            return false;
+            return Ok(false);
         }
     }};
 }
 
+/// Small utility: check that a boolean is true, or fail with an error at the
+/// given location
+macro_rules! check_or_fail {
+    ($cond:expr, $meta:expr, $msg:expr $(,)?) => {{
+        if !$cond {
+            return Err(TranslationError::new($meta, $msg));
+        }
+    }};
+}
+
+/// Return the value of an operand if it is a constant scalar
+fn as_scalar_constant(op: &Operand) -> Option<&ScalarValue> {
+    match op {
+        Operand::Const(_, OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(v))) => {
+            Some(v)
+        }
+        _ => None,
+    }
+}
+
+/// Return true iff the operand is a non-zero constant integer
+fn is_non_zero_constant(op: &Operand) -> bool {
+    match as_scalar_constant(op) {
+        Some(cv) if cv.is_uint() => cv.as_uint().unwrap() != 0,
+        Some(cv) => cv.as_int().unwrap() != 0,
+        None => false,
+    }
+}
+
 /// Return true iff: `place ++ [pelem] == full_place`
 fn check_places_similar_but_last_proj_elem(
     place: &Place,
@@ -62,7 +96,7 @@ fn check_places_similar_but_last_proj_elem(
 
 /// Return true if the binary operation might fail and thus requires its result
 /// to be checked (overflows, for instance).
-fn binop_requires_assert_after(binop: BinOp) -> bool {
+pub(crate) fn binop_requires_assert_after(binop: BinOp) -> bool {
     match binop {
         BinOp::BitXor
         | BinOp::BitAnd
@@ -74,7 +108,8 @@ fn binop_requires_assert_after(binop: BinOp) -> bool {
         | BinOp::Ge
         | BinOp::Gt
         | BinOp::Div
-        | BinOp::Rem => false,
+        | BinOp::Rem
+        | BinOp::Offset => false,
         BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Shl | BinOp::Shr => true,
     }
 }
@@ -85,11 +120,6 @@ fn unop_requires_assert_before(unop: UnOp) -> bool {
     match unop {
         UnOp::Not => false,
         UnOp::Neg => true,
-        UnOp::Cast(_, _) => {
-            // This case is peculiar, because rustc doesn't insert assertions
-            // while it can actually fail
-            false
-        }
     }
 }
 
@@ -97,10 +127,6 @@ fn unop_can_fail(unop: UnOp) -> bool {
     match unop {
         UnOp::Not => false,
         UnOp::Neg => true,
-        UnOp::Cast(_, _) => {
-            // See [unop_requires_assert_before]
-            false
-        }
     }
 }
 
@@ -121,7 +147,8 @@ fn binop_requires_assert_before(binop: BinOp) -> bool {
         | BinOp::Sub
         | BinOp::Mul
         | BinOp::Shl
-        | BinOp::Shr => false,
+        | BinOp::Shr
+        | BinOp::Offset => false,
         BinOp::Div | BinOp::Rem => true,
     }
 }
@@ -138,7 +165,7 @@ fn check_if_assert_then_unop<R>(
     st1: &Statement<R>,
     st2: &Statement<R>,
     st3: &Statement<R>,
-) -> bool {
+) -> TransResult<bool> {
     match &st3.content {
         RawStatement::Assign(_, Rvalue::UnaryOp(unop, _)) => {
             if unop_requires_assert_before(*unop) {
@@ -154,10 +181,10 @@ fn check_if_assert_then_unop<R>(
                 // If it is note the case, we can't collapse...
                 check_if_simplifiable_assert_then_unop(release, st1, st2, st3)
             } else {
-                false
+                Ok(false)
             }
         }
-        _ => false,
+        _ => Ok(false),
     }
 }
 
@@ -176,7 +203,7 @@ fn check_if_simplifiable_assert_then_unop<R>(
     st1: &Statement<R>,
     st2: &Statement<R>,
     st3: &Statement<R>,
-) -> bool {
+) -> TransResult<bool> {
     match (&st1.content, &st2.content, &st3.content) {
         (
             RawStatement::Assign(
@@ -193,6 +220,7 @@ fn check_if_simplifiable_assert_then_unop<R>(
             RawStatement::Assert(Assert {
                 cond: Operand::Move(cond_op),
                 expected,
+                kind: _,
             }),
             RawStatement::Assign(_mp, Rvalue::UnaryOp(unop, op1)),
         ) => {
@@ -205,17 +233,20 @@ fn check_if_simplifiable_assert_then_unop<R>(
             // Check the two operands:
             // - either they are (copy, move)
             // - or they are the same constant
+            let msg = "the negated operand is not the operand we checked for overflow";
             match (op, op1) {
-                (Operand::Copy(p), Operand::Move(p1)) => assert!(p == p1),
-                (Operand::Const(_, cv), Operand::Const(_, cv1)) => assert!(cv == cv1),
+                (Operand::Copy(p), Operand::Move(p1)) => check_or_fail!(p == p1, st3.meta, msg),
+                (Operand::Const(_, cv), Operand::Const(_, cv1)) => {
+                    check_or_fail!(cv == cv1, st3.meta, msg)
+                }
                 _ => {
-                    assert!(release);
-                    return false;
+                    check_or_fail!(release, st3.meta, msg);
+                    return Ok(false);
                 }
             }
 
             assert_or_return!(saturated.is_int() && saturated.is_min());
-            true
+            Ok(true)
         }
         (
             _,
@@ -235,12 +266,16 @@ fn check_if_simplifiable_assert_then_unop<R>(
             // Case 2: no assertion to check that there will not be an overflow:
             // - either we are in release mode
             // - or the value must be a constant which will not lead to an overflow.
-            assert!(!release || (value.is_int() && !value.is_min()));
-            false
+            check_or_fail!(
+                !release || (value.is_int() && !value.is_min()),
+                st3.meta,
+                "the negation of a constant may overflow, but is not checked"
+            );
+            Ok(false)
         }
         _ => {
-            assert!(release);
-            false
+            check_or_fail!(release, st3.meta, "the negation is not checked for overflow");
+            Ok(false)
         }
     }
 }
@@ -275,7 +310,7 @@ fn check_if_binop_then_assert<R>(
     st1: &Statement<R>,
     st2: &Statement<R>,
     st3: &Statement<R>,
-) -> bool {
+) -> TransResult<bool> {
     match &st1.content {
         RawStatement::Assign(_, Rvalue::BinaryOp(binop, _, _)) => {
             if binop_requires_assert_after(*binop) {
@@ -291,10 +326,10 @@ fn check_if_binop_then_assert<R>(
                 // If it is note the case, we can't collapse...
                 check_if_simplifiable_binop_then_assert(release, st1, st2, st3)
             } else {
-                false
+                Ok(false)
             }
         }
-        _ => false,
+        _ => Ok(false),
     }
 }
 
@@ -311,13 +346,14 @@ fn check_if_simplifiable_binop_then_assert<R>(
     st1: &Statement<R>,
     st2: &Statement<R>,
     st3: &Statement<R>,
-) -> bool {
+) -> TransResult<bool> {
     match (&st1.content, &st2.content, &st3.content) {
         (
             RawStatement::Assign(bp, Rvalue::BinaryOp(binop, _op1, _op2)),
             RawStatement::Assert(Assert {
                 cond: Operand::Move(cond_op),
                 expected,
+                kind: _,
             }),
             RawStatement::Assign(_mp, Rvalue::Use(Operand::Move(mr))),
         ) => {
@@ -340,11 +376,12 @@ fn check_if_simplifiable_binop_then_assert<R>(
                 mr,
             );
             assert_or_return!(check2);
-            true
+            Ok(true)
         }
         _ => {
-            assert!(release);
-            false
+            let msg = "the checked binary operation is not followed by an overflow check";
+            check_or_fail!(release, st1.meta, msg);
+            Ok(false)
         }
     }
 }
@@ -363,7 +400,9 @@ fn check_if_simplifiable_binop_then_assert<R>(
 ///   ```
 /// Note that the type of the binop changes in the two situations (in the
 /// translation, before the transformation `+` returns a pair (bool, int),
-/// after it has a monadic type).
+/// after it has a monadic type). The callers record the location of the
+/// simplified binop, so that we can warn the users about this change (see
+/// [simplify]).
 fn simplify_binop_then_assert<R>(st1: Statement<R>, st2: Statement<R>, st3: Statement<R>) -> Statement<R> {
     match (st1.content, st2.content, st3.content) {
         (RawStatement::Assign(_, binop), RawStatement::Assert(_), RawStatement::Assign(mp, _)) => {
@@ -384,7 +423,7 @@ fn check_if_assert_then_binop<R>(
     st1: &Statement<R>,
     st2: &Statement<R>,
     st3: &Statement<R>,
-) -> bool {
+) -> TransResult<bool> {
     match &st3.content {
         RawStatement::Assign(_, Rvalue::BinaryOp(binop, _, _)) => {
             if binop_requires_assert_before(*binop) {
@@ -412,10 +451,10 @@ fn check_if_assert_then_binop<R>(
                 //   ```
                 check_if_simplifiable_assert_then_binop(release, st1, st2, st3)
             } else {
-                false
+                Ok(false)
             }
         }
-        _ => false,
+        _ => Ok(false),
     }
 }
 
@@ -432,7 +471,7 @@ fn check_if_simplifiable_assert_then_binop<R>(
     st1: &Statement<R>,
     st2: &Statement<R>,
     st3: &Statement<R>,
-) -> bool {
+) -> TransResult<bool> {
     match (&st1.content, &st2.content, &st3.content) {
         (
             RawStatement::Assign(
@@ -449,6 +488,7 @@ fn check_if_simplifiable_assert_then_binop<R>(
             RawStatement::Assert(Assert {
                 cond: Operand::Move(cond_op),
                 expected,
+                kind: _,
             }),
             RawStatement::Assign(_mp, Rvalue::BinaryOp(binop, _dividend, Operand::Move(divisor))),
         ) => {
@@ -462,7 +502,7 @@ fn check_if_simplifiable_assert_then_binop<R>(
             } else {
                 assert_or_return!(zero.as_uint().unwrap() == 0);
             }
-            true
+            Ok(true)
         }
         (
             RawStatement::Assign(
@@ -479,6 +519,7 @@ fn check_if_simplifiable_assert_then_binop<R>(
             RawStatement::Assert(Assert {
                 cond: Operand::Move(cond_op),
                 expected,
+                kind: _,
             }),
             RawStatement::Assign(_mp, Rvalue::BinaryOp(binop, _dividend, divisor1)),
         ) => {
@@ -492,8 +533,8 @@ fn check_if_simplifiable_assert_then_binop<R>(
                     OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(_)),
                 ) => (),
                 _ => {
-                    assert!(release);
-                    return false;
+                    check_or_fail!(release, st3.meta, "the divisor is not a scalar constant");
+                    return Ok(false);
                 }
             }
             assert_or_return!(divisor1 == divisor);
@@ -504,23 +545,17 @@ fn check_if_simplifiable_assert_then_binop<R>(
             } else {
                 assert_or_return!(zero.as_uint().unwrap() == 0);
             }
-            true
+            Ok(true)
         }
-        (_, _, RawStatement::Assign(_mp, Rvalue::BinaryOp(_, _, Operand::Const(_, divisor)))) => {
+        (_, _, RawStatement::Assign(_mp, Rvalue::BinaryOp(_, _, divisor @ Operand::Const(..)))) => {
             // Case 3: no assertion to check the divisor != 0, the divisor must be a
             // non-zero constant integer
-            let cv = divisor.as_primitive_value();
-            let cv = cv.as_scalar();
-            if cv.is_uint() {
-                assert_or_return!(cv.as_uint().unwrap() != 0)
-            } else {
-                assert_or_return!(cv.as_int().unwrap() != 0)
-            };
-            false
+            assert_or_return!(is_non_zero_constant(divisor));
+            Ok(false)
         }
         _ => {
-            assert!(release);
-            false
+            check_or_fail!(release, st3.meta, "the divisor is not checked to be non-zero");
+            Ok(false)
         }
     }
 }
@@ -541,73 +576,126 @@ fn simplify_assert_then_binop<R>(_st1: Statement<R>, _st2: Statement<R>, st3: St
     st3
 }
 
-/// Attempt to simplify a sequence of statemnets
-fn simplify_st_seq<R>(
+/// The groups of statements we collapse (see [simplify_st_seq])
+enum Simplification {
+    AssertThenUnop,
+    BinopThenAssert,
+    AssertThenBinop,
+}
+
+/// Attempt to simplify a sequence of statements `st1; st2; st3; ...`, in
+/// place
+fn simplify_st_seq(
     release: bool,
-    st1: Statement<R>,
-    st2: Statement<R>,
-    st3: Statement<R>,
-    st4: Option<Statement<R>>,
-) -> Statement<R> {
-    // Try to simplify
-    let simpl_st = {
-        // Simplify checked unops (negation)
-        if check_if_assert_then_unop(release, &st1, &st2, &st3) {
-            simplify_assert_then_unop(st1, st2, st3)
-        }
-        // Simplify checked binops
-        else if check_if_binop_then_assert(release, &st1, &st2, &st3) {
-            simplify_binop_then_assert(st1, st2, st3)
-        }
-        // Simplify unchecked binops (division, modulo)
-        else if check_if_assert_then_binop(release, &st1, &st2, &st3) {
-            simplify_assert_then_binop(st1, st2, st3)
-        } else {
-            // Not simplifyable
-            let next_st = match st4 {
-                Option::Some(st4) => new_sequence(st3, st4),
-                Option::None => st3,
+    float_vars: &HashSet<VarId::Id>,
+    type_changes: &mut Vec<Meta>,
+    st: &mut Statement,
+) -> TransResult<()> {
+    // Check if the first three statements can be collapsed
+    let simplification = match &st.content {
+        RawStatement::Sequence(st1, next) => match &next.content {
+            RawStatement::Sequence(st2, next) => {
+                let st3 = match &next.content {
+                    RawStatement::Sequence(st3, _) => st3,
+                    _ => next,
+                };
+                // Simplify checked unops (negation)
+                if check_if_assert_then_unop(release, st1, st2, st3)? {
+                    Some(Simplification::AssertThenUnop)
+                }
+                // Simplify checked binops
+                else if check_if_binop_then_assert(release, st1, st2, st3)? {
+                    Some(Simplification::BinopThenAssert)
+                }
+                // Simplify unchecked binops (division, modulo)
+                else if check_if_assert_then_binop(release, st1, st2, st3)? {
+                    Some(Simplification::AssertThenBinop)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        },
+        _ => unreachable!(),
+    };
+
+    let simplification = match simplification {
+        Some(simplification) => simplification,
+        None => {
+            // Not simplifyable: explore the sub-statements
+            return match &mut st.content {
+                RawStatement::Sequence(st1, st2) => {
+                    simplify_st(release, float_vars, type_changes, st1)?;
+                    simplify_st(release, float_vars, type_changes, st2)
+                }
+                _ => unreachable!(),
             };
-            let next_st = new_sequence(st2, next_st);
-            return new_sequence(simplify_st(release, st1), simplify_st(release, next_st));
         }
     };
 
+    // Split the sequence into the statements we collapse and the statement
+    // after, if there is
+    let (st1, next) = std::mem::replace(&mut st.content, RawStatement::Nop).to_sequence();
+    let (st2, next) = next.content.to_sequence();
+    let (st3, st4) = match next.content {
+        RawStatement::Sequence(st3, st4) => (*st3, Some(*st4)),
+        content => (Statement::new(next.meta, content), None),
+    };
+    let simpl_st = match simplification {
+        Simplification::AssertThenUnop => simplify_assert_then_unop(*st1, *st2, st3),
+        Simplification::BinopThenAssert => {
+            let st = simplify_binop_then_assert(*st1, *st2, st3);
+            type_changes.push(st.meta);
+            st
+        }
+        Simplification::AssertThenBinop => simplify_assert_then_binop(*st1, *st2, st3),
+    };
+
     // Combine the simplified statements with the statement after, if there is
-    match st4 {
-        Option::Some(st4) => {
-            let st4 = simplify_st(release, st4);
+    *st = match st4 {
+        Some(mut st4) => {
+            simplify_st(release, float_vars, type_changes, &mut st4)?;
             new_sequence(simpl_st, st4)
         }
-        Option::None => simpl_st,
-    }
+        None => simpl_st,
+    };
+    Ok(())
 }
 
-// TODO: don't consume `st`, use mutable borrows
-fn simplify_st(release: bool, st: Statement) -> Statement {
-    let content = match st.content {
+/// Simplify a statement in place.
+///
+/// `float_vars`: the local variables of type float. The operations on the
+/// floats never fail, and are not preceded or followed by checks.
+fn simplify_st(
+    release: bool,
+    float_vars: &HashSet<VarId::Id>,
+    type_changes: &mut Vec<Meta>,
+    st: &mut Statement,
+) -> TransResult<()> {
+    let meta = st.meta;
+    match &mut st.content {
         RawStatement::Assign(p, rv) => {
-            // Check that we never failed to simplify a binop
-            match &rv {
+            // Check that we never failed to simplify a binop (note that the
+            // destination of an operation is always a local in MIR)
+            let is_float = p.projection.is_empty() && float_vars.contains(&p.var_id);
+            match rv {
+                _ if is_float => (),
                 Rvalue::BinaryOp(binop, _, divisor) => {
                     // If it is an unsimplified binop, it must be / or %
                     // and the divisor must be a non-zero constant integer,
                     // unless we compile for release
                     if binop_can_fail(*binop) {
                         match binop {
-                            BinOp::Div | BinOp::Rem => {
-                                let (_, cv) = divisor.as_const();
-                                let cv = cv.as_primitive_value();
-                                let cv = cv.as_scalar();
-                                if cv.is_uint() {
-                                    assert!(cv.as_uint().unwrap() != 0)
-                                } else {
-                                    assert!(cv.as_int().unwrap() != 0)
-                                };
-                            }
-                            _ => {
-                                assert!(release);
-                            }
+                            BinOp::Div | BinOp::Rem => check_or_fail!(
+                                is_non_zero_constant(divisor),
+                                meta,
+                                "the divisor is not checked to be non-zero"
+                            ),
+                            _ => check_or_fail!(
+                                release,
+                                meta,
+                                format!("the operation {binop:?} is not checked for overflow")
+                            ),
                         }
                     }
                 }
@@ -623,11 +711,13 @@ fn simplify_st(release: bool, st: Statement) -> Statement {
                                 if release {
                                     // nothing to do
                                 } else {
-                                    let (_, cv) = v.as_const();
-                                    let cv = cv.as_primitive_value();
-                                    let cv = cv.as_scalar();
-                                    assert!(cv.is_int());
-                                    assert!(!cv.is_min());
+                                    let no_overflow = as_scalar_constant(v)
+                                        .map_or(false, |cv| cv.is_int() && !cv.is_min());
+                                    check_or_fail!(
+                                        no_overflow,
+                                        meta,
+                                        "the negation is not checked for overflow"
+                                    );
                                 }
                             }
                             _ => {
@@ -638,84 +728,162 @@ fn simplify_st(release: bool, st: Statement) -> Statement {
                 }
                 _ => (),
             }
-            RawStatement::Assign(p, rv)
         }
-        RawStatement::FakeRead(p) => RawStatement::FakeRead(p),
-        RawStatement::SetDiscriminant(p, vid) => RawStatement::SetDiscriminant(p, vid),
-        RawStatement::Drop(p) => RawStatement::Drop(p),
-        RawStatement::Assert(assert) => RawStatement::Assert(assert),
-        RawStatement::Call(call) => RawStatement::Call(call),
-        RawStatement::Panic => RawStatement::Panic,
-        RawStatement::Return => RawStatement::Return,
-        RawStatement::Break(i) => RawStatement::Break(i),
-        RawStatement::Continue(i) => RawStatement::Continue(i),
-        RawStatement::Nop => RawStatement::Nop,
-        RawStatement::Switch(switch) => {
-            let switch = match switch {
-                Switch::If(op, st1, st2) => Switch::If(
-                    op,
-                    Box::new(simplify_st(release, *st1)),
-                    Box::new(simplify_st(release, *st2)),
-                ),
-                Switch::SwitchInt(op, int_ty, targets, mut otherwise) => {
-                    let targets = Vec::from_iter(
-                        targets
-                            .into_iter()
-                            .map(|(v, e)| (v, simplify_st(release, e))),
-                    );
-                    *otherwise = simplify_st(release, *otherwise);
-                    Switch::SwitchInt(op, int_ty, targets, otherwise)
-                }
-                Switch::Match(_, _, _) => {
-                    // We shouldn't get there: those are introduced later, in [remove_read_discriminant]
-                    unreachable!();
+        RawStatement::CompoundAssign(_, _, _)
+        | RawStatement::FakeRead(_)
+        | RawStatement::SetDiscriminant(_, _)
+        | RawStatement::Drop(_)
+        | RawStatement::Assert(_)
+        | RawStatement::Call(_)
+        | RawStatement::Panic
+        | RawStatement::Return
+        | RawStatement::Break(_)
+        | RawStatement::Continue(_)
+        | RawStatement::Nop => (),
+        RawStatement::Switch(switch) => match switch {
+            Switch::If(_, st1, st2) => {
+                simplify_st(release, float_vars, type_changes, st1)?;
+                simplify_st(release, float_vars, type_changes, st2)?;
+            }
+            Switch::SwitchInt(_, _, targets, otherwise) => {
+                for (_, st) in targets.iter_mut() {
+                    simplify_st(release, float_vars, type_changes, st)?;
                 }
-            };
-            RawStatement::Switch(switch)
+                simplify_st(release, float_vars, type_changes, otherwise)?;
+            }
+            Switch::Match(_, _, _, _) => {
+                // We shouldn't get there: those are introduced later, in
+                // [remove_read_discriminant]
+                let msg = "unexpected match: the matches are only reconstructed later";
+                return Err(TranslationError::new(meta, msg));
+            }
+        },
+        RawStatement::Loop(loop_body, _) => {
+            simplify_st(release, float_vars, type_changes, loop_body)?;
         }
-        RawStatement::Loop(loop_body) => {
-            RawStatement::Loop(Box::new(simplify_st(release, *loop_body)))
+        RawStatement::While(_, _, _, _) => {
+            let msg = "unexpected while loop: the while loops are only reconstructed later";
+            return Err(TranslationError::new(meta, msg));
         }
-        RawStatement::Sequence(st1, st2) => match st2.content {
-            RawStatement::Sequence(st2, st3) => match st3.content {
-                RawStatement::Sequence(st3, st4) => {
-                    simplify_st_seq(release, *st1, *st2, *st3, Option::Some(*st4)).content
-                }
-                st3_raw => {
-                    // Below: the fact that we moved the value is very annoying
-                    simplify_st_seq(
-                        release,
-                        *st1,
-                        *st2,
-                        Statement::new(st3.meta, st3_raw),
-                        Option::None,
-                    )
-                    .content
-                }
-            },
-            st2_raw => RawStatement::Sequence(
-                Box::new(simplify_st(release, *st1)),
-                // Below: the fact that we moved the value is very annoying
-                Box::new(simplify_st(release, Statement::new(st2.meta, st2_raw))),
-            ),
-        },
-    };
-
-    Statement::new(st.meta, content)
+        RawStatement::Sequence(_, _) => {
+            simplify_st_seq(release, float_vars, type_changes, st)?;
+        }
+    }
+    Ok(())
 }
 
+/// If `warn_type_changes` is true, we emit a warning for every checked binop
+/// whose type changed from `(T, bool)` to `T` because we removed its overflow
+/// check, so that the users can audit those changes.
+///
 /// `fmt_ctx` is used for pretty-printing purposes.
 pub fn simplify(
     release: bool,
+    warn_type_changes: bool,
     fmt_ctx: &CtxNames<'_>,
     funs: &mut FunDecls,
     globals: &mut GlobalDecls,
-) {
-    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+) -> PassResult {
+    let errors = par_try_iter_bodies(funs, globals, |name, b| {
         trace!(
             "# About to simplify operands in decl: {name}:\n{}",
             b.fmt_with_ctx_names(fmt_ctx)
         );
-        take(&mut b.body, |b| simplify_st(release, b));
+        let float_vars: HashSet<VarId::Id> = b
+            .locals
+            .iter()
+            .filter(|v| v.ty.is_float())
+            .map(|v| v.index)
+            .collect();
+        let mut type_changes = Vec::new();
+        // If we fail, the driver drops the whole body: we don't need to
+        // restore the statements we were collapsing
+        simplify_st(release, &float_vars, &mut type_changes, &mut b.body)?;
+        if warn_type_changes {
+            for meta in type_changes {
+                log::warn!(
+                    "In {name}: the checked binary operation at line {}, column {} now has type `T` instead of `(T, bool)`",
+                    meta.span.beg.line, meta.span.beg.col
+                );
+            }
+        }
+        Ok(())
+    });
+    errors.into_result()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simplify_ops::*;
+    use crate::ullbc_ast::AssertKind;
+
+    fn mk_st(content: RawStatement) -> Statement {
+        Statement::new(Meta::dummy(), content)
+    }
+
+    fn mk_seq(sts: Vec<RawStatement>) -> Statement {
+        let mut sts = sts.into_iter().rev().map(mk_st);
+        let last = sts.next().unwrap();
+        sts.fold(last, |seq, st| new_sequence(st, seq))
+    }
+
+    #[test]
+    fn test_type_changes() {
+        let x = Operand::Copy(Place::new(VarId::Id::new(1)));
+        let y = Operand::Copy(Place::new(VarId::Id::new(2)));
+        let tmp = Place::new(VarId::Id::new(3));
+        let z = Place::new(VarId::Id::new(4));
+        let tmp_field = |i: usize| {
+            let mut p = tmp.clone();
+            p.projection.push_back(ProjectionElem::Field(
+                FieldProjKind::Tuple(2),
+                FieldId::Id::new(i),
+            ));
+            p
+        };
+
+        // The checked addition:
+        // ```
+        // tmp := copy x + copy y;
+        // assert(move (tmp.1) == false);
+        // z := move (tmp.0);
+        // return
+        // ```
+        let mut st = mk_seq(vec![
+            RawStatement::Assign(tmp.clone(), Rvalue::BinaryOp(BinOp::Add, x.clone(), y.clone())),
+            RawStatement::Assert(Assert {
+                cond: Operand::Move(tmp_field(1)),
+                expected: false,
+                kind: AssertKind::Overflow,
+            }),
+            RawStatement::Assign(z.clone(), Rvalue::Use(Operand::Move(tmp_field(0)))),
+            RawStatement::Return,
+        ]);
+        let mut type_changes = Vec::new();
+        simplify_st(false, &HashSet::new(), &mut type_changes, &mut st).unwrap();
+        // ```
+        // z := copy x + copy y;
+        // return
+        // ```
+        let (st1, _) = st.content.as_sequence();
+        assert!(
+            matches!(&st1.content, RawStatement::Assign(p, Rvalue::BinaryOp(BinOp::Add, _, _)) if *p == z)
+        );
+        assert!(type_changes.len() == 1);
+
+        // The comparisons are not checked: their type doesn't change
+        // ```
+        // tmp := copy x < copy y;
+        // z := move tmp;
+        // return
+        // ```
+        let mut st = mk_seq(vec![
+            RawStatement::Assign(tmp.clone(), Rvalue::BinaryOp(BinOp::Lt, x, y)),
+            RawStatement::Assign(z, Rvalue::Use(Operand::Move(tmp))),
+            RawStatement::Return,
+        ]);
+        let mut type_changes = Vec::new();
+        simplify_st(false, &HashSet::new(), &mut type_changes, &mut st).unwrap();
+        assert!(type_changes.is_empty());
     }
 }
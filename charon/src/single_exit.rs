@@ -0,0 +1,197 @@
+//! Normalize the placement of the `return` statements, for the backends which
+//! prefer the functions to have a single exit point.
+//!
+//! In LLBC, the returned value is stored in the return local (`_0`) before we
+//! return: whenever all the `return` statements of a body are in tail
+//! position, we can remove them and insert a single `return` at the end of
+//! the body. For instance:
+//! ```text
+//! if copy b {
+//!   _0 := copy x;
+//!   return;
+//! }
+//! else {
+//!   _0 := copy y;
+//!   return;
+//! }
+//! ```
+//! becomes:
+//! ```text
+//! if copy b {
+//!   _0 := copy x;
+//! }
+//! else {
+//!   _0 := copy y;
+//! }
+//! return;
+//! ```
+//!
+//! The diverging branches (`panic`) are left untouched, as they never reach
+//! the trailing `return`. We don't modify the bodies containing a `return`
+//! in a non-tail position (for instance, an early return followed by other
+//! statements, or a return inside a loop): this would require introducing
+//! control-flow flags.
+
+use crate::llbc_ast::{
+    new_sequence, visit_statements, CtxNames, FunDecls, GlobalDecls, RawStatement, Statement,
+    Switch,
+};
+use crate::ullbc_ast::{iter_function_bodies, iter_global_bodies};
+use take_mut::take;
+
+fn count_returns(st: &Statement) -> usize {
+    let mut count = 0;
+    visit_statements(
+        &mut |st: &Statement| {
+            if st.content.is_return() {
+                count += 1
+            }
+        },
+        st,
+    );
+    count
+}
+
+/// Return true if all the `return` statements are in tail position
+fn returns_only_in_tail(st: &Statement) -> bool {
+    match &st.content {
+        RawStatement::Return => true,
+        RawStatement::Sequence(st1, st2) => count_returns(st1) == 0 && returns_only_in_tail(st2),
+        RawStatement::Switch(switch) => switch.get_targets().into_iter().all(returns_only_in_tail),
+        _ => count_returns(st) == 0,
+    }
+}
+
+/// Remove the `return` statements in tail position
+fn remove_tail_returns(st: Statement) -> Statement {
+    let content = match st.content {
+        RawStatement::Return => RawStatement::Nop,
+        // We don't leave `nop`s at the end of the sequences
+        RawStatement::Sequence(st1, st2) if st2.content.is_return() => st1.content,
+        RawStatement::Sequence(st1, st2) => {
+            RawStatement::Sequence(st1, Box::new(remove_tail_returns(*st2)))
+        }
+        RawStatement::Switch(switch) => {
+            let switch = match switch {
+                Switch::If(op, st1, st2) => Switch::If(
+                    op,
+                    Box::new(remove_tail_returns(*st1)),
+                    Box::new(remove_tail_returns(*st2)),
+                ),
+                Switch::SwitchInt(op, int_ty, branches, otherwise) => {
+                    let branches = branches
+                        .into_iter()
+                        .map(|(values, st)| (values, remove_tail_returns(st)))
+                        .collect();
+                    let otherwise = Box::new(remove_tail_returns(*otherwise));
+                    Switch::SwitchInt(op, int_ty, branches, otherwise)
+                }
+                Switch::Match(p, branches, otherwise, provenance) => {
+                    let branches = branches
+                        .into_iter()
+                        .map(|(variants, st)| (variants, remove_tail_returns(st)))
+                        .collect();
+                    let otherwise = Box::new(remove_tail_returns(*otherwise));
+                    Switch::Match(p, branches, otherwise, provenance)
+                }
+            };
+            RawStatement::Switch(switch)
+        }
+        content => content,
+    };
+    Statement::new(st.meta, content)
+}
+
+fn transform_body(body: Statement) -> Statement {
+    if count_returns(&body) > 1 && returns_only_in_tail(&body) {
+        let meta = body.meta;
+        new_sequence(
+            remove_tail_returns(body),
+            Statement::new(meta, RawStatement::Return),
+        )
+    } else {
+        body
+    }
+}
+
+/// `fmt_ctx` is used for pretty-printing purposes.
+pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
+    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+        trace!(
+            "# About to move the returns to the end of the body in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        take(&mut b.body, transform_body);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::*;
+    use crate::llbc_ast::chain_statements;
+    use crate::meta::Meta;
+    use crate::single_exit::*;
+    use crate::values::*;
+
+    fn mk_st(content: RawStatement) -> Statement {
+        Statement::new(Meta::dummy(), content)
+    }
+
+    /// `_0 := copy x; return`
+    fn mk_return(x: VarId::Id) -> Statement {
+        let assign = RawStatement::Assign(
+            Place::new(VarId::ZERO),
+            Rvalue::Use(Operand::Copy(Place::new(x))),
+        );
+        chain_statements(vec![mk_st(assign)], mk_st(RawStatement::Return))
+    }
+
+    #[test]
+    fn test_if_return() {
+        let b = VarId::Id::new(1);
+        let x = VarId::Id::new(2);
+        let y = VarId::Id::new(3);
+        // ```
+        // if copy b { _0 := copy x; return } else { _0 := copy y; return }
+        // ```
+        let body = mk_st(RawStatement::Switch(Switch::If(
+            Operand::Copy(Place::new(b)),
+            Box::new(mk_return(x)),
+            Box::new(mk_return(y)),
+        )));
+        let body = transform_body(body);
+
+        // ```
+        // if copy b { _0 := copy x } else { _0 := copy y };
+        // return
+        // ```
+        assert!(count_returns(&body) == 1);
+        let (switch, ret) = body.content.as_sequence();
+        assert!(ret.content.is_return());
+        let (st1, st2) = match &switch.content {
+            RawStatement::Switch(Switch::If(_, st1, st2)) => (st1, st2),
+            _ => panic!(),
+        };
+        assert!(
+            matches!(&st1.content, RawStatement::Assign(p, Rvalue::Use(Operand::Copy(p1))) if *p == Place::new(VarId::ZERO) && *p1 == Place::new(x))
+        );
+        assert!(
+            matches!(&st2.content, RawStatement::Assign(p, Rvalue::Use(Operand::Copy(p1))) if *p == Place::new(VarId::ZERO) && *p1 == Place::new(y))
+        );
+
+        // An early return followed by other statements is left unchanged:
+        // ```
+        // if copy b { _0 := copy x; return } else { nop };
+        // _0 := copy y;
+        // return
+        // ```
+        let switch = mk_st(RawStatement::Switch(Switch::If(
+            Operand::Copy(Place::new(b)),
+            Box::new(mk_return(x)),
+            Box::new(mk_st(RawStatement::Nop)),
+        )));
+        let body = new_sequence(switch, mk_return(y));
+        let body = transform_body(body);
+        assert!(count_returns(&body) == 2);
+    }
+}
@@ -0,0 +1,294 @@
+//! Reconstruct the slicing operations (`&v[a..b]`).
+//!
+//! Slicing an array, a slice or a vector goes through a call to an `Index`
+//! (or `IndexMut`) trait method, with a range argument built right before the
+//! call:
+//! ```text
+//! r := &v;
+//! range := core::ops::range::Range { start: const 1, end: const 3 };
+//! s := core::ops::index::Index::index(move r, move range);
+//! ```
+//! We rewrite those calls to a structured [Rvalue::Slice]:
+//! ```text
+//! r := &v;
+//! s := &(*r)[const 1..const 3];
+//! ```
+//!
+//! The open-ended ranges (`&v[a..]`, `&v[..b]`, `&v[..]`) are handled by
+//! slicing from `0` and/or up to the length of the sliced value.
+//!
+//! We must perform this transformation before [crate::index_accesses], which
+//! handles the other calls to the `Index` trait methods. Note that the
+//! [Subslice](rustc_middle::mir::ProjectionElem::Subslice) projections, which
+//! come from the slice patterns (`[x, rest @ ..]`), are not supported yet.
+
+use crate::assumed;
+use crate::expressions::*;
+use crate::index_accesses::get_vec_index_borrow_kind;
+use crate::llbc_ast::{
+    chain_statements, new_sequence, transform_statements, Call, CtxNames, FunDecls, GlobalDecls,
+    RawStatement, Statement,
+};
+use crate::meta::Meta;
+use crate::types::*;
+use crate::ullbc_ast::{iter_function_bodies, iter_global_bodies, make_locals_generator};
+use crate::values::*;
+use take_mut::take;
+
+/// The bounds of a range. The `None` bounds are open.
+struct RangeBounds {
+    var_id: VarId::Id,
+    from: Option<Operand>,
+    to: Option<Operand>,
+}
+
+/// If the statement is of the shape `r := Range { ... }` (or `RangeFrom`,
+/// `RangeTo`, `RangeFull`), return the bounds of the range.
+fn get_range_bounds(type_defs: &TypeDecls, st: &Statement) -> Option<RangeBounds> {
+    let (p, id, ops) = match &st.content {
+        RawStatement::Assign(p, Rvalue::Aggregate(AggregateKind::Adt(id, None, _, _), ops))
+            if p.projection.is_empty() =>
+        {
+            (p, id, ops)
+        }
+        _ => return None,
+    };
+    let name = &type_defs.get_type_def(*id).unwrap().name;
+    let (from, to) = if name.equals_ref_name(&assumed::RANGE_NAME) {
+        (Some(ops[0].clone()), Some(ops[1].clone()))
+    } else if name.equals_ref_name(&assumed::RANGE_FROM_NAME) {
+        (Some(ops[0].clone()), None)
+    } else if name.equals_ref_name(&assumed::RANGE_TO_NAME) {
+        (None, Some(ops[0].clone()))
+    } else if name.equals_ref_name(&assumed::RANGE_FULL_NAME) {
+        (None, None)
+    } else {
+        return None;
+    };
+    Some(RangeBounds {
+        var_id: p.var_id,
+        from,
+        to,
+    })
+}
+
+/// Return true if the statement is an indexing with the range stored in `range`
+fn is_slice_call(st: &Statement, range: VarId::Id) -> bool {
+    let st = match &st.content {
+        RawStatement::Sequence(st1, _) => st1,
+        _ => st,
+    };
+    match &st.content {
+        RawStatement::Call(call) => {
+            get_vec_index_borrow_kind(call).is_some()
+                && call.args.len() == 2
+                && call.args[1] == Operand::Move(Place::new(range))
+        }
+        _ => false,
+    }
+}
+
+fn transform_call<F: FnMut(ETy) -> VarId::Id>(
+    make_new_var: &mut F,
+    meta: Meta,
+    mut call: Call,
+    range: RangeBounds,
+) -> Statement {
+    let kind = get_vec_index_borrow_kind(&call).unwrap();
+    call.args.pop();
+    let mut base = match call.args.pop().unwrap() {
+        Operand::Move(p) | Operand::Copy(p) => p,
+        Operand::Const(_, _) => unreachable!(),
+    };
+    base.projection.push_back(ProjectionElem::Deref);
+
+    let mut sts = Vec::new();
+    let from = range.from.unwrap_or_else(|| {
+        Operand::Const(
+            Ty::Integer(IntegerTy::Usize),
+            OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(ScalarValue::Usize(0))),
+        )
+    });
+    let to = match range.to {
+        Some(to) => to,
+        None => {
+            let len = make_new_var(Ty::Integer(IntegerTy::Usize));
+            sts.push(Statement::new(
+                meta,
+                RawStatement::Assign(Place::new(len), Rvalue::Len(base.clone())),
+            ));
+            Operand::Move(Place::new(len))
+        }
+    };
+
+    let slice = Rvalue::Slice {
+        base,
+        from,
+        to,
+        kind,
+    };
+    let slice = Statement::new(meta, RawStatement::Assign(call.dest, slice));
+    chain_statements(sts, slice)
+}
+
+fn transform_st<F: FnMut(ETy) -> VarId::Id>(
+    type_defs: &TypeDecls,
+    make_new_var: &mut F,
+    st: Statement,
+) -> Statement {
+    let (st1, st2) = match st.content {
+        RawStatement::Sequence(st1, st2) => (st1, st2),
+        content => return Statement::new(st.meta, content),
+    };
+    let range = match get_range_bounds(type_defs, &st1) {
+        Some(range) if is_slice_call(&st2, range.var_id) => range,
+        _ => return Statement::new(st.meta, RawStatement::Sequence(st1, st2)),
+    };
+
+    // We don't need the range anymore: we remove its assignment
+    let (call, rest) = match st2.content {
+        RawStatement::Sequence(call, rest) => (*call, Some(*rest)),
+        content => (Statement::new(st2.meta, content), None),
+    };
+    let slice = transform_call(make_new_var, call.meta, call.content.to_call(), range);
+    match rest {
+        Some(rest) => new_sequence(slice, rest),
+        None => slice,
+    }
+}
+
+/// `fmt_ctx` is used for pretty-printing purposes.
+pub fn transform(
+    fmt_ctx: &CtxNames<'_>,
+    type_defs: &TypeDecls,
+    funs: &mut FunDecls,
+    globals: &mut GlobalDecls,
+) {
+    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+        trace!(
+            "# About to reconstruct the slicing operations in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        let mut make_new_var = make_locals_generator(&mut b.locals);
+        take(&mut b.body, |st| {
+            transform_statements(&mut |st| transform_st(type_defs, &mut make_new_var, st), st)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::meta::AttrInfo;
+    use crate::names::Name;
+    use crate::regions_hierarchy::RegionGroups;
+    use crate::slice_accesses::*;
+    use crate::ullbc_ast::{AssumedFunId, FunId};
+
+    fn mk_usize(v: usize) -> Operand {
+        Operand::Const(
+            Ty::Integer(IntegerTy::Usize),
+            OperandConstantValue::PrimitiveValue(PrimitiveValue::Scalar(ScalarValue::Usize(v))),
+        )
+    }
+
+    /// The (opaque) declaration of a range type
+    fn mk_range_decl(def_id: TypeDeclId::Id, name: &[&str]) -> TypeDecl {
+        TypeDecl {
+            def_id,
+            meta: Meta::dummy(),
+            name: Name::from(name.iter().map(|s| s.to_string()).collect::<Vec<String>>()),
+            region_params: RegionVarId::Vector::new(),
+            type_params: TypeVarId::Vector::new(),
+            const_generic_params: ConstGenericVarId::Vector::new(),
+            trait_clauses: Vec::new(),
+            regions_hierarchy: RegionGroups::new(),
+            kind: TypeDeclKind::Opaque,
+            repr_packed: None,
+            under_aligned_fields: Vec::new(),
+            recursive_fields: Vec::new(),
+            attr_info: AttrInfo::default(),
+        }
+    }
+
+    /// ```text
+    /// range := Range { ops };
+    /// s := core::ops::index::Index::index(move r, move range);
+    /// ```
+    fn mk_slice_call(range_id: TypeDeclId::Id, ops: Vec<Operand>) -> Statement {
+        let (r, range, s) = (VarId::Id::new(1), VarId::Id::new(2), VarId::Id::new(3));
+        let usize_ty = Ty::Integer(IntegerTy::Usize);
+        let range_kind = AggregateKind::Adt(range_id, None, Vec::new(), vec![usize_ty]);
+        let call = Call {
+            func: FunId::Assumed(AssumedFunId::VecIndex),
+            region_args: Vec::new(),
+            type_args: Vec::new(),
+            args: vec![
+                Operand::Move(Place::new(r)),
+                Operand::Move(Place::new(range)),
+            ],
+            dest: Place::new(s),
+            sig: None,
+        };
+        new_sequence(
+            Statement::new(
+                Meta::dummy(),
+                RawStatement::Assign(Place::new(range), Rvalue::Aggregate(range_kind, ops)),
+            ),
+            Statement::new(Meta::dummy(), RawStatement::Call(call)),
+        )
+    }
+
+    #[test]
+    fn test_slice() {
+        let mut type_defs = TypeDecls::new();
+        let range_id = TypeDeclId::ZERO;
+        let range_from_id = TypeDeclId::ONE;
+        type_defs
+            .types
+            .push_back(mk_range_decl(range_id, &assumed::RANGE_NAME));
+        type_defs
+            .types
+            .push_back(mk_range_decl(range_from_id, &assumed::RANGE_FROM_NAME));
+        let mut num_vars = 4;
+        let mut make_new_var = |_| {
+            num_vars += 1;
+            VarId::Id::new(num_vars - 1)
+        };
+        let r = VarId::Id::new(1);
+        let s = Place::new(VarId::Id::new(3));
+        let mut base = Place::new(r);
+        base.projection.push_back(ProjectionElem::Deref);
+
+        // `let s = &v[1..3];`
+        let st = mk_slice_call(range_id, vec![mk_usize(1), mk_usize(3)]);
+        let st = transform_statements(
+            &mut |st| transform_st(&type_defs, &mut make_new_var, st),
+            st,
+        );
+        // ```
+        // s := &(*r)[const 1..const 3];
+        // ```
+        assert!(
+            matches!(&st.content, RawStatement::Assign(p, Rvalue::Slice { base: b, from, to, kind: BorrowKind::Shared }) if *p == s && *b == base && *from == mk_usize(1) && *to == mk_usize(3))
+        );
+
+        // `let s = &v[1..];`
+        let st = mk_slice_call(range_from_id, vec![mk_usize(1)]);
+        let st = transform_statements(
+            &mut |st| transform_st(&type_defs, &mut make_new_var, st),
+            st,
+        );
+        // ```
+        // len := len(*r);
+        // s := &(*r)[const 1..move len];
+        // ```
+        let len = Place::new(VarId::Id::new(4));
+        let (st1, st2) = st.content.as_sequence();
+        assert!(
+            matches!(&st1.content, RawStatement::Assign(p, Rvalue::Len(b)) if *p == len && *b == base)
+        );
+        assert!(
+            matches!(&st2.content, RawStatement::Assign(p, Rvalue::Slice { from, to, .. }) if *p == s && *from == mk_usize(1) && *to == Operand::Move(len.clone()))
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Helpers to build the ASTs in the unit tests.
+//!
+//! The declarations we build have dummy spans and no generics: the tests
+//! which need more than this tweak the result.
+#![allow(dead_code)]
+
+use crate::contracts::FunContract;
+use crate::expressions::*;
+use crate::gast::*;
+use crate::llbc_ast;
+use crate::meta::{AttrInfo, Meta};
+use crate::names::Name;
+use crate::regions_hierarchy::RegionGroups;
+use crate::types::*;
+use crate::ullbc_ast;
+use crate::values::*;
+use serde::Serialize;
+
+/// A local variable
+pub fn mk_var(index: usize, name: Option<&str>, ty: ETy) -> Var {
+    Var {
+        index: VarId::Id::new(index),
+        name: name.map(|n| n.to_string()),
+        ty,
+    }
+}
+
+/// The signature of a function without generics
+pub fn mk_sig(inputs: Vec<RTy>, output: RTy) -> FunSig {
+    FunSig {
+        region_params: RegionVarId::Vector::new(),
+        num_early_bound_regions: 0,
+        regions_hierarchy: RegionGroups::new(),
+        type_params: TypeVarId::Vector::new(),
+        const_generic_params: ConstGenericVarId::Vector::new(),
+        trait_clauses: Vec::new(),
+        inputs,
+        output,
+    }
+}
+
+/// A function declaration, without precision losses, unsafe operations,
+/// attributes nor contract
+pub fn mk_fun_decl<T: std::fmt::Debug + Clone + Serialize>(
+    def_id: usize,
+    name: &[&str],
+    signature: FunSig,
+    body: Option<GExprBody<T>>,
+) -> GFunDecl<T> {
+    GFunDecl {
+        def_id: FunDeclId::Id::new(def_id),
+        meta: Meta::dummy(),
+        name: Name::from(name.iter().map(|s| s.to_string()).collect::<Vec<String>>()),
+        signature,
+        body,
+        precision_losses: Vec::new(),
+        unsafe_operations: Vec::new(),
+        attr_info: AttrInfo::default(),
+        contract: FunContract::default(),
+    }
+}
+
+/// The sequence of the given LLBC statements, which must not be empty
+pub fn mk_statements(sts: Vec<llbc_ast::RawStatement>) -> llbc_ast::Statement {
+    let mut sts: Vec<llbc_ast::Statement> = sts
+        .into_iter()
+        .map(|st| llbc_ast::Statement::new(Meta::dummy(), st))
+        .collect();
+    let last = sts.pop().unwrap();
+    llbc_ast::chain_statements(sts, last)
+}
+
+/// Flatten a sequence of LLBC statements
+pub fn flatten(st: &llbc_ast::Statement) -> Vec<&llbc_ast::RawStatement> {
+    match &st.content {
+        llbc_ast::RawStatement::Sequence(st1, st2) => {
+            let mut sts = flatten(st1);
+            sts.append(&mut flatten(st2));
+            sts
+        }
+        content => vec![content],
+    }
+}
+
+/// An LLBC body, whose return variable is `@0`
+pub fn mk_body(
+    arg_count: usize,
+    locals: Vec<Var>,
+    sts: Vec<llbc_ast::RawStatement>,
+) -> llbc_ast::ExprBody {
+    GExprBody {
+        meta: Meta::dummy(),
+        arg_count,
+        return_local: VarId::ZERO,
+        locals: VarId::Vector::from(locals),
+        body: mk_statements(sts),
+    }
+}
+
+/// An ULLBC block
+pub fn mk_block(
+    statements: Vec<ullbc_ast::RawStatement>,
+    terminator: ullbc_ast::RawTerminator,
+) -> ullbc_ast::BlockData {
+    ullbc_ast::BlockData {
+        statements: statements
+            .into_iter()
+            .map(|st| ullbc_ast::Statement::new(Meta::dummy(), st))
+            .collect(),
+        terminator: ullbc_ast::Terminator::new(Meta::dummy(), terminator),
+    }
+}
+
+/// An ULLBC body, whose return variable is `@0`
+pub fn mk_cfg(
+    arg_count: usize,
+    locals: Vec<Var>,
+    blocks: Vec<ullbc_ast::BlockData>,
+) -> ullbc_ast::ExprBody {
+    GExprBody {
+        meta: Meta::dummy(),
+        arg_count,
+        return_local: VarId::ZERO,
+        locals: VarId::Vector::from(locals),
+        body: ullbc_ast::BlockId::Vector::from(blocks),
+    }
+}
+
+/// A call without generic arguments, which is not annotated with the
+/// signature of the callee
+pub fn mk_call(func: FunId, args: Vec<Operand>, dest: Place) -> llbc_ast::Call {
+    llbc_ast::Call {
+        func,
+        region_args: Vec::new(),
+        type_args: Vec::new(),
+        args,
+        dest,
+        sig: None,
+    }
+}
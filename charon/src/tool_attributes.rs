@@ -0,0 +1,141 @@
+//! Handle the attributes with which users can annotate their definitions to
+//! guide the translation, like `#[charon::overflow_checks]`.
+//!
+//! Those are tool attributes: the driver registers the `charon` tool when
+//! calling the compiler (see [register_tool_args]). We also export them, with
+//! the doc comments, in the generated files (see [get_attr_info]): the users
+//! can annotate their definitions with specifications for the verification
+//! frontends (for instance, `#[charon::requires(x > 0)]`).
+
+use crate::meta::{AttrInfo, Attribute};
+use rustc_ast::ast::AttrKind;
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::TyCtxt;
+
+/// The name of the tool, used as prefix for the attributes
+pub const TOOL_NAME: &str = "charon";
+
+/// Insert the overflow checks after the arithmetic operations of the
+/// annotated function (see [crate::insert_overflow_checks]).
+pub const OVERFLOW_CHECKS: &str = "overflow_checks";
+
+/// Don't reconstruct the control-flow of the annotated function, and don't
+/// apply the micro-passes to it: export its ULLBC body instead (see
+/// [crate::ullbc_to_llbc::unstructured_functions]). This is useful to
+/// investigate the bugs of the passes.
+pub const NO_SIMPLIFY: &str = "no_simplify";
+
+/// The preconditions and the postconditions of the annotated function (see
+/// [crate::contracts]).
+pub const REQUIRES: &str = "requires";
+pub const ENSURES: &str = "ensures";
+
+/// An invariant of the annotated loop (see [crate::contracts])
+pub const INVARIANT: &str = "invariant";
+
+/// The builtin attributes we export, in addition to the `charon` tool
+/// attributes
+pub const KEPT_BUILTIN_ATTRIBUTES: [&str; 3] = ["inline", "cold", "must_use"];
+
+/// The compiler arguments needed to register the `charon` tool, so that the
+/// compiler accepts the `#[charon::...]` attributes.
+pub fn register_tool_args() -> Vec<String> {
+    vec![
+        "-Zcrate-attr=feature(register_tool)".to_string(),
+        format!("-Zcrate-attr=register_tool({TOOL_NAME})"),
+    ]
+}
+
+/// Return true if the definition is annotated with `#[charon::<name>]`
+pub fn has_attribute(tcx: TyCtxt, def_id: DefId, name: &str) -> bool {
+    tcx.get_attrs_unchecked(def_id)
+        .iter()
+        .any(|attr| match &attr.kind {
+            AttrKind::Normal(normal) => {
+                let segments = &normal.item.path.segments;
+                segments.len() == 2
+                    && segments[0].ident.as_str() == TOOL_NAME
+                    && segments[1].ident.as_str() == name
+            }
+            AttrKind::DocComment(_, _) => false,
+        })
+}
+
+/// Retrieve the arguments of an attribute from its source code, for instance
+/// `x > 0` from `#[charon::requires(x > 0)]`, or `"msg"` from
+/// `#[must_use = "msg"]`
+pub(crate) fn attribute_args(snippet: &str) -> Option<String> {
+    let attr = snippet
+        .trim()
+        .strip_prefix("#!")
+        .or_else(|| snippet.trim().strip_prefix('#'))?
+        .trim()
+        .strip_prefix('[')?
+        .strip_suffix(']')?;
+    // The path of the attribute is made of identifiers and `::`: the
+    // arguments begin at the first delimiter or `=`
+    let i = attr.find(|c| matches!(c, '(' | '[' | '{' | '='))?;
+    let args = if &attr[i..i + 1] == "=" {
+        &attr[i + 1..]
+    } else {
+        // Remove the delimiters
+        &attr[i + 1..attr.len() - 1]
+    };
+    Some(args.trim().to_string())
+}
+
+/// Retrieve the doc comments and the attributes of a definition (see
+/// [AttrInfo])
+pub fn get_attr_info(tcx: TyCtxt, def_id: DefId) -> AttrInfo {
+    let mut info = AttrInfo::default();
+    for attr in tcx.get_attrs_unchecked(def_id) {
+        if let Some(doc) = attr.doc_str() {
+            info.doc_comments.push(doc.to_string());
+            continue;
+        }
+        let normal = match &attr.kind {
+            AttrKind::Normal(normal) => normal,
+            AttrKind::DocComment(_, _) => continue,
+        };
+        let path: Vec<String> = normal
+            .item
+            .path
+            .segments
+            .iter()
+            .map(|seg| seg.ident.to_string())
+            .collect();
+        let kept = match path.as_slice() {
+            [tool, _] => tool == TOOL_NAME,
+            [name] => KEPT_BUILTIN_ATTRIBUTES.contains(&name.as_str()),
+            _ => false,
+        };
+        if kept {
+            // We don't have the source code of the external definitions
+            let args = tcx
+                .sess
+                .source_map()
+                .span_to_snippet(attr.span)
+                .ok()
+                .and_then(|snippet| attribute_args(&snippet));
+            info.attributes.push(Attribute { path, args });
+        }
+    }
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tool_attributes::*;
+
+    #[test]
+    fn test_attribute_args() {
+        assert!(attribute_args("#[charon::opaque]").is_none());
+        assert!(attribute_args("#[charon::requires(x > 0)]") == Some("x > 0".to_string()));
+        assert!(attribute_args("#[inline(always)]") == Some("always".to_string()));
+        assert!(
+            attribute_args("#[charon::ensures(result == f(x, [0; 2]))]")
+                == Some("result == f(x, [0; 2])".to_string())
+        );
+        assert!(attribute_args("#[must_use = \"msg\"]") == Some("\"msg\"".to_string()));
+    }
+}
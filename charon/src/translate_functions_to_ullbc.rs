@@ -5,7 +5,9 @@
 
 #![allow(dead_code)]
 use crate::assumed;
+use crate::closures;
 use crate::common::*;
+use crate::contracts;
 use crate::expressions as e;
 use crate::formatter::Formatter;
 use crate::generics;
@@ -20,6 +22,8 @@ use crate::names::{function_def_id_to_name, type_def_id_to_name};
 use crate::regions_hierarchy as rh;
 use crate::regions_hierarchy::TypesConstraintsMap;
 use crate::rust_to_local_ids::*;
+use crate::tool_attributes;
+use crate::translate_traits;
 use crate::translate_types;
 use crate::types as ty;
 use crate::types::{FieldId, VariantId};
@@ -38,7 +42,7 @@ use rustc_middle::ty as mir_ty;
 use rustc_middle::ty::{ConstKind, Ty, TyCtxt, TyKind};
 use rustc_session::Session;
 use rustc_span::Span;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::iter::zip;
 use std::iter::FromIterator;
 use translate_types::{translate_erased_region, translate_region_name, TypeTransContext};
@@ -95,6 +99,13 @@ struct BodyTransContext<'tcx, 'ctx, 'ctx1> {
     /// Redundant with `rtype_vars_to_ids`. We need this for [translate_types::translate_ety].
     /// This maps type variables to types with erased regions.
     rtype_vars_to_etypes: im::OrdMap<u32, ty::ETy>,
+    /// Id counter for the const generic variables
+    const_generic_vars_counter: ty::ConstGenericVarId::Generator,
+    /// The const generic variables
+    const_generic_vars: ty::ConstGenericVarId::Vector<ty::ConstGenericVar>,
+    /// The map from rust const generic variable indices to translated const
+    /// generic variable indices.
+    rconst_generic_vars_to_ids: im::OrdMap<u32, ty::ConstGenericVarId::Id>,
     /// Id counter for the variables
     vars_counter: v::VarId::Generator,
     /// The "regular" variables
@@ -111,6 +122,12 @@ struct BodyTransContext<'tcx, 'ctx, 'ctx1> {
     /// Note that when translating terminators like DropAndReplace, we might have
     /// to introduce new blocks which don't appear in the original MIR.
     rblocks_to_ids: im::OrdMap<BasicBlock, ast::BlockId::Id>,
+    /// The approximations we performed while translating the body.
+    precision_losses: Vec<ast::PrecisionLoss>,
+    /// The invariants of the loops of the body, indexed by the spans of the
+    /// loops (see [crate::contracts]). We remove them once we attached them
+    /// to the terminators entering the loops.
+    loop_invariants: HashMap<Span, Vec<contracts::Spec>>,
 }
 
 impl<'tcx, 'ctx> DeclTransContext<'tcx, 'ctx> {
@@ -118,6 +135,20 @@ impl<'tcx, 'ctx> DeclTransContext<'tcx, 'ctx> {
         self.ordered.fun_rid_to_id.get(&def_id).copied()
     }
 
+    /// If the function is a method defined in a trait implementation, return
+    /// the identifier of this implementation
+    fn get_trait_impl_id_from_method(&self, def_id: DefId) -> Option<ast::TraitImplId::Id> {
+        let impl_id = self.tcx.impl_of_method(def_id)?;
+        self.ordered.trait_impl_rid_to_id.get(&impl_id).copied()
+    }
+
+    /// If the function is a method declared in a trait, return the identifier
+    /// of this trait
+    fn get_trait_decl_id_from_method(&self, def_id: DefId) -> Option<ast::TraitDeclId::Id> {
+        let trait_id = self.tcx.trait_of_item(def_id)?;
+        self.ordered.trait_rid_to_id.get(&trait_id).copied()
+    }
+
     fn get_def_rid_from_id(&self, def_id: ast::FunDeclId::Id) -> Option<DefId> {
         self.ordered
             .decls_info
@@ -140,12 +171,17 @@ impl<'tcx, 'ctx, 'ctx1> BodyTransContext<'tcx, 'ctx, 'ctx1> {
             rtype_vars_to_ids: im::OrdMap::new(),
             rtype_vars_to_rtypes: im::OrdMap::new(),
             rtype_vars_to_etypes: im::OrdMap::new(),
+            const_generic_vars_counter: ty::ConstGenericVarId::Generator::new(),
+            const_generic_vars: ty::ConstGenericVarId::Vector::new(),
+            rconst_generic_vars_to_ids: im::OrdMap::new(),
             vars_counter: v::VarId::Generator::new(),
             vars: v::VarId::Vector::new(),
             rvars_to_ids: im::OrdMap::new(),
             blocks_counter: ast::BlockId::Generator::new(),
             blocks: im::OrdMap::new(),
             rblocks_to_ids: im::OrdMap::new(),
+            precision_losses: Vec::new(),
+            loop_invariants: HashMap::new(),
         }
     }
 
@@ -173,12 +209,19 @@ impl<'tcx, 'ctx, 'ctx1> BodyTransContext<'tcx, 'ctx, 'ctx1> {
         r: rustc_middle::ty::RegionKind<'tcx>,
         name: Option<String>,
     ) -> ty::RegionVarId::Id {
+        let rid = self.push_fresh_region(name);
+        self.rregions_to_ids.insert(r, rid);
+        rid
+    }
+
+    /// Introduce a region which doesn't come from a rust region (we use it for
+    /// the regions of the closure environments)
+    fn push_fresh_region(&mut self, name: Option<String>) -> ty::RegionVarId::Id {
         use crate::id_vector::ToUsize;
         let rid = self.regions_counter.fresh_id();
         assert!(rid.to_usize() == self.regions.len());
         let var = ty::RegionVar { index: rid, name };
         self.regions.insert(rid, var);
-        self.rregions_to_ids.insert(r, rid);
         rid
     }
 
@@ -199,6 +242,19 @@ impl<'tcx, 'ctx, 'ctx1> BodyTransContext<'tcx, 'ctx, 'ctx1> {
         var_id
     }
 
+    fn push_const_generic_var(&mut self, rindex: u32, name: String, ty: ty::ETy) {
+        use crate::id_vector::ToUsize;
+        let var_id = self.const_generic_vars_counter.fresh_id();
+        assert!(var_id.to_usize() == self.const_generic_vars.len());
+        let var = ty::ConstGenericVar {
+            index: var_id,
+            name,
+            ty,
+        };
+        self.const_generic_vars.insert(var_id, var);
+        self.rconst_generic_vars_to_ids.insert(rindex, var_id);
+    }
+
     fn push_var(&mut self, rid: u32, ty: ty::ETy, name: Option<String>) {
         use crate::id_vector::ToUsize;
         let var_id = self.vars_counter.fresh_id();
@@ -290,20 +346,30 @@ impl<'tcx, 'ctx, 'ctx1> Formatter<&ty::Ty<ty::ErasedRegion>>
     }
 }
 
-fn translate_ety(bt_ctx: &BodyTransContext<'_, '_, '_>, ty: &mir_ty::Ty) -> Result<ty::ETy> {
+fn translate_ety<'tcx>(
+    bt_ctx: &BodyTransContext<'tcx, '_, '_>,
+    ty: &mir_ty::Ty<'tcx>,
+) -> Result<ty::ETy> {
     let ty_ctx = TypeTransContext::new(bt_ctx.ft_ctx.type_defs, bt_ctx.ft_ctx.ordered);
-    translate_types::translate_ety(bt_ctx.ft_ctx.tcx, &ty_ctx, &bt_ctx.rtype_vars_to_etypes, ty)
+    translate_types::translate_ety(
+        bt_ctx.ft_ctx.tcx,
+        &ty_ctx,
+        &bt_ctx.rtype_vars_to_etypes,
+        &bt_ctx.rconst_generic_vars_to_ids,
+        ty,
+    )
 }
 
-fn translate_ety_kind(
-    bt_ctx: &BodyTransContext<'_, '_, '_>,
-    ty: &mir_ty::TyKind,
+fn translate_ety_kind<'tcx>(
+    bt_ctx: &BodyTransContext<'tcx, '_, '_>,
+    ty: &mir_ty::TyKind<'tcx>,
 ) -> Result<ty::ETy> {
     let ty_ctx = TypeTransContext::new(bt_ctx.ft_ctx.type_defs, bt_ctx.ft_ctx.ordered);
     translate_types::translate_ety_kind(
         bt_ctx.ft_ctx.tcx,
         &ty_ctx,
         &bt_ctx.rtype_vars_to_etypes,
+        &bt_ctx.rconst_generic_vars_to_ids,
         ty,
     )
 }
@@ -318,6 +384,7 @@ fn translate_sig_ty<'tcx>(
         &ty_ctx,
         &bt_ctx.rregions_to_ids,
         &bt_ctx.rtype_vars_to_rtypes,
+        &bt_ctx.rconst_generic_vars_to_ids,
         ty,
     )
 }
@@ -385,16 +452,78 @@ fn translate_basic_block<'tcx>(
     if let Some(id) = id {
         return Ok(*id);
     }
-    let nid = bt_ctx.fresh_block_id(block_id);
+    let first_id = bt_ctx.fresh_block_id(block_id);
+    // The id of the block we are currently filling
+    let mut nid = first_id;
 
     // Retrieve the block data
     let block = body.basic_blocks.get(block_id).unwrap();
 
+    // The boxes which are initialized in place (`#[rustc_box] Box::new(v)`,
+    // used by `vec![...]` for instance) are compiled to:
+    // ```text
+    // size := SizeOf(T);
+    // align := AlignOf(T);
+    // p := alloc::alloc::exchange_malloc(move size, move align) -> bb1;
+    //
+    // bb1:
+    // b := ShallowInitBox(move p, T);
+    // (*b) := v;
+    // ```
+    // We translate this to `b := Box::new(v)`: we ignore the computation of
+    // the layout and the allocation, and split the block at the
+    // initialization, because the calls are terminators.
+    let mut init_boxes: HashMap<mir::Local, Ty<'tcx>> = HashMap::new();
+
     // Translate the statements
     let mut statements = Vec::new();
     for statement in &block.statements {
         trace!("statement: {:?}", statement);
 
+        if let StatementKind::Assign(assign) = &statement.kind {
+            match &assign.1 {
+                mir::Rvalue::NullaryOp(mir::NullOp::SizeOf | mir::NullOp::AlignOf, _) => {
+                    continue;
+                }
+                mir::Rvalue::ShallowInitBox(_, ty) => {
+                    assert!(assign.0.projection.is_empty());
+                    init_boxes.insert(assign.0.local, *ty);
+                    continue;
+                }
+                mir::Rvalue::Use(op)
+                    if assign.0.projection.len() == 1
+                        && assign.0.projection[0] == mir::ProjectionElem::Deref
+                        && init_boxes.contains_key(&assign.0.local) =>
+                {
+                    let ty = init_boxes.remove(&assign.0.local).unwrap();
+                    let meta = meta::get_meta_from_source_info(
+                        bt_ctx.ft_ctx.sess,
+                        &bt_ctx.ft_ctx.ordered.file_to_id,
+                        &body.source_scopes,
+                        statement.source_info,
+                    );
+                    let next_id = bt_ctx.blocks_counter.fresh_id();
+                    let call = ast::RawTerminator::Call {
+                        func: ast::FunId::Assumed(ast::AssumedFunId::BoxNew),
+                        region_args: vec![],
+                        type_args: vec![translate_ety(bt_ctx, &ty)?],
+                        args: vec![translate_operand(bt_ctx, op)?],
+                        dest: e::Place::new(bt_ctx.get_local(&assign.0.local).unwrap()),
+                        sig: None,
+                        target: next_id,
+                    };
+                    let block = ast::BlockData {
+                        statements: std::mem::take(&mut statements),
+                        terminator: ast::Terminator::new(meta, call),
+                    };
+                    bt_ctx.push_block(nid, block);
+                    nid = next_id;
+                    continue;
+                }
+                _ => (),
+            }
+        }
+
         // Some statements might be ignored, hence the optional returned value
         let opt_statement = translate_statement(bt_ctx, body, statement)?;
         if let Some(statement) = opt_statement {
@@ -402,9 +531,17 @@ fn translate_basic_block<'tcx>(
         }
     }
 
-    // Translate the terminator
+    // Translate the terminator. The dereferences of the shared pointers are
+    // calls: we translate them to assignments (see [translate_rc_deref]).
     let terminator = block.terminator();
-    let terminator = translate_terminator(bt_ctx, body, terminator)?;
+    let terminator = match translate_rc_deref(bt_ctx, body, terminator)? {
+        Some((statement, target)) => {
+            let meta = statement.meta;
+            statements.push(statement);
+            ast::Terminator::new(meta, ast::RawTerminator::Goto { target })
+        }
+        None => translate_terminator(bt_ctx, body, terminator)?,
+    };
 
     // Insert the block in the translated blocks
     let block = ast::BlockData {
@@ -414,32 +551,75 @@ fn translate_basic_block<'tcx>(
 
     bt_ctx.push_block(nid, block);
 
-    Ok(nid)
+    Ok(first_id)
+}
+
+/// The dereferences of the shared pointers (`Rc`, `Arc`) are calls to
+/// `Deref::deref`:
+/// ```text
+/// r = <Rc<T> as Deref>::deref(move p) -> bb1 // p: &Rc<T>
+/// ```
+/// We translate them to borrows: `r := &deref_rc (*p)`, and return the
+/// assignment with the target block. We return `None` if the terminator is
+/// not such a call.
+fn translate_rc_deref<'tcx>(
+    bt_ctx: &mut BodyTransContext<'tcx, '_, '_>,
+    body: &Body<'tcx>,
+    terminator: &Terminator<'tcx>,
+) -> Result<Option<(ast::Statement, ast::BlockId::Id)>> {
+    let (func, args, destination, target) = match &terminator.kind {
+        TerminatorKind::Call {
+            func,
+            args,
+            destination,
+            target: Some(target),
+            ..
+        } => (func, args, destination, target),
+        _ => return Ok(None),
+    };
+    match get_function_from_operand(func) {
+        Some((def_id, substs))
+            if translate_traits::is_rc_deref_call(bt_ctx.ft_ctx.tcx, def_id, substs) => {}
+        _ => return Ok(None),
+    }
+
+    let mut p = match translate_operand(bt_ctx, &args[0])? {
+        e::Operand::Move(p) | e::Operand::Copy(p) => p,
+        e::Operand::Const(_, _) => unreachable!(),
+    };
+    p.projection.push_back(e::ProjectionElem::Deref);
+    p.projection.push_back(e::ProjectionElem::DerefRc);
+    let rv = e::Rvalue::Ref(p, e::BorrowKind::Shared);
+    let dest = translate_place(bt_ctx, destination)?;
+    let meta = meta::get_meta_from_source_info(
+        bt_ctx.ft_ctx.sess,
+        &bt_ctx.ft_ctx.ordered.file_to_id,
+        &body.source_scopes,
+        terminator.source_info,
+    );
+    let statement = ast::Statement::new(meta, ast::RawStatement::Assign(dest, rv));
+    let target = translate_basic_block(bt_ctx, body, *target)?;
+    Ok(Some((statement, target)))
 }
 
 /// Translate a place and return its type
 fn translate_place_with_type<'tcx, 'ctx>(
     bt_ctx: &'ctx BodyTransContext<'tcx, 'ctx, '_>,
     place: &Place<'tcx>,
-) -> (e::Place, ty::ETy) {
+) -> Result<(e::Place, ty::ETy)> {
     let var_id = bt_ctx.get_local(&place.local).unwrap();
     let var = bt_ctx.get_var_from_id(var_id).unwrap();
-    let (projection, ty) = translate_projection(
-        bt_ctx.ft_ctx.mir_level,
-        bt_ctx.ft_ctx.type_defs,
-        var.ty.clone(),
-        place.projection,
-    );
+    let (projection, ty) = translate_projection(bt_ctx, var.ty.clone(), place.projection)?;
 
-    (e::Place { var_id, projection }, ty)
+    Ok((e::Place { var_id, projection }, ty))
 }
 
 /// Translate a place
 fn translate_place<'tcx, 'ctx>(
     bt_ctx: &'ctx BodyTransContext<'tcx, 'ctx, '_>,
     place: &Place<'tcx>,
-) -> e::Place {
-    translate_place_with_type(bt_ctx, place).0
+) -> Result<e::Place> {
+    Ok(translate_place_with_type(bt_ctx, place)?.0)
 }
 
 /// Translate a projection
@@ -451,14 +631,16 @@ fn translate_place<'tcx, 'ctx>(
 ///
 /// We return the translated projection, and its type.
 ///
-/// - `mir_level`: used for sanity checks
+/// The body translation context is used to retrieve the variables used as
+/// indices, and the MIR level (for sanity checks).
 fn translate_projection(
-    mir_level: MirLevel,
-    type_defs: &ty::TypeDecls,
+    bt_ctx: &BodyTransContext<'_, '_, '_>,
     var_ty: ty::ETy,
     rprojection: &rustc_middle::ty::List<PlaceElem<'_>>,
-) -> (e::Projection, ty::ETy) {
+) -> Result<(e::Projection, ty::ETy)> {
     trace!("- projection: {:?}\n- var_ty: {:?}", rprojection, var_ty);
+    let mir_level = bt_ctx.ft_ctx.mir_level;
+    let type_defs = bt_ctx.ft_ctx.type_defs;
 
     // We need to track the type of the value we look at, while exploring the path.
     // This is important to disambiguate, for instance, dereferencement operations.
@@ -517,7 +699,11 @@ fn translate_projection(
                             field_id,
                         );
 
-                        let proj_kind = e::FieldProjKind::Adt(type_id, downcast_id);
+                        let proj_kind = if type_def.kind.is_union() {
+                            e::FieldProjKind::Union(type_id)
+                        } else {
+                            e::FieldProjKind::Adt(type_id, downcast_id)
+                        };
                         e::ProjectionElem::Field(proj_kind, field_id)
                     }
                     ty::Ty::Adt(ty::TypeId::Tuple, regions, tys) => {
@@ -539,6 +725,23 @@ fn translate_projection(
                         let proj_kind = e::FieldProjKind::Option(variant_id);
                         e::ProjectionElem::Field(proj_kind, field_id)
                     }
+                    ty::Ty::Adt(ty::TypeId::Assumed(ty::AssumedTy::ControlFlow), regions, tys) => {
+                        // `ControlFlow<B, C>`: the variants are `Continue(C)`
+                        // and `Break(B)`
+                        assert!(regions.is_empty());
+                        assert!(tys.len() == 2);
+                        assert!(field_id == ty::FieldId::ZERO);
+
+                        let variant_id = downcast_id.unwrap();
+                        path_type = if variant_id == assumed::CONTROL_FLOW_CONTINUE_VARIANT_ID {
+                            tys[1].clone()
+                        } else {
+                            assert!(variant_id == assumed::CONTROL_FLOW_BREAK_VARIANT_ID);
+                            tys[0].clone()
+                        };
+                        let proj_kind = e::FieldProjKind::ControlFlow(variant_id);
+                        e::ProjectionElem::Field(proj_kind, field_id)
+                    }
                     ty::Ty::Adt(ty::TypeId::Assumed(aty), regions, tys)
                         if aty == ty::AssumedTy::Box
                             || aty == ty::AssumedTy::PtrUnique
@@ -599,22 +802,51 @@ fn translate_projection(
                 projection.push_back(proj_elem);
                 downcast_id = None;
             }
-            mir::ProjectionElem::Index(_local) => {
-                unimplemented!();
+            mir::ProjectionElem::Index(local) => {
+                // Note that the bounds checks are performed by separate
+                // assertions (see [crate::index_accesses])
+                downcast_id = None;
+                let var_id = bt_ctx.get_local(&local).unwrap();
+                path_type = match path_type {
+                    ty::Ty::Array(ty, _) | ty::Ty::Slice(ty) => *ty,
+                    _ => {
+                        unreachable!("- pelem: {:?}\n- path_type: {:?}", pelem, path_type);
+                    }
+                };
+                projection.push_back(e::ProjectionElem::Index(var_id));
             }
             mir::ProjectionElem::ConstantIndex {
-                offset: _,
-                min_length: _,
-                from_end: _,
+                offset,
+                min_length,
+                from_end,
             } => {
-                unimplemented!();
+                downcast_id = None;
+                path_type = match path_type {
+                    ty::Ty::Array(ty, _) | ty::Ty::Slice(ty) => *ty,
+                    _ => {
+                        unreachable!("- pelem: {:?}\n- path_type: {:?}", pelem, path_type);
+                    }
+                };
+                projection.push_back(e::ProjectionElem::ConstantIndex {
+                    offset,
+                    min_length,
+                    from_end,
+                });
             }
             mir::ProjectionElem::Subslice {
                 from: _,
                 to: _,
                 from_end: _,
             } => {
-                unimplemented!();
+                // Introduced by the slice patterns which bind the rest of the
+                // array or slice (`[x, rest @ ..]`)
+                let span = bt_ctx.ft_ctx.tcx.def_span(bt_ctx.def_id);
+                span_err(
+                    bt_ctx.ft_ctx.sess,
+                    span,
+                    "Unsupported slice pattern binding a sub-slice (`[x, rest @ ..]`)",
+                );
+                return Err(());
             }
             mir::ProjectionElem::OpaqueCast(_) => {
                 unimplemented!();
@@ -635,11 +867,11 @@ fn translate_projection(
         }
     }
 
-    (projection, path_type)
+    Ok((projection, path_type))
 }
 
 /// Translate the type of a [mir::interpret::ConstValue::Scalar] value :
-/// Either a bool, a char, an integer, an enumeration ADT, an empty tuple or a static reference.
+/// Either a bool, a char, an integer, a float, an enumeration ADT, an empty tuple or a static reference.
 fn translate_constant_scalar_type(ty: &TyKind, decls: &DeclTransContext<'_, '_>) -> ty::ETy {
     match ty {
         TyKind::Bool => ty::Ty::Bool,
@@ -682,11 +914,7 @@ fn translate_constant_scalar_type(ty: &TyKind, decls: &DeclTransContext<'_, '_>)
             ),
             _ => unreachable!(),
         },
-        TyKind::Float(_) => {
-            // We don't support floating point numbers:
-            // this should have been detected and eliminated before.
-            unreachable!();
-        }
+        TyKind::Float(float_ty) => ty::Ty::Float(ty::FloatTy::rust_float_ty_to_float_ty(*float_ty)),
         _ => {
             // The remaining types should not be used for constants, or
             // should have been filtered by the caller.
@@ -711,6 +939,7 @@ fn translate_constant_reference_type<'tcx>(
             let field_tys = type_params.into_iter().collect();
             ty::Ty::Adt(ty::TypeId::Tuple, Vector::new(), field_tys)
         }
+        TyKind::Array(_, _) => translate_ety_kind(bt_ctx, ty).unwrap(),
         TyKind::Adt(_, _) => {
             // Following tests, it seems rustc doesn't introduce constants
             // references when initializing ADTs, only when initializing tuples.
@@ -727,7 +956,8 @@ fn translate_constant_reference_type<'tcx>(
     }
 }
 
-/// Translate a typed constant value (either a bool, a char or an integer).
+/// Translate a typed constant value (either a bool, a char, an integer or a
+/// float).
 fn translate_constant_integer_like_value(
     ty: &ty::ETy,
     scalar: &mir::interpret::Scalar,
@@ -763,6 +993,13 @@ fn translate_constant_integer_like_value(
             ty::IntegerTy::I128 => v::ScalarValue::I128(scalar.to_i128().unwrap()),
             ty::IntegerTy::U128 => v::ScalarValue::U128(scalar.to_u128().unwrap()),
         }),
+        // We keep the bit patterns of the floats
+        ty::Ty::Float(ty::FloatTy::F32) => {
+            v::PrimitiveValue::Float(v::FloatValue::F32(scalar.to_u32().unwrap()))
+        }
+        ty::Ty::Float(ty::FloatTy::F64) => {
+            v::PrimitiveValue::Float(v::FloatValue::F64(scalar.to_u64().unwrap()))
+        }
         _ => {
             // The remaining types should not be used for constants,
             // or should have been filtered by the caller.
@@ -788,16 +1025,19 @@ fn translate_constant_scalar_value(
     // degenerate ADT or tuple (if an ADT has only one variant and no fields,
     // it is a constant, and unit is encoded by MIR as a 0-tuple).
     match llbc_ty {
-        ty::Ty::Bool | ty::Ty::Char | ty::Ty::Integer(_) => {
+        ty::Ty::Bool | ty::Ty::Char | ty::Ty::Integer(_) | ty::Ty::Float(_) => {
             let v = translate_constant_integer_like_value(llbc_ty, scalar);
             e::OperandConstantValue::PrimitiveValue(v)
         }
         ty::Ty::Adt(ty::TypeId::Adt(id), region_tys, field_tys) => {
-            assert!(region_tys.is_empty());
-            assert!(field_tys.is_empty());
-
             let def = decls.type_defs.get_type_def(*id).unwrap();
 
+            // The generic and opaque types (for instance `PhantomData<T>`)
+            // are zero-sized: we can't build an ADT value for them.
+            if !region_tys.is_empty() || !field_tys.is_empty() || def.kind.is_opaque() {
+                return e::OperandConstantValue::ZST(llbc_ty.clone());
+            }
+
             // Check that there is only one variant, with no fields
             // and no parameters. Construct the value at the same time.
             assert!(def.type_params.is_empty());
@@ -807,6 +1047,8 @@ fn translate_constant_scalar_value(
                     Option::Some(ty::VariantId::ZERO)
                 }
                 ty::TypeDeclKind::Struct(_) => Option::None,
+                // A union always has at least one field
+                ty::TypeDeclKind::Union(_) => unreachable!(),
                 ty::TypeDeclKind::Opaque => {
                     unreachable!("Can't analyze a constant value built from an opaque type")
                 }
@@ -818,7 +1060,10 @@ fn translate_constant_scalar_value(
             assert!(field_tys.is_empty());
             e::OperandConstantValue::Adt(Option::None, Vec::new())
         }
-        ty::Ty::Ref(ty::ErasedRegion::Erased, _, ty::RefKind::Shared) => match scalar {
+        // The statics are accessed through references, and the mutable statics
+        // through raw pointers
+        ty::Ty::Ref(ty::ErasedRegion::Erased, _, ty::RefKind::Shared)
+        | ty::Ty::RawPtr(_, ty::RefKind::Mut) => match scalar {
             mir::interpret::Scalar::Ptr(p, _) => match tcx.global_alloc(p.provenance) {
                 mir::interpret::GlobalAlloc::Static(s) => {
                     let id = decls.ordered.global_rid_to_id.get(&s).unwrap();
@@ -839,13 +1084,13 @@ fn translate_constant_scalar_value(
 }
 
 /// Translate a constant typed by [translate_constant_reference_type].
-/// This should always be a tuple.
+/// This should always be a tuple or an array.
 fn translate_constant_reference_value<'tcx>(
     bt_ctx: &BodyTransContext<'tcx, '_, '_>,
     llbc_ty: &ty::ETy,
     mir_ty: &Ty<'tcx>, // TODO: remove?
     value: &mir::interpret::ConstValue<'tcx>,
-) -> e::OperandConstantValue {
+) -> Result<e::OperandConstantValue> {
     trace!();
 
     let tcx = bt_ctx.ft_ctx.tcx;
@@ -876,19 +1121,59 @@ fn translate_constant_reference_value<'tcx>(
         .fields
         .iter()
         .map(|f| translate_constant_kind(bt_ctx, f))
-        .collect();
+        .collect::<Result<_>>()?;
 
-    // Sanity check
     match llbc_ty {
         ty::Ty::Adt(ty::TypeId::Tuple, regions, fields_tys) => {
+            // Sanity check
             assert!(regions.is_empty());
             assert!(zip(&fields, fields_tys).all(|(f, ty)| &f.0 == ty));
+
+            let fields: Vec<e::OperandConstantValue> = fields.into_iter().map(|f| f.1).collect();
+            Ok(e::OperandConstantValue::Adt(Option::None, fields))
         }
-        _ => unreachable!("Expected a tuple, got {:?}", mir_ty),
-    };
+        ty::Ty::Array(elem_ty, _) => {
+            // Sanity check
+            assert!(fields.iter().all(|(ty, _)| ty == elem_ty.as_ref()));
+
+            // We don't desugar the constant arrays: we thus only support
+            // arrays of primitive values (or of arrays of primitive values).
+            let elems: Vec<e::OperandConstantValue> = fields.into_iter().map(|f| f.1).collect();
+            if let Some(elem) = elems
+                .iter()
+                .find(|elem| !(elem.is_primitive_value() || elem.is_array() || elem.is_bytes()))
+            {
+                let span = tcx.def_span(bt_ctx.def_id);
+                let msg = format!("Unsupported constant array element: `{}`", elem.to_string());
+                span_err(bt_ctx.ft_ctx.sess, span, &msg);
+                return Err(());
+            }
+            Ok(e::OperandConstantValue::mk_array(elems))
+        }
+        _ => unreachable!("Expected a tuple or an array, got {:?}", mir_ty),
+    }
+}
 
-    let fields: Vec<e::OperandConstantValue> = fields.into_iter().map(|f| f.1).collect();
-    e::OperandConstantValue::Adt(Option::None, fields)
+/// Translate a [mir::interpret::ConstValue::Slice] value.
+/// The only slices we support are the string literals (`&str`): MIR encodes
+/// them as a range of bytes in a constant allocation, which we decode.
+fn translate_constant_slice_value(
+    llbc_ty: &ty::ETy,
+    data: &mir::interpret::ConstAllocation<'_>,
+    start: usize,
+    end: usize,
+) -> e::OperandConstantValue {
+    match llbc_ty {
+        ty::Ty::Ref(ty::ErasedRegion::Erased, ty, ty::RefKind::Shared) if ty.is_str() => {
+            let bytes = data
+                .inner()
+                .inspect_with_uninit_and_ptr_outside_interpreter(start..end);
+            // A string literal is always valid UTF-8
+            let s = std::str::from_utf8(bytes).unwrap();
+            e::OperandConstantValue::PrimitiveValue(v::PrimitiveValue::String(s.to_string()))
+        }
+        _ => unimplemented!("Unsupported slice constant of type: {:?}", llbc_ty),
+    }
 }
 
 /// Translate a [mir::interpret::ConstValue]
@@ -897,20 +1182,29 @@ fn translate_const_value<'tcx>(
     llbc_ty: &ty::ETy,
     mir_ty: &Ty<'tcx>, // TODO: remove?
     val: &mir::interpret::ConstValue<'tcx>,
-) -> e::OperandConstantValue {
+) -> Result<e::OperandConstantValue> {
     trace!("{:?}", val);
     match val {
-        mir::interpret::ConstValue::Scalar(scalar) => {
-            translate_constant_scalar_value(bt_ctx.ft_ctx, llbc_ty, scalar)
-        }
+        mir::interpret::ConstValue::Scalar(scalar) => Ok(translate_constant_scalar_value(
+            bt_ctx.ft_ctx,
+            llbc_ty,
+            scalar,
+        )),
         mir::interpret::ConstValue::ByRef { .. } => {
             translate_constant_reference_value(bt_ctx, llbc_ty, mir_ty, val)
         }
-        mir::interpret::ConstValue::Slice { .. } => unimplemented!(),
+        mir::interpret::ConstValue::Slice { data, start, end } => {
+            Ok(translate_constant_slice_value(llbc_ty, data, *start, *end))
+        }
         mir::interpret::ConstValue::ZeroSized { .. } => {
-            // Should be unit
-            assert!(llbc_ty.is_unit());
-            e::OperandConstantValue::Adt(None, Vec::new())
+            // We keep the unit value as an empty tuple: the other zero-sized
+            // values (`PhantomData<T>`, the structures without fields, etc.)
+            // are represented uniformly.
+            if llbc_ty.is_unit() {
+                Ok(e::OperandConstantValue::Adt(None, Vec::new()))
+            } else {
+                Ok(e::OperandConstantValue::ZST(llbc_ty.clone()))
+            }
         }
     }
 }
@@ -919,10 +1213,10 @@ fn translate_evaluated_operand_constant<'tcx>(
     bt_ctx: &BodyTransContext<'tcx, '_, '_>,
     ty: &Ty<'tcx>,
     val: &mir::interpret::ConstValue<'tcx>,
-) -> (ty::ETy, e::OperandConstantValue) {
+) -> Result<(ty::ETy, e::OperandConstantValue)> {
     let llbc_ty = translate_ety(bt_ctx, ty).unwrap();
-    let im_val = translate_const_value(bt_ctx, &llbc_ty, ty, val);
-    (llbc_ty, im_val)
+    let im_val = translate_const_value(bt_ctx, &llbc_ty, ty, val)?;
+    Ok((llbc_ty, im_val))
 }
 
 /// This function translates a constant id, under the condition that the
@@ -940,26 +1234,66 @@ fn translate_constant_id_as_top_level(
     (decl.ty.clone(), e::OperandConstantValue::ConstantId(id))
 }
 
+/// Translate a reference to an associated constant whose implementation we
+/// can't determine statically (see [e::OperandConstantValue::TraitConst]).
+fn translate_trait_const<'tcx>(
+    bt_ctx: &BodyTransContext<'tcx, '_, '_>,
+    mir_ty: &mir_ty::Ty<'tcx>,
+    rid: DefId,
+    substs: mir_ty::subst::SubstsRef<'tcx>,
+) -> (ty::ETy, e::OperandConstantValue) {
+    let tcx = bt_ctx.ft_ctx.tcx;
+    // The trait is registered together with the constant (see
+    // [crate::register])
+    let trait_rid = tcx.trait_of_item(rid).unwrap();
+    let trait_id = *bt_ctx
+        .ft_ctx
+        .ordered
+        .trait_rid_to_id
+        .get(&trait_rid)
+        .unwrap();
+    let (_, type_args) = translate_subst_generic_args_in_body(bt_ctx, None, substs).unwrap();
+    let name = tcx.item_name(rid).to_string();
+    let ty = translate_ety(bt_ctx, mir_ty).unwrap();
+    (
+        ty,
+        e::OperandConstantValue::TraitConst(trait_id, type_args, name),
+    )
+}
+
 fn translate_const_kind_unevaluated<'tcx>(
     bt_ctx: &BodyTransContext<'tcx, '_, '_>,
     mir_ty: &mir_ty::Ty<'tcx>,
     ucv: &rustc_middle::mir::UnevaluatedConst<'tcx>,
-) -> (ty::ETy, e::OperandConstantValue) {
+) -> Result<(ty::ETy, e::OperandConstantValue)> {
+    let tcx = bt_ctx.ft_ctx.tcx;
+    let rid =
+        match translate_traits::resolve_trait_const(tcx, bt_ctx.def_id, ucv.def.did, ucv.substs) {
+            Some(rid) => rid,
+            None => {
+                return Ok(translate_trait_const(
+                    bt_ctx,
+                    mir_ty,
+                    ucv.def.did,
+                    ucv.substs,
+                ))
+            }
+        };
+
     // Two cases:
     // - if we extract the constants at top level, we lookup the constant
     //   identifier and refer to it
     // - otherwise, we evaluate the constant and insert it in place
     if extract_constants_at_top_level(bt_ctx.ft_ctx.mir_level) {
-        translate_constant_id_as_top_level(bt_ctx, ucv.def.did)
+        Ok(translate_constant_id_as_top_level(bt_ctx, rid))
     } else {
         // Evaluate the constant.
         // We need a param_env: we use the function def id as a dummy id...
-        let tcx = bt_ctx.ft_ctx.tcx;
         let param_env = tcx.param_env(bt_ctx.def_id);
         let cv = tcx.const_eval_resolve(param_env, *ucv, None).unwrap();
         let llbc_ty = translate_ety(bt_ctx, mir_ty).unwrap();
-        let v = translate_const_value(bt_ctx, &llbc_ty, mir_ty, &cv);
-        (llbc_ty, v)
+        let v = translate_const_value(bt_ctx, &llbc_ty, mir_ty, &cv)?;
+        Ok((llbc_ty, v))
     }
 }
 
@@ -967,7 +1301,7 @@ fn translate_const_kind_unevaluated<'tcx>(
 fn translate_constant_kind<'tcx>(
     bt_ctx: &BodyTransContext<'tcx, '_, '_>,
     constant: &rustc_middle::mir::ConstantKind<'tcx>,
-) -> (ty::ETy, e::OperandConstantValue) {
+) -> Result<(ty::ETy, e::OperandConstantValue)> {
     trace!("{:?}", constant);
 
     match constant {
@@ -986,12 +1320,30 @@ fn translate_constant_kind<'tcx>(
                     unimplemented!();
                 }
                 ConstKind::Unevaluated(ucv) => {
+                    let tcx = bt_ctx.ft_ctx.tcx;
+                    let rid = match translate_traits::resolve_trait_const(
+                        tcx,
+                        bt_ctx.def_id,
+                        ucv.def.did,
+                        ucv.substs,
+                    ) {
+                        Some(rid) => rid,
+                        None => {
+                            return Ok(translate_trait_const(
+                                bt_ctx,
+                                &c.ty(),
+                                ucv.def.did,
+                                ucv.substs,
+                            ))
+                        }
+                    };
+
                     // Two cases:
                     // - if we extract the constants at top level, we lookup the constant
                     //   identifier and refer to it
                     // - otherwise, we evaluate the constant and insert it in place
                     if extract_constants_at_top_level(bt_ctx.ft_ctx.mir_level) {
-                        translate_constant_id_as_top_level(bt_ctx, ucv.def.did)
+                        Ok(translate_constant_id_as_top_level(bt_ctx, rid))
                     } else {
                         // TODO: we can't call [translate_const_kind_unevaluated]:
                         // the types don't match.
@@ -1024,7 +1376,7 @@ fn translate_constant_kind<'tcx>(
 fn translate_operand_constant<'tcx>(
     bt_ctx: &BodyTransContext<'tcx, '_, '_>,
     constant: &mir::Constant<'tcx>,
-) -> (ty::ETy, e::OperandConstantValue) {
+) -> Result<(ty::ETy, e::OperandConstantValue)> {
     trace!("{:?}", constant);
     use std::ops::Deref;
     let constant = &constant.deref();
@@ -1036,20 +1388,20 @@ fn translate_operand_constant<'tcx>(
 fn translate_operand_with_type<'tcx>(
     bt_ctx: &BodyTransContext<'tcx, '_, '_>,
     operand: &mir::Operand<'tcx>,
-) -> (e::Operand, ty::ETy) {
+) -> Result<(e::Operand, ty::ETy)> {
     trace!();
     match operand {
         Operand::Copy(place) => {
-            let (p, ty) = translate_place_with_type(bt_ctx, place);
-            (e::Operand::Copy(p), ty)
+            let (p, ty) = translate_place_with_type(bt_ctx, place)?;
+            Ok((e::Operand::Copy(p), ty))
         }
         Operand::Move(place) => {
-            let (p, ty) = translate_place_with_type(bt_ctx, place);
-            (e::Operand::Move(p), ty)
+            let (p, ty) = translate_place_with_type(bt_ctx, place)?;
+            Ok((e::Operand::Move(p), ty))
         }
         Operand::Constant(constant) => {
-            let (ty, constant) = translate_operand_constant(bt_ctx, constant);
-            (e::Operand::Const(ty.clone(), constant), ty)
+            let (ty, constant) = translate_operand_constant(bt_ctx, constant)?;
+            Ok((e::Operand::Const(ty.clone(), constant), ty))
         }
     }
 }
@@ -1058,9 +1410,9 @@ fn translate_operand_with_type<'tcx>(
 fn translate_operand<'tcx>(
     bt_ctx: &BodyTransContext<'tcx, '_, '_>,
     operand: &mir::Operand<'tcx>,
-) -> e::Operand {
+) -> Result<e::Operand> {
     trace!();
-    translate_operand_with_type(bt_ctx, operand).0
+    Ok(translate_operand_with_type(bt_ctx, operand)?.0)
 }
 
 /// Translate an operand which should be `move b.0` where `b` is a box (such
@@ -1154,6 +1506,7 @@ fn translate_binaryop_kind(binop: mir::BinOp) -> e::BinOp {
         BinOp::Mul => e::BinOp::Mul,
         BinOp::Shl => e::BinOp::Shl,
         BinOp::Shr => e::BinOp::Shr,
+        BinOp::Offset => e::BinOp::Offset,
         _ => {
             unreachable!();
         }
@@ -1173,14 +1526,14 @@ fn translate_rvalue<'tcx>(
     tcx: TyCtxt<'tcx>,
     bt_ctx: &BodyTransContext<'tcx, '_, '_>,
     rvalue: &mir::Rvalue<'tcx>,
-) -> e::Rvalue {
+) -> Result<e::Rvalue> {
     use std::ops::Deref;
-    match rvalue {
-        mir::Rvalue::Use(operand) => e::Rvalue::Use(translate_operand(bt_ctx, operand)),
+    Ok(match rvalue {
+        mir::Rvalue::Use(operand) => e::Rvalue::Use(translate_operand(bt_ctx, operand)?),
         mir::Rvalue::CopyForDeref(place) => {
             // According to the documentation, it seems to be an optimisation
             // for drop elaboration. We treat it as a regular copy.
-            let place = translate_place(bt_ctx, place);
+            let place = translate_place(bt_ctx, place)?;
             e::Rvalue::Use(e::Operand::Copy(place))
         }
         mir::Rvalue::Repeat(_operand, _const) => {
@@ -1188,70 +1541,105 @@ fn translate_rvalue<'tcx>(
             unimplemented!();
         }
         mir::Rvalue::Ref(_region, borrow_kind, place) => {
-            let place = translate_place(bt_ctx, place);
+            let place = translate_place(bt_ctx, place)?;
             let borrow_kind = translate_borrow_kind(*borrow_kind);
             e::Rvalue::Ref(place, borrow_kind)
         }
         mir::Rvalue::ThreadLocalRef(_) => {
             unreachable!();
         }
-        mir::Rvalue::AddressOf(_, _) => {
-            unreachable!();
-        }
-        mir::Rvalue::Len(_place) => {
-            unimplemented!();
+        mir::Rvalue::AddressOf(mutability, place) => {
+            let place = translate_place(bt_ctx, place)?;
+            let kind = match mutability {
+                mir::Mutability::Not => ty::RefKind::Shared,
+                mir::Mutability::Mut => ty::RefKind::Mut,
+            };
+            e::Rvalue::RawPtr(place, kind)
+        }
+        mir::Rvalue::Len(place) => e::Rvalue::Len(translate_place(bt_ctx, place)?),
+        mir::Rvalue::Cast(
+            mir::CastKind::Pointer(mir_ty::adjustment::PointerCast::Unsize),
+            operand,
+            tgt_ty,
+        ) => {
+            // Coercion to a slice or to a trait object
+            trace!("Rvalue::Cast (unsize): {:?}", rvalue);
+            let tgt_ty = translate_ety(bt_ctx, tgt_ty).unwrap();
+            let (op, src_ty) = translate_operand_with_type(bt_ctx, operand)?;
+            e::Rvalue::Unsize(op, src_ty, tgt_ty)
+        }
+        mir::Rvalue::Cast(
+            mir::CastKind::Pointer(mir_ty::adjustment::PointerCast::ReifyFnPointer),
+            operand,
+            _,
+        ) => {
+            // Take the address of a function
+            trace!("Rvalue::Cast (reify): {:?}", rvalue);
+            let (def_id, _) = get_function_from_operand(operand).unwrap();
+            let fun_id = bt_ctx.ft_ctx.get_def_id_from_rid(def_id).unwrap();
+            e::Rvalue::FnPtr(fun_id)
         }
         mir::Rvalue::Cast(cast_kind, operand, tgt_ty) => {
             trace!("Rvalue::Cast: {:?}", rvalue);
-            // Put aside the pointer casts (which we don't support), I think
-            // casts should only be from integers/booleans to integer/booleans.
-
-            // Sanity check
-            assert!(match cast_kind {
-                rustc_middle::mir::CastKind::IntToInt => true,
-                rustc_middle::mir::CastKind::FloatToInt
-                | rustc_middle::mir::CastKind::FloatToFloat
-                | rustc_middle::mir::CastKind::IntToFloat
-                | rustc_middle::mir::CastKind::PtrToPtr
-                | rustc_middle::mir::CastKind::FnPtrToPtr
-                | rustc_middle::mir::CastKind::Pointer(_)
-                | rustc_middle::mir::CastKind::PointerExposeAddress
-                | rustc_middle::mir::CastKind::PointerFromExposedAddress
-                | rustc_middle::mir::CastKind::DynStar => false,
-            });
+            // The pointer casts we support are handled above
+            if !matches!(
+                cast_kind,
+                mir::CastKind::IntToInt
+                    | mir::CastKind::FloatToInt
+                    | mir::CastKind::IntToFloat
+                    | mir::CastKind::FloatToFloat
+            ) {
+                let span = bt_ctx.ft_ctx.tcx.def_span(bt_ctx.def_id);
+                let msg = format!("Unsupported cast: {:?}", cast_kind);
+                span_err(bt_ctx.ft_ctx.sess, span, &msg);
+                return Err(());
+            }
 
             // Translate the target type
             let tgt_ty = translate_ety(bt_ctx, tgt_ty).unwrap();
 
             // Translate the operand
-            let (op, src_ty) = translate_operand_with_type(bt_ctx, operand);
-
-            // We only support source and target types for integers
-            let tgt_ty = *tgt_ty.as_integer();
-            let src_ty = *src_ty.as_integer();
-
-            e::Rvalue::UnaryOp(e::UnOp::Cast(src_ty, tgt_ty), op)
+            let (op, src_ty) = translate_operand_with_type(bt_ctx, operand)?;
+
+            // Note that the casts from the enumerations are preceded by a
+            // read of the discriminant: they are casts between integers
+            // here (see [crate::remove_read_discriminant]).
+            let kind = match (&src_ty, &tgt_ty) {
+                (ty::Ty::Integer(_), ty::Ty::Integer(_)) => e::CastKind::IntToInt,
+                (ty::Ty::Bool, ty::Ty::Integer(_)) => e::CastKind::BoolToInt,
+                (ty::Ty::Char, ty::Ty::Integer(_)) => e::CastKind::CharToInt,
+                (ty::Ty::Integer(ty::IntegerTy::U8), ty::Ty::Char) => e::CastKind::U8ToChar,
+                (ty::Ty::Float(_), ty::Ty::Integer(_)) => e::CastKind::FloatToInt,
+                (ty::Ty::Integer(_), ty::Ty::Float(_)) => e::CastKind::IntToFloat,
+                (ty::Ty::Float(_), ty::Ty::Float(_)) => e::CastKind::FloatToFloat,
+                _ => unreachable!(),
+            };
+            e::Rvalue::Cast(kind, op, src_ty, tgt_ty)
         }
         mir::Rvalue::BinaryOp(binop, operands) | mir::Rvalue::CheckedBinaryOp(binop, operands) => {
             // We merge checked and unchecked binary operations
             let (left, right) = operands.deref();
             e::Rvalue::BinaryOp(
                 translate_binaryop_kind(*binop),
-                translate_operand(bt_ctx, left),
-                translate_operand(bt_ctx, right),
+                translate_operand(bt_ctx, left)?,
+                translate_operand(bt_ctx, right)?,
             )
         }
         mir::Rvalue::NullaryOp(nullop, _ty) => {
             trace!("NullOp: {:?}", nullop);
             // Nullary operations are very low-level and shouldn't be necessary
-            // unless one needs to write unsafe code.
+            // unless one needs to write unsafe code. The ones which compute
+            // the layout of the boxes initialized in place are filtered in
+            // [translate_basic_block].
             unreachable!();
         }
         mir::Rvalue::UnaryOp(unop, operand) => e::Rvalue::UnaryOp(
             translate_unaryop_kind(*unop),
-            translate_operand(bt_ctx, operand),
+            translate_operand(bt_ctx, operand)?,
         ),
-        mir::Rvalue::Discriminant(place) => e::Rvalue::Discriminant(translate_place(bt_ctx, place)),
+        mir::Rvalue::Discriminant(place) => {
+            e::Rvalue::Discriminant(translate_place(bt_ctx, place)?)
+        }
         mir::Rvalue::Aggregate(aggregate_kind, operands) => {
             // It seems this instruction is not present in certain passes:
             // for example, it seems it is not used in optimized MIR, where
@@ -1273,7 +1661,7 @@ fn translate_rvalue<'tcx>(
             let operands_t: Vec<e::Operand> = operands
                 .iter()
                 .map(|op| translate_operand(bt_ctx, op))
-                .collect();
+                .collect::<Result<_>>()?;
 
             match aggregate_kind.deref() {
                 mir::AggregateKind::Array(_ty) => {
@@ -1326,6 +1714,11 @@ fn translate_rvalue<'tcx>(
                                 assert!(variant_idx.as_usize() == 0);
                                 None
                             }
+                            ty::TypeDeclKind::Union(_) => {
+                                // The union aggregates are translated to field
+                                // assignments in [translate_statement]
+                                unreachable!()
+                            }
                             ty::TypeDeclKind::Opaque => {
                                 unreachable!("Can't build an aggregate from an opaque type")
                             }
@@ -1342,30 +1735,57 @@ fn translate_rvalue<'tcx>(
                         // For instance, we can access the variants of any external
                         // enumeration marked as `public`.
                         let name = type_def_id_to_name(tcx, *adt_id);
-                        assert!(name.equals_ref_name(&assumed::OPTION_NAME));
-
-                        // Sanity checks
-                        assert!(region_params.is_empty());
-                        assert!(type_params.len() == 1);
-
-                        // Find the variant
-                        let variant_id = translate_variant_id(*variant_idx);
-                        if variant_id == assumed::OPTION_NONE_VARIANT_ID {
-                            assert!(operands_t.is_empty());
-                        } else if variant_id == assumed::OPTION_SOME_VARIANT_ID {
-                            assert!(operands_t.len() == 1);
+                        if assumed::is_range_name(&name) {
+                            // The ranges are used by the slicing operations
+                            // (`&v[a..b]`), which we reconstruct in
+                            // [crate::slice_accesses]. They are external (hence
+                            // opaque) types: we simply refer to their declaration.
+                            let id_t = *bt_ctx.ft_ctx.ordered.type_rid_to_id.get(adt_id).unwrap();
+                            let akind =
+                                e::AggregateKind::Adt(id_t, None, region_params, type_params);
+                            e::Rvalue::Aggregate(akind, operands_t)
                         } else {
-                            unreachable!();
-                        }
+                            assert!(name.equals_ref_name(&assumed::OPTION_NAME));
+
+                            // Sanity checks
+                            assert!(region_params.is_empty());
+                            assert!(type_params.len() == 1);
+
+                            // Find the variant
+                            let variant_id = translate_variant_id(*variant_idx);
+                            if variant_id == assumed::OPTION_NONE_VARIANT_ID {
+                                assert!(operands_t.is_empty());
+                            } else if variant_id == assumed::OPTION_SOME_VARIANT_ID {
+                                assert!(operands_t.len() == 1);
+                            } else {
+                                unreachable!();
+                            }
 
-                        let akind =
-                            e::AggregateKind::Option(variant_id, type_params.pop().unwrap());
+                            let akind =
+                                e::AggregateKind::Option(variant_id, type_params.pop().unwrap());
 
-                        e::Rvalue::Aggregate(akind, operands_t)
+                            e::Rvalue::Aggregate(akind, operands_t)
+                        }
                     }
                 }
-                mir::AggregateKind::Closure(_def_id, _subst) => {
-                    unimplemented!();
+                mir::AggregateKind::Closure(def_id, _subst) => {
+                    trace!("{:?}", rvalue);
+                    let def_id = def_id.to_def_id();
+
+                    // The operands are the captured variables, in the order
+                    // given by the capture analysis
+                    let capture_modes: Vec<e::CaptureMode> =
+                        translate_types::translate_closure_captures(tcx, def_id)
+                            .into_iter()
+                            .map(|(_, mode)| mode)
+                            .collect();
+                    assert!(capture_modes.len() == operands_t.len());
+
+                    // The closure body was registered as a function
+                    let fun_id = bt_ctx.ft_ctx.get_def_id_from_rid(def_id).unwrap();
+
+                    let akind = e::AggregateKind::Closure(fun_id, capture_modes);
+                    e::Rvalue::Aggregate(akind, operands_t)
                 }
                 mir::AggregateKind::Generator(_def_id, _subst, _movability) => {
                     unimplemented!();
@@ -1373,8 +1793,80 @@ fn translate_rvalue<'tcx>(
             }
         }
         mir::Rvalue::ShallowInitBox(_, _) => {
-            unimplemented!();
+            // Handled in [translate_basic_block]
+            unreachable!();
         }
+    })
+}
+
+/// The operands of an MIR rvalue, in the order in which [translate_rvalue]
+/// translates them
+fn rvalue_operands<'a, 'tcx>(rvalue: &'a mir::Rvalue<'tcx>) -> Vec<&'a Operand<'tcx>> {
+    use std::ops::Deref;
+    match rvalue {
+        mir::Rvalue::Use(op)
+        | mir::Rvalue::Repeat(op, _)
+        | mir::Rvalue::Cast(_, op, _)
+        | mir::Rvalue::UnaryOp(_, op)
+        | mir::Rvalue::ShallowInitBox(op, _) => vec![op],
+        mir::Rvalue::BinaryOp(_, operands) | mir::Rvalue::CheckedBinaryOp(_, operands) => {
+            let (left, right) = operands.deref();
+            vec![left, right]
+        }
+        mir::Rvalue::Aggregate(_, operands) => operands.iter().collect(),
+        mir::Rvalue::CopyForDeref(_)
+        | mir::Rvalue::Ref(_, _, _)
+        | mir::Rvalue::ThreadLocalRef(_)
+        | mir::Rvalue::AddressOf(_, _)
+        | mir::Rvalue::Len(_)
+        | mir::Rvalue::NullaryOp(_, _)
+        | mir::Rvalue::Discriminant(_) => Vec::new(),
+    }
+}
+
+/// Compute the spans of the expressions of a statement or of a terminator
+/// (see [meta::ExprMetas]), from its source information and the MIR operands
+/// it reads.
+///
+/// `translated` gives the operands of the translated statement. If they
+/// don't match the MIR operands (for instance, when we take the address of
+/// a function, the function operand disappears), we don't know which MIR
+/// operand a translated operand comes from: we don't keep their spans.
+fn translate_expr_metas<'tcx>(
+    bt_ctx: &BodyTransContext<'tcx, '_, '_>,
+    body: &Body<'tcx>,
+    source_info: mir::SourceInfo,
+    operands: &[&Operand<'tcx>],
+    translated: &[&e::Operand],
+) -> meta::ExprMetas {
+    let expr_meta = |span| {
+        meta::get_expr_meta(
+            bt_ctx.ft_ctx.sess,
+            &bt_ctx.ft_ctx.ordered.file_to_id,
+            &body.source_scopes,
+            source_info.scope,
+            span,
+        )
+    };
+
+    // MIR only gives the spans of the constants
+    let mut operand_metas: Vec<Option<meta::Meta>> = Vec::new();
+    if operands.len() == translated.len() {
+        operand_metas = operands
+            .iter()
+            .map(|op| match op {
+                Operand::Constant(constant) => expr_meta(constant.span),
+                Operand::Copy(_) | Operand::Move(_) => None,
+            })
+            .collect();
+    }
+    if operand_metas.iter().all(Option::is_none) {
+        operand_metas.clear();
+    }
+
+    meta::ExprMetas {
+        expr: expr_meta(source_info.span),
+        operands: operand_metas,
     }
 }
 
@@ -1396,14 +1888,31 @@ fn translate_statement<'tcx>(
     let t_statement: Option<ast::RawStatement> = match &statement.kind {
         StatementKind::Assign(assign) => {
             let (place, rvalue) = assign.deref();
-            let t_place = translate_place(bt_ctx, place);
-            let t_rvalue = translate_rvalue(tcx, bt_ctx, rvalue);
+            let mut t_place = translate_place(bt_ctx, place)?;
+            let t_rvalue = match rvalue {
+                // Building a union (`u = U { f: x }`) amounts to writing
+                // the active field (`u.f := x`). Note that the active field
+                // is only given for the unions.
+                mir::Rvalue::Aggregate(kind, operands) => match kind.deref() {
+                    mir::AggregateKind::Adt(adt_id, _, _, _, Some(field_index)) => {
+                        assert!(operands.len() == 1);
+                        let type_id = *bt_ctx.ft_ctx.ordered.type_rid_to_id.get(adt_id).unwrap();
+                        t_place.projection.push_back(e::ProjectionElem::Field(
+                            e::FieldProjKind::Union(type_id),
+                            translate_field(*field_index),
+                        ));
+                        e::Rvalue::Use(translate_operand(bt_ctx, &operands[0])?)
+                    }
+                    _ => translate_rvalue(tcx, bt_ctx, rvalue)?,
+                },
+                _ => translate_rvalue(tcx, bt_ctx, rvalue)?,
+            };
 
             Some(ast::RawStatement::Assign(t_place, t_rvalue))
         }
         StatementKind::FakeRead(info) => {
             let (_read_cause, place) = info.deref();
-            let t_place = translate_place(bt_ctx, place);
+            let t_place = translate_place(bt_ctx, place)?;
 
             Some(ast::RawStatement::FakeRead(t_place))
         }
@@ -1411,7 +1920,7 @@ fn translate_statement<'tcx>(
             place,
             variant_index,
         } => {
-            let t_place = translate_place(bt_ctx, place);
+            let t_place = translate_place(bt_ctx, place)?;
             let variant_id = translate_variant_id(*variant_index);
             Some(ast::RawStatement::SetDiscriminant(t_place, variant_id))
         }
@@ -1443,7 +1952,7 @@ fn translate_statement<'tcx>(
             None
         }
         StatementKind::Deinit(place) => {
-            let t_place = translate_place(bt_ctx, place);
+            let t_place = translate_place(bt_ctx, place)?;
             Some(ast::RawStatement::Deinit(t_place))
         }
         StatementKind::Intrinsic(_) => {
@@ -1462,7 +1971,38 @@ fn translate_statement<'tcx>(
                 statement.source_info,
             );
 
-            Ok(Some(ast::Statement::new(meta, t_statement)))
+            // The assignments are the only statements which evaluate an
+            // expression
+            let expr_metas = match &statement.kind {
+                StatementKind::Assign(assign) => translate_expr_metas(
+                    bt_ctx,
+                    body,
+                    statement.source_info,
+                    &rvalue_operands(&assign.1),
+                    &t_statement.operands(),
+                ),
+                _ => meta::ExprMetas::default(),
+            };
+
+            Ok(Some(
+                ast::Statement::new(meta, t_statement).with_expr_metas(expr_metas),
+            ))
+        }
+    }
+}
+
+/// Translate the kind of an assertion (we ignore the operands of the message)
+fn translate_assert_kind(msg: &mir::AssertMessage<'_>) -> ast::AssertKind {
+    match msg {
+        mir::AssertKind::BoundsCheck { len: _, index: _ } => ast::AssertKind::BoundsCheck,
+        mir::AssertKind::Overflow(_, _, _) | mir::AssertKind::OverflowNeg(_) => {
+            ast::AssertKind::Overflow
+        }
+        mir::AssertKind::DivisionByZero(_) | mir::AssertKind::RemainderByZero(_) => {
+            ast::AssertKind::DivisionByZero
+        }
+        mir::AssertKind::ResumedAfterReturn(_) | mir::AssertKind::ResumedAfterPanic(_) => {
+            ast::AssertKind::Other
         }
     }
 }
@@ -1487,6 +2027,7 @@ fn translate_terminator<'tcx>(
     );
 
     // Translate the terminator
+    let mut loop_invariants = Vec::new();
     let t_terminator: ast::RawTerminator = match &terminator.kind {
         TerminatorKind::Goto { target } => {
             let target = translate_basic_block(bt_ctx, body, *target)?;
@@ -1494,7 +2035,7 @@ fn translate_terminator<'tcx>(
         }
         TerminatorKind::SwitchInt { discr, targets } => {
             // Translate the operand which gives the discriminant
-            let (discr, discr_ty) = translate_operand_with_type(bt_ctx, discr);
+            let (discr, discr_ty) = translate_operand_with_type(bt_ctx, discr)?;
 
             // Translate the switch targets
             let targets = translate_switch_targets(bt_ctx, body, &discr_ty, targets)?;
@@ -1518,7 +2059,7 @@ fn translate_terminator<'tcx>(
             target,
             unwind: _,
         } => ast::RawTerminator::Drop {
-            place: translate_place(bt_ctx, place),
+            place: translate_place(bt_ctx, place)?,
             target: translate_basic_block(bt_ctx, body, *target)?,
         },
         TerminatorKind::DropAndReplace {
@@ -1533,8 +2074,8 @@ fn translate_terminator<'tcx>(
             let target = translate_basic_block(bt_ctx, body, *target)?;
 
             // Translate the assignment
-            let place = translate_place(bt_ctx, place);
-            let rv = e::Rvalue::Use(translate_operand(bt_ctx, value));
+            let place = translate_place(bt_ctx, place)?;
+            let rv = e::Rvalue::Use(translate_operand(bt_ctx, value)?);
             let assign = ast::Statement::new(meta, ast::RawStatement::Assign(place.clone(), rv));
 
             // Generate a goto
@@ -1569,15 +2110,17 @@ fn translate_terminator<'tcx>(
         TerminatorKind::Assert {
             cond,
             expected,
-            msg: _, // We ignore the message: if we panic, the state gets stuck
+            msg, // We only keep the kind of the message: if we panic, the state gets stuck
             target,
             cleanup: _, // If we panic, the state gets stuck: we don't need to model cleanup
         } => {
-            let cond = translate_operand(bt_ctx, cond);
+            let cond = translate_operand(bt_ctx, cond)?;
+            let kind = translate_assert_kind(msg);
             let target = translate_basic_block(bt_ctx, body, *target)?;
             ast::RawTerminator::Assert {
                 cond,
                 expected: *expected,
+                kind,
                 target,
             }
         }
@@ -1615,7 +2158,10 @@ fn translate_terminator<'tcx>(
             real_target,
             unwind: _,
         } => {
-            // We consider this to be a goto
+            // We consider this to be a goto. This terminator enters a loop,
+            // and has the span of the loop expression.
+            let span = terminator.source_info.span;
+            loop_invariants = bt_ctx.loop_invariants.remove(&span).unwrap_or_default();
             let target = translate_basic_block(bt_ctx, body, *real_target)?;
             ast::RawTerminator::Goto { target }
         }
@@ -1624,16 +2170,54 @@ fn translate_terminator<'tcx>(
             operands: _,
             options: _,
             line_spans: _,
-            destination: _,
+            destination,
             cleanup: _,
         } => {
-            // This case should have been eliminated during the registration phase
-            unreachable!();
+            // We don't model the assembly code: we ignore it, and remember
+            // that the translation is imprecise
+            bt_ctx
+                .precision_losses
+                .push(ast::PrecisionLoss::InlineAsm(meta));
+            match destination {
+                Some(target) => {
+                    let target = translate_basic_block(bt_ctx, body, *target)?;
+                    ast::RawTerminator::Goto { target }
+                }
+                // The assembly code never returns
+                None => ast::RawTerminator::Unreachable,
+            }
+        }
+    };
+
+    // Add the spans of the expressions the terminator evaluates
+    let operands: Vec<&Operand<'tcx>> = match &terminator.kind {
+        TerminatorKind::SwitchInt { discr, .. } => vec![discr],
+        TerminatorKind::Assert { cond, .. } => vec![cond],
+        TerminatorKind::Call { func, args, .. } => {
+            // The function operand is only kept for the indirect calls
+            let mut operands: Vec<&Operand<'tcx>> = Vec::new();
+            if get_function_from_operand(func).is_none() {
+                operands.push(func);
+            }
+            operands.extend(args.iter());
+            operands
+        }
+        _ => {
+            // The other terminators don't evaluate expressions
+            let terminator = ast::Terminator::new(meta, t_terminator);
+            return Ok(terminator.with_loop_invariants(loop_invariants));
         }
     };
+    let expr_metas = translate_expr_metas(
+        bt_ctx,
+        body,
+        terminator.source_info,
+        &operands,
+        &t_terminator.operands(),
+    );
 
     // Add the meta information
-    Ok(ast::Terminator::new(meta, t_terminator))
+    Ok(ast::Terminator::new(meta, t_terminator).with_expr_metas(expr_metas))
 }
 
 /// Translate switch targets
@@ -1693,15 +2277,16 @@ fn translate_switch_targets<'tcx>(
 /// parameters substitution.
 /// The `Operand` comes from a `TerminatorKind::Call`.
 /// Only supports calls to top-level functions (which are considered as constants
-/// by rustc); doesn't support closures for now.
+/// by rustc); doesn't support function pointers for now. Note that the calls
+/// to closures are calls to the (top-level) methods of the `Fn` traits.
 fn get_function_from_operand<'tcx>(
     func: &Operand<'tcx>,
-) -> (DefId, &'tcx rustc_middle::ty::subst::InternalSubsts<'tcx>) {
+) -> Option<(DefId, &'tcx rustc_middle::ty::subst::InternalSubsts<'tcx>)> {
     trace!("func: {:?}", func);
 
     use std::ops::Deref;
-    // Match on the func operand: it should be a constant as we don't support
-    // closures for now.
+    // Match on the func operand: it is a constant, unless we call a function
+    // pointer, in which case we return `None`.
     match func {
         mir::Operand::Constant(c) => {
             trace!("Operand::Constant: {:?}", c);
@@ -1713,7 +2298,7 @@ fn get_function_from_operand<'tcx>(
                     let c_ty = c.ty();
                     assert!(c_ty.is_fn());
                     match c_ty.kind() {
-                        mir_ty::TyKind::FnDef(def_id, subst) => (*def_id, subst),
+                        mir_ty::TyKind::FnDef(def_id, subst) => Some((*def_id, subst)),
                         _ => {
                             unreachable!();
                         }
@@ -1724,7 +2309,7 @@ fn get_function_from_operand<'tcx>(
                     // Same as for the `Ty` case above
                     assert!(c_ty.is_fn());
                     match c_ty.kind() {
-                        mir_ty::TyKind::FnDef(def_id, subst) => (*def_id, subst),
+                        mir_ty::TyKind::FnDef(def_id, subst) => Some((*def_id, subst)),
                         _ => {
                             unreachable!();
                         }
@@ -1735,9 +2320,7 @@ fn get_function_from_operand<'tcx>(
                 }
             }
         }
-        mir::Operand::Move(_place) | mir::Operand::Copy(_place) => {
-            unimplemented!();
-        }
+        mir::Operand::Move(_) | mir::Operand::Copy(_) => None,
     }
 }
 
@@ -1812,14 +2395,28 @@ fn translate_function_call<'tcx>(
 ) -> Result<ast::RawTerminator> {
     trace!();
 
-    // Translate the function operand - should be a constant: we don't
-    // support closures for now
     trace!("func: {:?}", func);
 
     let tcx = bt_ctx.ft_ctx.tcx;
 
-    // Retrieve the function's identifier and instantiation
-    let (def_id, substs) = get_function_from_operand(func);
+    // Retrieve the function's identifier and instantiation. If the function
+    // operand is not a constant, this is a call through a function pointer.
+    let (def_id, substs) = match get_function_from_operand(func) {
+        Some(fun) => fun,
+        None => {
+            let lval = translate_place(bt_ctx, destination)?;
+            let next_block = translate_basic_block(bt_ctx, body, target.unwrap())?;
+            return Ok(ast::RawTerminator::Call {
+                func: ast::FunId::Indirect(translate_operand(bt_ctx, func)?),
+                region_args: vec![],
+                type_args: vec![],
+                args: translate_arguments(bt_ctx, None, args)?,
+                dest: lval,
+                sig: None,
+                target: next_block,
+            });
+        }
+    };
 
     // Translate the name to check if is is `core::panicking::panic`
     let name = function_def_id_to_name(tcx, def_id);
@@ -1839,9 +2436,59 @@ fn translate_function_call<'tcx>(
         let next_block = target.unwrap();
 
         // Translate the target
-        let lval = translate_place(bt_ctx, destination);
+        let lval = translate_place(bt_ctx, destination)?;
         let next_block = translate_basic_block(bt_ctx, body, next_block)?;
 
+        // The calls to closures are calls to the methods of the `Fn` traits
+        if let Some((closure_id, kind)) = closures::get_closure_call(tcx, def_id, substs) {
+            return translate_closure_call(
+                bt_ctx, closure_id, kind, substs, args, lval, next_block,
+            );
+        }
+
+        // `<Box<T> as From<T>>::from(x)` is the same as `Box::new(x)`
+        if translate_traits::is_box_from_call(tcx, def_id, substs) {
+            let ty = translate_ety(bt_ctx, &substs.type_at(1))?;
+            return Ok(ast::RawTerminator::Call {
+                func: ast::FunId::Assumed(ast::AssumedFunId::BoxNew),
+                region_args: vec![],
+                type_args: vec![ty],
+                args: translate_arguments(bt_ctx, None, args)?,
+                dest: lval,
+                sig: None,
+                target: next_block,
+            });
+        }
+
+        // `<Rc<T> as Clone>::clone` and `<Arc<T> as Clone>::clone`: the type
+        // argument is `T`
+        if translate_traits::is_rc_clone_call(tcx, def_id, substs) {
+            let (aid, ty) = match translate_ety(bt_ctx, &substs.type_at(0))? {
+                ty::Ty::Adt(ty::TypeId::Assumed(ty::AssumedTy::Rc), _, tys) => {
+                    (ast::AssumedFunId::RcClone, tys[0].clone())
+                }
+                ty::Ty::Adt(ty::TypeId::Assumed(ty::AssumedTy::Arc), _, tys) => {
+                    (ast::AssumedFunId::ArcClone, tys[0].clone())
+                }
+                _ => unreachable!(),
+            };
+            return Ok(ast::RawTerminator::Call {
+                func: ast::FunId::Assumed(aid),
+                region_args: vec![],
+                type_args: vec![ty],
+                args: translate_arguments(bt_ctx, None, args)?,
+                dest: lval,
+                sig: None,
+                target: next_block,
+            });
+        }
+
+        // The allocation of a box initialized in place: the box is created
+        // by the `ShallowInitBox` which follows (see [translate_basic_block])
+        if name.equals_ref_name(&assumed::EXCHANGE_MALLOC_NAME) {
+            return Ok(ast::RawTerminator::Goto { target: next_block });
+        }
+
         // There is something annoying: when going to MIR, the rust compiler
         // sometimes introduces very low-level functions, which we need to
         // catch early - in particular, before we start translating types and
@@ -1878,12 +2525,16 @@ fn translate_function_call<'tcx>(
                 type_args: vec![t_ty],
                 args: vec![t_arg],
                 dest: lval,
+                sig: None,
                 target: next_block,
             })
         } else {
             // Retrieve the lists of used parameters, in case of non-local
             // definitions
-            let (used_type_args, used_args) = if def_id.is_local() {
+            // The iterator methods implemented for local types are not primitive
+            let is_local_iterator_call =
+                translate_traits::is_local_iterator_call(tcx, def_id, substs);
+            let (used_type_args, used_args) = if def_id.is_local() || is_local_iterator_call {
                 (Option::None, Option::None)
             } else {
                 match assumed::function_to_info(&name) {
@@ -1895,27 +2546,47 @@ fn translate_function_call<'tcx>(
                 }
             };
 
-            // Translate the type parameters
-            let (region_args, type_args) =
-                translate_subst_generic_args_in_body(bt_ctx, used_type_args, substs)?;
-
-            // Translate the arguments
-            let args = translate_arguments(bt_ctx, used_args, args);
-
             // Check if the function is considered primitive: primitive
             // functions benefit from special treatment.
-            let name = function_def_id_to_name(tcx, def_id);
-            let is_prim = if def_id.is_local() {
+            let is_prim = if def_id.is_local() || is_local_iterator_call {
                 false
             } else {
                 assumed::get_fun_id_from_name(&name).is_some()
             };
 
+            // The calls to the methods of the trait objects are dispatched at
+            // runtime: we can't resolve them
+            let is_virtual =
+                !is_prim && translate_traits::is_virtual_call(tcx, bt_ctx.def_id, def_id, substs);
+
+            // Resolve the calls to trait methods (see [crate::register]: we
+            // must do it after identifying the primitive functions)
+            let (def_id, substs) = if is_prim {
+                (def_id, substs)
+            } else {
+                translate_traits::resolve_trait_method(tcx, bt_ctx.def_id, def_id, substs)
+            };
+
+            // Translate the type parameters
+            let (region_args, type_args) =
+                translate_subst_generic_args_in_body(bt_ctx, used_type_args, substs)?;
+
+            // Translate the arguments
+            let args = translate_arguments(bt_ctx, used_args, args)?;
+
             if !is_prim {
                 // Retrieve the def id
-                let def_id = bt_ctx.ft_ctx.get_def_id_from_rid(def_id).unwrap();
-
-                let func = ast::FunId::Regular(def_id);
+                let fun_id = bt_ctx.ft_ctx.get_def_id_from_rid(def_id).unwrap();
+
+                let func = if is_virtual {
+                    let trait_id = bt_ctx.ft_ctx.get_trait_decl_id_from_method(def_id).unwrap();
+                    ast::FunId::Virtual(trait_id, fun_id)
+                } else {
+                    match bt_ctx.ft_ctx.get_trait_impl_id_from_method(def_id) {
+                        Some(impl_id) => ast::FunId::TraitMethod(impl_id, fun_id),
+                        None => ast::FunId::Regular(fun_id),
+                    }
+                };
 
                 Ok(ast::RawTerminator::Call {
                     func,
@@ -1923,6 +2594,7 @@ fn translate_function_call<'tcx>(
                     type_args,
                     args,
                     dest: lval,
+                    sig: None,
                     target: next_block,
                 })
             } else {
@@ -1936,7 +2608,7 @@ fn translate_function_call<'tcx>(
                 // `box_deref<T>`
                 // (the type parameter is not `Box<T>` but `T`).
                 translate_primitive_function_call(
-                    tcx,
+                    bt_ctx,
                     def_id,
                     region_args,
                     type_args,
@@ -1949,9 +2621,88 @@ fn translate_function_call<'tcx>(
     }
 }
 
+/// Translate a call to a closure (see [crate::closures]).
+///
+/// rustc desugars `f(x, y)` to a call to a method of the `Fn` traits, with
+/// tupled arguments: `Fn::call(&f, (x, y))`. We call the function we generated
+/// for the closure body instead, with the arguments untupled: the environment
+/// comes first, followed by the fields of the tuple.
+fn translate_closure_call<'tcx>(
+    bt_ctx: &BodyTransContext<'tcx, '_, '_>,
+    closure_id: DefId,
+    kind: mir_ty::ClosureKind,
+    substs: &rustc_middle::ty::subst::InternalSubsts<'tcx>,
+    args: &Vec<Operand<'tcx>>,
+    dest: e::Place,
+    target: ast::BlockId::Id,
+) -> Result<ast::RawTerminator> {
+    // The environment must be given the way the closure body expects it,
+    // which depends on the trait the closure implements
+    let closure_kind = match substs.type_at(0).kind() {
+        TyKind::Closure(_, closure_substs) => closure_substs.as_closure().kind(),
+        _ => unreachable!(),
+    };
+    if kind != closure_kind {
+        let span = bt_ctx.ft_ctx.tcx.def_span(bt_ctx.def_id);
+        let msg = format!(
+            "Calls to `{closure_kind:?}` closures through the `{kind:?}` trait are not supported"
+        );
+        span_err(bt_ctx.ft_ctx.sess, span, &msg);
+        return Err(());
+    }
+    assert!(args.len() == 2);
+    let mut t_args = vec![translate_operand(bt_ctx, &args[0])?];
+
+    // Untuple the arguments
+    let arity = substs.type_at(1).tuple_fields().len();
+    match &args[1] {
+        mir::Operand::Move(place) => {
+            let tuple = translate_place(bt_ctx, place)?;
+            for i in 0..arity {
+                let mut field = tuple.clone();
+                field.projection.push_back(e::ProjectionElem::Field(
+                    e::FieldProjKind::Tuple(arity),
+                    FieldId::Id::new(i),
+                ));
+                t_args.push(e::Operand::Move(field));
+            }
+        }
+        // The closures without arguments receive the unit constant
+        mir::Operand::Constant(_) => assert!(arity == 0),
+        mir::Operand::Copy(_) => unreachable!(),
+    }
+
+    // The region parameters of the closure are the ones of its environment,
+    // followed by the region of the borrow of the environment (if any): see
+    // [translate_closure_signature]
+    let env_id = *bt_ctx
+        .ft_ctx
+        .ordered
+        .type_rid_to_id
+        .get(&closure_id)
+        .unwrap();
+    let env_def = bt_ctx.get_type_defs().get_type_def(env_id).unwrap();
+    let num_regions = match kind {
+        mir_ty::ClosureKind::Fn | mir_ty::ClosureKind::FnMut => env_def.region_params.len() + 1,
+        mir_ty::ClosureKind::FnOnce => env_def.region_params.len(),
+    };
+
+    let fun_id = bt_ctx.ft_ctx.get_def_id_from_rid(closure_id).unwrap();
+    Ok(ast::RawTerminator::Call {
+        func: ast::FunId::Regular(fun_id),
+        region_args: vec![ty::ErasedRegion::Erased; num_regions],
+        type_args: vec![],
+        args: t_args,
+        dest,
+        sig: None,
+        target,
+    })
+}
+
 /// Translate a parameter substitution used inside a function body.
 ///
-/// Note that the regions parameters are expected to have been erased.
+/// Note that the regions parameters are expected to have been erased. The
+/// const generic arguments come after the type arguments (see [ty::Ty::Adt]).
 fn translate_subst_generic_args_in_body<'tcx, 'ctx, 'ctx1>(
     bt_ctx: &BodyTransContext<'tcx, 'ctx, 'ctx1>,
     used_args: Option<Vec<bool>>,
@@ -1971,6 +2722,7 @@ fn translate_subst_generic_args_in_body<'tcx, 'ctx, 'ctx1>(
 
     let mut t_args_regions = Vec::new();
     let mut t_args_tys = Vec::new();
+    let mut t_args_cgs = Vec::new();
     for param in substs.iter() {
         match param.unpack() {
             rustc_middle::ty::subst::GenericArgKind::Type(param_ty) => {
@@ -1981,11 +2733,17 @@ fn translate_subst_generic_args_in_body<'tcx, 'ctx, 'ctx1>(
             rustc_middle::ty::subst::GenericArgKind::Lifetime(region) => {
                 t_args_regions.push(translate_erased_region(region.kind()));
             }
-            rustc_middle::ty::subst::GenericArgKind::Const(_) => {
-                unimplemented!();
+            rustc_middle::ty::subst::GenericArgKind::Const(c) => {
+                let cg = translate_types::translate_const_generic(
+                    bt_ctx.ft_ctx.tcx,
+                    &bt_ctx.rconst_generic_vars_to_ids,
+                    c,
+                );
+                t_args_cgs.push(ty::Ty::ConstGeneric(cg));
             }
         }
     }
+    t_args_tys.append(&mut t_args_cgs);
 
     Ok((t_args_regions, t_args_tys))
 }
@@ -2011,7 +2769,7 @@ fn translate_arguments<'tcx, 'ctx, 'ctx1>(
     bt_ctx: &BodyTransContext<'tcx, 'ctx, 'ctx1>,
     used_args: Option<Vec<bool>>,
     args: &Vec<Operand<'tcx>>,
-) -> Vec<e::Operand> {
+) -> Result<Vec<e::Operand>> {
     let args: Vec<&Operand<'tcx>> = match used_args {
         Option::None => args.iter().collect(),
         Option::Some(used_args) => {
@@ -2036,17 +2794,17 @@ fn translate_arguments<'tcx, 'ctx, 'ctx1>(
         }
 
         // Translate
-        let op = translate_operand(bt_ctx, arg);
+        let op = translate_operand(bt_ctx, arg)?;
         t_args.push(op);
     }
 
-    t_args
+    Ok(t_args)
 }
 
 /// Translate a call to a function considered primitive and which is not:
 /// panic, begin_panic, box_free (those have a *very* special treatment).
 fn translate_primitive_function_call(
-    tcx: TyCtxt<'_>,
+    bt_ctx: &BodyTransContext<'_, '_, '_>,
     def_id: DefId,
     region_args: Vec<ty::ErasedRegion>,
     type_args: Vec<ty::ETy>,
@@ -2055,6 +2813,7 @@ fn translate_primitive_function_call(
     target: ast::BlockId::Id,
 ) -> Result<ast::RawTerminator> {
     trace!("- def_id: {:?}", def_id,);
+    let tcx = bt_ctx.ft_ctx.tcx;
 
     // Translate the function name
     let name = function_def_id_to_name(tcx, def_id);
@@ -2080,24 +2839,43 @@ fn translate_primitive_function_call(
     match aid {
         ast::AssumedFunId::Replace
         | ast::AssumedFunId::BoxNew
+        | ast::AssumedFunId::RcNew
+        | ast::AssumedFunId::ArcNew
         | ast::AssumedFunId::VecNew
         | ast::AssumedFunId::VecPush
         | ast::AssumedFunId::VecInsert
-        | ast::AssumedFunId::VecLen => Ok(ast::RawTerminator::Call {
+        | ast::AssumedFunId::VecLen
+        | ast::AssumedFunId::OptionUnwrap
+        | ast::AssumedFunId::OptionExpect
+        | ast::AssumedFunId::OptionUnwrapOr
+        | ast::AssumedFunId::OptionIsSome
+        | ast::AssumedFunId::OptionIsNone
+        | ast::AssumedFunId::OptionMap
+        | ast::AssumedFunId::ResultUnwrap
+        | ast::AssumedFunId::ResultExpect
+        | ast::AssumedFunId::ResultIsOk
+        | ast::AssumedFunId::ResultIsErr
+        | ast::AssumedFunId::ResultMap
+        | ast::AssumedFunId::ResultMapErr
+        | ast::AssumedFunId::TryBranch
+        | ast::AssumedFunId::FromResidual
+        | ast::AssumedFunId::IntoIter
+        | ast::AssumedFunId::IteratorNext => Ok(ast::RawTerminator::Call {
             func: ast::FunId::Assumed(aid),
             region_args,
             type_args,
             args,
             dest,
+            sig: None,
             target,
         }),
         ast::AssumedFunId::BoxDeref | ast::AssumedFunId::BoxDerefMut => {
             translate_box_deref(aid, region_args, type_args, args, dest, target)
         }
         ast::AssumedFunId::VecIndex | ast::AssumedFunId::VecIndexMut => {
-            translate_vec_index(aid, region_args, type_args, args, dest, target)
+            translate_vec_index(bt_ctx, aid, region_args, type_args, args, dest, target)
         }
-        ast::AssumedFunId::BoxFree => {
+        ast::AssumedFunId::BoxFree | ast::AssumedFunId::RcClone | ast::AssumedFunId::ArcClone => {
             unreachable!();
         }
     }
@@ -2137,13 +2915,16 @@ fn translate_box_deref(
         type_args,
         args,
         dest,
+        sig: None,
         target,
     })
 }
 
 /// Translate `core::ops::index::{Index,IndexMut}::{index,index_mut}`
-/// applied on `Vec`. We need a custom function because it is a trait.
+/// applied on `Vec` (or on arrays and slices, with a range). We need a custom
+/// function because it is a trait.
 fn translate_vec_index(
+    bt_ctx: &BodyTransContext<'_, '_, '_>,
     aid: ast::AssumedFunId,
     region_args: Vec<ty::ErasedRegion>,
     type_args: Vec<ty::ETy>,
@@ -2156,17 +2937,25 @@ fn translate_vec_index(
     assert!(type_args.len() == 1);
     assert!(args.len() == 2);
 
-    // For now we only support index on vectors
-    // Retrieve the boxed value
-    let arg_ty = type_args.get(0).unwrap(); // should be `Vec<...>`
-    let arg_ty = match arg_ty.as_vec() {
-        Option::Some(ty) => ty,
-        Option::None => {
-            panic!(
-            "Index/IndexMut trait applied with parameter {:?} while it is only supported on Vec<T> values",
-            arg_ty
-        );
-        }
+    // We support the indexing of vectors, as well as the indexing of arrays
+    // and slices with a range (`&a[1..3]`, which we reconstruct in
+    // [crate::slice_accesses]): indexing them with a `usize` doesn't go
+    // through the `Index` trait. Retrieve the type of the elements.
+    let arg_ty = type_args.get(0).unwrap();
+    let arg_ty = match arg_ty {
+        ty::Ty::Array(ty, _) | ty::Ty::Slice(ty) => ty.as_ref(),
+        _ => match arg_ty.as_vec() {
+            Option::Some(ty) => ty,
+            Option::None => {
+                let span = bt_ctx.ft_ctx.tcx.def_span(bt_ctx.def_id);
+                let msg = format!(
+                    "Index/IndexMut trait applied with parameter {:?} while it is only supported on vectors, arrays and slices",
+                    arg_ty
+                );
+                span_err(bt_ctx.ft_ctx.sess, span, &msg);
+                return Err(());
+            }
+        },
     };
 
     let type_args = vec![arg_ty.clone()];
@@ -2176,6 +2965,7 @@ fn translate_vec_index(
         type_args,
         args,
         dest,
+        sig: None,
         target,
     })
 }
@@ -2260,28 +3050,7 @@ fn translate_function_signature<'tcx, 'ctx, 'ctx1>(
         }
     };
 
-    for param in substs.iter() {
-        match param.unpack() {
-            rustc_middle::ty::subst::GenericArgKind::Type(param_ty) => {
-                // This type should be a param type
-                match param_ty.kind() {
-                    TyKind::Param(param_ty) => {
-                        bt_ctx.push_type_var(param_ty.index, param_ty.name.to_ident_string());
-                    }
-                    _ => {
-                        unreachable!();
-                    }
-                }
-            }
-            rustc_middle::ty::subst::GenericArgKind::Lifetime(region) => {
-                let name = translate_region_name(&region);
-                bt_ctx.push_region(*region, name);
-            }
-            rustc_middle::ty::subst::GenericArgKind::Const(_) => {
-                unimplemented!();
-            }
-        }
-    }
+    push_generic_params(&mut bt_ctx, substs);
 
     // Instantiate the regions bound in the signature, and generate a mapping
     // while doing so (the mapping uses a linked hash map so that we remember
@@ -2319,36 +3088,205 @@ fn translate_function_signature<'tcx, 'ctx, 'ctx1>(
     );
     let output = translate_sig_ty(&bt_ctx, &signature.output()).unwrap();
 
+    // Translate the bounds on the parameters
+    let ty_ctx = TypeTransContext::new(bt_ctx.ft_ctx.type_defs, bt_ctx.ft_ctx.ordered);
+    let trait_clauses = translate_types::translate_trait_clauses(
+        tcx,
+        &ty_ctx,
+        &bt_ctx.rregions_to_ids,
+        &bt_ctx.rtype_vars_to_rtypes,
+        &bt_ctx.rconst_generic_vars_to_ids,
+        def_id,
+    )
+    .unwrap();
+
     trace!(
         "# Input variables types:\n{}",
         iterator_to_string(&|x| bt_ctx.format_object(x), inputs.iter())
     );
     trace!("# Output variable type:\n{}", bt_ctx.format_object(&output));
 
+    let sig = mk_function_signature(
+        types_constraints,
+        &bt_ctx,
+        late_bound_regions.len(),
+        trait_clauses,
+        inputs,
+        output,
+    );
+    (bt_ctx, sig)
+}
+
+/// Introduce the parameters of a function in the body translation context,
+/// from the "normal" substitution of the function (which only contains
+/// parameters).
+fn push_generic_params<'tcx>(
+    bt_ctx: &mut BodyTransContext<'tcx, '_, '_>,
+    substs: &[rustc_middle::ty::subst::GenericArg<'tcx>],
+) {
+    for param in substs.iter() {
+        match param.unpack() {
+            rustc_middle::ty::subst::GenericArgKind::Type(param_ty) => {
+                // This type should be a param type
+                match param_ty.kind() {
+                    TyKind::Param(param_ty) => {
+                        bt_ctx.push_type_var(param_ty.index, param_ty.name.to_ident_string());
+                    }
+                    _ => {
+                        unreachable!();
+                    }
+                }
+            }
+            rustc_middle::ty::subst::GenericArgKind::Lifetime(region) => {
+                let name = translate_region_name(&region);
+                bt_ctx.push_region(*region, name);
+            }
+            rustc_middle::ty::subst::GenericArgKind::Const(c) => {
+                // This constant should be a param
+                match c.kind() {
+                    ConstKind::Param(param) => {
+                        let ty = translate_types::translate_const_generic_param_ty(c.ty());
+                        bt_ctx.push_const_generic_var(
+                            param.index,
+                            param.name.to_ident_string(),
+                            ty,
+                        );
+                    }
+                    _ => {
+                        unreachable!();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Build a function signature from the parameters introduced in the body
+/// translation context, and compute its regions hierarchy.
+fn mk_function_signature(
+    types_constraints: &TypesConstraintsMap,
+    bt_ctx: &BodyTransContext<'_, '_, '_>,
+    num_late_bound_regions: usize,
+    trait_clauses: Vec<ty::TraitClause>,
+    inputs: Vec<ty::RTy>,
+    output: ty::RTy,
+) -> ast::FunSig {
     let sig = ast::FunSig {
         region_params: bt_ctx.regions.clone(),
-        num_early_bound_regions: late_bound_regions.len(),
+        // The early-bound regions are the ones we introduced before the
+        // late-bound ones
+        num_early_bound_regions: bt_ctx.regions.len() - num_late_bound_regions,
         regions_hierarchy: rh::RegionGroups::new(), // Hierarchy not yet computed
         type_params: bt_ctx.type_vars.clone(),
+        const_generic_params: bt_ctx.const_generic_vars.clone(),
+        trait_clauses,
         inputs,
         output,
     };
 
     // Analyze the signature to compute the regions hierarchy
     let regions_hierarchy = rh::compute_regions_hierarchy_for_sig(types_constraints, &sig);
-    let sig = ast::FunSig {
+    ast::FunSig {
         regions_hierarchy,
         ..sig
+    }
+}
+
+/// Translate the signature of the function we generate for the body of a
+/// closure (see [crate::closures]).
+///
+/// We can't use [TyCtxt::fn_sig] for the closures: rustc gives us the
+/// signature of the closure as written in the source, and we have to add the
+/// environment as first parameter. The environment is borrowed if the closure
+/// implements `Fn` or `FnMut`, and moved if it only implements `FnOnce`. The
+/// closure bodies in MIR follow the same convention.
+fn translate_closure_signature<'tcx, 'ctx, 'ctx1>(
+    types_constraints: &TypesConstraintsMap,
+    decl_ctx: &'ctx DeclTransContext<'tcx, 'ctx1>,
+    def_id: DefId,
+) -> (BodyTransContext<'tcx, 'ctx, 'ctx1>, ast::FunSig) {
+    let tcx = decl_ctx.tcx;
+    let mut bt_ctx = BodyTransContext::new(def_id, decl_ctx);
+
+    // **Sanity checks on the HIR**
+    generics::check_function_generics(tcx, def_id);
+
+    let substs = match tcx.type_of(def_id).kind() {
+        TyKind::Closure(_, substs) => substs.as_closure(),
+        _ => unreachable!(),
     };
+    // The parameters of the parent function: we don't translate the bodies
+    // of the closures defined in generic functions (see
+    // [translate_types::unsupported_closure]), but their signatures may
+    // refer to those parameters
+    push_generic_params(&mut bt_ctx, substs.parent_substs());
+
+    // The environment: we introduce one region per region parameter of its
+    // declaration (i.e., per variable captured by reference), then one region
+    // for the borrow of the environment itself
+    let env_id = *decl_ctx.ordered.type_rid_to_id.get(&def_id).unwrap();
+    let env_def = decl_ctx.type_defs.get_type_def(env_id).unwrap();
+    let env_regions = env_def
+        .region_params
+        .iter()
+        .map(|r| ty::Region::Var(bt_ctx.push_fresh_region(r.name.clone())))
+        .collect();
+    let env_ty = ty::Ty::Adt(ty::TypeId::Adt(env_id), env_regions, Vector::new());
+    let env_ty = match substs.kind() {
+        mir_ty::ClosureKind::Fn => {
+            let r = ty::Region::Var(bt_ctx.push_fresh_region(None));
+            ty::Ty::Ref(r, Box::new(env_ty), ty::RefKind::Shared)
+        }
+        mir_ty::ClosureKind::FnMut => {
+            let r = ty::Region::Var(bt_ctx.push_fresh_region(None));
+            ty::Ty::Ref(r, Box::new(env_ty), ty::RefKind::Mut)
+        }
+        mir_ty::ClosureKind::FnOnce => env_ty,
+    };
+
+    // Instantiate the regions bound in the signature, like for the regular
+    // functions
+    let (signature, late_bound_regions) =
+        generics::replace_late_bound_regions(tcx, substs.sig(), def_id);
+    for (_, region) in &late_bound_regions {
+        let name = translate_region_name(region);
+        bt_ctx.push_region(**region, name);
+    }
+
+    let inputs: Vec<ty::RTy> = std::iter::once(env_ty)
+        .chain(
+            signature
+                .inputs()
+                .iter()
+                .map(|ty| translate_sig_ty(&bt_ctx, ty).unwrap()),
+        )
+        .collect();
+    let output = translate_sig_ty(&bt_ctx, &signature.output()).unwrap();
 
+    // We don't translate the bodies of the generic closures (see above): we
+    // don't need their trait clauses
+    let sig = mk_function_signature(
+        types_constraints,
+        &bt_ctx,
+        late_bound_regions.len(),
+        Vec::new(),
+        inputs,
+        output,
+    );
     (bt_ctx, sig)
 }
 
+/// Translate a body, and return the approximations we performed while
+/// translating it, as well as the unsafe operations it performs.
 fn translate_body(
     mut bt_ctx: BodyTransContext<'_, '_, '_>,
     local_id: LocalDefId,
     arg_count: usize,
-) -> Result<ast::ExprBody> {
+) -> Result<(
+    ast::ExprBody,
+    Vec<ast::PrecisionLoss>,
+    Vec<ast::UnsafeOperation>,
+)> {
     let sess = bt_ctx.ft_ctx.sess;
     let tcx = bt_ctx.ft_ctx.tcx;
 
@@ -2361,10 +3299,34 @@ fn translate_body(
     trace!("Translating the body locals");
     translate_body_locals(&mut bt_ctx, body)?;
 
+    // Retrieve the loop invariants, which refer to the locals. We reported
+    // the ill-formed invariants when registering the declarations.
+    let vars: Vec<(String, v::VarId::Id)> = bt_ctx
+        .vars
+        .iter()
+        .filter_map(|var| Some((var.name.clone()?, var.index)))
+        .collect();
+    bt_ctx.loop_invariants =
+        contracts::get_loop_invariants(tcx, local_id, &vars).unwrap_or_else(|(_, msg)| {
+            warn!("Ignoring the loop invariants of {:?}: {}", local_id, msg);
+            HashMap::new()
+        });
+
     // Translate the expression body
     trace!("Translating the expression body");
     translate_transparent_expression_body(&mut bt_ctx, body)?;
 
+    // Retrieve the variable used for the return value
+    let return_local = bt_ctx.get_local(&mir::RETURN_PLACE).unwrap();
+
+    // The optimized MIR doesn't mark the loop entries
+    for span in bt_ctx.loop_invariants.keys() {
+        warn!(
+            "{}:\nCould not find the entry of the loop: its invariants will be ignored",
+            meta::span_to_string(sess, *span)
+        );
+    }
+
     // We need to convert the blocks map to an index vector
     let mut blocks = ast::BlockId::Vector::new();
     for (id, block) in bt_ctx.blocks {
@@ -2374,13 +3336,87 @@ fn translate_body(
         blocks.push_back(block);
     }
 
+    // List the reads of union fields, which are unsafe
+    let unsafe_operations = compute_union_field_reads(&blocks);
+
     // Create the body
-    Ok(ast::ExprBody {
+    let body = ast::ExprBody {
         meta,
         arg_count,
+        return_local,
         locals: bt_ctx.vars,
         body: blocks,
-    })
+    };
+    Ok((body, bt_ctx.precision_losses, unsafe_operations))
+}
+
+/// Return true if the place goes through a field of a union
+fn projects_union_field(p: &e::Place) -> bool {
+    p.projection
+        .iter()
+        .any(|pe| matches!(pe, e::ProjectionElem::Field(e::FieldProjKind::Union(_), _)))
+}
+
+/// List the reads (and the borrows) of union fields in a body. Note that the
+/// writes to union fields are safe, as well as the raw borrows.
+fn compute_union_field_reads(
+    blocks: &ast::BlockId::Vector<ast::BlockData>,
+) -> Vec<ast::UnsafeOperation> {
+    let reads_union_field = |op: &e::Operand| match op {
+        e::Operand::Copy(p) | e::Operand::Move(p) => projects_union_field(p),
+        e::Operand::Const(_, _) => false,
+    };
+    let mut reads = Vec::new();
+    for block in blocks.iter() {
+        for st in &block.statements {
+            let read = match &st.content {
+                ast::RawStatement::Assign(_, rv) => match rv {
+                    e::Rvalue::Use(op)
+                    | e::Rvalue::UnaryOp(_, op)
+                    | e::Rvalue::Unsize(op, _, _)
+                    | e::Rvalue::Cast(_, op, _, _) => reads_union_field(op),
+                    e::Rvalue::BinaryOp(_, op1, op2) => {
+                        reads_union_field(op1) || reads_union_field(op2)
+                    }
+                    e::Rvalue::Aggregate(_, ops) => ops.iter().any(reads_union_field),
+                    e::Rvalue::Ref(p, _)
+                    | e::Rvalue::Discriminant(p)
+                    | e::Rvalue::Len(p)
+                    | e::Rvalue::IsVariant(p, _) => projects_union_field(p),
+                    e::Rvalue::Slice { base, from, to, .. } => {
+                        projects_union_field(base)
+                            || reads_union_field(from)
+                            || reads_union_field(to)
+                    }
+                    e::Rvalue::RawPtr(_, _)
+                    | e::Rvalue::Global(_)
+                    | e::Rvalue::GlobalRef(_, _)
+                    | e::Rvalue::FnPtr(_) => false,
+                },
+                ast::RawStatement::FakeRead(_)
+                | ast::RawStatement::SetDiscriminant(_, _)
+                | ast::RawStatement::StorageDead(_)
+                | ast::RawStatement::Deinit(_) => false,
+            };
+            if read {
+                reads.push(ast::UnsafeOperation::UnionFieldRead(st.meta));
+            }
+        }
+        let read = match &block.terminator.content {
+            ast::RawTerminator::Switch { discr, .. } => reads_union_field(discr),
+            ast::RawTerminator::Call { args, .. } => args.iter().any(reads_union_field),
+            ast::RawTerminator::Assert { cond, .. } => reads_union_field(cond),
+            ast::RawTerminator::Goto { .. }
+            | ast::RawTerminator::Panic
+            | ast::RawTerminator::Return
+            | ast::RawTerminator::Unreachable
+            | ast::RawTerminator::Drop { .. } => false,
+        };
+        if read {
+            reads.push(ast::UnsafeOperation::UnionFieldRead(block.terminator.meta));
+        }
+    }
+    reads
 }
 
 /// Translate one function.
@@ -2394,6 +3430,7 @@ fn translate_function(
     global_defs: &ast::GlobalDecls,
     mir_level: MirLevel,
     def_id: ast::FunDeclId::Id,
+    reuse_body: bool,
 ) -> Result<ast::FunDecl> {
     trace!("{:?}", def_id);
 
@@ -2421,17 +3458,38 @@ fn translate_function(
     // at the same time (the signature gives us the region and type parameters,
     // that we put in the translation context).
     trace!("Translating function signature");
-    let (bt_ctx, signature) = translate_function_signature(types_constraints, &ft_ctx, info.rid);
-
-    // Check if the type is opaque or transparent
-    let body = if !info.is_transparent || !info.is_local() {
-        Option::None
+    let (bt_ctx, signature) = if tcx.is_closure(info.rid) {
+        translate_closure_signature(types_constraints, &ft_ctx, info.rid)
     } else {
-        Option::Some(translate_body(
-            bt_ctx,
-            info.rid.expect_local(),
-            signature.inputs.len(),
-        )?)
+        translate_function_signature(types_constraints, &ft_ctx, info.rid)
+    };
+
+    // Check if the type is opaque or transparent. We don't translate the
+    // bodies we reuse from a previous extraction (see [crate::incremental]),
+    // nor the bodies of the closures we don't support (we reported the error
+    // when translating their environment).
+    let unsupported_closure =
+        tcx.is_closure(info.rid) && translate_types::unsupported_closure(tcx, info.rid).is_some();
+    let (body, precision_losses, unsafe_operations) =
+        if !info.is_transparent || !info.is_local() || reuse_body || unsupported_closure {
+            (Option::None, Vec::new(), Vec::new())
+        } else {
+            match translate_body(bt_ctx, info.rid.expect_local(), signature.inputs.len()) {
+                Ok((body, precision_losses, unsafe_operations)) => {
+                    (Option::Some(body), precision_losses, unsafe_operations)
+                }
+                // The error was reported: we make the function opaque
+                Err(()) => (Option::None, Vec::new(), Vec::new()),
+            }
+        };
+
+    // We reported the ill-formed contracts when registering the declarations
+    let contract = match contracts::get_fun_contract(tcx, info.rid) {
+        Ok(contract) => contract,
+        Err((_, msg)) => {
+            warn!("Ignoring the contract of {:?}: {}", info.rid, msg);
+            contracts::FunContract::default()
+        }
     };
 
     // Return the new function
@@ -2441,6 +3499,10 @@ fn translate_function(
         name,
         signature,
         body,
+        precision_losses,
+        unsafe_operations,
+        attr_info: tool_attributes::get_attr_info(tcx, info.rid),
+        contract,
     })
 }
 
@@ -2486,6 +3548,7 @@ fn global_generate_assignment_body(
     ast::ExprBody {
         meta,
         arg_count: 0,
+        return_local: var.index,
         locals: id_vector::Vector::from(vec![var]),
         body: id_vector::Vector::from(vec![block]),
     }
@@ -2531,7 +3594,7 @@ fn translate_global(
     let type_ = {
         let ty_ctx = TypeTransContext::new(ft_ctx.type_defs, ft_ctx.ordered);
         let empty = im::OrdMap::new();
-        translate_types::translate_ety(tcx, &ty_ctx, &empty, &mir_ty)?
+        translate_types::translate_ety(tcx, &ty_ctx, &empty, &im::OrdMap::new(), &mir_ty)?
     };
 
     let bt_ctx = BodyTransContext::new(info.rid, &ft_ctx);
@@ -2540,7 +3603,9 @@ fn translate_global(
         (true, false) => Option::None,
 
         // It's a local and transparent global: we extract its body as for functions.
-        (true, true) => Option::Some(translate_body(bt_ctx, info.rid.expect_local(), 0)?),
+        // Note that the constant bodies can't contain inline assembly: we can
+        // ignore the approximations.
+        (true, true) => Option::Some(translate_body(bt_ctx, info.rid.expect_local(), 0)?.0),
 
         // It's an external global.
         // The fact that it is listed among the declarations to extract means that
@@ -2559,7 +3624,7 @@ fn translate_global(
                     // Evaluate the constant
                     // We need a param_env: we use the expression def id as a dummy id...
 
-                    let (ty, val) = translate_evaluated_operand_constant(&bt_ctx, &mir_ty, &c);
+                    let (ty, val) = translate_evaluated_operand_constant(&bt_ctx, &mir_ty, &c)?;
                     Option::Some(global_generate_assignment_body(
                         sess,
                         tcx,
@@ -2577,13 +3642,23 @@ fn translate_global(
         }
     };
 
+    // Check if this is a mutable static
+    let is_mutable = tcx.static_mutability(info.rid) == Some(mir::Mutability::Mut);
+    // Check if this is a static with interior mutability (note that the
+    // constants are copied at every use, so we don't need to check them)
+    let interior_mutability =
+        tcx.is_static(info.rid) && !mir_ty.is_freeze(tcx, mir_ty::ParamEnv::empty());
+
     // Return the new global
     Ok(ast::GlobalDecl {
         def_id,
         meta,
         name,
         ty: type_,
+        is_mutable,
+        interior_mutability,
         body,
+        attr_info: tool_attributes::get_attr_info(tcx, info.rid),
     })
 }
 
@@ -2595,6 +3670,7 @@ pub fn translate_functions(
     types_constraints: &TypesConstraintsMap,
     type_defs: &ty::TypeDecls,
     mir_level: MirLevel,
+    reused_funs: &HashSet<ast::FunDeclId::Id>,
 ) -> Result<(ast::FunDecls, ast::GlobalDecls)> {
     let mut fun_defs = ast::FunDecls::new();
     let mut const_defs = ast::GlobalDecls::new();
@@ -2614,6 +3690,7 @@ pub fn translate_functions(
                     &const_defs,
                     mir_level,
                     *def_id,
+                    reused_funs.contains(def_id),
                 )?;
                 // We have to make sure we translate the definitions in the
                 // proper order, otherwise we mess with the vector of ids
@@ -2632,6 +3709,7 @@ pub fn translate_functions(
                         &const_defs,
                         mir_level,
                         *def_id,
+                        reused_funs.contains(def_id),
                     )?;
                     // We have to make sure we translate the definitions in the
                     // proper order, otherwise we mess with the vector of ids
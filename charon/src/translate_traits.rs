@@ -0,0 +1,269 @@
+//! Translate the traits and the trait implementations.
+//!
+//! The trait methods are translated as regular functions: the trait
+//! declarations and the trait implementations simply list them. We only
+//! translate the traits and the implementations the registered functions
+//! belong to (see [crate::rust_to_local_ids]).
+//!
+//! rustc desugars the method calls (`x.clone()`) to calls to the methods of
+//! the traits (`Clone::clone(&x)`). When the implementation can be determined
+//! statically, we resolve the call to the method of the implementation (see
+//! [crate::gast::FunId::TraitMethod]).
+#![allow(dead_code)]
+
+use crate::assumed;
+use crate::meta;
+use crate::names::{function_def_id_to_name, trait_def_id_to_name, type_def_id_to_name};
+use crate::rust_to_local_ids::*;
+use crate::ullbc_ast::{TraitDecl, TraitDecls, TraitImpl, TraitImpls};
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::subst::{EarlyBinder, SubstsRef};
+use rustc_middle::ty::{
+    AliasTy, AssocKind, Clause, Instance, InstanceDef, PredicateKind, Ty, TyCtxt, TyKind,
+};
+use rustc_session::Session;
+use std::collections::HashMap;
+
+/// Resolve a call to a trait method, if we can statically determine the
+/// implementation, and return the method of the implementation together with
+/// its instantiation. The other calls are left unchanged.
+///
+/// `caller`: the function in which the call happens (we need its environment
+/// to resolve the call).
+pub fn resolve_trait_method<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    caller: DefId,
+    fid: DefId,
+    substs: SubstsRef<'tcx>,
+) -> (DefId, SubstsRef<'tcx>) {
+    if tcx.trait_of_item(fid).is_none() {
+        return (fid, substs);
+    }
+    let param_env = tcx.param_env(caller);
+    match Instance::resolve(tcx, param_env, fid, substs) {
+        Ok(Some(instance)) => match instance.def {
+            // Note that this may be a provided method of the trait, if the
+            // implementation doesn't override it
+            InstanceDef::Item(def) => (def.did, instance.substs),
+            // The compiler generated implementations (the shims): we have no
+            // function to call but the method of the trait
+            _ => (fid, substs),
+        },
+        // The implementation depends on the type parameters
+        Ok(None) | Err(_) => (fid, substs),
+    }
+}
+
+/// Resolve a reference to an associated constant of a trait (`T::CONST`, or
+/// `<T as Trait>::CONST`), like [resolve_trait_method]. We return `None` if we
+/// can't determine the implementation statically, or if the constant of the
+/// implementation is generic (the globals don't have parameters): see
+/// [crate::expressions::OperandConstantValue::TraitConst]. The other
+/// constants are left unchanged.
+pub fn resolve_trait_const<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    caller: DefId,
+    rid: DefId,
+    substs: SubstsRef<'tcx>,
+) -> Option<DefId> {
+    if tcx.trait_of_item(rid).is_none() {
+        return Some(rid);
+    }
+    let param_env = tcx.param_env(caller);
+    match Instance::resolve(tcx, param_env, rid, substs) {
+        // Note that this may be the default value given in the trait (which
+        // depends on `Self`), if the implementation doesn't override it
+        Ok(Some(Instance {
+            def: InstanceDef::Item(def),
+            ..
+        })) if tcx.generics_of(def.did).count() == 0 => Some(def.did),
+        _ => None,
+    }
+}
+
+/// Return true if the call to a trait method is dispatched at runtime, that is
+/// if the method is called on a trait object (see [crate::gast::FunId::Virtual]).
+pub fn is_virtual_call<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    caller: DefId,
+    fid: DefId,
+    substs: SubstsRef<'tcx>,
+) -> bool {
+    if tcx.trait_of_item(fid).is_none() {
+        return false;
+    }
+    let param_env = tcx.param_env(caller);
+    matches!(
+        Instance::resolve(tcx, param_env, fid, substs),
+        Ok(Some(Instance {
+            def: InstanceDef::Virtual(_, _),
+            ..
+        }))
+    )
+}
+
+/// Return true if this is a call to `<Box<T> as From<T>>::from`, which we
+/// translate like a call to `Box::new` (the other implementations of `From`
+/// for boxes, like `From<&str> for Box<str>`, are regular functions).
+pub fn is_box_from_call<'tcx>(tcx: TyCtxt<'tcx>, fid: DefId, substs: SubstsRef<'tcx>) -> bool {
+    if fid.is_local() || !function_def_id_to_name(tcx, fid).equals_ref_name(&assumed::FROM_NAME) {
+        return false;
+    }
+    // The type parameters are: `Self`, then `T`
+    let self_ty = substs.type_at(0);
+    self_ty.is_box() && self_ty.boxed_ty() == substs.type_at(1)
+}
+
+/// Return true if the type is a shared pointer (`Rc<T>` or `Arc<T>`)
+fn is_rc_ty(tcx: TyCtxt, ty: Ty) -> bool {
+    match ty.kind() {
+        TyKind::Adt(adt_def, _) => {
+            let name = type_def_id_to_name(tcx, adt_def.did());
+            name.equals_ref_name(&assumed::RC_NAME) || name.equals_ref_name(&assumed::ARC_NAME)
+        }
+        _ => false,
+    }
+}
+
+/// Return true if this is a call to `Clone::clone` on a shared pointer, which
+/// we translate to [crate::ullbc_ast::AssumedFunId::RcClone] or
+/// [crate::ullbc_ast::AssumedFunId::ArcClone].
+pub fn is_rc_clone_call<'tcx>(tcx: TyCtxt<'tcx>, fid: DefId, substs: SubstsRef<'tcx>) -> bool {
+    !fid.is_local()
+        && function_def_id_to_name(tcx, fid).equals_ref_name(&assumed::CLONE_NAME)
+        && is_rc_ty(tcx, substs.type_at(0))
+}
+
+/// Return true if this is a call to `Deref::deref` on a shared pointer, which
+/// we translate to a borrow (see [crate::expressions::ProjectionElem::DerefRc]).
+pub fn is_rc_deref_call<'tcx>(tcx: TyCtxt<'tcx>, fid: DefId, substs: SubstsRef<'tcx>) -> bool {
+    !fid.is_local()
+        && function_def_id_to_name(tcx, fid).equals_ref_name(&assumed::DEREF_DEREF_NAME)
+        && is_rc_ty(tcx, substs.type_at(0))
+}
+
+/// Return true if this is a call to a method of the iterator traits
+/// (`IntoIterator::into_iter`, `Iterator::next`) for a local type. Those
+/// methods are primitive (see [crate::assumed::ITERATOR_NEXT_NAME]), but
+/// the implementations for the local types are translated like the other
+/// trait implementations.
+pub fn is_local_iterator_call<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    fid: DefId,
+    substs: SubstsRef<'tcx>,
+) -> bool {
+    if fid.is_local() || !assumed::is_iterator_method_name(&function_def_id_to_name(tcx, fid)) {
+        return false;
+    }
+    match substs.type_at(0).kind() {
+        TyKind::Adt(adt_def, _) => adt_def.did().is_local(),
+        _ => false,
+    }
+}
+
+/// The methods of a trait or of an `impl` block, in the order in which they
+/// are defined
+pub fn get_methods(tcx: TyCtxt, rid: DefId) -> impl Iterator<Item = (String, DefId)> + '_ {
+    tcx.associated_items(rid)
+        .in_definition_order()
+        .filter(|item| item.kind == AssocKind::Fn)
+        .map(|item| (item.name.to_string(), item.def_id))
+}
+
+/// The traits an opaque type (`impl Trait`) implements, in the order in which
+/// they are given. We ignore the auto traits, and the traits without methods
+/// (`Sized`, `Copy`, etc.): see [crate::types::Ty::Opaque].
+pub fn get_opaque_type_bounds(tcx: TyCtxt, rid: DefId) -> Vec<DefId> {
+    tcx.explicit_item_bounds(rid)
+        .iter()
+        .filter_map(|(pred, _)| match pred.kind().skip_binder() {
+            PredicateKind::Clause(Clause::Trait(trait_pred)) => Some(trait_pred.def_id()),
+            _ => None,
+        })
+        .filter(|trait_id| {
+            !tcx.trait_is_auto(*trait_id) && get_methods(tcx, *trait_id).next().is_some()
+        })
+        .collect()
+}
+
+/// The trait clauses of a declaration (see [crate::types::TraitClause]), as
+/// the traits with their arguments (the first argument is the `Self` type).
+/// For the methods, this includes the clauses of the `impl` block or of the
+/// trait.
+///
+/// Like for the opaque types (see [get_opaque_type_bounds]), we ignore the
+/// auto traits and the traits without methods. We also ignore the
+/// higher-ranked clauses (`for<'a> F: Fn(&'a u32)`), whose bound regions we
+/// can't translate.
+pub fn get_trait_clauses<'tcx>(tcx: TyCtxt<'tcx>, rid: DefId) -> Vec<(DefId, SubstsRef<'tcx>)> {
+    tcx.predicates_of(rid)
+        .instantiate_identity(tcx)
+        .predicates
+        .into_iter()
+        .filter(|pred| pred.kind().bound_vars().is_empty())
+        .filter_map(|pred| match pred.kind().skip_binder() {
+            PredicateKind::Clause(Clause::Trait(trait_pred)) => {
+                Some((trait_pred.def_id(), trait_pred.trait_ref.substs))
+            }
+            _ => None,
+        })
+        .filter(|(trait_id, _)| {
+            !tcx.trait_is_auto(*trait_id) && get_methods(tcx, *trait_id).next().is_some()
+        })
+        .collect()
+}
+
+/// The type an opaque type stands for, if the opaque type is introduced by a
+/// function of the current crate.
+pub fn get_opaque_hidden_type<'tcx>(tcx: TyCtxt<'tcx>, alias: &AliasTy<'tcx>) -> Option<Ty<'tcx>> {
+    if alias.def_id.is_local() {
+        Some(EarlyBinder(tcx.type_of(alias.def_id)).subst(tcx, alias.substs))
+    } else {
+        None
+    }
+}
+
+/// The entries of a map from Rust identifiers, sorted by identifier
+fn sorted_by_id<Id: Copy + Ord>(map: &HashMap<DefId, Id>) -> Vec<(DefId, Id)> {
+    let mut entries: Vec<(DefId, Id)> = map.iter().map(|(rid, id)| (*rid, *id)).collect();
+    entries.sort_by_key(|(_, id)| *id);
+    entries
+}
+
+pub fn translate_traits(
+    sess: &Session,
+    tcx: TyCtxt,
+    decls: &OrderedDecls,
+) -> (TraitDecls, TraitImpls) {
+    let mut trait_decls = TraitDecls::new();
+    for (rid, def_id) in sorted_by_id(&decls.trait_rid_to_id) {
+        let methods = get_methods(tcx, rid)
+            .map(|(name, rid)| (name, decls.fun_rid_to_id.get(&rid).copied()))
+            .collect();
+        trait_decls.push_back(TraitDecl {
+            def_id,
+            meta: meta::get_meta_from_rid(sess, tcx, &decls.file_to_id, rid),
+            name: trait_def_id_to_name(tcx, rid),
+            methods,
+        });
+    }
+
+    let mut trait_impls = TraitImpls::new();
+    for (rid, def_id) in sorted_by_id(&decls.trait_impl_rid_to_id) {
+        let trait_rid = tcx.trait_id_of_impl(rid).unwrap();
+        // We only registered the methods of the external implementations
+        // which are used by the crate
+        let methods = get_methods(tcx, rid)
+            .filter_map(|(name, rid)| Some((name, *decls.fun_rid_to_id.get(&rid)?)))
+            .collect();
+        trait_impls.push_back(TraitImpl {
+            def_id,
+            meta: meta::get_meta_from_rid(sess, tcx, &decls.file_to_id, rid),
+            trait_id: *decls.trait_rid_to_id.get(&trait_rid).unwrap(),
+            self_type: get_impl_self_type(tcx, &decls.type_rid_to_id, rid),
+            methods,
+        });
+    }
+
+    (trait_decls, trait_impls)
+}
@@ -1,21 +1,30 @@
 use crate::assumed;
+use crate::closures;
 use crate::common::*;
+use crate::expressions as e;
 use crate::formatter::Formatter;
+use crate::gast::TraitDeclId;
 use crate::generics;
 use crate::id_vector::ToUsize;
 use crate::meta;
 use crate::names::type_def_id_to_name;
+use crate::recursive_types;
 use crate::regions_hierarchy;
 use crate::regions_hierarchy::TypesConstraintsMap;
 use crate::reorder_decls::DeclarationGroup;
 use crate::rust_to_local_ids::*;
+use crate::tool_attributes;
+use crate::translate_traits;
 use crate::types as ty;
 use crate::types::TypeDeclId;
+use crate::values::{PrimitiveValue, ScalarValue};
 use im::Vector;
 use rustc_hir::def_id::DefId;
 use rustc_middle::mir::Mutability;
-use rustc_middle::ty::{Ty, TyCtxt, TyKind};
+use rustc_middle::ty::subst::GenericArgKind;
+use rustc_middle::ty::{AliasKind, CapturedPlace, ConstKind, Ty, TyCtxt, TyKind};
 use rustc_session::Session;
+use std::collections::HashMap;
 
 /// Translation context for type definitions
 #[derive(Clone)]
@@ -34,6 +43,10 @@ impl<'ctx> TypeTransContext<'ctx> {
     pub fn get_id(&self, rid: DefId) -> TypeDeclId::Id {
         *self.decls.type_rid_to_id.get(&rid).unwrap()
     }
+
+    pub fn get_trait_id(&self, rid: DefId) -> TraitDeclId::Id {
+        *self.decls.trait_rid_to_id.get(&rid).unwrap()
+    }
 }
 
 /// Auxiliary definition used to format definitions.
@@ -179,16 +192,24 @@ pub fn translate_erased_region(region: rustc_middle::ty::RegionKind<'_>) -> ty::
 /// regions can be translated in several manners (non-erased region or erased
 /// regions), in which case the return type is different.
 pub fn translate_ty<'tcx, R>(
-    tcx: TyCtxt,
+    tcx: TyCtxt<'tcx>,
     trans_ctx: &TypeTransContext,
     region_translator: &dyn Fn(&rustc_middle::ty::RegionKind<'tcx>) -> R,
     type_params: &im::OrdMap<u32, ty::Ty<R>>,
+    const_generic_params: &im::OrdMap<u32, ty::ConstGenericVarId::Id>,
     ty: &Ty<'tcx>,
 ) -> Result<ty::Ty<R>>
 where
     R: Clone + Eq,
 {
-    translate_ty_kind(tcx, trans_ctx, region_translator, type_params, ty.kind())
+    translate_ty_kind(
+        tcx,
+        trans_ctx,
+        region_translator,
+        type_params,
+        const_generic_params,
+        ty.kind(),
+    )
 }
 
 /// Translate a [TyKind].
@@ -196,10 +217,11 @@ where
 /// See the comments for [translate_ty] (the two functions do the same thing,
 /// they simply don't take the same input parameters).
 pub fn translate_ty_kind<'tcx, R>(
-    tcx: TyCtxt,
+    tcx: TyCtxt<'tcx>,
     trans_ctx: &TypeTransContext,
     region_translator: &dyn Fn(&rustc_middle::ty::RegionKind<'tcx>) -> R,
     type_params: &im::OrdMap<u32, ty::Ty<R>>,
+    const_generic_params: &im::OrdMap<u32, ty::ConstGenericVarId::Id>,
     ty_kind: &TyKind<'tcx>,
 ) -> Result<ty::Ty<R>>
 where
@@ -216,15 +238,50 @@ where
             *int_ty,
         ))),
         TyKind::Str => Ok(ty::Ty::Str),
-        TyKind::Float(_) => {
-            trace!("Float");
-            // This case should have been filtered during the registration phase
-            unreachable!();
-        }
+        TyKind::Float(float_ty) => Ok(ty::Ty::Float(ty::FloatTy::rust_float_ty_to_float_ty(
+            *float_ty,
+        ))),
         TyKind::Never => Ok(ty::Ty::Never),
 
-        TyKind::Alias(_, _) => {
-            unimplemented!();
+        TyKind::Alias(AliasKind::Opaque, alias) => {
+            trace!("Opaque");
+            let hidden_ty = match translate_traits::get_opaque_hidden_type(tcx, alias) {
+                Some(hidden_ty) => Some(Box::new(translate_ty(
+                    tcx,
+                    trans_ctx,
+                    region_translator,
+                    type_params,
+                    const_generic_params,
+                    &hidden_ty,
+                )?)),
+                None => None,
+            };
+            let bounds = translate_traits::get_opaque_type_bounds(tcx, alias.def_id)
+                .into_iter()
+                .map(|trait_id| trans_ctx.get_trait_id(trait_id))
+                .collect();
+            Ok(ty::Ty::Opaque(hidden_ty, bounds))
+        }
+        TyKind::Alias(AliasKind::Projection, alias) => {
+            trace!("Projection");
+            // The trait is registered together with the projection
+            // (see [crate::register])
+            let trait_id = trans_ctx.get_trait_id(alias.trait_def_id(tcx));
+            let (regions, params) = translate_substs(
+                tcx,
+                trans_ctx,
+                region_translator,
+                type_params,
+                const_generic_params,
+                None,
+                &alias.substs,
+            )?;
+            Ok(ty::Ty::TraitProjection(
+                trait_id,
+                Vector::from(regions),
+                Vector::from(params),
+                tcx.item_name(alias.def_id).to_string(),
+            ))
         }
 
         TyKind::Adt(adt, substs) => {
@@ -232,11 +289,14 @@ where
             trace!("Adt: {:?}", adt_did);
 
             // Retrieve the list of used arguments
-            let used_params = if adt_did.is_local() {
-                Option::None
+            let (used_params, is_pin) = if adt_did.is_local() {
+                (Option::None, false)
             } else {
                 let name = type_def_id_to_name(tcx, adt_did);
-                assumed::type_to_used_params(&name)
+                (
+                    assumed::type_to_used_params(&name),
+                    assumed::is_pin_name(&name),
+                )
             };
 
             // Translate the type parameters instantiation
@@ -245,10 +305,17 @@ where
                 trans_ctx,
                 region_translator,
                 type_params,
+                const_generic_params,
                 used_params,
                 substs,
             )?;
 
+            // We don't model the pinning: `Pin<P>` is translated to `P`
+            if is_pin {
+                assert!(regions.is_empty() && params.len() == 1);
+                return Ok(params.into_iter().next().unwrap());
+            }
+
             // Retrieve the ADT identifier
             let def_id = translate_defid(tcx, trans_ctx, adt_did);
 
@@ -259,23 +326,45 @@ where
                 Vector::from(params),
             ))
         }
-        TyKind::Array(ty, _const_param) => {
+        TyKind::Array(ty, len) => {
             trace!("Array");
 
-            let ty = translate_ty(tcx, trans_ctx, region_translator, type_params, ty)?;
-            Ok(ty::Ty::Array(Box::new(ty)))
+            let ty = translate_ty(
+                tcx,
+                trans_ctx,
+                region_translator,
+                type_params,
+                const_generic_params,
+                ty,
+            )?;
+            let len = translate_const_generic(tcx, const_generic_params, *len);
+            Ok(ty::Ty::Array(Box::new(ty), len))
         }
         TyKind::Slice(ty) => {
             trace!("Slice");
 
-            let ty = translate_ty(tcx, trans_ctx, region_translator, type_params, ty)?;
+            let ty = translate_ty(
+                tcx,
+                trans_ctx,
+                region_translator,
+                type_params,
+                const_generic_params,
+                ty,
+            )?;
             Ok(ty::Ty::Slice(Box::new(ty)))
         }
         TyKind::Ref(region, ty, mutability) => {
             trace!("Ref");
 
             let region = region_translator(region);
-            let ty = translate_ty(tcx, trans_ctx, region_translator, type_params, ty)?;
+            let ty = translate_ty(
+                tcx,
+                trans_ctx,
+                region_translator,
+                type_params,
+                const_generic_params,
+                ty,
+            )?;
             let kind = match *mutability {
                 Mutability::Not => ty::RefKind::Shared,
                 Mutability::Mut => ty::RefKind::Mut,
@@ -289,6 +378,7 @@ where
                 trans_ctx,
                 region_translator,
                 type_params,
+                const_generic_params,
                 &ty_and_mut.ty,
             )?;
             let kind = match ty_and_mut.mutbl {
@@ -302,8 +392,14 @@ where
 
             let mut params = vec![];
             for param in substs.iter() {
-                let param_ty =
-                    translate_ty(tcx, trans_ctx, region_translator, type_params, &param)?;
+                let param_ty = translate_ty(
+                    tcx,
+                    trans_ctx,
+                    region_translator,
+                    type_params,
+                    const_generic_params,
+                    &param,
+                )?;
                 params.push(param_ty);
             }
 
@@ -314,9 +410,32 @@ where
             ))
         }
 
-        TyKind::FnPtr(_) => {
+        TyKind::FnPtr(sig) => {
             trace!("FnPtr");
-            unimplemented!();
+            // The function pointers which quantify over regions are filtered
+            // during the registration phase
+            let sig = sig.no_bound_vars().unwrap();
+            let mut inputs = vec![];
+            for input in sig.inputs().iter() {
+                let input = translate_ty(
+                    tcx,
+                    trans_ctx,
+                    region_translator,
+                    type_params,
+                    const_generic_params,
+                    input,
+                )?;
+                inputs.push(input);
+            }
+            let output = translate_ty(
+                tcx,
+                trans_ctx,
+                region_translator,
+                type_params,
+                const_generic_params,
+                &sig.output(),
+            )?;
+            Ok(ty::Ty::FnPtr(Vector::from(inputs), Box::new(output)))
         }
         TyKind::Param(param) => {
             // A type parameter, for example `T` in `fn f<T>(x : T) {}`.
@@ -334,6 +453,35 @@ where
             Ok(ty.clone())
         }
 
+        TyKind::Closure(def_id, _) => {
+            trace!("Closure");
+            // A closure is translated to the structure holding its environment
+            // (see [crate::closures]).
+            // The environment has one region parameter per variable captured
+            // by reference. The closure types only appear in the bodies,
+            // where the regions are erased. The environments of the closures
+            // we don't support are opaque, and have no parameters.
+            let regions = if unsupported_closure(tcx, *def_id).is_some() {
+                Vector::new()
+            } else {
+                translate_closure_captures(tcx, *def_id)
+                    .into_iter()
+                    .filter(|(_, mode)| mode.ref_kind().is_some())
+                    .map(|_| region_translator(&rustc_middle::ty::RegionKind::ReErased))
+                    .collect()
+            };
+            let id = ty::TypeId::Adt(trans_ctx.get_id(*def_id));
+            Ok(ty::Ty::Adt(id, regions, Vector::new()))
+        }
+
+        TyKind::Dynamic(preds, _, _) => {
+            trace!("Dynamic");
+            // The trait objects without principal trait are filtered during
+            // the registration phase
+            let trait_id = preds.principal_def_id().unwrap();
+            Ok(ty::Ty::DynTrait(trans_ctx.get_trait_id(trait_id)))
+        }
+
         // Below: those types should be unreachable: if such types are used in
         // the MIR, we should have found them and failed during the registration
         // phase.
@@ -351,15 +499,6 @@ where
             unreachable!();
         }
 
-        TyKind::Dynamic(_, _, _) => {
-            trace!("Dynamic");
-            unreachable!();
-        }
-        TyKind::Closure(_, _) => {
-            trace!("Closure");
-            unreachable!();
-        }
-
         TyKind::Generator(_, _, _) | TyKind::GeneratorWitness(_) => {
             trace!("Generator");
             unreachable!();
@@ -384,10 +523,11 @@ where
 /// variable ids.
 /// Simply calls [`translate_ty`](translate_ty)
 pub fn translate_sig_ty<'tcx>(
-    tcx: TyCtxt,
+    tcx: TyCtxt<'tcx>,
     trans_ctx: &TypeTransContext,
     region_params: &im::OrdMap<rustc_middle::ty::RegionKind<'tcx>, ty::RegionVarId::Id>,
     type_params: &im::OrdMap<u32, ty::RTy>,
+    const_generic_params: &im::OrdMap<u32, ty::ConstGenericVarId::Id>,
     ty: &Ty<'tcx>,
 ) -> Result<ty::RTy> {
     translate_ty(
@@ -395,48 +535,156 @@ pub fn translate_sig_ty<'tcx>(
         trans_ctx,
         &|r| translate_non_erased_region(region_params, *r),
         type_params,
+        const_generic_params,
         ty,
     )
 }
 
 /// Translate a type where the regions are erased
 /// Simply calls [translate_ty]
-pub fn translate_ety(
-    tcx: TyCtxt,
+pub fn translate_ety<'tcx>(
+    tcx: TyCtxt<'tcx>,
     trans_ctx: &TypeTransContext,
     type_params: &im::OrdMap<u32, ty::ETy>,
-    ty: &Ty,
+    const_generic_params: &im::OrdMap<u32, ty::ConstGenericVarId::Id>,
+    ty: &Ty<'tcx>,
 ) -> Result<ty::ETy> {
     translate_ty(
         tcx,
         trans_ctx,
         &|r| translate_erased_region(*r),
         type_params,
+        const_generic_params,
         ty,
     )
 }
 
 /// Simply calls [translate_ty_kind]
-pub fn translate_ety_kind(
-    tcx: TyCtxt,
+pub fn translate_ety_kind<'tcx>(
+    tcx: TyCtxt<'tcx>,
     trans_ctx: &TypeTransContext,
     type_params: &im::OrdMap<u32, ty::ETy>,
-    ty: &TyKind,
+    const_generic_params: &im::OrdMap<u32, ty::ConstGenericVarId::Id>,
+    ty: &TyKind<'tcx>,
 ) -> Result<ty::ETy> {
     translate_ty_kind(
         tcx,
         trans_ctx,
         &|r| translate_erased_region(*r),
         type_params,
+        const_generic_params,
         ty,
     )
 }
 
+/// Translate the trait clauses of a declaration (see
+/// [translate_traits::get_trait_clauses]). We ignore the clauses of the
+/// traits we don't translate: the bounds of the external declarations may
+/// refer to traits the crate doesn't use.
+pub fn translate_trait_clauses<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    trans_ctx: &TypeTransContext,
+    region_params: &im::OrdMap<rustc_middle::ty::RegionKind<'tcx>, ty::RegionVarId::Id>,
+    type_params: &im::OrdMap<u32, ty::RTy>,
+    const_generic_params: &im::OrdMap<u32, ty::ConstGenericVarId::Id>,
+    rid: DefId,
+) -> Result<Vec<ty::TraitClause>> {
+    let mut clauses = Vec::new();
+    for (trait_rid, substs) in translate_traits::get_trait_clauses(tcx, rid) {
+        let clause = translate_trait_clause(&trans_ctx.decls.trait_rid_to_id, trait_rid, || {
+            translate_substs(
+                tcx,
+                trans_ctx,
+                &|r| translate_non_erased_region(region_params, *r),
+                type_params,
+                const_generic_params,
+                None,
+                &substs,
+            )
+        })?;
+        clauses.extend(clause);
+    }
+    Ok(clauses)
+}
+
+/// Translate a clause on the trait `trait_rid`, given the translation of the
+/// arguments of the trait. Return `None` if we don't translate the trait:
+/// in this case, we don't translate the arguments either.
+fn translate_trait_clause(
+    trait_rid_to_id: &HashMap<DefId, TraitDeclId::Id>,
+    trait_rid: DefId,
+    translate_args: impl FnOnce() -> Result<(Vec<ty::Region<ty::RegionVarId::Id>>, Vec<ty::RTy>)>,
+) -> Result<Option<ty::TraitClause>> {
+    let trait_id = match trait_rid_to_id.get(&trait_rid) {
+        Some(trait_id) => *trait_id,
+        None => return Ok(None),
+    };
+    let (region_args, type_args) = translate_args()?;
+    Ok(Some(ty::TraitClause {
+        trait_id,
+        region_args,
+        type_args,
+    }))
+}
+
+/// Translate the type of a const generic parameter. Rust only accepts the
+/// integers, `bool` and `char`.
+pub fn translate_const_generic_param_ty(ty: Ty<'_>) -> ty::ETy {
+    match ty.kind() {
+        TyKind::Bool => ty::Ty::Bool,
+        TyKind::Char => ty::Ty::Char,
+        TyKind::Int(int_ty) => ty::Ty::Integer(ty::IntegerTy::rust_int_ty_to_integer_ty(*int_ty)),
+        TyKind::Uint(int_ty) => ty::Ty::Integer(ty::IntegerTy::rust_uint_ty_to_integer_ty(*int_ty)),
+        _ => unreachable!("Unexpected type for a const generic: {:?}", ty),
+    }
+}
+
+/// Translate a constant which appears in a type: the length of an array, or
+/// the argument of a const generic parameter.
+pub fn translate_const_generic<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    const_generic_params: &im::OrdMap<u32, ty::ConstGenericVarId::Id>,
+    c: rustc_middle::ty::Const<'tcx>,
+) -> ty::ConstGeneric {
+    match c.kind() {
+        ConstKind::Param(param) => {
+            ty::ConstGeneric::Var(*const_generic_params.get(&param.index).unwrap())
+        }
+        ConstKind::Value(_) | ConstKind::Unevaluated(_) => {
+            // The constant doesn't depend on the parameters: we can evaluate it
+            let bits = c.eval_bits(tcx, rustc_middle::ty::ParamEnv::empty(), c.ty());
+            let value = match translate_const_generic_param_ty(c.ty()) {
+                ty::Ty::Bool => PrimitiveValue::Bool(bits != 0),
+                ty::Ty::Char => PrimitiveValue::Char(char::from_u32(bits as u32).unwrap()),
+                ty::Ty::Integer(int_ty) => {
+                    // We need to reinterpret the bytes (`bits as i128` is not correct)
+                    PrimitiveValue::Scalar(ScalarValue::from_le_bytes(int_ty, bits.to_le_bytes()))
+                }
+                _ => unreachable!(),
+            };
+            ty::ConstGeneric::Value(value)
+        }
+        ConstKind::Expr(_) => {
+            // For instance: `[u8; N + 1]`
+            unimplemented!("Generic constant expressions: {:?}", c)
+        }
+        ConstKind::Infer(_)
+        | ConstKind::Bound(_, _)
+        | ConstKind::Placeholder(_)
+        | ConstKind::Error(_) => {
+            unreachable!("Unexpected constant: {:?}", c)
+        }
+    }
+}
+
+/// Translate a substitution. The const generic arguments are translated to
+/// [ty::Ty::ConstGeneric] and come after the type arguments.
 fn translate_substs<'tcx, R>(
-    tcx: TyCtxt,
+    tcx: TyCtxt<'tcx>,
     trans_ctx: &TypeTransContext,
     region_translator: &dyn Fn(&rustc_middle::ty::RegionKind<'tcx>) -> R,
     type_params: &im::OrdMap<u32, ty::Ty<R>>,
+    const_generic_params: &im::OrdMap<u32, ty::ConstGenericVarId::Id>,
     used_params: Option<Vec<bool>>,
     substs: &rustc_middle::ty::subst::SubstsRef<'tcx>,
 ) -> Result<(Vec<R>, Vec<ty::Ty<R>>)>
@@ -471,22 +719,31 @@ where
 
     let mut regions: Vec<R> = vec![];
     let mut params = vec![];
+    let mut const_generics = vec![];
     for (param, param_i) in substs.iter() {
         trace!("Adt: param {}: {:?}", param_i, param);
         match param.unpack() {
             rustc_middle::ty::subst::GenericArgKind::Type(param_ty) => {
-                let param_ty =
-                    translate_ty(tcx, trans_ctx, region_translator, type_params, &param_ty)?;
+                let param_ty = translate_ty(
+                    tcx,
+                    trans_ctx,
+                    region_translator,
+                    type_params,
+                    const_generic_params,
+                    &param_ty,
+                )?;
                 params.push(param_ty);
             }
             rustc_middle::ty::subst::GenericArgKind::Lifetime(region) => {
                 regions.push(region_translator(&region));
             }
-            rustc_middle::ty::subst::GenericArgKind::Const(_) => {
-                unimplemented!();
+            rustc_middle::ty::subst::GenericArgKind::Const(c) => {
+                let c = translate_const_generic(tcx, const_generic_params, c);
+                const_generics.push(ty::Ty::ConstGeneric(c));
             }
         }
     }
+    params.append(&mut const_generics);
 
     Result::Ok((regions, params))
 }
@@ -523,6 +780,8 @@ struct TypeGenericsInfo<'tcx> {
     region_params_map: im::OrdMap<rustc_middle::ty::RegionKind<'tcx>, ty::RegionVarId::Id>,
     type_params: Vec<ty::TypeVar>,
     type_params_map: im::OrdMap<u32, ty::RTy>,
+    const_generic_params: Vec<ty::ConstGenericVar>,
+    const_generic_params_map: im::OrdMap<u32, ty::ConstGenericVarId::Id>,
 }
 
 /// Auxiliary helper.
@@ -552,6 +811,10 @@ fn translate_type_generics<'tcx>(tcx: TyCtxt<'tcx>, def_id: DefId) -> TypeGeneri
     let mut type_params: Vec<ty::TypeVar> = vec![];
     let mut type_params_map: im::OrdMap<u32, ty::RTy> = im::OrdMap::new();
     let mut type_params_counter = ty::TypeVarId::Generator::new();
+    let mut const_generic_params: Vec<ty::ConstGenericVar> = vec![];
+    let mut const_generic_params_map: im::OrdMap<u32, ty::ConstGenericVarId::Id> =
+        im::OrdMap::new();
+    let mut const_generic_params_counter = ty::ConstGenericVarId::Generator::new();
     for p in substs.iter() {
         match p.unpack() {
             rustc_middle::ty::subst::GenericArgKind::Type(param_ty) => {
@@ -580,8 +843,22 @@ fn translate_type_generics<'tcx>(tcx: TyCtxt<'tcx>, def_id: DefId) -> TypeGeneri
                 region_params_map.insert(*region, t_region.index);
                 region_params.push(t_region);
             }
-            rustc_middle::ty::subst::GenericArgKind::Const(_) => {
-                unimplemented!();
+            rustc_middle::ty::subst::GenericArgKind::Const(c) => {
+                // The constant should be a Param
+                match c.kind() {
+                    ConstKind::Param(param) => {
+                        let var = ty::ConstGenericVar {
+                            index: const_generic_params_counter.fresh_id(),
+                            name: param.name.to_ident_string(),
+                            ty: translate_const_generic_param_ty(c.ty()),
+                        };
+                        const_generic_params_map.insert(param.index, var.index);
+                        const_generic_params.push(var);
+                    }
+                    _ => {
+                        panic!("Inconsistent state");
+                    }
+                }
             }
         }
     }
@@ -609,6 +886,8 @@ fn translate_type_generics<'tcx>(tcx: TyCtxt<'tcx>, def_id: DefId) -> TypeGeneri
         region_params_map,
         type_params,
         type_params_map,
+        const_generic_params,
+        const_generic_params_map,
     }
 }
 
@@ -638,6 +917,8 @@ fn translate_transparent_type<'tcx>(
         region_params_map,
         type_params: _,
         type_params_map,
+        const_generic_params: _,
+        const_generic_params_map,
     } = generics;
 
     // Explore the variants
@@ -657,7 +938,14 @@ fn translate_transparent_type<'tcx>(
             let ty = field_def.ty(tcx, substs);
 
             // Translate the field type
-            let ty = translate_sig_ty(tcx, &trans_ctx, region_params_map, type_params_map, &ty)?;
+            let ty = translate_sig_ty(
+                tcx,
+                &trans_ctx,
+                region_params_map,
+                type_params_map,
+                const_generic_params_map,
+                &ty,
+            )?;
 
             // Retrieve the field name.
             // Note that the only way to check if the user wrote the name or
@@ -715,15 +1003,113 @@ fn translate_transparent_type<'tcx>(
         rustc_middle::ty::AdtKind::Enum => {
             ty::TypeDeclKind::Enum(ty::VariantId::Vector::from(variants))
         }
-        rustc_middle::ty::AdtKind::Union => {
-            // Should have been filtered during the registration phase
-            unreachable!();
-        }
+        rustc_middle::ty::AdtKind::Union => ty::TypeDeclKind::Union(variants[0].fields.clone()),
     };
 
     Ok(type_def_kind)
 }
 
+/// Retrieve the variables captured by a closure, and the way they are
+/// captured, in the order of the fields of the closure environment.
+pub(crate) fn translate_closure_captures(
+    tcx: TyCtxt<'_>,
+    def_id: DefId,
+) -> Vec<(&CapturedPlace<'_>, e::CaptureMode)> {
+    let local_def_id = def_id.expect_local();
+    tcx.typeck(local_def_id)
+        .closure_min_captures_flattened(local_def_id)
+        .map(|place| {
+            let mode = match place.info.capture_kind {
+                rustc_middle::ty::UpvarCapture::ByValue => e::CaptureMode::ByValue,
+                // A unique immutable borrow is introduced when the closure
+                // mutates the target of a mutable reference it captures: the
+                // reference itself is not mutated, but the borrow must be
+                // unique, and the field of the environment is a mutable
+                // reference
+                rustc_middle::ty::UpvarCapture::ByRef(
+                    rustc_middle::ty::BorrowKind::MutBorrow
+                    | rustc_middle::ty::BorrowKind::UniqueImmBorrow,
+                ) => e::CaptureMode::ByMutRef,
+                rustc_middle::ty::UpvarCapture::ByRef(rustc_middle::ty::BorrowKind::ImmBorrow) => {
+                    e::CaptureMode::ByRef
+                }
+            };
+            (place, mode)
+        })
+        .collect()
+}
+
+/// Check if we support a closure, and return the reason why we don't if it is
+/// not the case. The environment and the body of such a closure are opaque.
+pub(crate) fn unsupported_closure(tcx: TyCtxt<'_>, def_id: DefId) -> Option<&'static str> {
+    // The environment would need the parameters of the parent function
+    if tcx.generics_of(def_id).parent_count != 0 {
+        return Some("Closures defined in generic functions are not supported");
+    }
+    // The regions are erased in the types computed by the type checker: we
+    // can't relate them to the region parameters of the environment
+    let captures_regions = translate_closure_captures(tcx, def_id)
+        .into_iter()
+        .any(|(place, _)| {
+            place
+                .place
+                .ty()
+                .walk()
+                .any(|arg| matches!(arg.unpack(), GenericArgKind::Lifetime(_)))
+        });
+    if captures_regions {
+        return Some("Closures capturing values which contain references are not supported");
+    }
+    None
+}
+
+/// Translate the structure holding the environment of a closure. It shares
+/// its rust identifier with the function we generate for the closure body.
+fn translate_closure_env_type(
+    sess: &Session,
+    tcx: TyCtxt,
+    decls: &OrderedDecls,
+    type_defs: &mut ty::TypeDecls,
+    trans_id: ty::TypeDeclId::Id,
+    def_id: DefId,
+) -> Result<()> {
+    let name = type_def_id_to_name(tcx, def_id);
+    let meta = meta::get_meta_from_rid(sess, tcx, &decls.file_to_id, def_id);
+    if let Some(msg) = unsupported_closure(tcx, def_id) {
+        span_err(sess, tcx.def_span(def_id), msg);
+        let mut type_def = closures::mk_env_type_decl(trans_id, meta, name, &[]);
+        type_def.kind = ty::TypeDeclKind::Opaque;
+        assert!(type_defs.types.len() == trans_id.to_usize());
+        type_defs.types.push_back(type_def);
+        return Ok(());
+    }
+
+    let trans_ctx = TypeTransContext::new(type_defs, decls);
+    let mut captures = Vec::new();
+    for (place, mode) in translate_closure_captures(tcx, def_id) {
+        // The captured types don't contain regions (see [unsupported_closure])
+        let ty = translate_ty(
+            tcx,
+            &trans_ctx,
+            &|_| -> ty::Region<ty::RegionVarId::Id> { unreachable!() },
+            &im::OrdMap::new(),
+            &im::OrdMap::new(),
+            &place.place.ty(),
+        )?;
+        captures.push(closures::CapturedVar {
+            name: Some(place.to_symbol(tcx).to_ident_string()),
+            mode,
+            ty,
+        });
+    }
+
+    let type_def = closures::mk_env_type_decl(trans_id, meta, name, &captures);
+    trace!("{} -> {}", trans_id.to_string(), type_def.to_string());
+    assert!(type_defs.types.len() == trans_id.to_usize());
+    type_defs.types.push_back(type_def);
+    Ok(())
+}
+
 /// Translate a type definition.
 ///
 /// Note that we translate the types one by one: we don't need to take into
@@ -737,10 +1123,23 @@ fn translate_type(
     trans_id: ty::TypeDeclId::Id,
 ) -> Result<()> {
     let info = decls.decls_info.get(&AnyDeclId::Type(trans_id)).unwrap();
+    if tcx.is_closure(info.rid) {
+        return translate_closure_env_type(sess, tcx, decls, type_defs, trans_id, info.rid);
+    }
 
     // Check and translate the generics
     let generics = translate_type_generics(tcx, info.rid);
 
+    // Translate the bounds on the parameters
+    let trait_clauses = translate_trait_clauses(
+        tcx,
+        &TypeTransContext::new(type_defs, decls),
+        &generics.region_params_map,
+        &generics.type_params_map,
+        &generics.const_generic_params_map,
+        info.rid,
+    )?;
+
     // Check if the type is opaque or external, and delegate the translation
     // of the "body" to the proper function
     let kind = if !info.is_local() || !info.is_transparent {
@@ -759,25 +1158,65 @@ fn translate_type(
         region_params_map: _,
         type_params,
         type_params_map: _,
+        const_generic_params,
+        const_generic_params_map: _,
     } = generics;
 
     let name = type_def_id_to_name(tcx, info.rid);
     let region_params = ty::RegionVarId::Vector::from(region_params);
     let type_params = ty::TypeVarId::Vector::from(type_params);
+    let const_generic_params = ty::ConstGenericVarId::Vector::from(const_generic_params);
 
     // Translate the span information
     let meta = meta::get_meta_from_rid(sess, tcx, &decls.file_to_id, info.rid);
 
+    // Retrieve the packing information (we only need it for the transparent
+    // types, because we can't project the fields of the opaque ones)
+    let repr_packed = if kind.is_opaque() {
+        None
+    } else {
+        tcx.adt_def(info.rid).repr().pack.map(|align| align.bytes())
+    };
+
+    // The fields of a packed type which may be under-aligned. We can't compute
+    // the layouts of the fields whose types are generic: we conservatively
+    // consider them as under-aligned.
+    let under_aligned_fields = match repr_packed {
+        None => Vec::new(),
+        Some(pack) => {
+            let param_env = tcx.param_env(info.rid);
+            tcx.adt_def(info.rid)
+                .all_fields()
+                .enumerate()
+                .filter(
+                    |(_, field)| match tcx.layout_of(param_env.and(tcx.type_of(field.did))) {
+                        Ok(layout) => layout.align.abi.bytes() > pack,
+                        Err(_) => true,
+                    },
+                )
+                .map(|(i, _)| ty::FieldId::Id::new(i))
+                .collect()
+        }
+    };
+
     let type_def = ty::TypeDecl {
         def_id: trans_id,
         meta,
         name,
         region_params,
         type_params,
+        const_generic_params,
+        trait_clauses,
         kind,
         // For now, initialize the regions hierarchy with a dummy value:
         // we compute it later (after returning to [translate_types]
         regions_hierarchy: regions_hierarchy::RegionGroups::new(),
+        repr_packed,
+        under_aligned_fields,
+        // Same as for the regions hierarchy: we need the whole declaration
+        // group to compute the recursive fields
+        recursive_fields: Vec::new(),
+        attr_info: tool_attributes::get_attr_info(tcx, info.rid),
     };
 
     trace!("{} -> {}", trans_id.to_string(), type_def.to_string());
@@ -827,6 +1266,10 @@ pub fn translate_types(
                         &mut type_defs,
                         decl,
                     );
+                    recursive_types::compute_recursive_fields_for_type_decl_group(
+                        &mut type_defs,
+                        ids,
+                    );
                 }
             },
             DeclarationGroup::Fun(_) | DeclarationGroup::Global(_) => {
@@ -849,3 +1292,47 @@ pub fn translate_types(
 
     Ok((types_cover_regions, type_defs))
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::translate_types::*;
+    use rustc_hir::def_id::{DefIndex, LOCAL_CRATE};
+
+    fn mk_def_id(index: u32) -> DefId {
+        DefId {
+            krate: LOCAL_CRATE,
+            index: DefIndex::from_u32(index),
+        }
+    }
+
+    #[test]
+    fn test_translate_trait_clause() {
+        // We only translate the trait with identifier 1
+        let trait_id = TraitDeclId::Id::new(3);
+        let trait_rid_to_id = HashMap::from([(mk_def_id(1), trait_id)]);
+
+        // `T: Trait<'a, u32>`
+        let t = ty::Ty::TypeVar(ty::TypeVarId::ZERO);
+        let u32_ty = ty::Ty::Integer(ty::IntegerTy::U32);
+        let region = ty::Region::Var(ty::RegionVarId::ZERO);
+        let args = (vec![region], vec![t, u32_ty]);
+        let clause = translate_trait_clause(&trait_rid_to_id, mk_def_id(1), || Ok(args.clone()));
+        let expected = ty::TraitClause {
+            trait_id,
+            region_args: args.0.clone(),
+            type_args: args.1.clone(),
+        };
+        assert!(clause == Ok(Some(expected)));
+
+        // We ignore the clauses of the other traits, without translating
+        // their arguments
+        let clause = translate_trait_clause(&trait_rid_to_id, mk_def_id(2), || {
+            panic!("we shouldn't translate the arguments")
+        });
+        assert!(clause == Ok(None));
+
+        // We fail if we can't translate the arguments
+        let clause = translate_trait_clause(&trait_rid_to_id, mk_def_id(1), || Err(()));
+        assert!(clause == Err(()));
+    }
+}
@@ -1,12 +1,14 @@
 #![allow(dead_code)]
 
-use crate::meta::Meta;
+use crate::gast::TraitDeclId;
+use crate::meta::{AttrInfo, Meta};
 use crate::names::TypeName;
 use crate::regions_hierarchy::RegionGroups;
 pub use crate::types_utils::*;
+use crate::values::PrimitiveValue;
 use im::Vector;
 use macros::{generate_index_type, EnumAsGetters, EnumIsA, VariantIndexArity, VariantName};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 pub type FieldName = String;
 
@@ -20,11 +22,12 @@ generate_index_type!(TypeDeclId);
 generate_index_type!(VariantId);
 generate_index_type!(FieldId);
 generate_index_type!(RegionVarId);
+generate_index_type!(ConstGenericVarId);
 
 /// Type variable.
 /// We make sure not to mix variables and type variables by having two distinct
 /// definitions.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TypeVar {
     /// Unique index identifying the variable
     pub index: TypeVarId::Id,
@@ -33,7 +36,7 @@ pub struct TypeVar {
 }
 
 /// Region variable.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegionVar {
     /// Unique index identifying the variable
     pub index: RegionVarId::Id,
@@ -41,11 +44,64 @@ pub struct RegionVar {
     pub name: Option<String>,
 }
 
+/// Const generic variable, like `N` in `struct Buffer<T, const N: usize>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstGenericVar {
+    /// Unique index identifying the variable
+    pub index: ConstGenericVarId::Id,
+    /// Const generic name
+    pub name: String,
+    /// The type of the parameter (an integer, `bool` or `char`)
+    pub ty: ETy,
+}
+
+/// A constant which appears in a type: the argument of a const generic
+/// parameter, or the length of an array.
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Clone,
+    VariantName,
+    EnumIsA,
+    EnumAsGetters,
+    Serialize,
+    Deserialize,
+)]
+pub enum ConstGeneric {
+    /// A const generic parameter of the enclosing definition
+    Var(ConstGenericVarId::Id),
+    /// A constant value (we evaluate the constant expressions, like
+    /// `N + 1` when `N` is known)
+    Value(PrimitiveValue),
+}
+
+/// A trait clause on the generic parameters of a declaration, like
+/// `T: Clone` in `fn f<T: Clone>(x: &T)` or `K: Ord` in
+/// `struct Map<K, V> where K: Ord { ... }`.
+///
+/// We only keep the clauses of the traits we translate (see
+/// [crate::translate_traits::get_trait_clauses]): the marker traits (`Sized`,
+/// `Copy`, etc.) and the auto traits don't appear here.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraitClause {
+    pub trait_id: TraitDeclId::Id,
+    pub region_args: Vec<Region<RegionVarId::Id>>,
+    /// The type arguments of the trait: the first one is the `Self` type (`T`
+    /// in `T: PartialEq<U>`). The const generic arguments come after the type
+    /// arguments, as [Ty::ConstGeneric].
+    pub type_args: Vec<RTy>,
+}
+
 /// Region as used in a function's signatures (in which case we use region variable
 /// ids) and in symbolic variables and projections (in which case we use region
 /// ids).
 #[derive(
     Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord, EnumIsA, EnumAsGetters, Serialize,
+    Deserialize,
 )]
 pub enum Region<Rid: Copy + Eq> {
     /// Static region
@@ -56,7 +112,10 @@ pub enum Region<Rid: Copy + Eq> {
 
 /// The type of erased regions. See [`Ty`](Ty) for more explanations.
 /// We could use `()`, but having a dedicated type makes things more explicit.
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+///
+/// Note that because there is only one variant, the erased regions never
+/// influence the comparison of types.
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
 pub enum ErasedRegion {
     Erased,
 }
@@ -74,7 +133,7 @@ pub enum ErasedRegion {
 ///
 /// A type can only be an ADT (structure or enumeration), as type aliases are
 /// inlined in MIR.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TypeDecl {
     pub def_id: TypeDeclId::Id,
     /// Meta information associated with the type.
@@ -82,37 +141,65 @@ pub struct TypeDecl {
     pub name: TypeName,
     pub region_params: RegionVarId::Vector<RegionVar>,
     pub type_params: TypeVarId::Vector<TypeVar>,
+    pub const_generic_params: ConstGenericVarId::Vector<ConstGenericVar>,
+    /// The bounds on the generic parameters
+    pub trait_clauses: Vec<TraitClause>,
     /// The lifetime's hierarchy between the different regions.
     pub regions_hierarchy: RegionGroups,
     /// The type kind: enum, struct, or opaque.
     pub kind: TypeDeclKind,
+    /// If the type is annotated with `#[repr(packed(n))]`, the alignment `n`
+    /// (in bytes) of the type. The fields of such a type may be under-aligned,
+    /// meaning references to them must not be created.
+    pub repr_packed: Option<u64>,
+    /// If the type is packed, the fields whose alignment is greater than the
+    /// packing alignment, and which may thus be under-aligned. The alignments
+    /// depend on the target: we compute them from the layouts given by rustc.
+    /// This is only used by [crate::packed_fields].
+    #[serde(skip)]
+    pub under_aligned_fields: Vec<FieldId::Id>,
+    /// If the type belongs to a group of mutually recursive types, the fields
+    /// (given by their variant, for the enumerations, and their index) whose
+    /// types refer to a type of the group. For instance, the tail of `Cons`
+    /// in `enum List { Cons(u32, Box<List>), Nil }`.
+    /// This is computed in [crate::recursive_types].
+    pub recursive_fields: Vec<(Option<VariantId::Id>, FieldId::Id)>,
+    /// The doc comments and the attributes of the type
+    pub attr_info: AttrInfo,
 }
 
-#[derive(Debug, Clone, EnumIsA, EnumAsGetters, Serialize)]
+#[derive(Debug, Clone, EnumIsA, EnumAsGetters, Serialize, Deserialize)]
 pub enum TypeDeclKind {
     Struct(FieldId::Vector<Field>),
     Enum(VariantId::Vector<Variant>),
+    /// A union: the fields share the same memory, and reading a field
+    /// reinterprets the content of the union. This is why the reads of
+    /// union fields are unsafe (see [crate::gast::UnsafeOperation]).
+    Union(FieldId::Vector<Field>),
     /// An opaque type.
     ///
     /// Either a local type marked as opaque, or an external type.
     Opaque,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Variant {
     pub meta: Meta,
     pub name: String,
     pub fields: FieldId::Vector<Field>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Field {
     pub meta: Meta,
     pub name: Option<String>,
     pub ty: RTy,
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, EnumIsA, VariantName, Serialize)]
+#[derive(
+    Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Copy, Clone, EnumIsA, VariantName, Serialize,
+    Deserialize,
+)]
 pub enum IntegerTy {
     Isize,
     I8,
@@ -128,7 +215,24 @@ pub enum IntegerTy {
     U128,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, VariantName, EnumIsA, Serialize)]
+/// The floating-point types.
+///
+/// Contrary to the integer operations, the operations on the floats never
+/// fail: they follow the IEEE 754 semantics (an overflow gives an infinity,
+/// `0.0 / 0.0` gives `NaN`, etc.).
+#[derive(
+    Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Copy, Clone, EnumIsA, VariantName, Serialize,
+    Deserialize,
+)]
+pub enum FloatTy {
+    F32,
+    F64,
+}
+
+#[derive(
+    Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy, VariantName, EnumIsA, Serialize,
+    Deserialize,
+)]
 pub enum RefKind {
     Mut,
     Shared,
@@ -136,7 +240,7 @@ pub enum RefKind {
 
 /// We represent (at least for the momement) raw pointers by ignoring their
 /// lifetime information.
-#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct RawPtrTy
 {
    boxedtype : Box<Ty<ErasedRegion>>,
@@ -146,7 +250,20 @@ pub struct RawPtrTy
 /// Type identifier.
 ///
 /// Allows us to factorize the code for assumed types, adts and tuples
-#[derive(Debug, PartialEq, Eq, Clone, VariantName, EnumAsGetters, EnumIsA, Serialize)]
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Clone,
+    VariantName,
+    EnumAsGetters,
+    EnumIsA,
+    Serialize,
+    Deserialize,
+)]
 pub enum TypeId {
     /// A "regular" ADT type.
     ///
@@ -175,7 +292,22 @@ pub struct TypeDecls {
 /// error prone) in our encoding by using two different types: [`Region`](Region)
 /// and [`ErasedRegion`](ErasedRegion), the latter being an enumeration with only
 /// one variant.
-#[derive(Debug, PartialEq, Eq, Clone, VariantName, EnumIsA, EnumAsGetters, VariantIndexArity)]
+///
+/// Types are totally ordered (provided the regions are), so that we can use
+/// them, or lists of type arguments, as keys in ordered maps.
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Clone,
+    VariantName,
+    EnumIsA,
+    EnumAsGetters,
+    VariantIndexArity,
+)]
 pub enum Ty<R>
 where
     R: Clone + std::cmp::Eq,
@@ -186,6 +318,9 @@ where
     /// - tuples (including `unit`, which is a 0-tuple)
     /// - assumed types
     /// The information on the nature of the ADT is stored in (`TypeId`)[TypeId].
+    ///
+    /// The arguments of the const generic parameters (if there are) come
+    /// after the type arguments, as [Ty::ConstGeneric].
     Adt(TypeId, Vector<R>, Vector<Ty<R>>),
     TypeVar(TypeVarId::Id),
     Bool,
@@ -201,13 +336,15 @@ where
     /// ```
     /// the second branch will have type `Never`. Also note that `Never`
     /// can be coerced to any type.
-    /// TODO: but do we really use this type for variables?...
+    /// We use this type for the locals which receive the (non-existent)
+    /// result of a diverging call (`panic!`, `std::process::exit`, etc.):
+    /// the code which uses such a local is dead.
     Never,
     Integer(IntegerTy),
-    // We don't support floating point numbers on purpose
+    Float(FloatTy),
     Str,
-    // TODO: there should be a constant with the array
-    Array(Box<Ty<R>>),
+    /// An array, with its length
+    Array(Box<Ty<R>>, ConstGeneric),
     Slice(Box<Ty<R>>),
     /// A borrow
     Ref(R, Box<Ty<R>>, RefKind),
@@ -233,6 +370,43 @@ where
     /// For now, we detect this case (this is hardcoded in [crate::register] and
     /// [crate::translate_functions_to_ullbc]) to rewrite it to `free(move b)`.
     RawPtr(Box<Ty<R>>, RefKind),
+    /// A const generic argument. This only appears in the arguments of the
+    /// ADTs and of the function calls, after the type arguments.
+    ConstGeneric(ConstGeneric),
+    /// A trait object (`dyn Trait`), identified by its principal trait. The
+    /// auto traits (`dyn Trait + Send`) and the lifetime bound are ignored.
+    ///
+    /// Like [Ty::Str] and [Ty::Slice], this type is unsized: it only appears
+    /// behind a pointer (`&dyn Trait`, `Box<dyn Trait>`). The values of this
+    /// type are created with [crate::expressions::Rvalue::Unsize], and their
+    /// methods are called with [crate::gast::FunId::Virtual].
+    DynTrait(TraitDeclId::Id),
+    /// A function pointer (`fn(u32) -> bool`): `FnPtr(inputs, output)`.
+    ///
+    /// We don't support the function pointer types which quantify over
+    /// regions (`for<'a> fn(&'a u32)`, or `fn(&u32)` where the region is
+    /// elided): the regions must come from the environment.
+    FnPtr(Vector<Ty<R>>, Box<Ty<R>>),
+    /// An opaque type (`impl Trait` in the return type of a function):
+    /// `Opaque(hidden_ty, bounds)`.
+    ///
+    /// `hidden_ty` is the type the opaque type stands for, as computed by
+    /// the type-checker. We only give it for the opaque types introduced by
+    /// the functions of the current crate: the hidden types of the other
+    /// crates are implementation details.
+    /// `bounds` are the traits the type implements (`Iterator` in
+    /// `impl Iterator<Item = u32>`). Like for [Ty::DynTrait], we ignore the
+    /// auto traits, and we don't record the associated types.
+    Opaque(Option<Box<Ty<R>>>, Vector<TraitDeclId::Id>),
+    /// An associated type of a trait, whose implementation we can't determine
+    /// statically (`T::Item`, or `<T as Iterator>::Item`):
+    /// `TraitProjection(trait_id, regions, types, name)`.
+    ///
+    /// The arguments are the arguments of the trait, the first type argument
+    /// being the `Self` type (see [TraitClause]). In the function bodies,
+    /// rustc normalizes the projections whose implementation is known
+    /// (`<u32 as Trait>::Assoc`).
+    TraitProjection(TraitDeclId::Id, Vector<R>, Vector<Ty<R>>, String),
 }
 
 /// Type with *R*egions.
@@ -257,7 +431,21 @@ pub type ETy = Ty<ErasedRegion>;
 /// TODO: update to not hardcode the types (except `Box` maybe) and be more
 /// modular.
 /// TODO: move to assumed.rs?
-#[derive(Debug, PartialEq, Eq, Clone, Copy, EnumIsA, EnumAsGetters, VariantName, Serialize)]
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Clone,
+    Copy,
+    EnumIsA,
+    EnumAsGetters,
+    VariantName,
+    Serialize,
+    Deserialize,
+)]
 pub enum AssumedTy {
     /// Boxes have a special treatment: we translate them as identity.
     Box,
@@ -265,9 +453,18 @@ pub enum AssumedTy {
     Vec,
     /// Comes from the standard library
     Option,
+    /// `core::ops::control_flow::ControlFlow<B, C>`, returned by the method
+    /// `branch` used in the desugaring of the `?` operator. Its variants are
+    /// `Continue(C)` and `Break(B)`, in this order.
+    ControlFlow,
     /// Comes from the standard library. See the comments for [Ty::RawPtr]
     /// as to why we have this here.
     PtrUnique,
     /// Same comments as for [AssumedTy::PtrUnique]
     PtrNonNull,
+    /// `alloc::rc::Rc<T>`. We see the shared pointers as opaque smart
+    /// pointers: they are dereferenced with [crate::expressions::ProjectionElem::DerefRc].
+    Rc,
+    /// `alloc::sync::Arc<T>`. Same comments as for [AssumedTy::Rc]
+    Arc,
 }
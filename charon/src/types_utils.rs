@@ -4,13 +4,14 @@
 use crate::assumed::get_name_from_type_id;
 use crate::common::*;
 use crate::formatter::Formatter;
+use crate::gast::TraitDeclId;
 use crate::id_vector;
 use crate::types::*;
 use crate::ullbc_ast::GlobalDeclId;
 use im::{HashMap, OrdSet, Vector};
-use rustc_middle::ty::{IntTy, UintTy};
+use rustc_middle::ty::{FloatTy as RustFloatTy, IntTy, UintTy};
 use serde::ser::SerializeTupleVariant;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::iter::FromIterator;
 use std::iter::Iterator;
 
@@ -18,6 +19,7 @@ pub type RegionSubst<R> = HashMap<RegionVarId::Id, R>;
 pub type TypeSubst<R> = HashMap<TypeVarId::Id, Ty<R>>;
 /// Type substitution where the regions are erased
 pub type ETypeSubst = TypeSubst<ErasedRegion>;
+pub type ConstGenericSubst = HashMap<ConstGenericVarId::Id, ConstGeneric>;
 
 impl RegionVarId::Id {
     pub fn substitute<R>(&self, rsubst: &RegionSubst<R>) -> R
@@ -78,6 +80,30 @@ impl std::string::ToString for TypeVar {
     }
 }
 
+impl std::string::ToString for ConstGenericVar {
+    fn to_string(&self) -> String {
+        format!("const {} : {}", self.name, self.ty.to_string())
+    }
+}
+
+impl ConstGeneric {
+    pub fn substitute(&self, cgsubst: &dyn Fn(&ConstGenericVarId::Id) -> ConstGeneric) -> Self {
+        match self {
+            ConstGeneric::Var(id) => cgsubst(id),
+            ConstGeneric::Value(v) => ConstGeneric::Value(v.clone()),
+        }
+    }
+}
+
+impl std::string::ToString for ConstGeneric {
+    fn to_string(&self) -> String {
+        match self {
+            ConstGeneric::Var(id) => const_generic_var_id_to_pretty_string(*id),
+            ConstGeneric::Value(v) => v.to_string(),
+        }
+    }
+}
+
 impl std::string::ToString for RegionVar {
     fn to_string(&self) -> String {
         let id = region_var_id_to_pretty_string(self.index);
@@ -89,12 +115,12 @@ impl std::string::ToString for RegionVar {
 }
 
 impl TypeDecl {
-    /// The variant id should be `None` if it is a structure and `Some` if it
-    /// is an enumeration.
+    /// The variant id should be `None` if it is a structure or a union, and
+    /// `Some` if it is an enumeration.
     pub fn get_fields(&self, variant_id: Option<VariantId::Id>) -> &FieldId::Vector<Field> {
         match &self.kind {
             TypeDeclKind::Enum(variants) => &variants.get(variant_id.unwrap()).unwrap().fields,
-            TypeDeclKind::Struct(fields) => {
+            TypeDeclKind::Struct(fields) | TypeDeclKind::Union(fields) => {
                 assert!(variant_id.is_none());
                 fields
             }
@@ -104,6 +130,40 @@ impl TypeDecl {
         }
     }
 
+    /// Return the name of a field, as it appears in the source: the fields of
+    /// the tuple structs and of the tuple-like variants have no names, in
+    /// which case we use their positions (`.0`, `.1`, etc.).
+    pub fn get_field_name(
+        &self,
+        variant_id: Option<VariantId::Id>,
+        field_id: FieldId::Id,
+    ) -> String {
+        let field = self.get_fields(variant_id).get(field_id).unwrap();
+        match &field.name {
+            Option::Some(name) => name.clone(),
+            Option::None => field_id.to_string(),
+        }
+    }
+
+    /// Compute the substitutions for the type parameters and the const generic
+    /// parameters, from the arguments given to the type (the const generic
+    /// arguments come after the type arguments).
+    pub fn make_generics_subst<R>(
+        &self,
+        inst_types: &Vector<Ty<R>>,
+    ) -> (TypeSubst<R>, ConstGenericSubst)
+    where
+        R: Clone + Eq,
+    {
+        let (inst_types, inst_cgs) = split_const_generic_args(inst_types);
+        let ty_subst = make_type_subst(self.type_params.iter().map(|x| x.index), inst_types.iter());
+        let cg_subst = make_const_generic_subst(
+            self.const_generic_params.iter().map(|x| x.index),
+            inst_cgs.iter(),
+        );
+        (ty_subst, cg_subst)
+    }
+
     /// Instantiate the fields of every variant of a type definition.
     ///
     /// Return an option: `Some` if we have access to the type definition,
@@ -118,23 +178,19 @@ impl TypeDecl {
             self.region_params.iter().map(|x| x.index),
             inst_regions.iter(),
         );
-        let ty_subst = make_type_subst(self.type_params.iter().map(|x| x.index), inst_types.iter());
+        let (ty_subst, cg_subst) = self.make_generics_subst(inst_types);
+        let subst = |ty: &RTy| ty.substitute_regions_types(&r_subst, &ty_subst, &cg_subst);
 
         match &self.kind {
-            TypeDeclKind::Struct(fields) => {
+            // We consider that a union has a single variant, like a structure
+            TypeDeclKind::Struct(fields) | TypeDeclKind::Union(fields) => {
                 Option::Some(VariantId::Vector::from(vec![FieldId::Vector::from_iter(
-                    fields
-                        .iter()
-                        .map(|f| f.ty.substitute_regions_types(&r_subst, &ty_subst)),
+                    fields.iter().map(|f| subst(&f.ty)),
                 )]))
             }
             TypeDeclKind::Enum(variants) => {
                 Option::Some(VariantId::Vector::from_iter(variants.iter().map(|v| {
-                    FieldId::Vector::from_iter(
-                        v.fields
-                            .iter()
-                            .map(|f| f.ty.substitute_regions_types(&r_subst, &ty_subst)),
-                    )
+                    FieldId::Vector::from_iter(v.fields.iter().map(|f| subst(&f.ty)))
                 })))
             }
             TypeDeclKind::Opaque => Option::None,
@@ -148,13 +204,13 @@ impl TypeDecl {
         variant_id: Option<VariantId::Id>,
         inst_types: &Vector<ETy>,
     ) -> Vector<ETy> {
-        // Introduce the substitution
-        let ty_subst = make_type_subst(self.type_params.iter().map(|x| x.index), inst_types.iter());
+        // Introduce the substitutions
+        let (ty_subst, cg_subst) = self.make_generics_subst(inst_types);
 
         let fields = self.get_fields(variant_id);
         let field_types: Vec<ETy> = fields
             .iter()
-            .map(|f| f.ty.erase_regions_substitute_types(&ty_subst))
+            .map(|f| f.ty.erase_regions_substitute_types(&ty_subst, &cg_subst))
             .collect();
 
         Vector::from(field_types)
@@ -168,8 +224,8 @@ impl TypeDecl {
         inst_types: &Vector<ETy>,
         field_id: FieldId::Id,
     ) -> ETy {
-        // Introduce the substitution
-        let ty_subst = make_type_subst(self.type_params.iter().map(|x| x.index), inst_types.iter());
+        // Introduce the substitutions
+        let (ty_subst, cg_subst) = self.make_generics_subst(inst_types);
 
         let fields = self.get_fields(variant_id);
         let field_type = fields
@@ -177,7 +233,7 @@ impl TypeDecl {
             .unwrap()
             .ty
             .erase_regions()
-            .substitute_types(&ty_subst);
+            .substitute_types(&ty_subst, &cg_subst);
         field_type
     }
 
@@ -195,7 +251,11 @@ impl TypeDecl {
             .collect();
         let regions_hierarchy = regions_hierarchy.join("\n");
 
-        let params = TypeDecl::fmt_params(&self.region_params, &self.type_params);
+        let params = TypeDecl::fmt_params(
+            &self.region_params,
+            &self.type_params,
+            &self.const_generic_params,
+        );
         match &self.kind {
             TypeDeclKind::Struct(fields) => {
                 if !fields.is_empty() {
@@ -223,6 +283,17 @@ impl TypeDecl {
                     self.name, params, variants, regions_hierarchy
                 )
             }
+            TypeDeclKind::Union(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|f| format!("\n  {}", f.fmt_with_ctx(ctx)))
+                    .collect();
+                let fields = fields.join(",");
+                format!(
+                    "union {}{} = {{{}\n}}\n{}",
+                    self.name, params, fields, regions_hierarchy
+                )
+            }
             TypeDeclKind::Opaque => format!(
                 "opaque type {}{}\nRegions hierarchy:\n{}",
                 self.name, params, regions_hierarchy
@@ -233,11 +304,13 @@ impl TypeDecl {
     fn fmt_params(
         region_params: &RegionVarId::Vector<RegionVar>,
         type_params: &TypeVarId::Vector<TypeVar>,
+        const_generic_params: &ConstGenericVarId::Vector<ConstGenericVar>,
     ) -> String {
-        if region_params.len() + type_params.len() > 0 {
+        if region_params.len() + type_params.len() + const_generic_params.len() > 0 {
             let regions = region_params.iter().map(|r| r.to_string());
             let type_params = type_params.iter().map(|p| p.to_string());
-            let params: Vec<String> = regions.chain(type_params).collect();
+            let cg_params = const_generic_params.iter().map(|p| p.to_string());
+            let params: Vec<String> = regions.chain(type_params).chain(cg_params).collect();
             format!("<{}>", params.join(", "))
         } else {
             "".to_string()
@@ -251,6 +324,14 @@ impl std::string::ToString for TypeDecl {
     }
 }
 
+impl TypeDecl {
+    /// Same as [TypeDecl::to_string], but we print the names of the types
+    /// we refer to, rather than their identifiers.
+    pub fn fmt_with_decls(&self, decls: &TypeDecls) -> String {
+        self.fmt_with_ctx(&TypeDeclsFormatter { decls, def: self })
+    }
+}
+
 impl Variant {
     pub fn fmt_with_ctx<'a, T>(&'a self, ctx: &'a T) -> String
     where
@@ -351,6 +432,24 @@ impl IntegerTy {
     }
 }
 
+impl FloatTy {
+    pub fn rust_float_ty_to_float_ty(ty: RustFloatTy) -> FloatTy {
+        match ty {
+            RustFloatTy::F32 => FloatTy::F32,
+            RustFloatTy::F64 => FloatTy::F64,
+        }
+    }
+}
+
+impl std::fmt::Display for FloatTy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            FloatTy::F32 => write!(f, "f32"),
+            FloatTy::F64 => write!(f, "f64"),
+        }
+    }
+}
+
 pub fn type_def_id_to_pretty_string(id: TypeDeclId::Id) -> String {
     format!("@Adt{id}")
 }
@@ -362,6 +461,10 @@ pub fn region_var_id_to_pretty_string(id: RegionVarId::Id) -> String {
     format!("@R{id}")
 }
 
+pub fn const_generic_var_id_to_pretty_string(id: ConstGenericVarId::Id) -> String {
+    format!("@Cg{id}")
+}
+
 pub fn integer_ty_to_string(ty: IntegerTy) -> String {
     match ty {
         IntegerTy::Isize => "isize".to_string(),
@@ -465,11 +568,23 @@ where
     pub fn is_leaf(&self) -> bool {
         match self {
             Ty::Adt(_, _, _)
-            | Ty::Array(_)
+            | Ty::Array(_, _)
             | Ty::Slice(_)
             | Ty::Ref(_, _, _)
-            | Ty::RawPtr(_, _) => false,
-            Ty::TypeVar(_) | Ty::Bool | Ty::Char | Ty::Never | Ty::Integer(_) | Ty::Str => true,
+            | Ty::RawPtr(_, _)
+            | Ty::FnPtr(_, _)
+            | Ty::Opaque(Some(_), _)
+            | Ty::TraitProjection(_, _, _, _) => false,
+            Ty::TypeVar(_)
+            | Ty::Bool
+            | Ty::Char
+            | Ty::Never
+            | Ty::Integer(_)
+            | Ty::Float(_)
+            | Ty::Str
+            | Ty::ConstGeneric(_)
+            | Ty::DynTrait(_)
+            | Ty::Opaque(None, _) => true,
         }
     }
 
@@ -509,8 +624,9 @@ where
             Ty::Char => "char".to_string(),
             Ty::Never => "!".to_string(),
             Ty::Integer(int_ty) => integer_ty_to_string(*int_ty),
+            Ty::Float(float_ty) => float_ty.to_string(),
             Ty::Str => "str".to_string(),
-            Ty::Array(ty) => format!("[{}; ?]", ty.fmt_with_ctx(ctx)),
+            Ty::Array(ty, len) => format!("[{}; {}]", ty.fmt_with_ctx(ctx), len.to_string()),
             Ty::Slice(ty) => format!("[{}]", ty.fmt_with_ctx(ctx)),
             Ty::Ref(r, ty, kind) => match kind {
                 RefKind::Mut => {
@@ -524,6 +640,41 @@ where
                 RefKind::Mut => format!("*const {}", ty.fmt_with_ctx(ctx)),
                 RefKind::Shared => format!("*mut {}", ty.fmt_with_ctx(ctx)),
             },
+            Ty::ConstGeneric(cg) => cg.to_string(),
+            Ty::DynTrait(trait_id) => format!("dyn @Trait{trait_id}"),
+            Ty::FnPtr(inputs, output) => {
+                let inputs: Vec<String> = inputs.iter().map(|ty| ty.fmt_with_ctx(ctx)).collect();
+                format!("fn({}) -> {}", inputs.join(", "), output.fmt_with_ctx(ctx))
+            }
+            Ty::Opaque(hidden_ty, bounds) => {
+                let bounds: Vec<String> = bounds.iter().map(|id| format!("@Trait{id}")).collect();
+                let bounds = if bounds.is_empty() {
+                    "_".to_string()
+                } else {
+                    bounds.join(" + ")
+                };
+                match hidden_ty {
+                    Some(ty) => format!("impl {} (= {})", bounds, ty.fmt_with_ctx(ctx)),
+                    None => format!("impl {bounds}"),
+                }
+            }
+            Ty::TraitProjection(trait_id, regions, tys, name) => {
+                // The first type argument is the `Self` type
+                let self_ty = tys[0].fmt_with_ctx(ctx);
+                let args: Vec<String> = regions
+                    .iter()
+                    .map(|r| ctx.format_object(r))
+                    .chain(tys.iter().skip(1).map(|ty| ty.fmt_with_ctx(ctx)))
+                    .collect();
+                if args.is_empty() {
+                    format!("<{self_ty} as @Trait{trait_id}>::{name}")
+                } else {
+                    format!(
+                        "<{self_ty} as @Trait{trait_id}<{}>>::{name}",
+                        args.join(", ")
+                    )
+                }
+            }
         }
     }
 
@@ -580,11 +731,19 @@ impl<Rid: Copy + Eq + Ord + std::hash::Hash> Ty<Region<Rid>> {
     pub fn contains_region_var(&self, rset: &OrdSet<Rid>) -> bool {
         match self {
             Ty::TypeVar(_) => false,
-            Ty::Bool | Ty::Char | Ty::Never | Ty::Integer(_) | Ty::Str => false,
-            Ty::Array(ty) | Ty::Slice(ty) => ty.contains_region_var(rset),
+            Ty::Bool | Ty::Char | Ty::Never | Ty::Integer(_) | Ty::Float(_) | Ty::Str => false,
+            Ty::ConstGeneric(_) | Ty::DynTrait(_) => false,
+            Ty::Array(ty, _) | Ty::Slice(ty) => ty.contains_region_var(rset),
             Ty::Ref(r, ty, _) => r.contains_var(rset) || ty.contains_region_var(rset),
             Ty::RawPtr(ty, _) => ty.contains_region_var(rset),
-            Ty::Adt(_, regions, tys) => regions
+            Ty::FnPtr(inputs, output) => {
+                inputs.iter().any(|ty| ty.contains_region_var(rset))
+                    || output.contains_region_var(rset)
+            }
+            Ty::Opaque(hidden_ty, _) => hidden_ty
+                .as_ref()
+                .map_or(false, |ty| ty.contains_region_var(rset)),
+            Ty::Adt(_, regions, tys) | Ty::TraitProjection(_, regions, tys, _) => regions
                 .iter()
                 .any(|r| r.contains_var(rset) || tys.iter().any(|x| x.contains_region_var(rset))),
         }
@@ -652,6 +811,40 @@ impl<'a> Formatter<TypeDeclId::Id> for IncompleteFormatter<'a> {
     }
 }
 
+/// Formatter for a type declaration, which looks up the names of the
+/// other declarations in the context.
+pub struct TypeDeclsFormatter<'a> {
+    decls: &'a TypeDecls,
+    def: &'a TypeDecl,
+}
+
+impl<'a> Formatter<TypeVarId::Id> for TypeDeclsFormatter<'a> {
+    fn format_object(&self, id: TypeVarId::Id) -> String {
+        self.def.format_object(id)
+    }
+}
+
+impl<'a, 'b, Rid: Copy + Eq> Formatter<&'b Region<Rid>> for TypeDeclsFormatter<'a>
+where
+    TypeDecl: Formatter<&'b Region<Rid>>,
+{
+    fn format_object(&self, r: &'b Region<Rid>) -> String {
+        self.def.format_object(r)
+    }
+}
+
+impl<'a> Formatter<RegionVarId::Id> for TypeDeclsFormatter<'a> {
+    fn format_object(&self, id: RegionVarId::Id) -> String {
+        self.def.format_object(id)
+    }
+}
+
+impl<'a> Formatter<TypeDeclId::Id> for TypeDeclsFormatter<'a> {
+    fn format_object(&self, id: TypeDeclId::Id) -> String {
+        self.decls.format_object(id)
+    }
+}
+
 pub struct DummyFormatter {}
 
 impl Formatter<TypeVarId::Id> for DummyFormatter {
@@ -702,14 +895,19 @@ where
         &self,
         rsubst: &dyn Fn(&R) -> R1,
         tsubst: &dyn Fn(&TypeVarId::Id) -> Ty<R1>,
+        cgsubst: &dyn Fn(&ConstGenericVarId::Id) -> ConstGeneric,
     ) -> Ty<R1>
     where
         R1: Clone + Eq,
     {
+        let subst = |ty: &Ty<R>| Box::new(ty.substitute(rsubst, tsubst, cgsubst));
         match self {
             Ty::Adt(id, regions, tys) => {
                 let nregions = Ty::substitute_regions(regions, rsubst);
-                let ntys = tys.iter().map(|ty| ty.substitute(rsubst, tsubst)).collect();
+                let ntys = tys
+                    .iter()
+                    .map(|ty| ty.substitute(rsubst, tsubst, cgsubst))
+                    .collect();
                 Ty::Adt(id.clone(), nregions, ntys)
             }
             Ty::TypeVar(id) => tsubst(id),
@@ -717,13 +915,32 @@ where
             Ty::Char => Ty::Char,
             Ty::Never => Ty::Never,
             Ty::Integer(k) => Ty::Integer(*k),
+            Ty::Float(k) => Ty::Float(*k),
             Ty::Str => Ty::Str,
-            Ty::Array(ty) => Ty::Array(Box::new(ty.substitute(rsubst, tsubst))),
-            Ty::Slice(ty) => Ty::Slice(Box::new(ty.substitute(rsubst, tsubst))),
-            Ty::Ref(rid, ty, kind) => {
-                Ty::Ref(rsubst(rid), Box::new(ty.substitute(rsubst, tsubst)), *kind)
+            Ty::Array(ty, len) => Ty::Array(subst(ty), len.substitute(cgsubst)),
+            Ty::Slice(ty) => Ty::Slice(subst(ty)),
+            Ty::Ref(rid, ty, kind) => Ty::Ref(rsubst(rid), subst(ty), *kind),
+            Ty::RawPtr(ty, kind) => Ty::RawPtr(subst(ty), *kind),
+            Ty::ConstGeneric(cg) => Ty::ConstGeneric(cg.substitute(cgsubst)),
+            Ty::DynTrait(trait_id) => Ty::DynTrait(*trait_id),
+            Ty::FnPtr(inputs, output) => Ty::FnPtr(
+                inputs
+                    .iter()
+                    .map(|ty| ty.substitute(rsubst, tsubst, cgsubst))
+                    .collect(),
+                subst(output),
+            ),
+            Ty::Opaque(hidden_ty, bounds) => {
+                Ty::Opaque(hidden_ty.as_ref().map(|ty| subst(ty)), bounds.clone())
             }
-            Ty::RawPtr(ty, kind) => Ty::RawPtr(Box::new(ty.substitute(rsubst, tsubst)), *kind),
+            Ty::TraitProjection(trait_id, regions, tys, name) => Ty::TraitProjection(
+                *trait_id,
+                Ty::substitute_regions(regions, rsubst),
+                tys.iter()
+                    .map(|ty| ty.substitute(rsubst, tsubst, cgsubst))
+                    .collect(),
+                name.clone(),
+            ),
         }
     }
 
@@ -734,32 +951,55 @@ where
         Vector::from_iter(regions.iter().map(|rid| rsubst(rid)))
     }
 
-    /// Substitute the type parameters
-    pub fn substitute_types(&self, subst: &TypeSubst<R>) -> Self {
-        self.substitute(&|r| *r, &|tid| subst.get(tid).unwrap().clone())
+    /// Substitute the type parameters and the const generic parameters
+    pub fn substitute_types(&self, subst: &TypeSubst<R>, cgsubst: &ConstGenericSubst) -> Self {
+        self.substitute(&|r| *r, &|tid| subst.get(tid).unwrap().clone(), &|cgid| {
+            cgsubst.get(cgid).unwrap().clone()
+        })
     }
 
     /// Erase the regions
     pub fn erase_regions(&self) -> ETy {
-        self.substitute(&|_| ErasedRegion::Erased, &|tid| Ty::TypeVar(*tid))
+        self.substitute(
+            &|_| ErasedRegion::Erased,
+            &|tid| Ty::TypeVar(*tid),
+            &|cgid| ConstGeneric::Var(*cgid),
+        )
     }
 
-    /// Erase the regions and substitute the types at the same time
-    pub fn erase_regions_substitute_types(&self, subst: &TypeSubst<ErasedRegion>) -> ETy {
-        self.substitute(&|_| ErasedRegion::Erased, &|tid| {
-            subst.get(tid).unwrap().clone()
-        })
+    /// Erase the regions and substitute the types (and the const generics) at
+    /// the same time
+    pub fn erase_regions_substitute_types(
+        &self,
+        subst: &TypeSubst<ErasedRegion>,
+        cgsubst: &ConstGenericSubst,
+    ) -> ETy {
+        self.substitute(
+            &|_| ErasedRegion::Erased,
+            &|tid| subst.get(tid).unwrap().clone(),
+            &|cgid| cgsubst.get(cgid).unwrap().clone(),
+        )
     }
 
-    /// Returns `true` if the type contains some region or type variables
+    /// Returns `true` if the type contains some region, type or const generic
+    /// variables
     pub fn contains_variables(&self) -> bool {
         match self {
             Ty::TypeVar(_) => true,
-            Ty::Bool | Ty::Char | Ty::Never | Ty::Integer(_) | Ty::Str => false,
-            Ty::Array(ty) | Ty::Slice(ty) => ty.contains_variables(),
+            Ty::Bool | Ty::Char | Ty::Never | Ty::Integer(_) | Ty::Float(_) | Ty::Str => false,
+            Ty::ConstGeneric(cg) => cg.is_var(),
+            Ty::DynTrait(_) => false,
+            Ty::Array(ty, len) => len.is_var() || ty.contains_variables(),
+            Ty::Slice(ty) => ty.contains_variables(),
             Ty::Ref(_, _, _) => true, // Always contains a region identifier
             Ty::RawPtr(ty, _) => ty.contains_variables(),
-            Ty::Adt(_, regions, tys) => {
+            Ty::FnPtr(inputs, output) => {
+                inputs.iter().any(|ty| ty.contains_variables()) || output.contains_variables()
+            }
+            Ty::Opaque(hidden_ty, _) => hidden_ty
+                .as_ref()
+                .map_or(false, |ty| ty.contains_variables()),
+            Ty::Adt(_, regions, tys) | Ty::TraitProjection(_, regions, tys, _) => {
                 !regions.is_empty() || tys.iter().any(|x| x.contains_variables())
             }
         }
@@ -769,11 +1009,18 @@ where
     pub fn contains_regions(&self) -> bool {
         match self {
             Ty::TypeVar(_) => false,
-            Ty::Bool | Ty::Char | Ty::Never | Ty::Integer(_) | Ty::Str => false,
-            Ty::Array(ty) | Ty::Slice(ty) => ty.contains_regions(),
+            Ty::Bool | Ty::Char | Ty::Never | Ty::Integer(_) | Ty::Float(_) | Ty::Str => false,
+            Ty::ConstGeneric(_) | Ty::DynTrait(_) => false,
+            Ty::Array(ty, _) | Ty::Slice(ty) => ty.contains_regions(),
             Ty::Ref(_, _, _) => true,
             Ty::RawPtr(ty, _) => ty.contains_regions(),
-            Ty::Adt(_, regions, tys) => {
+            Ty::FnPtr(inputs, output) => {
+                inputs.iter().any(|ty| ty.contains_regions()) || output.contains_regions()
+            }
+            Ty::Opaque(hidden_ty, _) => {
+                hidden_ty.as_ref().map_or(false, |ty| ty.contains_regions())
+            }
+            Ty::Adt(_, regions, tys) | Ty::TraitProjection(_, regions, tys, _) => {
                 !regions.is_empty() || tys.iter().any(|x| x.contains_regions())
             }
         }
@@ -782,11 +1029,12 @@ where
 
 // TODO: mixing Copy and Clone in the trait requirements below. Update to only use Copy.
 impl RTy {
-    /// Substitute the regions and type parameters
+    /// Substitute the regions, type parameters and const generic parameters
     pub fn substitute_regions_types(
         &self,
         rsubst: &RegionSubst<Region<RegionVarId::Id>>,
         tsubst: &TypeSubst<Region<RegionVarId::Id>>,
+        cgsubst: &ConstGenericSubst,
     ) -> Self {
         self.substitute(
             &|rid| match rid {
@@ -794,6 +1042,7 @@ impl RTy {
                 Region::Var(rid) => *rsubst.get(rid).unwrap(),
             },
             &|tid| tsubst.get(tid).unwrap().clone(),
+            &|cgid| cgsubst.get(cgid).unwrap().clone(),
         )
     }
 }
@@ -837,6 +1086,29 @@ where
     make_subst(params, types)
 }
 
+pub fn make_const_generic_subst<
+    'a,
+    I1: Iterator<Item = ConstGenericVarId::Id>,
+    I2: Iterator<Item = &'a ConstGeneric>,
+>(
+    params: I1,
+    values: I2,
+) -> ConstGenericSubst {
+    make_subst(params, values)
+}
+
+/// Split the arguments of an ADT or of a function call between the type
+/// arguments and the const generic arguments (which come last).
+pub fn split_const_generic_args<R>(args: &Vector<Ty<R>>) -> (Vector<Ty<R>>, Vector<ConstGeneric>)
+where
+    R: Clone + Eq,
+{
+    let num_types = args.iter().take_while(|ty| !ty.is_const_generic()).count();
+    let cgs = args.skip(num_types);
+    let cgs = cgs.iter().map(|ty| ty.as_const_generic().clone()).collect();
+    (args.take(num_types), cgs)
+}
+
 pub fn make_region_subst<
     'a,
     R: 'a + Eq,
@@ -912,7 +1184,9 @@ impl<R: Clone + std::cmp::Eq + Serialize> Serialize for Ty<R> {
         // It seems the "standard" way of doing is the following (this is
         // consistent with what the automatically generated serializer does):
         // - if the arity is > 0, use `serialize_tuple_variant`
-        // - otherwise simply serialize a string with the variant name
+        // - otherwise use `serialize_unit_variant` (in JSON, this gives a
+        //   string with the variant name, while the binary formats only use
+        //   the variant index)
         if variant_arity > 0 {
             let mut vs = serializer.serialize_tuple_variant(
                 enum_name,
@@ -937,8 +1211,12 @@ impl<R: Clone + std::cmp::Eq + Serialize> Serialize for Ty<R> {
                 Ty::Integer(int_ty) => {
                     vs.serialize_field(int_ty)?;
                 }
-                Ty::Array(ty) => {
+                Ty::Float(float_ty) => {
+                    vs.serialize_field(float_ty)?;
+                }
+                Ty::Array(ty, len) => {
                     vs.serialize_field(ty)?;
+                    vs.serialize_field(len)?;
                 }
                 Ty::Slice(ty) => {
                     vs.serialize_field(ty)?;
@@ -952,23 +1230,277 @@ impl<R: Clone + std::cmp::Eq + Serialize> Serialize for Ty<R> {
                     vs.serialize_field(ty)?;
                     vs.serialize_field(ref_kind)?;
                 }
+                Ty::ConstGeneric(cg) => {
+                    vs.serialize_field(cg)?;
+                }
+                Ty::DynTrait(trait_id) => {
+                    vs.serialize_field(trait_id)?;
+                }
+                Ty::FnPtr(inputs, output) => {
+                    let inputs = VectorSerializer::new(inputs);
+                    vs.serialize_field(&inputs)?;
+                    vs.serialize_field(output)?;
+                }
+                Ty::Opaque(hidden_ty, bounds) => {
+                    vs.serialize_field(hidden_ty)?;
+                    let bounds = VectorSerializer::new(bounds);
+                    vs.serialize_field(&bounds)?;
+                }
+                Ty::TraitProjection(trait_id, regions, tys, name) => {
+                    vs.serialize_field(trait_id)?;
+                    let regions = VectorSerializer::new(regions);
+                    vs.serialize_field(&regions)?;
+                    let tys = VectorSerializer::new(tys);
+                    vs.serialize_field(&tys)?;
+                    vs.serialize_field(name)?;
+                }
             }
             vs.end()
         } else {
-            variant_name.serialize(serializer)
+            serializer.serialize_unit_variant(enum_name, variant_index, variant_name)
         }
     }
 }
 
+impl<'de, R: Clone + std::cmp::Eq + Deserialize<'de>> Deserialize<'de> for Ty<R> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Mirror of [Ty], with the shape produced by our serializer: the
+        // variants of arity 1 are serialized as tuple variants, and the
+        // `im::Vector` fields as sequences.
+        #[derive(Deserialize)]
+        #[serde(rename = "Ty")]
+        enum SerializedTy<R: Clone + std::cmp::Eq> {
+            Adt(TypeId, Vec<R>, Vec<Ty<R>>),
+            TypeVar((TypeVarId::Id,)),
+            Bool,
+            Char,
+            Never,
+            Integer((IntegerTy,)),
+            Float((FloatTy,)),
+            Str,
+            Array(Box<Ty<R>>, ConstGeneric),
+            Slice((Box<Ty<R>>,)),
+            Ref(R, Box<Ty<R>>, RefKind),
+            RawPtr(Box<Ty<R>>, RefKind),
+            ConstGeneric((ConstGeneric,)),
+            DynTrait((TraitDeclId::Id,)),
+            FnPtr(Vec<Ty<R>>, Box<Ty<R>>),
+            Opaque(Option<Box<Ty<R>>>, Vec<TraitDeclId::Id>),
+            TraitProjection(TraitDeclId::Id, Vec<R>, Vec<Ty<R>>, String),
+        }
+
+        use SerializedTy as S;
+        Ok(match S::deserialize(deserializer)? {
+            S::Adt(id, regions, tys) => {
+                Ty::Adt(id, Vector::from_iter(regions), Vector::from_iter(tys))
+            }
+            S::TypeVar((var_id,)) => Ty::TypeVar(var_id),
+            S::Bool => Ty::Bool,
+            S::Char => Ty::Char,
+            S::Never => Ty::Never,
+            S::Integer((int_ty,)) => Ty::Integer(int_ty),
+            S::Float((float_ty,)) => Ty::Float(float_ty),
+            S::Str => Ty::Str,
+            S::Array(ty, len) => Ty::Array(ty, len),
+            S::Slice((ty,)) => Ty::Slice(ty),
+            S::Ref(region, ty, ref_kind) => Ty::Ref(region, ty, ref_kind),
+            S::RawPtr(ty, ref_kind) => Ty::RawPtr(ty, ref_kind),
+            S::ConstGeneric((cg,)) => Ty::ConstGeneric(cg),
+            S::DynTrait((trait_id,)) => Ty::DynTrait(trait_id),
+            S::FnPtr(inputs, output) => Ty::FnPtr(Vector::from_iter(inputs), output),
+            S::Opaque(hidden_ty, bounds) => Ty::Opaque(hidden_ty, Vector::from_iter(bounds)),
+            S::TraitProjection(trait_id, regions, tys, name) => Ty::TraitProjection(
+                trait_id,
+                Vector::from_iter(regions),
+                Vector::from_iter(tys),
+                name,
+            ),
+        })
+    }
+}
+
 impl<R: Clone + std::cmp::Eq> Ty<R> {
+    /// Returns `true` if the type refers to one of the ADTs listed in the set
+    pub fn contains_adt(&self, ids: &OrdSet<TypeDeclId::Id>) -> bool {
+        match self {
+            Ty::Adt(id, _, tys) => {
+                matches!(id, TypeId::Adt(id) if ids.contains(id))
+                    || tys.iter().any(|ty| ty.contains_adt(ids))
+            }
+            Ty::TypeVar(_)
+            | Ty::Bool
+            | Ty::Char
+            | Ty::Never
+            | Ty::Str
+            | Ty::Integer(_)
+            | Ty::Float(_) => false,
+            Ty::ConstGeneric(_) | Ty::DynTrait(_) => false,
+            Ty::Array(ty, _) | Ty::Slice(ty) | Ty::Ref(_, ty, _) | Ty::RawPtr(ty, _) => {
+                ty.contains_adt(ids)
+            }
+            Ty::FnPtr(inputs, output) => {
+                inputs.iter().any(|ty| ty.contains_adt(ids)) || output.contains_adt(ids)
+            }
+            Ty::Opaque(hidden_ty, _) => hidden_ty.as_ref().map_or(false, |ty| ty.contains_adt(ids)),
+            Ty::TraitProjection(_, _, tys, _) => tys.iter().any(|ty| ty.contains_adt(ids)),
+        }
+    }
+
     pub fn contains_never(&self) -> bool {
         match self {
             Ty::Never => true,
             Ty::Adt(_, _, tys) => tys.iter().any(|ty| ty.contains_never()),
-            Ty::TypeVar(_) | Ty::Bool | Ty::Char | Ty::Str | Ty::Integer(_) => false,
-            Ty::Array(ty) | Ty::Slice(ty) | Ty::Ref(_, ty, _) | Ty::RawPtr(ty, _) => {
+            Ty::TypeVar(_) | Ty::Bool | Ty::Char | Ty::Str | Ty::Integer(_) | Ty::Float(_) => false,
+            Ty::ConstGeneric(_) | Ty::DynTrait(_) => false,
+            Ty::Array(ty, _) | Ty::Slice(ty) | Ty::Ref(_, ty, _) | Ty::RawPtr(ty, _) => {
                 ty.contains_never()
             }
+            // A function pointer is a value even if the function diverges
+            Ty::FnPtr(_, _) => false,
+            Ty::Opaque(hidden_ty, _) => hidden_ty.as_ref().map_or(false, |ty| ty.contains_never()),
+            // We don't know the type the projection stands for
+            Ty::TraitProjection(_, _, _, _) => false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::meta::{AttrInfo, Meta};
+    use crate::names::TypeName;
+    use crate::regions_hierarchy::RegionGroups;
+    use crate::types::*;
+    use crate::values::{PrimitiveValue, ScalarValue};
+    use std::collections::BTreeMap;
+
+    fn mk_vec(ty: ETy) -> ETy {
+        Ty::Adt(
+            TypeId::Assumed(AssumedTy::Vec),
+            im::Vector::new(),
+            im::Vector::from(vec![ty]),
+        )
+    }
+
+    #[test]
+    fn test_ety_ordering() {
+        let u32_ty: ETy = Ty::Integer(IntegerTy::U32);
+        let u64_ty: ETy = Ty::Integer(IntegerTy::U64);
+        let vec_u32_ty = mk_vec(u32_ty.clone());
+        let vec_u64_ty = mk_vec(u64_ty.clone());
+
+        assert!(u32_ty < u64_ty);
+        // The ordering is consistent through the type constructors
+        assert!(vec_u32_ty < vec_u64_ty);
+        assert!((u32_ty < vec_u32_ty) == (u64_ty < vec_u64_ty));
+
+        // The erased regions don't influence the ordering
+        let ref_u32_ty = Ty::Ref(
+            ErasedRegion::Erased,
+            Box::new(u32_ty.clone()),
+            RefKind::Shared,
+        );
+        let ref_u64_ty = Ty::Ref(
+            ErasedRegion::Erased,
+            Box::new(u64_ty.clone()),
+            RefKind::Shared,
+        );
+        assert!(ref_u32_ty < ref_u64_ty);
+
+        // Lists of type arguments can be used as keys in ordered maps
+        let mut map: BTreeMap<Vec<ETy>, usize> = BTreeMap::new();
+        map.insert(vec![vec_u64_ty.clone()], 0);
+        map.insert(vec![u64_ty.clone(), u32_ty.clone()], 1);
+        map.insert(vec![u32_ty.clone(), u64_ty.clone()], 2);
+        map.insert(vec![u32_ty.clone(), u64_ty], 3);
+        assert!(map.len() == 3);
+        assert!(map.get(&vec![u32_ty.clone(), Ty::Integer(IntegerTy::U64)]) == Some(&3));
+        let keys: Vec<&Vec<ETy>> = map.keys().collect();
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        assert!(*keys[0] < vec![vec_u32_ty, u32_ty]);
+    }
+
+    #[test]
+    fn test_const_generic_subst() {
+        // `struct Buffer<T, const N: usize> { data: [T; N] }`
+        let (t, n) = (TypeVarId::ZERO, ConstGenericVarId::ZERO);
+        let data = Field {
+            meta: Meta::dummy(),
+            name: Some("data".to_string()),
+            ty: Ty::Array(Box::new(Ty::TypeVar(t)), ConstGeneric::Var(n)),
+        };
+        let buffer = TypeDecl {
+            def_id: TypeDeclId::ZERO,
+            meta: Meta::dummy(),
+            name: TypeName::from(vec!["Buffer".to_string()]),
+            region_params: RegionVarId::Vector::new(),
+            type_params: TypeVarId::Vector::from(vec![TypeVar::new(t, "T".to_string())]),
+            const_generic_params: ConstGenericVarId::Vector::from(vec![ConstGenericVar {
+                index: n,
+                name: "N".to_string(),
+                ty: Ty::Integer(IntegerTy::Usize),
+            }]),
+            trait_clauses: Vec::new(),
+            regions_hierarchy: RegionGroups::new(),
+            kind: TypeDeclKind::Struct(FieldId::Vector::from(vec![data])),
+            repr_packed: None,
+            under_aligned_fields: Vec::new(),
+            recursive_fields: Vec::new(),
+            attr_info: AttrInfo::default(),
+        };
+
+        // `Buffer<u32, 4>`: the const generic arguments come last
+        let u32_ty: ETy = Ty::Integer(IntegerTy::U32);
+        let four = ConstGeneric::Value(PrimitiveValue::Scalar(ScalarValue::Usize(4)));
+        let args = im::Vector::from(vec![u32_ty.clone(), Ty::ConstGeneric(four.clone())]);
+        let (tys, cgs) = split_const_generic_args(&args);
+        assert!(tys == im::Vector::from(vec![u32_ty.clone()]));
+        assert!(cgs == im::Vector::from(vec![four.clone()]));
+
+        let field_tys = buffer.get_erased_regions_instantiated_field_types(None, &args);
+        assert!(field_tys == im::Vector::from(vec![Ty::Array(Box::new(u32_ty), four)]));
+        assert!(!field_tys[0].contains_variables());
+    }
+
+    #[test]
+    fn test_dyn_trait() {
+        // `&T`, instantiated with `dyn Shape`
+        let t = TypeVarId::ZERO;
+        let ref_t: ETy = Ty::Ref(
+            ErasedRegion::Erased,
+            Box::new(Ty::TypeVar(t)),
+            RefKind::Shared,
+        );
+        let dyn_shape: ETy = Ty::DynTrait(crate::gast::TraitDeclId::ZERO);
+        let subst: ETypeSubst = im::HashMap::from(vec![(t, dyn_shape.clone())]);
+        let ref_dyn_shape = ref_t.substitute_types(&subst, &ConstGenericSubst::new());
+        assert!(
+            ref_dyn_shape == Ty::Ref(ErasedRegion::Erased, Box::new(dyn_shape), RefKind::Shared)
+        );
+        assert!(ref_dyn_shape.to_string() == "&'_ (dyn @Trait0)");
+        assert!(!ref_dyn_shape.contains_variables());
+    }
+
+    #[test]
+    fn test_trait_projection() {
+        // `<T as Iterator>::Item`, instantiated with `Vec<u32>`
+        let t = TypeVarId::ZERO;
+        let item: ETy = Ty::TraitProjection(
+            crate::gast::TraitDeclId::ZERO,
+            im::Vector::new(),
+            im::Vector::from(vec![Ty::TypeVar(t)]),
+            "Item".to_string(),
+        );
+        assert!(item.contains_variables());
+        let vec_u32 = mk_vec(Ty::Integer(IntegerTy::U32));
+        let subst: ETypeSubst = im::HashMap::from(vec![(t, vec_u32.clone())]);
+        let vec_item = item.substitute_types(&subst, &ConstGenericSubst::new());
+        assert!(vec_item.to_string() == "<alloc::vec::Vec<u32> as @Trait0>::Item");
+        assert!(!vec_item.contains_variables());
+
+        let json = serde_json::to_string(&vec_item).unwrap();
+        assert!(serde_json::from_str::<ETy>(&json).unwrap() == vec_item);
+    }
+}
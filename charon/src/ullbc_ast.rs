@@ -3,16 +3,17 @@
 #![allow(dead_code)]
 
 pub use crate::expressions::GlobalDeclId;
+use crate::contracts::Spec;
 use crate::expressions::*;
 pub use crate::gast::*;
-use crate::meta::Meta;
+use crate::meta::{ExprMetas, Meta};
 use crate::types::*;
 pub use crate::ullbc_ast_utils::*;
 use crate::values::*;
 use hashlink::linked_hash_map::LinkedHashMap;
 use macros::generate_index_type;
 use macros::{EnumAsGetters, EnumIsA, VariantIndexArity, VariantName};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // Block identifier. Similar to rust's `BasicBlock`.
 generate_index_type!(BlockId);
@@ -28,8 +29,12 @@ pub type FunDecls = FunDeclId::Vector<FunDecl>;
 pub type GlobalDecl = GGlobalDecl<BlockId::Vector<BlockData>>;
 pub type GlobalDecls = GlobalDeclId::Vector<GlobalDecl>;
 
+// The trait declarations don't contain bodies: they are shared by ULLBC and LLBC
+pub type TraitDecls = TraitDeclId::Vector<TraitDecl>;
+pub type TraitImpls = TraitImplId::Vector<TraitImpl>;
+
 /// A raw statement: a statement without meta data.
-#[derive(Debug, Clone, EnumIsA, EnumAsGetters, VariantName, Serialize)]
+#[derive(Debug, Clone, EnumIsA, EnumAsGetters, VariantName, Serialize, Deserialize)]
 pub enum RawStatement {
     Assign(Place, Rvalue),
     FakeRead(Place),
@@ -40,10 +45,12 @@ pub enum RawStatement {
     Deinit(Place),
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Statement {
     pub meta: Meta,
     pub content: RawStatement,
+    #[serde(default, skip_serializing_if = "ExprMetas::is_empty")]
+    pub expr_metas: ExprMetas,
 }
 
 #[derive(Debug, Clone, EnumIsA, EnumAsGetters, VariantName, VariantIndexArity)]
@@ -63,7 +70,7 @@ pub enum SwitchTargets {
 }
 
 /// A raw terminator: a terminator without meta data.
-#[derive(Debug, Clone, EnumIsA, EnumAsGetters, Serialize)]
+#[derive(Debug, Clone, EnumIsA, EnumAsGetters, Serialize, Deserialize)]
 pub enum RawTerminator {
     Goto {
         target: BlockId::Id,
@@ -80,7 +87,8 @@ pub enum RawTerminator {
         target: BlockId::Id,
     },
     /// Function call.
-    /// For now, we only accept calls to top-level functions.
+    /// We accept calls to top-level functions, and calls through function
+    /// pointers ([FunId::Indirect]), whose callee is the value of an operand.
     Call {
         func: FunId,
         /// Technically, this is useless, but we still keep it because we might
@@ -88,25 +96,39 @@ pub enum RawTerminator {
         /// is as simple as possible - and in MIR we also have a vector of erased
         /// regions).
         region_args: Vec<ErasedRegion>,
+        /// The const generic arguments (if there are) come after the type
+        /// arguments, as [Ty::ConstGeneric].
         type_args: Vec<ETy>,
         args: Vec<Operand>,
         dest: Place,
+        /// The signature of the callee, with its region parameters and the
+        /// outlives constraints between them (the type parameters of the
+        /// signature are instantiated by `type_args`). We only compute it in
+        /// the non-erased mode: see [crate::call_signatures].
+        sig: Option<Box<FunSig>>,
         target: BlockId::Id,
     },
     Assert {
         cond: Operand,
         expected: bool,
+        kind: AssertKind,
         target: BlockId::Id,
     },
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Terminator {
     pub meta: Meta,
     pub content: RawTerminator,
+    #[serde(default, skip_serializing_if = "ExprMetas::is_empty")]
+    pub expr_metas: ExprMetas,
+    /// If this terminator enters a loop (it comes from a MIR `FalseUnwind`),
+    /// the invariants the user gave for this loop (see [crate::contracts])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub loop_invariants: Vec<Spec>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockData {
     pub statements: Vec<Statement>,
     pub terminator: Terminator,
@@ -2,15 +2,17 @@
 #![allow(dead_code)]
 
 use crate::common::*;
+use crate::contracts::Spec;
 use crate::expressions::*;
 use crate::formatter::Formatter;
 pub use crate::gast_utils::*;
-use crate::meta::Meta;
+use crate::meta::{ExprMetas, Meta};
 use crate::types::*;
 use crate::ullbc_ast::*;
 use crate::values::*;
+use hashlink::linked_hash_map::LinkedHashMap;
 use serde::ser::SerializeTupleVariant;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::iter::FromIterator;
 use take_mut::take;
 
@@ -32,7 +34,7 @@ impl SwitchTargets {
     }
 
     /// Perform a type substitution - actually simply clone the object
-    pub fn substitute(&self, _subst: &ETypeSubst) -> Self {
+    pub fn substitute(&self, _subst: &ETypeSubst, _cgsubst: &ConstGenericSubst) -> Self {
         self.clone()
     }
 }
@@ -67,47 +69,164 @@ impl Serialize for SwitchTargets {
     }
 }
 
+impl<'de> Deserialize<'de> for SwitchTargets {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // The targets of a `SwitchInt` are serialized as a list of pairs
+        #[derive(Deserialize)]
+        #[serde(rename = "SwitchTargets")]
+        enum SerializedSwitchTargets {
+            If(BlockId::Id, BlockId::Id),
+            SwitchInt(IntegerTy, Vec<(ScalarValue, BlockId::Id)>, BlockId::Id),
+        }
+
+        use SerializedSwitchTargets as S;
+        Ok(match S::deserialize(deserializer)? {
+            S::If(id1, id2) => SwitchTargets::If(id1, id2),
+            S::SwitchInt(int_ty, targets, otherwise) => {
+                SwitchTargets::SwitchInt(int_ty, LinkedHashMap::from_iter(targets), otherwise)
+            }
+        })
+    }
+}
+
 impl Statement {
     pub fn new(meta: Meta, content: RawStatement) -> Self {
-        Statement { meta, content }
+        Statement {
+            meta,
+            content,
+            expr_metas: ExprMetas::default(),
+        }
+    }
+
+    /// Attach the spans of the expressions of the statement (see [ExprMetas])
+    pub fn with_expr_metas(mut self, expr_metas: ExprMetas) -> Self {
+        self.expr_metas = expr_metas;
+        self
     }
 
     /// Substitute the type variables and return the resulting statement.
-    pub fn substitute(&self, subst: &ETypeSubst) -> Statement {
+    pub fn substitute(&self, subst: &ETypeSubst, cgsubst: &ConstGenericSubst) -> Statement {
         let st = match &self.content {
-            RawStatement::Assign(place, rvalue) => {
-                RawStatement::Assign(place.substitute(subst), rvalue.substitute(subst))
+            RawStatement::Assign(place, rvalue) => RawStatement::Assign(
+                place.substitute(subst, cgsubst),
+                rvalue.substitute(subst, cgsubst),
+            ),
+            RawStatement::FakeRead(place) => {
+                RawStatement::FakeRead(place.substitute(subst, cgsubst))
             }
-            RawStatement::FakeRead(place) => RawStatement::FakeRead(place.substitute(subst)),
             RawStatement::SetDiscriminant(place, variant_id) => {
-                RawStatement::SetDiscriminant(place.substitute(subst), *variant_id)
+                RawStatement::SetDiscriminant(place.substitute(subst, cgsubst), *variant_id)
             }
             RawStatement::StorageDead(var_id) => RawStatement::StorageDead(*var_id),
-            RawStatement::Deinit(place) => RawStatement::Deinit(place.substitute(subst)),
+            RawStatement::Deinit(place) => RawStatement::Deinit(place.substitute(subst, cgsubst)),
         };
 
-        Statement::new(self.meta, st)
+        Statement::new(self.meta, st).with_expr_metas(self.expr_metas.clone())
+    }
+}
+
+impl RawStatement {
+    /// The operands of the statement (see [BlockData::transform_operands])
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            RawStatement::Assign(_, rvalue) => rvalue.operands(),
+            RawStatement::FakeRead(_)
+            | RawStatement::SetDiscriminant(_, _)
+            | RawStatement::StorageDead(_)
+            | RawStatement::Deinit(_) => Vec::new(),
+        }
+    }
+}
+
+impl RawTerminator {
+    /// The operands of the terminator (see [BlockData::transform_operands])
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            RawTerminator::Switch { discr, targets: _ } => vec![discr],
+            RawTerminator::Call { func, args, .. } => {
+                let mut ops: Vec<&Operand> = Vec::new();
+                if let FunId::Indirect(op) = func {
+                    ops.push(op);
+                }
+                ops.extend(args.iter());
+                ops
+            }
+            RawTerminator::Assert { cond, .. } => vec![cond],
+            RawTerminator::Panic
+            | RawTerminator::Return
+            | RawTerminator::Unreachable
+            | RawTerminator::Goto { .. }
+            | RawTerminator::Drop { .. } => Vec::new(),
+        }
+    }
+
+    /// Return the blocks this terminator may jump to
+    pub fn successors(&self) -> Vec<BlockId::Id> {
+        match self {
+            RawTerminator::Goto { target }
+            | RawTerminator::Drop { place: _, target }
+            | RawTerminator::Call {
+                func: _,
+                region_args: _,
+                type_args: _,
+                args: _,
+                dest: _,
+                sig: _,
+                target,
+            }
+            | RawTerminator::Assert {
+                cond: _,
+                expected: _,
+                kind: _,
+                target,
+            } => {
+                vec![*target]
+            }
+            RawTerminator::Switch { discr: _, targets } => targets.get_targets(),
+            RawTerminator::Panic | RawTerminator::Unreachable | RawTerminator::Return => {
+                vec![]
+            }
+        }
     }
 }
 
 impl Terminator {
     pub fn new(meta: Meta, content: RawTerminator) -> Self {
-        Terminator { meta, content }
+        Terminator {
+            meta,
+            content,
+            expr_metas: ExprMetas::default(),
+            loop_invariants: Vec::new(),
+        }
+    }
+
+    /// Attach the spans of the expressions of the terminator (see [ExprMetas])
+    pub fn with_expr_metas(mut self, expr_metas: ExprMetas) -> Self {
+        self.expr_metas = expr_metas;
+        self
+    }
+
+    pub fn with_loop_invariants(mut self, loop_invariants: Vec<Spec>) -> Self {
+        self.loop_invariants = loop_invariants;
+        self
     }
 
     /// Substitute the type variables and return the resulting terminator
-    pub fn substitute(&self, subst: &ETypeSubst) -> Terminator {
+    pub fn substitute(&self, subst: &ETypeSubst, cgsubst: &ConstGenericSubst) -> Terminator {
         let terminator = match &self.content {
             RawTerminator::Goto { target } => RawTerminator::Goto { target: *target },
             RawTerminator::Switch { discr, targets } => RawTerminator::Switch {
-                discr: discr.substitute(subst),
-                targets: targets.substitute(subst),
+                discr: discr.substitute(subst, cgsubst),
+                targets: targets.substitute(subst, cgsubst),
             },
             RawTerminator::Panic => RawTerminator::Panic,
             RawTerminator::Return => RawTerminator::Return,
             RawTerminator::Unreachable => RawTerminator::Unreachable,
             RawTerminator::Drop { place, target } => RawTerminator::Drop {
-                place: place.substitute(subst),
+                place: place.substitute(subst, cgsubst),
                 target: *target,
             },
             RawTerminator::Call {
@@ -116,47 +235,66 @@ impl Terminator {
                 type_args,
                 args,
                 dest,
+                sig,
                 target,
             } => RawTerminator::Call {
                 func: func.clone(),
                 region_args: region_args.clone(),
                 type_args: type_args
                     .iter()
-                    .map(|ty| ty.substitute_types(subst))
+                    .map(|ty| ty.substitute_types(subst, cgsubst))
                     .collect(),
-                args: Vec::from_iter(args.iter().map(|arg| arg.substitute(subst))),
-                dest: dest.substitute(subst),
+                args: Vec::from_iter(args.iter().map(|arg| arg.substitute(subst, cgsubst))),
+                dest: dest.substitute(subst, cgsubst),
+                sig: sig.clone(),
                 target: *target,
             },
             RawTerminator::Assert {
                 cond,
                 expected,
+                kind,
                 target,
             } => RawTerminator::Assert {
-                cond: cond.substitute(subst),
+                cond: cond.substitute(subst, cgsubst),
                 expected: *expected,
+                kind: *kind,
                 target: *target,
             },
         };
 
         Terminator::new(self.meta, terminator)
+            .with_expr_metas(self.expr_metas.clone())
+            .with_loop_invariants(self.loop_invariants.clone())
     }
 }
 
 impl BlockData {
     /// Substitute the type variables and return the resulting `BlockData`
-    pub fn substitute(&self, subst: &ETypeSubst) -> BlockData {
+    pub fn substitute(&self, subst: &ETypeSubst, cgsubst: &ConstGenericSubst) -> BlockData {
         let statements = self
             .statements
             .iter()
-            .map(|x| x.substitute(subst))
+            .map(|x| x.substitute(subst, cgsubst))
             .collect();
-        let terminator = self.terminator.substitute(subst);
+        let terminator = self.terminator.substitute(subst, cgsubst);
         BlockData {
             statements,
             terminator,
         }
     }
+
+    /// Return the span of the source code the block corresponds to: this is
+    /// the span of the first statement which comes from the source code, or
+    /// the span of the terminator if there are no such statements. This is
+    /// meant for the tools which report information about the blocks to the
+    /// user (by highlighting the source code, for instance).
+    pub fn representative_span(&self) -> Option<Meta> {
+        self.statements
+            .iter()
+            .map(|st| st.meta)
+            .chain(std::iter::once(self.terminator.meta))
+            .find(|meta| !meta.is_dummy())
+    }
 }
 
 impl Statement {
@@ -238,6 +376,7 @@ impl Terminator {
                 type_args,
                 args,
                 dest,
+                sig: _,
                 target,
             } => {
                 let call = fmt_call(ctx, func, region_args, type_args, args);
@@ -247,6 +386,7 @@ impl Terminator {
             RawTerminator::Assert {
                 cond,
                 expected,
+                kind: _,
                 target,
             } => format!(
                 "assert({} == {}) -> bb{}",
@@ -515,15 +655,19 @@ impl GlobalDecl {
 
 impl BlockData {
     /// Visit the operands in an rvalue and generate statements.
-    /// Used below in [BlockData::transform_operands].
-    fn transform_rvalue_operands<F: FnMut(&Meta, &mut Vec<Statement>, &mut Operand)>(
+    /// Used below in [BlockData::transform_operands], and in
+    /// [crate::erase_phantoms].
+    pub(crate) fn transform_rvalue_operands<F: FnMut(&Meta, &mut Vec<Statement>, &mut Operand)>(
         meta: &Meta,
         nst: &mut Vec<Statement>,
         rval: &mut Rvalue,
         f: &mut F,
     ) {
         match rval {
-            Rvalue::Use(op) | Rvalue::UnaryOp(_, op) => f(meta, nst, op),
+            Rvalue::Use(op)
+            | Rvalue::UnaryOp(_, op)
+            | Rvalue::Unsize(op, _, _)
+            | Rvalue::Cast(_, op, _, _) => f(meta, nst, op),
             Rvalue::BinaryOp(_, o1, o2) => {
                 f(meta, nst, o1);
                 f(meta, nst, o2);
@@ -533,7 +677,18 @@ impl BlockData {
                     f(meta, nst, op);
                 }
             }
-            Rvalue::Global(_) | Rvalue::Discriminant(_) | Rvalue::Ref(_, _) => {
+            Rvalue::Slice { from, to, .. } => {
+                f(meta, nst, from);
+                f(meta, nst, to);
+            }
+            Rvalue::Global(_)
+            | Rvalue::GlobalRef(_, _)
+            | Rvalue::FnPtr(_)
+            | Rvalue::Discriminant(_)
+            | Rvalue::Ref(_, _)
+            | Rvalue::RawPtr(_, _)
+            | Rvalue::Len(_)
+            | Rvalue::IsVariant(_, _) => {
                 // No operands: nothing to do
             }
         }
@@ -572,13 +727,17 @@ impl BlockData {
                 f(meta, &mut nst, discr);
             }
             RawTerminator::Call {
-                func: _,
+                func,
                 region_args: _,
                 type_args: _,
                 args,
                 dest: _,
+                sig: _,
                 target: _,
             } => {
+                if let FunId::Indirect(op) = func {
+                    f(meta, &mut nst, op);
+                }
                 for arg in args {
                     f(meta, &mut nst, arg);
                 }
@@ -586,6 +745,7 @@ impl BlockData {
             RawTerminator::Assert {
                 cond,
                 expected: _,
+                kind: _,
                 target: _,
             } => {
                 f(meta, &mut nst, cond);
@@ -626,3 +786,41 @@ pub fn body_transform_operands<F: FnMut(&Meta, &mut Vec<Statement>, &mut Operand
         take(block, |b| b.transform_operands(f));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::meta::*;
+    use crate::ullbc_ast_utils::*;
+
+    fn mk_meta(line: usize) -> Meta {
+        Meta {
+            span: Span {
+                file_id: FileId::Id::LocalId(LocalFileId::ZERO),
+                beg: Loc { line, col: 4 },
+                end: Loc { line, col: 20 },
+            },
+            generated_from_span: None,
+        }
+    }
+
+    #[test]
+    fn test_representative_span() {
+        let x = VarId::Id::new(1);
+        let st = |meta| Statement::new(meta, RawStatement::StorageDead(x));
+        let terminator = Terminator::new(mk_meta(5), RawTerminator::Return);
+
+        // The statements introduced by Charon are ignored
+        let block = BlockData {
+            statements: vec![st(Meta::dummy()), st(mk_meta(3)), st(mk_meta(4))],
+            terminator: terminator.clone(),
+        };
+        assert!(block.representative_span().unwrap().span.beg.line == 3);
+
+        // If there are no statements, we use the terminator
+        let block = BlockData {
+            statements: vec![],
+            terminator,
+        };
+        assert!(block.representative_span().unwrap().span.beg.line == 5);
+    }
+}
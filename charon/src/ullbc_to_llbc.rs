@@ -20,6 +20,7 @@
 //! only be performed by terminators -, meaning that MIR graphs don't have that
 //! many nodes and edges).
 
+use crate::dominators::compute_dominators;
 use crate::expressions::Place;
 use crate::llbc_ast as tgt;
 use crate::meta::{combine_meta, Meta};
@@ -33,6 +34,7 @@ use petgraph::algo::floyd_warshall::floyd_warshall;
 use petgraph::algo::toposort;
 use petgraph::graphmap::DiGraphMap;
 use petgraph::Direction;
+use rayon::prelude::*;
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::iter::FromIterator;
@@ -53,32 +55,7 @@ struct BlockInfo<'a> {
 
 fn get_block_targets(body: &src::ExprBody, block_id: src::BlockId::Id) -> Vec<src::BlockId::Id> {
     let block = body.body.get(block_id).unwrap();
-
-    match &block.terminator.content {
-        src::RawTerminator::Goto { target }
-        | src::RawTerminator::Drop { place: _, target }
-        | src::RawTerminator::Call {
-            func: _,
-            region_args: _,
-            type_args: _,
-            args: _,
-            dest: _,
-            target,
-        }
-        | src::RawTerminator::Assert {
-            cond: _,
-            expected: _,
-            target,
-        } => {
-            vec![*target]
-        }
-        src::RawTerminator::Switch { discr: _, targets } => targets.get_targets(),
-        src::RawTerminator::Panic
-        | src::RawTerminator::Unreachable
-        | src::RawTerminator::Return => {
-            vec![]
-        }
-    }
+    block.terminator.content.successors()
 }
 
 /// This structure contains various information about a function's CFG.
@@ -1412,9 +1389,9 @@ fn opt_statement_to_nop_if_none(meta: Meta, opt_st: Option<tgt::Statement>) -> t
     }
 }
 
-fn translate_statement(st: &src::Statement) -> Option<tgt::Statement> {
-    let src_meta = st.meta;
-    let st = match &st.content {
+fn translate_statement(src_st: &src::Statement) -> Option<tgt::Statement> {
+    let src_meta = src_st.meta;
+    let st = match &src_st.content {
         src::RawStatement::Assign(place, rvalue) => {
             tgt::RawStatement::Assign(place.clone(), rvalue.clone())
         }
@@ -1432,7 +1409,7 @@ fn translate_statement(st: &src::Statement) -> Option<tgt::Statement> {
             tgt::RawStatement::Drop(place.clone())
         }
     };
-    Some(tgt::Statement::new(src_meta, st))
+    Some(tgt::Statement::new(src_meta, st).with_expr_metas(src_st.expr_metas.clone()))
 }
 
 fn translate_terminator(
@@ -1474,6 +1451,7 @@ fn translate_terminator(
             type_args,
             args,
             dest,
+            sig,
             target,
         } => {
             let opt_child = translate_child_block(
@@ -1489,13 +1467,16 @@ fn translate_terminator(
                 type_args: type_args.clone(),
                 args: args.clone(),
                 dest: dest.clone(),
+                sig: sig.clone(),
             });
-            let st = tgt::Statement::new(src_meta, st);
+            let st =
+                tgt::Statement::new(src_meta, st).with_expr_metas(terminator.expr_metas.clone());
             Some(combine_statement_and_statement(st, opt_child))
         }
         src::RawTerminator::Assert {
             cond,
             expected,
+            kind,
             target,
         } => {
             let opt_child = translate_child_block(
@@ -1508,8 +1489,10 @@ fn translate_terminator(
             let st = tgt::RawStatement::Assert(tgt::Assert {
                 cond: cond.clone(),
                 expected: *expected,
+                kind: *kind,
             });
-            let st = tgt::Statement::new(src_meta, st);
+            let st =
+                tgt::Statement::new(src_meta, st).with_expr_metas(terminator.expr_metas.clone());
             Some(combine_statement_and_statement(st, opt_child))
         }
         src::RawTerminator::Switch { discr, targets } => {
@@ -1617,7 +1600,7 @@ fn translate_terminator(
             let meta = tgt::combine_switch_targets_meta(&switch);
             let meta = combine_meta(&src_meta, &meta);
             let st = tgt::RawStatement::Switch(switch);
-            let st = tgt::Statement::new(meta, st);
+            let st = tgt::Statement::new(meta, st).with_expr_metas(terminator.expr_metas.clone());
             Some(st)
         }
     }
@@ -1652,6 +1635,7 @@ fn is_terminal(exp: &tgt::Statement) -> bool {
 fn is_terminal_explore(num_loops: usize, st: &tgt::Statement) -> bool {
     match &st.content {
         tgt::RawStatement::Assign(_, _)
+        | tgt::RawStatement::CompoundAssign(_, _, _)
         | tgt::RawStatement::FakeRead(_)
         | tgt::RawStatement::SetDiscriminant(_, _)
         | tgt::RawStatement::Drop(_)
@@ -1672,7 +1656,9 @@ fn is_terminal_explore(num_loops: usize, st: &tgt::Statement) -> bool {
             .get_targets()
             .iter()
             .all(|tgt_st| is_terminal_explore(num_loops, tgt_st)),
-        tgt::RawStatement::Loop(loop_st) => is_terminal_explore(num_loops + 1, loop_st),
+        tgt::RawStatement::Loop(loop_st, _) => is_terminal_explore(num_loops + 1, loop_st),
+        // We exit the loop when the condition is false
+        tgt::RawStatement::While(_, _, _, _) => false,
     }
 }
 
@@ -1761,7 +1747,9 @@ fn translate_block(
 
         // Put the whole loop body inside a `Loop` wrapper
         let exp = exp.unwrap();
-        let exp = tgt::Statement::new(exp.meta, tgt::RawStatement::Loop(Box::new(exp)));
+        // The loop entry carries the invariants of the loop
+        let invariants = block.terminator.loop_invariants.clone();
+        let exp = tgt::Statement::new(exp.meta, tgt::RawStatement::Loop(Box::new(exp), invariants));
 
         // Add the exit block
         if let Some(exit_block_id) = next_block {
@@ -1801,6 +1789,15 @@ fn translate_body(no_code_duplication: bool, src_body: &src::ExprBody) -> tgt::E
     let cfg_info = build_cfg_partial_info(src_body);
     let cfg_info = compute_cfg_info_from_partial(cfg_info);
 
+    // Sanity: the reconstruction of the loops requires the control-flow graph
+    // to be reducible, that is, the target of every backward edge dominates
+    // its source. This is always the case for the code coming from Rust,
+    // which has no `goto`.
+    let dominators = compute_dominators(src_body);
+    for (src, tgt) in &cfg_info.backward_edges {
+        assert!(dominators.dominates(*tgt, *src));
+    }
+
     // Find the exit block for all the loops and switches, if such an exit point
     // exists.
     let exits_info = compute_loop_switch_exits(&cfg_info);
@@ -1835,6 +1832,7 @@ fn translate_body(no_code_duplication: bool, src_body: &src::ExprBody) -> tgt::E
     tgt::ExprBody {
         meta: src_body.meta,
         arg_count: src_body.arg_count,
+        return_local: src_body.return_local,
         locals: src_body.locals.clone(),
         body: stmt,
     }
@@ -1843,6 +1841,7 @@ fn translate_body(no_code_duplication: bool, src_body: &src::ExprBody) -> tgt::E
 /// `type_defs`, `global_defs`: those parameters are used for pretty-printing purposes
 fn translate_function(
     no_code_duplication: bool,
+    no_simplify: &HashSet<FunDeclId::Id>,
     type_defs: &TypeDecls,
     src_defs: &src::FunDecls,
     src_def_id: FunDeclId::Id,
@@ -1856,16 +1855,28 @@ fn translate_function(
         src_def.fmt_with_decls(type_defs, src_defs, global_defs)
     );
 
+    // If the user asked us not to touch this function, we leave the body
+    // empty: the unstructured body is exported separately
+    let body = if no_simplify.contains(&src_def_id) {
+        None
+    } else {
+        src_def
+            .body
+            .as_ref()
+            .map(|b| translate_body(no_code_duplication, b))
+    };
+
     // Return the translated definition
     tgt::FunDecl {
         def_id: src_def.def_id,
         meta: src_def.meta,
         name: src_def.name.clone(),
         signature: src_def.signature.clone(),
-        body: src_def
-            .body
-            .as_ref()
-            .map(|b| translate_body(no_code_duplication, b)),
+        body,
+        precision_losses: src_def.precision_losses.clone(),
+        unsafe_operations: src_def.unsafe_operations.clone(),
+        attr_info: src_def.attr_info.clone(),
+        contract: src_def.contract.clone(),
     }
 }
 
@@ -1889,10 +1900,13 @@ fn translate_global(
         meta: src_def.meta,
         name: src_def.name.clone(),
         ty: src_def.ty.clone(),
+        is_mutable: src_def.is_mutable,
+        interior_mutability: src_def.interior_mutability,
         body: src_def
             .body
             .as_ref()
             .map(|b| translate_body(no_code_duplication, b)),
+        attr_info: src_def.attr_info.clone(),
     }
 }
 
@@ -1902,34 +1916,50 @@ fn translate_global(
 /// can be a sign that the reconstruction is of poor quality, but sometimes
 /// code duplication is necessary, in the presence of "fused" match branches for
 /// instance).
+///
+/// `no_simplify`: the functions whose control-flow we must not reconstruct.
+/// Their translated definitions have no body: see [unstructured_functions].
 pub fn translate_functions(
     no_code_duplication: bool,
+    no_simplify: &HashSet<FunDeclId::Id>,
     type_defs: &TypeDecls,
     src_funs: &src::FunDecls,
     src_globals: &src::GlobalDecls,
 ) -> Defs {
-    let mut tgt_funs = FunDeclId::Vector::new();
-    let mut tgt_globals = GlobalDeclId::Vector::new();
-
-    // Translate the bodies one at a time
-    for fun_id in src_funs.iter_indices() {
-        tgt_funs.push_back(translate_function(
-            no_code_duplication,
-            type_defs,
-            src_funs,
-            fun_id,
-            src_globals,
-        ));
-    }
-    for global_id in src_globals.iter_indices() {
-        tgt_globals.push_back(translate_global(
-            no_code_duplication,
-            type_defs,
-            src_globals,
-            global_id,
-            src_funs,
-        ));
-    }
+    // Translate the bodies in parallel: the reconstruction of a body doesn't
+    // depend on the other bodies. Collecting the results preserves the order
+    // of the declarations.
+    let fun_ids: Vec<FunDeclId::Id> = src_funs.iter_indices().collect();
+    let tgt_funs: tgt::FunDecls = FunDeclId::Vector::from(
+        fun_ids
+            .into_par_iter()
+            .map(|fun_id| {
+                translate_function(
+                    no_code_duplication,
+                    no_simplify,
+                    type_defs,
+                    src_funs,
+                    fun_id,
+                    src_globals,
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
+    let global_ids: Vec<GlobalDeclId::Id> = src_globals.iter_indices().collect();
+    let tgt_globals: tgt::GlobalDecls = GlobalDeclId::Vector::from(
+        global_ids
+            .into_par_iter()
+            .map(|global_id| {
+                translate_global(
+                    no_code_duplication,
+                    type_defs,
+                    src_globals,
+                    global_id,
+                    src_funs,
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
 
     // Print the functions
     for fun in &tgt_funs {
@@ -1950,3 +1980,188 @@ pub fn translate_functions(
 
     (tgt_funs, tgt_globals)
 }
+
+/// Retrieve the unstructured definitions of the functions whose control-flow
+/// we don't reconstruct (those are exported as is, next to the LLBC
+/// definitions).
+pub fn unstructured_functions(
+    src_funs: &src::FunDecls,
+    no_simplify: &HashSet<FunDeclId::Id>,
+) -> Vec<src::FunDecl> {
+    src_funs
+        .iter()
+        .filter(|f| no_simplify.contains(&f.def_id))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::contracts::{Spec, SpecExpr};
+    use crate::expressions::*;
+    use crate::meta::ExprMetas;
+    use crate::test_utils::*;
+    use crate::types::*;
+    use crate::ullbc_to_llbc::*;
+
+    /// A function `fn name() { return }`
+    fn mk_unit_fun(def_id: usize, name: &str) -> src::FunDecl {
+        let locals = vec![mk_var(0, None, Ty::mk_unit())];
+        let body = mk_cfg(
+            0,
+            locals,
+            vec![mk_block(vec![], src::RawTerminator::Return)],
+        );
+        mk_fun_decl(
+            def_id,
+            &[name],
+            mk_sig(Vec::new(), Ty::mk_unit()),
+            Some(body),
+        )
+    }
+
+    #[test]
+    fn test_no_simplify() {
+        let src_funs = FunDeclId::Vector::from(vec![mk_unit_fun(0, "f"), mk_unit_fun(1, "g")]);
+        // The user asked us not to simplify `g`
+        let no_simplify = HashSet::from([FunDeclId::Id::new(1)]);
+        let (tgt_funs, _) = translate_functions(
+            false,
+            &no_simplify,
+            &TypeDecls::new(),
+            &src_funs,
+            &GlobalDeclId::Vector::new(),
+        );
+
+        // `f` is reconstructed, while `g` is exported in ULLBC
+        let f = tgt_funs.get(FunDeclId::Id::new(0)).unwrap();
+        assert!(f.body.as_ref().unwrap().body.content.is_return());
+        assert!(tgt_funs.get(FunDeclId::Id::new(1)).unwrap().body.is_none());
+        let unstructured = unstructured_functions(&src_funs, &no_simplify);
+        assert!(unstructured.len() == 1);
+        let g = &unstructured[0];
+        assert!(g.def_id == FunDeclId::Id::new(1));
+        let g_body = &g.body.as_ref().unwrap().body;
+        assert!(g_body
+            .get(src::BlockId::ZERO)
+            .unwrap()
+            .terminator
+            .content
+            .is_return());
+    }
+
+    #[test]
+    fn test_expr_metas() {
+        // `f() { @0 := (); return }`, where we know the span of `()`
+        let mut fun = mk_unit_fun(0, "f");
+        let mut meta = Meta::dummy();
+        meta.span.beg.line = 3;
+        let unit = Operand::Const(
+            Ty::Adt(TypeId::Tuple, Vector::new(), Vector::new()),
+            OperandConstantValue::Adt(None, Vec::new()),
+        );
+        let assign = src::RawStatement::Assign(Place::new(v::VarId::ZERO), Rvalue::Use(unit));
+        let expr_metas = ExprMetas {
+            expr: Some(meta),
+            operands: vec![Some(meta)],
+        };
+        let block = fun
+            .body
+            .as_mut()
+            .unwrap()
+            .body
+            .get_mut(src::BlockId::ZERO)
+            .unwrap();
+        block.statements =
+            vec![src::Statement::new(Meta::dummy(), assign).with_expr_metas(expr_metas)];
+
+        let (tgt_funs, _) = translate_functions(
+            false,
+            &HashSet::new(),
+            &TypeDecls::new(),
+            &FunDeclId::Vector::from(vec![fun]),
+            &GlobalDeclId::Vector::new(),
+        );
+        let body = &tgt_funs
+            .get(FunDeclId::ZERO)
+            .unwrap()
+            .body
+            .as_ref()
+            .unwrap()
+            .body;
+        let (assign, ret) = body.content.as_sequence();
+        assert!(assign.content.is_assign() && ret.content.is_return());
+        assert!(assign.expr_metas.operand(0).unwrap().span.beg.line == 3);
+        assert!(assign.expr_metas.operand(1).is_none());
+    }
+
+    #[test]
+    fn test_loop_invariants() {
+        // ```
+        // f() {
+        //   b: bool;
+        //   0: goto 1
+        //   1: goto 2 // `#[charon::invariant(b)]`
+        //   2: if copy b then goto 1 else goto 3
+        //   3: return
+        // }
+        // ```
+        let mut fun = mk_unit_fun(0, "f");
+        let body = fun.body.as_mut().unwrap();
+        let b = v::VarId::Id::new(1);
+        body.locals.push_back(src::Var {
+            index: b,
+            name: Some("b".to_string()),
+            ty: Ty::Bool,
+        });
+        let invariant = Spec {
+            source: "b".to_string(),
+            expr: SpecExpr::Var(b),
+        };
+        let goto = |target| src::RawTerminator::Goto {
+            target: src::BlockId::Id::new(target),
+        };
+        let switch = src::RawTerminator::Switch {
+            discr: Operand::Copy(Place::new(b)),
+            targets: src::SwitchTargets::If(src::BlockId::Id::new(1), src::BlockId::Id::new(3)),
+        };
+        let terminators = vec![
+            src::Terminator::new(Meta::dummy(), goto(1)),
+            src::Terminator::new(Meta::dummy(), goto(2)).with_loop_invariants(vec![invariant]),
+            src::Terminator::new(Meta::dummy(), switch),
+            src::Terminator::new(Meta::dummy(), src::RawTerminator::Return),
+        ];
+        body.body = src::BlockId::Vector::from_iter(terminators.into_iter().map(|terminator| {
+            src::BlockData {
+                statements: Vec::new(),
+                terminator,
+            }
+        }));
+
+        let (tgt_funs, _) = translate_functions(
+            false,
+            &HashSet::new(),
+            &TypeDecls::new(),
+            &FunDeclId::Vector::from(vec![fun]),
+            &GlobalDeclId::Vector::new(),
+        );
+        let body = &tgt_funs
+            .get(FunDeclId::ZERO)
+            .unwrap()
+            .body
+            .as_ref()
+            .unwrap()
+            .body;
+        let mut invariants = Vec::new();
+        tgt::visit_statements(
+            &mut |st: &tgt::Statement| {
+                if let tgt::RawStatement::Loop(_, invs) = &st.content {
+                    invariants.push(invs.clone());
+                }
+            },
+            body,
+        );
+        assert!(invariants.len() == 1 && invariants[0].len() == 1);
+        assert!(invariants[0][0].expr == SpecExpr::Var(b));
+    }
+}
@@ -6,7 +6,7 @@ pub use crate::values_utils::DummyFormatter; // Don't understand why we need to
 pub use crate::values_utils::*;
 use core::hash::Hash;
 use macros::{generate_index_type, EnumAsGetters, EnumIsA, VariantIndexArity, VariantName};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // We need to manipulate a lot of indices for the types, variables, definitions,
 // etc. In order not to confuse them, we define an index type for every one of
@@ -19,12 +19,26 @@ generate_index_type!(VarId);
 /// A primitive value.
 ///
 /// Those are for instance used for the constant operands [crate::expressions::Operand::Const]
-#[derive(Debug, PartialEq, Eq, Clone, VariantName, EnumIsA, EnumAsGetters, Serialize)]
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Clone,
+    VariantName,
+    EnumIsA,
+    EnumAsGetters,
+    Serialize,
+    Deserialize,
+)]
 pub enum PrimitiveValue {
     Scalar(ScalarValue),
     Bool(bool),
     Char(char),
     String(String),
+    Float(FloatValue),
 }
 
 /// It might be a good idea to use a structure:
@@ -36,7 +50,18 @@ pub enum PrimitiveValue {
 /// the values to integers, leading to potential overflows: we implement a custom
 /// serialization, which serializes the values to strings.
 #[derive(
-    Debug, PartialEq, Eq, Copy, Clone, EnumIsA, EnumAsGetters, VariantName, VariantIndexArity, Hash,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Copy,
+    Clone,
+    EnumIsA,
+    EnumAsGetters,
+    VariantName,
+    VariantIndexArity,
+    Hash,
 )]
 pub enum ScalarValue {
     Isize(isize),
@@ -52,3 +77,27 @@ pub enum ScalarValue {
     U64(u64),
     U128(u128),
 }
+
+/// A floating-point value.
+///
+/// We store the bit patterns of the values rather than `f32` and `f64`, which
+/// don't implement [Eq], [Hash], etc. Like the scalar values, the floats are
+/// serialized to strings.
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Copy,
+    Clone,
+    EnumIsA,
+    EnumAsGetters,
+    VariantName,
+    VariantIndexArity,
+    Hash,
+)]
+pub enum FloatValue {
+    F32(u32),
+    F64(u64),
+}
@@ -6,8 +6,9 @@ use crate::formatter::Formatter;
 use crate::types::*;
 use crate::ullbc_ast::GlobalDeclId;
 use crate::values::*;
+use serde::de::Error;
 use serde::ser::SerializeTupleVariant;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 pub fn var_id_to_pretty_string(id: VarId::Id) -> String {
     format!("var@{id}")
@@ -311,8 +312,75 @@ impl std::string::ToString for PrimitiveValue {
         match self {
             PrimitiveValue::Scalar(v) => v.to_string(),
             PrimitiveValue::Bool(v) => v.to_string(),
-            PrimitiveValue::Char(v) => v.to_string(),
-            PrimitiveValue::String(v) => v.to_string(),
+            // We print the literals the way they appear in the source code
+            PrimitiveValue::Char(v) => format!("{v:?}"),
+            PrimitiveValue::String(v) => format!("{v:?}"),
+            PrimitiveValue::Float(v) => v.to_string(),
+        }
+    }
+}
+
+impl FloatValue {
+    pub fn get_float_ty(&self) -> FloatTy {
+        match self {
+            FloatValue::F32(_) => FloatTy::F32,
+            FloatValue::F64(_) => FloatTy::F64,
+        }
+    }
+
+    /// Print the value the way rust does (`1.5`, `NaN`, `inf`, etc.)
+    fn value_to_string(&self) -> String {
+        match self {
+            FloatValue::F32(bits) => format!("{:?}", f32::from_bits(*bits)),
+            FloatValue::F64(bits) => format!("{:?}", f64::from_bits(*bits)),
+        }
+    }
+}
+
+impl std::string::ToString for FloatValue {
+    fn to_string(&self) -> String {
+        format!("{} : {}", self.value_to_string(), self.get_float_ty())
+    }
+}
+
+impl Serialize for FloatValue {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let (variant_index, _) = self.variant_index_arity();
+        serializer.serialize_newtype_variant(
+            "FloatValue",
+            variant_index,
+            self.variant_name(),
+            &self.value_to_string(),
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for FloatValue {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(rename = "FloatValue")]
+        enum SerializedFloatValue {
+            F32(String),
+            F64(String),
+        }
+
+        // Note that the payloads of the NaNs are not preserved by the
+        // round-trip, because we print them as `NaN`.
+        match SerializedFloatValue::deserialize(deserializer)? {
+            SerializedFloatValue::F32(v) => match v.parse::<f32>() {
+                Ok(f) => Ok(FloatValue::F32(f.to_bits())),
+                Err(_) => Err(D::Error::custom(format!("invalid f32 literal: {v}"))),
+            },
+            SerializedFloatValue::F64(v) => match v.parse::<f64>() {
+                Ok(f) => Ok(FloatValue::F64(f.to_bits())),
+                Err(_) => Err(D::Error::custom(format!("invalid f64 literal: {v}"))),
+            },
         }
     }
 }
@@ -348,7 +416,53 @@ impl Serialize for ScalarValue {
             };
             vs.end()
         } else {
-            variant_name.serialize(serializer)
+            serializer.serialize_unit_variant(enum_name, variant_index, variant_name)
         }
     }
 }
+
+impl<'de> Deserialize<'de> for ScalarValue {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // The values are serialized to strings, as tuple variants of arity 1
+        #[derive(Deserialize)]
+        #[serde(rename = "ScalarValue")]
+        enum SerializedScalarValue {
+            Isize((String,)),
+            I8((String,)),
+            I16((String,)),
+            I32((String,)),
+            I64((String,)),
+            I128((String,)),
+            Usize((String,)),
+            U8((String,)),
+            U16((String,)),
+            U32((String,)),
+            U64((String,)),
+            U128((String,)),
+        }
+
+        fn parse<T: std::str::FromStr, E: Error>(v: &str) -> std::result::Result<T, E> {
+            v.parse::<T>()
+                .map_err(|_| E::custom(format!("invalid scalar literal: {v}")))
+        }
+
+        use SerializedScalarValue as S;
+        Ok(match S::deserialize(deserializer)? {
+            S::Isize((v,)) => ScalarValue::Isize(parse(&v)?),
+            S::I8((v,)) => ScalarValue::I8(parse(&v)?),
+            S::I16((v,)) => ScalarValue::I16(parse(&v)?),
+            S::I32((v,)) => ScalarValue::I32(parse(&v)?),
+            S::I64((v,)) => ScalarValue::I64(parse(&v)?),
+            S::I128((v,)) => ScalarValue::I128(parse(&v)?),
+            S::Usize((v,)) => ScalarValue::Usize(parse(&v)?),
+            S::U8((v,)) => ScalarValue::U8(parse(&v)?),
+            S::U16((v,)) => ScalarValue::U16(parse(&v)?),
+            S::U32((v,)) => ScalarValue::U32(parse(&v)?),
+            S::U64((v,)) => ScalarValue::U64(parse(&v)?),
+            S::U128((v,)) => ScalarValue::U128(parse(&v)?),
+        })
+    }
+}
@@ -0,0 +1,236 @@
+//! Reconstruct the `while` loops. The control-flow reconstruction (see
+//! [crate::ullbc_to_llbc]) only generates infinite loops, which we exit with
+//! a `break`:
+//! ```text
+//! loop {
+//!   c := copy i < copy n;
+//!   if move c {
+//!     ...
+//!     continue 0
+//!   }
+//!   else {
+//!     break 0
+//!   }
+//! }
+//! ```
+//! We rewrite the loops of this shape to:
+//! ```text
+//! while {
+//!   c := copy i < copy n;
+//!   move c
+//! } {
+//!   ...
+//! }
+//! ```
+//! The `for` loops over ranges have this shape once they are rewritten to
+//! counter loops (see [crate::range_loops]). The statements which precede
+//! the `if` must be simple statements (assignments, calls, etc.): they become
+//! the statement computing the condition of the loop. The statements which
+//! follow the `if` are executed after its `then` branch, and thus go to the
+//! body of the loop.
+//!
+//! The backends which prefer a uniform encoding of the loops can disable this
+//! pass with `--no-while-loops`.
+
+use crate::expressions::*;
+use crate::llbc_ast::{
+    chain_statements, new_sequence, transform_statements, CtxNames, FunDecls, GlobalDecls,
+    RawStatement, Statement, Switch,
+};
+use crate::ullbc_ast::{iter_function_bodies, iter_global_bodies};
+use take_mut::take;
+
+/// Return true if the statement can be part of the computation of the
+/// condition: it must not contain control-flow, nor exit the loop.
+fn is_cond_statement(st: &Statement) -> bool {
+    match &st.content {
+        RawStatement::Assign(_, _)
+        | RawStatement::CompoundAssign(_, _, _)
+        | RawStatement::FakeRead(_)
+        | RawStatement::SetDiscriminant(_, _)
+        | RawStatement::Drop(_)
+        | RawStatement::Assert(_)
+        | RawStatement::Call(_)
+        | RawStatement::Nop => true,
+        RawStatement::Panic
+        | RawStatement::Return
+        | RawStatement::Break(_)
+        | RawStatement::Continue(_)
+        | RawStatement::Sequence(_, _)
+        | RawStatement::Switch(_)
+        | RawStatement::Loop(_, _)
+        | RawStatement::While(_, _, _, _) => false,
+    }
+}
+
+/// Return true if the statement is of the shape `if op { ... } else { break 0 }`
+fn is_exit_test(st: &Statement) -> bool {
+    match &st.content {
+        RawStatement::Switch(Switch::If(_, _, st2)) => {
+            matches!(st2.content, RawStatement::Break(0))
+        }
+        _ => false,
+    }
+}
+
+/// Flatten a sequence of statements
+fn flatten(st: Statement, out: &mut Vec<Statement>) {
+    match st.content {
+        RawStatement::Sequence(st1, st2) => {
+            out.push(*st1);
+            flatten(*st2, out)
+        }
+        content => out.push(Statement { content, ..st }),
+    }
+}
+
+/// Remove the `continue 0` at the end of the body of a `while`: reaching the
+/// end of the body already evaluates the condition again.
+fn remove_tail_continue(st: Statement) -> Statement {
+    match st.content {
+        RawStatement::Continue(0) => Statement {
+            content: RawStatement::Nop,
+            ..st
+        },
+        RawStatement::Sequence(st1, st2) if matches!(st2.content, RawStatement::Continue(0)) => {
+            *st1
+        }
+        RawStatement::Sequence(st1, st2) => Statement {
+            content: RawStatement::Sequence(st1, Box::new(remove_tail_continue(*st2))),
+            ..st
+        },
+        content => Statement { content, ..st },
+    }
+}
+
+/// Rewrite the body of a loop to a `while`, if it has the proper shape.
+/// Otherwise, give the body back.
+fn to_while(body: Statement) -> Result<(Statement, Operand, Statement), Statement> {
+    let mut sts = Vec::new();
+    let meta = body.meta;
+    flatten(body, &mut sts);
+    let test = sts.iter().position(is_exit_test);
+    let test = match test {
+        Some(i) if sts[..i].iter().all(is_cond_statement) => i,
+        _ => {
+            let last = sts.pop().unwrap();
+            return Err(chain_statements(sts, last));
+        }
+    };
+
+    let rest = sts.split_off(test + 1);
+    let (op, then_st) = match sts.pop().unwrap().content {
+        RawStatement::Switch(Switch::If(op, st1, _)) => (op, *st1),
+        _ => unreachable!(),
+    };
+    let cond = match sts.pop() {
+        Some(last) => chain_statements(sts, last),
+        None => Statement::new(meta, RawStatement::Nop),
+    };
+    let mut rest = rest;
+    let body = match rest.pop() {
+        Some(last) => new_sequence(then_st, chain_statements(rest, last)),
+        None => then_st,
+    };
+    Ok((cond, op, remove_tail_continue(body)))
+}
+
+fn transform_st(mut st: Statement) -> Statement {
+    st.content = match st.content {
+        RawStatement::Loop(body, invariants) => match to_while(*body) {
+            Ok((cond, op, body)) => {
+                RawStatement::While(Box::new(cond), op, Box::new(body), invariants)
+            }
+            Err(body) => RawStatement::Loop(Box::new(body), invariants),
+        },
+        content => content,
+    };
+    st
+}
+
+/// `fmt_ctx` is used for pretty-printing purposes.
+pub fn transform(fmt_ctx: &CtxNames<'_>, funs: &mut FunDecls, globals: &mut GlobalDecls) {
+    for (name, b) in iter_function_bodies(funs).chain(iter_global_bodies(globals)) {
+        trace!(
+            "# About to reconstruct the while loops in decl: {name}:\n{}",
+            b.fmt_with_ctx_names(fmt_ctx)
+        );
+        take(&mut b.body, |st| {
+            transform_statements(&mut transform_st, st)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llbc_builder::*;
+    use crate::types::*;
+    use crate::while_loops::*;
+
+    fn lt(i: VarId::Id, n: VarId::Id) -> Rvalue {
+        Rvalue::BinaryOp(
+            BinOp::Lt,
+            Operand::Copy(Place::new(i)),
+            Operand::Copy(Place::new(n)),
+        )
+    }
+
+    #[test]
+    fn test_while_loop() {
+        let i = VarId::Id::new(1);
+        let n = VarId::Id::new(2);
+        let c = VarId::Id::new(3);
+        // ```
+        // loop {
+        //   c := copy i < copy n;
+        //   if move c { drop i; continue 0 } else { break 0 }
+        // }
+        // ```
+        let body = Block::new()
+            .assign(Place::new(c), lt(i, n))
+            .then(Switch::if_then_else(
+                Operand::Move(Place::new(c)),
+                Block::new().drop(Place::new(i)).cont(0),
+                Block::new().brk(0),
+            ));
+        let st = transform_statements(&mut transform_st, loop_(body));
+
+        // ```
+        // while { c := copy i < copy n; move c } { drop i }
+        // ```
+        let (cond, op, body, _) = st.content.as_while();
+        assert!(*op == Operand::Move(Place::new(c)));
+        assert!(cond.content.is_assign());
+        assert!(body.content.is_drop());
+    }
+
+    #[test]
+    fn test_loop_with_break_in_condition() {
+        let i = VarId::Id::new(1);
+        let n = VarId::Id::new(2);
+        let c = VarId::Id::new(3);
+        // The statements before the test exit the loop: we can't evaluate
+        // them as part of the condition.
+        // ```
+        // loop {
+        //   if copy c { break 0 } else { nop };
+        //   c := copy i < copy n;
+        //   if move c { continue 0 } else { break 0 }
+        // }
+        // ```
+        let body = Block::new()
+            .push_statement(Switch::if_then_else(
+                Operand::Copy(Place::new(c)),
+                Block::new().brk(0),
+                Block::new().end(),
+            ))
+            .assign(Place::new(c), lt(i, n))
+            .then(Switch::if_then_else(
+                Operand::Move(Place::new(c)),
+                Block::new().cont(0),
+                Block::new().brk(0),
+            ));
+        let st = transform_statements(&mut transform_st, loop_(body));
+        assert!(st.content.is_loop());
+    }
+}
@@ -0,0 +1,339 @@
+//! Extract all the members of a Cargo workspace (see the `--workspace`
+//! option).
+//!
+//! We list the members with `cargo metadata`, then extract them one by one,
+//! with the options given on the command line: this generates one file per
+//! crate. We finally write a manifest, `charon-manifest.json`, which lists
+//! the generated files together with the dependencies between the crates.
+//! The manifest also links the external function declarations of every crate
+//! to their declarations in the crates they come from, so that the consumers
+//! can resolve the calls across the crates:
+//! ```text
+//! { "from_crate": "app", "from_fun": 3, "to_crate": "utils", "to_fun": 12 }
+//! ```
+//! means that the function 3 of `app` is the function 12 of `utils`. We
+//! can only compute those links if the files are in JSON.
+
+use crate::cli_options::{CliOpts, Emit, OutputFormat};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+pub const MANIFEST_FILE_NAME: &str = "charon-manifest.json";
+
+/// The target of a package we extract
+#[derive(Debug, PartialEq, Eq)]
+pub enum Target {
+    Lib,
+    Bin(String),
+}
+
+/// A member of the workspace
+#[derive(Debug)]
+pub struct Member {
+    /// The name of the package (what we give to `cargo rustc -p`)
+    pub package: String,
+    /// The name of the extracted crate
+    pub crate_name: String,
+    /// We extract the library of the package if there is one, otherwise its
+    /// first binary
+    pub target: Target,
+    /// The packages of the workspace this package depends on (we ignore the
+    /// development dependencies)
+    pub dependencies: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CrateEntry {
+    pub name: String,
+    pub package: String,
+    /// The generated file, relative to the manifest
+    pub file: String,
+    /// The crates of the workspace this crate depends on
+    pub dependencies: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct FunLink {
+    pub from_crate: String,
+    pub from_fun: u64,
+    pub to_crate: String,
+    pub to_fun: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    pub crates: Vec<CrateEntry>,
+    pub links: Vec<FunLink>,
+}
+
+/// Retrieve the root and the members of the workspace from the output of
+/// `cargo metadata --format-version 1 --no-deps`.
+pub fn members_of_metadata(metadata: &Value) -> Result<(PathBuf, Vec<Member>), String> {
+    let root = metadata["workspace_root"]
+        .as_str()
+        .ok_or("Missing field in the metadata: `workspace_root`")?;
+    let ids: HashSet<&str> = metadata["workspace_members"]
+        .as_array()
+        .ok_or("Missing field in the metadata: `workspace_members`")?
+        .iter()
+        .filter_map(|id| id.as_str())
+        .collect();
+    let packages = metadata["packages"]
+        .as_array()
+        .ok_or("Missing field in the metadata: `packages`")?;
+    let packages: Vec<&Value> = packages
+        .iter()
+        .filter(|p| p["id"].as_str().map_or(false, |id| ids.contains(id)))
+        .collect();
+    let names: HashSet<&str> = packages.iter().filter_map(|p| p["name"].as_str()).collect();
+
+    let mut members = Vec::new();
+    for p in packages {
+        let package = p["name"].as_str().unwrap_or_default().to_string();
+        let targets: Vec<&Value> = p["targets"].as_array().into_iter().flatten().collect();
+        let has_kind = |t: &Value, kind: &str| {
+            t["kind"]
+                .as_array()
+                .map_or(false, |ks| ks.iter().any(|k| k == kind))
+        };
+        let target = targets
+            .iter()
+            .find(|t| has_kind(t, "lib"))
+            .map(|t| (t, Target::Lib))
+            .or_else(|| {
+                targets.iter().find(|t| has_kind(t, "bin")).map(|t| {
+                    let name = t["name"].as_str().unwrap_or_default();
+                    (t, Target::Bin(name.to_string()))
+                })
+            });
+        let (target_value, target) = match target {
+            Some(target) => target,
+            None => {
+                log::warn!("Ignoring the package {package}: it has no library nor binary");
+                continue;
+            }
+        };
+        // Cargo replaces the dashes in the names of the crates
+        let crate_name = target_value["name"]
+            .as_str()
+            .unwrap_or_default()
+            .replace('-', "_");
+        let dependencies = p["dependencies"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|d| d["kind"] != "dev")
+            .filter_map(|d| d["name"].as_str())
+            .filter(|name| names.contains(name))
+            .map(String::from)
+            .collect();
+        members.push(Member {
+            package,
+            crate_name,
+            target,
+            dependencies,
+        });
+    }
+    Ok((PathBuf::from(root), members))
+}
+
+/// Compute the links between the function declarations of the crates (see
+/// the module documentation). `crates` gives the names and the exported files
+/// (in JSON) of the crates.
+///
+/// A function is declared in a crate if its name starts with the name of the
+/// crate: we link the functions of the other crates which have the same name.
+pub fn compute_links(crates: &[(String, Value)]) -> Vec<FunLink> {
+    let fun_decls = |file: &Value| -> Vec<(u64, String, Value)> {
+        file["crate"]["functions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|f| {
+                let id = f["def_id"].as_u64()?;
+                let krate = f["name"][0]["Ident"].as_str()?.to_string();
+                Some((id, krate, f["name"].clone()))
+            })
+            .collect()
+    };
+
+    // The functions declared in every crate, identified by their names
+    let mut declared: HashMap<&str, HashMap<String, u64>> = HashMap::new();
+    for (name, file) in crates {
+        let funs = fun_decls(file)
+            .into_iter()
+            .filter(|(_, krate, _)| krate == name)
+            .map(|(id, _, fun_name)| (fun_name.to_string(), id))
+            .collect();
+        declared.insert(name, funs);
+    }
+
+    let mut links = Vec::new();
+    for (name, file) in crates {
+        for (id, krate, fun_name) in fun_decls(file) {
+            if &krate == name {
+                continue;
+            }
+            let target = declared
+                .get(krate.as_str())
+                .and_then(|funs| funs.get(&fun_name.to_string()));
+            if let Some(target) = target {
+                links.push(FunLink {
+                    from_crate: name.clone(),
+                    from_fun: id,
+                    to_crate: krate,
+                    to_fun: *target,
+                });
+            }
+        }
+    }
+    links
+}
+
+/// Extract the members of the workspace, then write the manifest.
+/// `extract` extracts a member of the workspace with the given options.
+pub fn process(
+    options: &CliOpts,
+    metadata: &Value,
+    extract: &dyn Fn(&Member, &CliOpts) -> Result<(), i32>,
+) -> Result<(), i32> {
+    let (root, members) = match members_of_metadata(metadata) {
+        Ok(x) => x,
+        Err(msg) => {
+            log::error!("{msg}");
+            return Err(1);
+        }
+    };
+
+    // We generate all the files in the same directory: by default, the root
+    // of the workspace
+    let dest_dir = options.dest_dir.clone().unwrap_or(root);
+    let mut member_options = options.clone();
+    member_options.dest_dir = Some(dest_dir.clone());
+
+    let language = if options.emit() == Emit::Ullbc {
+        "ullbc"
+    } else {
+        "llbc"
+    };
+    let extension = options.output_format.extension(language);
+    let crate_names: HashMap<&str, &str> = members
+        .iter()
+        .map(|m| (m.package.as_str(), m.crate_name.as_str()))
+        .collect();
+
+    let mut entries = Vec::new();
+    let mut files = Vec::new();
+    for member in &members {
+        log::info!("Extracting the package {}", member.package);
+        extract(member, &member_options)?;
+        let file = format!("{}.{extension}", member.crate_name);
+        if options.output_format == OutputFormat::Json {
+            let path = dest_dir.join(&file);
+            let value = std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|s| serde_json::from_str(&s).ok());
+            match value {
+                Some(value) => files.push((member.crate_name.clone(), value)),
+                None => log::warn!("Could not read the generated file: {:?}", path),
+            }
+        }
+        entries.push(CrateEntry {
+            name: member.crate_name.clone(),
+            package: member.package.clone(),
+            file,
+            dependencies: member
+                .dependencies
+                .iter()
+                .map(|p| crate_names[p.as_str()].to_string())
+                .collect(),
+        });
+    }
+
+    if options.output_format != OutputFormat::Json {
+        log::warn!("We only compute the links between the functions of the crates for JSON files");
+    }
+    let manifest = Manifest {
+        crates: entries,
+        links: compute_links(&files),
+    };
+    let path = dest_dir.join(MANIFEST_FILE_NAME);
+    match std::fs::write(&path, serde_json::to_string_pretty(&manifest).unwrap()) {
+        Ok(()) => {
+            log::info!("Generated the manifest: {:?}", path);
+            Ok(())
+        }
+        Err(err) => {
+            log::error!("Could not write to {:?}: {}", path, err);
+            Err(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::workspace::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_members_of_metadata() {
+        let metadata = json!({
+            "workspace_root": "/ws",
+            "workspace_members": ["app 0.1.0", "my-utils 0.1.0"],
+            "packages": [
+                {
+                    "name": "app",
+                    "id": "app 0.1.0",
+                    "targets": [{"name": "app", "kind": ["bin"]}],
+                    "dependencies": [
+                        {"name": "my-utils", "kind": null},
+                        {"name": "serde", "kind": null},
+                        {"name": "app-tests", "kind": "dev"}
+                    ]
+                },
+                {
+                    "name": "my-utils",
+                    "id": "my-utils 0.1.0",
+                    "targets": [{"name": "my-utils", "kind": ["lib"]}],
+                    "dependencies": []
+                }
+            ]
+        });
+        let (root, members) = members_of_metadata(&metadata).unwrap();
+        assert!(root == PathBuf::from("/ws") && members.len() == 2);
+        assert!(members[0].target == Target::Bin("app".to_string()));
+        // We only keep the dependencies to the members of the workspace
+        assert!(members[0].dependencies == vec!["my-utils"]);
+        assert!(members[1].target == Target::Lib && members[1].crate_name == "my_utils");
+    }
+
+    #[test]
+    fn test_compute_links() {
+        let fun = |id: u64, name: &[&str], body: bool| {
+            let name: Vec<Value> = name.iter().map(|s| json!({ "Ident": s })).collect();
+            let body = if body { json!([]) } else { Value::Null };
+            json!({"def_id": id, "name": name, "body": body})
+        };
+        let app = json!({"crate": {"functions": [
+            fun(0, &["app", "main"], true),
+            fun(1, &["utils", "f"], false),
+            fun(2, &["core", "mem", "swap"], false),
+        ]}});
+        let utils = json!({"crate": {"functions": [
+            fun(0, &["utils", "g"], true),
+            fun(1, &["utils", "f"], true),
+        ]}});
+        let links = compute_links(&[("app".to_string(), app), ("utils".to_string(), utils)]);
+        assert!(
+            links
+                == vec![FunLink {
+                    from_crate: "app".to_string(),
+                    from_fun: 1,
+                    to_crate: "utils".to_string(),
+                    to_fun: 1,
+                }]
+        );
+    }
+}
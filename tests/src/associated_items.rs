@@ -0,0 +1,58 @@
+//! Tests with the associated types and constants of the generic parameters
+//! (see `TraitProjection` in `charon/src/types.rs` and `TraitConst` in
+//! `charon/src/expressions.rs`)
+#![allow(dead_code)]
+
+pub trait Shape {
+    type Side;
+    const SIDES: u32;
+
+    fn side(&self) -> Self::Side;
+}
+
+pub struct Square {
+    side: u32,
+}
+
+impl Shape for Square {
+    type Side = u32;
+    const SIDES: u32 = 4;
+
+    fn side(&self) -> u32 {
+        self.side
+    }
+}
+
+/// An associated type of a type parameter: `T::Side`
+pub fn get_side<T: Shape>(x: &T) -> T::Side {
+    x.side()
+}
+
+/// An associated constant of a type parameter: `T::SIDES`
+pub fn num_sides<T: Shape>(_x: &T) -> u32 {
+    T::SIDES
+}
+
+/// The qualified syntax, in a type definition
+pub struct Sides<T: Shape> {
+    first: <T as Shape>::Side,
+    count: u32,
+}
+
+pub fn mk_sides<T: Shape>(x: &T) -> Sides<T> {
+    Sides {
+        first: x.side(),
+        count: <T as Shape>::SIDES,
+    }
+}
+
+/// When the implementation is known, rustc normalizes the associated type
+/// and we refer to the constant of the implementation
+pub fn square_sides(sq: &Square) -> (u32, u32) {
+    (get_side(sq), Square::SIDES + num_sides(sq))
+}
+
+/// An associated type of a standard trait
+pub fn first<I: Iterator>(mut it: I) -> Option<I::Item> {
+    it.next()
+}
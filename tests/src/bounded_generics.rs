@@ -0,0 +1,67 @@
+//! Tests with generic declarations whose parameters are bounded by traits
+//! (see the trait clauses in `charon/src/types.rs`)
+#![allow(dead_code)]
+
+pub trait Value {
+    fn value(&self) -> u32;
+}
+
+pub trait Convert<U> {
+    fn convert(self) -> U;
+}
+
+impl Value for u32 {
+    fn value(&self) -> u32 {
+        *self
+    }
+}
+
+impl Convert<u64> for u32 {
+    fn convert(self) -> u64 {
+        self as u64
+    }
+}
+
+/// A function with a single bound: `T: Value`
+pub fn get_value<T: Value>(x: &T) -> u32 {
+    x.value()
+}
+
+/// A bound in a `where` clause, on a trait with a type parameter:
+/// `T: Convert<U>`. The marker trait `Copy` doesn't appear in the clauses.
+pub fn convert_twice<T, U>(x: T) -> (U, U)
+where
+    T: Convert<U> + Copy,
+{
+    (x.convert(), x.convert())
+}
+
+/// Several bounds on the same parameter
+pub fn sum_values<T: Value + Convert<u64>>(x: T, y: &T) -> u64 {
+    y.value() as u64 + x.convert()
+}
+
+/// A bounded type parameter of a type definition
+pub struct Wrapper<T: Value> {
+    x: T,
+}
+
+/// The bounds of the type also appear in the functions which use it
+pub fn wrapper_value<T: Value>(w: &Wrapper<T>) -> u32 {
+    w.x.value()
+}
+
+/// A bound on a reference type, with a region argument
+pub fn ref_value<'a, T>(x: &'a T) -> u32
+where
+    &'a T: Convert<u32>,
+{
+    x.convert()
+}
+
+/// A call to the bounded functions, with the arguments instantiated
+pub fn use_bounded(x: u32) -> u64 {
+    let w = Wrapper { x };
+    let (a, _) = convert_twice::<u32, u64>(x);
+    a + sum_values(x, &x) + (get_value(&x) + wrapper_value(&w)) as u64
+}
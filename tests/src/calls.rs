@@ -0,0 +1,29 @@
+//! Tests with calls between functions which manipulate borrows. We translate
+//! this file in the non-erased mode (`--keep-regions`): the calls are then
+//! annotated with the signatures of their callees.
+#![allow(dead_code)]
+
+pub fn choose<'a, T>(b: bool, x: &'a mut T, y: &'a mut T) -> &'a mut T {
+    if b {
+        x
+    } else {
+        y
+    }
+}
+
+pub fn first<'a, 'b>(x: &'a u32, _y: &'b u32) -> &'a u32 {
+    x
+}
+
+pub fn incr(x: &mut u32) {
+    *x += 1;
+}
+
+pub fn test_calls() -> u32 {
+    let mut x = 0;
+    let mut y = 1;
+    let z = choose(true, &mut x, &mut y);
+    incr(z);
+    let w = first(&x, &y);
+    *w
+}
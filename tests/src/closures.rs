@@ -0,0 +1,49 @@
+//! Tests with closures (see `charon/src/closures.rs`)
+#![allow(dead_code)]
+
+/// A closure which doesn't capture anything
+pub fn no_captures(x: u32) -> u32 {
+    let f = |y: u32| y + 1;
+    f(x)
+}
+
+/// A closure which captures a variable by reference
+pub fn capture_by_ref(x: u32) -> u32 {
+    let y = 2;
+    let f = |z: u32| z + y;
+    f(x) + f(y)
+}
+
+/// A closure which captures a variable by mutable reference: it implements
+/// `FnMut`
+pub fn capture_by_mut_ref(x: u32) -> u32 {
+    let mut count = 0;
+    let mut incr = |y: u32| {
+        count += y;
+    };
+    incr(x);
+    incr(1);
+    count
+}
+
+/// A closure which consumes the variable it captures: it only implements
+/// `FnOnce`
+pub fn capture_by_value(v: Vec<u32>) -> Vec<u32> {
+    let f = move |x: u32| {
+        let mut v = v;
+        v.push(x);
+        v
+    };
+    f(0)
+}
+
+/// A closure which captures several variables, in different modes
+pub fn capture_several(x: u32, mut y: u32) -> u32 {
+    let z = 3;
+    let mut f = |a: u32, b: u32| {
+        y += a;
+        x + z + b
+    };
+    let r = f(1, 2);
+    r + y
+}
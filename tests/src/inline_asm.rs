@@ -0,0 +1,15 @@
+//! Tests with inline assembly
+#![allow(dead_code)]
+
+/// We don't model the assembly code: `nop` should be translated to a function
+/// whose body does nothing, and which records an `InlineAsm` precision loss.
+pub fn nop() {
+    unsafe { core::arch::asm!("nop") }
+}
+
+/// The precision losses are recorded per function: `add` calls `nop`, but
+/// its own translation is precise.
+pub fn add(x: u32, y: u32) -> u32 {
+    nop();
+    x.wrapping_add(y)
+}
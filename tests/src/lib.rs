@@ -1,9 +1,15 @@
+mod associated_items;
+mod bounded_generics;
+mod calls;
+mod closures;
 mod constants;
 mod external;
 mod hashmap;
+mod inline_asm;
 mod loops;
 mod loops_cfg;
 mod matches;
 mod nested_borrows;
 mod no_nested_borrows;
 mod paper;
+mod traits;
@@ -41,3 +41,15 @@ fn test1(x: E1) -> bool {
         E1::V3 => false,
     }
 }
+
+/// Testing the `if let ... else if let ...` chains: they are printed
+/// flattened in the LLBC (see `charon/src/if_let_chains.rs`).
+fn test_if_let_chain(x: Option<u32>, y: Result<u32, bool>) -> u32 {
+    if let Some(z) = x {
+        z
+    } else if let Ok(z) = y {
+        z
+    } else {
+        0
+    }
+}
@@ -0,0 +1,10 @@
+//! Tests with trait implementations
+#![allow(dead_code)]
+
+/// The derived `Clone::clone` should be translated as a regular function,
+/// tagged with the `Clone` trait and the `Pair` type.
+#[derive(Clone, Copy)]
+pub struct Pair {
+    x: u32,
+    y: u32,
+}